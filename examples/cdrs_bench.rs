@@ -0,0 +1,397 @@
+//! Load testing utility running read and write workloads against a cluster, e.g. to compare
+//! pooling settings or to detect performance regressions:
+//!
+//! `cargo run --release --example cdrs-bench --features bench -- --nodes 127.0.0.1:9042 \
+//!     --workload mixed --concurrency 64 --duration 30`
+//!
+//! Run with `--help` to list all options.
+
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cdrs_tokio::authenticators::NoneAuthenticator;
+use cdrs_tokio::cluster::session::{new as new_session, Session};
+use cdrs_tokio::cluster::{ClusterTcpConfig, NodeTcpConfigBuilder, TcpConnectionPool};
+use cdrs_tokio::load_balancing::RoundRobin;
+use cdrs_tokio::query::*;
+use cdrs_tokio::query_values;
+
+type CurrentSession = Session<RoundRobin<TcpConnectionPool>>;
+
+const USAGE: &str = "Usage: cdrs-bench [options]
+
+Options:
+    --nodes <addr,...>      contact points (default: 127.0.0.1:9042)
+    --workload <name>       read, write or mixed (default: mixed)
+    --read-ratio <0-100>    percentage of reads in the mixed workload (default: 50)
+    --concurrency <n>       number of concurrent requests (default: 32)
+    --duration <seconds>    duration of the measurement (default: 10)
+    --warmup <seconds>      duration of the warmup, not measured (default: 2)
+    --partitions <n>        number of distinct partitions written and read (default: 10000)
+    --pool-size <n>         maximum number of connections per node (default: 10)
+    --value-size <bytes>    size of written values (default: 100)
+    --keyspace <name>       keyspace created for the benchmark (default: cdrs_bench)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Workload {
+    Read,
+    Write,
+    Mixed,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    nodes: Vec<String>,
+    workload: Workload,
+    read_ratio: u32,
+    concurrency: usize,
+    duration: Duration,
+    warmup: Duration,
+    partitions: i64,
+    pool_size: u32,
+    value_size: usize,
+    keyspace: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            nodes: vec!["127.0.0.1:9042".into()],
+            workload: Workload::Mixed,
+            read_ratio: 50,
+            concurrency: 32,
+            duration: Duration::from_secs(10),
+            warmup: Duration::from_secs(2),
+            partitions: 10_000,
+            pool_size: 10,
+            value_size: 100,
+            keyspace: "cdrs_bench".into(),
+        }
+    }
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value of {}", arg))?;
+            match arg.as_str() {
+                "--nodes" => options.nodes = value.split(',').map(str::to_string).collect(),
+                "--workload" => {
+                    options.workload = match value.as_str() {
+                        "read" => Workload::Read,
+                        "write" => Workload::Write,
+                        "mixed" => Workload::Mixed,
+                        _ => return Err(format!("Unknown workload: {}", value)),
+                    }
+                }
+                "--read-ratio" => options.read_ratio = parse_number::<u32>(&arg, &value)?.min(100),
+                "--concurrency" => options.concurrency = parse_number(&arg, &value)?,
+                "--duration" => options.duration = Duration::from_secs(parse_number(&arg, &value)?),
+                "--warmup" => options.warmup = Duration::from_secs(parse_number(&arg, &value)?),
+                "--partitions" => options.partitions = parse_number(&arg, &value)?,
+                "--pool-size" => options.pool_size = parse_number(&arg, &value)?,
+                "--value-size" => options.value_size = parse_number(&arg, &value)?,
+                "--keyspace" => options.keyspace = value,
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value of {}: {}", arg, value))
+}
+
+/// Latency histogram with logarithmic buckets, each a power of 2 microseconds wide, split into
+/// 16 linear sub-buckets. Recorded values are accurate to about 6%.
+#[derive(Debug, Clone)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Histogram {
+    const SUB_BUCKETS: u64 = 16;
+
+    fn new() -> Self {
+        Histogram {
+            buckets: vec![0; 64 * Self::SUB_BUCKETS as usize],
+            count: 0,
+            max: 0,
+        }
+    }
+
+    fn index(value: u64) -> usize {
+        if value < Self::SUB_BUCKETS {
+            return value as usize;
+        }
+
+        let magnitude = 63 - value.leading_zeros() as u64;
+        let shift = magnitude - 4;
+        let sub_bucket = (value >> shift) - Self::SUB_BUCKETS;
+        ((shift + 1) * Self::SUB_BUCKETS + sub_bucket) as usize
+    }
+
+    fn value_at(index: usize) -> u64 {
+        let index = index as u64;
+        if index < Self::SUB_BUCKETS {
+            return index;
+        }
+
+        let shift = index / Self::SUB_BUCKETS - 1;
+        let sub_bucket = index % Self::SUB_BUCKETS;
+        (Self::SUB_BUCKETS + sub_bucket) << shift
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let value = latency.as_micros() as u64;
+        self.buckets[Self::index(value)] += 1;
+        self.count += 1;
+        self.max = self.max.max(value);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (bucket, other) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += other;
+        }
+
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    fn percentile(&self, percentile: f64) -> Duration {
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(Self::value_at(index).min(self.max));
+            }
+        }
+
+        Duration::from_micros(self.max)
+    }
+}
+
+#[derive(Debug)]
+struct Stats {
+    reads: Histogram,
+    writes: Histogram,
+    errors: u64,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            reads: Histogram::new(),
+            writes: Histogram::new(),
+            errors: 0,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::parse().unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+
+    let nodes = options
+        .nodes
+        .iter()
+        .map(|addr| {
+            NodeTcpConfigBuilder::new(addr, Arc::new(NoneAuthenticator))
+                .max_size(options.pool_size)
+                .build()
+        })
+        .collect();
+    let session: Arc<CurrentSession> = Arc::new(
+        new_session(&ClusterTcpConfig::new(nodes), RoundRobin::new())
+            .await
+            .expect("session should be created"),
+    );
+
+    let (insert, select) = prepare_schema(&session, &options).await;
+    let insert = Arc::new(insert);
+    let select = Arc::new(select);
+
+    println!(
+        "Running {:?} workload with concurrency {} against {}",
+        options.workload,
+        options.concurrency,
+        options.nodes.join(",")
+    );
+
+    if !options.warmup.is_zero() {
+        run(&session, &insert, &select, &options, options.warmup).await;
+    }
+
+    let started = Instant::now();
+    let stats = run(&session, &insert, &select, &options, options.duration).await;
+    report(&stats, started.elapsed());
+}
+
+async fn prepare_schema(
+    session: &CurrentSession,
+    options: &Options,
+) -> (PreparedQuery, PreparedQuery) {
+    session
+        .query(format!(
+            "CREATE KEYSPACE IF NOT EXISTS {} WITH REPLICATION = \
+             {{ 'class' : 'SimpleStrategy', 'replication_factor' : 1 }}",
+            options.keyspace
+        ))
+        .await
+        .expect("Keyspace creation error");
+    session
+        .query(format!(
+            "CREATE TABLE IF NOT EXISTS {}.kv (key bigint PRIMARY KEY, value blob)",
+            options.keyspace
+        ))
+        .await
+        .expect("Table creation error");
+
+    let insert = session
+        .prepare(format!(
+            "INSERT INTO {}.kv (key, value) VALUES (?, ?)",
+            options.keyspace
+        ))
+        .await
+        .expect("Prepare insert error");
+    let select = session
+        .prepare(format!(
+            "SELECT key, value FROM {}.kv WHERE key = ?",
+            options.keyspace
+        ))
+        .await
+        .expect("Prepare select error");
+
+    (insert, select)
+}
+
+async fn run(
+    session: &Arc<CurrentSession>,
+    insert: &Arc<PreparedQuery>,
+    select: &Arc<PreparedQuery>,
+    options: &Options,
+    duration: Duration,
+) -> Stats {
+    let running = Arc::new(AtomicBool::new(true));
+    let stats = Arc::new(Mutex::new(Stats::new()));
+
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|_| {
+            tokio::spawn(worker(
+                session.clone(),
+                insert.clone(),
+                select.clone(),
+                options.clone(),
+                running.clone(),
+                stats.clone(),
+            ))
+        })
+        .collect();
+
+    tokio::time::sleep(duration).await;
+    running.store(false, Ordering::Relaxed);
+
+    for worker in workers {
+        worker.await.expect("worker error");
+    }
+
+    Arc::try_unwrap(stats)
+        .expect("workers finished")
+        .into_inner()
+        .expect("stats lock poisoned")
+}
+
+async fn worker(
+    session: Arc<CurrentSession>,
+    insert: Arc<PreparedQuery>,
+    select: Arc<PreparedQuery>,
+    options: Options,
+    running: Arc<AtomicBool>,
+    stats: Arc<Mutex<Stats>>,
+) {
+    let value = vec![0xABu8; options.value_size];
+    let mut local = Stats::new();
+
+    while running.load(Ordering::Relaxed) {
+        let key = rand::random::<i64>().rem_euclid(options.partitions.max(1));
+        let read = match options.workload {
+            Workload::Read => true,
+            Workload::Write => false,
+            Workload::Mixed => rand::random::<u32>() % 100 < options.read_ratio,
+        };
+
+        let started = Instant::now();
+        let result = if read {
+            session.exec_with_values(&select, query_values!(key)).await
+        } else {
+            session
+                .exec_with_values(&insert, query_values!(key, value.clone()))
+                .await
+        };
+
+        match result {
+            Ok(_) if read => local.reads.record(started.elapsed()),
+            Ok(_) => local.writes.record(started.elapsed()),
+            Err(_) => local.errors += 1,
+        }
+    }
+
+    let mut stats = stats.lock().expect("stats lock poisoned");
+    stats.reads.merge(&local.reads);
+    stats.writes.merge(&local.writes);
+    stats.errors += local.errors;
+}
+
+fn report(stats: &Stats, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    let requests = stats.reads.count + stats.writes.count;
+
+    println!(
+        "\n{} requests in {:.1}s: {:.0} req/s, {} errors",
+        requests,
+        seconds,
+        requests as f64 / seconds,
+        stats.errors
+    );
+
+    for (name, histogram) in [("reads", &stats.reads), ("writes", &stats.writes)] {
+        if histogram.count == 0 {
+            continue;
+        }
+
+        println!(
+            "{:>6}: {:>8.0} req/s  p50 {:>8.2?}  p90 {:>8.2?}  p99 {:>8.2?}  p99.9 {:>8.2?}  max {:>8.2?}",
+            name,
+            histogram.count as f64 / seconds,
+            histogram.percentile(50.0),
+            histogram.percentile(90.0),
+            histogram.percentile(99.0),
+            histogram.percentile(99.9),
+            Duration::from_micros(histogram.max),
+        );
+    }
+}
@@ -87,7 +87,7 @@ async fn main() {
     let node_a = NodeTcpConfigBuilder::new("127.0.0.1:9042", auth.clone()).build();
     let node_b = NodeTcpConfigBuilder::new("127.0.0.1:9043", auth.clone()).build();
     let event_src = NodeTcpConfigBuilder::new("127.0.0.1:9042", auth.clone()).build();
-    let cluster_config = ClusterTcpConfig(vec![node_a, node_b]);
+    let cluster_config = ClusterTcpConfig::new(vec![node_a, node_b]);
 
     println!("> Starting cluster...");
     start_cluster();
@@ -25,7 +25,7 @@ async fn main() {
     let password = "password";
     let auth = StaticPasswordAuthenticator::new(&user, &password);
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(auth)).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let mut no_compression: CurrentSession = new_session(&cluster_config, RoundRobin::new())
         .await
         .expect("session should be created");
@@ -18,7 +18,7 @@ type CurrentSession = Session<RoundRobin<TcpConnectionPool>>;
 #[tokio::main]
 async fn main() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let no_compression: Arc<CurrentSession> = Arc::new(
         new_session(&cluster_config, lb)
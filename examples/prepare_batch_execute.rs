@@ -31,7 +31,7 @@ impl RowStruct {
 #[tokio::main]
 async fn main() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let mut no_compression = new_session(&cluster_config, lb)
         .await
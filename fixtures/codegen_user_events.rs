@@ -0,0 +1,53 @@
+// Generated by cdrs_tokio::codegen. Do not edit.
+
+/// Row of `ks.user_events`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserEvents {
+    pub user_id: uuid::Uuid,
+    pub at: std::time::SystemTime,
+    pub counts: std::collections::HashMap<String, i64>,
+    pub payload_size: Option<i32>,
+    pub tags: Vec<String>,
+    pub r#type: Option<String>,
+}
+
+impl UserEvents {
+    pub const TABLE: &'static str = "ks.user_events";
+    pub const INSERT: &'static str = "INSERT INTO ks.user_events (user_id, at, counts, \"payloadSize\", tags, type) VALUES (?, ?, ?, ?, ?, ?)";
+}
+
+impl cdrs_tokio::frame::TryFromRow for UserEvents {
+    fn try_from_row(row: cdrs_tokio::types::rows::Row) -> cdrs_tokio::error::Result<Self> {
+        use cdrs_tokio::types::IntoRustByName;
+
+        Ok(UserEvents {
+            user_id: row.get_r_by_name("user_id")?,
+            at: row.get_r_by_name("at")?,
+            counts: row
+                .get_opt_by_name::<cdrs_tokio::types::map::Map>("counts")?
+                .map(|value| <cdrs_tokio::types::map::Map as cdrs_tokio::types::AsRustType<std::collections::HashMap<String, i64>>>::as_r_type(&value))
+                .transpose()?
+                .unwrap_or_default(),
+            payload_size: row.get_opt_by_name("payloadSize")?,
+            tags: row
+                .get_opt_by_name::<cdrs_tokio::types::list::List>("tags")?
+                .map(|value| <cdrs_tokio::types::list::List as cdrs_tokio::types::AsRustType<Vec<String>>>::as_r_type(&value))
+                .transpose()?
+                .unwrap_or_default(),
+            r#type: row.get_opt_by_name("type")?,
+        })
+    }
+}
+
+impl cdrs_tokio::frame::IntoQueryValues for UserEvents {
+    fn into_query_values(self) -> cdrs_tokio::query::QueryValues {
+        let mut values = std::collections::HashMap::new();
+        values.insert("user_id".to_string(), cdrs_tokio::types::value::Value::from(self.user_id));
+        values.insert("at".to_string(), cdrs_tokio::types::value::Value::from(self.at));
+        values.insert("counts".to_string(), cdrs_tokio::types::value::Value::from(self.counts));
+        values.insert("payloadSize".to_string(), cdrs_tokio::types::value::Value::from(self.payload_size));
+        values.insert("tags".to_string(), cdrs_tokio::types::value::Value::from(self.tags));
+        values.insert("type".to_string(), cdrs_tokio::types::value::Value::from(self.r#type));
+        cdrs_tokio::query::QueryValues::NamedValues(values)
+    }
+}
@@ -1,8 +1,44 @@
+#[cfg(feature = "runtime")]
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(feature = "runtime")]
+use std::sync::Mutex;
+
+use crate::error;
 use crate::types::CBytes;
 
+const PASSWORD_AUTHENTICATOR: &str = "org.apache.cassandra.auth.PasswordAuthenticator";
+
 pub trait Authenticator {
     fn get_auth_token(&self) -> CBytes;
     fn get_cassandra_name(&self) -> Option<&str>;
+
+    /// Returns `true` if the authenticator can authenticate with a node requiring given
+    /// authenticator class. Defaults to comparing it with `get_cassandra_name`.
+    fn supports(&self, class_name: &str) -> bool {
+        self.get_cassandra_name() == Some(class_name)
+    }
+
+    /// Returns the token sent to a node requiring given authenticator class. Defaults to
+    /// `get_auth_token`.
+    fn get_auth_token_for(&self, _class_name: &str) -> CBytes {
+        self.get_auth_token()
+    }
+
+    /// Returns the token sent to a node requiring given authenticator class, called whenever
+    /// a connection is established, e.g. to fetch current credentials. Defaults to
+    /// `get_auth_token_for`.
+    fn fetch_auth_token_for<'a>(
+        &'a self,
+        class_name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = error::Result<CBytes>> + Send + 'a>> {
+        let token = self.get_auth_token_for(class_name);
+        Box::pin(async move { Ok(token) })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -22,16 +58,119 @@ impl StaticPasswordAuthenticator {
 
 impl Authenticator for StaticPasswordAuthenticator {
     fn get_auth_token(&self) -> CBytes {
-        let mut token = vec![0];
-        token.extend_from_slice(self.username.as_bytes());
-        token.push(0);
-        token.extend_from_slice(self.password.as_bytes());
+        CBytes::new(password_token(&self.username, &self.password))
+    }
+
+    fn get_cassandra_name(&self) -> Option<&str> {
+        Some(PASSWORD_AUTHENTICATOR)
+    }
+}
+
+fn password_token(username: &str, password: &str) -> Vec<u8> {
+    let mut token = vec![0];
+    token.extend_from_slice(username.as_bytes());
+    token.push(0);
+    token.extend_from_slice(password.as_bytes());
+    token
+}
+
+/// User name and password used by `PasswordAuthenticator`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new<S: ToString>(username: S, password: S) -> Self {
+        Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Source of credentials of `PasswordAuthenticator`, e.g. a secret manager.
+#[cfg(feature = "runtime")]
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    /// Returns current credentials. Called whenever a connection is established, so rotated
+    /// credentials are used by connections established afterwards. Implementations may cache
+    /// credentials between calls.
+    async fn get_credentials(&self) -> error::Result<Credentials>;
+}
+
+#[cfg(feature = "runtime")]
+#[async_trait]
+impl CredentialsProvider for Credentials {
+    async fn get_credentials(&self) -> error::Result<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+/// Password authenticator getting credentials from a `CredentialsProvider` for every new
+/// connection, allowing passwords to be rotated without restarting sessions.
+#[cfg(feature = "runtime")]
+pub struct PasswordAuthenticator {
+    provider: Arc<dyn CredentialsProvider>,
+    // token of last fetched credentials, returned by `get_auth_token`
+    last_token: Mutex<Option<Vec<u8>>>,
+}
+
+#[cfg(feature = "runtime")]
+impl PasswordAuthenticator {
+    pub fn new(provider: Arc<dyn CredentialsProvider>) -> Self {
+        PasswordAuthenticator {
+            provider,
+            last_token: Mutex::new(None),
+        }
+    }
+}
 
-        CBytes::new(token)
+#[cfg(feature = "runtime")]
+impl Authenticator for PasswordAuthenticator {
+    /// Returns the token of last fetched credentials, since credentials are only fetched
+    /// asynchronously by `fetch_auth_token_for`.
+    fn get_auth_token(&self) -> CBytes {
+        CBytes::new(
+            self.last_token
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or_else(|| vec![0]),
+        )
     }
 
     fn get_cassandra_name(&self) -> Option<&str> {
-        Some("org.apache.cassandra.auth.PasswordAuthenticator")
+        Some(PASSWORD_AUTHENTICATOR)
+    }
+
+    fn fetch_auth_token_for<'a>(
+        &'a self,
+        _class_name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = error::Result<CBytes>> + Send + 'a>> {
+        Box::pin(async move {
+            let credentials = self.provider.get_credentials().await?;
+            let token = password_token(&credentials.username, &credentials.password);
+            *self.last_token.lock().unwrap() = Some(token.clone());
+            Ok(CBytes::new(token))
+        })
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl fmt::Debug for PasswordAuthenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordAuthenticator").finish()
     }
 }
 
@@ -48,6 +187,120 @@ impl Authenticator for NoneAuthenticator {
     }
 }
 
+/// Authenticators keyed by the authenticator class they handle, picking the one matching the
+/// class each node requires, e.g. in clusters migrating between authentication schemes. Class
+/// names are matched exactly or, if no authenticator is registered under the full name, by
+/// their simple names, so `PasswordAuthenticator` matches
+/// `org.apache.cassandra.auth.PasswordAuthenticator`.
+#[derive(Clone, Default)]
+pub struct AuthenticatorRegistry {
+    authenticators: HashMap<String, Arc<dyn Authenticator + Send + Sync>>,
+    // class names of registered authenticators, reported by `get_cassandra_name`
+    names: String,
+}
+
+impl AuthenticatorRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers an authenticator under its own class name. Authenticators without a class
+    /// name, like `NoneAuthenticator`, are ignored.
+    pub fn with_authenticator(self, authenticator: Arc<dyn Authenticator + Send + Sync>) -> Self {
+        match authenticator.get_cassandra_name().map(ToString::to_string) {
+            Some(class_name) => self.with_authenticator_for(&class_name, authenticator),
+            None => self,
+        }
+    }
+
+    /// Registers an authenticator used for given authenticator class, e.g. a password
+    /// authenticator for a custom class accepting the same tokens.
+    pub fn with_authenticator_for(
+        mut self,
+        class_name: &str,
+        authenticator: Arc<dyn Authenticator + Send + Sync>,
+    ) -> Self {
+        if self
+            .authenticators
+            .insert(class_name.to_string(), authenticator)
+            .is_none()
+        {
+            if !self.names.is_empty() {
+                self.names.push_str(", ");
+            }
+            self.names.push_str(class_name);
+        }
+
+        self
+    }
+
+    /// Returns the authenticator used for a node requiring given authenticator class.
+    pub fn authenticator_for(
+        &self,
+        class_name: &str,
+    ) -> Option<&(dyn Authenticator + Send + Sync)> {
+        self.authenticators
+            .get(class_name)
+            .or_else(|| {
+                let simple_name = simple_class_name(class_name);
+                self.authenticators
+                    .iter()
+                    .find(|(registered, _)| simple_class_name(registered) == simple_name)
+                    .map(|(_, authenticator)| authenticator)
+            })
+            .map(|authenticator| authenticator.as_ref())
+    }
+}
+
+fn simple_class_name(class_name: &str) -> &str {
+    class_name.rsplit('.').next().unwrap_or(class_name)
+}
+
+impl Authenticator for AuthenticatorRegistry {
+    /// Returns the token of an authenticator registered under its own class name, if there is
+    /// only one. Nodes are authenticated with `get_auth_token_for`.
+    fn get_auth_token(&self) -> CBytes {
+        match self.authenticators.values().next() {
+            Some(authenticator) if self.authenticators.len() == 1 => authenticator.get_auth_token(),
+            _ => CBytes::new(vec![0]),
+        }
+    }
+
+    /// Returns class names of registered authenticators, separated by commas.
+    fn get_cassandra_name(&self) -> Option<&str> {
+        Some(self.names.as_str()).filter(|names| !names.is_empty())
+    }
+
+    fn supports(&self, class_name: &str) -> bool {
+        self.authenticator_for(class_name).is_some()
+    }
+
+    fn get_auth_token_for(&self, class_name: &str) -> CBytes {
+        match self.authenticator_for(class_name) {
+            Some(authenticator) => authenticator.get_auth_token_for(class_name),
+            None => CBytes::new(vec![0]),
+        }
+    }
+
+    fn fetch_auth_token_for<'a>(
+        &'a self,
+        class_name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = error::Result<CBytes>> + Send + 'a>> {
+        match self.authenticator_for(class_name) {
+            Some(authenticator) => authenticator.fetch_auth_token_for(class_name),
+            None => Box::pin(async { Ok(CBytes::new(vec![0])) }),
+        }
+    }
+}
+
+impl fmt::Debug for AuthenticatorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthenticatorRegistry")
+            .field("authenticators", &self.names)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 #[allow(deprecated)]
 mod tests {
@@ -73,4 +326,64 @@ mod tests {
         assert_eq!(auth.get_cassandra_name(), None);
         assert_eq!(auth.get_auth_token().into_plain().unwrap(), vec![0]);
     }
+
+    #[test]
+    fn registry_picks_authenticator_by_class() {
+        let registry = AuthenticatorRegistry::new()
+            .with_authenticator(Arc::new(NoneAuthenticator))
+            .with_authenticator(Arc::new(StaticPasswordAuthenticator::new("foo", "bar")))
+            .with_authenticator_for(
+                "DseAuthenticator",
+                Arc::new(StaticPasswordAuthenticator::new("dse", "secret")),
+            );
+
+        assert_eq!(
+            registry.get_cassandra_name(),
+            Some("org.apache.cassandra.auth.PasswordAuthenticator, DseAuthenticator")
+        );
+        assert!(registry.supports("org.apache.cassandra.auth.PasswordAuthenticator"));
+        assert!(!registry.supports("com.example.KerberosAuthenticator"));
+        assert_eq!(
+            registry
+                .get_auth_token_for("com.datastax.bdp.cassandra.auth.DseAuthenticator")
+                .into_plain()
+                .unwrap(),
+            b"\0dse\0secret".to_vec()
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn password_authenticator_fetches_rotated_credentials() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct RotatingProvider(AtomicUsize);
+
+        #[async_trait]
+        impl CredentialsProvider for RotatingProvider {
+            async fn get_credentials(&self) -> error::Result<Credentials> {
+                let rotation = self.0.fetch_add(1, Ordering::Relaxed);
+                Ok(Credentials::new(
+                    "user".to_string(),
+                    format!("password{}", rotation),
+                ))
+            }
+        }
+
+        let authenticator = PasswordAuthenticator::new(Arc::new(RotatingProvider::default()));
+        assert_eq!(authenticator.get_auth_token().into_plain(), Some(vec![0]));
+
+        for expected in &[b"\0user\0password0", b"\0user\0password1"] {
+            let token = authenticator
+                .fetch_auth_token_for(PASSWORD_AUTHENTICATOR)
+                .await
+                .unwrap();
+            assert_eq!(token.into_plain().unwrap(), expected.to_vec());
+        }
+        assert_eq!(
+            authenticator.get_auth_token().into_plain().unwrap(),
+            b"\0user\0password1".to_vec()
+        );
+    }
 }
@@ -0,0 +1,601 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::cluster::{
+    CDRSSession, ConnectionPool, GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks,
+    ResponseCache, SessionHooks,
+};
+use crate::compression::Compression;
+use crate::error;
+use crate::frame::frame_batch::BatchQuerySubj;
+use crate::frame::{AsBytes, Frame, Opcode, StreamId};
+use crate::query::{
+    BatchExecutor, ExecExecutor, PrepareExecutor, PreparedQuery, QueryBatch, QueryDefaults,
+    QueryExecutor, QueryParams, QueryValues,
+};
+use crate::transport::CDRSTransport;
+use crate::types::value::Value;
+
+/// Read-through cache of `SELECT` results in front of a session. Results of `SELECT` queries
+/// with bound values, e.g. by primary key, are kept for a limited time, keyed by the query
+/// text, bound values and consistency. Writes (`INSERT`, `UPDATE`, `DELETE` and batches)
+/// executed through the cache invalidate all cached results of the written table, since the
+/// partition cannot be told without the schema. Writes bypassing the cache can be reported with
+/// `invalidate_table` or, more selectively, `invalidate_partition`.
+///
+/// Unqualified tables refer to the keyspace set by the last `USE` statement executed through the
+/// cache, so keyspaces should be switched through it rather than the underlying session.
+pub struct CachedSession<S> {
+    session: S,
+    ttl: Duration,
+    max_entries: usize,
+    entries: std::sync::Mutex<HashMap<CacheKey, CacheEntry>>,
+    keyspace: std::sync::Mutex<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    keyspace: Option<String>,
+    query: String,
+    values: Vec<u8>,
+    consistency: Vec<u8>,
+}
+
+impl CacheKey {
+    fn new(
+        keyspace: Option<String>,
+        query: &str,
+        params: &QueryParams,
+        values: &QueryValues,
+    ) -> Self {
+        let values = match values {
+            QueryValues::SimpleValues(_) => values.as_bytes(),
+            // named values are hashed in random order
+            QueryValues::NamedValues(named) => {
+                let mut named: Vec<(&String, &Value)> = named.iter().collect();
+                named.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                named
+                    .into_iter()
+                    .flat_map(|(name, value)| {
+                        let mut bytes = name.as_bytes().to_vec();
+                        bytes.push(0);
+                        bytes.extend(value.as_bytes());
+                        bytes
+                    })
+                    .collect()
+            }
+        };
+
+        CacheKey {
+            keyspace,
+            query: query.to_string(),
+            values,
+            consistency: params.consistency.as_bytes(),
+        }
+    }
+}
+
+struct CacheEntry {
+    frame: Frame,
+    table: String,
+    values: QueryValues,
+    expires_at: Instant,
+}
+
+impl<S> CachedSession<S> {
+    const DEFAULT_TTL: Duration = Duration::from_secs(1);
+    const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+    pub fn new(session: S) -> Self {
+        CachedSession {
+            session,
+            ttl: Self::DEFAULT_TTL,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            entries: Default::default(),
+            keyspace: Default::default(),
+        }
+    }
+
+    /// Sets how long results are cached.
+    /// Defaults to 1 second.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of cached results. Once reached, new results are not cached
+    /// until older ones expire.
+    /// Defaults to 10000.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Returns the underlying session.
+    pub fn session(&self) -> &S {
+        &self.session
+    }
+
+    /// Removes all cached results of given table. An unqualified table refers to the current
+    /// keyspace of the cache.
+    pub fn invalidate_table(&self, table: &str) {
+        let table = self.qualify(normalize_table(table));
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, entry| entry.table != table);
+    }
+
+    /// Removes cached results of given table read with bound values starting with
+    /// `partition_key`, i.e. results of queries which bind partition key columns first. Results
+    /// read with named values are removed regardless of the values.
+    pub fn invalidate_partition(&self, table: &str, partition_key: &[Value]) {
+        let table = self.qualify(normalize_table(table));
+        self.entries.lock().unwrap().retain(|_, entry| {
+            entry.table != table
+                || match &entry.values {
+                    QueryValues::SimpleValues(values) => !values.starts_with(partition_key),
+                    QueryValues::NamedValues(_) => false,
+                }
+        });
+    }
+
+    /// Removes all cached results.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Returns the keyspace set by the last `USE` statement executed through the cache.
+    pub fn current_keyspace(&self) -> Option<String> {
+        self.keyspace.lock().unwrap().clone()
+    }
+
+    fn qualify(&self, table: String) -> String {
+        match self.keyspace.lock().unwrap().as_ref() {
+            Some(keyspace) if !table.contains('.') => format!("{}.{}", keyspace, table),
+            _ => table,
+        }
+    }
+
+    fn track_keyspace(&self, frame: &Frame) {
+        if frame.opcode != Opcode::Result {
+            return;
+        }
+
+        if let Some(set_keyspace) = frame
+            .get_body()
+            .ok()
+            .and_then(|body| body.into_set_keyspace())
+        {
+            *self.keyspace.lock().unwrap() = Some(set_keyspace.body.as_str().to_string());
+        }
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<Frame> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.frame.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn cache(&self, key: CacheKey, table: String, values: QueryValues, frame: &Frame) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries {
+            let now = Instant::now();
+            entries.retain(|_, entry| entry.expires_at > now);
+
+            if entries.len() >= self.max_entries {
+                return;
+            }
+        }
+
+        entries.insert(
+            key,
+            CacheEntry {
+                frame: frame.clone(),
+                table,
+                values,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+    }
+
+    /// Invalidates results of the table written by given query. Writes invalidate both before
+    /// and after they are sent, since results read while a write is in flight might be stale
+    /// and a failed write might have been applied anyway.
+    fn invalidate_written(&self, query: &str) {
+        if let Some(Statement::Write(table)) = Statement::parse(query) {
+            self.invalidate_table(&table);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Statement {
+    Select(String),
+    Write(String),
+}
+
+impl Statement {
+    pub(crate) fn parse(query: &str) -> Option<Self> {
+        let mut tokens = query
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ';')
+            .filter(|token| !token.is_empty());
+
+        let keyword = tokens.next()?.to_ascii_uppercase();
+        let mut table_after = |keyword: &str| {
+            tokens
+                .by_ref()
+                .skip_while(|token| !token.eq_ignore_ascii_case(keyword))
+                .nth(1)
+                .map(normalize_table)
+        };
+
+        match keyword.as_str() {
+            "SELECT" => table_after("FROM").map(Statement::Select),
+            "INSERT" => table_after("INTO").map(Statement::Write),
+            "DELETE" => table_after("FROM").map(Statement::Write),
+            "UPDATE" => tokens.next().map(normalize_table).map(Statement::Write),
+            _ => None,
+        }
+    }
+}
+
+// unquoted identifiers are case insensitive
+fn normalize_table(table: &str) -> String {
+    table
+        .split('.')
+        .map(|part| match part.strip_prefix('"') {
+            Some(quoted) => quoted.trim_end_matches('"').to_string(),
+            None => part.to_lowercase(),
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+impl<S: GetCompressor> GetCompressor for CachedSession<S> {
+    fn get_compressor(&self) -> Compression {
+        self.session.get_compressor()
+    }
+}
+
+impl<S: GetQueryDefaults> GetQueryDefaults for CachedSession<S> {
+    fn query_defaults(&self) -> QueryDefaults {
+        self.session.query_defaults()
+    }
+}
+
+impl<S: GetSessionHooks> GetSessionHooks for CachedSession<S> {
+    fn hooks(&self) -> &SessionHooks {
+        self.session.hooks()
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Send + Sync + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: GetConnection<T, M> + Sync,
+    > GetConnection<T, M> for CachedSession<S>
+{
+    async fn get_connection(&self) -> Option<Arc<ConnectionPool<M>>> {
+        self.session.get_connection().await
+    }
+
+    async fn get_routed_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        self.session
+            .get_routed_connection(keyspace, routing_key)
+            .await
+    }
+
+    async fn get_primary_replica_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        self.session
+            .get_primary_replica_connection(keyspace, routing_key)
+            .await
+    }
+}
+
+#[async_trait]
+impl<S: ResponseCache + Sync> ResponseCache for CachedSession<S> {
+    async fn match_or_cache_response(&self, stream_id: StreamId, frame: Frame) -> Option<Frame> {
+        self.session.match_or_cache_response(stream_id, frame).await
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > QueryExecutor<T, M> for CachedSession<S>
+{
+    async fn query_with_params_tw<Q: ToString + Send>(
+        &self,
+        query: Q,
+        query_params: QueryParams,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        let query = query.to_string();
+
+        match (Statement::parse(&query), &query_params.values) {
+            (Some(Statement::Select(table)), Some(values))
+                if !with_tracing && query_params.paging_state.is_none() =>
+            {
+                let key = CacheKey::new(self.current_keyspace(), &query, &query_params, values);
+                let table = self.qualify(table);
+                if let Some(frame) = self.cached(&key) {
+                    return Ok(frame);
+                }
+
+                let values = values.clone();
+                let frame = self
+                    .session
+                    .query_with_params_tw(query, query_params, with_tracing, with_warnings)
+                    .await?;
+
+                if frame.opcode == Opcode::Result {
+                    self.cache(key, table, values, &frame);
+                }
+
+                Ok(frame)
+            }
+            _ => {
+                self.invalidate_written(&query);
+                let result = self
+                    .session
+                    .query_with_params_tw(query.as_str(), query_params, with_tracing, with_warnings)
+                    .await;
+                self.invalidate_written(&query);
+
+                if let Ok(frame) = &result {
+                    self.track_keyspace(frame);
+                }
+
+                result
+            }
+        }
+    }
+}
+
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > PrepareExecutor<T, M> for CachedSession<S>
+{
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > ExecExecutor<T, M> for CachedSession<S>
+{
+    async fn exec_with_params_tw(
+        &self,
+        prepared: &PreparedQuery,
+        query_parameters: QueryParams,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        self.invalidate_written(&prepared.query);
+        let result = self
+            .session
+            .exec_with_params_tw(prepared, query_parameters, with_tracing, with_warnings)
+            .await;
+        self.invalidate_written(&prepared.query);
+        result
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > BatchExecutor<T, M> for CachedSession<S>
+{
+    async fn batch_with_params_tw(
+        &self,
+        batch: QueryBatch,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        let queries: Vec<String> = batch
+            .queries
+            .iter()
+            .map(|query| match &query.subject {
+                BatchQuerySubj::QueryString(query) => query.as_str().to_string(),
+                BatchQuerySubj::PreparedId(prepared) => prepared.query.clone(),
+            })
+            .collect();
+        queries
+            .iter()
+            .for_each(|query| self.invalidate_written(query));
+
+        let result = self
+            .session
+            .batch_with_params_tw(batch, with_tracing, with_warnings)
+            .await;
+        queries
+            .iter()
+            .for_each(|query| self.invalidate_written(query));
+        result
+    }
+}
+
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > CDRSSession<T, M> for CachedSession<S>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::session::{new, Session};
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::{
+        ClusterTcpConfig, DirectSession, NodeTcpConfigBuilder, TcpConnectionPool,
+    };
+    use crate::load_balancing::RoundRobin;
+
+    // answers USE statements with the keyspace set and other queries with a void result
+    fn set_keyspace(_: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+        let length = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+        let query = std::str::from_utf8(&body[4..4 + length]).unwrap();
+        let result = match query.strip_prefix("USE ") {
+            Some(keyspace) => {
+                let mut result = vec![0, 0, 0, 3];
+                result.extend_from_slice(&(keyspace.len() as u16).to_be_bytes());
+                result.extend_from_slice(keyspace.as_bytes());
+                result
+            }
+            None => vec![0, 0, 0, 1],
+        };
+
+        Some((Opcode::Result, result))
+    }
+
+    #[tokio::test]
+    async fn results_are_cached_per_keyspace() {
+        let node = TestNode::start_with(set_keyspace).await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            node.addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .build()]);
+        let session: CachedSession<Session<RoundRobin<TcpConnectionPool>>> =
+            CachedSession::new(new(&config, RoundRobin::new()).await.unwrap());
+        let select = || {
+            session.query_with_values(
+                "SELECT * FROM t WHERE k = ?",
+                QueryValues::SimpleValues(vec![1.into()]),
+            )
+        };
+
+        // pooled connections are validated with USE statements, so only reads are counted
+        let reads = || {
+            node.requests()
+                .iter()
+                .filter(|request| request.body.windows(6).any(|bytes| bytes == b"SELECT"))
+                .count()
+        };
+
+        session.query("USE ks1").await.unwrap();
+        select().await.unwrap();
+        select().await.unwrap();
+        assert_eq!(reads(), 1);
+
+        session.query("USE ks2").await.unwrap();
+        assert_eq!(session.current_keyspace().as_deref(), Some("ks2"));
+        select().await.unwrap();
+        select().await.unwrap();
+        assert_eq!(reads(), 2);
+
+        // writes invalidate results of tables in the current keyspace only
+        session.query("INSERT INTO t (k) VALUES (1)").await.unwrap();
+        select().await.unwrap();
+        assert_eq!(reads(), 3);
+
+        session.query("USE ks1").await.unwrap();
+        select().await.unwrap();
+        assert_eq!(reads(), 3);
+
+        session.invalidate_table("ks1.t");
+        select().await.unwrap();
+        assert_eq!(reads(), 4);
+    }
+
+    #[test]
+    fn parse_statements() {
+        assert_eq!(
+            Statement::parse("select * from KS.Users where id = ?"),
+            Some(Statement::Select("ks.users".into()))
+        );
+        assert_eq!(
+            Statement::parse("INSERT INTO \"Users\"(id) VALUES (?)"),
+            Some(Statement::Write("Users".into()))
+        );
+        assert_eq!(
+            Statement::parse("UPDATE users SET name = ? WHERE id = ?"),
+            Some(Statement::Write("users".into()))
+        );
+        assert_eq!(
+            Statement::parse("DELETE name FROM users WHERE id = ?;"),
+            Some(Statement::Write("users".into()))
+        );
+        assert_eq!(Statement::parse("TRUNCATE users"), None);
+    }
+
+    #[test]
+    fn named_values_key() {
+        let params = QueryParams::default();
+        let mut first = HashMap::new();
+        let mut second = HashMap::new();
+        for i in 0..16 {
+            first.insert(format!("v{}", i), Value::from(i));
+            second.insert(format!("v{}", 15 - i), Value::from(15 - i));
+        }
+
+        assert_eq!(
+            CacheKey::new(None, "q", &params, &QueryValues::NamedValues(first)),
+            CacheKey::new(None, "q", &params, &QueryValues::NamedValues(second))
+        );
+    }
+
+    #[tokio::test]
+    async fn results_are_cached_until_invalidated() {
+        let node = TestNode::start().await;
+        let session = CachedSession::new(DirectSession::connect(node.addr).await.unwrap())
+            .ttl(Duration::from_secs(60));
+        let select = "SELECT * FROM users WHERE id = ?";
+        let selects = || {
+            node.requests()
+                .iter()
+                .filter(|request| request.body[4..].starts_with(b"SELECT"))
+                .count()
+        };
+
+        session.query_with_values(select, vec![1]).await.unwrap();
+        session.query_with_values(select, vec![1]).await.unwrap();
+        assert_eq!(selects(), 1);
+
+        session.query_with_values(select, vec![2]).await.unwrap();
+        assert_eq!(selects(), 2);
+
+        session
+            .query_with_values("UPDATE users SET name = ? WHERE id = ?", vec![3, 1])
+            .await
+            .unwrap();
+        session.query_with_values(select, vec![1]).await.unwrap();
+        session.query_with_values(select, vec![2]).await.unwrap();
+        assert_eq!(selects(), 4);
+
+        session.invalidate_partition("users", &[1.into()]);
+        session.query_with_values(select, vec![1]).await.unwrap();
+        session.query_with_values(select, vec![2]).await.unwrap();
+        assert_eq!(selects(), 5);
+    }
+}
@@ -1,10 +1,50 @@
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::authenticators::Authenticator;
+use crate::cluster::{ConnectionStateListener, ConnectionThrottle, NodeHealthConfig, SessionHooks};
+use crate::compression::DEFAULT_MAX_DECOMPRESSED_LENGTH;
+use crate::error;
+use crate::load_balancing::NodeDistance;
+use crate::query::QueryDefaults;
+use crate::transport::{TcpSocketOptions, TransportWrapper, WriteCoalescing};
 
-/// Cluster configuration that holds per node TCP configs
-pub struct ClusterTcpConfig(pub Vec<NodeTcpConfig>);
+/// Cluster configuration that holds per node TCP configs, session level query defaults
+/// and session hooks.
+pub struct ClusterTcpConfig {
+    pub nodes: Vec<NodeTcpConfig>,
+    /// Defaults for query parameters which were not explicitly set and the request timeout,
+    /// used by sessions created with this config.
+    pub query_defaults: QueryDefaults,
+    /// Hooks observing or altering requests of sessions created with this config.
+    pub hooks: SessionHooks,
+}
+
+impl ClusterTcpConfig {
+    /// Creates a config of given nodes with default `QueryDefaults` and no hooks.
+    pub fn new(nodes: Vec<NodeTcpConfig>) -> Self {
+        ClusterTcpConfig {
+            nodes,
+            query_defaults: Default::default(),
+            hooks: Default::default(),
+        }
+    }
+
+    /// Sets defaults for query parameters which were not explicitly set and the request
+    /// timeout.
+    pub fn with_query_defaults(mut self, query_defaults: QueryDefaults) -> Self {
+        self.query_defaults = query_defaults;
+        self
+    }
+
+    /// Sets hooks observing or altering requests, e.g. statement interceptors or a prepared
+    /// statement cache.
+    pub fn with_hooks(mut self, hooks: SessionHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+}
 
 /// Single node TCP connection config.
 #[derive(Clone)]
@@ -12,10 +52,39 @@ pub struct NodeTcpConfig {
     pub addr: String,
     pub authenticator: Arc<dyn Authenticator + Send + Sync>,
     pub max_size: u32,
+    pub remote_max_size: u32,
     pub min_idle: Option<u32>,
     pub max_lifetime: Option<Duration>,
     pub idle_timeout: Option<Duration>,
     pub connection_timeout: Duration,
+    pub socket_options: TcpSocketOptions,
+    pub write_coalescing: Option<WriteCoalescing>,
+    pub transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    pub connection_throttle: ConnectionThrottle,
+    pub node_health: NodeHealthConfig,
+    pub max_orphaned_streams: u32,
+    pub max_decompressed_length: usize,
+    pub connection_state_listeners: Vec<Arc<dyn ConnectionStateListener>>,
+}
+
+impl NodeTcpConfig {
+    /// Returns pool size for a node at given distance or `None` if the node should not be
+    /// connected to.
+    pub fn max_size_for(&self, distance: NodeDistance) -> Option<u32> {
+        match distance {
+            NodeDistance::Local => Some(self.max_size),
+            NodeDistance::Remote => Some(self.remote_max_size),
+            NodeDistance::Ignored => None,
+        }
+    }
+
+    /// Resolves configured address into a socket address.
+    pub fn resolve_addr(&self) -> error::Result<SocketAddr> {
+        self.addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| error::Error::from("Cannot parse address"))
+    }
 }
 
 /// Builder structure that helps to configure TCP connection for node.
@@ -23,15 +92,26 @@ pub struct NodeTcpConfigBuilder {
     addr: String,
     authenticator: Arc<dyn Authenticator + Send + Sync>,
     max_size: Option<u32>,
+    remote_max_size: Option<u32>,
     min_idle: Option<u32>,
     max_lifetime: Option<Duration>,
     idle_timeout: Option<Duration>,
     connection_timeout: Option<Duration>,
+    socket_options: TcpSocketOptions,
+    write_coalescing: Option<WriteCoalescing>,
+    transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    connection_throttle: ConnectionThrottle,
+    node_health: NodeHealthConfig,
+    max_orphaned_streams: Option<u32>,
+    max_decompressed_length: Option<usize>,
+    connection_state_listeners: Vec<Arc<dyn ConnectionStateListener>>,
 }
 
 impl NodeTcpConfigBuilder {
     const DEFAULT_MAX_SIZE: u32 = 10;
+    const DEFAULT_REMOTE_MAX_SIZE: u32 = 1;
     const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+    const DEFAULT_MAX_ORPHANED_STREAMS: u32 = 256;
 
     pub fn new<S: ToString>(
         addr: S,
@@ -41,10 +121,19 @@ impl NodeTcpConfigBuilder {
             addr: addr.to_string(),
             authenticator,
             max_size: None,
+            remote_max_size: None,
             min_idle: None,
             max_lifetime: None,
             idle_timeout: None,
             connection_timeout: None,
+            socket_options: Default::default(),
+            write_coalescing: None,
+            transport_wrapper: None,
+            connection_throttle: Default::default(),
+            node_health: Default::default(),
+            max_orphaned_streams: None,
+            max_decompressed_length: None,
+            connection_state_listeners: vec![],
         }
     }
 
@@ -55,6 +144,14 @@ impl NodeTcpConfigBuilder {
         self
     }
 
+    /// Sets the maximum number of connections managed by the pool for nodes considered
+    /// remote by the load balancing strategy.
+    /// Defaults to 1.
+    pub fn remote_max_size(mut self, size: u32) -> Self {
+        self.remote_max_size = Some(size);
+        self
+    }
+
     /// Sets the minimum idle connection count maintained by the pool.
     /// If set, the pool will try to maintain at least this many idle
     /// connections at all times, while respecting the value of `max_size`.
@@ -88,6 +185,105 @@ impl NodeTcpConfigBuilder {
         self
     }
 
+    /// Sets `TCP_NODELAY` on connections.
+    /// Defaults to true.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.socket_options.nodelay = nodelay;
+        self
+    }
+
+    /// Enables TCP keep-alive with given idle time before the first probe is sent.
+    /// Defaults to None (keep-alive disabled).
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.socket_options.keepalive = keepalive;
+        self
+    }
+
+    /// Sets the interval between TCP keep-alive probes. Used only with `keepalive` set.
+    /// Defaults to None (system default).
+    pub fn keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.socket_options.keepalive_interval = interval;
+        self
+    }
+
+    /// Sets the size of the socket send buffer.
+    /// Defaults to None (system default).
+    pub fn send_buffer_size(mut self, size: Option<usize>) -> Self {
+        self.socket_options.send_buffer_size = size;
+        self
+    }
+
+    /// Sets the size of the socket receive buffer.
+    /// Defaults to None (system default).
+    pub fn recv_buffer_size(mut self, size: Option<usize>) -> Self {
+        self.socket_options.recv_buffer_size = size;
+        self
+    }
+
+    /// Sets the delay before trying the next address of a node resolving to multiple ones.
+    /// Defaults to 250ms.
+    pub fn connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.socket_options.connection_attempt_delay = delay;
+        self
+    }
+
+    /// Enables coalescing of small outgoing frames into bigger writes.
+    /// Defaults to None (coalescing disabled).
+    pub fn write_coalescing(mut self, write_coalescing: Option<WriteCoalescing>) -> Self {
+        self.write_coalescing = write_coalescing;
+        self
+    }
+
+    /// Sets a hook wrapping newly established streams, e.g. to talk to a custom proxy.
+    /// Defaults to None.
+    pub fn transport_wrapper(
+        mut self,
+        transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    ) -> Self {
+        self.transport_wrapper = transport_wrapper;
+        self
+    }
+
+    /// Sets a throttle for establishing new connections. Use the same throttle (or its clones)
+    /// for all nodes to limit connection attempts cluster-wide.
+    /// Defaults to no throttling.
+    pub fn connection_throttle(mut self, connection_throttle: ConnectionThrottle) -> Self {
+        self.connection_throttle = connection_throttle;
+        self
+    }
+
+    /// Sets when the node is marked down after connection errors and how it is probed until it
+    /// recovers.
+    /// Defaults to marking the node down after 3 consecutive errors and probing it every 5
+    /// seconds.
+    pub fn node_health(mut self, node_health: NodeHealthConfig) -> Self {
+        self.node_health = node_health;
+        self
+    }
+
+    /// Sets the number of outstanding requests abandoned before their responses were read, e.g.
+    /// cancelled ones, above which a connection is closed and replaced by a new one. Drained
+    /// responses stop counting. 0 disables recycling connections. Defaults to 256.
+    pub fn max_orphaned_streams(mut self, max_orphaned_streams: u32) -> Self {
+        self.max_orphaned_streams = Some(max_orphaned_streams);
+        self
+    }
+
+    /// Sets the maximum length of a decompressed frame body. Compressed frames declaring longer
+    /// bodies fail with an error before the body is allocated.
+    /// Defaults to 256 MiB.
+    pub fn max_decompressed_length(mut self, max_decompressed_length: usize) -> Self {
+        self.max_decompressed_length = Some(max_decompressed_length);
+        self
+    }
+
+    /// Adds a listener notified when connections to the node change their state, e.g. to
+    /// export connection metrics.
+    pub fn connection_state_listener(mut self, listener: Arc<dyn ConnectionStateListener>) -> Self {
+        self.connection_state_listeners.push(listener);
+        self
+    }
+
     /// Sets new authenticator.
     pub fn authenticator(mut self, authenticator: Arc<dyn Authenticator + Send + Sync>) -> Self {
         self.authenticator = authenticator;
@@ -101,12 +297,45 @@ impl NodeTcpConfigBuilder {
             authenticator: self.authenticator,
 
             max_size: self.max_size.unwrap_or(Self::DEFAULT_MAX_SIZE),
+            remote_max_size: self
+                .remote_max_size
+                .unwrap_or(Self::DEFAULT_REMOTE_MAX_SIZE),
             min_idle: self.min_idle,
             max_lifetime: self.max_lifetime,
             idle_timeout: self.idle_timeout,
             connection_timeout: self
                 .connection_timeout
                 .unwrap_or(Self::DEFAULT_CONNECTION_TIMEOUT),
+            socket_options: self.socket_options,
+            write_coalescing: self.write_coalescing,
+            transport_wrapper: self.transport_wrapper,
+            connection_throttle: self.connection_throttle,
+            node_health: self.node_health,
+            max_orphaned_streams: self
+                .max_orphaned_streams
+                .unwrap_or(Self::DEFAULT_MAX_ORPHANED_STREAMS),
+            max_decompressed_length: self
+                .max_decompressed_length
+                .unwrap_or(DEFAULT_MAX_DECOMPRESSED_LENGTH),
+            connection_state_listeners: self.connection_state_listeners,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+
+    #[test]
+    fn max_size_for_distance() {
+        let config = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator))
+            .max_size(5)
+            .remote_max_size(2)
+            .build();
+
+        assert_eq!(config.max_size_for(NodeDistance::Local), Some(5));
+        assert_eq!(config.max_size_for(NodeDistance::Remote), Some(2));
+        assert_eq!(config.max_size_for(NodeDistance::Ignored), None);
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::query::QueryDefaults;
+
+/// Changes of session configuration applied at runtime with `Session::update_config`, e.g. when
+/// reloading configuration without recreating the session. Only options which are set are
+/// changed.
+///
+/// Query defaults apply to requests started after the update. Changed pool options replace
+/// connection pools of nodes by new ones, which establish connections as they are needed;
+/// requests in flight finish on connections of the previous pools.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigUpdate {
+    pub(crate) query_defaults: Option<QueryDefaults>,
+    pub(crate) max_size: Option<u32>,
+    pub(crate) remote_max_size: Option<u32>,
+    pub(crate) min_idle: Option<Option<u32>>,
+    pub(crate) max_lifetime: Option<Option<Duration>>,
+    pub(crate) idle_timeout: Option<Option<Duration>>,
+    pub(crate) connection_timeout: Option<Duration>,
+}
+
+impl ConfigUpdate {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Replaces defaults of query parameters and the request timeout.
+    pub fn query_defaults(mut self, query_defaults: QueryDefaults) -> Self {
+        self.query_defaults = Some(query_defaults);
+        self
+    }
+
+    /// Sets the maximum number of connections to each local node.
+    pub fn max_size(mut self, size: u32) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum number of connections to each node considered remote by the load
+    /// balancing strategy.
+    pub fn remote_max_size(mut self, size: u32) -> Self {
+        self.remote_max_size = Some(size);
+        self
+    }
+
+    /// Sets the minimum idle connection count maintained by pools.
+    pub fn min_idle(mut self, min_idle: Option<u32>) -> Self {
+        self.min_idle = Some(min_idle);
+        self
+    }
+
+    /// Sets the maximum lifetime of pooled connections.
+    pub fn max_lifetime(mut self, max_lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    /// Sets the idle timeout of pooled connections.
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Sets the timeout of waiting for a pooled connection.
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection_timeout = Some(connection_timeout);
+        self
+    }
+
+    /// Returns `true` if the update changes any option of connection pools.
+    pub(crate) fn changes_pools(&self) -> bool {
+        self.max_size.is_some()
+            || self.remote_max_size.is_some()
+            || self.min_idle.is_some()
+            || self.max_lifetime.is_some()
+            || self.idle_timeout.is_some()
+            || self.connection_timeout.is_some()
+    }
+}
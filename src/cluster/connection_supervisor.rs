@@ -0,0 +1,309 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::cluster::ConnectionThrottle;
+use crate::error;
+use crate::frame::ProtocolVersion;
+use crate::transport::CDRSTransport;
+
+/// Lifecycle state of a connection. Connections only move forward through the states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConnectionState {
+    /// The connection is established, but its startup handshake has not completed yet.
+    Connecting,
+    /// The connection can be used for requests.
+    Ready,
+    /// The connection is being closed; frames queued so far are still written.
+    Draining,
+    /// The connection is closed, e.g. by the peer or after an IO error.
+    Closed,
+}
+
+impl ConnectionState {
+    const ALL: [ConnectionState; 4] = [
+        ConnectionState::Connecting,
+        ConnectionState::Ready,
+        ConnectionState::Draining,
+        ConnectionState::Closed,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL[index as usize]
+    }
+}
+
+/// Hook notified about state changes of connections, e.g. to export metrics or log reconnects.
+pub trait ConnectionStateListener: Send + Sync {
+    /// Called when a connection to given node changes its state.
+    fn on_state_change(&self, node: &str, from: ConnectionState, to: ConnectionState);
+}
+
+/// Numbers of connections to a single node in each state, along with listeners notified about
+/// their changes.
+pub struct ConnectionStates {
+    node: String,
+    counts: [AtomicUsize; 4],
+    keyspace_failures: AtomicUsize,
+    listeners: Vec<Arc<dyn ConnectionStateListener>>,
+}
+
+impl ConnectionStates {
+    pub fn new(node: String, listeners: Vec<Arc<dyn ConnectionStateListener>>) -> Self {
+        ConnectionStates {
+            node,
+            counts: Default::default(),
+            keyspace_failures: AtomicUsize::new(0),
+            listeners,
+        }
+    }
+
+    /// Returns the number of connections in given state. Closed connections are counted until
+    /// they are dropped by their pool.
+    pub fn count(&self, state: ConnectionState) -> usize {
+        self.counts[state.index()].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times the keyspace of a connection could not be set, either when
+    /// it was established or validated.
+    pub fn keyspace_failures(&self) -> usize {
+        self.keyspace_failures.load(Ordering::Relaxed)
+    }
+
+    fn transition(&self, from: ConnectionState, to: ConnectionState) {
+        self.counts[from.index()].fetch_sub(1, Ordering::Relaxed);
+        self.counts[to.index()].fetch_add(1, Ordering::Relaxed);
+
+        for listener in &self.listeners {
+            listener.on_state_change(&self.node, from, to);
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionStates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionStates")
+            .field("node", &self.node)
+            .field("counts", &self.counts)
+            .field("keyspace_failures", &self.keyspace_failures)
+            .finish()
+    }
+}
+
+/// State of a single connection, shared between its transport and, once tracked,
+/// `ConnectionStates` of its node.
+#[derive(Debug)]
+pub struct ConnectionStateCell {
+    state: AtomicU8,
+    states: OnceLock<Arc<ConnectionStates>>,
+}
+
+impl ConnectionStateCell {
+    /// Creates an untracked cell of a connection in `Connecting` state.
+    pub fn new() -> Self {
+        ConnectionStateCell {
+            state: AtomicU8::new(ConnectionState::Connecting as u8),
+            states: OnceLock::new(),
+        }
+    }
+
+    /// Returns current state of the connection.
+    pub fn get(&self) -> ConnectionState {
+        ConnectionState::from_index(self.state.load(Ordering::Relaxed))
+    }
+
+    /// Moves the connection to given state, unless it is already in the same or a later one.
+    /// Returns `true` if the state changed.
+    pub fn advance(&self, to: ConnectionState) -> bool {
+        let from = self
+            .state
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+                (state < to as u8).then_some(to as u8)
+            });
+
+        match from {
+            Ok(from) => {
+                if let Some(states) = self.states.get() {
+                    states.transition(ConnectionState::from_index(from), to);
+                }
+
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Starts counting the connection in given states. A cell can be tracked only once.
+    pub fn track(&self, states: Arc<ConnectionStates>) {
+        let counts = &states.counts[self.get().index()];
+        if self.states.set(states.clone()).is_ok() {
+            counts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for ConnectionStateCell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ConnectionStateCell {
+    fn drop(&mut self) {
+        self.advance(ConnectionState::Closed);
+        if let Some(states) = self.states.get() {
+            states.counts[ConnectionState::Closed.index()].fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Supervises connections to a single node: establishes them respecting the connection
+/// throttle, tracks their states and decides when they are broken and need to be replaced.
+#[derive(Debug)]
+pub(crate) struct ConnectionSupervisor {
+    states: Arc<ConnectionStates>,
+    connection_throttle: ConnectionThrottle,
+    max_orphaned_streams: u32,
+    failed_connects: AtomicU32,
+    protocol_version: Arc<AtomicU8>,
+}
+
+impl Clone for ConnectionSupervisor {
+    /// Returns a supervisor sharing connection states, the connection throttle and the
+    /// negotiated protocol version, e.g. for a rebuilt pool. Backoff after failed attempts
+    /// starts anew.
+    fn clone(&self) -> Self {
+        ConnectionSupervisor {
+            states: self.states.clone(),
+            connection_throttle: self.connection_throttle.clone(),
+            max_orphaned_streams: self.max_orphaned_streams,
+            failed_connects: AtomicU32::new(0),
+            protocol_version: self.protocol_version.clone(),
+        }
+    }
+}
+
+impl ConnectionSupervisor {
+    pub fn new(node: String) -> Self {
+        ConnectionSupervisor {
+            states: Arc::new(ConnectionStates::new(node, vec![])),
+            connection_throttle: Default::default(),
+            max_orphaned_streams: 0,
+            failed_connects: AtomicU32::new(0),
+            protocol_version: Arc::new(AtomicU8::new(ProtocolVersion::default().request_byte())),
+        }
+    }
+
+    pub fn set_connection_throttle(&mut self, connection_throttle: ConnectionThrottle) {
+        self.connection_throttle = connection_throttle;
+    }
+
+    pub fn set_max_orphaned_streams(&mut self, max_orphaned_streams: u32) {
+        self.max_orphaned_streams = max_orphaned_streams;
+    }
+
+    /// Replaces listeners of state changes. Connections established before keep notifying
+    /// previous listeners.
+    pub fn set_listeners(&mut self, listeners: Vec<Arc<dyn ConnectionStateListener>>) {
+        self.states = Arc::new(ConnectionStates::new(self.states.node.clone(), listeners));
+    }
+
+    pub fn states(&self) -> &Arc<ConnectionStates> {
+        &self.states
+    }
+
+    /// Establishes a new connection with `connect`, respecting the connection throttle.
+    pub async fn connect<T, F, Fut>(&self, connect: F) -> error::Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = error::Result<T>>,
+    {
+        self.connection_throttle
+            .connect(&self.failed_connects, connect)
+            .await
+    }
+
+    /// Returns the protocol version new connections start with: the one last negotiated with
+    /// the node, or the default one.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::from_byte(self.protocol_version.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    /// Records the protocol version negotiated by a new connection.
+    pub fn set_protocol_version(&self, version: ProtocolVersion) {
+        self.protocol_version
+            .store(version.request_byte(), Ordering::Relaxed);
+    }
+
+    /// Records given error of establishing or validating a connection in connection states.
+    pub fn record_failure(&self, error: error::Error) -> error::Error {
+        if let error::Error::UseKeyspace { .. } = error {
+            self.states
+                .keyspace_failures
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        error
+    }
+
+    /// Returns `true` if given connection is not ready anymore or has too many abandoned
+    /// requests whose responses haven't been drained, so it needs to be replaced.
+    pub fn has_broken<T: CDRSTransport>(&self, transport: &T) -> bool {
+        transport.state() != ConnectionState::Ready
+            || (self.max_orphaned_streams > 0
+                && transport.orphaned_streams() > self.max_orphaned_streams as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingListener(Mutex<Vec<(ConnectionState, ConnectionState)>>);
+
+    impl ConnectionStateListener for RecordingListener {
+        fn on_state_change(&self, _node: &str, from: ConnectionState, to: ConnectionState) {
+            self.0.lock().unwrap().push((from, to));
+        }
+    }
+
+    #[test]
+    fn tracks_connection_states() {
+        let listener = Arc::new(RecordingListener::default());
+        let states = Arc::new(ConnectionStates::new(
+            "127.0.0.1:9042".into(),
+            vec![listener.clone()],
+        ));
+
+        let cell = ConnectionStateCell::new();
+        cell.track(states.clone());
+        assert_eq!(states.count(ConnectionState::Connecting), 1);
+
+        assert!(cell.advance(ConnectionState::Ready));
+        assert!(!cell.advance(ConnectionState::Connecting));
+        assert_eq!(cell.get(), ConnectionState::Ready);
+        assert_eq!(states.count(ConnectionState::Connecting), 0);
+        assert_eq!(states.count(ConnectionState::Ready), 1);
+
+        drop(cell);
+        for state in ConnectionState::ALL {
+            assert_eq!(states.count(state), 0);
+        }
+
+        assert_eq!(
+            *listener.0.lock().unwrap(),
+            vec![
+                (ConnectionState::Connecting, ConnectionState::Ready),
+                (ConnectionState::Ready, ConnectionState::Closed),
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use rand::Rng;
+use std::cmp::min;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::sleep;
+
+use crate::error;
+
+/// Throttles establishing of new connections, to avoid connection storms when many pools
+/// (re)connect at the same time, e.g. when a node comes back up. Clones share the same limit
+/// of concurrent connection attempts, so a single throttle can be set on all node configs to
+/// limit attempts cluster-wide.
+///
+/// Each attempt is preceded by a random delay between zero and the current backoff. The
+/// backoff starts at `jitter` and doubles with each consecutive failed attempt of a given
+/// pool, up to `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct ConnectionThrottle {
+    permits: Option<Arc<Semaphore>>,
+    jitter: Duration,
+    max_backoff: Duration,
+}
+
+impl ConnectionThrottle {
+    const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+    /// Creates a throttle which doesn't limit connection attempts.
+    pub fn new() -> Self {
+        ConnectionThrottle {
+            permits: None,
+            jitter: Duration::from_secs(0),
+            max_backoff: Self::DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    /// Sets the maximum number of concurrent connection attempts.
+    /// Defaults to unlimited, which is also what 0 means.
+    pub fn max_concurrent_connects(mut self, max: usize) -> Self {
+        // a semaphore without permits would block all attempts forever
+        self.permits = if max == 0 {
+            None
+        } else {
+            Some(Arc::new(Semaphore::new(max)))
+        };
+        self
+    }
+
+    /// Sets the maximum random delay before a connection attempt.
+    /// Defaults to 0 (no delay).
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the upper bound of the delay growing with consecutive failures.
+    /// Defaults to 10 seconds.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let backoff = self
+            .jitter
+            .checked_mul(1 << min(failures, 16))
+            .unwrap_or(self.max_backoff);
+        min(backoff, self.max_backoff)
+    }
+
+    fn random_delay(&self, failures: u32) -> Duration {
+        let backoff = self.backoff(failures).as_millis() as u64;
+        if backoff == 0 {
+            return Duration::from_secs(0);
+        }
+
+        Duration::from_millis(rand::thread_rng().gen_range(0..=backoff))
+    }
+
+    /// Runs a connection attempt respecting the throttle. `failures` tracks consecutive failed
+    /// attempts of a single pool.
+    pub(crate) async fn connect<T, F, Fut>(
+        &self,
+        failures: &AtomicU32,
+        connect: F,
+    ) -> error::Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = error::Result<T>>,
+    {
+        let delay = self.random_delay(failures.load(Ordering::Relaxed));
+        if delay > Duration::from_secs(0) {
+            sleep(delay).await;
+        }
+
+        let _permit = match self.permits {
+            Some(ref permits) => Some(
+                permits
+                    .acquire()
+                    .await
+                    .map_err(|error| error::Error::from(error.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let result = connect().await;
+        if result.is_ok() {
+            failures.store(0, Ordering::Relaxed);
+        } else {
+            failures.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+}
+
+impl Default for ConnectionThrottle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_with_failures() {
+        let throttle = ConnectionThrottle::new()
+            .jitter(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500));
+
+        assert_eq!(throttle.backoff(0), Duration::from_millis(100));
+        assert_eq!(throttle.backoff(1), Duration::from_millis(200));
+        assert_eq!(throttle.backoff(3), Duration::from_millis(500));
+        assert_eq!(throttle.backoff(u32::MAX), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn zero_limit_is_unlimited() {
+        let throttle = ConnectionThrottle::new().max_concurrent_connects(0);
+        let failures = AtomicU32::new(0);
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            throttle.connect(&failures, || async { Ok(()) }),
+        )
+        .await;
+        assert!(matches!(result, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_connects() {
+        let throttle = ConnectionThrottle::new().max_concurrent_connects(2);
+        let active = Arc::new(AtomicU32::new(0));
+        let max_active = Arc::new(AtomicU32::new(0));
+
+        let attempts = (0..8).map(|_| {
+            let throttle = throttle.clone();
+            let active = active.clone();
+            let max_active = max_active.clone();
+
+            tokio::spawn(async move {
+                let failures = AtomicU32::new(0);
+                throttle
+                    .connect(&failures, || async {
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_active.fetch_max(now, Ordering::SeqCst);
+                        sleep(Duration::from_millis(10)).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        Ok(())
+                    })
+                    .await
+            })
+        });
+
+        for attempt in attempts.collect::<Vec<_>>() {
+            attempt.await.unwrap().unwrap();
+        }
+
+        assert_eq!(max_active.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn tracks_failures() {
+        let throttle = ConnectionThrottle::new();
+        let failures = AtomicU32::new(0);
+
+        let _ = throttle
+            .connect(&failures, || async {
+                Err::<(), _>(error::Error::from("fail"))
+            })
+            .await;
+        assert_eq!(failures.load(Ordering::Relaxed), 1);
+
+        throttle
+            .connect(&failures, || async { Ok(()) })
+            .await
+            .unwrap();
+        assert_eq!(failures.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use fxhash::FxHashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::authenticators::NoneAuthenticator;
+use crate::cluster::{
+    connect_negotiated, CDRSSession, GetCompressor, GetConnection, GetQueryDefaults,
+    GetSessionHooks, KeyspaceHolder, NodeTcpConfig, NodeTcpConfigBuilder, ResponseCache,
+    SessionHooks, SessionPager, TcpConnectionPool, TcpConnectionsManager,
+};
+use crate::compression::{Compression, CompressionStats};
+use crate::error;
+use crate::frame::{Frame, ProtocolVersion, StreamId};
+use crate::query::{BatchExecutor, ExecExecutor, PrepareExecutor, QueryDefaults, QueryExecutor};
+use crate::transport::TransportTcp;
+
+/// Lightweight session which talks to a single node over a single connection, without load
+/// balancing, node discovery or server event listening. Useful for tools, sidecars and tests
+/// where the full cluster machinery is not needed. Requests share the connection, each on its
+/// own stream id, and responses are routed to them by stream id in any order. The connection is
+/// not reestablished once closed.
+pub struct DirectSession {
+    connection: Arc<TcpConnectionPool>,
+    responses: Mutex<FxHashMap<StreamId, Frame>>,
+    pub compression: Compression,
+    query_defaults: QueryDefaults,
+    hooks: SessionHooks,
+}
+
+impl DirectSession {
+    /// Connects to a node at given address without authentication and compression.
+    pub async fn connect<S: ToString>(addr: S) -> error::Result<Self> {
+        let node_config = NodeTcpConfigBuilder::new(addr, Arc::new(NoneAuthenticator)).build();
+        Self::connect_with_config(node_config, Compression::None, Default::default()).await
+    }
+
+    /// Connects to a node using given node config and defaults for query parameters which
+    /// were not explicitly set and the request timeout. Pool options of the config are
+    /// ignored, since exactly one connection is established eagerly and held without a pool.
+    pub async fn connect_with_config(
+        node_config: NodeTcpConfig,
+        compression: Compression,
+        query_defaults: QueryDefaults,
+    ) -> error::Result<Self> {
+        let addr = node_config.resolve_addr()?;
+        let keyspace_holder = Arc::new(KeyspaceHolder::default());
+        let compression_stats = Arc::new(CompressionStats::default());
+        let transport = connect_negotiated(
+            ProtocolVersion::default(),
+            || async {
+                Ok(TransportTcp::with_transport_wrapper(
+                    &node_config.addr,
+                    keyspace_holder.clone(),
+                    node_config.socket_options,
+                    node_config.transport_wrapper.clone(),
+                )
+                .await?
+                .with_write_coalescing(node_config.write_coalescing)
+                .with_compression_stats(compression_stats.clone())
+                .with_max_decompressed_length(node_config.max_decompressed_length))
+            },
+            node_config.authenticator.deref(),
+            keyspace_holder.deref(),
+        )
+        .await?;
+
+        Ok(DirectSession {
+            connection: Arc::new(
+                TcpConnectionPool::direct(transport, addr)
+                    .with_compression_stats(compression_stats),
+            ),
+            responses: Default::default(),
+            compression,
+            query_defaults,
+            hooks: Default::default(),
+        })
+    }
+
+    /// Sets hooks observing or altering requests of the session.
+    pub fn with_hooks(mut self, hooks: SessionHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Returns statistics of compressed frames received over the connection.
+    pub fn compression_stats(&self) -> Option<&Arc<CompressionStats>> {
+        self.connection.compression_stats()
+    }
+
+    /// Returns new `SessionPager` that can be used for performing paged queries.
+    pub fn paged(
+        &mut self,
+        page_size: i32,
+    ) -> SessionPager<'_, TcpConnectionsManager, DirectSession, TransportTcp> {
+        SessionPager::new(self, page_size)
+    }
+}
+
+impl GetCompressor for DirectSession {
+    fn get_compressor(&self) -> Compression {
+        self.compression
+    }
+}
+
+impl GetQueryDefaults for DirectSession {
+    fn query_defaults(&self) -> QueryDefaults {
+        self.query_defaults
+    }
+}
+
+impl GetSessionHooks for DirectSession {
+    fn hooks(&self) -> &SessionHooks {
+        &self.hooks
+    }
+}
+
+#[async_trait]
+impl GetConnection<TransportTcp, TcpConnectionsManager> for DirectSession {
+    async fn get_connection(&self) -> Option<Arc<TcpConnectionPool>> {
+        Some(self.connection.clone())
+    }
+}
+
+#[async_trait]
+impl ResponseCache for DirectSession {
+    async fn match_or_cache_response(&self, stream_id: StreamId, frame: Frame) -> Option<Frame> {
+        if frame.stream == stream_id {
+            return Some(frame);
+        }
+
+        let mut responses = self.responses.lock().await;
+
+        responses.insert(frame.stream, frame);
+        responses.remove(&stream_id)
+    }
+}
+
+impl QueryExecutor<TransportTcp, TcpConnectionsManager> for DirectSession {}
+
+impl PrepareExecutor<TransportTcp, TcpConnectionsManager> for DirectSession {}
+
+impl ExecExecutor<TransportTcp, TcpConnectionsManager> for DirectSession {}
+
+impl BatchExecutor<TransportTcp, TcpConnectionsManager> for DirectSession {}
+
+impl CDRSSession<TransportTcp, TcpConnectionsManager> for DirectSession {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::test_node::{handshake, Reply, Request, TestNode};
+    use crate::frame::Opcode;
+
+    #[tokio::test]
+    async fn query_over_direct_connection() {
+        let node = TestNode::start().await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let frame = session.query("SELECT * FROM t").await.unwrap();
+
+        assert_eq!(frame.opcode, Opcode::Result);
+        assert!(session.connection.get_pool().is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_are_multiplexed() {
+        // the first query is answered only after the second one
+        let node = TestNode::start_scripted(Arc::new(|request: &Request| {
+            if let Some(reply) = handshake(&request.opcode) {
+                return vec![reply];
+            }
+
+            let rows = |value: u8| vec![0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, value];
+            if request.body.windows(5).any(|window| window == b"first") {
+                vec![Reply::Held(Opcode::Result, rows(1))]
+            } else {
+                vec![Reply::Frame(Opcode::Result, rows(2))]
+            }
+        }))
+        .await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+
+        let (first, second) = tokio::join!(
+            session.query("SELECT first FROM t"),
+            session.query("SELECT second FROM t")
+        );
+        assert_eq!(first.unwrap().body.last(), Some(&1));
+        assert_eq!(second.unwrap().body.last(), Some(&2));
+
+        // both requests were in flight on the only connection at the same time
+        let requests = node.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.connection == 0));
+        assert_ne!(requests[0].stream, requests[1].stream);
+        assert_eq!(session.connection.connections_in_use(), 0);
+    }
+}
@@ -1,29 +1,556 @@
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::fmt;
+use std::io;
+use std::net::{self, SocketAddr};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, timeout_at, Instant};
+
+use crate::cluster::node_health::NodeHealth;
+use crate::cluster::{ConfigUpdate, ConnectionStates, NodeHealthConfig, WaitHistogram};
+use crate::compression::CompressionStats;
+use crate::error;
+use crate::load_balancing::NodeDistance;
+use crate::transport::CDRSTransport;
+
+/// Interval of checking if requests in flight to a drained node have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Number of requests a direct connection carries at the same time, one per stream id.
+const DIRECT_MAX_STREAMS: u32 = 1 << 15;
+
+/// Options a pool is built with, which can be changed at runtime with `Session::update_config`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PoolOptions {
+    pub max_size: u32,
+    pub min_idle: Option<u32>,
+    pub max_lifetime: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub connection_timeout: Duration,
+}
+
+impl PoolOptions {
+    /// Returns a builder of `bb8::Pool` with these options.
+    pub fn builder<M: bb8::ManageConnection>(&self) -> bb8::Builder<M> {
+        bb8::Builder::new()
+            .max_size(self.max_size)
+            .min_idle(self.min_idle)
+            .max_lifetime(self.max_lifetime)
+            .idle_timeout(self.idle_timeout)
+            .connection_timeout(self.connection_timeout)
+    }
+
+    /// Returns options with changes of given update applied, for a pool of a node at given
+    /// distance.
+    pub fn apply(self, update: &ConfigUpdate, distance: NodeDistance) -> Self {
+        let max_size = match distance {
+            NodeDistance::Remote => update.remote_max_size,
+            _ => update.max_size,
+        };
+
+        PoolOptions {
+            max_size: max_size.unwrap_or(self.max_size),
+            min_idle: update.min_idle.unwrap_or(self.min_idle),
+            max_lifetime: update.max_lifetime.unwrap_or(self.max_lifetime),
+            idle_timeout: update.idle_timeout.unwrap_or(self.idle_timeout),
+            connection_timeout: update.connection_timeout.unwrap_or(self.connection_timeout),
+        }
+    }
+}
+
+/// Connections to a node: a pool or a single connection held directly, which is shared by all
+/// requests and not reestablished once closed. Requests on a direct connection have their
+/// responses routed by stream id, see `CDRSTransport::response_routes`.
+enum Connections<M: bb8::ManageConnection> {
+    Pool(RwLock<Arc<bb8::Pool<M>>>),
+    Direct(Box<dyn SharedConnection<M::Connection>>),
+}
+
+/// Connection which can be shared by requests, i.e. one guarded by a `Mutex`. Pooled
+/// connections are only required to be `Send`.
+trait SharedConnection<C>: Send + Sync {
+    fn connection(&self) -> &C;
+}
+
+impl<T: Send> SharedConnection<Mutex<T>> for Mutex<T> {
+    fn connection(&self) -> &Mutex<T> {
+        self
+    }
+}
+
+impl<M: bb8::ManageConnection> fmt::Debug for Connections<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Connections::Pool(pool) => f.debug_tuple("Pool").field(pool).finish(),
+            Connections::Direct(_) => f.write_str("Direct"),
+        }
+    }
+}
+
+/// Connection checked out of a `ConnectionPool` by a request, for exclusive use unless it is
+/// a direct connection.
+pub(crate) enum Checkout<'a, M: bb8::ManageConnection> {
+    // the slot is only held to release it along with the connection
+    Pooled(
+        bb8::PooledConnection<'static, M>,
+        #[allow(dead_code)] Slot<'a>,
+    ),
+    Direct(&'a M::Connection, #[allow(dead_code)] Slot<'a>),
+}
+
+/// Connection of a pool, or a stream of a direct connection, reserved by a checkout, released
+/// when dropped.
+pub(crate) struct Slot<'a>(&'a AtomicU32);
+
+impl<'a> Drop for Slot<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<'a, M: bb8::ManageConnection> Deref for Checkout<'a, M> {
+    type Target = M::Connection;
+
+    fn deref(&self) -> &M::Connection {
+        match self {
+            Checkout::Pooled(connection, _) => connection,
+            Checkout::Direct(connection, _) => connection,
+        }
+    }
+}
 
 /// Generic pool connection that is able to return an
 /// `bb8::Pool` as well as an IP address of a node.
 #[derive(Debug)]
 pub struct ConnectionPool<M: bb8::ManageConnection> {
-    pool: Arc<bb8::Pool<M>>,
+    connections: Connections<M>,
     addr: SocketAddr,
+    distance: NodeDistance,
+    health: NodeHealth,
+    // 0 if unknown
+    max_size: AtomicU32,
+    // connections checked out with `checkout` or `try_checkout`, or requests in flight on
+    // a direct connection
+    checked_out: AtomicU32,
+    connection_states: Option<Arc<ConnectionStates>>,
+    compression_stats: Option<Arc<CompressionStats>>,
+    drained: AtomicBool,
+    wait_times: WaitHistogram,
+    rebuild: Option<(M, SyncMutex<PoolOptions>)>,
 }
 
 impl<M: bb8::ManageConnection> ConnectionPool<M> {
     pub fn new(pool: bb8::Pool<M>, addr: SocketAddr) -> Self {
+        Self::with_distance(pool, addr, NodeDistance::Local)
+    }
+
+    pub fn with_distance(pool: bb8::Pool<M>, addr: SocketAddr, distance: NodeDistance) -> Self {
+        Self::with_health(pool, addr, distance, Default::default())
+    }
+
+    pub fn with_health(
+        pool: bb8::Pool<M>,
+        addr: SocketAddr,
+        distance: NodeDistance,
+        health: NodeHealthConfig,
+    ) -> Self {
+        Self::with_connections(
+            Connections::Pool(RwLock::new(Arc::new(pool))),
+            addr,
+            distance,
+            health,
+        )
+    }
+
+    fn with_connections(
+        connections: Connections<M>,
+        addr: SocketAddr,
+        distance: NodeDistance,
+        health: NodeHealthConfig,
+    ) -> Self {
         ConnectionPool {
-            pool: Arc::new(pool),
+            connections,
             addr,
+            distance,
+            health: NodeHealth::new(health),
+            max_size: AtomicU32::new(0),
+            checked_out: AtomicU32::new(0),
+            connection_states: None,
+            compression_stats: None,
+            drained: AtomicBool::new(false),
+            wait_times: Default::default(),
+            rebuild: None,
         }
     }
 
-    /// Returns reference to underlying `bb8::Pool`.
-    pub fn get_pool(&self) -> Arc<bb8::Pool<M>> {
-        self.pool.clone()
+    /// Sets maximum size the underlying pool was built with, which allows checking if the
+    /// pool is exhausted.
+    pub fn with_max_size(self, max_size: u32) -> Self {
+        self.max_size.store(max_size, Ordering::Relaxed);
+        self
+    }
+
+    /// Sets numbers of connections in each state, as tracked by the connection manager.
+    pub fn with_connection_states(mut self, connection_states: Arc<ConnectionStates>) -> Self {
+        self.connection_states = Some(connection_states);
+        self
     }
 
+    /// Sets statistics of compressed frames received over connections to the node.
+    pub fn with_compression_stats(mut self, compression_stats: Arc<CompressionStats>) -> Self {
+        self.compression_stats = Some(compression_stats);
+        self
+    }
+
+    /// Returns reference to underlying `bb8::Pool`, or `None` if the node is reached over
+    /// a single connection held directly, e.g. by `DirectSession`.
+    pub fn get_pool(&self) -> Option<Arc<bb8::Pool<M>>> {
+        match &self.connections {
+            Connections::Pool(pool) => Some(pool.read().unwrap().clone()),
+            Connections::Direct(_) => None,
+        }
+    }
     /// Return an IP address.
     pub fn get_addr(&self) -> SocketAddr {
         self.addr
     }
+
+    /// Returns node distance the pool was created for.
+    pub fn get_distance(&self) -> NodeDistance {
+        self.distance
+    }
+
+    /// Returns `false` if the node has been marked down after consecutive connection errors or
+    /// drained with `Session::drain_node`.
+    pub fn is_up(&self) -> bool {
+        self.health.is_up() && !self.is_drained()
+    }
+
+    /// Returns `true` if the node replied it is overloaded or bootstrapping recently, within the
+    /// cooldown of its `NodeHealthConfig`. Such nodes are only used if no other node is up.
+    pub fn is_cooling_down(&self) -> bool {
+        self.health.is_cooling_down()
+    }
+
+    /// Returns the number of requests the node rejected because it was overloaded.
+    pub fn overloaded_responses(&self) -> u64 {
+        self.health.overloaded_responses()
+    }
+
+    /// Returns the number of requests the node rejected because it was bootstrapping.
+    pub fn bootstrapping_responses(&self) -> u64 {
+        self.health.bootstrapping_responses()
+    }
+
+    /// Returns `true` if the node has been drained with `Session::drain_node`.
+    pub fn is_drained(&self) -> bool {
+        self.drained.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of connections currently checked out of the pool, i.e. used by
+    /// requests in flight.
+    pub fn connections_in_use(&self) -> u32 {
+        match &self.connections {
+            Connections::Pool(pool) => {
+                let state = pool.read().unwrap().state();
+                state.connections - state.idle_connections
+            }
+            Connections::Direct(_) => (self.checked_out.load(Ordering::Acquire) > 0) as u32,
+        }
+    }
+
+    /// Returns the number of requests which can be sent to the node without waiting for
+    /// a connection, or `None` if the maximum size of the pool is unknown. Each pooled
+    /// connection carries a single request at a time, so every idle or not yet established
+    /// connection provides one free stream. A direct connection provides a stream per stream id
+    /// not used by requests in flight.
+    pub fn available_streams(&self) -> Option<u32> {
+        if let Connections::Direct(_) = self.connections {
+            let in_flight = self.checked_out.load(Ordering::Acquire);
+            return Some(DIRECT_MAX_STREAMS.saturating_sub(in_flight));
+        }
+
+        match self.max_size.load(Ordering::Relaxed) {
+            0 => None,
+            max_size => Some(max_size.saturating_sub(self.connections_in_use())),
+        }
+    }
+
+    /// Returns the histogram of times requests spent waiting for a connection to the node.
+    /// Long waits mean the pool is too small for the load, adding to request latency.
+    pub fn wait_times(&self) -> &WaitHistogram {
+        &self.wait_times
+    }
+
+    /// Returns numbers of connections to the node in each state, if tracked by the connection
+    /// manager.
+    pub fn connection_states(&self) -> Option<&Arc<ConnectionStates>> {
+        self.connection_states.as_ref()
+    }
+
+    /// Returns statistics of compressed frames received over connections to the node, if
+    /// collected by the connection manager.
+    pub fn compression_stats(&self) -> Option<&Arc<CompressionStats>> {
+        self.compression_stats.as_ref()
+    }
+
+    /// Returns `true` if all connections are in use and the pool has reached its maximum
+    /// size. Always `false` if the maximum size is unknown.
+    pub fn is_exhausted(&self) -> bool {
+        match &self.connections {
+            Connections::Pool(pool) => {
+                let state = pool.read().unwrap().state();
+                let max_size = self.max_size.load(Ordering::Relaxed);
+                state.idle_connections == 0 && max_size > 0 && state.connections >= max_size
+            }
+            Connections::Direct(_) => self.available_streams() == Some(0),
+        }
+    }
+
+    pub(crate) fn health(&self) -> &NodeHealth {
+        &self.health
+    }
+
+    pub(crate) fn set_drained(&self, drained: bool) {
+        self.drained.store(drained, Ordering::Relaxed);
+    }
+}
+
+impl<M: bb8::ManageConnection + Clone> ConnectionPool<M> {
+    /// Allows rebuilding the pool with changed options, establishing connections with clones
+    /// of given manager.
+    pub(crate) fn with_rebuild(mut self, manager: M, options: PoolOptions) -> Self {
+        self.rebuild = Some((manager, SyncMutex::new(options)));
+        self
+    }
+
+    /// Replaces the underlying pool by one built with changes of given update applied, if they
+    /// change its options. Requests in flight keep using connections of the previous pool,
+    /// which is closed once they finish. Pools created without options, e.g. by custom code,
+    /// are left unchanged.
+    pub(crate) fn reconfigure(&self, update: &ConfigUpdate) {
+        if let Some((manager, options)) = &self.rebuild {
+            let mut options = options.lock().unwrap();
+            let updated = options.apply(update, self.distance);
+            if updated == *options {
+                return;
+            }
+
+            if let Connections::Pool(pool) = &self.connections {
+                *pool.write().unwrap() =
+                    Arc::new(updated.builder().build_unchecked(manager.clone()));
+            }
+            self.max_size.store(updated.max_size, Ordering::Relaxed);
+            *options = updated;
+        }
+    }
+}
+
+impl<T, M> ConnectionPool<M>
+where
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    /// Creates a node reached over given connection only, without a pool.
+    pub(crate) fn direct(connection: M::Connection, addr: SocketAddr) -> Self {
+        Self::with_connections(
+            Connections::Direct(Box::new(connection)),
+            addr,
+            NodeDistance::Local,
+            Default::default(),
+        )
+        .with_max_size(1)
+    }
+
+    /// Checks a connection out for exclusive use, waiting for one if all are in use. A direct
+    /// connection is shared instead, and fails to check out once it is closed.
+    pub(crate) async fn checkout(&self) -> Result<Checkout<'_, M>, bb8::RunError<error::Error>> {
+        match &self.connections {
+            Connections::Pool(pool) => {
+                self.checked_out.fetch_add(1, Ordering::AcqRel);
+                let slot = Slot(&self.checked_out);
+                let pool = pool.read().unwrap().clone();
+                pool.get_owned()
+                    .await
+                    .map(|connection| Checkout::Pooled(connection, slot))
+            }
+            Connections::Direct(connection) => self.check_out_direct(connection.connection()).await,
+        }
+    }
+
+    /// Checks a connection out like `checkout`, but returns `None` instead of waiting if all
+    /// connections are in use. Checkouts are counted atomically, so concurrent requests cannot
+    /// check out more connections than the maximum size of the pool. The pool can still take
+    /// time to establish a connection, bounded by its connection timeout.
+    pub(crate) async fn try_checkout(
+        &self,
+    ) -> Result<Option<Checkout<'_, M>>, bb8::RunError<error::Error>> {
+        match &self.connections {
+            Connections::Pool(pool) => {
+                let max_size = self.max_size.load(Ordering::Relaxed);
+                let reserved = self.checked_out.fetch_update(
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    |checked_out| {
+                        Some(checked_out + 1).filter(|_| max_size == 0 || checked_out < max_size)
+                    },
+                );
+                if reserved.is_err() {
+                    return Ok(None);
+                }
+
+                let slot = Slot(&self.checked_out);
+                let pool = pool.read().unwrap().clone();
+                pool.get_owned()
+                    .await
+                    .map(|connection| Some(Checkout::Pooled(connection, slot)))
+            }
+            Connections::Direct(_) if self.is_exhausted() => Ok(None),
+            Connections::Direct(connection) => self
+                .check_out_direct(connection.connection())
+                .await
+                .map(Some),
+        }
+    }
+
+    async fn check_out_direct<'a>(
+        &'a self,
+        connection: &'a M::Connection,
+    ) -> Result<Checkout<'a, M>, bb8::RunError<error::Error>> {
+        if connection.lock().await.is_alive() {
+            self.checked_out.fetch_add(1, Ordering::AcqRel);
+            Ok(Checkout::Direct(connection, Slot(&self.checked_out)))
+        } else {
+            Err(bb8::RunError::User(
+                io::Error::new(io::ErrorKind::NotConnected, "Connection is closed").into(),
+            ))
+        }
+    }
+
+    /// Waits until no connection is in use, then closes all idle connections. Closed
+    /// connections are discarded by the pool when returned to it. Fails if connections are
+    /// still in use after given timeout, e.g. by stuck requests or open continuous paging
+    /// streams, leaving them open.
+    pub(crate) async fn drain(&self, timeout: Duration) -> error::Result<()> {
+        let deadline = Instant::now() + timeout;
+        while self.connections_in_use() > 0 {
+            if Instant::now() >= deadline {
+                return Err(error::Error::General(format!(
+                    "Timed out draining node {} with {} connections in use",
+                    self.addr,
+                    self.connections_in_use()
+                )));
+            }
+            sleep(DRAIN_POLL_INTERVAL).await;
+        }
+
+        // requests which picked the node before it was drained can still check connections
+        // out, so waiting for idle ones is bounded by the deadline as well
+        let idle_connections = match &self.connections {
+            Connections::Pool(pool) => pool.read().unwrap().state().idle_connections,
+            Connections::Direct(_) => 1,
+        };
+        let mut connections = vec![];
+        for _ in 0..idle_connections {
+            match timeout_at(deadline, self.checkout()).await {
+                Ok(Ok(connection)) => connections.push(connection),
+                _ => break,
+            }
+        }
+
+        for connection in &connections {
+            let _ = connection.lock().await.close(net::Shutdown::Both).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::TcpConnectionsManager;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn drained_pool_is_not_up() {
+        let manager = TcpConnectionsManager::new("127.0.0.1:9042", Arc::new(NoneAuthenticator));
+        let pool = ConnectionPool::new(
+            bb8::Pool::builder().build_unchecked(manager),
+            "127.0.0.1:9042".parse().unwrap(),
+        );
+        assert!(pool.is_up());
+
+        pool.set_drained(true);
+        pool.drain(Duration::from_secs(1)).await.unwrap();
+        assert!(pool.is_drained());
+        assert!(!pool.is_up());
+        assert_eq!(pool.connections_in_use(), 0);
+
+        pool.set_drained(false);
+        assert!(pool.is_up());
+    }
+
+    #[tokio::test]
+    async fn reconfigured_pool_is_replaced() {
+        let manager = TcpConnectionsManager::new("127.0.0.1:9042", Arc::new(NoneAuthenticator));
+        let options = PoolOptions {
+            max_size: 2,
+            min_idle: Some(0),
+            max_lifetime: None,
+            idle_timeout: None,
+            connection_timeout: Duration::from_secs(1),
+        };
+        let pool = ConnectionPool::with_distance(
+            options.builder().build_unchecked(manager.clone()),
+            "127.0.0.1:9042".parse().unwrap(),
+            NodeDistance::Remote,
+        )
+        .with_max_size(2)
+        .with_rebuild(manager, options);
+
+        let previous = pool.get_pool().unwrap();
+        pool.reconfigure(&ConfigUpdate::new().max_size(8));
+        assert!(Arc::ptr_eq(&previous, &pool.get_pool().unwrap()));
+
+        pool.reconfigure(&ConfigUpdate::new().remote_max_size(4).min_idle(None));
+        assert!(!Arc::ptr_eq(&previous, &pool.get_pool().unwrap()));
+        assert_eq!(pool.max_size.load(Ordering::Relaxed), 4);
+        assert_eq!(
+            *pool.rebuild.as_ref().unwrap().1.lock().unwrap(),
+            PoolOptions {
+                max_size: 4,
+                min_idle: None,
+                ..options
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn try_checkout_does_not_wait() {
+        let node = TestNode::start().await;
+        let addr = node.addr.to_string();
+        let manager = TcpConnectionsManager::new(addr.as_str(), Arc::new(NoneAuthenticator));
+        let options = PoolOptions {
+            max_size: 1,
+            min_idle: Some(0),
+            max_lifetime: None,
+            idle_timeout: None,
+            connection_timeout: Duration::from_secs(30),
+        };
+        let pool = ConnectionPool::new(options.builder().build_unchecked(manager), node.addr)
+            .with_max_size(1);
+
+        let connection = pool.checkout().await.unwrap();
+        let checkout = timeout(Duration::from_secs(1), pool.try_checkout()).await;
+        assert!(matches!(checkout, Ok(Ok(None))));
+
+        drop(connection);
+        assert!(matches!(pool.try_checkout().await, Ok(Some(_))));
+        assert_eq!(pool.checked_out.load(Ordering::Relaxed), 0);
+    }
 }
@@ -2,37 +2,74 @@ use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+mod cached_session;
 #[cfg(feature = "rust-tls")]
 mod config_rustls;
 mod config_tcp;
+mod config_update;
+mod connection_supervisor;
+mod connection_throttle;
+mod direct_session;
 mod generic_connection_pool;
 mod keyspace_holder;
+mod node_health;
 mod pager;
+mod pinned_session;
+mod read_your_writes;
 #[cfg(feature = "rust-tls")]
 mod rustls_connection_pool;
 pub mod session;
+mod session_hooks;
+mod session_registry;
+#[cfg(feature = "tower")]
+mod session_service;
 mod tcp_connection_pool;
+#[cfg(test)]
+pub(crate) mod test_node;
+mod token_map;
+mod wait_histogram;
 
+pub use crate::cluster::cached_session::CachedSession;
 #[cfg(feature = "rust-tls")]
 pub use crate::cluster::config_rustls::{
     ClusterRustlsConfig, NodeRustlsConfig, NodeRustlsConfigBuilder,
 };
 pub use crate::cluster::config_tcp::{ClusterTcpConfig, NodeTcpConfig, NodeTcpConfigBuilder};
+pub use crate::cluster::config_update::ConfigUpdate;
+pub use crate::cluster::connection_supervisor::{
+    ConnectionState, ConnectionStateCell, ConnectionStateListener, ConnectionStates,
+};
+pub use crate::cluster::connection_throttle::ConnectionThrottle;
+pub use crate::cluster::direct_session::DirectSession;
 pub use crate::cluster::keyspace_holder::KeyspaceHolder;
-pub use crate::cluster::pager::{ExecPager, PagerState, QueryPager, SessionPager};
+pub use crate::cluster::node_health::NodeHealthConfig;
+pub use crate::cluster::pager::{
+    ExecPager, PageInfo, PagerState, QueryPager, SessionPager, SnapshotQueryPager,
+};
+pub use crate::cluster::pinned_session::PinnedSession;
+pub use crate::cluster::read_your_writes::ReadYourWritesSession;
 #[cfg(feature = "rust-tls")]
 pub use crate::cluster::rustls_connection_pool::{
-    new_rustls_pool, RustlsConnectionPool, RustlsConnectionsManager,
+    new_rustls_pool, new_rustls_pool_with_distance, RustlsConnectionPool, RustlsConnectionsManager,
 };
+pub use crate::cluster::session_hooks::SessionHooks;
+pub use crate::cluster::session_registry::SessionRegistry;
+#[cfg(feature = "tower")]
+pub use crate::cluster::session_service::SessionService;
 pub use crate::cluster::tcp_connection_pool::{
-    new_tcp_pool, startup, TcpConnectionPool, TcpConnectionsManager,
+    connect_negotiated, new_tcp_pool, new_tcp_pool_with_distance, startup, validate,
+    TcpConnectionPool, TcpConnectionsManager,
 };
-pub(crate) use generic_connection_pool::ConnectionPool;
+pub use crate::cluster::token_map::{ReplicationStrategy, TokenMap, TokenRingNode};
+pub use crate::cluster::wait_histogram::WaitHistogram;
+pub(crate) use connection_supervisor::ConnectionSupervisor;
+pub(crate) use generic_connection_pool::{ConnectionPool, PoolOptions};
+pub(crate) use node_health::report_node_failure;
 
 use crate::compression::Compression;
 use crate::error;
 use crate::frame::{Frame, StreamId};
-use crate::query::{BatchExecutor, ExecExecutor, PrepareExecutor, QueryExecutor};
+use crate::query::{BatchExecutor, ExecExecutor, PrepareExecutor, QueryDefaults, QueryExecutor};
 use crate::transport::CDRSTransport;
 
 /// `GetConnection` trait provides a unified interface for Session to get a connection
@@ -45,6 +82,33 @@ pub trait GetConnection<
 {
     /// Returns connection from a load balancer.
     async fn get_connection(&self) -> Option<Arc<ConnectionPool<M>>>;
+
+    /// Returns connection to a replica of the partition with given routing key in given
+    /// keyspace, for token aware routing. Defaults to `get_connection`.
+    async fn get_routed_connection(
+        &self,
+        _keyspace: &str,
+        _routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>>
+    where
+        Self: Sync,
+    {
+        self.get_connection().await
+    }
+
+    /// Returns connection to the primary replica of the partition with given routing key in
+    /// given keyspace if it is up, e.g. for lightweight transactions, or to another replica
+    /// otherwise. Defaults to `get_routed_connection`.
+    async fn get_primary_replica_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>>
+    where
+        Self: Sync,
+    {
+        self.get_routed_connection(keyspace, routing_key).await
+    }
 }
 
 /// `GetCompressor` trait provides a unified interface for Session to get a compressor
@@ -54,6 +118,22 @@ pub trait GetCompressor {
     fn get_compressor(&self) -> Compression;
 }
 
+/// `GetQueryDefaults` trait provides a unified interface for Session to get defaults for
+/// query parameters and requests.
+pub trait GetQueryDefaults {
+    /// Returns actual query defaults.
+    fn query_defaults(&self) -> QueryDefaults {
+        Default::default()
+    }
+}
+
+/// `GetSessionHooks` trait provides a unified interface for Session to get hooks observing or
+/// altering its requests.
+pub trait GetSessionHooks {
+    /// Returns actual session hooks.
+    fn hooks(&self) -> &SessionHooks;
+}
+
 /// `ResponseCache` caches responses to match them by their stream id to requests.
 #[async_trait]
 pub trait ResponseCache {
@@ -67,6 +147,8 @@ pub trait CDRSSession<
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
 >:
     GetCompressor
+    + GetQueryDefaults
+    + GetSessionHooks
     + GetConnection<T, M>
     + QueryExecutor<T, M>
     + PrepareExecutor<T, M>
@@ -0,0 +1,250 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use crate::cluster::ConnectionPool;
+
+/// Configuration of node health tracking. A node is marked down after a number of consecutive
+/// connection errors, which removes it from query plans, and is probed in the background until
+/// a connection to it can be established again. A node replying it is overloaded or
+/// bootstrapping is deprioritized for a cooldown period, i.e. only used if no other node is
+/// available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeHealthConfig {
+    failure_threshold: u32,
+    probe_interval: Duration,
+    overload_cooldown: Duration,
+}
+
+impl NodeHealthConfig {
+    const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+    const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+    const DEFAULT_OVERLOAD_COOLDOWN: Duration = Duration::from_secs(5);
+
+    pub fn new() -> Self {
+        NodeHealthConfig {
+            failure_threshold: Self::DEFAULT_FAILURE_THRESHOLD,
+            probe_interval: Self::DEFAULT_PROBE_INTERVAL,
+            overload_cooldown: Self::DEFAULT_OVERLOAD_COOLDOWN,
+        }
+    }
+
+    /// Sets the number of consecutive connection errors after which a node is marked down.
+    /// 0 disables marking nodes down.
+    /// Defaults to 3.
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    /// Sets the interval between attempts to connect to a node marked down.
+    /// Defaults to 5 seconds.
+    pub fn probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Sets how long a node is deprioritized after replying it is overloaded or bootstrapping.
+    /// Zero disables deprioritizing nodes.
+    /// Defaults to 5 seconds.
+    pub fn overload_cooldown(mut self, overload_cooldown: Duration) -> Self {
+        self.overload_cooldown = overload_cooldown;
+        self
+    }
+}
+
+impl Default for NodeHealthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Health of a single node, updated with results of requests sent to it.
+#[derive(Debug)]
+pub(crate) struct NodeHealth {
+    config: NodeHealthConfig,
+    failures: AtomicU32,
+    down: AtomicBool,
+    epoch: Instant,
+    // milliseconds since epoch until which the node is deprioritized, 0 if it never was
+    cooldown_until: AtomicU64,
+    overloaded_responses: AtomicU64,
+    bootstrapping_responses: AtomicU64,
+}
+
+impl NodeHealth {
+    pub fn new(config: NodeHealthConfig) -> Self {
+        NodeHealth {
+            config,
+            failures: AtomicU32::new(0),
+            down: AtomicBool::new(false),
+            epoch: Instant::now(),
+            cooldown_until: AtomicU64::new(0),
+            overloaded_responses: AtomicU64::new(0),
+            bootstrapping_responses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        !self.down.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the node replied it is overloaded or bootstrapping within the
+    /// cooldown period.
+    pub fn is_cooling_down(&self) -> bool {
+        let cooldown_until = self.cooldown_until.load(Ordering::Relaxed);
+        cooldown_until != 0 && self.millis_since_epoch() < cooldown_until
+    }
+
+    pub fn overloaded_responses(&self) -> u64 {
+        self.overloaded_responses.load(Ordering::Relaxed)
+    }
+
+    pub fn bootstrapping_responses(&self) -> u64 {
+        self.bootstrapping_responses.load(Ordering::Relaxed)
+    }
+
+    /// Records a reply that the node is overloaded or, if `bootstrapping` is set, still
+    /// bootstrapping, and starts its cooldown. Returns `true` if the node has just started
+    /// cooling down.
+    pub fn report_overloaded(&self, bootstrapping: bool) -> bool {
+        if bootstrapping {
+            self.bootstrapping_responses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.overloaded_responses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if self.config.overload_cooldown == Duration::ZERO {
+            return false;
+        }
+
+        let was_cooling_down = self.is_cooling_down();
+        let cooldown_until =
+            self.millis_since_epoch() + self.config.overload_cooldown.as_millis() as u64;
+        self.cooldown_until
+            .store(cooldown_until.max(1), Ordering::Relaxed);
+        !was_cooling_down
+    }
+
+    fn millis_since_epoch(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    pub fn report_success(&self) {
+        if self.failures.load(Ordering::Relaxed) != 0 {
+            self.failures.store(0, Ordering::Relaxed);
+        }
+
+        if self.down.load(Ordering::Relaxed) {
+            self.down.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a connection error. Returns `true` if the node has just been marked down.
+    pub fn report_failure(&self) -> bool {
+        if self.config.failure_threshold == 0 {
+            return false;
+        }
+
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        failures >= self.config.failure_threshold
+            && self
+                .down
+                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+    }
+}
+
+/// Records a connection error of given node and starts probing it in the background if it has
+/// just been marked down.
+pub(crate) fn report_node_failure<M: bb8::ManageConnection>(node: &Arc<ConnectionPool<M>>) {
+    if node.health().report_failure() {
+        tokio::spawn(probe(
+            Arc::downgrade(node),
+            node.health().config.probe_interval,
+        ));
+    }
+}
+
+async fn probe<M: bb8::ManageConnection>(node: Weak<ConnectionPool<M>>, interval: Duration) {
+    loop {
+        sleep(interval).await;
+
+        // stop probing once the node is removed from the cluster
+        let node = match node.upgrade() {
+            Some(node) => node,
+            None => return,
+        };
+
+        // direct connections are not reestablished
+        let pool = match node.get_pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+
+        if pool.get().await.is_ok() {
+            node.health().report_success();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marked_down_after_threshold() {
+        let health = NodeHealth::new(NodeHealthConfig::new().failure_threshold(2));
+
+        assert!(!health.report_failure());
+        assert!(health.is_up());
+        assert!(health.report_failure());
+        assert!(!health.is_up());
+        assert!(!health.report_failure());
+
+        health.report_success();
+        assert!(health.is_up());
+        assert!(!health.report_failure());
+    }
+
+    #[test]
+    fn disabled_threshold() {
+        let health = NodeHealth::new(NodeHealthConfig::new().failure_threshold(0));
+
+        for _ in 0..10 {
+            assert!(!health.report_failure());
+        }
+        assert!(health.is_up());
+    }
+
+    #[test]
+    fn cooldown_after_overload() {
+        let health = NodeHealth::new(NodeHealthConfig::new());
+
+        assert!(!health.is_cooling_down());
+        assert!(health.report_overloaded(false));
+        assert!(health.is_cooling_down());
+        assert!(!health.report_overloaded(true));
+        assert!(health.is_up());
+        assert_eq!(health.overloaded_responses(), 1);
+        assert_eq!(health.bootstrapping_responses(), 1);
+
+        let health = NodeHealth::new(NodeHealthConfig::new().overload_cooldown(Duration::ZERO));
+        assert!(!health.report_overloaded(false));
+        assert!(!health.is_cooling_down());
+        assert_eq!(health.overloaded_responses(), 1);
+    }
+
+    #[tokio::test]
+    async fn cooldown_expires() {
+        let health =
+            NodeHealth::new(NodeHealthConfig::new().overload_cooldown(Duration::from_millis(20)));
+
+        health.report_overloaded(false);
+        assert!(health.is_cooling_down());
+        sleep(Duration::from_millis(40)).await;
+        assert!(!health.is_cooling_down());
+    }
+}
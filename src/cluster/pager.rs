@@ -1,11 +1,20 @@
+use std::cmp::min;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::time::sleep;
+use uuid::Uuid;
 
 use crate::cluster::CDRSSession;
 use crate::consistency::Consistency;
 use crate::error;
 use crate::frame::frame_result::{RowsMetadata, RowsMetadataFlag};
-use crate::query::{PreparedQuery, QueryParams, QueryParamsBuilder, QueryValues};
+use crate::frame::Frame;
+use crate::query::{
+    with_type_registry, PreparedQuery, QueryFlags, QueryParams, QueryParamsBuilder, QueryValues,
+    WriteTimestamp,
+};
 use crate::transport::CDRSTransport;
 use crate::types::rows::Row;
 use crate::types::CBytes;
@@ -65,6 +74,9 @@ impl<
             query,
             qv: qp.values,
             consistency: qp.consistency,
+            max_retries: QueryPager::<Q, Self>::DEFAULT_MAX_RETRIES,
+            with_tracing: false,
+            page_info: None,
         }
     }
 
@@ -91,6 +103,41 @@ impl<
         self.query_with_pager_state_params(query, PagerState::new(), qp)
     }
 
+    /// Returns a pager which sends the same client timestamp with every page and retries pages
+    /// failed due to timeouts or IO errors, waiting longer before each further retry. The
+    /// timestamp is taken from `qp` or, if not set, from the current time. Other parameters of
+    /// `qp` are sent with every page as they are, except for page size and paging state.
+    ///
+    /// Note that Cassandra does not support reading at a given point in time, so the timestamp
+    /// does not guarantee a consistent snapshot - rows written between pages can still be
+    /// visible on subsequent pages. Pinning only ensures every page request is issued with
+    /// the same timestamp, which is useful for export jobs correlating their reads.
+    pub fn query_snapshot<Q>(
+        &'a mut self,
+        query: Q,
+        qp: QueryParams,
+    ) -> SnapshotQueryPager<'a, Q, SessionPager<'a, M, S, T>>
+    where
+        Q: ToString,
+    {
+        let timestamp = qp
+            .timestamp
+            .unwrap_or_else(|| WriteTimestamp::now().as_micros());
+
+        SnapshotQueryPager {
+            pager: self,
+            pager_state: PagerState::new(),
+            query,
+            params: qp,
+            timestamp,
+            max_retries: SnapshotQueryPager::<Q, Self>::DEFAULT_MAX_RETRIES,
+            retry_delay: SnapshotQueryPager::<Q, Self>::DEFAULT_RETRY_DELAY,
+            max_retry_delay: SnapshotQueryPager::<Q, Self>::DEFAULT_MAX_RETRY_DELAY,
+            with_tracing: false,
+            page_info: None,
+        }
+    }
+
     pub fn exec_with_pager_state(
         &'a mut self,
         query: &'a PreparedQuery,
@@ -100,6 +147,9 @@ impl<
             pager: self,
             pager_state: state,
             query,
+            max_retries: ExecPager::<Self>::DEFAULT_MAX_RETRIES,
+            with_tracing: false,
+            page_info: None,
         }
     }
 
@@ -111,12 +161,80 @@ impl<
     }
 }
 
+/// Execution info of a page fetched by a pager, e.g. to debug inconsistent paging or to measure
+/// per-page latencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageInfo {
+    /// Index of the page among pages fetched by the pager, starting at 0.
+    pub index: usize,
+    /// Address of the node which coordinated the page request.
+    pub coordinator: Option<SocketAddr>,
+    /// Consistency the page was read with.
+    pub consistency: Consistency,
+    /// Tracing id of the page request, if tracing was requested.
+    pub tracing_id: Option<Uuid>,
+    /// Time spent fetching the page, including retries.
+    pub elapsed: Duration,
+}
+
+impl PageInfo {
+    fn new(
+        previous: Option<&PageInfo>,
+        frame: &Frame,
+        consistency: Consistency,
+        started: Instant,
+    ) -> Self {
+        PageInfo {
+            index: previous.map_or(0, |previous| previous.index + 1),
+            coordinator: frame.coordinator,
+            consistency,
+            tracing_id: frame.tracing_id,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+/// Returns `true` if sending a request failed due to a broken connection, so the request can
+/// be sent again to another node.
+fn is_connection_failure(error: &error::Error) -> bool {
+    matches!(
+        error,
+        error::Error::Io(_) | error::Error::ConnectionPoisoned(_)
+    )
+}
+
+/// Pager of a query. A page failed due to a broken connection is requested again with the same
+/// paging state, which sends it to the next node of the load balancing strategy.
 pub struct QueryPager<'a, Q: ToString, P: 'a> {
     pager: &'a mut P,
     pager_state: PagerState,
     query: Q,
     qv: Option<QueryValues>,
     consistency: Consistency,
+    max_retries: usize,
+    with_tracing: bool,
+    page_info: Option<PageInfo>,
+}
+
+impl<'a, Q: ToString, P: 'a> QueryPager<'a, Q, P> {
+    const DEFAULT_MAX_RETRIES: usize = 3;
+
+    /// Sets how many times a single page is retried after connection failures. Defaults to 3.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets whether page requests are traced. Defaults to false.
+    pub fn tracing(mut self, with_tracing: bool) -> Self {
+        self.with_tracing = with_tracing;
+        self
+    }
+
+    /// Returns execution info of the last fetched page.
+    pub fn page_info(&self) -> Option<&PageInfo> {
+        self.page_info.as_ref()
+    }
 }
 
 impl<
@@ -128,24 +246,37 @@ impl<
     > QueryPager<'a, Q, SessionPager<'a, M, S, T>>
 {
     pub async fn next(&mut self) -> error::Result<Vec<Row>> {
-        let mut params = QueryParamsBuilder::new()
-            .consistency(self.consistency)
-            .page_size(self.pager.page_size);
+        let started = Instant::now();
+        let mut retries = 0;
 
-        if let Some(qv) = &self.qv {
-            params = params.values(qv.clone());
-        }
-        if let Some(cursor) = &self.pager_state.cursor {
-            params = params.paging_state(cursor.clone());
-        }
-        let query = self.query.to_string();
+        let frame = loop {
+            let mut params = QueryParamsBuilder::new()
+                .consistency(self.consistency)
+                .page_size(self.pager.page_size);
+
+            if let Some(qv) = &self.qv {
+                params = params.values(qv.clone());
+            }
+            if let Some(cursor) = &self.pager_state.cursor {
+                params = params.paging_state(cursor.clone());
+            }
+            let query = self.query.to_string();
+
+            match self
+                .pager
+                .session
+                .query_with_params_tw(query, params.finalize(), self.with_tracing, false)
+                .await
+            {
+                Err(error) if retries < self.max_retries && is_connection_failure(&error) => {
+                    retries += 1;
+                    warn!("Retrying page after connection failure: {}", error);
+                }
+                result => break result?,
+            }
+        };
 
-        let body = self
-            .pager
-            .session
-            .query_with_params(query, params.finalize())
-            .await
-            .and_then(|frame| frame.get_body())?;
+        let body = frame.get_body()?;
 
         let metadata_res: error::Result<RowsMetadata> = body
             .as_rows_metadata()
@@ -155,7 +286,14 @@ impl<
         self.pager_state.has_more_pages =
             Some(RowsMetadataFlag::has_has_more_pages(metadata.flags));
         self.pager_state.cursor = metadata.paging_state;
+        self.page_info = Some(PageInfo::new(
+            self.page_info.as_ref(),
+            &frame,
+            self.consistency,
+            started,
+        ));
         body.into_rows()
+            .map(|rows| with_type_registry(rows, self.pager.session.hooks().type_registry.as_ref()))
             .ok_or_else(|| "Pager query should yield a vector of rows".into())
     }
 
@@ -170,10 +308,199 @@ impl<
     }
 }
 
+/// Pager which pins client timestamp for all pages and retries failed pages. See
+/// `SessionPager::query_snapshot` for consistency caveats.
+///
+/// Retries of a page are delayed by a backoff which starts at `retry_delay` and doubles with
+/// each further retry, up to `max_retry_delay`.
+pub struct SnapshotQueryPager<'a, Q: ToString, P: 'a> {
+    pager: &'a mut P,
+    pager_state: PagerState,
+    query: Q,
+    params: QueryParams,
+    timestamp: i64,
+    max_retries: usize,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+    with_tracing: bool,
+    page_info: Option<PageInfo>,
+}
+
+impl<'a, Q: ToString, P: 'a> SnapshotQueryPager<'a, Q, P> {
+    const DEFAULT_MAX_RETRIES: usize = 3;
+    const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+    const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+    /// Sets how many times a single page is retried. Defaults to 3.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry of a page. Defaults to 100 milliseconds.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sets the upper bound of the delay growing with further retries of a page.
+    /// Defaults to 5 seconds.
+    pub fn max_retry_delay(mut self, max_retry_delay: Duration) -> Self {
+        self.max_retry_delay = max_retry_delay;
+        self
+    }
+
+    /// Sets whether page requests are traced. Defaults to false.
+    pub fn tracing(mut self, with_tracing: bool) -> Self {
+        self.with_tracing = with_tracing;
+        self
+    }
+
+    /// Returns client timestamp sent with every page.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Returns execution info of the last fetched page.
+    pub fn page_info(&self) -> Option<&PageInfo> {
+        self.page_info.as_ref()
+    }
+
+    fn backoff(&self, retries: usize) -> Duration {
+        let backoff = self
+            .retry_delay
+            .checked_mul(1 << min(retries, 16))
+            .unwrap_or(self.max_retry_delay);
+        min(backoff, self.max_retry_delay)
+    }
+
+    fn is_retryable(error: &error::Error) -> bool {
+        match error {
+            error::Error::Io(_) => true,
+            error::Error::Server(error) => {
+                error.is_read_timeout() || error.is_unavailable() || error.is_overloaded()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<
+        'a,
+        Q: ToString,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + Sync + Send,
+    > SnapshotQueryPager<'a, Q, SessionPager<'a, M, S, T>>
+{
+    pub async fn next(&mut self) -> error::Result<Vec<Row>> {
+        let started = Instant::now();
+        let mut retries = 0;
+
+        loop {
+            let mut params = self.params.clone();
+            params.flags.retain(|flag| {
+                !matches!(
+                    flag,
+                    QueryFlags::PageSize
+                        | QueryFlags::WithPagingState
+                        | QueryFlags::WithDefaultTimestamp
+                )
+            });
+            params.page_size = Some(self.pager.page_size);
+            params.flags.push(QueryFlags::PageSize);
+            params.paging_state = self.pager_state.cursor.clone();
+            if params.paging_state.is_some() {
+                params.flags.push(QueryFlags::WithPagingState);
+            }
+            params.timestamp = Some(self.timestamp);
+            params.flags.push(QueryFlags::WithDefaultTimestamp);
+
+            let result = self
+                .pager
+                .session
+                .query_with_params_tw(self.query.to_string(), params, self.with_tracing, false)
+                .await;
+
+            let frame = match result {
+                Err(error) if retries < self.max_retries && Self::is_retryable(&error) => {
+                    let delay = self.backoff(retries);
+                    retries += 1;
+                    warn!(
+                        "Retrying snapshot page in {:?} after error: {}",
+                        delay, error
+                    );
+                    sleep(delay).await;
+                    continue;
+                }
+                result => result?,
+            };
+
+            let body = frame.get_body()?;
+            let metadata_res: error::Result<RowsMetadata> = body
+                .as_rows_metadata()
+                .ok_or_else(|| "Pager query should yield a vector of rows".into());
+            let metadata = metadata_res?;
+
+            self.pager_state.has_more_pages =
+                Some(RowsMetadataFlag::has_has_more_pages(metadata.flags));
+            self.pager_state.cursor = metadata.paging_state;
+            self.page_info = Some(PageInfo::new(
+                self.page_info.as_ref(),
+                &frame,
+                self.params.consistency,
+                started,
+            ));
+            return body
+                .into_rows()
+                .map(|rows| {
+                    with_type_registry(rows, self.pager.session.hooks().type_registry.as_ref())
+                })
+                .ok_or_else(|| "Pager query should yield a vector of rows".into());
+        }
+    }
+
+    pub fn has_more(&self) -> bool {
+        self.pager_state.has_more_pages.unwrap_or(false)
+    }
+
+    /// This method returns a copy of pager state so
+    /// the state may be used later for continuing paging.
+    pub fn pager_state(&self) -> PagerState {
+        self.pager_state.clone()
+    }
+}
+
+/// Pager of a prepared query. Pages are retried after connection failures like by
+/// `QueryPager`.
 pub struct ExecPager<'a, P: 'a> {
     pager: &'a mut P,
     pager_state: PagerState,
     query: &'a PreparedQuery,
+    max_retries: usize,
+    with_tracing: bool,
+    page_info: Option<PageInfo>,
+}
+
+impl<'a, P: 'a> ExecPager<'a, P> {
+    const DEFAULT_MAX_RETRIES: usize = 3;
+
+    /// Sets how many times a single page is retried after connection failures. Defaults to 3.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets whether page requests are traced. Defaults to false.
+    pub fn tracing(mut self, with_tracing: bool) -> Self {
+        self.with_tracing = with_tracing;
+        self
+    }
+
+    /// Returns execution info of the last fetched page.
+    pub fn page_info(&self) -> Option<&PageInfo> {
+        self.page_info.as_ref()
+    }
 }
 
 impl<
@@ -184,17 +511,32 @@ impl<
     > ExecPager<'a, SessionPager<'a, M, S, T>>
 {
     pub async fn next(&mut self) -> error::Result<Vec<Row>> {
-        let mut params = QueryParamsBuilder::new().page_size(self.pager.page_size);
-        if self.pager_state.cursor.is_some() {
-            params = params.paging_state(self.pager_state.cursor.clone().unwrap());
-        }
+        let started = Instant::now();
+        let mut retries = 0;
+
+        let (frame, consistency) = loop {
+            let mut params = QueryParamsBuilder::new().page_size(self.pager.page_size);
+            if self.pager_state.cursor.is_some() {
+                params = params.paging_state(self.pager_state.cursor.clone().unwrap());
+            }
+            let params = params.finalize();
+            let consistency = params.consistency;
 
-        let body = self
-            .pager
-            .session
-            .exec_with_params(self.query, params.finalize())
-            .await
-            .and_then(|frame| frame.get_body())?;
+            match self
+                .pager
+                .session
+                .exec_with_params_tw(self.query, params, self.with_tracing, false)
+                .await
+            {
+                Err(error) if retries < self.max_retries && is_connection_failure(&error) => {
+                    retries += 1;
+                    warn!("Retrying page after connection failure: {}", error);
+                }
+                result => break (result?, consistency),
+            }
+        };
+
+        let body = frame.get_body()?;
 
         let metadata_res: error::Result<RowsMetadata> = body
             .as_rows_metadata()
@@ -204,7 +546,14 @@ impl<
         self.pager_state.has_more_pages =
             Some(RowsMetadataFlag::has_has_more_pages(metadata.flags));
         self.pager_state.cursor = metadata.paging_state;
+        self.page_info = Some(PageInfo::new(
+            self.page_info.as_ref(),
+            &frame,
+            consistency,
+            started,
+        ));
         body.into_rows()
+            .map(|rows| with_type_registry(rows, self.pager.session.hooks().type_registry.as_ref()))
             .ok_or_else(|| "Pager query should yield a vector of rows".into())
     }
 
@@ -252,3 +601,185 @@ impl PagerState {
         self.cursor.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::session::{new, Session};
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::{
+        ClusterTcpConfig, DirectSession, NodeTcpConfigBuilder, TcpConnectionPool,
+    };
+    use crate::consistency::Consistency;
+    use crate::frame::{AsByte, AsBytes, Opcode, Version};
+    use crate::load_balancing::RoundRobin;
+    use crate::query::{QueryFlags, QueryParamsBuilder, QueryValues, PROXY_EXECUTE_PAYLOAD_KEY};
+    use crate::types::{to_short, CBytes, CString, CStringLong};
+
+    #[tokio::test]
+    async fn pager_retries_page_after_connection_failure() {
+        // the first connection is closed once it receives a query
+        async fn serve_dropping_once(listener: TcpListener) {
+            let mut dropped = false;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut header = [0; 9];
+
+                while stream.read_exact(&mut header).await.is_ok() {
+                    let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+                    let mut body = vec![0; length as usize];
+                    stream.read_exact(&mut body).await.unwrap();
+
+                    let (opcode, body) = match Opcode::from(header[4]) {
+                        Opcode::Startup => (Opcode::Ready, vec![]),
+                        Opcode::Options => (Opcode::Supported, vec![0, 0]),
+                        _ if !dropped => {
+                            dropped = true;
+                            break;
+                        }
+                        // empty rows result
+                        _ => (
+                            Opcode::Result,
+                            vec![0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                        ),
+                    };
+
+                    let mut response = vec![
+                        Version::Response.as_byte(),
+                        0,
+                        header[2],
+                        header[3],
+                        opcode.as_byte(),
+                    ];
+                    response.extend_from_slice(&(body.len() as u32).to_be_bytes());
+                    response.extend_from_slice(&body);
+                    if stream.write_all(&response).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_dropping_once(listener));
+
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .build()]);
+        let mut session: Session<RoundRobin<TcpConnectionPool>> =
+            new(&config, RoundRobin::new()).await.unwrap();
+        let mut pager = session.paged(10);
+        let mut query_pager = pager.query("SELECT * FROM t");
+
+        assert!(query_pager.next().await.unwrap().is_empty());
+        assert!(!query_pager.has_more());
+    }
+
+    #[tokio::test]
+    async fn pager_page_info() {
+        let node = TestNode::start_with(empty_rows).await;
+        let mut session = DirectSession::connect(node.addr).await.unwrap();
+        let mut pager = session.paged(10);
+        let mut query_pager = pager.query_with_param(
+            "SELECT * FROM t",
+            QueryParamsBuilder::new()
+                .consistency(Consistency::Quorum)
+                .finalize(),
+        );
+        assert!(query_pager.page_info().is_none());
+
+        query_pager.next().await.unwrap();
+        query_pager.next().await.unwrap();
+
+        let page_info = query_pager.page_info().unwrap();
+        assert_eq!(page_info.index, 1);
+        assert_eq!(page_info.coordinator, Some(node.addr));
+        assert_eq!(page_info.consistency, Consistency::Quorum);
+        assert_eq!(page_info.tracing_id, None);
+    }
+
+    fn empty_rows(_: Opcode, _: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+        Some((
+            Opcode::Result,
+            vec![0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ))
+    }
+
+    #[tokio::test]
+    async fn snapshot_pager_keeps_query_params() {
+        let node = TestNode::start_with(empty_rows).await;
+        let mut session = DirectSession::connect(node.addr).await.unwrap();
+        let mut pager = session.paged(10);
+        let params = QueryParamsBuilder::new()
+            .flags(vec![QueryFlags::WithSerialConsistency])
+            .consistency(Consistency::Quorum)
+            .values(QueryValues::from(vec![1]))
+            .serial_consistency(Consistency::LocalSerial)
+            .timestamp(42)
+            .execute_as("reader")
+            .finalize();
+        let mut query_pager = pager.query_snapshot("SELECT * FROM t", params);
+        query_pager.next().await.unwrap();
+
+        let mut expected = to_short(1);
+        expected.extend(CString::new(PROXY_EXECUTE_PAYLOAD_KEY.to_string()).as_bytes());
+        expected.extend(CBytes::new(b"reader".to_vec()).as_bytes());
+        expected.extend(CStringLong::new("SELECT * FROM t".to_string()).as_bytes());
+        let expected_params = QueryParamsBuilder::new()
+            .flags(vec![
+                QueryFlags::WithSerialConsistency,
+                QueryFlags::WithDefaultTimestamp,
+            ])
+            .consistency(Consistency::Quorum)
+            .values(QueryValues::from(vec![1]))
+            .page_size(10)
+            .serial_consistency(Consistency::LocalSerial)
+            .timestamp(42)
+            .finalize();
+        expected.extend(expected_params.as_bytes());
+
+        assert_eq!(node.requests()[0].body, expected);
+    }
+
+    #[tokio::test]
+    async fn snapshot_pager_backs_off_between_retries() {
+        static QUERIES: AtomicUsize = AtomicUsize::new(0);
+
+        // the first two pages are rejected as overloaded
+        fn overloaded_twice(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            if QUERIES.fetch_add(1, Ordering::SeqCst) < 2 {
+                let mut body = vec![0, 0, 0x10, 0x01];
+                body.extend(CString::new("overloaded".to_string()).as_bytes());
+                return Some((Opcode::Error, body));
+            }
+            empty_rows(opcode, body)
+        }
+
+        let node = TestNode::start_with(overloaded_twice).await;
+        let mut session = DirectSession::connect(node.addr).await.unwrap();
+        let mut pager = session.paged(10);
+        let mut query_pager = pager
+            .query_snapshot("SELECT * FROM t", Default::default())
+            .retry_delay(Duration::from_millis(50))
+            .max_retry_delay(Duration::from_millis(120));
+
+        assert_eq!(query_pager.backoff(0), Duration::from_millis(50));
+        assert_eq!(query_pager.backoff(1), Duration::from_millis(100));
+        assert_eq!(query_pager.backoff(2), Duration::from_millis(120));
+        assert_eq!(query_pager.backoff(usize::MAX), Duration::from_millis(120));
+
+        let started = Instant::now();
+        assert!(query_pager.next().await.unwrap().is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(node.requests().len(), 3);
+    }
+}
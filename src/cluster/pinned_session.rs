@@ -0,0 +1,182 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::cluster::{
+    CDRSSession, ConnectionPool, GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks,
+    ResponseCache, SessionHooks,
+};
+use crate::compression::Compression;
+use crate::error;
+use crate::frame::{Frame, StreamId};
+use crate::query::{BatchExecutor, ExecExecutor, PrepareExecutor, QueryDefaults, QueryExecutor};
+use crate::transport::CDRSTransport;
+
+/// Session handle which sends all requests to the same node, e.g. to run a series of
+/// lightweight transactions through a single coordinator. The node is picked by the
+/// underlying session's load balancer on the first request and kept until the handle is
+/// dropped. If the node is marked down, another one is picked and pinned.
+pub struct PinnedSession<'a, S, M: bb8::ManageConnection> {
+    session: &'a S,
+    node: Mutex<Option<Arc<ConnectionPool<M>>>>,
+}
+
+impl<'a, S, M: bb8::ManageConnection> PinnedSession<'a, S, M> {
+    pub(crate) fn new(session: &'a S) -> Self {
+        PinnedSession {
+            session,
+            node: Mutex::new(None),
+        }
+    }
+
+    /// Returns the node requests are currently sent to, if any was picked yet.
+    pub async fn pinned_node(&self) -> Option<Arc<ConnectionPool<M>>> {
+        self.node.lock().await.clone()
+    }
+}
+
+impl<'a, S: GetCompressor, M: bb8::ManageConnection> GetCompressor for PinnedSession<'a, S, M> {
+    fn get_compressor(&self) -> Compression {
+        self.session.get_compressor()
+    }
+}
+
+impl<'a, S: GetQueryDefaults, M: bb8::ManageConnection> GetQueryDefaults
+    for PinnedSession<'a, S, M>
+{
+    fn query_defaults(&self) -> QueryDefaults {
+        self.session.query_defaults()
+    }
+}
+
+impl<'a, S: GetSessionHooks, M: bb8::ManageConnection> GetSessionHooks for PinnedSession<'a, S, M> {
+    fn hooks(&self) -> &SessionHooks {
+        self.session.hooks()
+    }
+}
+
+#[async_trait]
+impl<
+        'a,
+        T: CDRSTransport + Send + Sync + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: GetConnection<T, M> + Sync,
+    > GetConnection<T, M> for PinnedSession<'a, S, M>
+{
+    async fn get_connection(&self) -> Option<Arc<ConnectionPool<M>>> {
+        let mut node = self.node.lock().await;
+        match node.as_ref() {
+            Some(node) if node.is_up() => Some(node.clone()),
+            _ => {
+                *node = self.session.get_connection().await;
+                node.clone()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, S: ResponseCache + Sync, M: bb8::ManageConnection> ResponseCache
+    for PinnedSession<'a, S, M>
+{
+    async fn match_or_cache_response(&self, stream_id: StreamId, frame: Frame) -> Option<Frame> {
+        self.session.match_or_cache_response(stream_id, frame).await
+    }
+}
+
+impl<
+        'a,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > QueryExecutor<T, M> for PinnedSession<'a, S, M>
+{
+}
+
+impl<
+        'a,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > PrepareExecutor<T, M> for PinnedSession<'a, S, M>
+{
+}
+
+impl<
+        'a,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > ExecExecutor<T, M> for PinnedSession<'a, S, M>
+{
+}
+
+impl<
+        'a,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > BatchExecutor<T, M> for PinnedSession<'a, S, M>
+{
+}
+
+impl<
+        'a,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > CDRSSession<T, M> for PinnedSession<'a, S, M>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::session::{new, Session};
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::{ClusterTcpConfig, NodeTcpConfigBuilder, TcpConnectionPool};
+    use crate::frame::Opcode;
+    use crate::load_balancing::RoundRobin;
+
+    #[tokio::test]
+    async fn requests_reuse_pinned_connection() {
+        let nodes = vec![TestNode::start().await, TestNode::start().await];
+        let config = ClusterTcpConfig::new(
+            nodes
+                .iter()
+                .map(|node| {
+                    NodeTcpConfigBuilder::new(node.addr, Arc::new(NoneAuthenticator)).build()
+                })
+                .collect(),
+        );
+        let session: Session<RoundRobin<TcpConnectionPool>> =
+            new(&config, RoundRobin::new()).await.unwrap();
+
+        // connection manager of pinned sessions is inferred from the load balancer
+        let pinned = session.pinned();
+        assert!(pinned.pinned_node().await.is_none());
+
+        let mut coordinators = vec![];
+        for _ in 0..4 {
+            let frame = pinned.query("SELECT * FROM t").await.unwrap();
+            coordinators.push(frame.coordinator.unwrap());
+        }
+
+        let pinned_node = pinned.pinned_node().await.unwrap();
+        assert!(coordinators
+            .iter()
+            .all(|addr| *addr == pinned_node.get_addr()));
+
+        let requests = nodes
+            .iter()
+            .find(|node| node.addr == pinned_node.get_addr())
+            .unwrap()
+            .requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests
+            .iter()
+            .all(|request| request.opcode == Opcode::Query
+                && request.connection == requests[0].connection));
+    }
+}
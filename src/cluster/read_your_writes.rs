@@ -0,0 +1,487 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::cluster::cached_session::Statement;
+use crate::cluster::{
+    CDRSSession, ConnectionPool, GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks,
+    ResponseCache, SessionHooks,
+};
+use crate::compression::Compression;
+use crate::consistency::Consistency;
+use crate::error;
+use crate::frame::frame_batch::BatchQuerySubj;
+use crate::frame::{Frame, StreamId};
+use crate::query::{
+    BatchExecutor, ExecExecutor, PrepareExecutor, PreparedQuery, QueryBatch, QueryDefaults,
+    QueryExecutor, QueryParams, TimestampGenerator, WriteTimestamp,
+};
+use crate::transport::CDRSTransport;
+
+/// Read-your-writes guarantee in front of a session. Writes executed through it are stamped
+/// with client timestamps, which are remembered per partition after the writes succeed. Reads
+/// of a partition written within the window, measured from the write timestamp, are upgraded
+/// to a stronger consistency, so they see the writes even if these were applied by a subset of
+/// replicas so far. Other reads keep their consistency.
+///
+/// Partitions are identified by tables and routing keys of prepared statements, so only
+/// executions of prepared statements binding the whole partition key, also in batches, are
+/// tracked. Other writes can be reported with `record_write`. The upgraded consistency only
+/// guarantees reading the writes if the two overlap, e.g. `LocalQuorum` for both.
+pub struct ReadYourWritesSession<S> {
+    session: S,
+    window: Duration,
+    consistency: Consistency,
+    max_partitions: usize,
+    generator: TimestampGenerator,
+    writes: std::sync::Mutex<Writes>,
+}
+
+#[derive(Debug, Default)]
+struct Writes {
+    // write timestamp and last use of each partition
+    partitions: HashMap<(String, Vec<u8>), (i64, u64)>,
+    last_use: u64,
+}
+
+impl<S> ReadYourWritesSession<S> {
+    const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+    const DEFAULT_MAX_PARTITIONS: usize = 10_000;
+
+    pub fn new(session: S) -> Self {
+        ReadYourWritesSession {
+            session,
+            window: Self::DEFAULT_WINDOW,
+            consistency: Consistency::LocalQuorum,
+            max_partitions: Self::DEFAULT_MAX_PARTITIONS,
+            generator: TimestampGenerator::new(),
+            writes: Default::default(),
+        }
+    }
+
+    /// Sets how long after a write reads of its partition are upgraded.
+    /// Defaults to 5 seconds.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the consistency reads of recently written partitions are upgraded to. Reads with
+    /// a consistency which requires a quorum or more are left unchanged.
+    /// Defaults to `LocalQuorum`.
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Sets the maximum number of remembered partitions. Once reached, the least recently used
+    /// partition is forgotten.
+    /// Defaults to 10000.
+    pub fn max_partitions(mut self, max_partitions: usize) -> Self {
+        self.max_partitions = max_partitions.max(1);
+        self
+    }
+
+    /// Returns the underlying session.
+    pub fn session(&self) -> &S {
+        &self.session
+    }
+
+    /// Remembers a write of a partition of given table with given routing key (see
+    /// `RoutingKey`) and client timestamp in microseconds, e.g. of a write bypassing the
+    /// session.
+    pub fn record_write(&self, table: &str, routing_key: &[u8], timestamp: i64) {
+        let mut writes = self.writes.lock().unwrap();
+        writes.last_use += 1;
+        let last_use = writes.last_use;
+        let key = (table.to_string(), routing_key.to_vec());
+
+        if writes.partitions.len() >= self.max_partitions && !writes.partitions.contains_key(&key) {
+            let least_recent = writes
+                .partitions
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recent) = least_recent {
+                writes.partitions.remove(&least_recent);
+            }
+        }
+
+        let entry = writes
+            .partitions
+            .entry(key)
+            .or_insert((timestamp, last_use));
+        *entry = (entry.0.max(timestamp), last_use);
+    }
+
+    /// Returns `true` if a partition of given table with given routing key was written within
+    /// the window.
+    pub fn is_recently_written(&self, table: &str, routing_key: &[u8]) -> bool {
+        let mut writes = self.writes.lock().unwrap();
+        if writes.partitions.is_empty() {
+            return false;
+        }
+
+        writes.last_use += 1;
+        let last_use = writes.last_use;
+        let key = (table.to_string(), routing_key.to_vec());
+        match writes.partitions.get_mut(&key) {
+            Some((timestamp, used)) => {
+                let elapsed = WriteTimestamp::now().as_micros() - *timestamp;
+                if elapsed < self.window.as_micros() as i64 {
+                    *used = last_use;
+                    true
+                } else {
+                    writes.partitions.remove(&key);
+                    false
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets all remembered writes.
+    pub fn clear(&self) {
+        self.writes.lock().unwrap().partitions.clear();
+    }
+
+    fn upgrade(&self, consistency: &mut Consistency) {
+        if matches!(
+            consistency,
+            Consistency::Any
+                | Consistency::One
+                | Consistency::Two
+                | Consistency::Three
+                | Consistency::LocalOne
+        ) {
+            *consistency = self.consistency;
+        }
+    }
+
+    /// Returns the timestamp generator of the session, or an own one if it has none.
+    fn generator(&self) -> &TimestampGenerator
+    where
+        S: GetSessionHooks,
+    {
+        self.session
+            .hooks()
+            .timestamp_generator
+            .as_deref()
+            .unwrap_or(&self.generator)
+    }
+}
+
+/// Returns the table and routing key of the partition a prepared query addresses with given
+/// parameters, along with `true` for reads and `false` for writes.
+fn partition(prepared: &PreparedQuery, params: &QueryParams) -> Option<(String, Vec<u8>, bool)> {
+    let (table, is_read) = match Statement::parse(&prepared.query)? {
+        Statement::Select(table) => (table, true),
+        Statement::Write(table) => (table, false),
+    };
+
+    let routing_key = prepared.routing_key(params.values.as_ref()?)?;
+    Some((table, routing_key, is_read))
+}
+
+impl<S: GetCompressor> GetCompressor for ReadYourWritesSession<S> {
+    fn get_compressor(&self) -> Compression {
+        self.session.get_compressor()
+    }
+}
+
+impl<S: GetQueryDefaults> GetQueryDefaults for ReadYourWritesSession<S> {
+    fn query_defaults(&self) -> QueryDefaults {
+        self.session.query_defaults()
+    }
+}
+
+impl<S: GetSessionHooks> GetSessionHooks for ReadYourWritesSession<S> {
+    fn hooks(&self) -> &SessionHooks {
+        self.session.hooks()
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Send + Sync + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: GetConnection<T, M> + Sync,
+    > GetConnection<T, M> for ReadYourWritesSession<S>
+{
+    async fn get_connection(&self) -> Option<Arc<ConnectionPool<M>>> {
+        self.session.get_connection().await
+    }
+
+    async fn get_routed_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        self.session
+            .get_routed_connection(keyspace, routing_key)
+            .await
+    }
+
+    async fn get_primary_replica_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        self.session
+            .get_primary_replica_connection(keyspace, routing_key)
+            .await
+    }
+}
+
+#[async_trait]
+impl<S: ResponseCache + Sync> ResponseCache for ReadYourWritesSession<S> {
+    async fn match_or_cache_response(&self, stream_id: StreamId, frame: Frame) -> Option<Frame> {
+        self.session.match_or_cache_response(stream_id, frame).await
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > QueryExecutor<T, M> for ReadYourWritesSession<S>
+{
+    async fn query_with_params_tw<Q: ToString + Send>(
+        &self,
+        query: Q,
+        query_params: QueryParams,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        self.session
+            .query_with_params_tw(query, query_params, with_tracing, with_warnings)
+            .await
+    }
+}
+
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > PrepareExecutor<T, M> for ReadYourWritesSession<S>
+{
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > ExecExecutor<T, M> for ReadYourWritesSession<S>
+{
+    async fn exec_with_params_tw(
+        &self,
+        prepared: &PreparedQuery,
+        mut query_parameters: QueryParams,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        let write = match partition(prepared, &query_parameters) {
+            Some((table, routing_key, true)) => {
+                if self.is_recently_written(&table, &routing_key) {
+                    self.upgrade(&mut query_parameters.consistency);
+                }
+                None
+            }
+            Some((table, routing_key, false)) => {
+                self.generator().stamp(&mut query_parameters);
+                query_parameters
+                    .timestamp
+                    .map(|timestamp| (table, routing_key, timestamp))
+            }
+            None => None,
+        };
+
+        let frame = self
+            .session
+            .exec_with_params_tw(prepared, query_parameters, with_tracing, with_warnings)
+            .await?;
+
+        if let Some((table, routing_key, timestamp)) = write {
+            self.record_write(&table, &routing_key, timestamp);
+        }
+
+        Ok(frame)
+    }
+}
+
+#[async_trait]
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > BatchExecutor<T, M> for ReadYourWritesSession<S>
+{
+    async fn batch_with_params_tw(
+        &self,
+        mut batch: QueryBatch,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        let writes: Vec<(String, Vec<u8>)> = batch
+            .queries
+            .iter()
+            .filter_map(|query| match &query.subject {
+                BatchQuerySubj::PreparedId(prepared) => match Statement::parse(&prepared.query)? {
+                    Statement::Write(table) => Some((table, prepared.routing_key(&query.values)?)),
+                    Statement::Select(_) => None,
+                },
+                BatchQuerySubj::QueryString(_) => None,
+            })
+            .collect();
+
+        if !writes.is_empty() {
+            self.generator().stamp_batch(&mut batch);
+        }
+        let timestamp = batch.timestamp;
+
+        let frame = self
+            .session
+            .batch_with_params_tw(batch, with_tracing, with_warnings)
+            .await?;
+
+        if let Some(timestamp) = timestamp {
+            for (table, routing_key) in writes {
+                self.record_write(&table, &routing_key, timestamp);
+            }
+        }
+
+        Ok(frame)
+    }
+}
+
+impl<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        S: CDRSSession<T, M> + ResponseCache + Sync,
+    > CDRSSession<T, M> for ReadYourWritesSession<S>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::session::{new, Session};
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::{ClusterTcpConfig, NodeTcpConfigBuilder, TcpConnectionPool};
+    use crate::frame::frame_result::{ColSpec, ColType, ColTypeOption, PreparedMetadata};
+    use crate::frame::AsBytes;
+    use crate::load_balancing::RoundRobin;
+    use crate::query::RoutingMetadata;
+    use crate::query::{QueryParamsBuilder, QueryValues};
+    use crate::types::{CBytesShort, CString};
+    use std::sync::RwLock;
+
+    fn prepared(query: &str) -> PreparedQuery {
+        let metadata = PreparedMetadata {
+            flags: 1,
+            columns_count: 1,
+            pk_count: 1,
+            pk_indexes: vec![0],
+            global_table_spec: Some((CString::new("ks".into()), CString::new("t".into()))),
+            col_specs: vec![ColSpec {
+                ksname: None,
+                tablename: None,
+                name: CString::new("pk".into()),
+                col_type: ColTypeOption {
+                    id: ColType::Int,
+                    value: None,
+                },
+            }],
+        };
+
+        PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Default::default(),
+            query: query.into(),
+            keyspace: Default::default(),
+            routing: RoutingMetadata::new(&metadata, false),
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_of_written_partitions_are_upgraded() {
+        let node = TestNode::start().await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            node.addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .build()]);
+        let session: ReadYourWritesSession<Session<RoundRobin<TcpConnectionPool>>> =
+            ReadYourWritesSession::new(new(&config, RoundRobin::new()).await.unwrap());
+
+        let insert = prepared("INSERT INTO ks.t (pk) VALUES (?)");
+        let select = prepared("SELECT * FROM ks.t WHERE pk = ?");
+        let params = |pk: i32| {
+            QueryParamsBuilder::new()
+                .consistency(Consistency::One)
+                .values(QueryValues::SimpleValues(vec![pk.into()]))
+                .finalize()
+        };
+
+        session.exec_with_params(&insert, params(1)).await.unwrap();
+        session.exec_with_params(&select, params(1)).await.unwrap();
+        session.exec_with_params(&select, params(2)).await.unwrap();
+
+        // execute bodies start with the id [0, 1, 1], followed by the consistency
+        let consistencies: Vec<Vec<u8>> = node
+            .requests()
+            .iter()
+            .map(|request| request.body[3..5].to_vec())
+            .collect();
+        assert_eq!(
+            consistencies,
+            vec![
+                Consistency::One.as_bytes(),
+                Consistency::LocalQuorum.as_bytes(),
+                Consistency::One.as_bytes(),
+            ]
+        );
+        assert!(session.is_recently_written("ks.t", &[0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn recently_written_partitions() {
+        let session = ReadYourWritesSession::new(()).max_partitions(2);
+        let now = WriteTimestamp::now().as_micros();
+
+        session.record_write("ks.t", &[1], now);
+        session.record_write("ks.t", &[2], now - 10_000_000);
+        assert!(session.is_recently_written("ks.t", &[1]));
+        assert!(!session.is_recently_written("ks.u", &[1]));
+        // expired writes are forgotten
+        assert!(!session.is_recently_written("ks.t", &[2]));
+
+        session.record_write("ks.t", &[2], now);
+        session.is_recently_written("ks.t", &[1]);
+        session.record_write("ks.t", &[3], now);
+        assert!(session.is_recently_written("ks.t", &[1]));
+        assert!(!session.is_recently_written("ks.t", &[2]));
+        assert!(session.is_recently_written("ks.t", &[3]));
+
+        session.clear();
+        assert!(!session.is_recently_written("ks.t", &[1]));
+    }
+
+    #[test]
+    fn upgrades_weak_consistencies() {
+        let session = ReadYourWritesSession::new(()).consistency(Consistency::Quorum);
+
+        let mut consistency = Consistency::LocalOne;
+        session.upgrade(&mut consistency);
+        assert_eq!(consistency, Consistency::Quorum);
+
+        let mut consistency = Consistency::All;
+        session.upgrade(&mut consistency);
+        assert_eq!(consistency, Consistency::All);
+    }
+}
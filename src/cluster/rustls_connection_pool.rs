@@ -1,19 +1,22 @@
 use async_trait::async_trait;
-use bb8::{Builder, ManageConnection, PooledConnection};
-use tokio::io::AsyncWriteExt;
+use bb8::{ManageConnection, PooledConnection};
 use tokio::sync::Mutex;
 
 use std::net;
 use std::sync::Arc;
 
 use crate::authenticators::Authenticator;
-use crate::cluster::ConnectionPool;
-use crate::cluster::{startup, KeyspaceHolder, NodeRustlsConfig};
-use crate::compression::Compression;
+use crate::cluster::{
+    connect_negotiated, validate, ConnectionStateListener, ConnectionStates, ConnectionSupervisor,
+    ConnectionThrottle, KeyspaceHolder, NodeRustlsConfig,
+};
+use crate::cluster::{ConnectionPool, PoolOptions};
+use crate::compression::{CompressionStats, DEFAULT_MAX_DECOMPRESSED_LENGTH};
 use crate::error;
-use crate::frame::parser::parse_frame;
-use crate::frame::{AsBytes, Frame};
-use crate::transport::TransportRustls;
+use crate::load_balancing::NodeDistance;
+use crate::transport::{
+    CDRSTransport, TcpSocketOptions, TransportRustls, TransportWrapper, WriteCoalescing,
+};
 use std::ops::Deref;
 
 pub type RustlsConnectionPool = ConnectionPool<RustlsConnectionsManager>;
@@ -22,33 +25,76 @@ pub type RustlsConnectionPool = ConnectionPool<RustlsConnectionsManager>;
 ///
 /// Used internally for SSL Session for holding connections to a specific Cassandra node.
 pub async fn new_rustls_pool(node_config: NodeRustlsConfig) -> error::Result<RustlsConnectionPool> {
+    new_rustls_pool_with_distance(node_config, NodeDistance::Local).await
+}
+
+/// `bb8::Pool` of SSL-based CDRS connections sized for a node at given distance.
+pub async fn new_rustls_pool_with_distance(
+    node_config: NodeRustlsConfig,
+    distance: NodeDistance,
+) -> error::Result<RustlsConnectionPool> {
+    let max_size = node_config
+        .max_size_for(distance)
+        .ok_or_else(|| error::Error::from("Cannot create a pool for an ignored node"))?;
+
     let manager = RustlsConnectionsManager::new(
         node_config.addr,
         node_config.dns_name,
         node_config.config,
         node_config.authenticator,
-    );
-
-    let pool = Builder::new()
-        .max_size(node_config.max_size)
-        .min_idle(node_config.min_idle)
-        .max_lifetime(node_config.max_lifetime)
-        .idle_timeout(node_config.idle_timeout)
-        .connection_timeout(node_config.connection_timeout)
-        .build(manager)
+    )
+    .socket_options(node_config.socket_options)
+    .write_coalescing(node_config.write_coalescing)
+    .transport_wrapper(node_config.transport_wrapper)
+    .connection_throttle(node_config.connection_throttle)
+    .max_orphaned_streams(node_config.max_orphaned_streams)
+    .max_decompressed_length(node_config.max_decompressed_length)
+    .connection_state_listeners(node_config.connection_state_listeners);
+    let connection_states = manager.connection_states().clone();
+    let compression_stats = manager.compression_stats().clone();
+
+    let options = PoolOptions {
+        max_size,
+        min_idle: node_config.min_idle,
+        max_lifetime: node_config.max_lifetime,
+        idle_timeout: node_config.idle_timeout,
+        connection_timeout: node_config.connection_timeout,
+    };
+    let pool = options
+        .builder()
+        .build(manager.clone())
         .await
         .map_err(|err| error::Error::from(err.to_string()))?;
 
-    Ok(RustlsConnectionPool::new(pool, node_config.addr))
+    Ok(
+        RustlsConnectionPool::with_health(
+            pool,
+            node_config.addr,
+            distance,
+            node_config.node_health,
+        )
+        .with_max_size(max_size)
+        .with_connection_states(connection_states)
+        .with_compression_stats(compression_stats)
+        .with_rebuild(manager, options),
+    )
 }
 
-/// `bb8` connection manager.
+/// `bb8` connection manager. Clones share the keyspace, connection states, compression
+/// statistics and throttle.
+#[derive(Clone)]
 pub struct RustlsConnectionsManager {
     addr: net::SocketAddr,
     dns_name: webpki::DNSName,
     config: Arc<rustls::ClientConfig>,
     auth: Arc<dyn Authenticator + Send + Sync>,
     keyspace_holder: Arc<KeyspaceHolder>,
+    socket_options: TcpSocketOptions,
+    write_coalescing: Option<WriteCoalescing>,
+    transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    max_decompressed_length: usize,
+    compression_stats: Arc<CompressionStats>,
+    supervisor: ConnectionSupervisor,
 }
 
 impl RustlsConnectionsManager {
@@ -60,13 +106,79 @@ impl RustlsConnectionsManager {
         auth: Arc<dyn Authenticator + Send + Sync>,
     ) -> Self {
         Self {
+            supervisor: ConnectionSupervisor::new(addr.to_string()),
             addr,
             dns_name,
             config,
             auth,
             keyspace_holder: Default::default(),
+            socket_options: Default::default(),
+            write_coalescing: None,
+            transport_wrapper: None,
+            max_decompressed_length: DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            compression_stats: Default::default(),
         }
     }
+
+    /// Sets socket options used for new connections.
+    pub fn socket_options(mut self, socket_options: TcpSocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
+    }
+
+    /// Sets write coalescing used for new connections.
+    pub fn write_coalescing(mut self, write_coalescing: Option<WriteCoalescing>) -> Self {
+        self.write_coalescing = write_coalescing;
+        self
+    }
+
+    /// Sets a hook wrapping newly established streams.
+    pub fn transport_wrapper(
+        mut self,
+        transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    ) -> Self {
+        self.transport_wrapper = transport_wrapper;
+        self
+    }
+
+    /// Sets a throttle for establishing new connections.
+    pub fn connection_throttle(mut self, connection_throttle: ConnectionThrottle) -> Self {
+        self.supervisor.set_connection_throttle(connection_throttle);
+        self
+    }
+
+    /// Sets the number of outstanding abandoned requests above which a connection is considered
+    /// broken. 0 disables the check.
+    pub fn max_orphaned_streams(mut self, max_orphaned_streams: u32) -> Self {
+        self.supervisor
+            .set_max_orphaned_streams(max_orphaned_streams);
+        self
+    }
+
+    /// Sets the maximum length of decompressed frame bodies received over new connections.
+    pub fn max_decompressed_length(mut self, max_decompressed_length: usize) -> Self {
+        self.max_decompressed_length = max_decompressed_length;
+        self
+    }
+
+    /// Sets listeners notified about state changes of new connections.
+    pub fn connection_state_listeners(
+        mut self,
+        listeners: Vec<Arc<dyn ConnectionStateListener>>,
+    ) -> Self {
+        self.supervisor.set_listeners(listeners);
+        self
+    }
+
+    /// Returns numbers of connections established by this manager in each state.
+    pub fn connection_states(&self) -> &Arc<ConnectionStates> {
+        self.supervisor.states()
+    }
+
+    /// Returns statistics of compressed frames received over connections of this manager.
+    pub fn compression_stats(&self) -> &Arc<CompressionStats> {
+        &self.compression_stats
+    }
 }
 
 #[async_trait]
@@ -75,28 +187,45 @@ impl ManageConnection for RustlsConnectionsManager {
     type Error = error::Error;
 
     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        let transport = Mutex::new(
-            TransportRustls::new(
-                self.addr,
-                self.dns_name.clone(),
-                self.config.clone(),
-                self.keyspace_holder.clone(),
-            )
-            .await?,
-        );
-        startup(&transport, self.auth.deref(), self.keyspace_holder.deref()).await?;
-
-        Ok(transport)
+        self.supervisor
+            .connect(|| async {
+                let transport = connect_negotiated(
+                    self.supervisor.protocol_version(),
+                    || async {
+                        Ok(TransportRustls::with_transport_wrapper(
+                            self.addr,
+                            self.dns_name.clone(),
+                            self.config.clone(),
+                            self.keyspace_holder.clone(),
+                            self.socket_options,
+                            self.transport_wrapper.clone(),
+                        )
+                        .await?
+                        .with_write_coalescing(self.write_coalescing)
+                        .with_connection_states(self.supervisor.states().clone())
+                        .with_compression_stats(self.compression_stats.clone())
+                        .with_max_decompressed_length(self.max_decompressed_length))
+                    },
+                    self.auth.deref(),
+                    self.keyspace_holder.deref(),
+                )
+                .await
+                .map_err(|error| self.supervisor.record_failure(error))?;
+                self.supervisor
+                    .set_protocol_version(transport.lock().await.protocol_version());
+
+                Ok(transport)
+            })
+            .await
     }
 
     async fn is_valid(&self, conn: &mut PooledConnection<'_, Self>) -> Result<(), Self::Error> {
-        let options_frame = Frame::new_req_options().as_bytes();
-        conn.lock().await.write(options_frame.as_slice()).await?;
-
-        parse_frame(&conn, Compression::None).await.map(|_| ())
+        validate(conn.deref(), self.keyspace_holder.deref())
+            .await
+            .map_err(|error| self.supervisor.record_failure(error))
     }
 
-    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
-        false
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        self.supervisor.has_broken(conn.get_mut())
     }
 }
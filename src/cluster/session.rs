@@ -1,30 +1,66 @@
 use async_trait::async_trait;
 use fxhash::FxHashMap;
 use std::iter::Iterator;
+use std::net::SocketAddr;
 use std::ops::Deref;
-use std::sync::Arc;
-use tokio::{io::AsyncWriteExt, sync::Mutex};
+#[cfg(feature = "unstable-dynamic-cluster")]
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock as SyncRwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
+#[cfg(feature = "unstable-dynamic-cluster")]
+use tokio::time::sleep;
 
+use crate::cluster::token_map::keyspaces_from_rows;
 #[cfg(feature = "unstable-dynamic-cluster")]
 use crate::cluster::NodeTcpConfig;
-#[cfg(feature = "rust-tls")]
-use crate::cluster::{new_rustls_pool, ClusterRustlsConfig, RustlsConnectionPool};
 use crate::cluster::{
-    new_tcp_pool, startup, CDRSSession, ClusterTcpConfig, ConnectionPool, GetCompressor,
-    GetConnection, KeyspaceHolder, ResponseCache, TcpConnectionPool,
+    connect_negotiated, new_tcp_pool_with_distance, CDRSSession, ClusterTcpConfig, ConfigUpdate,
+    ConnectionPool, GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks,
+    KeyspaceHolder, ResponseCache, SessionHooks, TcpConnectionPool, TokenMap, TokenRingNode,
 };
+#[cfg(feature = "rust-tls")]
+use crate::cluster::{new_rustls_pool_with_distance, ClusterRustlsConfig, RustlsConnectionPool};
 use crate::error;
-use crate::load_balancing::LoadBalancingStrategy;
-use crate::transport::{CDRSTransport, TransportTcp};
+use crate::load_balancing::{LoadBalancingStrategy, NodeDistance};
+use crate::schema::SchemaSnapshot;
+use crate::token::Partitioner;
+use crate::transport::{write_frame, CDRSTransport, TransportTcp};
+use crate::types::IntoRustByName;
 
 use crate::authenticators::Authenticator;
-use crate::cluster::SessionPager;
-use crate::compression::Compression;
-use crate::events::{new_listener, EventStream, EventStreamNonBlocking, Listener};
+use crate::cluster::{PinnedSession, SessionPager};
+use crate::compression::{Compression, CompressionStats};
+use crate::events::{new_listener, ClusterEvent, EventStream, EventStreamNonBlocking, Listener};
 use crate::frame::events::{ServerEvent, SimpleServerEvent, StatusChange, StatusChangeType};
-use crate::frame::parser::parse_frame;
-use crate::frame::{AsBytes, Frame, StreamId};
-use crate::query::{BatchExecutor, ExecExecutor, PrepareExecutor, QueryExecutor};
+use crate::frame::parser::from_transport;
+use crate::frame::{AsBytes, Frame, ProtocolVersion, StreamId};
+#[cfg(feature = "unstable-raw-frames")]
+use crate::frame::{Flag, Opcode, Version};
+#[cfg(feature = "unstable-raw-frames")]
+use crate::query::send_frame;
+use crate::query::{
+    frame_rows, BatchExecutor, ExecExecutor, PrepareExecutor, QueryDefaults, QueryExecutor,
+};
+
+/// Maximum number of nodes marked down skipped when picking a node for a request.
+const MAX_DOWN_NODE_SKIPS: usize = 64;
+
+/// Events dynamic sessions listen to on their event source node.
+#[cfg(feature = "unstable-dynamic-cluster")]
+const CLUSTER_EVENT_TYPES: [SimpleServerEvent; 3] = [
+    SimpleServerEvent::TopologyChange,
+    SimpleServerEvent::StatusChange,
+    SimpleServerEvent::SchemaChange,
+];
+
+/// Minimum delay between attempts to re-establish a failed event connection.
+#[cfg(feature = "unstable-dynamic-cluster")]
+const EVENTS_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Number of cluster events kept for subscribers which haven't received them yet.
+const CLUSTER_EVENTS_CAPACITY: usize = 64;
 
 /// CDRS session that holds one pool of authorized connecitons per node.
 /// `compression` field contains data compressor that will be used
@@ -36,6 +72,11 @@ pub struct Session<LB> {
     responses: Mutex<FxHashMap<StreamId, Frame>>,
     #[allow(dead_code)]
     pub compression: Compression,
+    query_defaults: SyncRwLock<QueryDefaults>,
+    hooks: SessionHooks,
+    token_map: RwLock<Arc<TokenMap>>,
+    replica_rotation: AtomicUsize,
+    cluster_events: broadcast::Sender<ClusterEvent>,
 }
 
 impl<LB> GetCompressor for Session<LB> {
@@ -45,6 +86,20 @@ impl<LB> GetCompressor for Session<LB> {
     }
 }
 
+impl<LB> GetQueryDefaults for Session<LB> {
+    /// Returns query defaults that current session has.
+    fn query_defaults(&self) -> QueryDefaults {
+        *self.query_defaults.read().unwrap()
+    }
+}
+
+impl<LB> GetSessionHooks for Session<LB> {
+    /// Returns hooks that current session has.
+    fn hooks(&self) -> &SessionHooks {
+        &self.hooks
+    }
+}
+
 impl<'a, LB> Session<LB> {
     /// Basing on current session returns new `SessionPager` that can be used
     /// for performing paged queries.
@@ -60,6 +115,291 @@ impl<'a, LB> Session<LB> {
     {
         SessionPager::new(self, page_size)
     }
+
+    /// Reads token ring and keyspace replication settings from system tables, so replicas of
+    /// partitions can be computed with `replicas_for`. Peers are assumed to listen on the same
+    /// port as the node answering the queries. Needs to be called again to reflect topology or
+    /// schema changes.
+    pub async fn refresh_token_map<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    >(
+        &self,
+    ) -> error::Result<()>
+    where
+        Session<LB>: CDRSSession<T, M>,
+    {
+        // system.local and system.peers need to be read from the same node
+        let pinned = self.pinned::<M>();
+
+        let local_rows = frame_rows(
+            pinned
+                .query("SELECT data_center, rack, tokens, partitioner FROM system.local")
+                .await?,
+        )?;
+        let partitioner = match local_rows.first() {
+            Some(row) => {
+                let class_name: String = row.get_r_by_name("partitioner")?;
+                let partitioner = Partitioner::from_class_name(&class_name);
+                if partitioner.is_none() {
+                    warn!(
+                        "Unsupported partitioner {}, requests will not be routed by tokens",
+                        class_name
+                    );
+                }
+
+                partitioner
+            }
+            None => None,
+        };
+        let local_addr = pinned
+            .pinned_node()
+            .await
+            .ok_or_else(|| error::Error::from("Unable to get transport"))?
+            .get_addr();
+
+        let peer_rows = frame_rows(
+            pinned
+                .query("SELECT peer, rpc_address, data_center, rack, tokens FROM system.peers")
+                .await?,
+        )?;
+
+        let nodes = local_rows
+            .iter()
+            .map(|row| {
+                TokenRingNode::from_row(row, Some(local_addr), local_addr.port(), partitioner)
+            })
+            .chain(
+                peer_rows
+                    .iter()
+                    .map(|row| TokenRingNode::from_row(row, None, local_addr.port(), partitioner)),
+            )
+            .collect::<error::Result<Vec<_>>>()?;
+
+        let keyspaces = keyspaces_from_rows(frame_rows(
+            pinned
+                .query("SELECT keyspace_name, replication FROM system_schema.keyspaces")
+                .await?,
+        )?)?;
+
+        *self.token_map.write().await =
+            Arc::new(TokenMap::with_partitioner(partitioner, nodes, keyspaces));
+        Ok(())
+    }
+
+    /// Reads a snapshot of all tables of given keyspaces, which can be exported and used without
+    /// a cluster, e.g. for code generation or validating statements in tests.
+    pub async fn schema<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    >(
+        &self,
+        keyspaces: &[&str],
+    ) -> error::Result<SchemaSnapshot>
+    where
+        Session<LB>: CDRSSession<T, M>,
+    {
+        SchemaSnapshot::read(self, keyspaces).await
+    }
+
+    /// Sets defaults for query parameters which were not explicitly set and the request
+    /// timeout. Applies to requests started afterwards.
+    pub fn set_query_defaults(&self, query_defaults: QueryDefaults) {
+        *self.query_defaults.write().unwrap() = query_defaults;
+    }
+
+    /// Applies given configuration changes at runtime, without recreating the session or
+    /// interrupting requests in flight. See `ConfigUpdate` for details.
+    pub async fn update_config<M: bb8::ManageConnection + Clone>(&self, update: ConfigUpdate)
+    where
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        if let Some(query_defaults) = update.query_defaults {
+            self.set_query_defaults(query_defaults);
+        }
+
+        if update.changes_pools() {
+            // visits all nodes; reconfiguring a node again (e.g. a weighted one occurring
+            // multiple times) leaves it unchanged
+            self.load_balancing.lock().await.find(|node| {
+                node.reconfigure(&update);
+                false
+            });
+        }
+    }
+
+    /// Returns token map read by the last `refresh_token_map` call.
+    pub async fn token_map(&self) -> Arc<TokenMap> {
+        self.token_map.read().await.clone()
+    }
+
+    /// Returns addresses of replicas of given serialized partition key in given keyspace,
+    /// basing on the token map read by the last `refresh_token_map` call.
+    pub async fn replicas_for(&self, keyspace: &str, partition_key: &[u8]) -> Vec<SocketAddr> {
+        self.token_map
+            .read()
+            .await
+            .replicas_for(keyspace, partition_key)
+    }
+
+    /// Subscribes to topology, node status and schema changes. Events are received by
+    /// sessions listening to a control connection, i.e. the ones created with `*_dynamic`
+    /// functions. A subscriber which falls behind by more than 64 events misses the oldest
+    /// ones.
+    pub fn cluster_events(&self) -> broadcast::Receiver<ClusterEvent> {
+        self.cluster_events.subscribe()
+    }
+
+    /// Returns a handle which sends all requests to a single node, picked by the load balancer
+    /// on the first request. Useful for sequences of lightweight transactions.
+    pub fn pinned<M: bb8::ManageConnection>(&'a self) -> PinnedSession<'a, Session<LB>, M> {
+        PinnedSession::new(self)
+    }
+
+    /// Stops sending requests to a node with given address, e.g. before restarting it during
+    /// a rolling deployment. The node is skipped by the load balancer and token aware routing;
+    /// once requests already in flight to it have finished, its idle connections are closed.
+    /// Pools configured with `min_idle` keep re-establishing idle connections in the background.
+    ///
+    /// Fails if requests are still in flight after `timeout`, e.g. open continuous paging
+    /// streams, in which case the node stays drained but its connections are left open.
+    /// Requests which picked the node just before it was drained can still check out
+    /// connections while idle ones are being closed, so a few of them may fail.
+    pub async fn drain_node<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    >(
+        &self,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> error::Result<()>
+    where
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        let node = self.node(addr).await?;
+        node.set_drained(true);
+        node.drain(timeout).await
+    }
+
+    /// Resumes sending requests to a node drained with `drain_node`. New connections are
+    /// established as they are needed.
+    pub async fn undrain_node<M: bb8::ManageConnection>(
+        &self,
+        addr: SocketAddr,
+    ) -> error::Result<()>
+    where
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        self.node(addr).await?.set_drained(false);
+        Ok(())
+    }
+
+    /// Sends a request frame with given opcode, body and flags, returning the response. Stream
+    /// ids, response matching, request timeouts and node health are handled like for other
+    /// requests. If the flags contain `Flag::Compression`, the body is compressed with the
+    /// compression of the session.
+    ///
+    /// This is an escape hatch for experimenting with protocol features the driver doesn't
+    /// support, e.g. DSE extensions, and is not covered by semantic versioning: it may change
+    /// or disappear in any release.
+    #[cfg(feature = "unstable-raw-frames")]
+    pub async fn send_raw<
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    >(
+        &self,
+        opcode: Opcode,
+        body: Vec<u8>,
+        flags: Vec<Flag>,
+    ) -> error::Result<Frame>
+    where
+        Session<LB>: CDRSSession<T, M> + ResponseCache,
+    {
+        let build = |protocol_version| {
+            Ok(Frame::new(
+                Version::Request,
+                flags.clone(),
+                opcode.clone(),
+                body.clone(),
+                None,
+                vec![],
+            )
+            .with_protocol_version(protocol_version))
+        };
+
+        send_frame(self, &build).await
+    }
+
+    /// Returns the connection pool of a node with given address, e.g. to read numbers of
+    /// requests it rejected with `ConnectionPool::overloaded_responses`.
+    pub async fn node<M: bb8::ManageConnection>(
+        &self,
+        addr: SocketAddr,
+    ) -> error::Result<Arc<ConnectionPool<M>>>
+    where
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        self.load_balancing
+            .lock()
+            .await
+            .find(|node| node.get_addr() == addr)
+            .ok_or_else(|| format!("Unknown node {}", addr).into())
+    }
+
+    /// Returns statistics of compressed frames received from each node, e.g. to monitor
+    /// compression ratios. Nodes which don't collect them are skipped.
+    pub async fn compression_stats<M: bb8::ManageConnection>(
+        &self,
+    ) -> Vec<(SocketAddr, Arc<CompressionStats>)>
+    where
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        let mut compression_stats: Vec<(SocketAddr, Arc<CompressionStats>)> = vec![];
+        // visits all nodes; weighted ones may occur multiple times
+        self.load_balancing.lock().await.find(|node| {
+            if let Some(stats) = node.compression_stats() {
+                if compression_stats
+                    .iter()
+                    .all(|(addr, _)| *addr != node.get_addr())
+                {
+                    compression_stats.push((node.get_addr(), stats.clone()));
+                }
+            }
+            false
+        });
+
+        compression_stats
+    }
+}
+
+impl<LB> Session<LB> {
+    /// Returns replicas of the partition with given routing key which are up and not cooling
+    /// down after being overloaded, owner first, along with their distances.
+    async fn up_replicas<M>(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Vec<(Arc<ConnectionPool<M>>, NodeDistance)>
+    where
+        M: bb8::ManageConnection,
+        LB: LoadBalancingStrategy<ConnectionPool<M>>,
+    {
+        let replicas = self.replicas_for(keyspace, routing_key).await;
+        if replicas.is_empty() {
+            return vec![];
+        }
+
+        let load_balancing = self.load_balancing.lock().await;
+        replicas
+            .into_iter()
+            .filter_map(|replica| {
+                load_balancing
+                    .find(|node| node.get_addr() == replica)
+                    .filter(|node| node.is_up() && !node.is_cooling_down())
+                    .map(|node| (node, load_balancing.distance(replica)))
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -94,7 +434,83 @@ impl<
             }
         }
 
-        self.load_balancing.lock().await.next()
+        let load_balancing = self.load_balancing.lock().await;
+        let first = load_balancing.next()?;
+        if first.is_up() && !first.is_cooling_down() {
+            return Some(first);
+        }
+
+        // skip nodes marked down or cooling down after being overloaded, falling back to the
+        // first node which is up, or not drained if no node is up
+        let mut up_fallback = first.is_up().then(|| first.clone());
+        let mut fallback = (!first.is_drained()).then(|| first.clone());
+        for _ in 0..MAX_DOWN_NODE_SKIPS {
+            let node = load_balancing.next()?;
+            if Arc::ptr_eq(&node, &first) {
+                break;
+            }
+
+            if node.is_up() {
+                if !node.is_cooling_down() {
+                    return Some(node);
+                }
+
+                if up_fallback.is_none() {
+                    up_fallback = Some(node.clone());
+                }
+            }
+
+            if fallback.is_none() && !node.is_drained() {
+                fallback = Some(node);
+            }
+        }
+
+        up_fallback.or(fallback)
+    }
+
+    /// Returns connection to a replica which is up and not cooling down after being overloaded,
+    /// basing on the token map read by the last `refresh_token_map` call. Requests are rotated
+    /// across such replicas, preferring local ones. Falls back to `get_connection` if no such
+    /// replica is known.
+    async fn get_routed_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        let mut replicas = self.up_replicas(keyspace, routing_key).await;
+        if replicas
+            .iter()
+            .any(|(_, distance)| *distance == NodeDistance::Local)
+        {
+            replicas.retain(|(_, distance)| *distance == NodeDistance::Local);
+        }
+
+        if !replicas.is_empty() {
+            let rotation = self.replica_rotation.fetch_add(1, Ordering::Relaxed);
+            let (node, _) = replicas.swap_remove(rotation % replicas.len());
+            return Some(node);
+        }
+
+        self.get_connection().await
+    }
+
+    /// Returns connection to the first replica which is up and not cooling down after being
+    /// overloaded, trying the owner first, so lightweight transactions (see
+    /// `PreparedQuery::is_lwt`) are coordinated by the primary replica whenever possible.
+    async fn get_primary_replica_connection(
+        &self,
+        keyspace: &str,
+        routing_key: &[u8],
+    ) -> Option<Arc<ConnectionPool<M>>> {
+        let replica = self
+            .up_replicas(keyspace, routing_key)
+            .await
+            .into_iter()
+            .next();
+        match replica {
+            Some((node, _)) => Some(node),
+            None => self.get_connection().await,
+        }
     }
 }
 
@@ -172,10 +588,16 @@ async fn connect_tls_static<LB>(
 where
     LB: LoadBalancingStrategy<RustlsConnectionPool>,
 {
-    let mut nodes: Vec<Arc<RustlsConnectionPool>> = Vec::with_capacity(node_configs.0.len());
+    let mut nodes: Vec<Arc<RustlsConnectionPool>> = Vec::with_capacity(node_configs.nodes.len());
 
-    for node_config in &node_configs.0 {
-        let node_connection_pool = new_rustls_pool(node_config.clone()).await?;
+    for node_config in &node_configs.nodes {
+        let distance = load_balancing.distance(node_config.addr);
+        if distance == NodeDistance::Ignored {
+            continue;
+        }
+
+        let node_connection_pool =
+            new_rustls_pool_with_distance(node_config.clone(), distance).await?;
         nodes.push(Arc::new(node_connection_pool));
     }
 
@@ -186,6 +608,11 @@ where
         event_stream: None,
         responses: Default::default(),
         compression,
+        query_defaults: SyncRwLock::new(node_configs.query_defaults),
+        hooks: node_configs.hooks.clone(),
+        token_map: Default::default(),
+        replica_rotation: AtomicUsize::new(0),
+        cluster_events: broadcast::channel(CLUSTER_EVENTS_CAPACITY).0,
     })
 }
 
@@ -199,10 +626,16 @@ async fn connect_tls_dynamic<LB>(
 where
     LB: LoadBalancingStrategy<RustlsConnectionPool>,
 {
-    let mut nodes: Vec<Arc<RustlsConnectionPool>> = Vec::with_capacity(node_configs.0.len());
+    let mut nodes: Vec<Arc<RustlsConnectionPool>> = Vec::with_capacity(node_configs.nodes.len());
+
+    for node_config in &node_configs.nodes {
+        let distance = load_balancing.distance(node_config.addr);
+        if distance == NodeDistance::Ignored {
+            continue;
+        }
 
-    for node_config in &node_configs.0 {
-        let node_connection_pool = new_rustls_pool(node_config.clone()).await?;
+        let node_connection_pool =
+            new_rustls_pool_with_distance(node_config.clone(), distance).await?;
         nodes.push(Arc::new(node_connection_pool));
     }
 
@@ -213,19 +646,14 @@ where
         event_stream: None,
         responses: Default::default(),
         compression,
+        query_defaults: SyncRwLock::new(node_configs.query_defaults),
+        hooks: node_configs.hooks.clone(),
+        token_map: Default::default(),
+        replica_rotation: AtomicUsize::new(0),
+        cluster_events: broadcast::channel(CLUSTER_EVENTS_CAPACITY).0,
     };
 
-    let (listener, event_stream) = session
-        .listen_non_blocking(
-            &event_src.addr,
-            event_src.authenticator.deref(),
-            vec![SimpleServerEvent::StatusChange],
-        )
-        .await?;
-
-    tokio::spawn(listener.start(Compression::None));
-
-    session.event_stream = Some(Mutex::new(event_stream));
+    session.start_event_listener(event_src).await?;
 
     Ok(session)
 }
@@ -238,10 +666,16 @@ async fn connect_static<LB>(
 where
     LB: LoadBalancingStrategy<TcpConnectionPool>,
 {
-    let mut nodes: Vec<Arc<TcpConnectionPool>> = Vec::with_capacity(node_configs.0.len());
+    let mut nodes: Vec<Arc<TcpConnectionPool>> = Vec::with_capacity(node_configs.nodes.len());
+
+    for node_config in &node_configs.nodes {
+        let distance = load_balancing.distance(node_config.resolve_addr()?);
+        if distance == NodeDistance::Ignored {
+            continue;
+        }
 
-    for node_config in &node_configs.0 {
-        let node_connection_pool = new_tcp_pool(node_config.clone()).await?;
+        let node_connection_pool =
+            new_tcp_pool_with_distance(node_config.clone(), distance).await?;
         nodes.push(Arc::new(node_connection_pool));
     }
 
@@ -252,6 +686,11 @@ where
         event_stream: None,
         responses: Default::default(),
         compression,
+        query_defaults: SyncRwLock::new(node_configs.query_defaults),
+        hooks: node_configs.hooks.clone(),
+        token_map: Default::default(),
+        replica_rotation: AtomicUsize::new(0),
+        cluster_events: broadcast::channel(CLUSTER_EVENTS_CAPACITY).0,
     })
 }
 
@@ -265,10 +704,16 @@ async fn connect_dynamic<LB>(
 where
     LB: LoadBalancingStrategy<TcpConnectionPool>,
 {
-    let mut nodes: Vec<Arc<TcpConnectionPool>> = Vec::with_capacity(node_configs.0.len());
+    let mut nodes: Vec<Arc<TcpConnectionPool>> = Vec::with_capacity(node_configs.nodes.len());
+
+    for node_config in &node_configs.nodes {
+        let distance = load_balancing.distance(node_config.resolve_addr()?);
+        if distance == NodeDistance::Ignored {
+            continue;
+        }
 
-    for node_config in &node_configs.0 {
-        let node_connection_pool = new_tcp_pool(node_config.clone()).await?;
+        let node_connection_pool =
+            new_tcp_pool_with_distance(node_config.clone(), distance).await?;
         nodes.push(Arc::new(node_connection_pool));
     }
 
@@ -279,19 +724,14 @@ where
         event_stream: None,
         responses: Default::default(),
         compression,
+        query_defaults: SyncRwLock::new(node_configs.query_defaults),
+        hooks: node_configs.hooks.clone(),
+        token_map: Default::default(),
+        replica_rotation: AtomicUsize::new(0),
+        cluster_events: broadcast::channel(CLUSTER_EVENTS_CAPACITY).0,
     };
 
-    let (listener, event_stream) = session
-        .listen_non_blocking(
-            &event_src.addr,
-            event_src.authenticator.deref(),
-            vec![SimpleServerEvent::StatusChange],
-        )
-        .await?;
-
-    tokio::spawn(listener.start(Compression::None));
-
-    session.event_stream = Some(Mutex::new(event_stream));
+    session.start_event_listener(event_src).await?;
 
     Ok(session)
 }
@@ -503,6 +943,79 @@ where
     connect_tls_dynamic(node_configs, load_balancing, Compression::Lz4, event_src).await
 }
 
+/// Connects to given node and registers for given events on the connection.
+async fn connect_events<A: Authenticator + Send + Sync + ?Sized + 'static>(
+    node: &str,
+    authenticator: &A,
+    events: Vec<SimpleServerEvent>,
+    compression: Compression,
+) -> error::Result<Mutex<TransportTcp>> {
+    let keyspace_holder = Arc::new(KeyspaceHolder::default());
+    let transport = connect_negotiated(
+        ProtocolVersion::default(),
+        || async { Ok(TransportTcp::new(node, keyspace_holder.clone()).await?) },
+        authenticator,
+        keyspace_holder.deref(),
+    )
+    .await?;
+    let protocol_version = transport.lock().await.protocol_version();
+
+    debug!("Handshake with {}: sending REGISTER for {:?}", node, events);
+    let query_frame = Frame::new_req_register(events)
+        .with_protocol_version(protocol_version)
+        .as_bytes();
+    write_frame(&transport, query_frame.as_slice()).await?;
+    let response = from_transport(&transport, compression).await?;
+    debug!(
+        "Handshake with {}: REGISTER answered with {:?}",
+        node, response.opcode
+    );
+
+    Ok(transport)
+}
+
+#[cfg(feature = "unstable-dynamic-cluster")]
+impl<LB> Session<LB> {
+    /// Starts listening to cluster events of given node in the background. When the event
+    /// connection fails, it is re-established respecting the connection throttle of the node
+    /// and the events are registered again.
+    async fn start_event_listener(&mut self, event_src: NodeTcpConfig) -> error::Result<()> {
+        let (listener, event_stream) = self
+            .listen_non_blocking(
+                &event_src.addr,
+                event_src.authenticator.deref(),
+                CLUSTER_EVENT_TYPES.to_vec(),
+            )
+            .await?;
+        let listener = listener.with_cluster_events(self.cluster_events.clone());
+
+        let compression = self.get_compressor();
+        let event_src = Arc::new(event_src);
+        let failures = Arc::new(AtomicU32::new(0));
+        tokio::spawn(listener.start_reconnecting(Compression::None, move || {
+            let event_src = event_src.clone();
+            let failures = failures.clone();
+            async move {
+                sleep(EVENTS_RECONNECT_DELAY).await;
+                event_src
+                    .connection_throttle
+                    .connect(&failures, || {
+                        connect_events(
+                            &event_src.addr,
+                            event_src.authenticator.deref(),
+                            CLUSTER_EVENT_TYPES.to_vec(),
+                            compression,
+                        )
+                    })
+                    .await
+            }
+        }));
+
+        self.event_stream = Some(Mutex::new(event_stream));
+        Ok(())
+    }
+}
+
 impl<L> Session<L> {
     /// Returns new event listener.
     pub async fn listen<A: Authenticator + Send + Sync + ?Sized + 'static>(
@@ -511,19 +1024,9 @@ impl<L> Session<L> {
         authenticator: &A,
         events: Vec<SimpleServerEvent>,
     ) -> error::Result<(Listener<Mutex<TransportTcp>>, EventStream)> {
-        let compression = self.get_compressor();
-        let keyspace_holder = Arc::new(KeyspaceHolder::default());
-        let transport = TransportTcp::new(node, keyspace_holder.clone())
+        connect_events(node, authenticator, events, self.get_compressor())
             .await
-            .map(Mutex::new)?;
-
-        startup(&transport, authenticator, keyspace_holder.deref()).await?;
-
-        let query_frame = Frame::new_req_register(events).as_bytes();
-        transport.lock().await.write(query_frame.as_slice()).await?;
-        parse_frame(&transport, compression).await?;
-
-        Ok(new_listener(transport))
+            .map(new_listener)
     }
 
     pub async fn listen_non_blocking<A: Authenticator + Send + Sync + ?Sized + 'static>(
@@ -538,3 +1041,343 @@ impl<L> Session<L> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::test_node::TestNode;
+    use crate::cluster::NodeTcpConfigBuilder;
+    use crate::frame::Opcode;
+    use crate::load_balancing::RoundRobin;
+
+    /// Starts a node answering queries with a void result or, if `overloaded` is set, an
+    /// Overloaded error, returning its address.
+    async fn node(overloaded: bool) -> SocketAddr {
+        fn overloaded_error(_: Opcode, _: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            Some((Opcode::Error, vec![0, 0, 0x10, 0x01, 0, 0]))
+        }
+
+        if overloaded {
+            TestNode::start_with(overloaded_error).await.addr
+        } else {
+            TestNode::start().await.addr
+        }
+    }
+
+    #[tokio::test]
+    async fn overloaded_node_is_deprioritized() {
+        let overloaded = node(true).await;
+        let healthy = node(false).await;
+        let config = ClusterTcpConfig::new(
+            [overloaded, healthy]
+                .iter()
+                .map(|addr| {
+                    NodeTcpConfigBuilder::new(addr, Arc::new(NoneAuthenticator))
+                        .max_size(1)
+                        .build()
+                })
+                .collect(),
+        );
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+
+        for _ in 0..4 {
+            let frame = session.query("SELECT * FROM t").await.unwrap();
+            assert_eq!(frame.coordinator, Some(healthy));
+        }
+
+        let node: Arc<TcpConnectionPool> = session.node(overloaded).await.unwrap();
+        assert!(node.is_cooling_down());
+        assert!(node.is_up());
+        assert_eq!(node.overloaded_responses(), 1);
+
+        // requests are not retried on the same node
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            overloaded,
+            Arc::new(NoneAuthenticator),
+        )
+        .max_size(1)
+        .build()]);
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+        match session.query("SELECT * FROM t").await {
+            Err(error::Error::Server(error)) => assert!(error.is_overloaded()),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_waits_are_recorded() {
+        let addr = node(false).await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .max_size(1)
+        .build()]);
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+
+        for _ in 0..3 {
+            session.query("SELECT * FROM t").await.unwrap();
+        }
+
+        let node: Arc<TcpConnectionPool> = session.node(addr).await.unwrap();
+        assert_eq!(node.wait_times().count(), 3);
+        assert_eq!(node.available_streams(), Some(1));
+
+        let pool = node.get_pool().unwrap();
+        let _connection = pool.get().await.unwrap();
+        assert_eq!(node.available_streams(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn compression_stats_per_node() {
+        let first = node(false).await;
+        let second = node(false).await;
+        let config = ClusterTcpConfig::new(
+            [first, second]
+                .iter()
+                .map(|addr| NodeTcpConfigBuilder::new(addr, Arc::new(NoneAuthenticator)).build())
+                .collect(),
+        );
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+        session.query("SELECT * FROM t").await.unwrap();
+
+        let compression_stats = session
+            .compression_stats::<crate::cluster::TcpConnectionsManager>()
+            .await;
+        let addrs: Vec<SocketAddr> = compression_stats.iter().map(|(addr, _)| *addr).collect();
+        assert_eq!(addrs, vec![first, second]);
+        // responses are not compressed without compression negotiated
+        assert!(compression_stats
+            .iter()
+            .all(|(_, stats)| stats.frames() == 0));
+    }
+
+    #[tokio::test]
+    async fn query_defaults_from_config() {
+        use crate::consistency::Consistency;
+
+        let addr = node(false).await;
+        let query_defaults = QueryDefaults {
+            consistency: Consistency::Quorum,
+            page_size: Some(100),
+            ..Default::default()
+        };
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .build()])
+        .with_query_defaults(query_defaults);
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+
+        assert_eq!(session.query_defaults(), query_defaults);
+    }
+
+    #[tokio::test]
+    async fn requests_respect_deadlines() {
+        use crate::query::{with_deadline, QueryParamsBuilder};
+        use std::time::{Duration, Instant};
+
+        let addr = node(false).await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .max_size(1)
+        .build()]);
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+        let node: Arc<TcpConnectionPool> = session.node(addr).await.unwrap();
+
+        let params = QueryParamsBuilder::new()
+            .deadline(Instant::now() - Duration::from_millis(1))
+            .finalize();
+        match session.query_with_params("SELECT * FROM t", params).await {
+            Err(error::Error::DeadlineExceeded) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(node.wait_times().count(), 0);
+
+        let params = QueryParamsBuilder::new()
+            .deadline(Instant::now() + Duration::from_secs(5))
+            .finalize();
+        session
+            .query_with_params("SELECT * FROM t", params)
+            .await
+            .unwrap();
+
+        // the only connection is busy, so the request cannot be sent before the deadline
+        let pool = node.get_pool().unwrap();
+        let _connection = pool.get().await.unwrap();
+        let started = Instant::now();
+        let result = with_deadline(
+            Instant::now() + Duration::from_millis(50),
+            session.query("SELECT * FROM t"),
+        )
+        .await;
+        match result {
+            Err(error::Error::DeadlineExceeded) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn coordinators_are_reported() {
+        use crate::query::{CoordinatorListener, QueryResponse};
+        use std::sync::Mutex as SyncMutex;
+
+        let seen = Arc::new(SyncMutex::new(vec![]));
+        let listener: Arc<dyn CoordinatorListener> = {
+            let seen = seen.clone();
+            Arc::new(move |coordinator, _, error: Option<&error::Error>| {
+                seen.lock().unwrap().push((coordinator, error.is_some()));
+            })
+        };
+
+        let healthy = node(false).await;
+        let overloaded = node(true).await;
+        for addr in vec![healthy, overloaded] {
+            let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+                addr,
+                Arc::new(NoneAuthenticator),
+            )
+            .build()])
+            .with_hooks(SessionHooks {
+                coordinator_listeners: vec![listener.clone()],
+                ..Default::default()
+            });
+            let session = new(&config, RoundRobin::new()).await.unwrap();
+
+            if let Ok(frame) = session.query("SELECT * FROM t").await {
+                let response = QueryResponse::from_frame(frame).unwrap();
+                assert_eq!(response.coordinator, Some(addr));
+            }
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.first(), Some(&(healthy, false)));
+        assert!(seen[1..].iter().all(|seen| *seen == (overloaded, true)));
+        assert!(seen.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn drain_times_out_with_requests_in_flight() {
+        let addr = node(false).await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .max_size(1)
+        .build()]);
+        let session = new(&config, RoundRobin::new()).await.unwrap();
+        let node: Arc<TcpConnectionPool> = session.node(addr).await.unwrap();
+        type Manager = crate::cluster::TcpConnectionsManager;
+
+        let pool = node.get_pool().unwrap();
+        let connection = pool.get().await.unwrap();
+        assert!(session
+            .drain_node::<TransportTcp, Manager>(addr, Duration::from_millis(50))
+            .await
+            .is_err());
+        assert!(node.is_drained());
+
+        drop(connection);
+        session
+            .drain_node::<TransportTcp, Manager>(addr, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(node.connections_in_use(), 0);
+    }
+
+    /// Round robin treating one node as remote.
+    struct WithRemote<N> {
+        round_robin: RoundRobin<N>,
+        remote: SocketAddr,
+    }
+
+    impl<N: Sync + Send> LoadBalancingStrategy<N> for WithRemote<N> {
+        fn init(&mut self, cluster: Vec<Arc<N>>) {
+            self.round_robin.init(cluster)
+        }
+
+        fn next(&self) -> Option<Arc<N>> {
+            self.round_robin.next()
+        }
+
+        fn distance(&self, addr: SocketAddr) -> NodeDistance {
+            if addr == self.remote {
+                NodeDistance::Remote
+            } else {
+                NodeDistance::Local
+            }
+        }
+
+        fn find<F>(&self, filter: F) -> Option<Arc<N>>
+        where
+            F: FnMut(&N) -> bool,
+        {
+            self.round_robin.find(filter)
+        }
+    }
+
+    #[tokio::test]
+    async fn routed_requests_rotate_across_local_replicas() {
+        use crate::cluster::{ReplicationStrategy, TokenRingNode};
+        use std::collections::{HashMap, HashSet};
+
+        let addrs = vec![node(false).await, node(false).await, node(false).await];
+        let config = ClusterTcpConfig::new(
+            addrs
+                .iter()
+                .map(|addr| NodeTcpConfigBuilder::new(addr, Arc::new(NoneAuthenticator)).build())
+                .collect(),
+        );
+        let load_balancing = WithRemote {
+            round_robin: RoundRobin::new(),
+            remote: addrs[2],
+        };
+        let session = new(&config, load_balancing).await.unwrap();
+
+        let nodes = addrs
+            .iter()
+            .enumerate()
+            .map(|(index, addr)| TokenRingNode {
+                addr: *addr,
+                datacenter: "dc1".into(),
+                rack: "r1".into(),
+                tokens: vec![(index as i64 * 1000).into()],
+            })
+            .collect();
+        let mut keyspaces = HashMap::new();
+        keyspaces.insert(
+            "ks".to_string(),
+            ReplicationStrategy::SimpleStrategy {
+                replication_factor: 3,
+            },
+        );
+        *session.token_map.write().await = Arc::new(TokenMap::new(nodes, keyspaces));
+
+        let key = 1i32.to_be_bytes();
+        let replicas = session.replicas_for("ks", &key).await;
+        assert_eq!(replicas.len(), 3);
+        let primary = replicas[0];
+
+        let mut routed = HashSet::new();
+        for _ in 0..4 {
+            let node: Arc<TcpConnectionPool> =
+                session.get_routed_connection("ks", &key).await.unwrap();
+            routed.insert(node.get_addr());
+
+            let node: Arc<TcpConnectionPool> = session
+                .get_primary_replica_connection("ks", &key)
+                .await
+                .unwrap();
+            assert_eq!(node.get_addr(), primary);
+        }
+
+        // the remote replica is skipped while local ones are up
+        let local: HashSet<SocketAddr> = addrs[..2].iter().copied().collect();
+        assert_eq!(routed, local);
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use crate::query::{
+    CoordinatorListener, PrepareAdvisor, PreparedCache, StatementInterceptor, TimestampGenerator,
+    TracingListener, WarningLog,
+};
+use crate::types::registry::TypeRegistry;
+
+/// Hooks of a session observing or altering its requests, set with `with_hooks` of cluster
+/// configs or `DirectSession::with_hooks`. All hooks are disabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct SessionHooks {
+    /// Interceptors rewriting queries before they are sent, applied in order.
+    pub statement_interceptors: Vec<Arc<dyn StatementInterceptor>>,
+    /// Listeners notified about tracing ids of traced requests, e.g. sampled ones.
+    pub tracing_listeners: Vec<Arc<dyn TracingListener>>,
+    /// Listeners notified about coordinators of requests, e.g. to find hot coordinators.
+    pub coordinator_listeners: Vec<Arc<dyn CoordinatorListener>>,
+    /// Generator of default timestamps of requests which don't set one. Without it, write
+    /// timestamps are assigned by the coordinators.
+    pub timestamp_generator: Option<Arc<TimestampGenerator>>,
+    /// Advisor suggesting to prepare large or repeatedly executed simple queries.
+    pub prepare_advisor: Option<Arc<PrepareAdvisor>>,
+    /// Cache of prepared statements used by `prepare_cached`, which prepares every time
+    /// without it.
+    pub prepared_cache: Option<Arc<PreparedCache>>,
+    /// Log of warnings sent by the server, rate-limiting similar ones. Without it, warnings
+    /// are only available in responses.
+    pub warning_log: Option<Arc<WarningLog>>,
+    /// Registry of conversions of custom types and UDTs attached to rows converted by the
+    /// session, e.g. by `exec_rows` and pagers.
+    pub type_registry: Option<Arc<TypeRegistry>>,
+}
@@ -0,0 +1,184 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::error;
+
+type SessionSlot<S> = Arc<Mutex<Option<Arc<S>>>>;
+
+/// Cache of sessions shared by parts of an application connecting to many clusters, e.g. a
+/// multi-tenant service. Sessions are created lazily on first use and kept per key, which
+/// would usually identify the cluster, e.g. its sorted contact points. Concurrent requests for
+/// the same key wait for a single session to be created, while requests for other keys are not
+/// blocked.
+pub struct SessionRegistry<K, S> {
+    shards: Vec<std::sync::Mutex<HashMap<K, SessionSlot<S>>>>,
+}
+
+impl<K: Hash + Eq + Clone, S> SessionRegistry<K, S> {
+    const DEFAULT_SHARDS: usize = 16;
+
+    pub fn new() -> Self {
+        Self::with_shards(Self::DEFAULT_SHARDS)
+    }
+
+    /// Creates a registry with keys spread over given number of independently locked shards.
+    pub fn with_shards(shards: usize) -> Self {
+        SessionRegistry {
+            shards: (0..shards.max(1))
+                .map(|_| std::sync::Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Returns the session for given key, creating it with `create` if there is none yet. If
+    /// creation fails, the error is returned and the next call tries to create the session
+    /// again.
+    pub async fn get_or_try_create<F, Fut>(&self, key: K, create: F) -> error::Result<Arc<S>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = error::Result<S>>,
+    {
+        loop {
+            let slot = self
+                .shard(&key)
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(None)))
+                .clone();
+
+            let mut session = slot.lock().await;
+            if let Some(session) = session.as_ref() {
+                return Ok(session.clone());
+            }
+
+            // the slot was removed after a failed creation while waiting for it
+            if !self.is_registered(&key, &slot) {
+                continue;
+            }
+
+            return match create().await {
+                Ok(created) => {
+                    let created = Arc::new(created);
+                    *session = Some(created.clone());
+                    Ok(created)
+                }
+                Err(error) => {
+                    self.remove_slot(&key, &slot);
+                    Err(error)
+                }
+            };
+        }
+    }
+
+    /// Returns the session for given key, if it has already been created. Waits for
+    /// a session being created concurrently.
+    pub async fn get(&self, key: &K) -> Option<Arc<S>> {
+        let slot = self.shard(key).lock().unwrap().get(key)?.clone();
+        let session = slot.lock().await;
+        session.clone()
+    }
+
+    /// Removes the session for given key from the registry. The session is closed once all
+    /// its users drop it. Waits for a session being created concurrently.
+    pub async fn remove(&self, key: &K) -> Option<Arc<S>> {
+        let slot = self.shard(key).lock().unwrap().remove(key)?;
+        let session = slot.lock().await;
+        session.clone()
+    }
+
+    fn is_registered(&self, key: &K, slot: &SessionSlot<S>) -> bool {
+        matches!(self.shard(key).lock().unwrap().get(key), Some(registered) if Arc::ptr_eq(registered, slot))
+    }
+
+    fn remove_slot(&self, key: &K, slot: &SessionSlot<S>) {
+        let mut shard = self.shard(key).lock().unwrap();
+        if matches!(shard.get(key), Some(registered) if Arc::ptr_eq(registered, slot)) {
+            shard.remove(key);
+        }
+    }
+
+    fn shard(&self, key: &K) -> &std::sync::Mutex<HashMap<K, SessionSlot<S>>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+impl<K: Hash + Eq + Clone, S> Default for SessionRegistry<K, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn creates_session_once() {
+        let registry = SessionRegistry::new();
+        let created = AtomicUsize::new(0);
+
+        let create = || async {
+            created.fetch_add(1, Ordering::Relaxed);
+            sleep(Duration::from_millis(10)).await;
+            Ok("session")
+        };
+
+        let (first, second) = tokio::join!(
+            registry.get_or_try_create("127.0.0.1:9042", create),
+            registry.get_or_try_create("127.0.0.1:9042", create)
+        );
+
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+        assert_eq!(created.load(Ordering::Relaxed), 1);
+        assert!(registry.get(&"127.0.0.1:9042").await.is_some());
+        assert!(registry.get(&"127.0.0.2:9042").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_failed_creation() {
+        let registry: SessionRegistry<&str, &str> = SessionRegistry::with_shards(1);
+
+        let result = registry
+            .get_or_try_create("cluster", || async { Err("unreachable".into()) })
+            .await;
+        assert!(result.is_err());
+        assert!(registry.get(&"cluster").await.is_none());
+        assert!(registry.shards[0].lock().unwrap().is_empty());
+
+        let session = registry
+            .get_or_try_create("cluster", || async { Ok("session") })
+            .await
+            .unwrap();
+        assert_eq!(*session, "session");
+
+        assert!(registry.remove(&"cluster").await.is_some());
+        assert!(registry.get(&"cluster").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn waits_for_session_being_created() {
+        let registry = SessionRegistry::new();
+
+        let create = registry.get_or_try_create("cluster", || async {
+            sleep(Duration::from_millis(20)).await;
+            Ok("session")
+        });
+        let get = async {
+            sleep(Duration::from_millis(5)).await;
+            registry.get(&"cluster").await
+        };
+        let (created, got) = tokio::join!(create, get);
+
+        assert!(Arc::ptr_eq(&created.unwrap(), &got.unwrap()));
+        assert!(registry.remove(&"cluster").await.is_some());
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::sync::Mutex;
+use tower_service::Service;
+
+use crate::error;
+use crate::frame::Frame;
+use crate::query::{Statement, StatementExecutor};
+use crate::transport::CDRSTransport;
+
+/// Adapter of a shared session implementing `tower::Service`, so middlewares of the tower
+/// ecosystem (timeouts, retries, concurrency limits, load shedding, etc.) can be composed around
+/// requests. Serves `Statement`s of all kinds; clones of the service are cheap and share the
+/// session.
+///
+/// The service is always ready - waiting for connections happens when requests are executed, so
+/// concurrency should be limited with middlewares rather than by pool sizes.
+pub struct SessionService<S, T, M> {
+    session: Arc<S>,
+    _transport: PhantomData<fn() -> (T, M)>,
+}
+
+impl<S, T, M> SessionService<S, T, M> {
+    pub fn new(session: Arc<S>) -> Self {
+        SessionService {
+            session,
+            _transport: PhantomData,
+        }
+    }
+
+    /// Returns the underlying session.
+    pub fn session(&self) -> &Arc<S> {
+        &self.session
+    }
+}
+
+impl<S, T, M> Clone for SessionService<S, T, M> {
+    fn clone(&self) -> Self {
+        SessionService::new(self.session.clone())
+    }
+}
+
+impl<S, T, M> fmt::Debug for SessionService<S, T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionService").finish()
+    }
+}
+
+impl<S, T, M> Service<Statement> for SessionService<S, T, M>
+where
+    S: StatementExecutor<T, M> + Send + Sync + 'static,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    type Response = Frame;
+    type Error = error::Error;
+    type Future = Pin<Box<dyn Future<Output = error::Result<Frame>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<error::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, statement: Statement) -> Self::Future {
+        let session = self.session.clone();
+        Box::pin(async move { session.execute(statement).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::cluster::DirectSession;
+    use crate::frame::{AsByte, Opcode, Version};
+    use crate::query::{BatchQueryBuilder, Query, QueryParamsBuilder, QueryValues};
+
+    async fn serve(listener: TcpListener) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut header = [0; 9];
+
+        while stream.read_exact(&mut header).await.is_ok() {
+            let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+            let mut body = vec![0; length as usize];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let (opcode, body) = match Opcode::from(header[4]) {
+                Opcode::Startup => (Opcode::Ready, vec![]),
+                Opcode::Options => (Opcode::Supported, vec![0, 0]),
+                _ => (Opcode::Result, vec![0, 0, 0, 1]),
+            };
+
+            let mut response = vec![
+                Version::Response.as_byte(),
+                0,
+                header[2],
+                header[3],
+                opcode.as_byte(),
+            ];
+            response.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            response.extend_from_slice(&body);
+            stream.write_all(&response).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn executes_queries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let session = Arc::new(DirectSession::connect(addr).await.unwrap());
+        let mut service = SessionService::new(session);
+
+        let statements: Vec<Statement> = vec![
+            Query {
+                query: "SELECT * FROM t".into(),
+                params: QueryParamsBuilder::new().finalize(),
+            }
+            .into(),
+            BatchQueryBuilder::new()
+                .add_query(
+                    "INSERT INTO t (id) VALUES (?)",
+                    QueryValues::SimpleValues(vec![1.into()]),
+                )
+                .finalize()
+                .unwrap()
+                .into(),
+        ];
+
+        for statement in statements {
+            ready(&mut service).await.unwrap();
+            let frame = service.call(statement).await.unwrap();
+            assert_eq!(frame.opcode, Opcode::Result);
+        }
+    }
+
+    async fn ready<S: Service<Statement>>(service: &mut S) -> Result<(), S::Error> {
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await
+    }
+}
@@ -1,20 +1,29 @@
 use async_trait::async_trait;
-use bb8::{Builder, ManageConnection, PooledConnection};
-use std::io;
-use std::net::ToSocketAddrs;
+use bb8::{ManageConnection, PooledConnection};
+use std::future::Future;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::time::sleep;
 
 use crate::authenticators::Authenticator;
-use crate::cluster::ConnectionPool;
-use crate::cluster::KeyspaceHolder;
 use crate::cluster::NodeTcpConfig;
-use crate::compression::Compression;
+use crate::cluster::{ConnectionPool, PoolOptions};
+use crate::cluster::{
+    ConnectionStateListener, ConnectionStates, ConnectionSupervisor, ConnectionThrottle,
+    KeyspaceHolder,
+};
+use crate::compression::{Compression, CompressionStats, DEFAULT_MAX_DECOMPRESSED_LENGTH};
 use crate::error;
-use crate::frame::parser::parse_frame;
-use crate::frame::{AsBytes, Frame, Opcode};
-use crate::transport::{CDRSTransport, TransportTcp};
+use crate::frame::frame_error::ErrorCode;
+use crate::frame::frame_supported::{LWT_OPTIMIZATION_META_BIT_MASK, SCYLLA_LWT_ADD_METADATA_MARK};
+use crate::frame::parser::from_transport;
+use crate::frame::{AsBytes, Frame, Opcode, ProtocolVersion};
+use crate::load_balancing::NodeDistance;
+use crate::query::Query;
+use crate::transport::{
+    write_frame, CDRSTransport, TcpSocketOptions, TransportTcp, TransportWrapper, WriteCoalescing,
+};
 use std::ops::Deref;
 
 /// Shortcut for `bb8::Pool` type of TCP-based CDRS connections.
@@ -24,43 +33,143 @@ pub type TcpConnectionPool = ConnectionPool<TcpConnectionsManager>;
 ///
 /// Used internally for TCP Session for holding connections to a specific Cassandra node.
 pub async fn new_tcp_pool(node_config: NodeTcpConfig) -> error::Result<TcpConnectionPool> {
+    new_tcp_pool_with_distance(node_config, NodeDistance::Local).await
+}
+
+/// `bb8::Pool` of TCP-based CDRS connections sized for a node at given distance.
+pub async fn new_tcp_pool_with_distance(
+    node_config: NodeTcpConfig,
+    distance: NodeDistance,
+) -> error::Result<TcpConnectionPool> {
+    let max_size = node_config
+        .max_size_for(distance)
+        .ok_or_else(|| error::Error::from("Cannot create a pool for an ignored node"))?;
+    let addr = node_config.resolve_addr()?;
+
     let manager =
-        TcpConnectionsManager::new(node_config.addr.to_string(), node_config.authenticator);
-
-    let pool = Builder::new()
-        .max_size(node_config.max_size)
-        .min_idle(node_config.min_idle)
-        .max_lifetime(node_config.max_lifetime)
-        .idle_timeout(node_config.idle_timeout)
-        .connection_timeout(node_config.connection_timeout)
-        .build(manager)
+        TcpConnectionsManager::new(node_config.addr.to_string(), node_config.authenticator)
+            .socket_options(node_config.socket_options)
+            .write_coalescing(node_config.write_coalescing)
+            .transport_wrapper(node_config.transport_wrapper)
+            .connection_throttle(node_config.connection_throttle)
+            .max_orphaned_streams(node_config.max_orphaned_streams)
+            .max_decompressed_length(node_config.max_decompressed_length)
+            .connection_state_listeners(node_config.connection_state_listeners);
+    let connection_states = manager.connection_states().clone();
+    let compression_stats = manager.compression_stats().clone();
+
+    let options = PoolOptions {
+        max_size,
+        min_idle: node_config.min_idle,
+        max_lifetime: node_config.max_lifetime,
+        idle_timeout: node_config.idle_timeout,
+        connection_timeout: node_config.connection_timeout,
+    };
+    let pool = options
+        .builder()
+        .build(manager.clone())
         .await
         .map_err(|err| error::Error::from(err.to_string()))?;
 
-    let addr = node_config
-        .addr
-        .to_socket_addrs()?
-        .next()
-        .ok_or_else(|| error::Error::from("Cannot parse address"))?;
-
-    Ok(TcpConnectionPool::new(pool, addr))
+    Ok(
+        TcpConnectionPool::with_health(pool, addr, distance, node_config.node_health)
+            .with_max_size(max_size)
+            .with_connection_states(connection_states)
+            .with_compression_stats(compression_stats)
+            .with_rebuild(manager, options),
+    )
 }
 
-/// `bb8` connection manager.
+/// `bb8` connection manager. Clones share the keyspace, connection states, compression
+/// statistics and throttle.
+#[derive(Clone)]
 pub struct TcpConnectionsManager {
     addr: String,
     auth: Arc<dyn Authenticator + Send + Sync>,
     keyspace_holder: Arc<KeyspaceHolder>,
+    socket_options: TcpSocketOptions,
+    write_coalescing: Option<WriteCoalescing>,
+    transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    max_decompressed_length: usize,
+    compression_stats: Arc<CompressionStats>,
+    supervisor: ConnectionSupervisor,
 }
 
 impl TcpConnectionsManager {
     pub fn new<S: ToString>(addr: S, auth: Arc<dyn Authenticator + Send + Sync>) -> Self {
+        let addr = addr.to_string();
         TcpConnectionsManager {
-            addr: addr.to_string(),
+            supervisor: ConnectionSupervisor::new(addr.clone()),
+            addr,
             auth,
             keyspace_holder: Default::default(),
+            socket_options: Default::default(),
+            write_coalescing: None,
+            transport_wrapper: None,
+            max_decompressed_length: DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            compression_stats: Default::default(),
         }
     }
+
+    /// Sets socket options used for new connections.
+    pub fn socket_options(mut self, socket_options: TcpSocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
+    }
+
+    /// Sets write coalescing used for new connections.
+    pub fn write_coalescing(mut self, write_coalescing: Option<WriteCoalescing>) -> Self {
+        self.write_coalescing = write_coalescing;
+        self
+    }
+
+    /// Sets a hook wrapping newly established streams.
+    pub fn transport_wrapper(
+        mut self,
+        transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    ) -> Self {
+        self.transport_wrapper = transport_wrapper;
+        self
+    }
+
+    /// Sets a throttle for establishing new connections.
+    pub fn connection_throttle(mut self, connection_throttle: ConnectionThrottle) -> Self {
+        self.supervisor.set_connection_throttle(connection_throttle);
+        self
+    }
+
+    /// Sets the number of outstanding abandoned requests above which a connection is considered
+    /// broken. 0 disables the check.
+    pub fn max_orphaned_streams(mut self, max_orphaned_streams: u32) -> Self {
+        self.supervisor
+            .set_max_orphaned_streams(max_orphaned_streams);
+        self
+    }
+
+    /// Sets the maximum length of decompressed frame bodies received over new connections.
+    pub fn max_decompressed_length(mut self, max_decompressed_length: usize) -> Self {
+        self.max_decompressed_length = max_decompressed_length;
+        self
+    }
+
+    /// Sets listeners notified about state changes of new connections.
+    pub fn connection_state_listeners(
+        mut self,
+        listeners: Vec<Arc<dyn ConnectionStateListener>>,
+    ) -> Self {
+        self.supervisor.set_listeners(listeners);
+        self
+    }
+
+    /// Returns numbers of connections established by this manager in each state.
+    pub fn connection_states(&self) -> &Arc<ConnectionStates> {
+        self.supervisor.states()
+    }
+
+    /// Returns statistics of compressed frames received over connections of this manager.
+    pub fn compression_stats(&self) -> &Arc<CompressionStats> {
+        &self.compression_stats
+    }
 }
 
 #[async_trait]
@@ -69,23 +178,86 @@ impl ManageConnection for TcpConnectionsManager {
     type Error = error::Error;
 
     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        let transport =
-            Mutex::new(TransportTcp::new(&self.addr, self.keyspace_holder.clone()).await?);
-        startup(&transport, self.auth.deref(), self.keyspace_holder.deref()).await?;
+        self.supervisor
+            .connect(|| async {
+                let transport = connect_negotiated(
+                    self.supervisor.protocol_version(),
+                    || async {
+                        Ok(TransportTcp::with_transport_wrapper(
+                            &self.addr,
+                            self.keyspace_holder.clone(),
+                            self.socket_options,
+                            self.transport_wrapper.clone(),
+                        )
+                        .await?
+                        .with_write_coalescing(self.write_coalescing)
+                        .with_connection_states(self.supervisor.states().clone())
+                        .with_compression_stats(self.compression_stats.clone())
+                        .with_max_decompressed_length(self.max_decompressed_length))
+                    },
+                    self.auth.deref(),
+                    self.keyspace_holder.deref(),
+                )
+                .await
+                .map_err(|error| self.supervisor.record_failure(error))?;
+                self.supervisor
+                    .set_protocol_version(transport.lock().await.protocol_version());
 
-        Ok(transport)
+                Ok(transport)
+            })
+            .await
     }
 
     async fn is_valid(&self, conn: &mut PooledConnection<'_, Self>) -> Result<(), Self::Error> {
-        let options_frame = Frame::new_req_options().as_bytes();
-        conn.lock().await.write(options_frame.as_slice()).await?;
+        validate(conn.deref(), self.keyspace_holder.deref())
+            .await
+            .map_err(|error| self.supervisor.record_failure(error))
+    }
 
-        parse_frame(&conn, Compression::None).await.map(|_| ())
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        // connections are closed when a malformed frame or timeout leaves their stream in an
+        // unknown state
+        self.supervisor.has_broken(conn.get_mut())
     }
+}
+
+/// Establishes a connection with `connect` and performs the startup handshake, starting with
+/// given protocol version. If the node rejects the version, a new connection is established
+/// with the next lower one. The negotiated version is recorded in the returned transport.
+pub async fn connect_negotiated<T, A, F, Fut>(
+    protocol_version: ProtocolVersion,
+    connect: F,
+    session_authenticator: &A,
+    keyspace_holder: &KeyspaceHolder,
+) -> error::Result<Mutex<T>>
+where
+    T: CDRSTransport + Unpin + 'static,
+    A: Authenticator + Send + Sync + ?Sized + 'static,
+    F: Fn() -> Fut,
+    Fut: Future<Output = error::Result<T>>,
+{
+    let mut protocol_version = protocol_version;
+    loop {
+        let transport = connect().await?;
+        transport.set_protocol_version(protocol_version);
+        let transport = Mutex::new(transport);
 
-    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
-        // cannot synchronously determine broken connection, so return false as per bb8 docs
-        false
+        match startup(&transport, session_authenticator, keyspace_holder).await {
+            Ok(()) => return Ok(transport),
+            Err(error::Error::Server(ref error))
+                if error.is_unsupported_protocol_version()
+                    && protocol_version.lower().is_some() =>
+            {
+                // the node closes connections after rejecting their version
+                let lower = protocol_version.lower().unwrap();
+                debug!(
+                    "Node rejected protocol {:?}, falling back to {:?}: {}",
+                    protocol_version, lower, error
+                );
+                protocol_version = lower;
+            }
+            Err(error) => return Err(error),
+        }
     }
 }
 
@@ -98,17 +270,58 @@ pub async fn startup<
     keyspace_holder: &KeyspaceHolder,
 ) -> error::Result<()> {
     let compression = Compression::None;
-    let startup_frame = Frame::new_req_startup(compression.as_str()).as_bytes();
+    let (node, protocol_version) = {
+        let transport = transport.lock().await;
+        let node = transport.addr().unwrap_or_else(|| "<unknown>".to_string());
+        (node, transport.protocol_version())
+    };
 
-    transport
-        .lock()
-        .await
-        .write(startup_frame.as_slice())
-        .await?;
+    debug!(
+        "Handshake with {}: sending OPTIONS with protocol {:?}",
+        node, protocol_version
+    );
+    let options_frame = Frame::new_req_options()
+        .with_protocol_version(protocol_version)
+        .as_bytes();
+    write_frame(transport, options_frame.as_slice()).await?;
+    let supported = from_transport(transport, compression)
+        .await?
+        .get_body()?
+        .into_supported();
+    if let Some(supported) = &supported {
+        debug!("Handshake with {}: SUPPORTED {:?}", node, supported.data);
+    }
+    let lwt_metadata_mask = supported.and_then(|supported| supported.lwt_metadata_mask());
+
+    // the extension is enabled by echoing it back
+    let lwt_extension =
+        lwt_metadata_mask.map(|mask| format!("{}={}", LWT_OPTIMIZATION_META_BIT_MASK, mask as u32));
+    let startup_options: Vec<(&'static str, &str)> = lwt_extension
+        .iter()
+        .map(|extension| (SCYLLA_LWT_ADD_METADATA_MARK, extension.as_str()))
+        .collect();
+    let startup_frame = Frame::new_req_startup_with_options(compression.as_str(), &startup_options)
+        .with_protocol_version(protocol_version)
+        .as_bytes();
+
+    debug!(
+        "Handshake with {}: sending STARTUP with compression {:?} and options {:?}",
+        node,
+        compression.as_str(),
+        startup_options
+    );
+    write_frame(transport, startup_frame.as_slice()).await?;
+    if let Some(mask) = lwt_metadata_mask {
+        transport.lock().await.set_lwt_metadata_mask(mask);
+    }
 
-    let start_response = parse_frame(transport, compression).await?;
+    let start_response = from_transport(transport, compression).await?;
 
     if start_response.opcode == Opcode::Ready {
+        debug!("Handshake with {}: READY without authentication", node);
+        use_keyspace_logged(transport, keyspace_holder, &node).await?;
+        transport.lock().await.mark_ready();
+        debug!("Handshake with {}: completed", node);
         return Ok(());
     }
 
@@ -118,71 +331,507 @@ pub async fn startup<
             "Cassandra Server did communicate that it needed
                 authentication but the auth schema was missing in the body response",
         );
+        debug!(
+            "Handshake with {}: AUTHENTICATE with {}, client authenticator {:?}",
+            node,
+            authenticator,
+            session_authenticator.get_cassandra_name()
+        );
 
-        // This creates a new scope; avoiding a clone
-        // and we check whether
-        // 1. any authenticators has been passed in by client and if not send error back
-        // 2. authenticator is provided by the client and `auth_scheme` presented by
-        //      the server and client are same if not send error back
-        // 3. if it falls through it means the preliminary conditions are true
-
-        let auth_check = session_authenticator
-            .get_cassandra_name()
-            .ok_or_else(|| error::Error::General("No authenticator was provided".to_string()))
-            .map(|auth| {
-                if authenticator != auth {
-                    let io_err = io::Error::new(
-                        io::ErrorKind::NotFound,
-                        format!(
-                            "Unsupported type of authenticator. {:?} got,
-                             but {} is supported.",
-                            authenticator, auth
-                        ),
-                    );
-                    return Err(error::Error::Io(io_err));
-                }
-                Ok(())
+        if !session_authenticator.supports(authenticator) {
+            return Err(match session_authenticator.get_cassandra_name() {
+                Some(client_authenticator) => error::Error::UnsupportedAuthenticator {
+                    node,
+                    server_authenticator: authenticator.to_string(),
+                    client_authenticator: client_authenticator.to_string(),
+                },
+                None => error::Error::MissingAuthenticator {
+                    node,
+                    server_authenticator: authenticator.to_string(),
+                },
             });
-
-        if let Err(err) = auth_check {
-            return Err(err);
         }
 
-        let auth_token_bytes = session_authenticator.get_auth_token();
-        transport
-            .lock()
-            .await
-            .write(
-                Frame::new_req_auth_response(auth_token_bytes)
-                    .as_bytes()
-                    .as_slice(),
-            )
+        // the token carries credentials, so only its size is logged
+        let auth_token_bytes = session_authenticator
+            .fetch_auth_token_for(authenticator)
             .await?;
-        parse_frame(transport, compression).await?;
-
-        if let Some(current_keyspace) = keyspace_holder.current_keyspace().await {
-            let use_frame = Frame::new_req_query(
-                format!("USE {}", current_keyspace),
-                Default::default(),
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-                Default::default(),
-            );
+        debug!(
+            "Handshake with {}: sending AUTH_RESPONSE of {} bytes",
+            node,
+            auth_token_bytes.as_slice().map_or(0, <[u8]>::len)
+        );
+        write_frame(
+            transport,
+            Frame::new_req_auth_response(auth_token_bytes)
+                .with_protocol_version(protocol_version)
+                .as_bytes()
+                .as_slice(),
+        )
+        .await?;
+        let auth_response = match from_transport(transport, compression).await {
+            Err(error::Error::Server(error)) if error.is_authentication() => {
+                return Err(error::Error::AuthenticationFailed {
+                    node,
+                    message: error.message.as_plain(),
+                })
+            }
+            result => result?,
+        };
+        debug!("Handshake with {}: {:?}", node, auth_response.opcode);
+
+        use_keyspace_logged(transport, keyspace_holder, &node).await?;
+        transport.lock().await.mark_ready();
+        debug!("Handshake with {}: completed", node);
+        return Ok(());
+    }
+
+    unreachable!();
+}
+
+async fn use_keyspace_logged<T: CDRSTransport + Unpin + 'static>(
+    transport: &Mutex<T>,
+    keyspace_holder: &KeyspaceHolder,
+    node: &str,
+) -> error::Result<()> {
+    if let Some(keyspace) = keyspace_holder.current_keyspace().await {
+        debug!("Handshake with {}: USE {}", node, keyspace);
+    }
+
+    use_keyspace(transport, keyspace_holder)
+        .await
+        .map_err(|error| {
+            debug!("Handshake with {}: USE failed: {}", node, error);
+            error
+        })
+}
 
-            transport
+/// Validates a pooled connection. If the pool uses a keyspace, the connection is validated by
+/// setting it, which also fixes connections left in another keyspace.
+pub async fn validate<T: CDRSTransport + Unpin + 'static>(
+    transport: &Mutex<T>,
+    keyspace_holder: &KeyspaceHolder,
+) -> error::Result<()> {
+    if keyspace_holder.current_keyspace().await.is_some() {
+        return use_keyspace(transport, keyspace_holder).await;
+    }
+
+    let protocol_version = transport.lock().await.protocol_version();
+    let options_frame = Frame::new_req_options()
+        .with_protocol_version(protocol_version)
+        .as_bytes();
+    write_frame(transport, options_frame.as_slice()).await?;
+
+    from_transport(transport, Compression::None)
+        .await
+        .map(|_| ())
+}
+
+/// Sets the keyspace last used by the pool on a connection. Failures the node may recover
+/// from, like being overloaded, are retried; others fail with `Error::UseKeyspace`.
+async fn use_keyspace<T: CDRSTransport + Unpin + 'static>(
+    transport: &Mutex<T>,
+    keyspace_holder: &KeyspaceHolder,
+) -> error::Result<()> {
+    const ATTEMPTS: u32 = 3;
+    const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+    let keyspace = match keyspace_holder.current_keyspace().await {
+        Some(keyspace) => keyspace,
+        None => return Ok(()),
+    };
+
+    let mut attempt = 1;
+    loop {
+        match send_use(transport, &keyspace).await {
+            Ok(()) => return Ok(()),
+            Err(error::Error::Server(ref error))
+                if attempt < ATTEMPTS
+                    && matches!(
+                        error.code(),
+                        Some(ErrorCode::Server)
+                            | Some(ErrorCode::Overloaded)
+                            | Some(ErrorCode::IsBootstrapping)
+                    ) =>
+            {
+                sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            Err(error) => {
+                return Err(error::Error::UseKeyspace {
+                    keyspace,
+                    source: Box::new(error),
+                })
+            }
+        }
+    }
+}
+
+async fn send_use<T: CDRSTransport + Unpin + 'static>(
+    transport: &Mutex<T>,
+    keyspace: &str,
+) -> error::Result<()> {
+    let protocol_version = transport.lock().await.protocol_version();
+    let use_query = Query {
+        query: format!("USE {}", keyspace),
+        params: Default::default(),
+    };
+    let use_frame = Frame::new_query_for(&use_query, Default::default(), protocol_version)?;
+    write_frame(transport, use_frame.as_bytes().as_slice()).await?;
+
+    from_transport(transport, Compression::None)
+        .await?
+        .get_body()?
+        .into_set_keyspace()
+        .map(|_| ())
+        .ok_or_else(|| error::Error::from("USE should yield a SetKeyspace result"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::{
+        AuthenticatorRegistry, NoneAuthenticator, StaticPasswordAuthenticator,
+    };
+    use crate::cluster::test_node::{Reply, Request, TestNode};
+    use crate::frame::Opcode;
+    use crate::transport::TransportStream;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Starts a node answering requests, handshakes included, with given responses in order,
+    /// returning its address.
+    async fn scripted_node(responses: Vec<(u8, Vec<u8>)>) -> String {
+        let responses = std::sync::Mutex::new(VecDeque::from(responses));
+        let node = TestNode::start_scripted(Arc::new(move |_: &Request| {
+            responses
                 .lock()
-                .await
-                .write(use_frame.as_bytes().as_slice())
-                .await?;
-            parse_frame(transport, compression).await?;
+                .unwrap()
+                .pop_front()
+                .map(|(opcode, body)| Reply::Frame(Opcode::from(opcode), body))
+                .into_iter()
+                .collect()
+        }))
+        .await;
+
+        node.addr.to_string()
+    }
+
+    fn error_body(code: u32, message: &str) -> (u8, Vec<u8>) {
+        let mut body = code.to_be_bytes().to_vec();
+        body.extend((message.len() as u16).to_be_bytes());
+        body.extend(message.as_bytes());
+        (0x00, body)
+    }
+
+    fn set_keyspace_body(keyspace: &str) -> (u8, Vec<u8>) {
+        let mut body = 3u32.to_be_bytes().to_vec();
+        body.extend((keyspace.len() as u16).to_be_bytes());
+        body.extend(keyspace.as_bytes());
+        (0x08, body)
+    }
+
+    async fn keyspace_connection(addr: &str) -> (Mutex<TransportTcp>, Arc<KeyspaceHolder>) {
+        let keyspace_holder = Arc::new(KeyspaceHolder::default());
+        keyspace_holder.set_current_keyspace("ks").await;
+        let transport = TransportTcp::new(addr, keyspace_holder.clone())
+            .await
+            .unwrap();
+
+        (Mutex::new(transport), keyspace_holder)
+    }
+
+    #[tokio::test]
+    async fn missing_keyspace_fails_validation() {
+        let addr = scripted_node(vec![error_body(0x2200, "Keyspace 'ks' does not exist")]).await;
+        let (transport, keyspace_holder) = keyspace_connection(&addr).await;
+
+        match validate(&transport, &keyspace_holder).await {
+            Err(error::Error::UseKeyspace { keyspace, source }) => {
+                assert_eq!(keyspace, "ks");
+                assert!(matches!(*source, error::Error::Server(_)));
+            }
+            result => panic!("unexpected result: {:?}", result),
         }
+    }
 
-        return Ok(());
+    #[tokio::test]
+    async fn missing_keyspace_fails_connection() {
+        let addr = scripted_node(vec![
+            (0x06, vec![0, 0]),
+            (0x02, vec![]),
+            error_body(0x2200, "Keyspace 'ks' does not exist"),
+        ])
+        .await;
+        let manager = TcpConnectionsManager::new(addr, Arc::new(NoneAuthenticator));
+        manager.keyspace_holder.set_current_keyspace("ks").await;
+
+        assert!(matches!(
+            manager.connect().await,
+            Err(error::Error::UseKeyspace { .. })
+        ));
+        assert_eq!(manager.connection_states().keyspace_failures(), 1);
     }
 
-    unreachable!();
+    #[tokio::test]
+    async fn negotiates_lwt_metadata_mark() {
+        let string = |value: &str| {
+            let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+            bytes.extend(value.as_bytes());
+            bytes
+        };
+        // a multimap with a single key and a single value
+        let mut supported = vec![0, 1];
+        supported.extend(string(SCYLLA_LWT_ADD_METADATA_MARK));
+        supported.extend([0, 1]);
+        supported.extend(string("LWT_OPTIMIZATION_META_BIT_MASK=2147483648"));
+        let addr = scripted_node(vec![(0x06, supported), (0x02, vec![])]).await;
+        let manager = TcpConnectionsManager::new(addr, Arc::new(NoneAuthenticator));
+
+        let transport = manager.connect().await.unwrap();
+        assert_eq!(transport.lock().await.lwt_metadata_mask(), Some(i32::MIN));
+    }
+
+    #[derive(Default)]
+    struct CountingWrapper {
+        wrapped: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TransportWrapper for CountingWrapper {
+        async fn wrap(&self, stream: TcpStream) -> io::Result<Box<dyn TransportStream>> {
+            self.wrapped.fetch_add(1, Ordering::Relaxed);
+            Ok(Box::new(stream))
+        }
+    }
+
+    #[tokio::test]
+    async fn connections_are_wrapped() {
+        let addr = scripted_node(vec![(0x06, vec![0, 0]), (0x02, vec![])]).await;
+        let wrapper = Arc::new(CountingWrapper::default());
+        let manager = TcpConnectionsManager::new(addr, Arc::new(NoneAuthenticator))
+            .transport_wrapper(Some(wrapper.clone()));
+
+        // the startup handshake goes through the wrapped stream
+        assert!(manager.connect().await.is_ok());
+        assert_eq!(wrapper.wrapped.load(Ordering::Relaxed), 1);
+    }
+
+    fn authenticate_body(authenticator: &str) -> (u8, Vec<u8>) {
+        let mut body = (authenticator.len() as u16).to_be_bytes().to_vec();
+        body.extend(authenticator.as_bytes());
+        (0x03, body)
+    }
+
+    #[tokio::test]
+    async fn authentication_errors() {
+        const PASSWORD_AUTHENTICATOR: &str = "org.apache.cassandra.auth.PasswordAuthenticator";
+
+        let addr = scripted_node(vec![
+            (0x06, vec![0, 0]),
+            authenticate_body(PASSWORD_AUTHENTICATOR),
+        ])
+        .await;
+        match TcpConnectionsManager::new(addr.clone(), Arc::new(NoneAuthenticator))
+            .connect()
+            .await
+        {
+            Err(error::Error::MissingAuthenticator {
+                node,
+                server_authenticator,
+            }) => {
+                assert_eq!(node, addr);
+                assert_eq!(server_authenticator, PASSWORD_AUTHENTICATOR);
+            }
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let addr = scripted_node(vec![
+            (0x06, vec![0, 0]),
+            authenticate_body("com.example.TokenAuthenticator"),
+        ])
+        .await;
+        let authenticator = Arc::new(StaticPasswordAuthenticator::new("user", "password"));
+        match TcpConnectionsManager::new(addr, authenticator.clone())
+            .connect()
+            .await
+        {
+            Err(error::Error::UnsupportedAuthenticator {
+                server_authenticator,
+                client_authenticator,
+                ..
+            }) => {
+                assert_eq!(server_authenticator, "com.example.TokenAuthenticator");
+                assert_eq!(client_authenticator, PASSWORD_AUTHENTICATOR);
+            }
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let addr = scripted_node(vec![
+            (0x06, vec![0, 0]),
+            authenticate_body(PASSWORD_AUTHENTICATOR),
+            error_body(
+                0x0100,
+                "Provided username user and/or password are incorrect",
+            ),
+        ])
+        .await;
+        match TcpConnectionsManager::new(addr, authenticator)
+            .connect()
+            .await
+        {
+            Err(error::Error::AuthenticationFailed { message, .. }) => {
+                assert_eq!(
+                    message,
+                    "Provided username user and/or password are incorrect"
+                );
+            }
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_authenticates_by_node_class() {
+        let addr = scripted_node(vec![
+            (0x06, vec![0, 0]),
+            authenticate_body("com.datastax.bdp.cassandra.auth.DseAuthenticator"),
+            (0x10, vec![0xff, 0xff, 0xff, 0xff]),
+        ])
+        .await;
+        let registry = AuthenticatorRegistry::new()
+            .with_authenticator(Arc::new(StaticPasswordAuthenticator::new(
+                "user", "password",
+            )))
+            .with_authenticator_for(
+                "DseAuthenticator",
+                Arc::new(StaticPasswordAuthenticator::new("dse", "password")),
+            );
+
+        TcpConnectionsManager::new(addr, Arc::new(registry))
+            .connect()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn overloaded_use_keyspace_is_retried() {
+        let addr = scripted_node(vec![
+            error_body(0x1001, "Overloaded"),
+            set_keyspace_body("ks"),
+        ])
+        .await;
+        let (transport, keyspace_holder) = keyspace_connection(&addr).await;
+
+        validate(&transport, &keyspace_holder).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn broken_after_orphaned_streams() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let manager = TcpConnectionsManager::new(addr.as_str(), Arc::new(NoneAuthenticator))
+            .max_orphaned_streams(1);
+        let mut transport = Mutex::new(TransportTcp::new(&addr, Default::default()).await.unwrap());
+        assert!(manager.has_broken(&mut transport));
+        transport.get_mut().mark_ready();
+
+        transport.get_mut().record_orphaned_stream();
+        assert!(!manager.has_broken(&mut transport));
+
+        transport.get_mut().record_orphaned_stream();
+        assert!(manager.has_broken(&mut transport));
+
+        // drained responses stop counting
+        transport.get_mut().release_orphaned_stream();
+        assert!(!manager.has_broken(&mut transport));
+
+        let unlimited = TcpConnectionsManager::new(addr, Arc::new(NoneAuthenticator));
+        assert!(!unlimited.has_broken(&mut transport));
+    }
+
+    #[cfg(not(feature = "v3"))]
+    /// Starts a node supporting protocol v3 only, returning its address and version bytes of
+    /// all requests it received.
+    async fn v3_node() -> (String, Arc<std::sync::Mutex<Vec<u8>>>) {
+        use crate::cluster::test_node::{handshake, response_frame};
+
+        let versions = Arc::new(std::sync::Mutex::new(vec![]));
+
+        let received = versions.clone();
+        let node = TestNode::start_scripted(Arc::new(move |request: &Request| {
+            received.lock().unwrap().push(request.version);
+            if request.version != 0x03 {
+                // nodes reject other versions in their own and close the connection
+                let (opcode, body) = error_body(0x000A, "Invalid or unsupported protocol version");
+                return vec![
+                    Reply::Raw(response_frame(
+                        0x03,
+                        request.stream,
+                        Opcode::from(opcode),
+                        &body,
+                    )),
+                    Reply::Close,
+                ];
+            }
+
+            match request.opcode {
+                Opcode::Query => vec![Reply::Frame(Opcode::Result, vec![0, 0, 0, 1])],
+                ref opcode => handshake(opcode).into_iter().collect(),
+            }
+        }))
+        .await;
+
+        (node.addr.to_string(), versions)
+    }
+
+    #[cfg(not(feature = "v3"))]
+    #[tokio::test]
+    async fn falls_back_to_lower_protocol_version() {
+        let (addr, versions) = v3_node().await;
+        let manager = TcpConnectionsManager::new(addr, Arc::new(NoneAuthenticator));
+
+        let transport = manager.connect().await.unwrap();
+        assert_eq!(
+            transport.lock().await.protocol_version(),
+            ProtocolVersion::V3
+        );
+        assert_eq!(*versions.lock().unwrap(), vec![0x04, 0x03, 0x03]);
+
+        // new connections start with the negotiated version
+        manager.connect().await.unwrap();
+        assert_eq!(
+            *versions.lock().unwrap(),
+            vec![0x04, 0x03, 0x03, 0x03, 0x03]
+        );
+
+        validate(&transport, &KeyspaceHolder::default())
+            .await
+            .unwrap();
+        assert_eq!(versions.lock().unwrap().last(), Some(&0x03));
+    }
+
+    #[cfg(not(feature = "v3"))]
+    #[tokio::test]
+    async fn requests_use_negotiated_protocol_version() {
+        use crate::cluster::DirectSession;
+        use crate::query::QueryExecutor;
+        use crate::types::value::Value;
+
+        let (addr, versions) = v3_node().await;
+        let session = DirectSession::connect(addr).await.unwrap();
+
+        session.query("SELECT").await.unwrap();
+        assert_eq!(versions.lock().unwrap().last(), Some(&0x03));
+
+        // unset values are not sent as nulls
+        let requests = versions.lock().unwrap().len();
+        assert!(session
+            .query_with_values("INSERT", vec![Value::new_not_set()])
+            .await
+            .is_err());
+        assert_eq!(versions.lock().unwrap().len(), requests);
+    }
 }
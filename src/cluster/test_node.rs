@@ -0,0 +1,175 @@
+//! Fake nodes answering requests of tests over TCP.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::frame::{AsByte, Opcode};
+
+/// Answers a request with given opcode and body, or leaves it unanswered with `None`.
+pub(crate) type Answer = fn(Opcode, &[u8]) -> Option<(Opcode, Vec<u8>)>;
+
+/// Replies to every request of a node, handshakes included. Requests without replies are left
+/// unanswered.
+pub(crate) type Script = Arc<dyn Fn(&Request) -> Vec<Reply> + Send + Sync>;
+
+/// Request received by a node, along with the number of the connection it arrived on.
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub(crate) connection: usize,
+    /// Protocol version byte of the request header.
+    pub(crate) version: u8,
+    pub(crate) stream: i16,
+    pub(crate) opcode: Opcode,
+    pub(crate) body: Vec<u8>,
+}
+
+/// Reply of a scripted node to a request.
+#[derive(Debug, Clone)]
+pub(crate) enum Reply {
+    /// Response with given opcode and body, in the protocol version of the request.
+    Frame(Opcode, Vec<u8>),
+    /// Response held back until the next response on the connection has been sent, which
+    /// answers requests out of order.
+    Held(Opcode, Vec<u8>),
+    /// Bytes written as they are, e.g. malformed frames.
+    Raw(Vec<u8>),
+    /// Closes the connection.
+    #[cfg_attr(feature = "v3", allow(dead_code))]
+    Close,
+}
+
+/// Node accepting any number of connections. Requests are answered by its `Script`, and all
+/// requests other than handshakes are recorded.
+pub(crate) struct TestNode {
+    pub(crate) addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+/// Answers all requests with a void result.
+pub(crate) fn void(_: Opcode, _: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+    Some((Opcode::Result, vec![0, 0, 0, 1]))
+}
+
+/// Answers OPTIONS and STARTUP requests, accepting connections without authentication.
+pub(crate) fn handshake(opcode: &Opcode) -> Option<Reply> {
+    match opcode {
+        Opcode::Startup => Some(Reply::Frame(Opcode::Ready, vec![])),
+        Opcode::Options => Some(Reply::Frame(Opcode::Supported, vec![0, 0])),
+        _ => None,
+    }
+}
+
+/// Encodes a response frame with given header fields and body.
+pub(crate) fn response_frame(version: u8, stream: i16, opcode: Opcode, body: &[u8]) -> Vec<u8> {
+    let mut frame = vec![version | 0x80, 0];
+    frame.extend_from_slice(&stream.to_be_bytes());
+    frame.push(opcode.as_byte());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(body);
+    frame
+}
+
+impl TestNode {
+    /// Starts a node answering requests with a void result.
+    pub(crate) async fn start() -> Self {
+        Self::start_with(void).await
+    }
+
+    /// Starts a node answering handshakes itself and all other requests with `answer`.
+    pub(crate) async fn start_with(answer: Answer) -> Self {
+        Self::start_scripted(Arc::new(move |request: &Request| {
+            match handshake(&request.opcode) {
+                Some(reply) => vec![reply],
+                None => answer(request.opcode.clone(), &request.body)
+                    .map(|(opcode, body)| Reply::Frame(opcode, body))
+                    .into_iter()
+                    .collect(),
+            }
+        }))
+        .await
+    }
+
+    /// Starts a node answering all requests, handshakes included, with `script`.
+    pub(crate) async fn start_scripted(script: Script) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node = TestNode {
+            addr: listener.local_addr().unwrap(),
+            requests: Default::default(),
+        };
+
+        let requests = node.requests.clone();
+        tokio::spawn(async move {
+            let mut connection = 0;
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, connection, script.clone(), requests.clone()));
+                connection += 1;
+            }
+        });
+
+        node
+    }
+
+    /// Returns received requests other than handshakes, in order of arrival.
+    pub(crate) fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    connection: usize,
+    script: Script,
+    requests: Arc<Mutex<Vec<Request>>>,
+) {
+    let mut held = vec![];
+    let mut header = [0; 9];
+    while stream.read_exact(&mut header).await.is_ok() {
+        let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+        let mut body = vec![0; length as usize];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+
+        let request = Request {
+            connection,
+            version: header[0],
+            stream: i16::from_be_bytes([header[2], header[3]]),
+            opcode: Opcode::from(header[4]),
+            body,
+        };
+        let replies = script(&request);
+        if handshake(&request.opcode).is_none() {
+            requests.lock().unwrap().push(request.clone());
+        }
+
+        for reply in replies {
+            let response = match reply {
+                Reply::Frame(opcode, body) => {
+                    let mut response =
+                        response_frame(request.version, request.stream, opcode, &body);
+                    for held in held.drain(..) {
+                        response.extend(held);
+                    }
+                    response
+                }
+                Reply::Held(opcode, body) => {
+                    held.push(response_frame(
+                        request.version,
+                        request.stream,
+                        opcode,
+                        &body,
+                    ));
+                    continue;
+                }
+                Reply::Raw(bytes) => bytes,
+                Reply::Close => return,
+            };
+
+            if stream.write_all(&response).await.is_err() {
+                return;
+            }
+        }
+    }
+}
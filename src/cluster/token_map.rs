@@ -0,0 +1,467 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::error;
+use crate::token::{Partitioner, Token};
+use crate::types::list::List;
+use crate::types::map::Map;
+use crate::types::rows::Row;
+use crate::types::{AsRustType, IntoRustByName};
+
+/// Replication strategy of a keyspace, as stored in `system_schema.keyspaces`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationStrategy {
+    SimpleStrategy {
+        replication_factor: usize,
+    },
+    NetworkTopologyStrategy {
+        datacenter_replication_factors: HashMap<String, usize>,
+    },
+    /// Other strategies, e.g. `LocalStrategy`. Only the token owner is considered a replica.
+    Other,
+}
+
+impl ReplicationStrategy {
+    /// Parses replication options of a keyspace.
+    pub fn from_replication(replication: &HashMap<String, String>) -> Self {
+        let class = replication.get("class").map(String::as_str).unwrap_or("");
+
+        if class.ends_with("NetworkTopologyStrategy") {
+            let datacenter_replication_factors = replication
+                .iter()
+                .filter(|(key, _)| key.as_str() != "class")
+                .filter_map(|(datacenter, factor)| {
+                    Self::parse_factor(factor).map(|factor| (datacenter.clone(), factor))
+                })
+                .collect();
+
+            ReplicationStrategy::NetworkTopologyStrategy {
+                datacenter_replication_factors,
+            }
+        } else if class.ends_with("SimpleStrategy") {
+            ReplicationStrategy::SimpleStrategy {
+                replication_factor: replication
+                    .get("replication_factor")
+                    .and_then(|factor| Self::parse_factor(factor))
+                    .unwrap_or(1),
+            }
+        } else {
+            ReplicationStrategy::Other
+        }
+    }
+
+    // transient replication uses "<all replicas>/<transient replicas>" format
+    fn parse_factor(factor: &str) -> Option<usize> {
+        factor.split('/').next()?.trim().parse().ok()
+    }
+}
+
+/// Node of the token ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenRingNode {
+    pub addr: SocketAddr,
+    pub datacenter: String,
+    pub rack: String,
+    pub tokens: Vec<Token>,
+}
+
+impl TokenRingNode {
+    /// Reads a node from a `system.local` or `system.peers` row. Peers are addressed by their
+    /// `rpc_address`, falling back to `peer` if it is unspecified, and given port. Tokens are
+    /// parsed for given partitioner; without one, they are not read.
+    pub(crate) fn from_row(
+        row: &Row,
+        addr: Option<SocketAddr>,
+        port: u16,
+        partitioner: Option<Partitioner>,
+    ) -> error::Result<Self> {
+        let addr = match addr {
+            Some(addr) => addr,
+            None => {
+                let rpc_address: IpAddr = row.get_r_by_name("rpc_address")?;
+                let ip = if rpc_address.is_unspecified() {
+                    row.get_r_by_name("peer")?
+                } else {
+                    rpc_address
+                };
+
+                SocketAddr::new(ip, port)
+            }
+        };
+
+        let tokens: Vec<String> = match partitioner {
+            Some(_) => {
+                let tokens: List = row.get_r_by_name("tokens")?;
+                tokens.as_r_type()?
+            }
+            None => vec![],
+        };
+
+        Ok(TokenRingNode {
+            addr,
+            datacenter: row.get_r_by_name("data_center")?,
+            rack: row.get_r_by_name("rack")?,
+            tokens: tokens
+                .iter()
+                .map(|token| {
+                    partitioner
+                        .and_then(|partitioner| partitioner.parse_token(token))
+                        .ok_or_else(|| error::Error::from(format!("Invalid token {}", token)))
+                })
+                .collect::<error::Result<_>>()?,
+        })
+    }
+}
+
+/// Reads replication strategies from `system_schema.keyspaces` rows.
+pub(crate) fn keyspaces_from_rows(
+    rows: Vec<Row>,
+) -> error::Result<HashMap<String, ReplicationStrategy>> {
+    rows.iter()
+        .map(|row| {
+            let name: String = row.get_r_by_name("keyspace_name")?;
+            let replication: Map = row.get_r_by_name("replication")?;
+            let replication: HashMap<String, String> = replication.as_r_type()?;
+
+            Ok((name, ReplicationStrategy::from_replication(&replication)))
+        })
+        .collect()
+}
+
+/// Token ring of a cluster along with replication strategies of its keyspaces, used to compute
+/// replicas owning given partitions. Without a known partitioner, no replicas are computed, so
+/// requests are not routed by tokens.
+#[derive(Debug, Clone, Default)]
+pub struct TokenMap {
+    nodes: Vec<TokenRingNode>,
+    ring: Vec<(Token, usize)>,
+    keyspaces: HashMap<String, ReplicationStrategy>,
+    partitioner: Option<Partitioner>,
+}
+
+impl TokenMap {
+    /// Creates a token map of a cluster using the `Murmur3Partitioner`.
+    pub fn new(nodes: Vec<TokenRingNode>, keyspaces: HashMap<String, ReplicationStrategy>) -> Self {
+        Self::with_partitioner(Some(Partitioner::Murmur3), nodes, keyspaces)
+    }
+
+    /// Creates a token map of a cluster using given partitioner, if it is known.
+    pub fn with_partitioner(
+        partitioner: Option<Partitioner>,
+        nodes: Vec<TokenRingNode>,
+        keyspaces: HashMap<String, ReplicationStrategy>,
+    ) -> Self {
+        let mut ring: Vec<(Token, usize)> = match partitioner {
+            Some(_) => nodes
+                .iter()
+                .enumerate()
+                .flat_map(|(index, node)| {
+                    node.tokens.iter().map(move |token| (token.clone(), index))
+                })
+                .collect(),
+            None => vec![],
+        };
+        ring.sort_unstable();
+
+        TokenMap {
+            nodes,
+            ring,
+            keyspaces,
+            partitioner,
+        }
+    }
+
+    /// Returns the partitioner of the cluster, if it is known.
+    pub fn partitioner(&self) -> Option<Partitioner> {
+        self.partitioner
+    }
+
+    /// Returns nodes of the ring.
+    pub fn nodes(&self) -> &[TokenRingNode] {
+        &self.nodes
+    }
+
+    /// Returns replication strategy of given keyspace.
+    pub fn replication(&self, keyspace: &str) -> Option<&ReplicationStrategy> {
+        self.keyspaces.get(keyspace)
+    }
+
+    /// Returns addresses of replicas of given serialized partition key in given keyspace.
+    pub fn replicas_for(&self, keyspace: &str, partition_key: &[u8]) -> Vec<SocketAddr> {
+        match self.partitioner {
+            Some(partitioner) => self.replicas(keyspace, partitioner.token(partition_key)),
+            None => vec![],
+        }
+    }
+
+    /// Returns addresses of replicas of given token in given keyspace, token owner first.
+    /// Returns no replicas for unknown keyspaces.
+    pub fn replicas<T: Into<Token>>(&self, keyspace: &str, token: T) -> Vec<SocketAddr> {
+        if self.ring.is_empty() {
+            return vec![];
+        }
+
+        let token = token.into();
+        let start = self
+            .ring
+            .partition_point(|(ring_token, _)| *ring_token < token);
+        let replicas = match self.keyspaces.get(keyspace) {
+            Some(ReplicationStrategy::SimpleStrategy { replication_factor }) => {
+                self.simple_replicas(start, *replication_factor)
+            }
+            Some(ReplicationStrategy::NetworkTopologyStrategy {
+                datacenter_replication_factors,
+            }) => self.network_topology_replicas(start, datacenter_replication_factors),
+            Some(ReplicationStrategy::Other) => self.simple_replicas(start, 1),
+            None => vec![],
+        };
+
+        replicas
+            .into_iter()
+            .map(|index| self.nodes[index].addr)
+            .collect()
+    }
+
+    // walks the ring once, starting at given position
+    fn walk(&self, start: usize) -> impl Iterator<Item = usize> + '_ {
+        self.ring
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(self.ring.len())
+            .map(|(_, index)| *index)
+    }
+
+    fn simple_replicas(&self, start: usize, replication_factor: usize) -> Vec<usize> {
+        let mut replicas = Vec::with_capacity(replication_factor);
+        for index in self.walk(start) {
+            if replicas.len() >= replication_factor {
+                break;
+            }
+
+            if !replicas.contains(&index) {
+                replicas.push(index);
+            }
+        }
+
+        replicas
+    }
+
+    fn network_topology_replicas(
+        &self,
+        start: usize,
+        datacenter_replication_factors: &HashMap<String, usize>,
+    ) -> Vec<usize> {
+        let mut replicas = vec![];
+
+        // the datacenter of the token owner goes first, others follow in order of their names
+        let owner_datacenter = self
+            .ring
+            .get(start)
+            .map(|(_, index)| &self.nodes[*index].datacenter);
+        let mut datacenters: Vec<_> = datacenter_replication_factors.iter().collect();
+        datacenters
+            .sort_by_key(|(datacenter, _)| (Some(*datacenter) != owner_datacenter, *datacenter));
+
+        for (datacenter, replication_factor) in datacenters {
+            let datacenter_nodes = self
+                .nodes
+                .iter()
+                .filter(|node| &node.datacenter == datacenter);
+            let racks: HashSet<&str> = datacenter_nodes
+                .clone()
+                .map(|node| node.rack.as_str())
+                .collect();
+            let replication_factor = (*replication_factor).min(datacenter_nodes.count());
+
+            // replicas are placed on distinct racks first, and on nodes skipped because their
+            // rack already had a replica once all racks are used
+            let mut datacenter_replicas = Vec::with_capacity(replication_factor);
+            let mut seen_racks = HashSet::new();
+            let mut skipped = vec![];
+
+            for index in self.walk(start) {
+                if datacenter_replicas.len() >= replication_factor {
+                    break;
+                }
+
+                let node = &self.nodes[index];
+                if &node.datacenter != datacenter
+                    || datacenter_replicas.contains(&index)
+                    || skipped.contains(&index)
+                {
+                    continue;
+                }
+
+                if seen_racks.insert(node.rack.as_str()) {
+                    datacenter_replicas.push(index);
+
+                    if seen_racks.len() == racks.len() {
+                        let missing = replication_factor.saturating_sub(datacenter_replicas.len());
+                        datacenter_replicas.extend(skipped.drain(..).take(missing));
+                    }
+                } else if seen_racks.len() == racks.len() {
+                    datacenter_replicas.push(index);
+                } else {
+                    skipped.push(index);
+                }
+            }
+
+            replicas.extend(datacenter_replicas);
+        }
+
+        replicas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(port: u16, datacenter: &str, rack: &str, tokens: Vec<i64>) -> TokenRingNode {
+        TokenRingNode {
+            addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            datacenter: datacenter.into(),
+            rack: rack.into(),
+            tokens: tokens.into_iter().map(Token::from).collect(),
+        }
+    }
+
+    fn ports(replicas: Vec<SocketAddr>) -> Vec<u16> {
+        replicas.iter().map(SocketAddr::port).collect()
+    }
+
+    #[test]
+    fn parse_replication() {
+        let replication = vec![
+            (
+                "class",
+                "org.apache.cassandra.locator.NetworkTopologyStrategy",
+            ),
+            ("dc1", "3"),
+            ("dc2", "2/1"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        match ReplicationStrategy::from_replication(&replication) {
+            ReplicationStrategy::NetworkTopologyStrategy {
+                datacenter_replication_factors,
+            } => {
+                assert_eq!(datacenter_replication_factors.get("dc1"), Some(&3));
+                assert_eq!(datacenter_replication_factors.get("dc2"), Some(&2));
+            }
+            strategy => panic!("unexpected strategy {:?}", strategy),
+        }
+    }
+
+    #[test]
+    fn simple_strategy_replicas() {
+        let mut keyspaces = HashMap::new();
+        keyspaces.insert(
+            "ks".to_string(),
+            ReplicationStrategy::SimpleStrategy {
+                replication_factor: 2,
+            },
+        );
+        let token_map = TokenMap::new(
+            vec![
+                node(1, "dc1", "r1", vec![-100, 100]),
+                node(2, "dc1", "r1", vec![0]),
+                node(3, "dc1", "r1", vec![50]),
+            ],
+            keyspaces,
+        );
+
+        assert_eq!(ports(token_map.replicas("ks", -50)), vec![2, 3]);
+        assert_eq!(ports(token_map.replicas("ks", 60)), vec![1, 2]);
+        assert_eq!(ports(token_map.replicas("ks", 200)), vec![1, 2]);
+        assert!(token_map.replicas("unknown", 0).is_empty());
+    }
+
+    #[test]
+    fn network_topology_strategy_replicas() {
+        let mut factors = HashMap::new();
+        factors.insert("dc1".to_string(), 2);
+        factors.insert("dc2".to_string(), 1);
+
+        let mut keyspaces = HashMap::new();
+        keyspaces.insert(
+            "ks".to_string(),
+            ReplicationStrategy::NetworkTopologyStrategy {
+                datacenter_replication_factors: factors,
+            },
+        );
+        let token_map = TokenMap::new(
+            vec![
+                node(1, "dc1", "r1", vec![0]),
+                node(2, "dc1", "r1", vec![10]),
+                node(3, "dc2", "r1", vec![20]),
+                node(4, "dc1", "r2", vec![30]),
+            ],
+            keyspaces,
+        );
+
+        // node 2 is skipped in favour of node 4 on a different rack
+        assert_eq!(ports(token_map.replicas("ks", 0)), vec![1, 4, 3]);
+        // the owner's datacenter goes first
+        assert_eq!(ports(token_map.replicas("ks", 15)), vec![3, 4, 1]);
+        // without another rack left, the skipped node is used
+        assert_eq!(ports(token_map.replicas("ks", 5)), vec![2, 4, 3]);
+        assert_eq!(ports(token_map.replicas("ks", 25)), vec![4, 1, 3]);
+    }
+
+    #[test]
+    fn network_topology_strategy_limits_replicas_to_datacenter_size() {
+        let mut factors = HashMap::new();
+        factors.insert("dc1".to_string(), 5);
+        factors.insert("dc3".to_string(), 1);
+
+        let mut keyspaces = HashMap::new();
+        keyspaces.insert(
+            "ks".to_string(),
+            ReplicationStrategy::NetworkTopologyStrategy {
+                datacenter_replication_factors: factors,
+            },
+        );
+        let token_map = TokenMap::new(
+            vec![
+                node(1, "dc1", "r1", vec![0]),
+                node(2, "dc2", "r1", vec![10]),
+                node(3, "dc1", "r1", vec![20]),
+            ],
+            keyspaces,
+        );
+
+        // dc3 has no nodes and dc2 is not replicated to
+        assert_eq!(ports(token_map.replicas("ks", 5)), vec![3, 1]);
+    }
+
+    #[test]
+    fn byte_ordered_replicas() {
+        let mut keyspaces = HashMap::new();
+        keyspaces.insert(
+            "ks".to_string(),
+            ReplicationStrategy::SimpleStrategy {
+                replication_factor: 1,
+            },
+        );
+        let node = |port: u16, token: &[u8]| TokenRingNode {
+            tokens: vec![Token::ByteOrdered(token.to_vec())],
+            ..node(port, "dc1", "r1", vec![])
+        };
+        let token_map = TokenMap::with_partitioner(
+            Some(Partitioner::ByteOrdered),
+            vec![node(1, b"g"), node(2, b"p")],
+            keyspaces.clone(),
+        );
+
+        assert_eq!(ports(token_map.replicas_for("ks", b"apple")), vec![1]);
+        assert_eq!(ports(token_map.replicas_for("ks", b"kiwi")), vec![2]);
+        assert_eq!(ports(token_map.replicas_for("ks", b"quince")), vec![1]);
+
+        let unknown = TokenMap::with_partitioner(None, vec![node(1, b"g")], keyspaces);
+        assert!(unknown.replicas_for("ks", b"apple").is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of buckets of `WaitHistogram`, in microseconds. Waits longer than the last
+/// bound are counted in an additional, unbounded bucket.
+const BUCKET_BOUNDS_MICROS: [u64; 12] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+
+/// Histogram of times requests spent waiting for a free connection of a node, with fixed
+/// buckets from 100µs to 1s. Waits are recorded whether or not a connection was obtained,
+/// so timeouts waiting for a connection show up in the last buckets.
+#[derive(Debug, Default)]
+pub struct WaitHistogram {
+    counts: [AtomicU64; BUCKET_BOUNDS_MICROS.len() + 1],
+    total_micros: AtomicU64,
+}
+
+impl WaitHistogram {
+    pub(crate) fn record(&self, wait: Duration) {
+        let micros = wait.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = BUCKET_BOUNDS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MICROS.len());
+
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Number of recorded waits.
+    pub fn count(&self) -> u64 {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Sum of all recorded waits.
+    pub fn total(&self) -> Duration {
+        Duration::from_micros(self.total_micros.load(Ordering::Relaxed))
+    }
+
+    /// Returns upper bounds of buckets with numbers of waits in each of them, not cumulative.
+    /// The bound of the last bucket is `None`.
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        BUCKET_BOUNDS_MICROS
+            .iter()
+            .map(|bound| Some(Duration::from_micros(*bound)))
+            .chain(std::iter::once(None))
+            .zip(
+                self.counts
+                    .iter()
+                    .map(|count| count.load(Ordering::Relaxed)),
+            )
+            .collect()
+    }
+
+    /// Returns the upper bound of the bucket containing given quantile, e.g. `0.99`, or `None`
+    /// if nothing was recorded. Quantiles in the unbounded bucket are reported as
+    /// `Duration::MAX`.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let buckets = self.buckets();
+        let count: u64 = buckets.iter().map(|(_, count)| count).sum();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        buckets
+            .into_iter()
+            .find(|(_, count)| {
+                seen += count;
+                seen >= rank
+            })
+            .map(|(bound, _)| bound.unwrap_or(Duration::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_waits() {
+        let histogram = WaitHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for _ in 0..98 {
+            histogram.record(Duration::from_micros(50));
+        }
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_secs(2));
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(
+            histogram.total(),
+            Duration::from_micros(98 * 50 + 3_000 + 2_000_000)
+        );
+        assert_eq!(
+            histogram.buckets()[0],
+            (Some(Duration::from_micros(100)), 98)
+        );
+        assert_eq!(histogram.buckets()[5].1, 1);
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(100)));
+        assert_eq!(histogram.quantile(0.99), Some(Duration::from_millis(5)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::MAX));
+    }
+}
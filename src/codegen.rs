@@ -0,0 +1,480 @@
+//! Generation of Rust structs mapping rows of tables, with `TryFromRow` and `IntoQueryValues`
+//! implementations, e.g. from a build script:
+//!
+//! ```no_run
+//! # async fn run(session: &cdrs_tokio::cluster::DirectSession) -> cdrs_tokio::Result<()> {
+//! let code = cdrs_tokio::codegen::generate_from_cluster(session, "ks", &["users"]).await?;
+//! std::fs::write(format!("{}/schema.rs", std::env::var("OUT_DIR").unwrap()), code)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Columns of the primary key are mapped to plain types and other columns to `Option`s, except
+//! collections, which are empty if null. Generated code refers to `uuid::Uuid` for `uuid`
+//! columns, so crates using it need to depend on `uuid`. Tables with columns of types without
+//! a mapping, e.g. user defined types, tuples or nested collections, are rejected.
+
+use std::fmt::Write;
+
+#[cfg(feature = "runtime")]
+use tokio::sync::Mutex;
+
+use crate::error;
+#[cfg(feature = "runtime")]
+use crate::query::QueryExecutor;
+use crate::schema::{ColumnSchema, SchemaSnapshot, TableSchema};
+#[cfg(feature = "runtime")]
+use crate::transport::CDRSTransport;
+
+const HEADER: &str = "// Generated by cdrs_tokio::codegen. Do not edit.\n";
+
+/// Generates code of given tables.
+pub fn generate(tables: &[TableSchema]) -> error::Result<String> {
+    let mut code = HEADER.to_string();
+    for table in tables {
+        code.push('\n');
+        code.push_str(&generate_table(table)?);
+    }
+
+    Ok(code)
+}
+
+/// Reads given tables of a keyspace from the cluster and generates their code.
+#[cfg(feature = "runtime")]
+pub async fn generate_from_cluster<S, T, M>(
+    session: &S,
+    keyspace: &str,
+    tables: &[&str],
+) -> error::Result<String>
+where
+    S: QueryExecutor<T, M>,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let mut schemas = Vec::with_capacity(tables.len());
+    for table in tables {
+        schemas.push(TableSchema::read(session, keyspace, table).await?);
+    }
+
+    generate(&schemas)
+}
+
+/// Generates code of given tables of a keyspace from a snapshot, e.g. imported with
+/// `SchemaSnapshot::import` without a live cluster.
+pub fn generate_from_snapshot(
+    snapshot: &SchemaSnapshot,
+    keyspace: &str,
+    tables: &[&str],
+) -> error::Result<String> {
+    let schemas = tables
+        .iter()
+        .map(|table| {
+            snapshot
+                .table(keyspace, table)
+                .cloned()
+                .ok_or_else(|| format!("Unknown table {}.{}", keyspace, table).into())
+        })
+        .collect::<error::Result<Vec<_>>>()?;
+
+    generate(&schemas)
+}
+
+/// Generates the struct of a single table along with its trait implementations. The struct is
+/// named after the table in upper camel case.
+pub fn generate_table(table: &TableSchema) -> error::Result<String> {
+    let struct_name = struct_name(&table.name);
+    let qualified_table = format!(
+        "{}.{}",
+        cql_identifier(&table.keyspace),
+        cql_identifier(&table.name)
+    );
+    let fields = table
+        .columns
+        .iter()
+        .map(|column| {
+            RustType::parse(&column.cql_type)
+                .map(|rust_type| (column, field_name(&column.name), rust_type))
+                .ok_or_else(|| {
+                    error::Error::from(format!(
+                        "Unsupported type {} of column {} of table {}",
+                        column.cql_type, column.name, qualified_table
+                    ))
+                })
+        })
+        .collect::<error::Result<Vec<(&ColumnSchema, String, RustType)>>>()?;
+
+    let mut code = String::new();
+
+    // writing to a String cannot fail
+    let _ = writeln!(code, "/// Row of `{}`.", qualified_table);
+    code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    let _ = writeln!(code, "pub struct {} {{", struct_name);
+    for (column, field, rust_type) in &fields {
+        let _ = writeln!(
+            code,
+            "    pub {}: {},",
+            field,
+            rust_type.field_type(column.kind.is_primary_key())
+        );
+    }
+    code.push_str("}\n\n");
+
+    let column_list = fields
+        .iter()
+        .map(|(column, _, _)| cql_identifier(&column.name))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let markers = vec!["?"; fields.len()].join(", ");
+    let _ = writeln!(code, "impl {} {{", struct_name);
+    let _ = writeln!(
+        code,
+        "    pub const TABLE: &'static str = {:?};",
+        qualified_table
+    );
+    let _ = writeln!(
+        code,
+        "    pub const INSERT: &'static str = {:?};",
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            qualified_table, column_list, markers
+        )
+    );
+    code.push_str("}\n\n");
+
+    let _ = writeln!(
+        code,
+        "impl cdrs_tokio::frame::TryFromRow for {} {{",
+        struct_name
+    );
+    code.push_str(
+        "    fn try_from_row(row: cdrs_tokio::types::rows::Row) -> cdrs_tokio::error::Result<Self> {\n",
+    );
+    code.push_str("        use cdrs_tokio::types::IntoRustByName;\n\n");
+    let _ = writeln!(code, "        Ok({} {{", struct_name);
+    for (column, field, rust_type) in &fields {
+        let _ = writeln!(
+            code,
+            "            {}: {},",
+            field,
+            rust_type.read_expression(&column.name, column.kind.is_primary_key())
+        );
+    }
+    code.push_str("        })\n    }\n}\n\n");
+
+    let _ = writeln!(
+        code,
+        "impl cdrs_tokio::frame::IntoQueryValues for {} {{",
+        struct_name
+    );
+    code.push_str("    fn into_query_values(self) -> cdrs_tokio::query::QueryValues {\n");
+    code.push_str("        let mut values = std::collections::HashMap::new();\n");
+    for (column, field, _) in &fields {
+        let _ = writeln!(
+            code,
+            "        values.insert({:?}.to_string(), cdrs_tokio::types::value::Value::from(self.{}));",
+            column.name, field
+        );
+    }
+    code.push_str("        cdrs_tokio::query::QueryValues::NamedValues(values)\n    }\n}\n");
+
+    Ok(code)
+}
+
+/// Rust type of a column.
+#[derive(Debug, Clone, PartialEq)]
+enum RustType {
+    Scalar(Scalar),
+    List(Scalar),
+    Map(Scalar, Scalar),
+}
+
+impl RustType {
+    fn parse(cql_type: &str) -> Option<Self> {
+        let cql_type = unfrozen(cql_type.trim());
+        match generic_arguments(cql_type) {
+            None => Scalar::parse(cql_type).map(RustType::Scalar),
+            Some((name, arguments)) => match (name, arguments.as_slice()) {
+                ("list", [item]) | ("set", [item]) => Scalar::parse(unfrozen(item))
+                    .filter(|item| item.in_collections)
+                    .map(RustType::List),
+                ("map", [key, value]) => {
+                    let key = Scalar::parse(unfrozen(key)).filter(|key| key.map_key)?;
+                    let value = Scalar::parse(unfrozen(value))
+                        .filter(|value| value.in_collections || key.any_map_value)?;
+                    Some(RustType::Map(key, value))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the type of the field of a column, wrapped in `Option` unless the column
+    /// cannot be null or is a collection.
+    fn field_type(&self, required: bool) -> String {
+        match self {
+            RustType::Scalar(scalar) if required => scalar.path.to_string(),
+            RustType::Scalar(scalar) => format!("Option<{}>", scalar.path),
+            RustType::List(item) => format!("Vec<{}>", item.path),
+            RustType::Map(key, value) => {
+                format!("std::collections::HashMap<{}, {}>", key.path, value.path)
+            }
+        }
+    }
+
+    /// Returns an expression reading the column from a `row`.
+    fn read_expression(&self, column: &str, required: bool) -> String {
+        let (raw_type, field_type) = match self {
+            RustType::Scalar(_) if required => {
+                return format!("row.get_r_by_name({:?})?", column);
+            }
+            RustType::Scalar(_) => return format!("row.get_opt_by_name({:?})?", column),
+            RustType::List(_) => ("cdrs_tokio::types::list::List", self.field_type(true)),
+            RustType::Map(_, _) => ("cdrs_tokio::types::map::Map", self.field_type(true)),
+        };
+
+        format!(
+            "row\n                .get_opt_by_name::<{raw}>({column:?})?\n                \
+             .map(|value| <{raw} as cdrs_tokio::types::AsRustType<{field}>>::as_r_type(&value))\n                \
+             .transpose()?\n                .unwrap_or_default()",
+            raw = raw_type,
+            column = column,
+            field = field_type
+        )
+    }
+}
+
+/// Rust type of a single value, along with the conversions of collections supporting it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scalar {
+    path: &'static str,
+    in_collections: bool,
+    map_key: bool,
+    // maps with some keys can't hold every type of values
+    any_map_value: bool,
+}
+
+impl Scalar {
+    fn parse(cql_type: &str) -> Option<Self> {
+        let (path, in_collections, map_key, any_map_value) = match cql_type {
+            "ascii" | "text" | "varchar" => ("String", true, true, true),
+            "bigint" | "counter" => ("i64", true, true, true),
+            "int" => ("i32", true, true, true),
+            "smallint" => ("i16", true, true, false),
+            "tinyint" => ("i8", true, true, false),
+            "boolean" => ("bool", true, true, false),
+            "float" => ("f32", true, false, false),
+            "double" => ("f64", true, false, false),
+            "uuid" | "timeuuid" => ("uuid::Uuid", true, true, true),
+            "inet" => ("std::net::IpAddr", true, true, true),
+            "blob" => ("cdrs_tokio::types::blob::Blob", true, true, false),
+            "decimal" => ("cdrs_tokio::types::decimal::Decimal", true, false, false),
+            "timestamp" => ("std::time::SystemTime", false, true, true),
+            _ => return None,
+        };
+
+        Some(Scalar {
+            path,
+            in_collections,
+            map_key,
+            any_map_value,
+        })
+    }
+}
+
+fn unfrozen(cql_type: &str) -> &str {
+    match generic_arguments(cql_type) {
+        Some(("frozen", arguments)) if arguments.len() == 1 => arguments[0],
+        _ => cql_type,
+    }
+}
+
+/// Splits a generic type like `map<text, int>` into its name and arguments.
+fn generic_arguments(cql_type: &str) -> Option<(&str, Vec<&str>)> {
+    let open = cql_type.find('<')?;
+    let inner = cql_type[open + 1..].strip_suffix('>')?;
+
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(inner[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(inner[start..].trim());
+
+    Some((cql_type[..open].trim(), arguments))
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+/// Returns a snake case field name of a column.
+fn field_name(column: &str) -> String {
+    let mut name = String::with_capacity(column.len());
+    let mut previous_lowercase = false;
+    for c in column.chars() {
+        if c.is_ascii_uppercase() && previous_lowercase {
+            name.push('_');
+        }
+        previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+        name.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '_'
+        });
+    }
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+
+    match name.as_str() {
+        "self" | "super" | "crate" => name + "_",
+        keyword if RUST_KEYWORDS.contains(&keyword) => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+/// Returns an upper camel case struct name of a table.
+fn struct_name(table: &str) -> String {
+    let name: String = field_name(table)
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("Table{}", name)
+    }
+}
+
+/// Returns an identifier as written in CQL, quoted unless it is a lowercase identifier.
+fn cql_identifier(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ColumnKind;
+
+    fn column(name: &str, cql_type: &str, kind: ColumnKind, position: i32) -> ColumnSchema {
+        ColumnSchema {
+            name: name.into(),
+            cql_type: cql_type.into(),
+            kind,
+            position,
+        }
+    }
+
+    fn user_events() -> TableSchema {
+        TableSchema::new(
+            "ks",
+            "user_events",
+            vec![
+                column("tags", "set<text>", ColumnKind::Regular, -1),
+                column("at", "timestamp", ColumnKind::Clustering, 0),
+                column("user_id", "uuid", ColumnKind::PartitionKey, 0),
+                column("type", "text", ColumnKind::Regular, -1),
+                column(
+                    "counts",
+                    "frozen<map<text, bigint>>",
+                    ColumnKind::Regular,
+                    -1,
+                ),
+                column("payloadSize", "int", ColumnKind::Static, -1),
+            ],
+        )
+    }
+
+    // the fixture is compiled by tests/codegen.rs
+    #[test]
+    fn generates_fixture() {
+        assert_eq!(
+            generate(&[user_events()]).unwrap(),
+            include_str!("../fixtures/codegen_user_events.rs")
+        );
+    }
+
+    #[test]
+    fn generates_from_snapshot() {
+        let snapshot = SchemaSnapshot::new(vec![user_events()]);
+
+        assert_eq!(
+            generate_from_snapshot(&snapshot, "ks", &["user_events"]).unwrap(),
+            generate(&[user_events()]).unwrap()
+        );
+        assert!(generate_from_snapshot(&snapshot, "ks", &["users"]).is_err());
+    }
+
+    #[test]
+    fn column_types() {
+        assert_eq!(
+            RustType::parse("frozen<list<inet>>").map(|rust_type| rust_type.field_type(false)),
+            Some("Vec<std::net::IpAddr>".to_string())
+        );
+        assert_eq!(
+            RustType::parse("map<int, timestamp>").map(|rust_type| rust_type.field_type(false)),
+            Some("std::collections::HashMap<i32, std::time::SystemTime>".to_string())
+        );
+        assert_eq!(RustType::parse("map<tinyint, timestamp>"), None);
+        assert_eq!(RustType::parse("map<double, int>"), None);
+        assert_eq!(RustType::parse("list<frozen<list<int>>>"), None);
+        assert_eq!(RustType::parse("frozen<address>"), None);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(field_name("userId"), "user_id");
+        assert_eq!(field_name("match"), "r#match");
+        assert_eq!(field_name("self"), "self_");
+        assert_eq!(field_name("1st value"), "_1st_value");
+        assert_eq!(struct_name("user_events"), "UserEvents");
+        assert_eq!(struct_name("2021_logs"), "Table2021Logs");
+        assert_eq!(cql_identifier("payloadSize"), "\"payloadSize\"");
+    }
+
+    #[test]
+    fn unsupported_column() {
+        let table = TableSchema::new(
+            "ks",
+            "t",
+            vec![
+                column("id", "int", ColumnKind::PartitionKey, 0),
+                column("location", "frozen<point>", ColumnKind::Regular, -1),
+            ],
+        );
+
+        assert!(generate_table(&table).is_err());
+    }
+}
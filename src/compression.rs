@@ -8,11 +8,12 @@
 //!by the server, messages can be compressed (including the response to the STARTUP
 //!request).
 
-use std::convert::From;
+use std::convert::{From, TryInto};
 use std::error::Error;
 use std::fmt;
 use std::io;
 use std::result;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use lz4_compress as lz4;
 use snap::raw::{Decoder, Encoder};
@@ -22,6 +23,10 @@ type Result<T> = result::Result<T, CompressionError>;
 pub const LZ4: &str = "lz4";
 pub const SNAPPY: &str = "snappy";
 
+/// Default maximum length of a decompressed frame body - 256 MiB, the maximum frame size
+/// accepted by Cassandra.
+pub const DEFAULT_MAX_DECOMPRESSED_LENGTH: usize = 256 * 1024 * 1024;
+
 /// It's an error which may occur during encoding or decoding
 /// frame body. As there are only two types of compressors it
 /// contains two related enum options.
@@ -31,6 +36,10 @@ pub enum CompressionError {
     Snappy(snap::Error),
     /// Lz4 error.
     Lz4(io::Error),
+    /// Declared length of decompressed data exceeds the allowed maximum.
+    DecompressedSizeExceeded { size: usize, max: usize },
+    /// Length of decompressed data differs from the declared one.
+    DecompressedSizeMismatch { expected: usize, actual: usize },
 }
 
 impl fmt::Display for CompressionError {
@@ -38,6 +47,16 @@ impl fmt::Display for CompressionError {
         match *self {
             CompressionError::Snappy(ref err) => write!(f, "Snappy Error: {:?}", err),
             CompressionError::Lz4(ref err) => write!(f, "Lz4 Error: {:?}", err),
+            CompressionError::DecompressedSizeExceeded { size, max } => write!(
+                f,
+                "Decompressed size {} exceeds maximum of {} bytes",
+                size, max
+            ),
+            CompressionError::DecompressedSizeMismatch { expected, actual } => write!(
+                f,
+                "Decompressed size {} differs from declared {} bytes",
+                actual, expected
+            ),
         }
     }
 }
@@ -47,6 +66,7 @@ impl Error for CompressionError {
         match *self {
             CompressionError::Snappy(ref err) => Some(err),
             CompressionError::Lz4(ref err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -84,7 +104,8 @@ impl Compression {
         }
     }
 
-    /// It decodes `bytes` basing on type of compression.
+    /// It decodes `bytes` basing on type of compression. Decompressed data cannot exceed
+    /// `DEFAULT_MAX_DECOMPRESSED_LENGTH`.
     ///
     /// # Examples
     ///
@@ -93,15 +114,22 @@ impl Compression {
     ///     let lz4_compression = Compression::Lz4;
     ///     let bytes = String::from("Hello World").into_bytes().to_vec();
     ///     let encoded = lz4_compression.encode(bytes.clone()).unwrap();
-    ///     let len = encoded.len() as u8;
+    ///     let len = bytes.len() as u8;
     ///     let mut input = vec![0, 0, 0, len];
     ///     input.extend_from_slice(encoded.as_slice());
     ///     assert_eq!(lz4_compression.decode(input).unwrap(), bytes);
     /// ```
     pub fn decode(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        self.decode_with_limit(bytes, DEFAULT_MAX_DECOMPRESSED_LENGTH)
+    }
+
+    /// It decodes `bytes` basing on type of compression. The declared length of decompressed
+    /// data is checked against `max_len` before any allocation and against the actual length
+    /// after decompression.
+    pub fn decode_with_limit(&self, bytes: Vec<u8>, max_len: usize) -> Result<Vec<u8>> {
         match *self {
-            Compression::Lz4 => Compression::decode_lz4(bytes),
-            Compression::Snappy => Compression::decode_snappy(bytes),
+            Compression::Lz4 => Compression::decode_lz4(bytes, max_len),
+            Compression::Snappy => Compression::decode_snappy(bytes, max_len),
             Compression::None => Ok(bytes),
         }
     }
@@ -122,7 +150,10 @@ impl Compression {
             .map_err(CompressionError::Snappy)
     }
 
-    fn decode_snappy(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    fn decode_snappy(bytes: Vec<u8>, max_len: usize) -> Result<Vec<u8>> {
+        let len = snap::raw::decompress_len(bytes.as_slice()).map_err(CompressionError::Snappy)?;
+        Compression::check_len(len, max_len)?;
+
         let mut decoder = Decoder::new();
         decoder
             .decompress_vec(bytes.as_slice())
@@ -133,11 +164,85 @@ impl Compression {
         lz4::compress(bytes.as_slice())
     }
 
-    fn decode_lz4(bytes: Vec<u8>) -> Result<Vec<u8>> {
-        // skip first 4 bytes in accordance to
+    fn decode_lz4(bytes: Vec<u8>, max_len: usize) -> Result<Vec<u8>> {
+        // first 4 bytes contain decompressed length in accordance to
         // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L805
-        lz4::decompress(&bytes[4..])
-            .map_err(|error| CompressionError::Lz4(io::Error::new(io::ErrorKind::Other, error)))
+        let len: [u8; 4] = bytes
+            .get(..4)
+            .and_then(|len| len.try_into().ok())
+            .ok_or_else(|| {
+                CompressionError::Lz4(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Missing decompressed length",
+                ))
+            })?;
+        let len = u32::from_be_bytes(len) as usize;
+        Compression::check_len(len, max_len)?;
+
+        let decompressed = lz4::decompress(&bytes[4..])
+            .map_err(|error| CompressionError::Lz4(io::Error::new(io::ErrorKind::Other, error)))?;
+        if decompressed.len() != len {
+            return Err(CompressionError::DecompressedSizeMismatch {
+                expected: len,
+                actual: decompressed.len(),
+            });
+        }
+
+        Ok(decompressed)
+    }
+
+    fn check_len(len: usize, max_len: usize) -> Result<()> {
+        if len > max_len {
+            return Err(CompressionError::DecompressedSizeExceeded {
+                size: len,
+                max: max_len,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of decompressed frames received over a single connection.
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+    frames: AtomicU64,
+    compressed_bytes: AtomicU64,
+    decompressed_bytes: AtomicU64,
+}
+
+impl CompressionStats {
+    pub(crate) fn record(&self, compressed_bytes: usize, decompressed_bytes: usize) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.compressed_bytes
+            .fetch_add(compressed_bytes as u64, Ordering::Relaxed);
+        self.decompressed_bytes
+            .fetch_add(decompressed_bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Number of decompressed frames.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Total size of compressed frame bodies.
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Total size of frame bodies after decompression.
+    pub fn decompressed_bytes(&self) -> u64 {
+        self.decompressed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Ratio of compressed to decompressed size, or `None` if nothing was decompressed yet.
+    pub fn ratio(&self) -> Option<f64> {
+        let decompressed_bytes = self.decompressed_bytes();
+        if decompressed_bytes == 0 {
+            return None;
+        }
+
+        Some(self.compressed_bytes() as f64 / decompressed_bytes as f64)
     }
 }
 
@@ -215,12 +320,57 @@ mod tests {
         let lz4_compression = Compression::Lz4;
         let bytes = String::from("Hello World").into_bytes().to_vec();
         let encoded = lz4_compression.encode(bytes.clone()).unwrap();
-        let len = encoded.len() as u8;
+        let len = bytes.len() as u8;
         let mut input = vec![0, 0, 0, len];
         input.extend_from_slice(encoded.as_slice());
         assert_eq!(lz4_compression.decode(input).unwrap(), bytes);
     }
 
+    #[test]
+    fn test_compression_decode_lz4_size_checks() {
+        let lz4_compression = Compression::Lz4;
+        let bytes = String::from("Hello World").into_bytes().to_vec();
+        let encoded = lz4_compression.encode(bytes.clone()).unwrap();
+
+        let mut input = vec![0, 0, 0, bytes.len() as u8 + 1];
+        input.extend_from_slice(encoded.as_slice());
+        assert!(matches!(
+            lz4_compression.decode(input),
+            Err(CompressionError::DecompressedSizeMismatch { .. })
+        ));
+
+        let mut input = vec![0x7f, 0xff, 0xff, 0xff];
+        input.extend_from_slice(encoded.as_slice());
+        assert!(matches!(
+            lz4_compression.decode(input),
+            Err(CompressionError::DecompressedSizeExceeded { .. })
+        ));
+
+        assert!(lz4_compression.decode(vec![0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_compression_decode_snappy_with_limit() {
+        let snappy_compression = Compression::Snappy;
+        let bytes = String::from("Hello World").into_bytes().to_vec();
+        let encoded = snappy_compression.encode(bytes).unwrap();
+        assert!(matches!(
+            snappy_compression.decode_with_limit(encoded, 5),
+            Err(CompressionError::DecompressedSizeExceeded { size: 11, max: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_compression_stats() {
+        let stats = CompressionStats::default();
+        assert_eq!(stats.ratio(), None);
+
+        stats.record(25, 100);
+        stats.record(25, 100);
+        assert_eq!(stats.frames(), 2);
+        assert_eq!(stats.ratio(), Some(0.25));
+    }
+
     #[test]
     fn test_compression_encode_none() {
         let none_compression = Compression::None;
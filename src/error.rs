@@ -2,6 +2,7 @@ use std::error;
 use std::fmt;
 use std::fmt::Display;
 use std::io;
+use std::net::SocketAddr;
 use std::result;
 use std::string::FromUtf8Error;
 
@@ -30,6 +31,54 @@ pub enum Error {
     Compression(CompressionError),
     /// Server error.
     Server(CDRSError),
+    /// Malformed frame was received. The connection it was received over has been closed,
+    /// since its stream cannot be resynchronized, and will be replaced by the pool.
+    ConnectionPoisoned(Box<Error>),
+    /// All connections to the node are in use and its pool cannot grow. Returned only by
+    /// requests which shouldn't wait for a connection.
+    PoolExhausted(SocketAddr),
+    /// The node replied that it is overloaded. Returned only by requests which shouldn't wait
+    /// for a connection.
+    Overloaded(SocketAddr),
+    /// Numeric value read from a column does not fit into requested type.
+    NumericOverflow {
+        column: String,
+        value: i64,
+        target_type: &'static str,
+    },
+    /// Value of a row column could not be converted into requested Rust type.
+    ColumnDecode {
+        column: String,
+        /// CQL type of the column, e.g. `list<int>`.
+        cql_type: String,
+        rust_type: &'static str,
+        source: Box<Error>,
+    },
+    /// The node rejected credentials of the authenticator, e.g. because of a wrong password.
+    AuthenticationFailed { node: String, message: String },
+    /// The node requires an authenticator other than the configured one.
+    UnsupportedAuthenticator {
+        node: String,
+        /// Class name of the authenticator advertised by the node.
+        server_authenticator: String,
+        /// Class name of the configured authenticator.
+        client_authenticator: String,
+    },
+    /// The node requires authentication, but no authenticator was configured.
+    MissingAuthenticator {
+        node: String,
+        /// Class name of the authenticator advertised by the node.
+        server_authenticator: String,
+    },
+    /// Deadline of the request passed before it was sent or before its response arrived.
+    DeadlineExceeded,
+    /// Keyspace of a pooled connection could not be set with `USE`, e.g. because the keyspace
+    /// was dropped. The connection is not handed out, since it would run requests in another
+    /// keyspace.
+    UseKeyspace {
+        keyspace: String,
+        source: Box<Error>,
+    },
 }
 
 pub fn column_is_empty_err<T: Display>(column_name: T) -> Error {
@@ -41,10 +90,66 @@ impl fmt::Display for Error {
         match *self {
             Error::Io(ref err) => write!(f, "IO error: {}", err),
             Error::Compression(ref err) => write!(f, "Compressor error: {}", err),
-            Error::Server(ref err) => write!(f, "Server error: {:?}", err.message),
+            Error::Server(ref err) => write!(f, "Server error: {}", err),
             Error::FromUtf8(ref err) => write!(f, "FromUtf8Error error: {:?}", err),
             Error::UUIDParse(ref err) => write!(f, "UUIDParse error: {:?}", err),
             Error::General(ref err) => write!(f, "GeneralParsing error: {:?}", err),
+            Error::ConnectionPoisoned(ref err) => {
+                write!(f, "Malformed frame received, connection closed: {}", err)
+            }
+            Error::PoolExhausted(ref addr) => {
+                write!(f, "No connection to {} available", addr)
+            }
+            Error::Overloaded(ref addr) => write!(f, "Node {} is overloaded", addr),
+            Error::DeadlineExceeded => write!(f, "Request deadline exceeded"),
+            Error::NumericOverflow {
+                ref column,
+                value,
+                target_type,
+            } => write!(
+                f,
+                "Numeric overflow: value {} of column '{}' does not fit into {}",
+                value, column, target_type
+            ),
+            Error::ColumnDecode {
+                ref column,
+                ref cql_type,
+                rust_type,
+                ref source,
+            } => write!(
+                f,
+                "Cannot read column '{}' of type {} as {}: {}",
+                column, cql_type, rust_type, source
+            ),
+            Error::AuthenticationFailed {
+                ref node,
+                ref message,
+            } => write!(f, "Authentication with {} failed: {}", node, message),
+            Error::UnsupportedAuthenticator {
+                ref node,
+                ref server_authenticator,
+                ref client_authenticator,
+            } => write!(
+                f,
+                "Node {} requires authenticator {}, but {} is configured",
+                node, server_authenticator, client_authenticator
+            ),
+            Error::MissingAuthenticator {
+                ref node,
+                ref server_authenticator,
+            } => write!(
+                f,
+                "Node {} requires authenticator {}, but none is configured",
+                node, server_authenticator
+            ),
+            Error::UseKeyspace {
+                ref keyspace,
+                ref source,
+            } => write!(
+                f,
+                "Cannot set keyspace '{}' of a connection: {}",
+                keyspace, source
+            ),
         }
     }
 }
@@ -56,6 +161,10 @@ impl error::Error for Error {
             Error::UUIDParse(ref e) => Some(e),
             Error::FromUtf8(ref e) => Some(e),
             Error::Compression(ref e) => Some(e),
+            Error::Server(ref e) => Some(e),
+            Error::ConnectionPoisoned(ref e) => Some(e.as_ref()),
+            Error::ColumnDecode { ref source, .. } => Some(source.as_ref()),
+            Error::UseKeyspace { ref source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
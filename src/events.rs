@@ -1,14 +1,18 @@
+use std::future::Future;
 use std::iter::Iterator;
+use std::net::{Shutdown, SocketAddr};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::Mutex;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, Mutex, Notify};
 
 use crate::compression::Compression;
 use crate::error;
 use crate::frame::events::{
     SchemaChange as FrameSchemaChange, ServerEvent as FrameServerEvent,
-    SimpleServerEvent as FrameSimpleServerEvent,
+    SimpleServerEvent as FrameSimpleServerEvent, StatusChange, StatusChangeType, TopologyChange,
+    TopologyChangeType,
 };
-use crate::frame::parser::parse_frame;
+use crate::frame::parser::from_transport;
 use crate::transport::CDRSTransport;
 
 /// Full Server Event which includes all details about occured change.
@@ -21,6 +25,48 @@ pub type SimpleServerEvent = FrameSimpleServerEvent;
 /// Reexport of `FrameSchemaChange`.
 pub type SchemaChange = FrameSchemaChange;
 
+/// Typed cluster change, broadcast by a session to its subscribers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClusterEvent {
+    /// A node joined the cluster.
+    NodeAdded(SocketAddr),
+    /// A node left the cluster.
+    NodeRemoved(SocketAddr),
+    /// A node became available.
+    NodeUp(SocketAddr),
+    /// A node became unavailable.
+    NodeDown(SocketAddr),
+    /// A keyspace, table, type, function or aggregate was created, updated or dropped.
+    SchemaChanged(SchemaChange),
+    /// The event connection was re-established after it failed, so events sent in between
+    /// were missed. Cached cluster state should be refreshed.
+    EventsMissed,
+}
+
+impl From<&ServerEvent> for ClusterEvent {
+    fn from(event: &ServerEvent) -> Self {
+        match event {
+            ServerEvent::TopologyChange(TopologyChange {
+                change_type: TopologyChangeType::NewNode,
+                addr,
+            }) => ClusterEvent::NodeAdded(addr.addr),
+            ServerEvent::TopologyChange(TopologyChange {
+                change_type: TopologyChangeType::RemovedNode,
+                addr,
+            }) => ClusterEvent::NodeRemoved(addr.addr),
+            ServerEvent::StatusChange(StatusChange {
+                change_type: StatusChangeType::Up,
+                addr,
+            }) => ClusterEvent::NodeUp(addr.addr),
+            ServerEvent::StatusChange(StatusChange {
+                change_type: StatusChangeType::Down,
+                addr,
+            }) => ClusterEvent::NodeDown(addr.addr),
+            ServerEvent::SchemaChange(change) => ClusterEvent::SchemaChanged(change.clone()),
+        }
+    }
+}
+
 /// Factory function which returns a `Listener` and related `EventStream.`
 ///
 /// `Listener` provides only one function `start` to start listening. It
@@ -31,7 +77,13 @@ pub type SchemaChange = FrameSchemaChange;
 /// It is similar to `Receiver::iter`.
 pub fn new_listener<X>(transport: X) -> (Listener<X>, EventStream) {
     let (tx, rx) = channel();
-    let listener = Listener { transport, tx };
+    let listener = Listener {
+        transport,
+        tx,
+        cluster_events: None,
+        stop: Default::default(),
+        stopped: watch::channel(false).0,
+    };
     let stream = EventStream { rx };
     (listener, stream)
 }
@@ -43,16 +95,110 @@ pub fn new_listener<X>(transport: X) -> (Listener<X>, EventStream) {
 pub struct Listener<X> {
     transport: X,
     tx: Sender<ServerEvent>,
+    cluster_events: Option<broadcast::Sender<ClusterEvent>>,
+    stop: Arc<Notify>,
+    stopped: watch::Sender<bool>,
+}
+
+impl<X> Listener<X> {
+    /// Returns a handle stopping the listener once started, e.g. to replace it after a
+    /// configuration change.
+    pub fn handle(&self) -> ListenerHandle {
+        ListenerHandle {
+            stop: self.stop.clone(),
+            stopped: self.stopped.subscribe(),
+        }
+    }
+
+    /// Additionally broadcasts received events as `ClusterEvent`s.
+    #[cfg(any(test, feature = "unstable-dynamic-cluster"))]
+    pub(crate) fn with_cluster_events(mut self, sender: broadcast::Sender<ClusterEvent>) -> Self {
+        self.cluster_events = Some(sender);
+        self
+    }
 }
 
 impl<X: CDRSTransport + Unpin + 'static> Listener<Mutex<X>> {
-    /// It starts a process of listening to new events. Locks a frame.
+    /// It starts a process of listening to new events, until it is stopped with a
+    /// `ListenerHandle` or the connection fails. Then the connection is closed and the
+    /// `EventStream` completes. Locks a frame.
     pub async fn start(self, compressor: Compression) -> error::Result<()> {
+        let result = match self.listen(compressor).await {
+            ListenEnd::Stopped => Ok(()),
+            ListenEnd::StreamDropped(error) | ListenEnd::ConnectionFailed(error) => Err(error),
+        };
+
+        self.finish(result).await
+    }
+
+    /// It starts listening like `start`, but when the connection fails a new one is
+    /// established with `reconnect`, which should also register for events again. Then
+    /// `ClusterEvent::EventsMissed` is broadcast, since events sent in between are lost. Failed
+    /// reconnection attempts are repeated, so `reconnect` should delay them.
+    pub async fn start_reconnecting<F, Fut>(
+        mut self,
+        compressor: Compression,
+        reconnect: F,
+    ) -> error::Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = error::Result<Mutex<X>>>,
+    {
         loop {
-            let event_opt = parse_frame(&self.transport, compressor)
-                .await?
-                .get_body()?
-                .into_server_event();
+            let error = match self.listen(compressor).await {
+                ListenEnd::Stopped => return self.finish(Ok(())).await,
+                ListenEnd::StreamDropped(error) => return self.finish(Err(error)).await,
+                ListenEnd::ConnectionFailed(error) => error,
+            };
+            warn!("Event connection failed, reconnecting: {}", error);
+            let _ = self.transport.lock().await.close(Shutdown::Both).await;
+
+            loop {
+                let transport = tokio::select! {
+                    transport = reconnect() => transport,
+                    _ = self.stop.notified() => return self.finish(Ok(())).await,
+                };
+
+                match transport {
+                    Ok(transport) => {
+                        self.transport = transport;
+                        break;
+                    }
+                    Err(error) => warn!("Cannot re-establish event connection: {}", error),
+                }
+            }
+
+            if let Some(ref cluster_events) = self.cluster_events {
+                let _ = cluster_events.send(ClusterEvent::EventsMissed);
+            }
+        }
+    }
+
+    async fn finish(self, result: error::Result<()>) -> error::Result<()> {
+        let Listener {
+            transport,
+            tx,
+            stopped,
+            ..
+        } = self;
+        drop(tx);
+        // the protocol cannot unregister events, closing the connection ends the registration
+        let _ = transport.lock().await.close(Shutdown::Both).await;
+        let _ = stopped.send(true);
+
+        result
+    }
+
+    async fn listen(&self, compressor: Compression) -> ListenEnd {
+        loop {
+            let frame = tokio::select! {
+                frame = from_transport(&self.transport, compressor) => frame,
+                _ = self.stop.notified() => return ListenEnd::Stopped,
+            };
+            let event_opt = match frame.and_then(|frame| frame.get_body()) {
+                Ok(body) => body.into_server_event(),
+                Err(error) => return ListenEnd::ConnectionFailed(error),
+            };
 
             let event = if event_opt.is_some() {
                 // unwrap is safe as we've checked that event_opt.is_some()
@@ -60,14 +206,60 @@ impl<X: CDRSTransport + Unpin + 'static> Listener<Mutex<X>> {
             } else {
                 continue;
             };
+
+            if let Some(ref cluster_events) = self.cluster_events {
+                // sending fails only if there are no subscribers at the moment
+                let _ = cluster_events.send(ClusterEvent::from(&event));
+            }
+
             match self.tx.send(event) {
-                Err(err) => return Err(error::Error::General(err.to_string())),
+                Err(err) => {
+                    return ListenEnd::StreamDropped(error::Error::General(err.to_string()))
+                }
                 _ => continue,
             }
         }
     }
 }
 
+/// Reason of a listener to stop listening on its connection.
+enum ListenEnd {
+    /// Stopped with a `ListenerHandle`.
+    Stopped,
+    /// `EventStream` of the listener was dropped.
+    StreamDropped(error::Error),
+    /// Reading from the connection failed.
+    ConnectionFailed(error::Error),
+}
+
+/// Handle stopping a `Listener`.
+#[derive(Debug, Clone)]
+pub struct ListenerHandle {
+    stop: Arc<Notify>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl ListenerHandle {
+    /// Stops the listener and waits until its connection is closed. Events received before
+    /// are still returned by its `EventStream`, which completes afterwards. Returns immediately
+    /// if the listener has already stopped or was dropped without being started.
+    pub async fn stop(&self) {
+        self.stop.notify_one();
+
+        let mut stopped = self.stopped.clone();
+        while !*stopped.borrow() {
+            if stopped.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Returns `true` once the listener has stopped, e.g. after its connection failed.
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.borrow()
+    }
+}
+
 /// `EventStream` is an iterator which returns new events once they come.
 /// It is similar to `Receiver::iter`.
 pub struct EventStream {
@@ -102,3 +294,155 @@ impl Iterator for EventStreamNonBlocking {
         self.rx.try_recv().ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::KeyspaceHolder;
+    use crate::frame::{AsByte, Opcode, Version};
+    use crate::transport::TransportTcp;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn event_frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![
+            Version::Response.as_byte(),
+            0,
+            0xff,
+            0xff,
+            Opcode::Event.as_byte(),
+        ];
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[tokio::test]
+    async fn broadcast_cluster_events() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = server.accept().await.unwrap();
+
+            let mut body = vec![0, 13];
+            body.extend_from_slice(b"STATUS_CHANGE");
+            body.extend_from_slice(&[0, 4]);
+            body.extend_from_slice(b"DOWN");
+            body.extend_from_slice(&[4, 127, 0, 0, 2, 0, 0, 0x23, 0x52]);
+            stream.write_all(&event_frame(&body)).await.unwrap();
+
+            let mut body = vec![0, 13];
+            body.extend_from_slice(b"SCHEMA_CHANGE");
+            body.extend_from_slice(&[0, 7]);
+            body.extend_from_slice(b"CREATED");
+            body.extend_from_slice(&[0, 8]);
+            body.extend_from_slice(b"KEYSPACE");
+            body.extend_from_slice(&[0, 2]);
+            body.extend_from_slice(b"ks");
+            stream.write_all(&event_frame(&body)).await.unwrap();
+        });
+
+        let transport = TransportTcp::new(&addr.to_string(), Arc::new(KeyspaceHolder::default()))
+            .await
+            .unwrap();
+        let (listener, _stream) = new_listener(Mutex::new(transport));
+
+        let (sender, mut receiver) = broadcast::channel(8);
+        tokio::spawn(
+            listener
+                .with_cluster_events(sender)
+                .start(Compression::None),
+        );
+
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ClusterEvent::NodeDown("127.0.0.2:9042".parse().unwrap())
+        );
+        match receiver.recv().await.unwrap() {
+            ClusterEvent::SchemaChanged(change) => {
+                assert_eq!(
+                    change.options,
+                    crate::frame::events::ChangeSchemeOptions::Keyspace("ks".into())
+                )
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn stop_listener() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let closed = tokio::spawn(async move {
+            let (mut stream, _) = server.accept().await.unwrap();
+            let mut buffer = [0; 16];
+            stream.read(&mut buffer).await.unwrap()
+        });
+
+        let transport = TransportTcp::new(&addr.to_string(), Arc::new(KeyspaceHolder::default()))
+            .await
+            .unwrap();
+        let (listener, mut stream) = new_listener(Mutex::new(transport));
+        let handle = listener.handle();
+        let listening = tokio::spawn(listener.start(Compression::None));
+
+        assert!(!handle.is_stopped());
+        handle.stop().await;
+        assert!(handle.is_stopped());
+
+        assert!(listening.await.unwrap().is_ok());
+        assert!(stream.next().is_none());
+        assert_eq!(closed.await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn reconnect_after_connection_failure() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            for host in [2, 3] {
+                let (mut stream, _) = server.accept().await.unwrap();
+                let mut body = vec![0, 13];
+                body.extend_from_slice(b"STATUS_CHANGE");
+                body.extend_from_slice(&[0, 2]);
+                body.extend_from_slice(b"UP");
+                body.extend_from_slice(&[4, 127, 0, 0, host, 0, 0, 0x23, 0x52]);
+                stream.write_all(&event_frame(&body)).await.unwrap();
+            }
+        });
+
+        let connect = move || {
+            let addr = addr.clone();
+            async move {
+                TransportTcp::new(&addr, Arc::new(KeyspaceHolder::default()))
+                    .await
+                    .map(Mutex::new)
+                    .map_err(error::Error::from)
+            }
+        };
+        let (listener, _stream) = new_listener(connect().await.unwrap());
+        let handle = listener.handle();
+        let (sender, mut receiver) = broadcast::channel(8);
+        tokio::spawn(
+            listener
+                .with_cluster_events(sender)
+                .start_reconnecting(Compression::None, connect),
+        );
+
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ClusterEvent::NodeUp("127.0.0.2:9042".parse().unwrap())
+        );
+        assert_eq!(receiver.recv().await.unwrap(), ClusterEvent::EventsMissed);
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ClusterEvent::NodeUp("127.0.0.3:9042".parse().unwrap())
+        );
+
+        handle.stop().await;
+    }
+}
@@ -33,7 +33,7 @@ const AGGREGATE: &str = "AGGREGATE";
 /// Simplified `ServerEvent` that does not contain details
 /// about a concrete change. It may be useful for subscription
 /// when you need only string representation of an event.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SimpleServerEvent {
     TopologyChange,
     StatusChange,
@@ -176,7 +176,7 @@ impl FromCursor for StatusChangeType {
 }
 
 /// Events related to schema change.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SchemaChange {
     pub change_type: ChangeType,
     pub target: Target,
@@ -199,7 +199,7 @@ impl FromCursor for SchemaChange {
 
 /// Represents type of changes.
 // TODO: rename to SchemaChangeType
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChangeType {
     Created,
     Updated,
@@ -219,7 +219,7 @@ impl FromCursor for ChangeType {
 
 /// Refers to a target of changes were made.
 // TODO: rename to SchemaChangeTarget
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Target {
     Keyspace,
     Table,
@@ -242,7 +242,7 @@ impl FromCursor for Target {
 }
 
 /// Option that contains an information about changes were made.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChangeSchemeOptions {
     /// Changes related to keyspaces. Contains keyspace name.
     Keyspace(String),
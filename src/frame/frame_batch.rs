@@ -1,7 +1,8 @@
 use crate::consistency::Consistency;
+use crate::error;
 use crate::frame::*;
 use crate::query::QueryValues;
-use crate::query::{PreparedQuery, QueryFlags};
+use crate::query::{PreparedQuery, QueryFlagSet, QueryFlags};
 use crate::types::*;
 
 /// `BodyResReady`
@@ -19,6 +20,10 @@ pub struct BodyReqBatch {
 
 impl AsBytes for BodyReqBatch {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let mut bytes = vec![];
 
         bytes.push(self.batch_type.as_byte());
@@ -26,17 +31,17 @@ impl AsBytes for BodyReqBatch {
         bytes.extend_from_slice(to_short(self.queries.len() as i16).as_slice());
 
         bytes = self.queries.iter().fold(bytes, |mut _bytes, q| {
-            _bytes.extend_from_slice(q.as_bytes().as_slice());
+            _bytes.extend_from_slice(q.as_bytes_for(version).as_slice());
             _bytes
         });
 
         bytes.extend_from_slice(self.consistency.as_bytes().as_slice());
 
-        let flag_byte = self
-            .query_flags
-            .iter()
-            .fold(0, |mut _bytes, f| _bytes | f.as_byte());
-        bytes.push(flag_byte);
+        bytes.extend_from_slice(
+            QueryFlagSet::from_flags(&self.query_flags)
+                .as_bytes_for(version)
+                .as_slice(),
+        );
 
         if let Some(ref serial_consistency) = self.serial_consistency {
             bytes.extend_from_slice(serial_consistency.as_bytes().as_slice());
@@ -111,6 +116,10 @@ pub enum BatchQuerySubj {
 
 impl AsBytes for BatchQuery {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let mut bytes = vec![];
 
         // kind
@@ -136,7 +145,7 @@ impl AsBytes for BatchQuery {
 
         bytes.extend_from_slice(to_short(self.values.len() as i16).as_slice());
 
-        bytes.extend_from_slice(self.values.as_bytes().as_slice());
+        bytes.extend_from_slice(self.values.as_bytes_for(version).as_slice());
 
         bytes
     }
@@ -150,4 +159,34 @@ impl Frame {
 
         Frame::new(version, flags, opcode, query.as_bytes(), None, vec![])
     }
+
+    /// Builds a batch request frame encoded with given protocol version. Fails if values of
+    /// any statement are unset with v3, which doesn't support them.
+    pub fn new_req_batch_for(
+        query: &BodyReqBatch,
+        flags: Vec<Flag>,
+        protocol_version: ProtocolVersion,
+    ) -> error::Result<Frame> {
+        let has_unset_values = query
+            .queries
+            .iter()
+            .any(|query| query.values.has_unset_values());
+        if has_unset_values && !protocol_version.supports_unset_values() {
+            return Err(format!(
+                "Unset values are not supported by protocol {:?}",
+                protocol_version
+            )
+            .into());
+        }
+
+        Ok(Frame::new(
+            Version::Request,
+            flags,
+            Opcode::Batch,
+            query.as_bytes_for(protocol_version),
+            None,
+            vec![],
+        )
+        .with_protocol_version(protocol_version))
+    }
 }
@@ -2,6 +2,9 @@
 //! (https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L1011)
 //! which server could respond to client.
 
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
 use std::io;
 use std::result;
 
@@ -30,6 +33,178 @@ pub struct CDRSError {
     pub additional_info: AdditionalErrorInfo,
 }
 
+impl CDRSError {
+    /// Typed error code. Returns `None` for codes not known to the driver.
+    pub fn code(&self) -> Option<ErrorCode> {
+        ErrorCode::try_from(self.error_code).ok()
+    }
+
+    /// Not enough replicas were alive to achieve the requested consistency.
+    pub fn is_unavailable(&self) -> bool {
+        self.code() == Some(ErrorCode::Unavailable)
+    }
+
+    /// The node rejected provided credentials.
+    pub fn is_authentication(&self) -> bool {
+        self.code() == Some(ErrorCode::Authentication)
+    }
+
+    /// The coordinator is overloaded.
+    pub fn is_overloaded(&self) -> bool {
+        self.code() == Some(ErrorCode::Overloaded)
+    }
+
+    /// The coordinator is still bootstrapping.
+    pub fn is_bootstrapping(&self) -> bool {
+        self.code() == Some(ErrorCode::IsBootstrapping)
+    }
+
+    /// Replicas did not respond to a read in time.
+    pub fn is_read_timeout(&self) -> bool {
+        self.code() == Some(ErrorCode::ReadTimeout)
+    }
+
+    /// Replicas did not respond to a write in time.
+    pub fn is_write_timeout(&self) -> bool {
+        self.code() == Some(ErrorCode::WriteTimeout)
+    }
+
+    /// A non-timeout failure occurred during a read.
+    pub fn is_read_failure(&self) -> bool {
+        self.code() == Some(ErrorCode::ReadFailure)
+    }
+
+    /// A non-timeout failure occurred during a write.
+    pub fn is_write_failure(&self) -> bool {
+        self.code() == Some(ErrorCode::WriteFailure)
+    }
+
+    /// The executed prepared statement is not known by the node.
+    pub fn is_unprepared(&self) -> bool {
+        self.code() == Some(ErrorCode::Unprepared)
+    }
+
+    /// The node does not support the protocol version of the request.
+    pub fn is_unsupported_protocol_version(&self) -> bool {
+        self.code() == Some(ErrorCode::Protocol)
+            && self
+                .message
+                .as_str()
+                .to_lowercase()
+                .contains("protocol version")
+    }
+}
+
+impl fmt::Display for CDRSError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.code() {
+            Some(code) => write!(f, "{:?} (0x{:04X}): ", code, self.error_code)?,
+            None => write!(f, "Unknown error (0x{:04X}): ", self.error_code)?,
+        }
+
+        write!(f, "{}", self.message.as_str())?;
+
+        match self.additional_info {
+            AdditionalErrorInfo::Unavailable(ref info) => write!(
+                f,
+                " (consistency: {:?}, required: {}, alive: {})",
+                info.cl, info.required, info.alive
+            ),
+            AdditionalErrorInfo::WriteTimeout(ref info) => write!(
+                f,
+                " (consistency: {:?}, received: {}, block for: {}, write type: {:?})",
+                info.cl, info.received, info.blockfor, info.write_type
+            ),
+            AdditionalErrorInfo::ReadTimeout(ref info) => write!(
+                f,
+                " (consistency: {:?}, received: {}, block for: {}, data present: {})",
+                info.cl,
+                info.received,
+                info.blockfor,
+                info.replica_has_responded()
+            ),
+            AdditionalErrorInfo::ReadFailure(ref info) => write!(
+                f,
+                " (consistency: {:?}, received: {}, block for: {}, failures: {})",
+                info.cl, info.received, info.blockfor, info.num_failures
+            ),
+            AdditionalErrorInfo::WriteFailure(ref info) => write!(
+                f,
+                " (consistency: {:?}, received: {}, block for: {}, failures: {}, write type: {:?})",
+                info.cl, info.received, info.blockfor, info.num_failures, info.write_type
+            ),
+            AdditionalErrorInfo::AlreadyExists(ref info) => write!(
+                f,
+                " (keyspace: {}, table: {})",
+                info.ks.as_str(),
+                info.table.as_str()
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Error for CDRSError {}
+
+/// Error codes in accordance to
+/// [Cassandra protocol v4]
+/// (https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L1011).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Server = 0x0000,
+    Protocol = 0x000A,
+    Authentication = 0x0100,
+    Unavailable = 0x1000,
+    Overloaded = 0x1001,
+    IsBootstrapping = 0x1002,
+    Truncate = 0x1003,
+    WriteTimeout = 0x1100,
+    ReadTimeout = 0x1200,
+    ReadFailure = 0x1300,
+    FunctionFailure = 0x1400,
+    WriteFailure = 0x1500,
+    Syntax = 0x2000,
+    Unauthorized = 0x2100,
+    Invalid = 0x2200,
+    Config = 0x2300,
+    AlreadyExists = 0x2400,
+    Unprepared = 0x2500,
+}
+
+impl TryFrom<CInt> for ErrorCode {
+    type Error = error::Error;
+
+    fn try_from(code: CInt) -> error::Result<ErrorCode> {
+        match code {
+            0x0000 => Ok(ErrorCode::Server),
+            0x000A => Ok(ErrorCode::Protocol),
+            0x0100 => Ok(ErrorCode::Authentication),
+            0x1000 => Ok(ErrorCode::Unavailable),
+            0x1001 => Ok(ErrorCode::Overloaded),
+            0x1002 => Ok(ErrorCode::IsBootstrapping),
+            0x1003 => Ok(ErrorCode::Truncate),
+            0x1100 => Ok(ErrorCode::WriteTimeout),
+            0x1200 => Ok(ErrorCode::ReadTimeout),
+            0x1300 => Ok(ErrorCode::ReadFailure),
+            0x1400 => Ok(ErrorCode::FunctionFailure),
+            0x1500 => Ok(ErrorCode::WriteFailure),
+            0x2000 => Ok(ErrorCode::Syntax),
+            0x2100 => Ok(ErrorCode::Unauthorized),
+            0x2200 => Ok(ErrorCode::Invalid),
+            0x2300 => Ok(ErrorCode::Config),
+            0x2400 => Ok(ErrorCode::AlreadyExists),
+            0x2500 => Ok(ErrorCode::Unprepared),
+            _ => Err(format!("Unexpected error code 0x{:04X}", code).into()),
+        }
+    }
+}
+
+impl From<ErrorCode> for CInt {
+    fn from(code: ErrorCode) -> CInt {
+        code as CInt
+    }
+}
+
 impl FromCursor for CDRSError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<CDRSError> {
         let error_code = CInt::from_cursor(&mut cursor)?;
@@ -74,63 +249,62 @@ impl AdditionalErrorInfo {
         mut cursor: &mut io::Cursor<&[u8]>,
         error_code: CInt,
     ) -> error::Result<AdditionalErrorInfo> {
-        match error_code {
-            0x0000 => Ok(AdditionalErrorInfo::Server(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x000A => Ok(AdditionalErrorInfo::Protocol(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x0100 => Ok(AdditionalErrorInfo::Authentication(
-                SimpleError::from_cursor(&mut cursor)?,
-            )),
-            0x1000 => Ok(AdditionalErrorInfo::Unavailable(
-                UnavailableError::from_cursor(&mut cursor)?,
-            )),
-            0x1001 => Ok(AdditionalErrorInfo::Overloaded(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x1002 => Ok(AdditionalErrorInfo::IsBootstrapping(
-                SimpleError::from_cursor(&mut cursor)?,
-            )),
-            0x1003 => Ok(AdditionalErrorInfo::Truncate(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x1100 => Ok(AdditionalErrorInfo::WriteTimeout(
-                WriteTimeoutError::from_cursor(&mut cursor)?,
-            )),
-            0x1200 => Ok(AdditionalErrorInfo::ReadTimeout(
-                ReadTimeoutError::from_cursor(&mut cursor)?,
-            )),
-            0x1300 => Ok(AdditionalErrorInfo::ReadFailure(
-                ReadFailureError::from_cursor(&mut cursor)?,
-            )),
-            0x1400 => Ok(AdditionalErrorInfo::FunctionFailure(
+        Ok(match ErrorCode::try_from(error_code)? {
+            ErrorCode::Server => {
+                AdditionalErrorInfo::Server(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Protocol => {
+                AdditionalErrorInfo::Protocol(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Authentication => {
+                AdditionalErrorInfo::Authentication(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Unavailable => {
+                AdditionalErrorInfo::Unavailable(UnavailableError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Overloaded => {
+                AdditionalErrorInfo::Overloaded(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::IsBootstrapping => {
+                AdditionalErrorInfo::IsBootstrapping(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Truncate => {
+                AdditionalErrorInfo::Truncate(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::WriteTimeout => {
+                AdditionalErrorInfo::WriteTimeout(WriteTimeoutError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::ReadTimeout => {
+                AdditionalErrorInfo::ReadTimeout(ReadTimeoutError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::ReadFailure => {
+                AdditionalErrorInfo::ReadFailure(ReadFailureError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::FunctionFailure => AdditionalErrorInfo::FunctionFailure(
                 FunctionFailureError::from_cursor(&mut cursor)?,
-            )),
-            0x1500 => Ok(AdditionalErrorInfo::WriteFailure(
-                WriteFailureError::from_cursor(&mut cursor)?,
-            )),
-            0x2000 => Ok(AdditionalErrorInfo::Syntax(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x2100 => Ok(AdditionalErrorInfo::Unauthorized(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x2200 => Ok(AdditionalErrorInfo::Invalid(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x2300 => Ok(AdditionalErrorInfo::Config(SimpleError::from_cursor(
-                &mut cursor,
-            )?)),
-            0x2400 => Ok(AdditionalErrorInfo::AlreadyExists(
-                AlreadyExistsError::from_cursor(&mut cursor)?,
-            )),
-            0x2500 => Ok(AdditionalErrorInfo::Unprepared(
-                UnpreparedError::from_cursor(&mut cursor)?,
-            )),
-            _ => Err("Unexpected additional error info".into()),
-        }
+            ),
+            ErrorCode::WriteFailure => {
+                AdditionalErrorInfo::WriteFailure(WriteFailureError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Syntax => {
+                AdditionalErrorInfo::Syntax(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Unauthorized => {
+                AdditionalErrorInfo::Unauthorized(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Invalid => {
+                AdditionalErrorInfo::Invalid(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Config => {
+                AdditionalErrorInfo::Config(SimpleError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::AlreadyExists => {
+                AdditionalErrorInfo::AlreadyExists(AlreadyExistsError::from_cursor(&mut cursor)?)
+            }
+            ErrorCode::Unprepared => {
+                AdditionalErrorInfo::Unprepared(UnpreparedError::from_cursor(&mut cursor)?)
+            }
+        })
     }
 }
 
@@ -402,3 +576,39 @@ impl FromCursor for UnpreparedError {
         Ok(UnpreparedError { id })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_code_round_trip() {
+        for code in &[0x0000, 0x000A, 0x1000, 0x1200, 0x2500] {
+            assert_eq!(CInt::from(ErrorCode::try_from(*code).unwrap()), *code);
+        }
+        assert!(ErrorCode::try_from(0x7777).is_err());
+    }
+
+    #[test]
+    fn unavailable_error() {
+        let bytes: &[u8] = &[
+            0x00, 0x00, 0x10, 0x00, 0x00, 0x04, b'f', b'a', b'i', b'l', 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x03, 0x00, 0x00, 0x00, 0x01,
+        ];
+        let error = CDRSError::from_cursor(&mut io::Cursor::new(bytes)).unwrap();
+
+        assert_eq!(error.code(), Some(ErrorCode::Unavailable));
+        assert!(error.is_unavailable());
+        assert!(!error.is_read_timeout());
+        assert_eq!(
+            error.to_string(),
+            "Unavailable (0x1000): fail (consistency: Quorum, required: 3, alive: 1)"
+        );
+    }
+
+    #[test]
+    fn unknown_error_code() {
+        let bytes: &[u8] = &[0x00, 0x00, 0x77, 0x77, 0x00, 0x00];
+        assert!(CDRSError::from_cursor(&mut io::Cursor::new(bytes)).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error;
 use crate::frame::*;
 use crate::query::QueryParams;
 use crate::types::*;
@@ -7,6 +8,8 @@ use crate::types::*;
 pub struct BodyReqExecute<'a> {
     /// Id of prepared query
     id: &'a CBytesShort,
+    /// Id of result metadata of prepared query, sent with protocol v5
+    result_metadata_id: Option<&'a CBytesShort>,
     /// Query parameters which have the same meaning as one for `query`
     /// TODO: clarify if it is QueryParams or its shortened variant
     query_parameters: &'a QueryParams,
@@ -17,16 +20,33 @@ impl<'a> BodyReqExecute<'a> {
     pub fn new<'b>(id: &'b CBytesShort, query_parameters: &'b QueryParams) -> BodyReqExecute<'b> {
         BodyReqExecute {
             id,
+            result_metadata_id: None,
             query_parameters,
         }
     }
+
+    /// Sets id of result metadata of the prepared query.
+    pub fn result_metadata_id(mut self, result_metadata_id: Option<&'a CBytesShort>) -> Self {
+        self.result_metadata_id = result_metadata_id;
+        self
+    }
 }
 
 impl<'a> AsBytes for BodyReqExecute<'a> {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
         v.extend_from_slice(self.id.as_bytes().as_slice());
-        v.extend_from_slice(self.query_parameters.as_bytes().as_slice());
+        if version.has_result_metadata_id() {
+            match self.result_metadata_id {
+                Some(result_metadata_id) => v.extend_from_slice(&result_metadata_id.as_bytes()),
+                None => v.extend_from_slice(&to_short(0)),
+            }
+        }
+        v.extend_from_slice(self.query_parameters.as_bytes_for(version).as_slice());
         v
     }
 }
@@ -37,6 +57,16 @@ impl Frame {
         id: &CBytesShort,
         query_parameters: &QueryParams,
         flags: Vec<Flag>,
+    ) -> Frame {
+        Frame::new_req_execute_with_metadata_id(id, None, query_parameters, flags)
+    }
+
+    /// **Note:** This function should be used internally for building query request frames.
+    pub fn new_req_execute_with_metadata_id(
+        id: &CBytesShort,
+        result_metadata_id: Option<&CBytesShort>,
+        query_parameters: &QueryParams,
+        flags: Vec<Flag>,
     ) -> Frame {
         let version = Version::Request;
         let opcode = Opcode::Execute;
@@ -44,8 +74,33 @@ impl Frame {
             "prepared statement id{:?} getting executed with parameters {:?}",
             id, query_parameters
         );
-        let body = BodyReqExecute::new(id, query_parameters);
+        let body = BodyReqExecute::new(id, query_parameters).result_metadata_id(result_metadata_id);
 
         Frame::new(version, flags, opcode, body.as_bytes(), None, vec![])
+            .with_custom_payload(query_parameters.custom_payload.clone())
+    }
+
+    /// Builds an execute request frame encoded with given protocol version. Fails if the
+    /// parameters use features the version doesn't support, e.g. unset values with v3.
+    pub fn new_req_execute_for(
+        id: &CBytesShort,
+        result_metadata_id: Option<&CBytesShort>,
+        query_parameters: &QueryParams,
+        flags: Vec<Flag>,
+        protocol_version: ProtocolVersion,
+    ) -> error::Result<Frame> {
+        query_parameters.check_version(protocol_version)?;
+
+        let body = BodyReqExecute::new(id, query_parameters).result_metadata_id(result_metadata_id);
+        Ok(Frame::new(
+            Version::Request,
+            flags,
+            Opcode::Execute,
+            body.as_bytes_for(protocol_version),
+            None,
+            vec![],
+        )
+        .with_custom_payload(query_parameters.custom_payload.clone())
+        .with_protocol_version(protocol_version))
     }
 }
@@ -18,7 +18,16 @@ impl BodyReqPrepare {
 
 impl AsBytes for BodyReqPrepare {
     fn as_bytes(&self) -> Vec<u8> {
-        self.query.as_bytes()
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
+        let mut v = self.query.as_bytes();
+        if version.has_int_flags() {
+            // no keyspace
+            v.extend_from_slice(to_int(0).as_slice());
+        }
+        v
     }
 }
 
@@ -31,4 +40,23 @@ impl Frame {
 
         Frame::new(version, flags, opcode, body.as_bytes(), None, vec![])
     }
+
+    /// Builds a prepare request frame encoded with given protocol version.
+    pub fn new_req_prepare_for(
+        query: String,
+        flags: Vec<Flag>,
+        protocol_version: ProtocolVersion,
+    ) -> Frame {
+        let body = BodyReqPrepare::new(query);
+
+        Frame::new(
+            Version::Request,
+            flags,
+            Opcode::Prepare,
+            body.as_bytes_for(protocol_version),
+            None,
+            vec![],
+        )
+        .with_protocol_version(protocol_version)
+    }
 }
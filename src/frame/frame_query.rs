@@ -1,6 +1,7 @@
 #![warn(missing_docs)]
 //! Contains Query Frame related functionality.
 use crate::consistency::Consistency;
+use crate::error;
 use crate::frame::*;
 use crate::query::{Query, QueryFlags, QueryParams, QueryValues};
 use crate::types::*;
@@ -59,6 +60,11 @@ impl BodyReqQuery {
                 paging_state,
                 serial_consistency,
                 timestamp,
+                custom_payload: Default::default(),
+                tracing: None,
+                deadline: None,
+                keyspace: None,
+                now_in_seconds: None,
             },
         }
     }
@@ -66,9 +72,13 @@ impl BodyReqQuery {
 
 impl AsBytes for BodyReqQuery {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
         v.extend_from_slice(self.query.clone().as_bytes().as_slice());
-        v.extend_from_slice(self.query_params.as_bytes().as_slice());
+        v.extend_from_slice(self.query_params.as_bytes_for(version).as_slice());
         v
     }
 }
@@ -105,8 +115,44 @@ impl Frame {
         Frame::new(version, flags, opcode, body.as_bytes(), None, vec![])
     }
 
+    /// Builds a query request frame encoded with given protocol version. Fails if the query
+    /// uses features the version doesn't support, e.g. unset values with v3.
+    pub fn new_query_for(
+        query: &Query,
+        flags: Vec<Flag>,
+        protocol_version: ProtocolVersion,
+    ) -> error::Result<Frame> {
+        query.params.check_version(protocol_version)?;
+
+        let params = &query.params;
+        let mut body = BodyReqQuery::new(
+            query.query.clone(),
+            params.consistency,
+            params.values.clone(),
+            params.with_names,
+            params.page_size,
+            params.paging_state.clone(),
+            params.serial_consistency,
+            params.timestamp,
+        );
+        body.query_params.keyspace = params.keyspace.clone();
+        body.query_params.now_in_seconds = params.now_in_seconds;
+
+        Ok(Frame::new(
+            Version::Request,
+            flags,
+            Opcode::Query,
+            body.as_bytes_for(protocol_version),
+            None,
+            vec![],
+        )
+        .with_custom_payload(params.custom_payload.clone())
+        .with_protocol_version(protocol_version))
+    }
+
     /// **Note:** This function should be used internally for building query request frames.
     pub fn new_query(query: Query, flags: Vec<Flag>) -> Frame {
+        let custom_payload = query.params.custom_payload;
         Frame::new_req_query(
             query.query,
             query.params.consistency,
@@ -118,5 +164,6 @@ impl Frame {
             query.params.timestamp,
             flags,
         )
+        .with_custom_payload(custom_payload)
     }
 }
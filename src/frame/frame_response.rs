@@ -12,7 +12,7 @@ use crate::frame::frame_result::{
 };
 use crate::frame::frame_supported::*;
 use crate::frame::FromCursor;
-use crate::frame::Opcode;
+use crate::frame::{Opcode, ProtocolVersion};
 use crate::types::rows::Row;
 
 #[derive(Debug)]
@@ -37,6 +37,15 @@ pub enum ResponseBody {
 
 impl ResponseBody {
     pub fn from(bytes: &[u8], response_type: &Opcode) -> error::Result<ResponseBody> {
+        Self::from_for(bytes, response_type, ProtocolVersion::default())
+    }
+
+    /// Parses a response body in the format of given protocol version.
+    pub fn from_for(
+        bytes: &[u8],
+        response_type: &Opcode,
+        version: ProtocolVersion,
+    ) -> error::Result<ResponseBody> {
         let mut cursor: Cursor<&[u8]> = Cursor::new(bytes);
         Ok(match *response_type {
             // request frames
@@ -58,7 +67,9 @@ impl ResponseBody {
             Opcode::Supported => {
                 ResponseBody::Supported(BodyResSupported::from_cursor(&mut cursor)?)
             }
-            Opcode::Result => ResponseBody::Result(ResResultBody::from_cursor(&mut cursor)?),
+            Opcode::Result => {
+                ResponseBody::Result(ResResultBody::from_cursor_for(&mut cursor, version)?)
+            }
             Opcode::Event => ResponseBody::Event(BodyResEvent::from_cursor(&mut cursor)?),
             Opcode::AuthChallenge => {
                 ResponseBody::AuthChallenge(BodyResAuthChallenge::from_cursor(&mut cursor)?)
@@ -108,6 +119,15 @@ impl ResponseBody {
         }
     }
 
+    /// It unwraps body and returns BodyResSupported.
+    /// If frame body is not of type `Supported` this method returns `None`.
+    pub fn into_supported(self) -> Option<BodyResSupported> {
+        match self {
+            ResponseBody::Supported(supported) => Some(supported),
+            _ => None,
+        }
+    }
+
     /// It unwraps body and returns BodyResEvent.
     /// If frame body is not of type `Result` this method returns `None`.
     pub fn into_server_event(self) -> Option<BodyResEvent> {
@@ -1,9 +1,12 @@
+use std::fmt;
 use std::io::Cursor;
 
 use crate::error;
 use crate::frame::events::SchemaChange;
-use crate::frame::{AsBytes, FromBytes, FromCursor};
+use crate::frame::{AsBytes, FromBytes, FromCursor, ProtocolVersion};
+use crate::query::{describe_columns, ColumnDescription};
 use crate::types::rows::Row;
+use crate::types::vector::VectorType;
 use crate::types::*;
 
 /// `ResultKind` is enum which represents types of result.
@@ -77,16 +80,19 @@ impl ResResultBody {
     fn parse_body_from_cursor(
         mut cursor: &mut Cursor<&[u8]>,
         result_kind: ResultKind,
+        version: ProtocolVersion,
     ) -> error::Result<ResResultBody> {
         Ok(match result_kind {
             ResultKind::Void => ResResultBody::Void(BodyResResultVoid::from_cursor(&mut cursor)?),
-            ResultKind::Rows => ResResultBody::Rows(BodyResResultRows::from_cursor(&mut cursor)?),
+            ResultKind::Rows => {
+                ResResultBody::Rows(BodyResResultRows::from_cursor_for(&mut cursor, version)?)
+            }
             ResultKind::SetKeyspace => {
                 ResResultBody::SetKeyspace(BodyResResultSetKeyspace::from_cursor(&mut cursor)?)
             }
-            ResultKind::Prepared => {
-                ResResultBody::Prepared(BodyResResultPrepared::from_cursor(&mut cursor)?)
-            }
+            ResultKind::Prepared => ResResultBody::Prepared(
+                BodyResResultPrepared::from_cursor_for(&mut cursor, version)?,
+            ),
             ResultKind::SchemaChange => {
                 ResResultBody::SchemaChange(SchemaChange::from_cursor(&mut cursor)?)
             }
@@ -129,10 +135,17 @@ impl ResResultBody {
 }
 
 impl FromCursor for ResResultBody {
-    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ResResultBody> {
+    fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<ResResultBody> {
+        Self::from_cursor_for(cursor, ProtocolVersion::default())
+    }
+
+    fn from_cursor_for(
+        mut cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<ResResultBody> {
         let result_kind = ResultKind::from_cursor(&mut cursor)?;
 
-        ResResultBody::parse_body_from_cursor(&mut cursor, result_kind)
+        ResResultBody::parse_body_from_cursor(&mut cursor, result_kind, version)
     }
 }
 
@@ -207,8 +220,15 @@ impl BodyResResultRows {
 }
 
 impl FromCursor for BodyResResultRows {
-    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResResultRows> {
-        let metadata = RowsMetadata::from_cursor(&mut cursor)?;
+    fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResResultRows> {
+        Self::from_cursor_for(cursor, ProtocolVersion::default())
+    }
+
+    fn from_cursor_for(
+        mut cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<BodyResResultRows> {
+        let metadata = RowsMetadata::from_cursor_for(&mut cursor, version)?;
         let rows_count = CInt::from_cursor(&mut cursor)?;
         let rows_content: Vec<Vec<CBytes>> =
             BodyResResultRows::get_rows_content(&mut cursor, rows_count, metadata.columns_count);
@@ -232,6 +252,12 @@ pub struct RowsMetadata {
     pub columns_count: i32,
     /// Paging state.
     pub paging_state: Option<CBytes>,
+    /// New id of result metadata of a prepared statement, sent with protocol v5 if the
+    /// metadata changed since the statement was prepared.
+    pub new_metadata_id: Option<CBytesShort>,
+    /// Number of the page among pages of a continuous paging result, sent by servers which
+    /// support continuous paging.
+    pub continuous_page_number: Option<i32>,
     // In fact by specification Vec should have only two elements representing the
     // (unique) keyspace name and table name the columns belong to
     /// `Option` that may contain global table space.
@@ -240,8 +266,31 @@ pub struct RowsMetadata {
     pub col_specs: Vec<ColSpec>,
 }
 
+impl RowsMetadata {
+    /// Describes columns of the rows in order of their appearance. Keyspaces and tables are
+    /// taken from the global table spec if the server sent one instead of per column ones.
+    pub fn columns(&self) -> Vec<ColumnDescription> {
+        describe_columns(
+            &self.col_specs,
+            self.global_table_space
+                .as_ref()
+                .and_then(|spec| match spec.as_slice() {
+                    [keyspace, table] => Some((keyspace, table)),
+                    _ => None,
+                }),
+        )
+    }
+}
+
 impl FromCursor for RowsMetadata {
-    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<RowsMetadata> {
+    fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<RowsMetadata> {
+        Self::from_cursor_for(cursor, ProtocolVersion::default())
+    }
+
+    fn from_cursor_for(
+        mut cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<RowsMetadata> {
         let flags = CInt::from_cursor(&mut cursor)?;
         let columns_count = CInt::from_cursor(&mut cursor)?;
 
@@ -250,6 +299,16 @@ impl FromCursor for RowsMetadata {
             paging_state = Some(CBytes::from_cursor(&mut cursor)?)
         }
 
+        let mut new_metadata_id: Option<CBytesShort> = None;
+        if version.has_result_metadata_id() && RowsMetadataFlag::has_metadata_changed(flags) {
+            new_metadata_id = Some(CBytesShort::from_cursor(cursor)?)
+        }
+
+        let mut continuous_page_number: Option<i32> = None;
+        if RowsMetadataFlag::has_continuous_paging(flags) {
+            continuous_page_number = Some(CInt::from_cursor(cursor)?)
+        }
+
         let mut global_table_space: Option<Vec<CString>> = None;
         let has_global_table_space = RowsMetadataFlag::has_global_table_space(flags);
         if has_global_table_space {
@@ -264,6 +323,8 @@ impl FromCursor for RowsMetadata {
             flags,
             columns_count,
             paging_state,
+            new_metadata_id,
+            continuous_page_number,
             global_table_space,
             col_specs,
         })
@@ -273,12 +334,18 @@ impl FromCursor for RowsMetadata {
 const GLOBAL_TABLE_SPACE: i32 = 0x0001;
 const HAS_MORE_PAGES: i32 = 0x0002;
 const NO_METADATA: i32 = 0x0004;
+const METADATA_CHANGED: i32 = 0x0008;
+const CONTINUOUS_PAGING: i32 = 0x4000_0000;
+const LAST_CONTINUOUS_PAGE: i32 = 0x8000_0000_u32 as i32;
 
 /// Enum that represent a set of possible row metadata flags that could be set.
 pub enum RowsMetadataFlag {
     GlobalTableSpace,
     HasMorePages,
     NoMetadata,
+    MetadataChanged,
+    ContinuousPaging,
+    LastContinuousPage,
 }
 
 impl RowsMetadataFlag {
@@ -311,6 +378,36 @@ impl RowsMetadataFlag {
     pub fn set_no_metadata(flag: i32) -> i32 {
         flag | NO_METADATA
     }
+
+    /// Shows if provided flag contains MetadataChanged rows metadata flag
+    pub fn has_metadata_changed(flag: i32) -> bool {
+        (flag & METADATA_CHANGED) != 0
+    }
+
+    /// Sets MetadataChanged rows metadata flag
+    pub fn set_metadata_changed(flag: i32) -> i32 {
+        flag | METADATA_CHANGED
+    }
+
+    /// Shows if provided flag contains ContinuousPaging rows metadata flag
+    pub fn has_continuous_paging(flag: i32) -> bool {
+        (flag & CONTINUOUS_PAGING) != 0
+    }
+
+    /// Sets ContinuousPaging rows metadata flag
+    pub fn set_continuous_paging(flag: i32) -> i32 {
+        flag | CONTINUOUS_PAGING
+    }
+
+    /// Shows if provided flag contains LastContinuousPage rows metadata flag
+    pub fn has_last_continuous_page(flag: i32) -> bool {
+        (flag & LAST_CONTINUOUS_PAGE) != 0
+    }
+
+    /// Sets LastContinuousPage rows metadata flag
+    pub fn set_last_continuous_page(flag: i32) -> i32 {
+        flag | LAST_CONTINUOUS_PAGE
+    }
 }
 
 impl AsBytes for RowsMetadataFlag {
@@ -319,6 +416,9 @@ impl AsBytes for RowsMetadataFlag {
             RowsMetadataFlag::GlobalTableSpace => to_int(GLOBAL_TABLE_SPACE),
             RowsMetadataFlag::HasMorePages => to_int(HAS_MORE_PAGES),
             RowsMetadataFlag::NoMetadata => to_int(NO_METADATA),
+            RowsMetadataFlag::MetadataChanged => to_int(METADATA_CHANGED),
+            RowsMetadataFlag::ContinuousPaging => to_int(CONTINUOUS_PAGING),
+            RowsMetadataFlag::LastContinuousPage => to_int(LAST_CONTINUOUS_PAGE),
         }
     }
 }
@@ -331,6 +431,9 @@ impl FromBytes for RowsMetadataFlag {
                 GLOBAL_TABLE_SPACE => Ok(RowsMetadataFlag::GlobalTableSpace),
                 HAS_MORE_PAGES => Ok(RowsMetadataFlag::HasMorePages),
                 NO_METADATA => Ok(RowsMetadataFlag::NoMetadata),
+                METADATA_CHANGED => Ok(RowsMetadataFlag::MetadataChanged),
+                CONTINUOUS_PAGING => Ok(RowsMetadataFlag::ContinuousPaging),
+                LAST_CONTINUOUS_PAGE => Ok(RowsMetadataFlag::LastContinuousPage),
                 _ => Err("Unexpected rows metadata flag".into()),
             })
     }
@@ -455,6 +558,42 @@ impl FromBytes for ColType {
     }
 }
 
+impl fmt::Display for ColType {
+    /// Formats the type as its CQL name, e.g. `bigint`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColType::Custom => "custom",
+            ColType::Ascii => "ascii",
+            ColType::Bigint => "bigint",
+            ColType::Blob => "blob",
+            ColType::Boolean => "boolean",
+            ColType::Counter => "counter",
+            ColType::Decimal => "decimal",
+            ColType::Double => "double",
+            ColType::Float => "float",
+            ColType::Int => "int",
+            ColType::Timestamp => "timestamp",
+            ColType::Uuid => "uuid",
+            ColType::Varchar => "text",
+            ColType::Varint => "varint",
+            ColType::Timeuuid => "timeuuid",
+            ColType::Inet => "inet",
+            ColType::Date => "date",
+            ColType::Time => "time",
+            ColType::Smallint => "smallint",
+            ColType::Tinyint => "tinyint",
+            ColType::List => "list",
+            ColType::Map => "map",
+            ColType::Set => "set",
+            ColType::Udt => "udt",
+            ColType::Tuple => "tuple",
+            ColType::Null => "null",
+        };
+
+        f.write_str(name)
+    }
+}
+
 impl FromCursor for ColType {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ColType> {
         cursor_fill_value(&mut cursor, &mut [0; SHORT_LEN])
@@ -474,6 +613,51 @@ pub struct ColTypeOption {
     pub value: Option<ColTypeOptionValue>,
 }
 
+impl ColTypeOption {
+    /// Returns type of a `vector` column, which is sent as a `custom` type.
+    /// Returns `None` for other columns.
+    pub fn vector_type(&self) -> Option<VectorType> {
+        match (&self.id, &self.value) {
+            (ColType::Custom, Some(ColTypeOptionValue::CString(class_name))) => {
+                VectorType::parse(class_name.as_str())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ColTypeOption {
+    /// Formats the type as in CQL, including types of collection elements, e.g.
+    /// `map<text, list<int>>`. User defined types are formatted with their keyspace and custom
+    /// types as quoted class names. Frozenness is not known from result metadata.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(ColTypeOptionValue::CList(item)) => write!(f, "list<{}>", item),
+            Some(ColTypeOptionValue::CSet(item)) => write!(f, "set<{}>", item),
+            Some(ColTypeOptionValue::CMap((key, value))) => {
+                write!(f, "map<{}, {}>", key, value)
+            }
+            Some(ColTypeOptionValue::UdtType(udt)) => {
+                write!(f, "{}.{}", udt.ks.as_str(), udt.udt_name.as_str())
+            }
+            Some(ColTypeOptionValue::TupleType(tuple)) => {
+                f.write_str("tuple<")?;
+                for (index, item) in tuple.types.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str(">")
+            }
+            Some(ColTypeOptionValue::CString(class_name)) if matches!(self.id, ColType::Custom) => {
+                write!(f, "'{}'", class_name.as_str())
+            }
+            _ => write!(f, "{}", self.id),
+        }
+    }
+}
+
 impl FromCursor for ColTypeOption {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ColTypeOption> {
         let id = ColType::from_cursor(&mut cursor)?;
@@ -578,6 +762,8 @@ impl FromCursor for CTuple {
 pub struct BodyResResultPrepared {
     /// id of prepared request
     pub id: CBytesShort,
+    /// id of result metadata, sent with protocol v5
+    pub result_metadata_id: Option<CBytesShort>,
     /// metadata
     pub metadata: PreparedMetadata,
     /// It is defined exactly the same as <metadata> in the Rows
@@ -586,13 +772,26 @@ pub struct BodyResResultPrepared {
 }
 
 impl FromCursor for BodyResResultPrepared {
-    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResResultPrepared> {
+    fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResResultPrepared> {
+        Self::from_cursor_for(cursor, ProtocolVersion::default())
+    }
+
+    fn from_cursor_for(
+        mut cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<BodyResResultPrepared> {
         let id = CBytesShort::from_cursor(&mut cursor)?;
-        let metadata = PreparedMetadata::from_cursor(&mut cursor)?;
-        let result_metadata = RowsMetadata::from_cursor(&mut cursor)?;
+        let result_metadata_id = if version.has_result_metadata_id() {
+            Some(CBytesShort::from_cursor(cursor)?)
+        } else {
+            None
+        };
+        let metadata = PreparedMetadata::from_cursor_for(&mut cursor, version)?;
+        let result_metadata = RowsMetadata::from_cursor_for(&mut cursor, version)?;
 
         Ok(BodyResResultPrepared {
             id,
+            result_metadata_id,
             metadata,
             result_metadata,
         })
@@ -611,14 +810,20 @@ pub struct PreparedMetadata {
 }
 
 impl FromCursor for PreparedMetadata {
-    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<PreparedMetadata> {
+    fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<PreparedMetadata> {
+        Self::from_cursor_for(cursor, ProtocolVersion::default())
+    }
+
+    fn from_cursor_for(
+        mut cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<PreparedMetadata> {
         let flags = CInt::from_cursor(&mut cursor)?;
         let columns_count = CInt::from_cursor(&mut cursor)?;
-        let pk_count = if cfg!(feature = "v3") {
-            0
-        } else {
-            // v4 or v5
+        let pk_count = if version.has_pk_indexes() {
             CInt::from_cursor(&mut cursor)?
+        } else {
+            0
         };
         let pk_index_results: Vec<Option<i16>> = (0..pk_count)
             .map(|_| {
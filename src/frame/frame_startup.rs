@@ -22,6 +22,12 @@ impl<'a> BodyReqStartup<'a> {
         BodyReqStartup { map }
     }
 
+    /// Adds an option, e.g. a protocol extension accepted by the server.
+    pub fn with_option(mut self, name: &'static str, value: &'a str) -> BodyReqStartup<'a> {
+        self.map.insert(name, value);
+        self
+    }
+
     // should be [u8; 2]
     // Number of key-value pairs
     fn num(&self) -> Vec<u8> {
@@ -53,10 +59,22 @@ impl<'a> AsBytes for BodyReqStartup<'a> {
 impl Frame {
     /// Creates new frame of type `startup`.
     pub fn new_req_startup(compression: Option<&str>) -> Frame {
+        Frame::new_req_startup_with_options(compression, &[])
+    }
+
+    /// Creates new frame of type `startup` with additional options.
+    pub fn new_req_startup_with_options(
+        compression: Option<&str>,
+        options: &[(&'static str, &str)],
+    ) -> Frame {
         let version = Version::Request;
         let flag = Flag::Ignore;
         let opcode = Opcode::Startup;
-        let body = BodyReqStartup::new(compression);
+        let body = options
+            .iter()
+            .fold(BodyReqStartup::new(compression), |body, (name, value)| {
+                body.with_option(name, value)
+            });
 
         Frame::new(version, vec![flag], opcode, body.as_bytes(), None, vec![])
     }
@@ -5,11 +5,31 @@ use crate::error;
 use crate::frame::FromCursor;
 use crate::types::{cursor_fill_value, try_from_bytes, CString, CStringList, SHORT_LEN};
 
+/// ScyllaDB protocol extension marking prepared statements which are lightweight
+/// transactions with a bit of their metadata flags.
+pub const SCYLLA_LWT_ADD_METADATA_MARK: &str = "SCYLLA_LWT_ADD_METADATA_MARK";
+/// Parameter of `SCYLLA_LWT_ADD_METADATA_MARK` holding the mask of the marking bit.
+pub const LWT_OPTIMIZATION_META_BIT_MASK: &str = "LWT_OPTIMIZATION_META_BIT_MASK";
+
 #[derive(Debug)]
 pub struct BodyResSupported {
     pub data: HashMap<String, Vec<String>>,
 }
 
+impl BodyResSupported {
+    /// Returns the bit of prepared metadata flags marking lightweight transactions, if the
+    /// node supports the `SCYLLA_LWT_ADD_METADATA_MARK` extension.
+    pub fn lwt_metadata_mask(&self) -> Option<i32> {
+        self.data
+            .get(SCYLLA_LWT_ADD_METADATA_MARK)?
+            .iter()
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(name, _)| *name == LWT_OPTIMIZATION_META_BIT_MASK)
+            .and_then(|(_, mask)| mask.parse::<u32>().ok())
+            .map(|mask| mask as i32)
+    }
+}
+
 impl FromCursor for BodyResSupported {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResSupported> {
         let l = try_from_bytes(cursor_fill_value(&mut cursor, &mut [0; SHORT_LEN])?)? as usize;
@@ -45,4 +65,22 @@ mod tests {
         assert_eq!(option_ab[0], "a".to_string());
         assert_eq!(option_ab[1], "b".to_string());
     }
+
+    #[test]
+    fn lwt_metadata_mask() {
+        let mut data = HashMap::new();
+        assert_eq!(
+            BodyResSupported { data: data.clone() }.lwt_metadata_mask(),
+            None
+        );
+
+        data.insert(
+            SCYLLA_LWT_ADD_METADATA_MARK.to_string(),
+            vec!["LWT_OPTIMIZATION_META_BIT_MASK=2147483648".to_string()],
+        );
+        assert_eq!(
+            BodyResSupported { data }.lwt_metadata_mask(),
+            Some(i32::MIN)
+        );
+    }
 }
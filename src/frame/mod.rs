@@ -1,16 +1,23 @@
 //! `frame` module contains general Frame functionality.
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicI16, Ordering};
 
 use crate::compression::Compression;
 use crate::frame::frame_response::ResponseBody;
+pub use crate::frame::protocol_version::ProtocolVersion;
 pub use crate::frame::traits::*;
-use crate::types::to_n_bytes;
+use crate::types::{to_n_bytes, to_short, CBytes, CString, CStringList};
 use uuid::Uuid;
 
 /// Number of stream bytes in accordance to protocol.
 pub const STREAM_LEN: usize = 2;
 /// Number of body length bytes in accordance to protocol.
 pub const LENGTH_LEN: usize = 4;
+/// Number of frame header bytes in accordance to protocol.
+pub const HEADER_LEN: usize = 9;
+/// Maximum frame body length accepted from the server - 256 MiB.
+pub const MAX_BODY_LEN: usize = 256 * 1024 * 1024;
 
 pub mod events;
 pub mod frame_auth_challenge;
@@ -30,7 +37,13 @@ pub mod frame_response;
 pub mod frame_result;
 pub mod frame_startup;
 pub mod frame_supported;
+#[cfg(feature = "runtime")]
 pub mod parser;
+mod protocol_version;
+#[cfg(all(test, feature = "runtime"))]
+mod snapshots;
+#[cfg(all(test, feature = "runtime"))]
+mod test_vectors;
 pub mod traits;
 
 use crate::error;
@@ -53,15 +66,25 @@ fn get_next_stream_id() -> StreamId {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Frame {
     pub version: Version,
+    /// Protocol version the frame is encoded with. Responses have the version negotiated by
+    /// the connection they were received over.
+    pub protocol_version: ProtocolVersion,
     pub flags: Vec<Flag>,
     pub opcode: Opcode,
     pub stream: StreamId,
     pub body: Vec<u8>,
     pub tracing_id: Option<Uuid>,
     pub warnings: Vec<String>,
+    pub custom_payload: HashMap<String, Vec<u8>>,
+    /// Address of the node which sent the frame, set for responses to requests sent by a
+    /// session.
+    pub coordinator: Option<SocketAddr>,
+    /// Bit of prepared metadata flags marking lightweight transactions, set for responses
+    /// received over connections which negotiated the `SCYLLA_LWT_ADD_METADATA_MARK` extension.
+    pub lwt_metadata_mask: Option<i32>,
 }
 
 impl Frame {
@@ -76,17 +99,21 @@ impl Frame {
         let stream = get_next_stream_id();
         Frame {
             version,
+            protocol_version: ProtocolVersion::default(),
             flags,
             opcode,
             stream,
             body,
             tracing_id,
             warnings,
+            custom_payload: HashMap::new(),
+            coordinator: None,
+            lwt_metadata_mask: None,
         }
     }
 
     pub fn get_body(&self) -> error::Result<ResponseBody> {
-        ResponseBody::from(self.body.as_slice(), &self.opcode)
+        ResponseBody::from_for(self.body.as_slice(), &self.opcode, self.protocol_version)
     }
 
     pub fn tracing_id(&self) -> &Option<Uuid> {
@@ -97,18 +124,89 @@ impl Frame {
         &self.warnings
     }
 
+    /// Custom payload sent by the server, e.g. by a custom query handler.
+    pub fn custom_payload(&self) -> &HashMap<String, Vec<u8>> {
+        &self.custom_payload
+    }
+
+    /// Address of the node which sent the frame.
+    pub fn coordinator(&self) -> Option<SocketAddr> {
+        self.coordinator
+    }
+
+    /// Sets custom payload sent with the frame, e.g. to pass options to a custom query handler.
+    /// An empty payload is not sent.
+    pub fn with_custom_payload(mut self, custom_payload: HashMap<String, Vec<u8>>) -> Self {
+        self.flags.retain(|flag| flag != &Flag::CustomPayload);
+        if !custom_payload.is_empty() {
+            self.flags.push(Flag::CustomPayload);
+        }
+
+        self.custom_payload = custom_payload;
+        self
+    }
+
+    /// Sets the protocol version the frame is encoded with. The body is not re-encoded, so it
+    /// is meant for requests whose bodies are the same in all versions, e.g. `STARTUP`.
+    pub fn with_protocol_version(mut self, protocol_version: ProtocolVersion) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// Version byte of the frame, according to its direction and protocol version.
+    fn version_byte(&self) -> u8 {
+        match self.version {
+            Version::Request => self.protocol_version.request_byte(),
+            Version::Response => self.protocol_version.response_byte(),
+        }
+    }
+
+    /// Body preceded by tracing id, warnings and custom payload, if the frame has them.
+    /// Tracing ids and warnings are only sent by the server, so they are written for
+    /// responses only, e.g. when re-encoding a parsed frame.
+    fn full_body(&self) -> Vec<u8> {
+        let mut v = vec![];
+
+        if self.flags.contains(&Flag::Tracing) {
+            if let Some(tracing_id) = self.tracing_id {
+                v.extend_from_slice(tracing_id.as_bytes());
+            }
+        }
+
+        if self.version == Version::Response && self.flags.contains(&Flag::Warning) {
+            let warnings = CStringList {
+                list: self.warnings.iter().cloned().map(CString::new).collect(),
+            };
+            v.extend_from_slice(&warnings.as_bytes());
+        }
+
+        if self.flags.contains(&Flag::CustomPayload) {
+            let mut entries: Vec<_> = self.custom_payload.iter().collect();
+            entries.sort();
+
+            v.extend_from_slice(&to_short(entries.len() as i16));
+            for (key, value) in entries {
+                v.extend_from_slice(&CString::new(key.clone()).as_bytes());
+                v.extend_from_slice(&CBytes::new(value.clone()).as_bytes());
+            }
+        }
+
+        v.extend_from_slice(&self.body);
+        v
+    }
+
     pub fn encode_with(self, compressor: Compression) -> error::Result<Vec<u8>> {
         let mut v = vec![];
 
-        let version_bytes = self.version.as_byte();
+        let version_bytes = self.version_byte();
         let flag_bytes = Flag::many_to_cbytes(&self.flags);
         let opcode_bytes = self.opcode.as_byte();
-        let encoded_body = compressor.encode(self.body)?;
+        let encoded_body = compressor.encode(self.full_body())?;
         let body_len = encoded_body.len();
 
         v.push(version_bytes);
         v.push(flag_bytes);
-        v.extend_from_slice(to_n_bytes(self.stream as u64, STREAM_LEN).as_slice());
+        v.extend_from_slice(to_short(self.stream).as_slice());
         v.push(opcode_bytes);
         v.extend_from_slice(to_n_bytes(body_len as u64, LENGTH_LEN).as_slice());
         v.extend_from_slice(encoded_body.as_slice());
@@ -121,17 +219,18 @@ impl AsBytes for Frame {
     fn as_bytes(&self) -> Vec<u8> {
         let mut v = vec![];
 
-        let version_bytes = self.version.as_byte();
+        let version_bytes = self.version_byte();
         let flag_bytes = Flag::many_to_cbytes(&self.flags);
         let opcode_bytes = self.opcode.as_byte();
-        let body_len = self.body.len();
+        let body = self.full_body();
+        let body_len = body.len();
 
         v.push(version_bytes);
         v.push(flag_bytes);
-        v.extend_from_slice(to_n_bytes(self.stream as u64, STREAM_LEN).as_slice());
+        v.extend_from_slice(to_short(self.stream).as_slice());
         v.push(opcode_bytes);
         v.extend_from_slice(to_n_bytes(body_len as u64, LENGTH_LEN).as_slice());
-        v.extend_from_slice(self.body.as_slice());
+        v.extend_from_slice(body.as_slice());
 
         v
     }
@@ -151,33 +250,13 @@ impl Version {
     /// It returns an actual Cassandra request frame version that CDRS can work with.
     /// This version is based on selected feature - on of `v3`, `v4` or `v5`.
     fn request_version() -> u8 {
-        if cfg!(feature = "v3") {
-            0x03
-        } else if cfg!(feature = "v4") || cfg!(feature = "v5") {
-            0x04
-        } else {
-            panic!(
-                "{}",
-                "Protocol version is not supported. CDRS should be run with protocol feature \
-                 set to v3, v4 or v5"
-            );
-        }
+        ProtocolVersion::default().request_byte()
     }
 
     /// It returns an actual Cassandra response frame version that CDRS can work with.
     /// This version is based on selected feature - on of `v3`, `v4` or `v5`.
     fn response_version() -> u8 {
-        if cfg!(feature = "v3") {
-            0x83
-        } else if cfg!(feature = "v4") || cfg!(feature = "v5") {
-            0x84
-        } else {
-            panic!(
-                "{}",
-                "Protocol version is not supported. CDRS should be run with protocol feature \
-                 set to v3, v4 or v5"
-            );
-        }
+        ProtocolVersion::default().response_byte()
     }
 }
 
@@ -205,29 +284,22 @@ impl From<Vec<u8>> for Version {
             );
         }
         let version = v[0];
-        let req = Version::request_version();
-        let res = Version::response_version();
+        if ProtocolVersion::from_byte(version).is_none() {
+            error!("Unexpected Cassandra version {:?}", version);
+            panic!("Unexpected Cassandra version {:?}", version);
+        }
 
-        if version == req {
+        if version & 0x80 == 0 {
             Version::Request
-        } else if version == res {
-            Version::Response
         } else {
-            error!(
-                "Unexpected Cassandra version {:?}, either {:?} or {:?} is expected",
-                version, req, res
-            );
-            panic!(
-                "Unexpected Cassandra version {:?}, either {:?} or {:?} is expected",
-                version, req, res
-            );
+            Version::Response
         }
     }
 }
 
 /// Frame's flag
 // Is not implemented functionality. Only Igonore works for now
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Flag {
     Compression,
     Tracing,
@@ -316,7 +388,7 @@ impl From<u8> for Flag {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Opcode {
     Error,
     Startup,
@@ -429,6 +501,12 @@ mod tests {
         assert_eq!(Version::from(response), Version::Response);
     }
 
+    #[test]
+    fn test_frame_version_from_any_protocol_version() {
+        assert_eq!(Version::from(vec![0x03]), Version::Request);
+        assert_eq!(Version::from(vec![0x85]), Version::Response);
+    }
+
     #[test]
     fn test_flag_from() {
         assert_eq!(Flag::from(0x01_u8), Flag::Compression);
@@ -493,6 +571,60 @@ mod tests {
         assert_eq!(Flag::get_collection(1), one);
     }
 
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn custom_payload_round_trip() {
+        let mut custom_payload = HashMap::new();
+        custom_payload.insert("k".to_string(), vec![1, 2]);
+        let frame = Frame::new(
+            Version::Request,
+            vec![],
+            Opcode::Options,
+            vec![3],
+            None,
+            vec![],
+        )
+        .with_custom_payload(custom_payload.clone());
+
+        let bytes = frame.as_bytes();
+        let parsed = parser::parse_frame(
+            &tokio::sync::Mutex::new(bytes.as_slice()),
+            Compression::None,
+            crate::compression::DEFAULT_MAX_DECOMPRESSED_LENGTH,
+        )
+        .await
+        .unwrap();
+        assert_eq!(parsed.custom_payload, custom_payload);
+        assert_eq!(parsed.body, vec![3]);
+
+        let frame = frame.with_custom_payload(HashMap::new());
+        assert_eq!(frame.flags, vec![]);
+        assert_eq!(frame.as_bytes().len(), 10);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn decompressed_length_is_limited() {
+        let frame = Frame::new(
+            Version::Response,
+            vec![Flag::Compression],
+            Opcode::Result,
+            vec![0; 64],
+            None,
+            vec![],
+        );
+        let bytes = frame.encode_with(Compression::Snappy).unwrap();
+        let parse = |max_decompressed_length| {
+            let reader = tokio::sync::Mutex::new(bytes.as_slice());
+            async move {
+                parser::parse_frame(&reader, Compression::Snappy, max_decompressed_length).await
+            }
+        };
+
+        assert_eq!(parse(64).await.unwrap().body, vec![0; 64]);
+        assert!(parse(63).await.is_err());
+    }
+
     #[test]
     fn test_opcode_as_byte() {
         assert_eq!(Opcode::Error.as_byte(), 0x00);
@@ -1,16 +1,17 @@
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::ops::Deref;
 use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::sync::Mutex;
 
 use super::*;
-use crate::compression::Compression;
+use crate::compression::{Compression, CompressionStats};
 use crate::error;
 use crate::frame::frame_response::ResponseBody;
 use crate::frame::FromCursor;
 use crate::transport::CDRSTransport;
 use crate::types::data_serialization_types::decode_timeuuid;
-use crate::types::{from_bytes, from_i16_bytes, CStringList, UUID_LEN};
+use crate::types::{from_bytes, from_i16_bytes, CBytes, CIntShort, CString, CStringList, UUID_LEN};
 
 pub async fn from_connection<M, T>(
     conn: &bb8::PooledConnection<'_, M>,
@@ -20,32 +21,89 @@ where
     T: CDRSTransport + Unpin + 'static,
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
 {
-    parse_frame(conn.deref(), compressor).await
+    from_transport(conn.deref(), compressor).await
 }
 
-pub async fn parse_frame<T>(cursor_cell: &Mutex<T>, compressor: Compression) -> error::Result<Frame>
+/// Parses a frame read from given transport, respecting the maximum decompressed length of the
+/// transport and recording its compression statistics.
+pub(crate) async fn from_transport<T>(
+    transport: &Mutex<T>,
+    compressor: Compression,
+) -> error::Result<Frame>
+where
+    T: CDRSTransport + Unpin + 'static,
+{
+    read_from_transport(transport, compressor)
+        .await
+        .and_then(convert_frame_into_result)
+}
+
+/// Parses a frame read from given transport like `from_transport`, but returns ERROR frames as
+/// frames, e.g. to match them with their requests by stream id first.
+pub(crate) async fn read_from_transport<T>(
+    transport: &Mutex<T>,
+    compressor: Compression,
+) -> error::Result<Frame>
+where
+    T: CDRSTransport + Unpin + 'static,
+{
+    let (compression_stats, max_decompressed_length) = {
+        let transport = transport.lock().await;
+        (
+            transport.compression_stats(),
+            transport.max_decompressed_length(),
+        )
+    };
+    read_frame_with_stats(
+        transport,
+        compressor,
+        max_decompressed_length,
+        compression_stats.as_deref(),
+    )
+    .await
+}
+
+/// Parses a frame read from given reader. Compressed bodies cannot be decompressed to more than
+/// `max_decompressed_length` bytes.
+pub async fn parse_frame<T>(
+    cursor_cell: &Mutex<T>,
+    compressor: Compression,
+    max_decompressed_length: usize,
+) -> error::Result<Frame>
 where
     T: AsyncRead + Unpin,
 {
-    let mut version_bytes = [0; Version::BYTE_LENGTH];
-    let mut flag_bytes = [0; Flag::BYTE_LENGTH];
-    let mut opcode_bytes = [0; Opcode::BYTE_LENGTH];
-    let mut stream_bytes = [0; STREAM_LEN];
-    let mut length_bytes = [0; LENGTH_LEN];
-    let mut cursor = cursor_cell.lock().await;
+    read_frame_with_stats(cursor_cell, compressor, max_decompressed_length, None)
+        .await
+        .and_then(convert_frame_into_result)
+}
 
-    // NOTE: order of reads matters
-    cursor.read_exact(&mut version_bytes).await?;
-    cursor.read_exact(&mut flag_bytes).await?;
-    cursor.read_exact(&mut stream_bytes).await?;
-    cursor.read_exact(&mut opcode_bytes).await?;
-    cursor.read_exact(&mut length_bytes).await?;
+/// Parses a whole frame held in memory, e.g. a test fixture, without compression.
+#[cfg(test)]
+pub(crate) fn parse_frame_bytes(bytes: &[u8]) -> error::Result<Frame> {
+    frame_from_bytes(
+        bytes.to_vec(),
+        Compression::None,
+        crate::compression::DEFAULT_MAX_DECOMPRESSED_LENGTH,
+        None,
+    )
+    .and_then(convert_frame_into_result)
+}
+
+async fn read_frame_with_stats<T>(
+    cursor_cell: &Mutex<T>,
+    compressor: Compression,
+    max_decompressed_length: usize,
+    compression_stats: Option<&CompressionStats>,
+) -> error::Result<Frame>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut header = [0; HEADER_LEN];
+    let mut cursor = cursor_cell.lock().await;
 
-    let version = Version::from(version_bytes.to_vec());
-    let flags = Flag::get_collection(flag_bytes[0]);
-    let stream = from_i16_bytes(&stream_bytes);
-    let opcode = Opcode::from(opcode_bytes[0]);
-    let length = from_bytes(&length_bytes) as usize;
+    cursor.read_exact(&mut header).await?;
+    let (protocol_version, length) = parse_header(&header)?;
 
     let mut body_bytes = Vec::with_capacity(length);
     unsafe {
@@ -54,8 +112,95 @@ where
 
     cursor.read_exact(&mut body_bytes).await?;
 
+    decode_frame(
+        &header,
+        protocol_version,
+        body_bytes,
+        compressor,
+        max_decompressed_length,
+        compression_stats,
+    )
+}
+
+/// Parses a whole frame, header included, e.g. one routed to its request by the reader task of
+/// a connection. ERROR frames are returned as frames, like by `read_from_transport`.
+pub(crate) fn frame_from_bytes(
+    mut bytes: Vec<u8>,
+    compressor: Compression,
+    max_decompressed_length: usize,
+    compression_stats: Option<&CompressionStats>,
+) -> error::Result<Frame> {
+    if bytes.len() < HEADER_LEN {
+        return Err(error::Error::from(format!(
+            "Frame of {} bytes is shorter than its header",
+            bytes.len()
+        )));
+    }
+
+    let body_bytes = bytes.split_off(HEADER_LEN);
+    let (protocol_version, length) = parse_header(&bytes)?;
+    if body_bytes.len() != length {
+        return Err(error::Error::from(format!(
+            "Frame body of {} bytes does not match its length {}",
+            body_bytes.len(),
+            length
+        )));
+    }
+
+    decode_frame(
+        &bytes,
+        protocol_version,
+        body_bytes,
+        compressor,
+        max_decompressed_length,
+        compression_stats,
+    )
+}
+
+/// Returns the protocol version and body length of a frame with given header, checking the
+/// maximum body length.
+fn parse_header(header: &[u8]) -> error::Result<(ProtocolVersion, usize)> {
+    let protocol_version = ProtocolVersion::from_byte(header[0]).ok_or_else(|| {
+        error::Error::from(format!(
+            "Unsupported protocol version of frame: {:#04x}",
+            header[0]
+        ))
+    })?;
+
+    let length = from_bytes(&header[HEADER_LEN - LENGTH_LEN..HEADER_LEN]) as usize;
+    if length > MAX_BODY_LEN {
+        return Err(error::Error::from(format!(
+            "Frame body length {} exceeds maximum of {} bytes",
+            length, MAX_BODY_LEN
+        )));
+    }
+
+    Ok((protocol_version, length))
+}
+
+fn decode_frame(
+    header: &[u8],
+    protocol_version: ProtocolVersion,
+    body_bytes: Vec<u8>,
+    compressor: Compression,
+    max_decompressed_length: usize,
+    compression_stats: Option<&CompressionStats>,
+) -> error::Result<Frame> {
+    // NOTE: order of header fields matters
+    let stream_offset = Version::BYTE_LENGTH + Flag::BYTE_LENGTH;
+    let version = Version::from(header[..Version::BYTE_LENGTH].to_vec());
+    let flags = Flag::get_collection(header[Version::BYTE_LENGTH]);
+    let stream = from_i16_bytes(&header[stream_offset..stream_offset + STREAM_LEN]);
+    let opcode = Opcode::from(header[stream_offset + STREAM_LEN]);
+
     let full_body = if flags.iter().any(|flag| flag == &Flag::Compression) {
-        compressor.decode(body_bytes)?
+        let compressed_length = body_bytes.len();
+        let full_body = compressor.decode_with_limit(body_bytes, max_decompressed_length)?;
+        if let Some(compression_stats) = compression_stats {
+            compression_stats.record(compressed_length, full_body.len());
+        }
+
+        full_body
     } else {
         Compression::None.decode(body_bytes)?
     };
@@ -81,24 +226,48 @@ where
         vec![]
     };
 
+    let custom_payload = if flags.iter().any(|flag| flag == &Flag::CustomPayload) {
+        parse_bytes_map(&mut body_cursor)?
+    } else {
+        HashMap::new()
+    };
+
     let mut body = vec![];
 
     std::io::Read::read_to_end(&mut body_cursor, &mut body)?;
 
     let frame = Frame {
         version,
+        protocol_version,
         flags,
         opcode,
         stream,
         body,
         tracing_id,
         warnings,
+        custom_payload,
+        coordinator: None,
+        lwt_metadata_mask: None,
     };
 
-    convert_frame_into_result(frame)
+    Ok(frame)
+}
+
+fn parse_bytes_map(cursor: &mut Cursor<&[u8]>) -> error::Result<HashMap<String, Vec<u8>>> {
+    let len = CIntShort::from_cursor(cursor)?;
+    (0..len)
+        .map(|_| {
+            let key = CString::from_cursor(cursor)?.into_plain();
+            let value = CBytes::from_cursor(cursor)?
+                .into_plain()
+                .unwrap_or_default();
+            Ok((key, value))
+        })
+        .collect()
 }
 
-fn convert_frame_into_result(frame: Frame) -> error::Result<Frame> {
+/// Converts an ERROR frame into `Error::Server`, returning other frames as they are.
+pub(crate) fn convert_frame_into_result(frame: Frame) -> error::Result<Frame> {
     match frame.opcode {
         Opcode::Error => frame.get_body().and_then(|err| match err {
             ResponseBody::Error(err) => Err(error::Error::Server(err)),
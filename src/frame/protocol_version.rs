@@ -0,0 +1,188 @@
+/// Version of the native protocol, deciding the format of request bodies.
+#[derive(Debug, PartialEq, Copy, Clone, Ord, PartialOrd, Eq, Hash)]
+pub enum ProtocolVersion {
+    V3,
+    V4,
+    V5,
+}
+
+impl ProtocolVersion {
+    /// Returns the version of a frame with given version byte of a request or a response, if
+    /// it is supported.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte & 0x7f {
+            0x03 => Some(ProtocolVersion::V3),
+            0x04 => Some(ProtocolVersion::V4),
+            0x05 => Some(ProtocolVersion::V5),
+            _ => None,
+        }
+    }
+
+    /// Returns the next lower supported version, which connections fall back to if a node
+    /// rejects this one.
+    pub fn lower(self) -> Option<Self> {
+        match self {
+            ProtocolVersion::V3 => None,
+            ProtocolVersion::V4 => Some(ProtocolVersion::V3),
+            ProtocolVersion::V5 => Some(ProtocolVersion::V4),
+        }
+    }
+
+    /// Returns version byte of request frames.
+    pub fn request_byte(self) -> u8 {
+        match self {
+            ProtocolVersion::V3 => 0x03,
+            ProtocolVersion::V4 => 0x04,
+            ProtocolVersion::V5 => 0x05,
+        }
+    }
+
+    /// Returns version byte of response frames.
+    pub fn response_byte(self) -> u8 {
+        self.request_byte() | 0x80
+    }
+
+    /// Returns `true` if values can be sent as unset. Older versions only accept nulls, which
+    /// create tombstones.
+    pub fn supports_unset_values(self) -> bool {
+        self >= ProtocolVersion::V4
+    }
+
+    /// Returns `true` if metadata of prepared statements carries indexes of partition key
+    /// columns.
+    pub fn has_pk_indexes(self) -> bool {
+        self >= ProtocolVersion::V4
+    }
+
+    /// Returns `true` if flags of `QUERY`, `EXECUTE` and `BATCH` requests are sent as 4 bytes
+    /// instead of a single byte, and `PREPARE` requests carry flags.
+    pub fn has_int_flags(self) -> bool {
+        self >= ProtocolVersion::V5
+    }
+
+    /// Returns `true` if prepared statements have ids of their result metadata, which are sent
+    /// with `EXECUTE` requests and changed by the server when the metadata changes.
+    pub fn has_result_metadata_id(self) -> bool {
+        self >= ProtocolVersion::V5
+    }
+
+    /// Returns `true` if requests can set the keyspace they are executed in.
+    pub fn supports_request_keyspace(self) -> bool {
+        self >= ProtocolVersion::V5
+    }
+
+    /// Returns `true` if requests can set the current time in seconds, e.g. to test TTLs.
+    pub fn supports_now_in_seconds(self) -> bool {
+        self >= ProtocolVersion::V5
+    }
+}
+
+impl Default for ProtocolVersion {
+    /// Returns the version selected with crate features - `v3` or `v4`. Connections start with
+    /// it and fall back to lower versions if a node doesn't support it.
+    fn default() -> Self {
+        if cfg!(feature = "v3") {
+            ProtocolVersion::V3
+        } else {
+            ProtocolVersion::V4
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_execute::BodyReqExecute;
+    use crate::frame::frame_prepare::BodyReqPrepare;
+    use crate::frame::{AsBytes, Frame};
+    use crate::query::{Query, QueryParamsBuilder, QueryValues};
+    use crate::types::value::Value;
+    use crate::types::CBytesShort;
+
+    #[test]
+    fn query_params_per_version() {
+        let params = QueryParamsBuilder::new()
+            .values(QueryValues::SimpleValues(vec![Value::new_not_set()]))
+            .finalize();
+
+        // unset values cannot be sent with v3
+        assert!(params.check_version(ProtocolVersion::V3).is_err());
+        assert!(params.check_version(ProtocolVersion::V4).is_ok());
+
+        // consistency, flags, values count, value
+        assert_eq!(
+            params.as_bytes_for(ProtocolVersion::V4),
+            vec![0, 1, 1, 0, 1, 255, 255, 255, 254]
+        );
+        assert_eq!(
+            params.as_bytes_for(ProtocolVersion::V5),
+            vec![0, 1, 0, 0, 0, 1, 0, 1, 255, 255, 255, 254]
+        );
+    }
+
+    #[test]
+    fn v5_query_params() {
+        let params = QueryParamsBuilder::new()
+            .keyspace("ks".into())
+            .now_in_seconds(7)
+            .finalize();
+
+        assert!(params.check_version(ProtocolVersion::V4).is_err());
+        assert!(params.check_version(ProtocolVersion::V5).is_ok());
+
+        // consistency, flags, keyspace, now in seconds
+        assert_eq!(
+            params.as_bytes_for(ProtocolVersion::V5),
+            vec![0, 1, 0, 0, 1, 0x80, 0, 2, b'k', b's', 0, 0, 0, 7]
+        );
+
+        let query = Query {
+            query: "SELECT".into(),
+            params,
+        };
+        assert!(Frame::new_query_for(&query, vec![], ProtocolVersion::V4).is_err());
+        let frame = Frame::new_query_for(&query, vec![], ProtocolVersion::V5).unwrap();
+        assert_eq!(frame.as_bytes()[0], 0x05);
+    }
+
+    #[test]
+    fn prepare_per_version() {
+        let body = BodyReqPrepare::new("SELECT".into());
+
+        assert_eq!(
+            body.as_bytes_for(ProtocolVersion::V4),
+            vec![0, 0, 0, 6, b'S', b'E', b'L', b'E', b'C', b'T']
+        );
+        assert_eq!(
+            body.as_bytes_for(ProtocolVersion::V5),
+            vec![0, 0, 0, 6, b'S', b'E', b'L', b'E', b'C', b'T', 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn execute_per_version() {
+        let id = CBytesShort::new(vec![1]);
+        let result_metadata_id = CBytesShort::new(vec![2]);
+        let params = QueryParamsBuilder::new().finalize();
+        let body = BodyReqExecute::new(&id, &params).result_metadata_id(Some(&result_metadata_id));
+
+        assert_eq!(
+            body.as_bytes_for(ProtocolVersion::V4),
+            vec![0, 1, 1, 0, 1, 0]
+        );
+        assert_eq!(
+            body.as_bytes_for(ProtocolVersion::V5),
+            vec![0, 1, 1, 0, 1, 2, 0, 1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn version_bytes() {
+        assert_eq!(ProtocolVersion::V3.request_byte(), 0x03);
+        assert_eq!(ProtocolVersion::V4.response_byte(), 0x84);
+        assert_eq!(ProtocolVersion::V5.response_byte(), 0x85);
+        assert_eq!(ProtocolVersion::from_byte(0x84), Some(ProtocolVersion::V4));
+        assert_eq!(ProtocolVersion::from_byte(0x03), Some(ProtocolVersion::V3));
+        assert_eq!(ProtocolVersion::from_byte(0x82), None);
+    }
+}
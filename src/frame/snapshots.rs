@@ -0,0 +1,367 @@
+//! Snapshot tests of frame serialization against frames in `fixtures/frame/snapshots`, one
+//! directory per protocol version. Fixtures hold whole v3 and v4 frames, header included. They
+//! were produced by the driver and checked by hand against the protocol specs, not captured from
+//! servers, so they catch unintended changes of the produced or accepted bytes rather than prove
+//! conformance with servers.
+//!
+//! Request fixtures are compared with request bodies serialized for their protocol version.
+//! All fixtures are parsed as frames and re-serialized, which must give identical bytes, and
+//! response fixtures are parsed into typed bodies.
+//!
+//! Frames recorded from real nodes by `tests/capture_frames.rs` are stored in
+//! `fixtures/frame/captured` along with the release version of the node, and are parsed and
+//! re-serialized the same way. See `fixtures/frame/README.md` for how to record them. Protocol
+//! v5 is out of scope: nodes wrap v5 frames in checksummed segments after the handshake, which
+//! the driver doesn't implement, so it cannot talk v5 to a node.
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use super::*;
+use crate::consistency::Consistency;
+use crate::frame::events::{ServerEvent, SimpleServerEvent, StatusChangeType};
+use crate::frame::frame_auth_response::BodyReqAuthResponse;
+use crate::frame::frame_batch::{BatchQuery, BatchQuerySubj, BatchType, BodyReqBatch};
+use crate::frame::frame_error::{AdditionalErrorInfo, CDRSError};
+use crate::frame::frame_execute::BodyReqExecute;
+use crate::frame::frame_prepare::BodyReqPrepare;
+use crate::frame::frame_query::BodyReqQuery;
+use crate::frame::frame_register::BodyReqRegister;
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::frame_result::ResResultBody;
+use crate::frame::frame_startup::BodyReqStartup;
+use crate::frame::parser::parse_frame_bytes as parse;
+use crate::query::{QueryParamsBuilder, QueryValues};
+use crate::types::value::Value;
+use crate::types::{CBytes, CBytesShort, CStringLong};
+
+const VERSIONS: &[ProtocolVersion] = &[ProtocolVersion::V3, ProtocolVersion::V4];
+
+const SELECT: &str = "SELECT id FROM ks.tbl WHERE id = ?";
+const INSERT: &str = "INSERT INTO ks.tbl (id) VALUES (?)";
+
+fn fixtures_dir(kind: &str, version: ProtocolVersion) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "fixtures/frame",
+        kind,
+        &format!("v{}", version.request_byte()),
+    ]
+    .iter()
+    .collect()
+}
+
+fn fixture(version: ProtocolVersion, name: &str) -> Vec<u8> {
+    let path = fixtures_dir("snapshots", version).join(name);
+
+    fs::read(&path).unwrap_or_else(|error| panic!("cannot read {:?}: {}", path, error))
+}
+
+/// Splits a frame into its version byte, flags, stream, opcode and body, checking the length.
+fn split(bytes: &[u8]) -> (u8, u8, StreamId, Opcode, &[u8]) {
+    let (header, body) = bytes.split_at(9);
+    let length = i32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+    assert_eq!(length as usize, body.len());
+
+    (
+        header[0],
+        header[1],
+        i16::from_be_bytes([header[2], header[3]]),
+        Opcode::from(header[4]),
+        body,
+    )
+}
+
+/// Re-serializes a fixture parsed as a frame.
+fn check_round_trip(version: ProtocolVersion, name: &str, bytes: &[u8]) {
+    let frame = parse(bytes).unwrap_or_else(|error| panic!("{:?} {}: {}", version, name, error));
+    assert_eq!(frame.protocol_version, version, "{}", name);
+    assert_eq!(frame.as_bytes(), bytes, "{:?} {}", version, name);
+}
+
+fn int_values() -> QueryValues {
+    QueryValues::SimpleValues(vec![Value::from(7_i32)])
+}
+
+fn request_bodies(version: ProtocolVersion) -> Vec<(&'static str, Opcode, Vec<u8>)> {
+    let params = QueryParamsBuilder::new()
+        .consistency(Consistency::One)
+        .values(int_values())
+        .finalize();
+    let id = CBytesShort::new(vec![0x01, 0x02]);
+    let result_metadata_id = CBytesShort::new(vec![0x03]);
+    let batch = BodyReqBatch {
+        batch_type: BatchType::Logged,
+        queries: vec![BatchQuery {
+            is_prepared: false,
+            subject: BatchQuerySubj::QueryString(CStringLong::new(INSERT.into())),
+            values: int_values(),
+        }],
+        consistency: Consistency::One,
+        query_flags: vec![],
+        serial_consistency: None,
+        timestamp: None,
+    };
+
+    vec![
+        (
+            "startup-request",
+            Opcode::Startup,
+            BodyReqStartup::new(None).as_bytes_for(version),
+        ),
+        ("options-request", Opcode::Options, vec![]),
+        (
+            "query-request",
+            Opcode::Query,
+            BodyReqQuery {
+                query: CStringLong::new(SELECT.into()),
+                query_params: params.clone(),
+            }
+            .as_bytes_for(version),
+        ),
+        (
+            "prepare-request",
+            Opcode::Prepare,
+            BodyReqPrepare::new(SELECT.into()).as_bytes_for(version),
+        ),
+        (
+            "execute-request",
+            Opcode::Execute,
+            BodyReqExecute::new(&id, &params)
+                .result_metadata_id(Some(&result_metadata_id))
+                .as_bytes_for(version),
+        ),
+        (
+            "register-request",
+            Opcode::Register,
+            BodyReqRegister {
+                events: vec![
+                    SimpleServerEvent::TopologyChange,
+                    SimpleServerEvent::StatusChange,
+                    SimpleServerEvent::SchemaChange,
+                ],
+            }
+            .as_bytes_for(version),
+        ),
+        ("batch-request", Opcode::Batch, batch.as_bytes_for(version)),
+        (
+            "auth-response-request",
+            Opcode::AuthResponse,
+            BodyReqAuthResponse::new(CBytes::new(b"\0cassandra\0cassandra".to_vec()))
+                .as_bytes_for(version),
+        ),
+    ]
+}
+
+#[test]
+fn request_frames() {
+    for &version in VERSIONS {
+        for (name, opcode, body) in request_bodies(version) {
+            let bytes = fixture(version, name);
+            let (version_byte, flags, stream, fixture_opcode, fixture_body) = split(&bytes);
+
+            assert_eq!(
+                version_byte,
+                version.request_byte(),
+                "{:?} {}",
+                version,
+                name
+            );
+            assert_eq!(flags, 0, "{:?} {}", version, name);
+            assert_eq!(stream, 1, "{:?} {}", version, name);
+            assert_eq!(fixture_opcode, opcode, "{:?} {}", version, name);
+            assert_eq!(fixture_body, body.as_slice(), "{:?} {}", version, name);
+
+            check_round_trip(version, name, &bytes);
+        }
+    }
+}
+
+const RESPONSES: &[&str] = &[
+    "ready-response",
+    "authenticate-response",
+    "supported-response",
+    "void-result-response",
+    "rows-result-response",
+    "set-keyspace-result-response",
+    "prepared-result-response",
+    "schema-change-result-response",
+    "event-response",
+    "auth-challenge-response",
+    "auth-success-response",
+    "traced-rows-result-response",
+];
+
+/// Responses with warnings and custom payloads, which are not sent in protocol v3.
+const V4_RESPONSES: &[&str] = &[
+    "warning-void-result-response",
+    "custom-payload-void-result-response",
+];
+
+fn check_response_body(name: &str, opcode: Opcode, body: ResponseBody) {
+    match (name, body) {
+        ("ready-response", ResponseBody::Ready(_)) => {}
+        ("authenticate-response", ResponseBody::Authenticate(body)) => assert_eq!(
+            body.data.as_str(),
+            "org.apache.cassandra.auth.PasswordAuthenticator"
+        ),
+        ("supported-response", ResponseBody::Supported(body)) => {
+            assert_eq!(body.data["COMPRESSION"], vec!["lz4", "snappy"]);
+            assert_eq!(body.data["CQL_VERSION"], vec!["3.4.5"]);
+        }
+        ("rows-result-response", ResponseBody::Result(ResResultBody::Rows(rows)))
+        | ("traced-rows-result-response", ResponseBody::Result(ResResultBody::Rows(rows))) => {
+            assert_eq!(rows.metadata.col_specs[0].name.as_str(), "id");
+            assert_eq!(
+                rows.rows_content[0][0].as_slice(),
+                Some(&[0x00, 0x00, 0x00, 0x07][..])
+            );
+        }
+        (
+            "set-keyspace-result-response",
+            ResponseBody::Result(ResResultBody::SetKeyspace(body)),
+        ) => {
+            assert_eq!(body.body.as_str(), "ks")
+        }
+        ("prepared-result-response", ResponseBody::Result(ResResultBody::Prepared(body))) => {
+            assert_eq!(body.id.into_plain(), Some(vec![0x01, 0x02]));
+            assert_eq!(body.metadata.columns_count, 1);
+            assert_eq!(body.result_metadata.col_specs[0].name.as_str(), "id");
+        }
+        ("schema-change-result-response", ResponseBody::Result(ResResultBody::SchemaChange(_))) => {
+        }
+        ("event-response", ResponseBody::Event(body)) => match body.event {
+            ServerEvent::StatusChange(change) => {
+                assert_eq!(change.change_type, StatusChangeType::Up);
+                assert_eq!(change.addr.addr, "127.0.0.1:9042".parse().unwrap());
+            }
+            event => panic!("unexpected event {:?}", event),
+        },
+        ("auth-challenge-response", ResponseBody::AuthChallenge(body)) => {
+            assert_eq!(body.data.into_plain(), Some(vec![0x01, 0x02]))
+        }
+        ("auth-success-response", ResponseBody::AuthSuccess(_)) => {}
+        ("warning-void-result-response", ResponseBody::Result(ResResultBody::Void(_)))
+        | ("custom-payload-void-result-response", ResponseBody::Result(ResResultBody::Void(_)))
+        | ("void-result-response", ResponseBody::Result(ResResultBody::Void(_))) => {}
+        (name, body) => panic!("unexpected {:?} body of {}: {:?}", opcode, name, body),
+    }
+}
+
+#[test]
+fn response_frames() {
+    for &version in VERSIONS {
+        let v4_responses = if version >= ProtocolVersion::V4 {
+            V4_RESPONSES
+        } else {
+            &[]
+        };
+
+        for &name in RESPONSES.iter().chain(v4_responses) {
+            let bytes = fixture(version, name);
+            let (version_byte, _, _, opcode, _) = split(&bytes);
+            assert_eq!(
+                version_byte,
+                version.response_byte(),
+                "{:?} {}",
+                version,
+                name
+            );
+
+            let frame =
+                parse(&bytes).unwrap_or_else(|error| panic!("{:?} {}: {}", version, name, error));
+            check_response_body(name, opcode, frame.get_body().unwrap());
+
+            check_round_trip(version, name, &bytes);
+        }
+    }
+}
+
+#[test]
+fn response_frame_extras() {
+    for &version in VERSIONS {
+        let frame = parse(&fixture(version, "traced-rows-result-response")).unwrap();
+        assert_eq!(
+            frame.tracing_id().unwrap().to_string(),
+            "8f4c3b00-e8d4-11ee-bd6a-7b6e8e9c2a1f"
+        );
+
+        if version >= ProtocolVersion::V4 {
+            let frame = parse(&fixture(version, "warning-void-result-response")).unwrap();
+            assert_eq!(
+                frame.warnings(),
+                &vec!["Read 1001 live rows and 5000 tombstone cells".to_string()]
+            );
+
+            let frame = parse(&fixture(version, "custom-payload-void-result-response")).unwrap();
+            assert_eq!(frame.custom_payload().get("k"), Some(&vec![0x01]));
+        }
+    }
+}
+
+#[test]
+fn error_frames() {
+    for &version in VERSIONS {
+        let bytes = fixture(version, "error-response");
+        let (version_byte, _, _, opcode, body) = split(&bytes);
+        assert_eq!(version_byte, version.response_byte());
+        assert_eq!(opcode, Opcode::Error);
+
+        let error = CDRSError::from_cursor(&mut Cursor::new(body)).unwrap();
+        assert_eq!(error.message.as_str(), "fail");
+        match error.additional_info {
+            AdditionalErrorInfo::Unavailable(info) => {
+                assert_eq!(info.cl, Consistency::Quorum);
+                assert_eq!(info.required, 3);
+                assert_eq!(info.alive, 1);
+            }
+            info => panic!("unexpected additional info {:?}", info),
+        }
+    }
+}
+
+#[test]
+fn captured_frames() {
+    for &version in VERSIONS {
+        // captures are recorded on demand, see `fixtures/frame/README.md`
+        let entries = match fs::read_dir(fixtures_dir("captured", version)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if name == "SERVER" {
+                continue;
+            }
+
+            let bytes = fs::read(&path).unwrap();
+            let (version_byte, _, _, opcode, body) = split(&bytes);
+            let expected_version_byte = if name.ends_with("-response") {
+                version.response_byte()
+            } else {
+                version.request_byte()
+            };
+            assert_eq!(
+                version_byte, expected_version_byte,
+                "{:?} {}",
+                version, name
+            );
+
+            if opcode == Opcode::Error {
+                CDRSError::from_cursor(&mut Cursor::new(body))
+                    .unwrap_or_else(|error| panic!("{:?} {}: {}", version, name, error));
+                continue;
+            }
+
+            if name.ends_with("-response") {
+                let frame = parse(&bytes).unwrap();
+                frame
+                    .get_body()
+                    .unwrap_or_else(|error| panic!("{:?} {}: {}", version, name, error));
+            }
+            check_round_trip(version, &name, &bytes);
+        }
+    }
+}
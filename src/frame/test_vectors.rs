@@ -0,0 +1,1159 @@
+//! Wire-level test vectors in accordance to
+//! [Cassandra protocol v4](https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec).
+//! They lock down serialization of frames and their bodies, so any change in the produced or
+//! accepted bytes gets noticed when refactoring.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use proptest::prelude::*;
+
+use super::*;
+use crate::consistency::Consistency;
+use crate::frame::events::{
+    ChangeSchemeOptions, ChangeType, SchemaChange, ServerEvent, SimpleServerEvent,
+    StatusChangeType, Target, TopologyChangeType,
+};
+use crate::frame::frame_auth_response::BodyReqAuthResponse;
+use crate::frame::frame_batch::{BatchQuery, BatchQuerySubj, BatchType, BodyReqBatch};
+use crate::frame::frame_error::{AdditionalErrorInfo, CDRSError};
+use crate::frame::frame_execute::BodyReqExecute;
+use crate::frame::frame_options::BodyReqOptions;
+use crate::frame::frame_prepare::BodyReqPrepare;
+use crate::frame::frame_query::BodyReqQuery;
+use crate::frame::frame_register::BodyReqRegister;
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::frame_result::{ColType, ResResultBody};
+use crate::frame::frame_startup::BodyReqStartup;
+use crate::frame::parser::parse_frame_bytes as parse;
+use crate::query::{QueryFlagSet, QueryFlags, QueryParams, QueryValues};
+use crate::types::value::{Bytes, Value};
+use crate::types::{
+    cursor_next_value, to_int, to_short, try_i_from_bytes, CBytes, CBytesShort, CInt, CIntShort,
+    CString, CStringList, CStringLong,
+};
+
+#[cfg(not(feature = "v3"))]
+/// OPTIONS request frame with stream id 1 and empty body.
+pub const OPTIONS_REQUEST: &[u8] = &[0x04, 0x00, 0x00, 0x01, 0x05, 0x00, 0x00, 0x00, 0x00];
+
+#[cfg(not(feature = "v3"))]
+/// READY response frame with stream id 1 and empty body.
+pub const READY_RESPONSE: &[u8] = &[0x84, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00];
+
+#[cfg(not(feature = "v3"))]
+/// Void RESULT response frame with stream id 1 and custom payload `{"k": [0x01]}`.
+pub const CUSTOM_PAYLOAD_RESPONSE: &[u8] = &[
+    0x84, 0x04, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x0E, // header
+    0x00, 0x01, // map length
+    0x00, 0x01, b'k', // key
+    0x00, 0x00, 0x00, 0x01, 0x01, // value
+    0x00, 0x00, 0x00, 0x01, // body
+];
+
+/// STARTUP request body without compression: `{"CQL_VERSION": "3.0.0"}`.
+pub const STARTUP_BODY: &[u8] = &[
+    0x00, 0x01, // map length
+    0x00, 0x0B, b'C', b'Q', b'L', b'_', b'V', b'E', b'R', b'S', b'I', b'O', b'N', // key
+    0x00, 0x05, b'3', b'.', b'0', b'.', b'0', // value
+];
+
+/// REGISTER request body for `TOPOLOGY_CHANGE` events.
+pub const REGISTER_BODY: &[u8] = &[
+    0x00, 0x01, // list length
+    0x00, 0x0F, b'T', b'O', b'P', b'O', b'L', b'O', b'G', b'Y', b'_', b'C', b'H', b'A', b'N', b'G',
+    b'E',
+];
+
+/// ERROR response body of Unavailable error at QUORUM with 3 required and 1 alive replica.
+pub const UNAVAILABLE_ERROR_BODY: &[u8] = &[
+    0x00, 0x00, 0x10, 0x00, // error code
+    0x00, 0x04, b'f', b'a', b'i', b'l', // message
+    0x00, 0x04, // consistency
+    0x00, 0x00, 0x00, 0x03, // required
+    0x00, 0x00, 0x00, 0x01, // alive
+];
+
+/// RESULT response body of kind Void.
+pub const VOID_RESULT_BODY: &[u8] = &[0x00, 0x00, 0x00, 0x01];
+
+/// RESULT response body of kind Set_keyspace for keyspace `test`.
+pub const SET_KEYSPACE_RESULT_BODY: &[u8] = &[
+    0x00, 0x00, 0x00, 0x03, // kind
+    0x00, 0x04, b't', b'e', b's', b't', // keyspace
+];
+
+/// RESULT response body of kind Rows for `ks.tbl` with a single `int` column `id` and a single
+/// row containing 7.
+pub const ROWS_RESULT_BODY: &[u8] = &[
+    0x00, 0x00, 0x00, 0x02, // kind
+    0x00, 0x00, 0x00, 0x01, // flags - global table spec
+    0x00, 0x00, 0x00, 0x01, // columns count
+    0x00, 0x02, b'k', b's', // keyspace
+    0x00, 0x03, b't', b'b', b'l', // table
+    0x00, 0x02, b'i', b'd', // column name
+    0x00, 0x09, // column type - int
+    0x00, 0x00, 0x00, 0x01, // rows count
+    0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x07, // value
+];
+
+/// Protocol v5 RESULT response frame of kind Prepared with stream id 1, for `ks.tbl` with
+/// a single `int` partition key column `id`, statement id `0xAA` and result metadata id `0xBB`.
+pub const V5_PREPARED_RESPONSE: &[u8] = &[
+    0x85, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x2F, // header
+    0x00, 0x00, 0x00, 0x04, // kind
+    0x00, 0x01, 0xAA, // id
+    0x00, 0x01, 0xBB, // result metadata id
+    0x00, 0x00, 0x00, 0x01, // flags - global table spec
+    0x00, 0x00, 0x00, 0x01, // columns count
+    0x00, 0x00, 0x00, 0x01, // partition key count
+    0x00, 0x00, // partition key index
+    0x00, 0x02, b'k', b's', // keyspace
+    0x00, 0x03, b't', b'b', b'l', // table
+    0x00, 0x02, b'i', b'd', // column name
+    0x00, 0x09, // column type - int
+    0x00, 0x00, 0x00, 0x00, // result metadata flags
+    0x00, 0x00, 0x00, 0x00, // result metadata columns count
+];
+
+/// Protocol v3 RESULT response frame of kind Prepared with stream id 1, for the same statement
+/// as `V5_PREPARED_RESPONSE`, without result metadata id and partition key indexes.
+pub const V3_PREPARED_RESPONSE: &[u8] = &[
+    0x83, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x26, // header
+    0x00, 0x00, 0x00, 0x04, // kind
+    0x00, 0x01, 0xAA, // id
+    0x00, 0x00, 0x00, 0x01, // flags - global table spec
+    0x00, 0x00, 0x00, 0x01, // columns count
+    0x00, 0x02, b'k', b's', // keyspace
+    0x00, 0x03, b't', b'b', b'l', // table
+    0x00, 0x02, b'i', b'd', // column name
+    0x00, 0x09, // column type - int
+    0x00, 0x00, 0x00, 0x00, // result metadata flags
+    0x00, 0x00, 0x00, 0x00, // result metadata columns count
+];
+
+/// Protocol v5 RESULT response frame of kind Rows with stream id 1, without columns and rows,
+/// reporting new result metadata id `0x07`.
+pub const V5_METADATA_CHANGED_RESPONSE: &[u8] = &[
+    0x85, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x13, // header
+    0x00, 0x00, 0x00, 0x02, // kind
+    0x00, 0x00, 0x00, 0x08, // flags - metadata changed
+    0x00, 0x00, 0x00, 0x00, // columns count
+    0x00, 0x01, 0x07, // new metadata id
+    0x00, 0x00, 0x00, 0x00, // rows count
+];
+
+fn from_cursor<T: FromCursor>(bytes: &[u8]) -> T {
+    T::from_cursor(&mut Cursor::new(bytes)).unwrap()
+}
+
+fn request_frame(opcode: Opcode, stream: StreamId, body: Vec<u8>) -> Frame {
+    Frame {
+        version: Version::Request,
+        protocol_version: ProtocolVersion::default(),
+        flags: vec![],
+        opcode,
+        stream,
+        body,
+        tracing_id: None,
+        warnings: vec![],
+        custom_payload: Default::default(),
+        coordinator: None,
+        lwt_metadata_mask: None,
+    }
+}
+
+#[test]
+#[cfg(not(feature = "v3"))]
+fn options_request() {
+    let frame = request_frame(Opcode::Options, 1, vec![]);
+    assert_eq!(frame.as_bytes(), OPTIONS_REQUEST);
+}
+
+#[test]
+#[cfg(not(feature = "v3"))]
+fn ready_response() {
+    let frame = parse(READY_RESPONSE).unwrap();
+    assert_eq!(frame.version, Version::Response);
+    assert_eq!(frame.opcode, Opcode::Ready);
+    assert_eq!(frame.stream, 1);
+    assert!(frame.body.is_empty());
+}
+
+#[test]
+#[cfg(not(feature = "v3"))]
+fn oversized_body_rejected() {
+    let mut bytes = READY_RESPONSE[..5].to_vec();
+    bytes.extend_from_slice(&[0x7f, 0xff, 0xff, 0xff]);
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+#[cfg(not(feature = "v3"))]
+fn custom_payload_response() {
+    let frame = parse(CUSTOM_PAYLOAD_RESPONSE).unwrap();
+    assert_eq!(frame.custom_payload().get("k"), Some(&vec![0x01]));
+    assert_eq!(frame.body, VOID_RESULT_BODY);
+}
+
+#[test]
+fn startup_body() {
+    assert_eq!(BodyReqStartup::new(None).as_bytes(), STARTUP_BODY);
+}
+
+#[test]
+fn register_body() {
+    let body = BodyReqRegister {
+        events: vec![SimpleServerEvent::TopologyChange],
+    };
+    assert_eq!(body.as_bytes(), REGISTER_BODY);
+}
+
+#[test]
+fn unavailable_error_body() {
+    let error: CDRSError = from_cursor(UNAVAILABLE_ERROR_BODY);
+    assert_eq!(error.error_code, 0x1000);
+    assert_eq!(error.message.as_str(), "fail");
+    match error.additional_info {
+        AdditionalErrorInfo::Unavailable(info) => {
+            assert_eq!(info.cl, Consistency::Quorum);
+            assert_eq!(info.required, 3);
+            assert_eq!(info.alive, 1);
+        }
+        info => panic!("unexpected additional info {:?}", info),
+    }
+}
+
+#[test]
+fn v5_prepared_response() {
+    let frame = parse(V5_PREPARED_RESPONSE).unwrap();
+    assert_eq!(frame.protocol_version, ProtocolVersion::V5);
+
+    let prepared = frame.get_body().unwrap().into_prepared().unwrap();
+    assert_eq!(prepared.id, CBytesShort::new(vec![0xAA]));
+    assert_eq!(
+        prepared.result_metadata_id,
+        Some(CBytesShort::new(vec![0xBB]))
+    );
+    assert_eq!(prepared.metadata.pk_indexes, vec![0]);
+    assert_eq!(prepared.metadata.col_specs[0].name.as_str(), "id");
+}
+
+#[test]
+fn v3_prepared_response() {
+    let frame = parse(V3_PREPARED_RESPONSE).unwrap();
+    assert_eq!(frame.protocol_version, ProtocolVersion::V3);
+
+    let prepared = frame.get_body().unwrap().into_prepared().unwrap();
+    assert_eq!(prepared.id, CBytesShort::new(vec![0xAA]));
+    assert_eq!(prepared.result_metadata_id, None);
+    assert!(prepared.metadata.pk_indexes.is_empty());
+    assert_eq!(prepared.metadata.col_specs[0].name.as_str(), "id");
+}
+
+#[test]
+fn v5_metadata_changed_response() {
+    let frame = parse(V5_METADATA_CHANGED_RESPONSE).unwrap();
+    let metadata = frame.get_body().unwrap().as_rows_metadata().unwrap();
+    assert_eq!(metadata.new_metadata_id, Some(CBytesShort::new(vec![0x07])));
+}
+
+#[test]
+fn void_result_body() {
+    let body: ResResultBody = from_cursor(VOID_RESULT_BODY);
+    assert!(matches!(body, ResResultBody::Void(_)));
+}
+
+#[test]
+fn set_keyspace_result_body() {
+    let body: ResResultBody = from_cursor(SET_KEYSPACE_RESULT_BODY);
+    assert_eq!(body.into_set_keyspace().unwrap().body.as_str(), "test");
+}
+
+#[test]
+fn rows_result_body() {
+    let body: ResResultBody = from_cursor(ROWS_RESULT_BODY);
+    match body {
+        ResResultBody::Rows(rows) => {
+            let col_spec = &rows.metadata.col_specs[0];
+            assert_eq!(col_spec.name.as_str(), "id");
+            assert!(matches!(col_spec.col_type.id, ColType::Int));
+            assert_eq!(rows.rows_count, 1);
+            assert_eq!(
+                rows.rows_content[0][0].as_slice(),
+                Some(&[0x00, 0x00, 0x00, 0x07][..])
+            );
+        }
+        body => panic!("unexpected body {:?}", body),
+    }
+}
+
+fn opcode_strategy() -> impl Strategy<Value = u8> {
+    // error frames are converted into errors by the parser, so they are covered separately
+    proptest::sample::select(vec![
+        0x01, 0x02, 0x03, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10,
+    ])
+}
+
+proptest! {
+    #[test]
+    fn frame_round_trip(
+        opcode in opcode_strategy(),
+        stream in 0..i16::MAX,
+        body in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let bytes = request_frame(Opcode::from(opcode), stream, body.clone()).as_bytes();
+        let frame = parse(&bytes).unwrap();
+
+        prop_assert_eq!(frame.version, Version::Request);
+        prop_assert_eq!(frame.opcode.as_byte(), opcode);
+        prop_assert_eq!(frame.stream, stream);
+        prop_assert_eq!(frame.as_bytes(), bytes);
+        prop_assert_eq!(frame.body, body);
+    }
+
+    #[test]
+    fn string_round_trip(value in "\\PC{0,64}") {
+        let decoded: CString = from_cursor(&CString::new(value.clone()).as_bytes());
+        prop_assert_eq!(decoded.into_plain(), value);
+    }
+
+    #[test]
+    fn long_string_round_trip(value in "\\PC{0,256}") {
+        let decoded: CStringLong = from_cursor(&CStringLong::new(value.clone()).as_bytes());
+        prop_assert_eq!(decoded.into_plain(), value);
+    }
+
+    #[test]
+    fn string_list_round_trip(values in proptest::collection::vec("\\PC{0,16}", 0..16)) {
+        let list = CStringList {
+            list: values.iter().cloned().map(CString::new).collect(),
+        };
+        let decoded: CStringList = from_cursor(&list.as_bytes());
+        prop_assert_eq!(decoded.into_plain(), values);
+    }
+
+    #[test]
+    fn bytes_round_trip(value in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let decoded: CBytes = from_cursor(&CBytes::new(value.clone()).as_bytes());
+        prop_assert_eq!(decoded.into_plain(), Some(value));
+    }
+
+    #[test]
+    fn short_bytes_round_trip(value in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let decoded: CBytesShort = from_cursor(&CBytesShort::new(value.clone()).as_bytes());
+        prop_assert_eq!(decoded.into_plain(), Some(value));
+    }
+}
+
+#[test]
+fn options_body() {
+    assert!(BodyReqOptions.as_bytes().is_empty());
+}
+
+#[test]
+fn ready_body() {
+    assert!(matches!(
+        response(Opcode::Ready, &[]),
+        ResponseBody::Ready(_)
+    ));
+}
+
+fn next<T: FromCursor>(cursor: &mut Cursor<&[u8]>) -> T {
+    T::from_cursor(cursor).unwrap()
+}
+
+fn next_byte(cursor: &mut Cursor<&[u8]>) -> u8 {
+    cursor_next_value(cursor, 1).unwrap()[0]
+}
+
+fn next_bigint(cursor: &mut Cursor<&[u8]>) -> i64 {
+    try_i_from_bytes(&cursor_next_value(cursor, 8).unwrap()).unwrap()
+}
+
+fn next_values(cursor: &mut Cursor<&[u8]>) -> Vec<Option<Vec<u8>>> {
+    (0..next::<CIntShort>(cursor))
+        .map(|_| next::<CBytes>(cursor).into_plain())
+        .collect()
+}
+
+fn response(opcode: Opcode, bytes: &[u8]) -> ResponseBody {
+    ResponseBody::from_for(bytes, &opcode, ProtocolVersion::V4).unwrap()
+}
+
+fn string(value: &str) -> Vec<u8> {
+    CString::new(value.to_string()).as_bytes()
+}
+
+fn inet(addr: &SocketAddr) -> Vec<u8> {
+    let mut bytes = vec![4];
+    if let IpAddr::V4(ip) = addr.ip() {
+        bytes.extend_from_slice(&ip.octets());
+    }
+    bytes.extend_from_slice(&to_int(addr.port() as i32));
+    bytes
+}
+
+fn simple_values(values: &[Option<Vec<u8>>]) -> QueryValues {
+    QueryValues::SimpleValues(
+        values
+            .iter()
+            .cloned()
+            .map(|value| Value::from(value.map(Bytes::new)))
+            .collect(),
+    )
+}
+
+/// Parameters of `QUERY` and `EXECUTE` requests, as read back from their bytes.
+#[derive(Debug, Clone, PartialEq)]
+struct Params {
+    consistency: Consistency,
+    values: Option<Vec<Option<Vec<u8>>>>,
+    page_size: Option<i32>,
+    paging_state: Option<Vec<u8>>,
+    serial_consistency: Option<Consistency>,
+    timestamp: Option<i64>,
+}
+
+impl Params {
+    fn to_query_params(&self) -> QueryParams {
+        let mut params = QueryParams {
+            consistency: self.consistency,
+            page_size: self.page_size,
+            paging_state: self.paging_state.clone().map(CBytes::new),
+            serial_consistency: self.serial_consistency,
+            timestamp: self.timestamp,
+            ..Default::default()
+        };
+        if let Some(ref values) = self.values {
+            params.set_values(simple_values(values));
+        }
+        if self.page_size.is_some() {
+            params.flags.push(QueryFlags::PageSize);
+        }
+        if self.paging_state.is_some() {
+            params.flags.push(QueryFlags::WithPagingState);
+        }
+        if self.serial_consistency.is_some() {
+            params.flags.push(QueryFlags::WithSerialConsistency);
+        }
+        if self.timestamp.is_some() {
+            params.flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+        params
+    }
+
+    fn read(cursor: &mut Cursor<&[u8]>, version: ProtocolVersion) -> Params {
+        let consistency = next(cursor);
+        let flags = QueryFlagSet::from_cursor_for(cursor, version).unwrap();
+        let values = if flags.contains(&QueryFlags::Value) {
+            Some(next_values(cursor))
+        } else {
+            None
+        };
+        let page_size = if flags.contains(&QueryFlags::PageSize) {
+            Some(next(cursor))
+        } else {
+            None
+        };
+        let paging_state = if flags.contains(&QueryFlags::WithPagingState) {
+            next::<CBytes>(cursor).into_plain()
+        } else {
+            None
+        };
+        let serial_consistency = if flags.contains(&QueryFlags::WithSerialConsistency) {
+            Some(next(cursor))
+        } else {
+            None
+        };
+        let timestamp = if flags.contains(&QueryFlags::WithDefaultTimestamp) {
+            Some(next_bigint(cursor))
+        } else {
+            None
+        };
+
+        Params {
+            consistency,
+            values,
+            page_size,
+            paging_state,
+            serial_consistency,
+            timestamp,
+        }
+    }
+}
+
+/// Additional info of an ERROR response body, along with its error code.
+#[derive(Debug, Clone)]
+enum ErrorInfo {
+    Simple(CInt),
+    Unavailable(Consistency, CInt, CInt),
+    AlreadyExists(String, String),
+    Unprepared(Vec<u8>),
+}
+
+impl ErrorInfo {
+    fn code(&self) -> CInt {
+        match *self {
+            ErrorInfo::Simple(code) => code,
+            ErrorInfo::Unavailable(..) => 0x1000,
+            ErrorInfo::AlreadyExists(..) => 0x2400,
+            ErrorInfo::Unprepared(_) => 0x2500,
+        }
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        match *self {
+            ErrorInfo::Simple(_) => vec![],
+            ErrorInfo::Unavailable(cl, required, alive) => {
+                let mut bytes = cl.as_bytes();
+                bytes.extend_from_slice(&to_int(required));
+                bytes.extend_from_slice(&to_int(alive));
+                bytes
+            }
+            ErrorInfo::AlreadyExists(ref ks, ref table) => {
+                let mut bytes = string(ks);
+                bytes.extend_from_slice(&string(table));
+                bytes
+            }
+            ErrorInfo::Unprepared(ref id) => CBytesShort::new(id.clone()).as_bytes(),
+        }
+    }
+}
+
+/// Server event of an EVENT response body.
+#[derive(Debug, Clone)]
+enum Event {
+    TopologyChange { new_node: bool, addr: SocketAddr },
+    StatusChange { up: bool, addr: SocketAddr },
+    SchemaChange(SchemaChange),
+}
+
+impl Event {
+    fn as_bytes(&self) -> Vec<u8> {
+        match *self {
+            Event::TopologyChange { new_node, ref addr } => {
+                let mut bytes = string("TOPOLOGY_CHANGE");
+                bytes.extend_from_slice(&string(if new_node {
+                    "NEW_NODE"
+                } else {
+                    "REMOVED_NODE"
+                }));
+                bytes.extend_from_slice(&inet(addr));
+                bytes
+            }
+            Event::StatusChange { up, ref addr } => {
+                let mut bytes = string("STATUS_CHANGE");
+                bytes.extend_from_slice(&string(if up { "UP" } else { "DOWN" }));
+                bytes.extend_from_slice(&inet(addr));
+                bytes
+            }
+            Event::SchemaChange(ref change) => {
+                let mut bytes = string("SCHEMA_CHANGE");
+                bytes.extend_from_slice(&schema_change_bytes(change));
+                bytes
+            }
+        }
+    }
+}
+
+fn schema_change_bytes(change: &SchemaChange) -> Vec<u8> {
+    let mut bytes = string(match change.change_type {
+        ChangeType::Created => "CREATED",
+        ChangeType::Updated => "UPDATED",
+        ChangeType::Dropped => "DROPPED",
+    });
+    bytes.extend_from_slice(&string(match change.target {
+        Target::Keyspace => "KEYSPACE",
+        Target::Table => "TABLE",
+        Target::Type => "TYPE",
+        Target::Function => "FUNCTION",
+        Target::Aggregate => "AGGREGATE",
+    }));
+    match change.options {
+        ChangeSchemeOptions::Keyspace(ref keyspace) => bytes.extend_from_slice(&string(keyspace)),
+        ChangeSchemeOptions::TableType((ref keyspace, ref name)) => {
+            bytes.extend_from_slice(&string(keyspace));
+            bytes.extend_from_slice(&string(name));
+        }
+        ChangeSchemeOptions::FunctionAggregate((ref keyspace, ref name, ref types)) => {
+            bytes.extend_from_slice(&string(keyspace));
+            bytes.extend_from_slice(&string(name));
+            let types = CStringList {
+                list: types.iter().cloned().map(CString::new).collect(),
+            };
+            bytes.extend_from_slice(&types.as_bytes());
+        }
+    }
+    bytes
+}
+
+/// Bytes of column specs of `ks.tbl` with global table spec.
+fn col_specs_bytes(columns: &[(String, (i16, &str))]) -> Vec<u8> {
+    let mut bytes = string("ks");
+    bytes.extend_from_slice(&string("tbl"));
+    for (name, (id, _)) in columns {
+        bytes.extend_from_slice(&string(name));
+        bytes.extend_from_slice(&to_short(*id));
+    }
+    bytes
+}
+
+const NAME: &str = "\\PC{0,16}";
+
+fn bytes_strategy() -> impl Strategy<Value = Vec<u8>> {
+    proptest::collection::vec(any::<u8>(), 0..64)
+}
+
+fn values_strategy() -> impl Strategy<Value = Vec<Option<Vec<u8>>>> {
+    proptest::collection::vec(proptest::option::of(bytes_strategy()), 0..8)
+}
+
+fn consistency_strategy() -> impl Strategy<Value = Consistency> {
+    proptest::sample::select(vec![
+        Consistency::Any,
+        Consistency::One,
+        Consistency::Two,
+        Consistency::Three,
+        Consistency::Quorum,
+        Consistency::All,
+        Consistency::LocalQuorum,
+        Consistency::EachQuorum,
+        Consistency::Serial,
+        Consistency::LocalSerial,
+        Consistency::LocalOne,
+    ])
+}
+
+fn serial_consistency_strategy() -> impl Strategy<Value = Consistency> {
+    proptest::sample::select(vec![Consistency::Serial, Consistency::LocalSerial])
+}
+
+fn version_strategy() -> impl Strategy<Value = ProtocolVersion> {
+    proptest::sample::select(vec![
+        ProtocolVersion::V3,
+        ProtocolVersion::V4,
+        ProtocolVersion::V5,
+    ])
+}
+
+fn params_strategy() -> impl Strategy<Value = Params> {
+    (
+        consistency_strategy(),
+        proptest::option::of(values_strategy()),
+        proptest::option::of(1..i32::MAX),
+        proptest::option::of(bytes_strategy()),
+        proptest::option::of(serial_consistency_strategy()),
+        proptest::option::of(any::<i64>()),
+    )
+        .prop_map(
+            |(consistency, values, page_size, paging_state, serial_consistency, timestamp)| {
+                Params {
+                    consistency,
+                    values,
+                    page_size,
+                    paging_state,
+                    serial_consistency,
+                    timestamp,
+                }
+            },
+        )
+}
+
+fn error_info_strategy() -> impl Strategy<Value = ErrorInfo> {
+    prop_oneof![
+        proptest::sample::select(vec![
+            0x0000, 0x000A, 0x0100, 0x1001, 0x1002, 0x1003, 0x2000, 0x2100, 0x2200, 0x2300,
+        ])
+        .prop_map(ErrorInfo::Simple),
+        (consistency_strategy(), any::<CInt>(), any::<CInt>())
+            .prop_map(|(cl, required, alive)| ErrorInfo::Unavailable(cl, required, alive)),
+        (NAME, NAME).prop_map(|(ks, table)| ErrorInfo::AlreadyExists(ks, table)),
+        bytes_strategy().prop_map(ErrorInfo::Unprepared),
+    ]
+}
+
+fn addr_strategy() -> impl Strategy<Value = SocketAddr> {
+    (any::<[u8; 4]>(), any::<u16>()).prop_map(|(ip, port)| (Ipv4Addr::from(ip), port).into())
+}
+
+fn schema_change_strategy() -> impl Strategy<Value = SchemaChange> {
+    let target = prop_oneof![
+        NAME.prop_map(|keyspace| (Target::Keyspace, ChangeSchemeOptions::Keyspace(keyspace))),
+        (
+            proptest::sample::select(vec![Target::Table, Target::Type]),
+            NAME,
+            NAME
+        )
+            .prop_map(|(target, keyspace, name)| (
+                target,
+                ChangeSchemeOptions::TableType((keyspace, name))
+            )),
+        (
+            proptest::sample::select(vec![Target::Function, Target::Aggregate]),
+            NAME,
+            NAME,
+            proptest::collection::vec(NAME, 0..4)
+        )
+            .prop_map(|(target, keyspace, name, types)| (
+                target,
+                ChangeSchemeOptions::FunctionAggregate((keyspace, name, types))
+            )),
+    ];
+
+    (
+        proptest::sample::select(vec![
+            ChangeType::Created,
+            ChangeType::Updated,
+            ChangeType::Dropped,
+        ]),
+        target,
+    )
+        .prop_map(|(change_type, (target, options))| SchemaChange {
+            change_type,
+            target,
+            options,
+        })
+}
+
+fn event_strategy() -> impl Strategy<Value = Event> {
+    prop_oneof![
+        (any::<bool>(), addr_strategy())
+            .prop_map(|(new_node, addr)| Event::TopologyChange { new_node, addr }),
+        (any::<bool>(), addr_strategy()).prop_map(|(up, addr)| Event::StatusChange { up, addr }),
+        schema_change_strategy().prop_map(Event::SchemaChange),
+    ]
+}
+
+/// Column names along with ids and CQL names of their types.
+fn columns_strategy() -> impl Strategy<Value = Vec<(String, (i16, &'static str))>> {
+    let col_type = proptest::sample::select(vec![
+        (0x0001, "ascii"),
+        (0x0002, "bigint"),
+        (0x0003, "blob"),
+        (0x0004, "boolean"),
+        (0x0009, "int"),
+        (0x000B, "timestamp"),
+        (0x000D, "text"),
+        (0x0010, "inet"),
+    ]);
+    proptest::collection::vec((NAME, col_type), 0..8)
+}
+
+proptest! {
+    #[test]
+    fn startup_body_round_trip(
+        compression in proptest::option::of(proptest::sample::select(vec!["lz4", "snappy"])),
+        driver_name in proptest::option::of(NAME),
+    ) {
+        let mut body = BodyReqStartup::new(compression);
+        if let Some(ref driver_name) = driver_name {
+            body = body.with_option("DRIVER_NAME", driver_name);
+        }
+        let bytes = body.as_bytes();
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let options: HashMap<String, String> = (0..next::<CIntShort>(&mut cursor))
+            .map(|_| {
+                let name = next::<CString>(&mut cursor).into_plain();
+                (name, next::<CString>(&mut cursor).into_plain())
+            })
+            .collect();
+
+        let mut expected = HashMap::new();
+        expected.insert("CQL_VERSION".to_string(), "3.0.0".to_string());
+        if let Some(compression) = compression {
+            expected.insert("COMPRESSION".to_string(), compression.to_string());
+        }
+        if let Some(driver_name) = driver_name {
+            expected.insert("DRIVER_NAME".to_string(), driver_name);
+        }
+        prop_assert_eq!(options, expected);
+        prop_assert_eq!(cursor.position() as usize, bytes.len());
+    }
+
+    #[test]
+    fn query_body_round_trip(
+        query in "\\PC{0,64}",
+        params in params_strategy(),
+        version in version_strategy(),
+    ) {
+        let body = BodyReqQuery {
+            query: CStringLong::new(query.clone()),
+            query_params: params.to_query_params(),
+        };
+        let bytes = body.as_bytes_for(version);
+        let mut cursor = Cursor::new(bytes.as_slice());
+
+        prop_assert_eq!(next::<CStringLong>(&mut cursor).into_plain(), query);
+        prop_assert_eq!(Params::read(&mut cursor, version), params);
+        prop_assert_eq!(cursor.position() as usize, bytes.len());
+    }
+
+    #[test]
+    fn prepare_body_round_trip(query in "\\PC{0,64}", version in version_strategy()) {
+        let bytes = BodyReqPrepare::new(query.clone()).as_bytes_for(version);
+        let mut cursor = Cursor::new(bytes.as_slice());
+
+        prop_assert_eq!(next::<CStringLong>(&mut cursor).into_plain(), query);
+        if version.has_int_flags() {
+            prop_assert_eq!(next::<CInt>(&mut cursor), 0);
+        }
+        prop_assert_eq!(cursor.position() as usize, bytes.len());
+    }
+
+    #[test]
+    fn execute_body_round_trip(
+        id in bytes_strategy(),
+        result_metadata_id in bytes_strategy(),
+        params in params_strategy(),
+        version in version_strategy(),
+    ) {
+        let id = CBytesShort::new(id);
+        let result_metadata_id = CBytesShort::new(result_metadata_id);
+        let query_params = params.to_query_params();
+        let bytes = BodyReqExecute::new(&id, &query_params)
+            .result_metadata_id(Some(&result_metadata_id))
+            .as_bytes_for(version);
+        let mut cursor = Cursor::new(bytes.as_slice());
+
+        prop_assert_eq!(next::<CBytesShort>(&mut cursor), id);
+        if version.has_result_metadata_id() {
+            prop_assert_eq!(next::<CBytesShort>(&mut cursor), result_metadata_id);
+        }
+        prop_assert_eq!(Params::read(&mut cursor, version), params);
+        prop_assert_eq!(cursor.position() as usize, bytes.len());
+    }
+
+    #[test]
+    fn batch_body_round_trip(
+        batch_type in proptest::sample::select(vec![
+            BatchType::Logged,
+            BatchType::Unlogged,
+            BatchType::Counter,
+        ]),
+        queries in proptest::collection::vec(("\\PC{0,32}", values_strategy()), 0..8),
+        consistency in consistency_strategy(),
+        serial_consistency in proptest::option::of(serial_consistency_strategy()),
+        timestamp in proptest::option::of(any::<i64>()),
+        version in version_strategy(),
+    ) {
+        let mut query_flags = vec![];
+        if serial_consistency.is_some() {
+            query_flags.push(QueryFlags::WithSerialConsistency);
+        }
+        if timestamp.is_some() {
+            query_flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+        let body = BodyReqBatch {
+            batch_type: batch_type.clone(),
+            queries: queries
+                .iter()
+                .map(|(query, values)| BatchQuery {
+                    is_prepared: false,
+                    subject: BatchQuerySubj::QueryString(CStringLong::new(query.clone())),
+                    values: simple_values(values),
+                })
+                .collect(),
+            consistency,
+            query_flags,
+            serial_consistency,
+            timestamp,
+        };
+        let bytes = body.as_bytes_for(version);
+        let mut cursor = Cursor::new(bytes.as_slice());
+
+        prop_assert_eq!(BatchType::from_byte(next_byte(&mut cursor)), batch_type);
+        let decoded_queries: Vec<(String, Vec<Option<Vec<u8>>>)> =
+            (0..next::<CIntShort>(&mut cursor))
+                .map(|_| {
+                    assert_eq!(next_byte(&mut cursor), 0);
+                    let query = next::<CStringLong>(&mut cursor).into_plain();
+                    (query, next_values(&mut cursor))
+                })
+                .collect();
+        prop_assert_eq!(decoded_queries, queries);
+        prop_assert_eq!(next::<Consistency>(&mut cursor), consistency);
+
+        let flags = QueryFlagSet::from_cursor_for(&mut cursor, version).unwrap();
+        prop_assert_eq!(
+            flags.contains(&QueryFlags::WithSerialConsistency),
+            serial_consistency.is_some()
+        );
+        prop_assert_eq!(
+            flags.contains(&QueryFlags::WithDefaultTimestamp),
+            timestamp.is_some()
+        );
+        if serial_consistency.is_some() {
+            prop_assert_eq!(Some(next::<Consistency>(&mut cursor)), serial_consistency);
+        }
+        if timestamp.is_some() {
+            prop_assert_eq!(Some(next_bigint(&mut cursor)), timestamp);
+        }
+        prop_assert_eq!(cursor.position() as usize, bytes.len());
+    }
+
+    #[test]
+    fn register_body_round_trip(
+        events in proptest::sample::subsequence(vec![
+            SimpleServerEvent::TopologyChange,
+            SimpleServerEvent::StatusChange,
+            SimpleServerEvent::SchemaChange,
+        ], 0..=3),
+    ) {
+        let names: Vec<String> = events.iter().map(SimpleServerEvent::as_string).collect();
+        let decoded: CStringList = from_cursor(&BodyReqRegister { events }.as_bytes());
+        prop_assert_eq!(decoded.into_plain(), names);
+    }
+
+    #[test]
+    fn auth_response_body_round_trip(data in bytes_strategy()) {
+        let body = BodyReqAuthResponse::new(CBytes::new(data.clone()));
+        let decoded: CBytes = from_cursor(&body.as_bytes());
+        prop_assert_eq!(decoded.into_plain(), Some(data));
+    }
+
+    #[test]
+    fn error_body_round_trip(message in "\\PC{0,64}", info in error_info_strategy()) {
+        let mut bytes = to_int(info.code());
+        bytes.extend_from_slice(&string(&message));
+        bytes.extend_from_slice(&info.as_bytes());
+
+        let error = match response(Opcode::Error, &bytes) {
+            ResponseBody::Error(error) => error,
+            body => panic!("unexpected body {:?}", body),
+        };
+        prop_assert_eq!(error.error_code, info.code());
+        prop_assert_eq!(error.message.as_str(), message.as_str());
+        match (info, error.additional_info) {
+            (ErrorInfo::Simple(_), decoded) => prop_assert!(
+                !matches!(
+                    decoded,
+                    AdditionalErrorInfo::Unavailable(_)
+                        | AdditionalErrorInfo::AlreadyExists(_)
+                        | AdditionalErrorInfo::Unprepared(_)
+                ),
+                "unexpected additional info {:?}",
+                decoded
+            ),
+            (ErrorInfo::Unavailable(cl, required, alive), AdditionalErrorInfo::Unavailable(decoded)) => {
+                prop_assert_eq!(decoded.cl, cl);
+                prop_assert_eq!(decoded.required, required);
+                prop_assert_eq!(decoded.alive, alive);
+            }
+            (ErrorInfo::AlreadyExists(ks, table), AdditionalErrorInfo::AlreadyExists(decoded)) => {
+                prop_assert_eq!(decoded.ks.into_plain(), ks);
+                prop_assert_eq!(decoded.table.into_plain(), table);
+            }
+            (ErrorInfo::Unprepared(id), AdditionalErrorInfo::Unprepared(decoded)) => {
+                prop_assert_eq!(decoded.id.into_plain(), Some(id));
+            }
+            (info, decoded) => panic!("{:?} decoded as {:?}", info, decoded),
+        }
+    }
+
+    #[test]
+    fn authenticate_body_round_trip(authenticator in "\\PC{0,64}") {
+        match response(Opcode::Authenticate, &string(&authenticator)) {
+            ResponseBody::Authenticate(body) => {
+                prop_assert_eq!(body.data.into_plain(), authenticator)
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn supported_body_round_trip(
+        options in proptest::collection::hash_map(
+            NAME,
+            proptest::collection::vec(NAME, 0..4),
+            0..8,
+        ),
+    ) {
+        let mut bytes = to_short(options.len() as i16);
+        for (name, values) in &options {
+            bytes.extend_from_slice(&string(name));
+            let values = CStringList {
+                list: values.iter().cloned().map(CString::new).collect(),
+            };
+            bytes.extend_from_slice(&values.as_bytes());
+        }
+
+        let supported = response(Opcode::Supported, &bytes).into_supported().unwrap();
+        prop_assert_eq!(supported.data, options);
+    }
+
+    #[test]
+    fn event_body_round_trip(event in event_strategy()) {
+        let decoded = response(Opcode::Event, &event.as_bytes())
+            .into_server_event()
+            .unwrap()
+            .event;
+        match (event, decoded) {
+            (Event::TopologyChange { new_node, addr }, ServerEvent::TopologyChange(decoded)) => {
+                let change_type = if new_node {
+                    TopologyChangeType::NewNode
+                } else {
+                    TopologyChangeType::RemovedNode
+                };
+                prop_assert_eq!(decoded.change_type, change_type);
+                prop_assert_eq!(decoded.addr.addr, addr);
+            }
+            (Event::StatusChange { up, addr }, ServerEvent::StatusChange(decoded)) => {
+                let change_type = if up {
+                    StatusChangeType::Up
+                } else {
+                    StatusChangeType::Down
+                };
+                prop_assert_eq!(decoded.change_type, change_type);
+                prop_assert_eq!(decoded.addr.addr, addr);
+            }
+            (Event::SchemaChange(change), ServerEvent::SchemaChange(decoded)) => {
+                prop_assert_eq!(decoded, change);
+            }
+            (event, decoded) => panic!("{:?} decoded as {:?}", event, decoded),
+        }
+    }
+
+    #[test]
+    fn auth_challenge_body_round_trip(data in bytes_strategy()) {
+        match response(Opcode::AuthChallenge, &CBytes::new(data.clone()).as_bytes()) {
+            ResponseBody::AuthChallenge(body) => prop_assert_eq!(body.data.into_plain(), Some(data)),
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn auth_success_body_round_trip(token in bytes_strategy()) {
+        let body = response(Opcode::AuthSuccess, &CBytes::new(token).as_bytes());
+        prop_assert!(matches!(body, ResponseBody::AuthSuccess(_)));
+    }
+
+    #[test]
+    fn set_keyspace_result_round_trip(keyspace in NAME) {
+        let mut bytes = to_int(3);
+        bytes.extend_from_slice(&string(&keyspace));
+
+        let set_keyspace = response(Opcode::Result, &bytes).into_set_keyspace().unwrap();
+        prop_assert_eq!(set_keyspace.body.into_plain(), keyspace);
+    }
+
+    #[test]
+    fn schema_change_result_round_trip(change in schema_change_strategy()) {
+        let mut bytes = to_int(5);
+        bytes.extend_from_slice(&schema_change_bytes(&change));
+
+        match response(Opcode::Result, &bytes) {
+            ResponseBody::Result(ResResultBody::SchemaChange(decoded)) => {
+                prop_assert_eq!(decoded, change)
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn rows_result_round_trip(
+        (columns, rows) in columns_strategy().prop_flat_map(|columns| {
+            let row = proptest::collection::vec(
+                proptest::option::of(bytes_strategy()),
+                columns.len(),
+            );
+            (Just(columns), proptest::collection::vec(row, 0..8))
+        }),
+        paging_state in proptest::option::of(bytes_strategy()),
+    ) {
+        let flags = if paging_state.is_some() { 0x0003 } else { 0x0001 };
+        let mut bytes = to_int(2);
+        bytes.extend_from_slice(&to_int(flags));
+        bytes.extend_from_slice(&to_int(columns.len() as CInt));
+        if let Some(ref paging_state) = paging_state {
+            bytes.extend_from_slice(&CBytes::new(paging_state.clone()).as_bytes());
+        }
+        bytes.extend_from_slice(&col_specs_bytes(&columns));
+        bytes.extend_from_slice(&to_int(rows.len() as CInt));
+        for value in rows.iter().flatten() {
+            match value {
+                Some(value) => bytes.extend_from_slice(&CBytes::new(value.clone()).as_bytes()),
+                None => bytes.extend_from_slice(&to_int(-1)),
+            }
+        }
+
+        let rows_body = match response(Opcode::Result, &bytes) {
+            ResponseBody::Result(ResResultBody::Rows(rows)) => rows,
+            body => panic!("unexpected body {:?}", body),
+        };
+        let metadata = rows_body.metadata;
+        prop_assert_eq!(metadata.paging_state.and_then(CBytes::into_plain), paging_state);
+        let col_specs: Vec<(String, String)> = metadata
+            .col_specs
+            .into_iter()
+            .map(|col_spec| (col_spec.name.into_plain(), col_spec.col_type.id.to_string()))
+            .collect();
+        let expected: Vec<(String, String)> = columns
+            .into_iter()
+            .map(|(name, (_, col_type))| (name, col_type.to_string()))
+            .collect();
+        prop_assert_eq!(col_specs, expected);
+        prop_assert_eq!(rows_body.rows_count as usize, rows.len());
+        let rows_content: Vec<Vec<Option<Vec<u8>>>> = rows_body
+            .rows_content
+            .into_iter()
+            .map(|row| row.into_iter().map(CBytes::into_plain).collect())
+            .collect();
+        prop_assert_eq!(rows_content, rows);
+    }
+
+    #[test]
+    fn prepared_result_round_trip(
+        (columns, pk_indexes) in columns_strategy().prop_flat_map(|columns| {
+            let indexes: Vec<i16> = (0..columns.len() as i16).collect();
+            let count = columns.len();
+            (Just(columns), proptest::sample::subsequence(indexes, 0..=count))
+        }),
+        id in bytes_strategy(),
+        result_metadata_id in bytes_strategy(),
+        version in version_strategy(),
+    ) {
+        let mut bytes = to_int(4);
+        bytes.extend_from_slice(&CBytesShort::new(id.clone()).as_bytes());
+        if version.has_result_metadata_id() {
+            bytes.extend_from_slice(&CBytesShort::new(result_metadata_id.clone()).as_bytes());
+        }
+        bytes.extend_from_slice(&to_int(0x0001));
+        bytes.extend_from_slice(&to_int(columns.len() as CInt));
+        if version.has_pk_indexes() {
+            bytes.extend_from_slice(&to_int(pk_indexes.len() as CInt));
+            for index in &pk_indexes {
+                bytes.extend_from_slice(&to_short(*index));
+            }
+        }
+        bytes.extend_from_slice(&col_specs_bytes(&columns));
+        // result metadata without columns
+        bytes.extend_from_slice(&to_int(0));
+        bytes.extend_from_slice(&to_int(0));
+
+        let prepared = ResponseBody::from_for(&bytes, &Opcode::Result, version)
+            .unwrap()
+            .into_prepared()
+            .unwrap();
+        prop_assert_eq!(prepared.id.into_plain(), Some(id));
+        prop_assert_eq!(
+            prepared.result_metadata_id.and_then(CBytesShort::into_plain),
+            Some(result_metadata_id).filter(|_| version.has_result_metadata_id())
+        );
+        let expected_pk_indexes = if version.has_pk_indexes() {
+            pk_indexes
+        } else {
+            vec![]
+        };
+        prop_assert_eq!(prepared.metadata.pk_indexes, expected_pk_indexes);
+        let names: Vec<String> = prepared
+            .metadata
+            .col_specs
+            .into_iter()
+            .map(|col_spec| col_spec.name.into_plain())
+            .collect();
+        let expected_names: Vec<String> = columns.into_iter().map(|(name, _)| name).collect();
+        prop_assert_eq!(names, expected_names);
+    }
+}
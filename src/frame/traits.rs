@@ -1,12 +1,19 @@
 use std::io::Cursor;
 
 use crate::error;
+use crate::frame::ProtocolVersion;
 use crate::query;
 
 /// `AsBytes` should be used to convert a structure into array of bytes.
 pub trait AsBytes {
     /// It should convert a struct into an array of bytes.
     fn as_bytes(&self) -> Vec<u8>;
+
+    /// Converts a struct into an array of bytes in the format of given protocol version.
+    /// Defaults to `as_bytes`, for structures whose format doesn't depend on the version.
+    fn as_bytes_for(&self, _version: ProtocolVersion) -> Vec<u8> {
+        self.as_bytes()
+    }
 }
 
 /// `FromBytes` should be used to parse an array of bytes into a structure.
@@ -37,6 +44,15 @@ pub trait FromCursor {
     fn from_cursor(cursor: &mut Cursor<&[u8]>) -> error::Result<Self>
     where
         Self: Sized;
+
+    /// Parses an implementor in the format of given protocol version. Defaults to
+    /// `from_cursor`, for structures whose format doesn't depend on the version.
+    fn from_cursor_for(cursor: &mut Cursor<&[u8]>, _version: ProtocolVersion) -> error::Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_cursor(cursor)
+    }
 }
 
 /// The trait that allows transformation of `Self` to CDRS query values.
@@ -1,4 +1,15 @@
 //! **cdrs** is a native Cassandra DB client written in Rust.
+//!
+//! The protocol layer - `frame`, `types`, `compression`, `consistency`, `error`, `token` and query
+//! parameters in `query` - does not depend on an async runtime. Sessions, connection pools and
+//! request execution are enabled by the default `runtime` feature; without it the crate can be
+//! used to encode and decode frames, e.g. in proxies and server emulators. The protocol layer
+//! also builds for `wasm32-unknown-unknown`, e.g. for browser side tooling:
+//!
+//! `cargo build --no-default-features --features v4 --target wasm32-unknown-unknown`
+
+// crate internal helpers of the protocol layer are used only by request execution
+#![cfg_attr(not(feature = "runtime"), allow(dead_code, unused_imports))]
 
 extern crate byteorder;
 extern crate snap;
@@ -7,23 +18,33 @@ pub mod macros;
 
 #[macro_use]
 extern crate log;
+#[cfg(feature = "runtime")]
 extern crate bb8;
 extern crate lz4_compress;
+#[cfg(feature = "runtime")]
 extern crate rand;
 extern crate time;
 extern crate uuid;
 
+#[cfg(feature = "runtime")]
 pub mod cluster;
+#[cfg(feature = "codegen")]
+pub mod codegen;
 pub mod frame;
+#[cfg(feature = "runtime")]
 pub mod load_balancing;
 pub mod query;
+pub mod schema;
+pub mod token;
 pub mod types;
 
 pub mod authenticators;
 pub mod compression;
 pub mod consistency;
 pub mod error;
+#[cfg(feature = "runtime")]
 pub mod events;
+#[cfg(feature = "runtime")]
 pub mod transport;
 
 pub type Error = error::Error;
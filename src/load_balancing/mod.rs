@@ -1,9 +1,12 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+mod node_distance;
 mod random;
 mod round_robin;
 mod single_node;
 
+pub use crate::load_balancing::node_distance::NodeDistance;
 pub use crate::load_balancing::random::Random;
 pub use crate::load_balancing::round_robin::RoundRobin;
 pub use crate::load_balancing::single_node::SingleNode;
@@ -11,10 +14,22 @@ pub use crate::load_balancing::single_node::SingleNode;
 pub trait LoadBalancingStrategy<N> {
     fn init(&mut self, cluster: Vec<Arc<N>>);
     fn next(&self) -> Option<Arc<N>>;
+    /// Returns distance to a node with given address. Called before connecting to each node, so
+    /// the returned value decides the size of the node's connection pool.
+    fn distance(&self, _addr: SocketAddr) -> NodeDistance {
+        NodeDistance::Local
+    }
     fn remove_node<F>(&mut self, _filter: F)
     where
         F: FnMut(&N) -> bool,
     {
         // default implementation does nothing
     }
+    /// Returns a node matching given filter, e.g. a replica picked by token aware routing.
+    fn find<F>(&self, _filter: F) -> Option<Arc<N>>
+    where
+        F: FnMut(&N) -> bool,
+    {
+        None
+    }
 }
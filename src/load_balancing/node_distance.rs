@@ -0,0 +1,12 @@
+/// Distance of a node as seen by a load balancing strategy. It decides how many connections
+/// are kept open to given node. Ignored nodes are never connected to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Default)]
+pub enum NodeDistance {
+    /// Node is considered local (e.g. in the same DC) - full pool is kept.
+    #[default]
+    Local,
+    /// Node is considered remote - a smaller pool is kept.
+    Remote,
+    /// Node is ignored - no connections are opened.
+    Ignored,
+}
@@ -51,6 +51,13 @@ where
             self.cluster.remove(i);
         }
     }
+
+    fn find<F>(&self, mut filter: F) -> Option<Arc<N>>
+    where
+        F: FnMut(&N) -> bool,
+    {
+        self.cluster.iter().find(|node| filter(node)).cloned()
+    }
 }
 
 #[cfg(test)]
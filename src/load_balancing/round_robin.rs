@@ -1,18 +1,66 @@
+use rand::seq::SliceRandom;
+use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use super::LoadBalancingStrategy;
 
-#[derive(Debug)]
+type NodeWeight<N> = Box<dyn Fn(&N) -> u32 + Send + Sync>;
+
 pub struct RoundRobin<N> {
     cluster: Vec<Arc<N>>,
     prev_idx: AtomicUsize,
+    shuffle: bool,
+    weight: Option<NodeWeight<N>>,
+    // weights of nodes in `cluster` and their accumulated weights, empty without `weight`
+    weights: Vec<i64>,
+    current_weights: Mutex<Vec<i64>>,
 }
 
 impl<N> RoundRobin<N> {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Shuffles the initial node order, so sessions of many application instances created
+    /// with the same contact points don't all start with the first one.
+    /// Defaults to `false`.
+    pub fn shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Sets a function returning capacity of a node. A node of weight `n` is returned `n` times
+    /// per round, interleaved with other nodes. Weights of 0 are treated as 1.
+    /// Defaults to equal weights.
+    pub fn weighted<F>(mut self, weight: F) -> Self
+    where
+        F: Fn(&N) -> u32 + Send + Sync + 'static,
+    {
+        self.weight = Some(Box::new(weight));
+        self
+    }
+
+    // smooth weighted round robin - every pick, the node with the highest accumulated weight
+    // is picked, which spreads repetitions of heavy nodes over the whole round without
+    // expanding nodes into a sequence as long as the sum of weights
+    fn next_weighted(&self) -> Option<Arc<N>> {
+        let total: i64 = self.weights.iter().sum();
+        let mut current_weights = self.current_weights.lock().unwrap();
+
+        for (current, weight) in current_weights.iter_mut().zip(&self.weights) {
+            *current += weight;
+        }
+
+        let (best, _) = current_weights
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, current)| **current)?;
+        current_weights[best] -= total;
+
+        self.cluster.get(best).cloned()
+    }
 }
 
 impl<N> Default for RoundRobin<N> {
@@ -20,6 +68,10 @@ impl<N> Default for RoundRobin<N> {
         RoundRobin {
             cluster: vec![],
             prev_idx: Default::default(),
+            shuffle: false,
+            weight: None,
+            weights: vec![],
+            current_weights: Default::default(),
         }
     }
 }
@@ -27,22 +79,49 @@ impl<N> Default for RoundRobin<N> {
 impl<N> From<Vec<Arc<N>>> for RoundRobin<N> {
     fn from(cluster: Vec<Arc<N>>) -> RoundRobin<N> {
         RoundRobin {
-            prev_idx: AtomicUsize::new(0),
             cluster,
+            ..Default::default()
         }
     }
 }
 
+impl<N: fmt::Debug> fmt::Debug for RoundRobin<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoundRobin")
+            .field("cluster", &self.cluster)
+            .field("prev_idx", &self.prev_idx)
+            .field("shuffle", &self.shuffle)
+            .field("weighted", &self.weight.is_some())
+            .finish()
+    }
+}
+
 impl<N> LoadBalancingStrategy<N> for RoundRobin<N>
 where
     N: Sync + Send,
 {
-    fn init(&mut self, cluster: Vec<Arc<N>>) {
+    fn init(&mut self, mut cluster: Vec<Arc<N>>) {
+        if self.shuffle {
+            cluster.shuffle(&mut rand::thread_rng());
+        }
+
+        self.weights = match &self.weight {
+            Some(weight) => cluster
+                .iter()
+                .map(|node| i64::from(weight(node).max(1)))
+                .collect(),
+            None => vec![],
+        };
+        self.current_weights = Mutex::new(vec![0; self.weights.len()]);
         self.cluster = cluster;
     }
 
     /// Returns next node from a cluster
     fn next(&self) -> Option<Arc<N>> {
+        if !self.weights.is_empty() {
+            return self.next_weighted();
+        }
+
         let cur_idx = self.prev_idx.fetch_add(1, Ordering::SeqCst);
         self.cluster.get(cur_idx % self.cluster.len()).cloned()
     }
@@ -53,8 +132,19 @@ where
     {
         if let Some(i) = self.cluster.iter().position(|node| filter(node)) {
             self.cluster.remove(i);
+            if !self.weights.is_empty() {
+                self.weights.remove(i);
+                self.current_weights.get_mut().unwrap().remove(i);
+            }
         }
     }
+
+    fn find<F>(&self, mut filter: F) -> Option<Arc<N>>
+    where
+        F: FnMut(&N) -> bool,
+    {
+        self.cluster.iter().find(|node| filter(node)).cloned()
+    }
 }
 
 #[cfg(test)]
@@ -90,4 +180,51 @@ mod tests {
         load_balancer.remove_node(|n| n == &"a");
         assert_eq!(&"b", load_balancer.next().unwrap().as_ref());
     }
+
+    #[test]
+    fn find_in_round_robin() {
+        let load_balancer = RoundRobin::from(vec![Arc::new("a"), Arc::new("b")]);
+
+        assert_eq!(load_balancer.find(|n| n == &"b").as_deref(), Some(&"b"));
+        assert!(load_balancer.find(|n| n == &"c").is_none());
+    }
+
+    #[test]
+    fn shuffled_round_robin() {
+        let nodes: Vec<Arc<u32>> = (0..100).map(Arc::new).collect();
+        let mut load_balancer = RoundRobin::new().shuffle(true);
+        load_balancer.init(nodes);
+
+        let mut order: Vec<u32> = (0..100).map(|_| *load_balancer.next().unwrap()).collect();
+        assert_ne!(order, (0..100).collect::<Vec<u32>>());
+
+        order.sort_unstable();
+        assert_eq!(order, (0..100).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn weighted_round_robin() {
+        let mut load_balancer =
+            RoundRobin::new().weighted(|n: &&str| if *n == "a" { 3 } else { 0 });
+        load_balancer.init(vec![Arc::new("a"), Arc::new("b"), Arc::new("c")]);
+
+        let round: Vec<&str> = (0..10).map(|_| *load_balancer.next().unwrap()).collect();
+        assert_eq!(
+            round,
+            vec!["a", "b", "a", "c", "a", "a", "b", "a", "c", "a"]
+        );
+
+        load_balancer.remove_node(|n| n == &"a");
+        let round: Vec<&str> = (0..4).map(|_| *load_balancer.next().unwrap()).collect();
+        assert_eq!(round, vec!["b", "c", "b", "c"]);
+    }
+
+    #[test]
+    fn huge_weights() {
+        let mut load_balancer =
+            RoundRobin::new().weighted(|n: &&str| if *n == "a" { u32::MAX } else { 1 });
+        load_balancer.init(vec![Arc::new("a"), Arc::new("b")]);
+
+        assert!((0..1000).all(|_| *load_balancer.next().unwrap() == "a"));
+    }
 }
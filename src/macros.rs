@@ -91,26 +91,55 @@ macro_rules! map_as_rust {
 }
 
 macro_rules! into_rust_by_name {
+    (Row, UDT) => (
+        into_rust_by_name!(@Row UDT::inherit_registry, UDT);
+    );
+    (Row, Tuple) => (
+        into_rust_by_name!(@Row Tuple::inherit_registry, Tuple);
+    );
     (Row, $($into_type:tt)+) => (
+        into_rust_by_name!(@Row crate::types::registry::keep, $($into_type)+);
+    );
+    (UDT, UDT) => (
+        into_rust_by_name!(@UDT UDT::inherit_registry, UDT);
+    );
+    (UDT, Tuple) => (
+        into_rust_by_name!(@UDT Tuple::inherit_registry, Tuple);
+    );
+    (UDT, $($into_type:tt)+) => (
+        into_rust_by_name!(@UDT crate::types::registry::keep, $($into_type)+);
+    );
+    (@Row $inherit:path, $($into_type:tt)+) => (
         impl IntoRustByName<$($into_type)+> for Row {
             fn get_by_name(&self, name: &str) -> Result<Option<$($into_type)+>> {
                 self.get_col_spec_by_name(name)
                     .ok_or(column_is_empty_err(name))
                     .and_then(|(col_spec, cbytes)| {
                         let col_type = &col_spec.col_type;
-                        as_rust_type!(col_type, cbytes, $($into_type)+)
+                        let registry = self.registry.as_ref();
+                        crate::types::registry::as_registered(registry, col_type, cbytes)
+                            .unwrap_or_else(|| {
+                                as_rust_type!(col_type, cbytes, $($into_type)+)
+                                    .map(|value| value.map(|value| $inherit(value, registry)))
+                            })
+                            .map_err(|error| column_decode_err(col_spec, stringify!($($into_type)+), error))
                     })
             }
         }
     );
-    (UDT, $($into_type:tt)+) => (
+    (@UDT $inherit:path, $($into_type:tt)+) => (
         impl IntoRustByName<$($into_type)+> for UDT {
             fn get_by_name(&self, name: &str) -> Result<Option<$($into_type)+>> {
                 self.data.get(name)
                 .ok_or(column_is_empty_err(name))
                 .and_then(|v| {
                     let &(ref col_type, ref bytes) = v;
-                    let converted = as_rust_type!(col_type, bytes, $($into_type)+);
+                    let registry = self.registry.as_ref();
+                    let converted = crate::types::registry::as_registered(registry, col_type, bytes)
+                        .unwrap_or_else(|| {
+                            as_rust_type!(col_type, bytes, $($into_type)+)
+                                .map(|value| value.map(|value| $inherit(value, registry)))
+                        });
                     converted.map_err(|err| err.into())
                 })
             }
@@ -119,7 +148,25 @@ macro_rules! into_rust_by_name {
 }
 
 macro_rules! into_rust_by_index {
+    (Tuple, UDT) => (
+        into_rust_by_index!(@Tuple UDT::inherit_registry, UDT);
+    );
+    (Tuple, Tuple) => (
+        into_rust_by_index!(@Tuple Tuple::inherit_registry, Tuple);
+    );
     (Tuple, $($into_type:tt)+) => (
+        into_rust_by_index!(@Tuple crate::types::registry::keep, $($into_type)+);
+    );
+    (Row, UDT) => (
+        into_rust_by_index!(@Row UDT::inherit_registry, UDT);
+    );
+    (Row, Tuple) => (
+        into_rust_by_index!(@Row Tuple::inherit_registry, Tuple);
+    );
+    (Row, $($into_type:tt)+) => (
+        into_rust_by_index!(@Row crate::types::registry::keep, $($into_type)+);
+    );
+    (@Tuple $inherit:path, $($into_type:tt)+) => (
         impl IntoRustByIndex<$($into_type)+> for Tuple {
             fn get_by_index(&self, index: usize) -> Result<Option<$($into_type)+>> {
                 self.data
@@ -127,20 +174,31 @@ macro_rules! into_rust_by_index {
                     .ok_or(column_is_empty_err(index))
                     .and_then(|v| {
                         let &(ref col_type, ref bytes) = v;
-                        let converted = as_rust_type!(col_type, bytes, $($into_type)+);
+                        let registry = self.registry.as_ref();
+                        let converted = crate::types::registry::as_registered(registry, col_type, bytes)
+                            .unwrap_or_else(|| {
+                                as_rust_type!(col_type, bytes, $($into_type)+)
+                                    .map(|value| value.map(|value| $inherit(value, registry)))
+                            });
                         converted.map_err(|err| err.into())
                     })
             }
         }
     );
-    (Row, $($into_type:tt)+) => (
+    (@Row $inherit:path, $($into_type:tt)+) => (
         impl IntoRustByIndex<$($into_type)+> for Row {
             fn get_by_index(&self, index: usize) -> Result<Option<$($into_type)+>> {
                 self.get_col_spec_by_index(index)
                     .ok_or(column_is_empty_err(index))
                     .and_then(|(col_spec, cbytes)| {
                         let col_type = &col_spec.col_type;
-                        as_rust_type!(col_type, cbytes, $($into_type)+)
+                        let registry = self.registry.as_ref();
+                        crate::types::registry::as_registered(registry, col_type, cbytes)
+                            .unwrap_or_else(|| {
+                                as_rust_type!(col_type, cbytes, $($into_type)+)
+                                    .map(|value| value.map(|value| $inherit(value, registry)))
+                            })
+                            .map_err(|error| column_decode_err(col_spec, stringify!($($into_type)+), error))
                     })
             }
         }
@@ -170,6 +228,18 @@ macro_rules! as_rust_type {
             ))),
         }
     };
+    ($data_type_option:ident, $data_value:ident, CustomValue) => {
+        match (&$data_type_option.id, &$data_type_option.value) {
+            (ColType::Custom, Some(ColTypeOptionValue::CString(class_name))) => Ok($data_value
+                .as_plain()
+                .map(|bytes| CustomValue::new(class_name.as_plain(), bytes))),
+            (id, _) => Err(Error::General(format!(
+                "Invalid conversion. \
+                 Cannot convert {:?} into CustomValue (valid types: Custom).",
+                id
+            ))),
+        }
+    };
     ($data_type_option:ident, $data_value:ident, String) => {
         match $data_type_option.id {
             ColType::Custom => as_res_opt!($data_value, decode_custom),
@@ -324,6 +394,32 @@ macro_rules! as_rust_type {
             ))),
         }
     };
+    ($data_type_option:ident, $data_value:ident, Vector) => {
+        match (&$data_type_option.id, &$data_type_option.value) {
+            (ColType::Custom, Some(ColTypeOptionValue::CString(class_name))) => {
+                match VectorType::parse(class_name.as_str()) {
+                    Some(ref vector_type) if vector_type.is_float() => {
+                        match $data_value.as_slice() {
+                            Some(ref bytes) => decode_float_vector(bytes, vector_type.dimensions())
+                                .map(|values| Some(Vector::new(values)))
+                                .map_err(Into::into),
+                            None => Ok(None),
+                        }
+                    }
+                    _ => Err(Error::General(format!(
+                        "Invalid conversion. \
+                         Cannot convert {} into Vector (valid types: vector<float, n>).",
+                        class_name.as_str()
+                    ))),
+                }
+            }
+            (id, _) => Err(Error::General(format!(
+                "Invalid conversion. \
+                 Cannot convert {:?} into Vector (valid types: vector<float, n>).",
+                id
+            ))),
+        }
+    };
     ($data_type_option:ident, $data_value:ident, IpAddr) => {
         match $data_type_option.id {
             ColType::Inet => as_res_opt!($data_value, decode_inet),
@@ -447,9 +543,7 @@ macro_rules! as_rust_type {
         match $data_type_option.id {
             ColType::Timestamp => match $data_value.as_slice() {
                 Some(ref bytes) => decode_timestamp(bytes)
-                    .map(|ts| {
-                        NaiveDateTime::from_timestamp_opt(ts / 1000, (ts % 1000 * 1_000_000) as u32)
-                    })
+                    .map(crate::types::timestamp::timestamp_to_naive_date_time)
                     .map_err(Into::into),
                 None => Ok(None),
             },
@@ -464,15 +558,7 @@ macro_rules! as_rust_type {
         match $data_type_option.id {
             ColType::Timestamp => match $data_value.as_slice() {
                 Some(ref bytes) => decode_timestamp(bytes)
-                    .map(|ts| {
-                        Some(DateTime::from_utc(
-                            NaiveDateTime::from_timestamp_opt(
-                                ts / 1000,
-                                (ts % 1000 * 1_000_000) as u32,
-                            )?,
-                            Utc,
-                        ))
-                    })
+                    .map(crate::types::timestamp::timestamp_to_date_time)
                     .map_err(Into::into),
                 None => Ok(None),
             },
@@ -483,4 +569,19 @@ macro_rules! as_rust_type {
             ))),
         }
     };
+    ($data_type_option:ident, $data_value:ident, SystemTime) => {
+        match $data_type_option.id {
+            ColType::Timestamp => match $data_value.as_slice() {
+                Some(ref bytes) => decode_timestamp(bytes)
+                    .map(|ts| Some(crate::types::timestamp::timestamp_to_system_time(ts)))
+                    .map_err(Into::into),
+                None => Ok(None),
+            },
+            _ => Err(Error::General(format!(
+                "Invalid conversion. \
+                 Cannot convert {:?} into SystemTime (valid types: Timestamp).",
+                $data_type_option.id
+            ))),
+        }
+    };
 }
@@ -0,0 +1,136 @@
+use crate::query::QueryValues;
+use crate::types::value::Value;
+use crate::types::vector::Vector;
+
+/// Approximate nearest neighbor query, i.e. `SELECT ... ORDER BY column ANN OF ? LIMIT n`,
+/// which returns rows with vectors most similar to given one. The searched column needs a
+/// storage attached index (SAI). The vector is bound as a `vector<float, n>` value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnQuery {
+    table: String,
+    column: String,
+    vector: Vector,
+    columns: Option<Vec<String>>,
+    filters: Vec<(String, Value)>,
+    limit: Option<u32>,
+}
+
+impl AnnQuery {
+    const DEFAULT_LIMIT: u32 = 10;
+
+    /// Creates a query for rows of `table` whose `column` is nearest to `vector`.
+    pub fn new<V: Into<Vector>>(table: &str, column: &str, vector: V) -> Self {
+        AnnQuery {
+            table: table.to_string(),
+            column: column.to_string(),
+            vector: vector.into(),
+            columns: None,
+            filters: vec![],
+            limit: None,
+        }
+    }
+
+    /// Sets selected columns.
+    /// Defaults to all columns.
+    pub fn select(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|column| column.to_string()).collect());
+        self
+    }
+
+    /// Adds a filtering condition with a single bind marker, e.g. `category = ?`, and the value
+    /// bound to it. Conditions are joined with `AND`.
+    pub fn filter<V: Into<Value>>(mut self, condition: &str, value: V) -> Self {
+        self.filters.push((condition.to_string(), value.into()));
+        self
+    }
+
+    /// Sets maximum number of returned rows.
+    /// Defaults to 10.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns CQL text of the query.
+    pub fn query(&self) -> String {
+        let columns = self
+            .columns
+            .as_ref()
+            .map(|columns| columns.join(", "))
+            .unwrap_or_else(|| "*".to_string());
+
+        let mut query = format!("SELECT {} FROM {}", columns, self.table);
+
+        if !self.filters.is_empty() {
+            let conditions: Vec<&str> = self
+                .filters
+                .iter()
+                .map(|(condition, _)| condition.as_str())
+                .collect();
+            query.push_str(" WHERE ");
+            query.push_str(&conditions.join(" AND "));
+        }
+
+        query.push_str(&format!(
+            " ORDER BY {} ANN OF ? LIMIT {}",
+            self.column,
+            self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+        ));
+
+        query
+    }
+
+    /// Returns values bound to the query: filter values followed by the searched vector.
+    pub fn values(&self) -> QueryValues {
+        let mut values: Vec<Value> = self
+            .filters
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect();
+        values.push(self.vector.clone().into());
+
+        QueryValues::SimpleValues(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ann_query() {
+        let query = AnnQuery::new("ks.items", "embedding", vec![1.0, -2.5]);
+
+        assert_eq!(
+            query.query(),
+            "SELECT * FROM ks.items ORDER BY embedding ANN OF ? LIMIT 10"
+        );
+        assert_eq!(
+            query.values(),
+            QueryValues::SimpleValues(vec![Value::new_normal(Vector::new(vec![1.0, -2.5]))])
+        );
+    }
+
+    #[test]
+    fn filtered_ann_query() {
+        let query = AnnQuery::new("items", "embedding", vec![0.5])
+            .select(&["id", "name"])
+            .filter("category = ?", "books")
+            .filter("price < ?", 20)
+            .limit(3);
+
+        assert_eq!(
+            query.query(),
+            "SELECT id, name FROM items WHERE category = ? AND price < ? \
+             ORDER BY embedding ANN OF ? LIMIT 3"
+        );
+        assert_eq!(
+            query.values(),
+            QueryValues::SimpleValues(vec![
+                "books".into(),
+                20.into(),
+                Value::new_normal(Vector::new(vec![0.5])),
+            ])
+        );
+    }
+}
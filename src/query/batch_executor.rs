@@ -1,11 +1,15 @@
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
-use crate::cluster::{GetCompressor, GetConnection, ResponseCache};
+use crate::cluster::{
+    GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks, ResponseCache,
+};
 use crate::error;
-use crate::frame::traits::AsBytes;
+use crate::frame::frame_batch::BatchQuerySubj;
+use crate::frame::frame_error::AdditionalErrorInfo;
 use crate::frame::Frame;
 use crate::query::batch_query_builder::QueryBatch;
+use crate::query::PrepareExecutor;
 use crate::transport::CDRSTransport;
 
 use super::utils::{prepare_flags, send_frame};
@@ -14,22 +18,132 @@ use super::utils::{prepare_flags, send_frame};
 pub trait BatchExecutor<
     T: CDRSTransport + Unpin + 'static,
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
->: GetConnection<T, M> + GetCompressor + ResponseCache + Sync
+>:
+    GetConnection<T, M> + GetCompressor + GetQueryDefaults + GetSessionHooks + ResponseCache + Sync
 {
+    /// Executes a batch. Prepared statements reported as unprepared by the node are
+    /// re-prepared and the batch is retried.
     async fn batch_with_params_tw(
         &self,
         batch: QueryBatch,
         with_tracing: bool,
         with_warnings: bool,
-    ) -> error::Result<Frame> {
-        let flags = prepare_flags(with_tracing, with_warnings);
+    ) -> error::Result<Frame>
+    where
+        Self: PrepareExecutor<T, M>,
+    {
+        let mut batch = batch;
+        if let Some(generator) = self.hooks().timestamp_generator.as_ref() {
+            generator.stamp_batch(&mut batch);
+        }
 
-        let query_frame = Frame::new_req_batch(batch, flags);
+        let build = |protocol_version| {
+            Frame::new_req_batch_for(
+                &batch,
+                prepare_flags(with_tracing, with_warnings),
+                protocol_version,
+            )
+        };
+        let mut result = send_frame(self, &build).await;
 
-        send_frame(self, query_frame.as_bytes(), query_frame.stream).await
+        // each retry re-prepares one statement
+        for _ in 0..batch.queries.len() {
+            let unprepared_id = match &result {
+                Err(error::Error::Server(error)) => match &error.additional_info {
+                    AdditionalErrorInfo::Unprepared(unprepared) => &unprepared.id,
+                    _ => break,
+                },
+                _ => break,
+            };
+
+            let prepared = batch.queries.iter().find_map(|query| match &query.subject {
+                BatchQuerySubj::PreparedId(prepared)
+                    if &*prepared.id.read().expect("Cannot read prepared query id!")
+                        == unprepared_id =>
+                {
+                    Some(prepared)
+                }
+                _ => None,
+            });
+
+            let prepared = match prepared {
+                Some(prepared) => prepared,
+                None => break,
+            };
+
+            if let Some(cache) = self.hooks().prepared_cache.as_ref() {
+                cache.record_re_prepare();
+            }
+
+            match self.prepare_raw(&prepared.query).await {
+                Ok(new) => {
+                    *prepared
+                        .id
+                        .write()
+                        .expect("Cannot write prepared query id!") = new.id
+                }
+                Err(_) => break,
+            }
+
+            result = send_frame(self, &build).await;
+        }
+
+        result
     }
 
-    async fn batch_with_params(&self, batch: QueryBatch) -> error::Result<Frame> {
+    async fn batch_with_params(&self, batch: QueryBatch) -> error::Result<Frame>
+    where
+        Self: PrepareExecutor<T, M>,
+    {
         self.batch_with_params_tw(batch, false, false).await
     }
 }
+
+// prepared results answered by test nodes are encoded for protocol v4, the default without v3
+#[cfg(all(test, not(feature = "v3")))]
+mod tests {
+    use super::*;
+    use crate::cluster::test_node::{void, TestNode};
+    use crate::cluster::DirectSession;
+    use crate::frame::Opcode;
+    use crate::query::{BatchQueryBuilder, PreparedQuery, QueryValues};
+    use crate::types::CBytesShort;
+    use std::sync::{Arc, RwLock};
+
+    #[tokio::test]
+    async fn batch_reprepares_statements() {
+        // statement with id 1 is unknown to the node, it knows it as 2 once prepared
+        fn unprepared_id_1(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            match opcode {
+                Opcode::Prepare => Some((
+                    Opcode::Result,
+                    vec![
+                        0, 0, 0, 4, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0,
+                        0, 0,
+                    ],
+                )),
+                Opcode::Batch if body[6] == 1 => {
+                    Some((Opcode::Error, vec![0, 0, 0x25, 0, 0, 0, 0, 1, 1]))
+                }
+                _ => void(opcode, body),
+            }
+        }
+
+        let node = TestNode::start_with(unprepared_id_1).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let prepared = PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Default::default(),
+            query: "INSERT INTO t (id) VALUES (1)".into(),
+            keyspace: Default::default(),
+            routing: Default::default(),
+        };
+        let batch = BatchQueryBuilder::new()
+            .add_query_prepared(&prepared, QueryValues::SimpleValues(vec![]))
+            .finalize()
+            .unwrap();
+
+        session.batch_with_params(batch).await.unwrap();
+        assert_eq!(*prepared.id.read().unwrap(), CBytesShort::new(vec![2]));
+    }
+}
@@ -1,11 +1,29 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+
 use crate::consistency::Consistency;
 use crate::error::{Error as CError, Result as CResult};
 use crate::frame::frame_batch::{BatchQuery, BatchQuerySubj, BatchType, BodyReqBatch};
-use crate::query::{PreparedQuery, QueryFlags, QueryValues};
+use crate::query::{CounterUpdate, PreparedQuery, QueryFlags, QueryValues, WriteTimestamp};
 use crate::types::CStringLong;
 
 pub type QueryBatch = BodyReqBatch;
 
+/// What `BatchQueryBuilder::finalize` does when statements of a batch target more than one
+/// partition. Batches spanning many partitions make coordinators contact replicas of all of
+/// them, and logged ones additionally write the batch log, so they are usually slower than
+/// executing statements separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartitionCheck {
+    /// Partitions are not checked.
+    #[default]
+    Disabled,
+    /// A warning is logged.
+    Warn,
+    /// Finalizing the batch fails.
+    Fail,
+}
+
 #[derive(Debug)]
 pub struct BatchQueryBuilder {
     batch_type: BatchType,
@@ -13,6 +31,8 @@ pub struct BatchQueryBuilder {
     consistency: Consistency,
     serial_consistency: Option<Consistency>,
     timestamp: Option<i64>,
+    has_counter_updates: bool,
+    partition_check: PartitionCheck,
 }
 
 impl Default for BatchQueryBuilder {
@@ -23,6 +43,8 @@ impl Default for BatchQueryBuilder {
             consistency: Consistency::One,
             serial_consistency: None,
             timestamp: None,
+            has_counter_updates: false,
+            partition_check: PartitionCheck::Disabled,
         }
     }
 }
@@ -32,6 +54,11 @@ impl BatchQueryBuilder {
         Default::default()
     }
 
+    /// Creates a builder of a counter batch, which can contain only counter updates.
+    pub fn counter() -> BatchQueryBuilder {
+        Self::new().batch_type(BatchType::Counter)
+    }
+
     pub fn batch_type(mut self, batch_type: BatchType) -> Self {
         self.batch_type = batch_type;
         self
@@ -47,18 +74,30 @@ impl BatchQueryBuilder {
         self
     }
 
-    /// Add a query (prepared one)
-    pub fn add_query_prepared(mut self, query: PreparedQuery, values: QueryValues) -> Self {
+    /// Add a query (prepared one). The batch shares the query id with given handle, so
+    /// statements reported as unprepared are re-prepared for all handles during execution.
+    pub fn add_query_prepared<P: Borrow<PreparedQuery>>(
+        mut self,
+        query: P,
+        values: QueryValues,
+    ) -> Self {
         self.queries.push(BatchQuery {
             is_prepared: true,
-            subject: BatchQuerySubj::PreparedId(query),
+            subject: BatchQuerySubj::PreparedId(query.borrow().share()),
             values,
         });
         self
     }
 
+    /// Add a counter update. Counter updates can be added only to counter batches.
+    pub fn add_counter_update(mut self, update: &CounterUpdate) -> Self {
+        self.has_counter_updates = true;
+        self.add_query(update.query(), update.values())
+    }
+
     pub fn clear_queries(mut self) -> Self {
         self.queries = vec![];
+        self.has_counter_updates = false;
         self
     }
 
@@ -77,7 +116,44 @@ impl BatchQueryBuilder {
         self
     }
 
+    /// Sets default timestamp of the batch from a typed value, e.g. `SystemTime` or `DateTime`,
+    /// converting it to microseconds.
+    pub fn default_timestamp<T: Into<WriteTimestamp>>(mut self, timestamp: T) -> Self {
+        self.timestamp = Some(timestamp.into().as_micros());
+        self
+    }
+
+    /// Sets whether to check that all statements target the same partition, using routing
+    /// keys of prepared statements. Statements whose partition is unknown, i.e. simple queries
+    /// and prepared ones not binding the whole partition key, are not checked.
+    pub fn partition_check(mut self, partition_check: PartitionCheck) -> Self {
+        self.partition_check = partition_check;
+        self
+    }
+
     pub fn finalize(self) -> CResult<BodyReqBatch> {
+        if self.has_counter_updates && self.batch_type != BatchType::Counter {
+            return Err(CError::General(String::from(
+                "Counter updates can only be executed in counter batches",
+            )));
+        }
+
+        if self.partition_check != PartitionCheck::Disabled {
+            let partitions = self.partitions();
+            if partitions > 1 {
+                let message = format!(
+                    "Batch of {} statements targets {} partitions",
+                    self.queries.len(),
+                    partitions
+                );
+                if self.partition_check == PartitionCheck::Fail {
+                    return Err(CError::General(message));
+                }
+
+                warn!("{}", message);
+            }
+        }
+
         let mut flags = vec![];
 
         if self.serial_consistency.is_some() {
@@ -114,4 +190,85 @@ impl BatchQueryBuilder {
             timestamp: self.timestamp,
         })
     }
+
+    /// Counts distinct partitions targeted by prepared statements with known routing keys.
+    fn partitions(&self) -> usize {
+        self.queries
+            .iter()
+            .filter_map(|query| match &query.subject {
+                BatchQuerySubj::PreparedId(prepared) => prepared
+                    .routing_key(&query.values)
+                    .map(|routing_key| (prepared.routing_keyspace(), routing_key)),
+                BatchQuerySubj::QueryString(_) => None,
+            })
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{ColSpec, ColType, ColTypeOption, PreparedMetadata};
+    use crate::query::prepared_query::RoutingMetadata;
+    use crate::types::{CBytesShort, CString};
+    use std::sync::{Arc, RwLock};
+
+    fn insert() -> PreparedQuery {
+        let metadata = PreparedMetadata {
+            flags: 1,
+            columns_count: 2,
+            pk_count: 1,
+            pk_indexes: vec![0],
+            global_table_spec: Some((CString::new("ks".into()), CString::new("t".into()))),
+            col_specs: ["pk", "ck"]
+                .iter()
+                .map(|name| ColSpec {
+                    ksname: None,
+                    tablename: None,
+                    name: CString::new(name.to_string()),
+                    col_type: ColTypeOption {
+                        id: ColType::Int,
+                        value: None,
+                    },
+                })
+                .collect(),
+        };
+
+        PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Default::default(),
+            query: "INSERT INTO t (pk, ck) VALUES (?, ?)".into(),
+            keyspace: Default::default(),
+            routing: RoutingMetadata::new(&metadata, false),
+        }
+    }
+
+    fn batch(partition_check: PartitionCheck, rows: &[(i32, i32)]) -> CResult<BodyReqBatch> {
+        let insert = insert();
+        rows.iter()
+            .fold(
+                BatchQueryBuilder::new()
+                    .partition_check(partition_check)
+                    .add_query(
+                        "INSERT INTO t (pk, ck) VALUES (3, 1)",
+                        QueryValues::SimpleValues(vec![]),
+                    ),
+                |builder, (pk, ck)| {
+                    builder.add_query_prepared(
+                        &insert,
+                        QueryValues::SimpleValues(vec![(*pk).into(), (*ck).into()]),
+                    )
+                },
+            )
+            .finalize()
+    }
+
+    #[test]
+    fn checks_partitions() {
+        assert!(batch(PartitionCheck::Fail, &[(1, 1), (1, 2)]).is_ok());
+        assert!(batch(PartitionCheck::Fail, &[(1, 1), (2, 1)]).is_err());
+        assert!(batch(PartitionCheck::Warn, &[(1, 1), (2, 1)]).is_ok());
+        assert!(batch(PartitionCheck::Disabled, &[(1, 1), (2, 1)]).is_ok());
+    }
 }
@@ -0,0 +1,418 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::poll_fn;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::Mutex;
+
+use crate::consistency::Consistency;
+use crate::error;
+use crate::frame::frame_batch::BatchType;
+use crate::query::{
+    BatchExecutor, BatchQueryBuilder, PrepareExecutor, PreparedQuery, QueryBatch, QueryValues,
+};
+use crate::transport::CDRSTransport;
+
+/// Writes many rows with a single prepared statement, grouping rows of the same partition into
+/// unlogged batches. Batches never span partitions, so each one is applied atomically by a
+/// single replica set without coordinator overhead of multi-partition batches.
+#[derive(Debug, Clone)]
+pub struct BulkWriter {
+    statement: PreparedQuery,
+    max_batch_size: Option<usize>,
+    concurrency: Option<usize>,
+    consistency: Option<Consistency>,
+}
+
+/// Rows which could not be written, together with the error of their batch.
+#[derive(Debug)]
+pub struct BulkWriteFailure {
+    /// Indexes of failed rows in the written sequence.
+    pub rows: Vec<usize>,
+    pub error: error::Error,
+}
+
+/// Outcome of a bulk write.
+#[derive(Debug, Default)]
+pub struct BulkWriteResult {
+    /// Number of written rows.
+    pub written: usize,
+    pub failures: Vec<BulkWriteFailure>,
+}
+
+impl BulkWriteResult {
+    /// Returns indexes of all failed rows in ascending order.
+    pub fn failed_rows(&self) -> Vec<usize> {
+        let mut rows: Vec<usize> = self
+            .failures
+            .iter()
+            .flat_map(|failure| failure.rows.iter().cloned())
+            .collect();
+        rows.sort_unstable();
+        rows
+    }
+}
+
+impl BulkWriter {
+    const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+    const DEFAULT_CONCURRENCY: usize = 16;
+
+    /// Creates a writer executing given statement, e.g. an `INSERT`, for every row.
+    pub fn new(statement: PreparedQuery) -> Self {
+        BulkWriter {
+            statement,
+            max_batch_size: None,
+            concurrency: None,
+            consistency: None,
+        }
+    }
+
+    /// Sets maximum number of rows in a batch. Partitions with more rows are split into
+    /// several batches.
+    /// Defaults to 100.
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = Some(max_batch_size.max(1));
+        self
+    }
+
+    /// Sets maximum number of batches in flight at once.
+    /// Defaults to 16.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Sets consistency of the batches.
+    /// Defaults to `Consistency::One`.
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = Some(consistency);
+        self
+    }
+
+    /// Writes rows given as pairs of a partition key, identifying the partition of a row, and
+    /// values bound to the statement. A batch is sent as soon as it reaches the maximum batch
+    /// size, while further rows are read; batches of partitions which didn't fill up are sent
+    /// after the last row, in order of first appearance of their partitions. A failed batch is
+    /// reported with indexes of all its rows.
+    pub async fn write<T, M, E, K, V, I>(&self, session: &E, rows: I) -> BulkWriteResult
+    where
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        E: BatchExecutor<T, M> + PrepareExecutor<T, M>,
+        K: Hash + Eq,
+        V: Into<QueryValues>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write_stream(session, IterStream(rows.into_iter()))
+            .await
+    }
+
+    /// Writes rows read from a stream like `write`. Rows are read while batches are in
+    /// flight, until `concurrency` batches are.
+    pub async fn write_stream<T, M, E, K, V, S>(&self, session: &E, mut rows: S) -> BulkWriteResult
+    where
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        E: BatchExecutor<T, M> + PrepareExecutor<T, M>,
+        K: Hash + Eq,
+        V: Into<QueryValues>,
+        S: Stream<Item = (K, V)> + Unpin,
+    {
+        let concurrency = self.concurrency.unwrap_or(Self::DEFAULT_CONCURRENCY).max(1);
+        let mut batches = Batches::new(self);
+        let mut rows_done = false;
+        let mut running = Vec::with_capacity(concurrency);
+        let mut result = BulkWriteResult::default();
+
+        poll_fn(|cx| loop {
+            while running.len() < concurrency {
+                if let Some((rows, batch)) = batches.next_ready() {
+                    match batch {
+                        Ok(batch) => running.push((rows, session.batch_with_params(batch))),
+                        Err(error) => result.failures.push(BulkWriteFailure { rows, error }),
+                    }
+                    continue;
+                }
+
+                if rows_done {
+                    break;
+                }
+
+                match Pin::new(&mut rows).poll_next(cx) {
+                    Poll::Ready(Some((key, values))) => batches.push(key, values.into()),
+                    Poll::Ready(None) => {
+                        rows_done = true;
+                        batches.finish();
+                    }
+                    Poll::Pending => break,
+                }
+            }
+
+            let before = running.len();
+            running.retain_mut(|(rows, batch)| match batch.as_mut().poll(cx) {
+                Poll::Ready(Ok(_)) => {
+                    result.written += rows.len();
+                    false
+                }
+                Poll::Ready(Err(error)) => {
+                    result.failures.push(BulkWriteFailure {
+                        rows: std::mem::take(rows),
+                        error,
+                    });
+                    false
+                }
+                Poll::Pending => true,
+            });
+
+            if running.is_empty() && rows_done && batches.is_empty() {
+                return Poll::Ready(());
+            }
+            if running.len() == before {
+                return Poll::Pending;
+            }
+        })
+        .await;
+
+        result
+    }
+
+    /// Writes rows like `write`, but in consecutive chunks of `chunk_size` rows, so batches of
+    /// partitions which didn't fill up are kept in memory for one chunk at most. Indexes of
+    /// failed rows refer to the whole sequence.
+    pub async fn write_chunked<T, M, E, K, V, I>(
+        &self,
+        session: &E,
+        rows: I,
+        chunk_size: usize,
+    ) -> BulkWriteResult
+    where
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+        E: BatchExecutor<T, M> + PrepareExecutor<T, M>,
+        K: Hash + Eq,
+        V: Into<QueryValues>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut result = BulkWriteResult::default();
+        let mut rows = rows.into_iter().peekable();
+        let mut offset = 0;
+
+        while rows.peek().is_some() {
+            let chunk: Vec<(K, V)> = rows.by_ref().take(chunk_size.max(1)).collect();
+            let chunk_len = chunk.len();
+            let chunk_result = self.write(session, chunk).await;
+
+            result.written += chunk_result.written;
+            result
+                .failures
+                .extend(chunk_result.failures.into_iter().map(|mut failure| {
+                    failure.rows.iter_mut().for_each(|row| *row += offset);
+                    failure
+                }));
+            offset += chunk_len;
+        }
+
+        result
+    }
+
+    fn batch(&self, rows: Vec<(usize, QueryValues)>) -> (Vec<usize>, error::Result<QueryBatch>) {
+        let mut builder = BatchQueryBuilder::new().batch_type(BatchType::Unlogged);
+        if let Some(consistency) = self.consistency {
+            builder = builder.consistency(consistency);
+        }
+
+        let mut indexes = Vec::with_capacity(rows.len());
+        for (index, values) in rows {
+            indexes.push(index);
+            builder = builder.add_query_prepared(&self.statement, values);
+        }
+
+        (indexes, builder.finalize())
+    }
+}
+
+/// Groups rows into batches of their partitions, handing out batches as soon as they fill up.
+struct Batches<'a, K> {
+    writer: &'a BulkWriter,
+    max_batch_size: usize,
+    next_index: usize,
+    /// Rows of batches which haven't filled up yet, with the order of their partitions.
+    pending: HashMap<K, (usize, Vec<(usize, QueryValues)>)>,
+    next_order: usize,
+    ready: VecDeque<Vec<(usize, QueryValues)>>,
+}
+
+impl<'a, K: Hash + Eq> Batches<'a, K> {
+    fn new(writer: &'a BulkWriter) -> Self {
+        Batches {
+            writer,
+            max_batch_size: writer
+                .max_batch_size
+                .unwrap_or(BulkWriter::DEFAULT_MAX_BATCH_SIZE),
+            next_index: 0,
+            pending: HashMap::new(),
+            next_order: 0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, key: K, values: QueryValues) {
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let next_order = &mut self.next_order;
+        let (_, rows) = self.pending.entry(key).or_insert_with(|| {
+            *next_order += 1;
+            (*next_order, vec![])
+        });
+        rows.push((index, values));
+
+        if rows.len() >= self.max_batch_size {
+            self.ready.push_back(std::mem::take(rows));
+        }
+    }
+
+    /// Hands out batches of all partitions after the last row.
+    fn finish(&mut self) {
+        let mut pending: Vec<_> = self
+            .pending
+            .drain()
+            .map(|(_, batch)| batch)
+            .filter(|(_, rows)| !rows.is_empty())
+            .collect();
+        pending.sort_unstable_by_key(|(order, _)| *order);
+
+        self.ready.extend(pending.into_iter().map(|(_, rows)| rows));
+    }
+
+    fn next_ready(&mut self) -> Option<(Vec<usize>, error::Result<QueryBatch>)> {
+        self.ready.pop_front().map(|rows| self.writer.batch(rows))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ready.is_empty() && self.pending.values().all(|(_, rows)| rows.is_empty())
+    }
+}
+
+/// Stream of items of an iterator, which are always ready.
+struct IterStream<I>(I);
+
+impl<I> Unpin for IterStream<I> {}
+
+impl<I: Iterator> Stream for IterStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::test_node::{void, TestNode};
+    use crate::cluster::DirectSession;
+    use crate::frame::Opcode;
+    use crate::types::CBytesShort;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    fn writer() -> BulkWriter {
+        BulkWriter::new(PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Default::default(),
+            query: "INSERT INTO t (pk, ck) VALUES (?, ?)".into(),
+            keyspace: Default::default(),
+            routing: Default::default(),
+        })
+        .max_batch_size(2)
+    }
+
+    struct ReceiverStream<T>(mpsc::UnboundedReceiver<T>);
+
+    impl<T> Stream for ReceiverStream<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.0.poll_recv(cx)
+        }
+    }
+
+    #[test]
+    fn batches_by_partition() {
+        let writer = writer();
+
+        let mut batches = Batches::new(&writer);
+        let mut ready = vec![];
+        for (pk, ck) in vec![("a", 1), ("b", 2), ("a", 3), ("a", 4), ("b", 5)] {
+            batches.push(pk, vec![ck].into());
+            // full batches are handed out before the next row is read
+            ready.extend(std::iter::from_fn(|| batches.next_ready()));
+        }
+        assert!(!batches.is_empty());
+        batches.finish();
+        ready.extend(std::iter::from_fn(|| batches.next_ready()));
+        assert!(batches.is_empty());
+
+        let rows: Vec<Vec<usize>> = ready.iter().map(|(rows, _)| rows.clone()).collect();
+        assert_eq!(rows, vec![vec![0, 2], vec![1, 4], vec![3]]);
+
+        let batch = ready[0].1.as_ref().unwrap();
+        assert_eq!(batch.batch_type, BatchType::Unlogged);
+        assert_eq!(batch.queries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn write_stream_sends_full_batches() {
+        let node = TestNode::start().await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let writer = writer();
+        let (rows_tx, rows) = mpsc::unbounded_channel();
+
+        let feed = async move {
+            for (pk, ck) in [("a", 1), ("b", 2), ("a", 3)] {
+                rows_tx.send((pk, vec![ck])).unwrap();
+            }
+
+            // the batch of partition "a" is sent while the stream is still open
+            while node.requests().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            assert_eq!(node.requests()[0].opcode, Opcode::Batch);
+            drop(rows_tx);
+            node
+        };
+        let write = writer.write_stream(&session, ReceiverStream(rows));
+        let (node, result) = tokio::join!(feed, write);
+
+        assert_eq!(result.written, 3);
+        assert!(result.failures.is_empty());
+        assert_eq!(node.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_batches_are_reported() {
+        // batches of partition 1 fail, the first value of a batch starts at byte 13
+        fn fail_partition_1(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            match opcode {
+                Opcode::Batch if body[16] == 1 => Some((Opcode::Error, vec![0, 0, 0x22, 0, 0, 0])),
+                _ => void(opcode, body),
+            }
+        }
+
+        let node = TestNode::start_with(fail_partition_1).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let writer = writer().concurrency(2);
+
+        let result = writer
+            .write(&session, (0..6).map(|i| (i % 3, vec![i % 3, i])))
+            .await;
+
+        assert_eq!(result.written, 4);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failed_rows(), vec![1, 4]);
+    }
+}
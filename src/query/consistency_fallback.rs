@@ -0,0 +1,35 @@
+use crate::consistency::Consistency;
+
+/// Chain of consistency levels a query is retried with, in order, when the coordinator reports
+/// that not enough replicas are alive to satisfy the requested consistency (`Unavailable`
+/// error). Meant for endpoints which prefer a possibly stale answer over no answer, e.g.
+/// `QUORUM -> ONE` reads, so it is passed explicitly to the executing method rather than being
+/// applied session-wide.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConsistencyFallback {
+    levels: Vec<Consistency>,
+}
+
+impl ConsistencyFallback {
+    /// Creates an empty chain, which doesn't retry queries.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a consistency level to the chain.
+    pub fn then(mut self, consistency: Consistency) -> Self {
+        self.levels.push(consistency);
+        self
+    }
+
+    /// Returns consistency levels to fall back to, in order.
+    pub fn levels(&self) -> &[Consistency] {
+        &self.levels
+    }
+}
+
+impl From<Vec<Consistency>> for ConsistencyFallback {
+    fn from(levels: Vec<Consistency>) -> Self {
+        ConsistencyFallback { levels }
+    }
+}
@@ -0,0 +1,383 @@
+use futures_core::Stream;
+use std::io::Cursor;
+use std::net;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::cluster::{report_node_failure, ConnectionPool};
+use crate::compression::Compression;
+use crate::error;
+use crate::frame::frame_result::{ResultKind, RowsMetadata, RowsMetadataFlag};
+use crate::frame::parser::{convert_frame_into_result, read_from_transport};
+use crate::frame::{AsBytes, Frame, FromBytes, FromCursor, Opcode};
+use crate::query::{Query, QueryParams};
+use crate::transport::{write_frame, CDRSTransport};
+use crate::types::{to_int, INT_LEN};
+
+/// Key of the custom payload entry carrying continuous paging options.
+pub const CONTINUOUS_PAGING_PAYLOAD_KEY: &str = "continuous_paging";
+
+/// Options of continuous paging. Instead of waiting for the client to request every page, the
+/// server streams all pages of the result over a single connection.
+///
+/// The options are sent in the `continuous_paging` custom payload entry as two ints: maximum
+/// pages and maximum pages per second. Neither Apache Cassandra nor DSE read this entry - DSE
+/// only supports continuous paging with its own protocol versions (`DSE_V1` and `DSE_V2`),
+/// which the driver doesn't speak. It is understood by servers with a custom query handler
+/// reading it, e.g. proxies or emulators, which mark pages with the continuous paging flags of
+/// rows metadata. Other servers ignore the entry and return a single regular page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuousPagingOptions {
+    /// Maximum number of pages sent by the server, 0 for no limit. Defaults to 0.
+    pub max_pages: i32,
+    /// Maximum number of pages sent by the server per second, 0 for no limit. Defaults to 0.
+    pub max_pages_per_second: i32,
+    /// Number of received pages buffered by the driver before it stops reading from the
+    /// connection, which in turn slows down the server. Defaults to 4.
+    pub buffered_pages: usize,
+}
+
+impl Default for ContinuousPagingOptions {
+    fn default() -> Self {
+        ContinuousPagingOptions {
+            max_pages: 0,
+            max_pages_per_second: 0,
+            buffered_pages: 4,
+        }
+    }
+}
+
+impl ContinuousPagingOptions {
+    /// Adds the options to custom payload of given query parameters.
+    pub fn apply(&self, query_params: &mut QueryParams) {
+        query_params
+            .custom_payload
+            .insert(CONTINUOUS_PAGING_PAYLOAD_KEY.into(), self.as_bytes());
+    }
+}
+
+impl AsBytes for ContinuousPagingOptions {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut v = to_int(self.max_pages);
+        v.extend_from_slice(&to_int(self.max_pages_per_second));
+        v
+    }
+}
+
+/// `Stream` of pages of a continuously paged result. Pages are read from the connection in the
+/// background, at most `ContinuousPagingOptions::buffered_pages` ahead of the consumer. Dropping
+/// the stream before the last page closes the connection, since the server would keep sending
+/// pages over it.
+#[derive(Debug)]
+pub struct ContinuousPages {
+    pages: mpsc::Receiver<error::Result<Frame>>,
+}
+
+impl ContinuousPages {
+    pub(crate) fn start<T, M>(
+        node: Arc<ConnectionPool<M>>,
+        compression: Compression,
+        query: Query,
+        buffered_pages: usize,
+    ) -> Self
+    where
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let (pages_tx, pages) = mpsc::channel(buffered_pages.max(1));
+        tokio::spawn(read_pages(node, compression, query, pages_tx));
+
+        ContinuousPages { pages }
+    }
+
+    /// Returns the next page, or `None` after the last one. An error ends the stream.
+    pub async fn next(&mut self) -> Option<error::Result<Frame>> {
+        self.pages.recv().await
+    }
+}
+
+impl Stream for ContinuousPages {
+    type Item = error::Result<Frame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.pages.poll_recv(cx)
+    }
+}
+
+async fn read_pages<T, M>(
+    node: Arc<ConnectionPool<M>>,
+    compression: Compression,
+    query: Query,
+    pages: mpsc::Sender<error::Result<Frame>>,
+) where
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let wait_started = Instant::now();
+    let connection = node.checkout().await;
+    node.wait_times().record(wait_started.elapsed());
+
+    let connection = match connection {
+        Ok(connection) => connection,
+        Err(error) => {
+            report_node_failure(&node);
+            let error = match error {
+                bb8::RunError::User(error) => error,
+                bb8::RunError::TimedOut => "Timed out waiting for connection".into(),
+            };
+            let _ = pages.send(Err(error)).await;
+            return;
+        }
+    };
+
+    let protocol_version = connection.lock().await.protocol_version();
+    let frame = match Frame::new_query_for(&query, vec![], protocol_version) {
+        Ok(frame) => frame,
+        Err(error) => {
+            let _ = pages.send(Err(error)).await;
+            return;
+        }
+    };
+    let stream_id = frame.stream;
+
+    if let Err(error) = write_frame(connection.deref(), &frame.as_bytes()).await {
+        report_node_failure(&node);
+        let _ = pages.send(Err(error.into())).await;
+        return;
+    }
+
+    loop {
+        let frame = tokio::select! {
+            frame = read_from_transport(&connection, compression) => frame,
+            _ = pages.closed() => {
+                // remaining pages would be left unread on the connection
+                let _ = connection.lock().await.close(net::Shutdown::Both).await;
+                return;
+            }
+        };
+
+        match frame {
+            // connections are used exclusively, so responses to other streams belong to
+            // requests abandoned before their responses were read
+            Ok(frame) if frame.stream != stream_id => {
+                connection.lock().await.release_orphaned_stream();
+            }
+            // server errors are received in well-formed frames and end the result
+            Ok(frame) if frame.opcode == Opcode::Error => {
+                let _ = pages.send(convert_frame_into_result(frame)).await;
+                return;
+            }
+            Ok(mut frame) => {
+                frame.coordinator = Some(node.get_addr());
+                let is_last = is_last_page(&frame);
+                if pages.send(Ok(frame)).await.is_err() && !is_last {
+                    let _ = connection.lock().await.close(net::Shutdown::Both).await;
+                    return;
+                }
+
+                if is_last {
+                    node.health().report_success();
+                    return;
+                }
+            }
+            Err(error) => {
+                let _ = connection.lock().await.close(net::Shutdown::Both).await;
+                let error = match error {
+                    error::Error::Io(error) => {
+                        report_node_failure(&node);
+                        error::Error::Io(error)
+                    }
+                    error => error::Error::ConnectionPoisoned(Box::new(error)),
+                };
+                let _ = pages.send(Err(error)).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Returns `true` if given response ends a continuously paged result. Servers which do not
+/// support continuous paging return a single regular page.
+fn is_last_page(frame: &Frame) -> bool {
+    if frame.opcode != Opcode::Result || frame.body.len() < INT_LEN {
+        return true;
+    }
+
+    if !matches!(
+        ResultKind::from_bytes(&frame.body[..INT_LEN]),
+        Ok(ResultKind::Rows)
+    ) {
+        return true;
+    }
+
+    match RowsMetadata::from_cursor_for(
+        &mut Cursor::new(&frame.body[INT_LEN..]),
+        frame.protocol_version,
+    ) {
+        Ok(metadata) => {
+            !RowsMetadataFlag::has_continuous_paging(metadata.flags)
+                || RowsMetadataFlag::has_last_continuous_page(metadata.flags)
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Version;
+
+    fn rows_frame(flags: i32) -> Frame {
+        let mut body = to_int(2);
+        body.extend_from_slice(&to_int(flags));
+        // no columns
+        body.extend_from_slice(&to_int(0));
+        if RowsMetadataFlag::has_continuous_paging(flags) {
+            body.extend_from_slice(&to_int(1));
+        }
+        // no rows
+        body.extend_from_slice(&to_int(0));
+
+        Frame::new(
+            Version::Response,
+            vec![],
+            Opcode::Result,
+            body,
+            None,
+            vec![],
+        )
+    }
+
+    #[test]
+    fn detects_last_page() {
+        let continuous = RowsMetadataFlag::set_continuous_paging(0);
+        assert!(!is_last_page(&rows_frame(continuous)));
+        assert!(is_last_page(&rows_frame(
+            RowsMetadataFlag::set_last_continuous_page(continuous)
+        )));
+        assert!(is_last_page(&rows_frame(0)));
+
+        let page = rows_frame(continuous).get_body().unwrap();
+        assert_eq!(
+            page.as_rows_metadata().unwrap().continuous_page_number,
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_are_a_stream() {
+        let (pages_tx, pages) = mpsc::channel(2);
+        let mut pages = ContinuousPages { pages };
+        pages_tx.send(Ok(rows_frame(0))).await.unwrap();
+        drop(pages_tx);
+
+        let first = std::future::poll_fn(|cx| Pin::new(&mut pages).poll_next(cx)).await;
+        assert!(matches!(first, Some(Ok(_))));
+        let end = std::future::poll_fn(|cx| Pin::new(&mut pages).poll_next(cx)).await;
+        assert!(end.is_none());
+    }
+
+    #[test]
+    fn options_in_custom_payload() {
+        let mut params = QueryParams::default();
+        ContinuousPagingOptions {
+            max_pages: 10,
+            max_pages_per_second: 2,
+            ..Default::default()
+        }
+        .apply(&mut params);
+
+        assert_eq!(
+            params.custom_payload.get(CONTINUOUS_PAGING_PAYLOAD_KEY),
+            Some(&vec![0, 0, 0, 10, 0, 0, 0, 2])
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_are_received_until_the_last_one() {
+        use crate::cluster::DirectSession;
+        use crate::frame::AsByte;
+        use crate::query::QueryExecutor;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        // answers queries carrying custom payload with three continuous pages
+        async fn serve_continuous(listener: TcpListener) {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = [0; 9];
+
+            while stream.read_exact(&mut header).await.is_ok() {
+                let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+                let mut body = vec![0; length as usize];
+                stream.read_exact(&mut body).await.unwrap();
+
+                let pages = match Opcode::from(header[4]) {
+                    Opcode::Startup => vec![(Opcode::Ready, vec![])],
+                    Opcode::Options => vec![(Opcode::Supported, vec![0, 0])],
+                    _ if header[1] & 0x04 != 0 => (1..=3)
+                        .map(|page| {
+                            let mut flags = RowsMetadataFlag::set_continuous_paging(0);
+                            if page == 3 {
+                                flags = RowsMetadataFlag::set_last_continuous_page(flags);
+                            }
+
+                            let mut body = to_int(2);
+                            body.extend_from_slice(&to_int(flags));
+                            body.extend_from_slice(&to_int(0));
+                            body.extend_from_slice(&to_int(page));
+                            body.extend_from_slice(&to_int(0));
+                            (Opcode::Result, body)
+                        })
+                        .collect(),
+                    _ => continue,
+                };
+
+                for (opcode, body) in pages {
+                    let mut response = vec![
+                        Version::Response.as_byte(),
+                        0,
+                        header[2],
+                        header[3],
+                        opcode.as_byte(),
+                    ];
+                    response.extend_from_slice(&(body.len() as u32).to_be_bytes());
+                    response.extend_from_slice(&body);
+                    stream.write_all(&response).await.unwrap();
+                }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_continuous(listener));
+
+        let session = DirectSession::connect(addr).await.unwrap();
+        let mut pages = session
+            .query_continuous(
+                "SELECT * FROM t",
+                Default::default(),
+                ContinuousPagingOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let mut page_numbers = vec![];
+        while let Some(page) = pages.next().await {
+            let page = page.unwrap();
+            assert_eq!(page.coordinator, Some(addr));
+            page_numbers.push(
+                page.get_body()
+                    .unwrap()
+                    .as_rows_metadata()
+                    .unwrap()
+                    .continuous_page_number,
+            );
+        }
+
+        assert_eq!(page_numbers, vec![Some(1), Some(2), Some(3)]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error;
+
+/// Hook notified about the coordinator of every request a node replied to, e.g. to count
+/// requests per coordinator and find hot coordinators or validate load balancing. Requests
+/// which failed before a node replied, e.g. with connection errors, are not reported.
+pub trait CoordinatorListener: Send + Sync {
+    /// Called with the node which coordinated a request, time from sending the request to
+    /// receiving its response and the error the node replied with, if any.
+    fn on_response(&self, coordinator: SocketAddr, latency: Duration, error: Option<&error::Error>);
+}
+
+impl<F: Fn(SocketAddr, Duration, Option<&error::Error>) + Send + Sync> CoordinatorListener for F {
+    fn on_response(
+        &self,
+        coordinator: SocketAddr,
+        latency: Duration,
+        error: Option<&error::Error>,
+    ) {
+        self(coordinator, latency, error)
+    }
+}
+
+impl fmt::Debug for dyn CoordinatorListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CoordinatorListener")
+    }
+}
+
+/// Notifies listeners about the coordinator of a request, if it replied with a frame.
+#[cfg(feature = "runtime")]
+pub(crate) fn notify_coordinator<T>(
+    listeners: &[Arc<dyn CoordinatorListener>],
+    coordinator: SocketAddr,
+    latency: Duration,
+    result: &error::Result<T>,
+) {
+    let error = match result {
+        Ok(_) => None,
+        Err(error @ error::Error::Server(_)) => Some(error),
+        Err(_) => return,
+    };
+
+    for listener in listeners {
+        listener.on_response(coordinator, latency, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn notifies_replies() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let listener: Arc<dyn CoordinatorListener> = {
+            let seen = seen.clone();
+            Arc::new(move |coordinator, _latency, error: Option<&error::Error>| {
+                seen.lock().unwrap().push((coordinator, error.is_some()));
+            })
+        };
+        let listeners = vec![listener];
+        let addr: SocketAddr = "127.0.0.1:9042".parse().unwrap();
+
+        notify_coordinator(&listeners, addr, Duration::from_millis(1), &Ok(()));
+        notify_coordinator::<()>(
+            &listeners,
+            addr,
+            Duration::from_millis(1),
+            &Err(error::Error::from("connection closed")),
+        );
+
+        assert_eq!(*seen.lock().unwrap(), vec![(addr, false)]);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::error;
+use crate::query::QueryValues;
+use crate::types::value::Value;
+
+/// Update of a counter column, i.e. `UPDATE ... SET c = c + ? WHERE ...`, incrementing or
+/// decrementing (with a negative delta) the counter of a single row identified by its primary
+/// key. Counter updates cannot be mixed with other statements in a batch; use
+/// `BatchQueryBuilder::counter` to batch them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterUpdate {
+    table: String,
+    counter_column: String,
+    delta: i64,
+    keys: Vec<(String, Value)>,
+}
+
+impl CounterUpdate {
+    /// Creates an update adding `delta` to `counter_column` of `table`.
+    pub fn new(table: &str, counter_column: &str, delta: i64) -> Self {
+        CounterUpdate {
+            table: table.to_string(),
+            counter_column: counter_column.to_string(),
+            delta,
+            keys: vec![],
+        }
+    }
+
+    /// Creates an update of a row identified by named key values, e.g. created with
+    /// `query_values!("id" => 1)`. Keys are ordered by name.
+    pub fn with_key_values(
+        table: &str,
+        key_values: QueryValues,
+        counter_column: &str,
+        delta: i64,
+    ) -> error::Result<Self> {
+        let mut keys: Vec<(String, Value)> = match key_values {
+            QueryValues::NamedValues(values) => values.into_iter().collect(),
+            QueryValues::SimpleValues(_) => {
+                return Err("Counter key values need to be named".into());
+            }
+        };
+        keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(CounterUpdate {
+            keys,
+            ..Self::new(table, counter_column, delta)
+        })
+    }
+
+    /// Adds a primary key column and its value. All primary key columns have to be given.
+    pub fn key<V: Into<Value>>(mut self, column: &str, value: V) -> Self {
+        self.keys.push((column.to_string(), value.into()));
+        self
+    }
+
+    /// Returns CQL text of the update.
+    pub fn query(&self) -> String {
+        let conditions: Vec<String> = self
+            .keys
+            .iter()
+            .map(|(column, _)| format!("{} = ?", column))
+            .collect();
+
+        format!(
+            "UPDATE {} SET {} = {} + ? WHERE {}",
+            self.table,
+            self.counter_column,
+            self.counter_column,
+            conditions.join(" AND ")
+        )
+    }
+
+    /// Returns values bound to the update: the delta followed by key values.
+    pub fn values(&self) -> QueryValues {
+        let mut values: Vec<Value> = vec![self.delta.into()];
+        values.extend(self.keys.iter().map(|(_, value)| value.clone()));
+
+        QueryValues::SimpleValues(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_batch::BatchType;
+    use crate::query::BatchQueryBuilder;
+    use std::collections::HashMap;
+
+    #[test]
+    fn counter_update() {
+        let update = CounterUpdate::new("ks.page_views", "views", -2)
+            .key("site", "example.com")
+            .key("day", 7);
+
+        assert_eq!(
+            update.query(),
+            "UPDATE ks.page_views SET views = views + ? WHERE site = ? AND day = ?"
+        );
+        assert_eq!(
+            update.values(),
+            QueryValues::SimpleValues(vec![(-2i64).into(), "example.com".into(), 7.into()])
+        );
+    }
+
+    #[test]
+    fn counter_update_with_key_values() {
+        let update = CounterUpdate::with_key_values(
+            "page_views",
+            QueryValues::NamedValues(HashMap::from([
+                ("site".to_string(), "example.com".into()),
+                ("day".to_string(), 7.into()),
+            ])),
+            "views",
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            update.query(),
+            "UPDATE page_views SET views = views + ? WHERE day = ? AND site = ?"
+        );
+        assert_eq!(
+            update.values(),
+            QueryValues::SimpleValues(vec![1i64.into(), 7.into(), "example.com".into()])
+        );
+
+        assert!(CounterUpdate::with_key_values("page_views", vec![1].into(), "views", 1).is_err());
+    }
+
+    #[test]
+    fn counter_batch() {
+        let update = CounterUpdate::new("page_views", "views", 1).key("site", "example.com");
+
+        let batch = BatchQueryBuilder::counter()
+            .add_counter_update(&update)
+            .add_counter_update(&update)
+            .finalize()
+            .unwrap();
+        assert_eq!(batch.batch_type, BatchType::Counter);
+        assert_eq!(batch.queries.len(), 2);
+
+        assert!(BatchQueryBuilder::new()
+            .add_counter_update(&update)
+            .finalize()
+            .is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Write;
+
+use crate::query::QueryValues;
+use crate::types::value::{Value, ValueType};
+
+/// Renders a query with its values inlined as literals, e.g. to paste a failing statement into
+/// `cqlsh`. **For debugging only** - values are sent without their types, so the rendering is
+/// approximate: printable UTF-8 values become text literals, all other ones blob literals.
+/// Bind markers without a matching value are left as they are.
+pub fn debug_cql(query: &str, values: Option<&QueryValues>) -> String {
+    let mut rendered = String::with_capacity(query.len());
+    let mut positional = match values {
+        Some(QueryValues::SimpleValues(values)) => values.iter(),
+        _ => [].iter(),
+    };
+
+    let mut chars = query.char_indices().peekable();
+    let mut quote = None;
+
+    while let Some((idx, c)) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('?', None) => {
+                if let Some(value) = positional.next() {
+                    write_literal(&mut rendered, value);
+                    continue;
+                }
+            }
+            (':', None) => {
+                if let Some(QueryValues::NamedValues(values)) = values {
+                    let name_len = query[idx + 1..]
+                        .find(|c: char| !c.is_alphanumeric() && c != '_')
+                        .unwrap_or(query.len() - idx - 1);
+
+                    if let Some(value) = values.get(&query[idx + 1..idx + 1 + name_len]) {
+                        write_literal(&mut rendered, value);
+                        for _ in 0..name_len {
+                            chars.next();
+                        }
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        rendered.push(c);
+    }
+
+    rendered
+}
+
+fn write_literal(rendered: &mut String, value: &Value) {
+    match value.value_type {
+        ValueType::Null => rendered.push_str("NULL"),
+        ValueType::NotSet => rendered.push_str("/* unset */ NULL"),
+        ValueType::Normal(_) => match std::str::from_utf8(&value.body) {
+            Ok(text) if !text.chars().any(char::is_control) => {
+                rendered.push('\'');
+                rendered.push_str(&text.replace('\'', "''"));
+                rendered.push('\'');
+            }
+            _ => {
+                rendered.push_str("0x");
+                for byte in &value.body {
+                    let _ = write!(rendered, "{:02x}", byte);
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn inline_simple_values() {
+        let values =
+            QueryValues::SimpleValues(vec!["O'Brien".into(), 1i32.into(), Value::new_null()]);
+
+        assert_eq!(
+            debug_cql(
+                "INSERT INTO t (name, id, note, extra) VALUES (?, ?, ?, '?')",
+                Some(&values)
+            ),
+            "INSERT INTO t (name, id, note, extra) VALUES ('O''Brien', 0x00000001, NULL, '?')"
+        );
+    }
+
+    #[test]
+    fn inline_named_values() {
+        let mut values = HashMap::new();
+        values.insert("id".to_string(), Value::from("a"));
+        let values = QueryValues::NamedValues(values);
+
+        assert_eq!(
+            debug_cql(
+                "SELECT * FROM t WHERE id = :id AND ts > '12:00' AND x = :missing",
+                Some(&values)
+            ),
+            "SELECT * FROM t WHERE id = 'a' AND ts > '12:00' AND x = :missing"
+        );
+    }
+
+    #[test]
+    fn keep_markers_without_values() {
+        assert_eq!(
+            debug_cql("SELECT * FROM t WHERE id = ?", None),
+            "SELECT * FROM t WHERE id = ?"
+        );
+    }
+}
@@ -1,20 +1,36 @@
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
-use crate::cluster::{GetCompressor, GetConnection, ResponseCache};
+use crate::cluster::{
+    GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks, ResponseCache,
+};
 use crate::error;
-use crate::frame::{AsBytes, Frame};
-use crate::query::{PrepareExecutor, PreparedQuery, QueryParams, QueryParamsBuilder, QueryValues};
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::{Frame, TryFromRow};
+use crate::query::{
+    notify_trace, should_trace, ConsistencyFallback, PrepareExecutor, PreparedQuery, QueryParams,
+    QueryParamsBuilder, QueryResponse, QueryValues, WithFrame,
+};
 use crate::transport::CDRSTransport;
 
-use super::utils::{prepare_flags, send_frame};
+use super::utils::{
+    prepare_flags, send_frame_routed, session_rows_of, session_typed_rows_of,
+    with_consistency_fallback, with_request_deadline, KeyspaceCheck, Routing,
+};
 use std::ops::Deref;
 
 #[async_trait]
 pub trait ExecExecutor<
     T: CDRSTransport + Unpin + 'static,
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
->: GetConnection<T, M> + GetCompressor + PrepareExecutor<T, M> + ResponseCache + Sync
+>:
+    GetConnection<T, M>
+    + GetCompressor
+    + GetQueryDefaults
+    + GetSessionHooks
+    + PrepareExecutor<T, M>
+    + ResponseCache
+    + Sync
 {
     async fn exec_with_params_tw(
         &self,
@@ -23,32 +39,104 @@ pub trait ExecExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
+        let mut query_parameters = query_parameters;
+        if let Some(generator) = self.hooks().timestamp_generator.as_ref() {
+            generator.stamp(&mut query_parameters);
+        }
+
+        let with_tracing = should_trace(&self.query_defaults(), &query_parameters, with_tracing);
         let flags = prepare_flags(with_tracing, with_warnings);
-        let options_frame = Frame::new_req_execute(
-            prepared
-                .id
-                .read()
-                .expect("Cannot read prepared query id!")
-                .deref(),
-            &query_parameters,
-            flags,
+        let build = |protocol_version| {
+            Frame::new_req_execute_for(
+                prepared
+                    .id
+                    .read()
+                    .expect("Cannot read prepared query id!")
+                    .deref(),
+                prepared
+                    .result_metadata_id
+                    .read()
+                    .expect("Cannot read result metadata id!")
+                    .as_ref(),
+                &query_parameters,
+                flags.clone(),
+                protocol_version,
+            )
+        };
+
+        let keyspace = prepared.keyspace();
+        let routing_key = query_parameters
+            .values
+            .as_ref()
+            .and_then(|values| prepared.routing_key(values));
+        let routing = prepared.routing_keyspace().zip(routing_key.as_deref()).map(
+            |(keyspace, routing_key)| Routing {
+                keyspace,
+                routing_key,
+                primary_first: prepared.is_lwt(),
+            },
         );
 
-        let mut result = send_frame(self, options_frame.as_bytes(), options_frame.stream).await;
+        let mut result = with_request_deadline(
+            query_parameters.deadline,
+            send_frame_routed(
+                self,
+                &build,
+                KeyspaceCheck::Expect(keyspace.as_deref()),
+                routing,
+            ),
+        )
+        .await
+        .map(|(frame, _)| frame);
         if let Err(error::Error::Server(error)) = &result {
             // if query is unprepared
-            if error.error_code == 0x2500 {
-                if let Ok(new) = self.prepare_raw(&prepared.query).await {
+            if error.is_unprepared() {
+                if let Some(cache) = self.hooks().prepared_cache.as_ref() {
+                    cache.record_re_prepare();
+                }
+
+                if let Ok((new, keyspace, _)) = self
+                    .prepare_raw_in_keyspace(&prepared.query, false, false)
+                    .await
+                {
                     *prepared
                         .id
                         .write()
                         .expect("Cannot write prepared query id!") = new.id.clone();
-                    let flags = prepare_flags(with_tracing, with_warnings);
-                    let options_frame = Frame::new_req_execute(&new.id, &query_parameters, flags);
-                    result = send_frame(self, options_frame.as_bytes(), options_frame.stream).await;
+                    *prepared
+                        .result_metadata_id
+                        .write()
+                        .expect("Cannot write result metadata id!") =
+                        new.result_metadata_id.clone();
+                    *prepared
+                        .keyspace
+                        .write()
+                        .expect("Cannot write prepared query keyspace!") = keyspace.clone();
+                    result = with_request_deadline(
+                        query_parameters.deadline,
+                        send_frame_routed(
+                            self,
+                            &build,
+                            KeyspaceCheck::Expect(keyspace.as_deref()),
+                            routing,
+                        ),
+                    )
+                    .await
+                    .map(|(frame, _)| frame);
                 }
             }
         }
+
+        if let Ok(frame) = &result {
+            if frame.protocol_version.has_result_metadata_id() {
+                update_result_metadata_id(prepared, frame);
+            }
+        }
+
+        if let (true, Ok(frame)) = (with_tracing, &result) {
+            notify_trace(&self.hooks().tracing_listeners, &prepared.query, frame);
+        }
+
         result
     }
 
@@ -68,7 +156,7 @@ pub trait ExecExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
-        let query_params_builder = QueryParamsBuilder::new();
+        let query_params_builder = QueryParamsBuilder::new().defaults(&self.query_defaults());
         let query_params = query_params_builder.values(values.into()).finalize();
         self.exec_with_params_tw(prepared, query_params, with_tracing, with_warnings)
             .await
@@ -89,7 +177,9 @@ pub trait ExecExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
-        let query_params = QueryParamsBuilder::new().finalize();
+        let query_params = QueryParamsBuilder::new()
+            .defaults(&self.query_defaults())
+            .finalize();
         self.exec_with_params_tw(prepared, query_params, with_tracing, with_warnings)
             .await
     }
@@ -100,4 +190,207 @@ pub trait ExecExecutor<
     {
         self.exec_tw(prepared, false, false).await
     }
+
+    /// Executes a prepared query with given parameters, retrying it with consistency levels
+    /// from `fallback` while the coordinator reports too few alive replicas. The response tells
+    /// which consistency the query succeeded with, if it was downgraded.
+    async fn exec_with_fallback(
+        &self,
+        prepared: &PreparedQuery,
+        query_parameters: QueryParams,
+        fallback: &ConsistencyFallback,
+    ) -> error::Result<QueryResponse<ResponseBody>> {
+        with_consistency_fallback(fallback, |consistency| {
+            let mut query_parameters = query_parameters.clone();
+            if let Some(consistency) = consistency {
+                query_parameters.consistency = consistency;
+            }
+
+            self.exec_with_params(prepared, query_parameters)
+        })
+        .await
+    }
+
+    /// Executes a prepared query with given values and converts all returned rows into `R`.
+    /// Returns an error if the response does not contain rows.
+    async fn exec_rows<R, V>(&self, prepared: &PreparedQuery, values: V) -> error::Result<Vec<R>>
+    where
+        R: TryFromRow + Send,
+        V: Into<QueryValues> + Sync + Send,
+    {
+        self.exec_rows_with_frame(prepared, values)
+            .await
+            .map(WithFrame::into_value)
+    }
+
+    /// Executes a prepared query like `exec_rows`, additionally returning the response frame.
+    async fn exec_rows_with_frame<R, V>(
+        &self,
+        prepared: &PreparedQuery,
+        values: V,
+    ) -> error::Result<WithFrame<Vec<R>>>
+    where
+        R: TryFromRow + Send,
+        V: Into<QueryValues> + Sync + Send,
+    {
+        let frame = self.exec_with_values(prepared, values).await?;
+        Ok(WithFrame {
+            value: session_typed_rows_of(&frame, self.hooks().type_registry.as_ref())?,
+            frame,
+        })
+    }
+
+    /// Executes a prepared query with given values and converts the first returned row into
+    /// `R`, if there is any.
+    async fn exec_first<R, V>(
+        &self,
+        prepared: &PreparedQuery,
+        values: V,
+    ) -> error::Result<Option<R>>
+    where
+        R: TryFromRow + Send,
+        V: Into<QueryValues> + Sync + Send,
+    {
+        self.exec_first_with_frame(prepared, values)
+            .await
+            .map(WithFrame::into_value)
+    }
+
+    /// Executes a prepared query like `exec_first`, additionally returning the response frame.
+    async fn exec_first_with_frame<R, V>(
+        &self,
+        prepared: &PreparedQuery,
+        values: V,
+    ) -> error::Result<WithFrame<Option<R>>>
+    where
+        R: TryFromRow + Send,
+        V: Into<QueryValues> + Sync + Send,
+    {
+        let frame = self.exec_with_values(prepared, values).await?;
+        let value = session_rows_of(&frame, self.hooks().type_registry.as_ref())?
+            .into_iter()
+            .next()
+            .map(R::try_from_row)
+            .transpose()?;
+
+        Ok(WithFrame { value, frame })
+    }
+}
+
+/// Stores new id of result metadata of a prepared query if the node reported changed metadata,
+/// so following executions send the new id.
+fn update_result_metadata_id(prepared: &PreparedQuery, frame: &Frame) {
+    let new_metadata_id = frame
+        .get_body()
+        .ok()
+        .and_then(|body| body.as_rows_metadata())
+        .and_then(|metadata| metadata.new_metadata_id);
+
+    if let Some(new_metadata_id) = new_metadata_id {
+        *prepared
+            .result_metadata_id
+            .write()
+            .expect("Cannot write result metadata id!") = Some(new_metadata_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Opcode, ProtocolVersion, Version};
+    use crate::types::CBytesShort;
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn changed_result_metadata_id() {
+        let prepared = PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Arc::new(RwLock::new(Some(CBytesShort::new(vec![1])))),
+            query: "SELECT * FROM t".into(),
+            keyspace: Default::default(),
+            routing: Default::default(),
+        };
+
+        // rows result with metadata changed flag, no columns and no rows
+        let frame = Frame::new(
+            Version::Response,
+            vec![],
+            Opcode::Result,
+            vec![0, 0, 0, 2, 0, 0, 0, 8, 0, 0, 0, 0, 0, 1, 7, 0, 0, 0, 0],
+            None,
+            vec![],
+        )
+        .with_protocol_version(ProtocolVersion::V5);
+        update_result_metadata_id(&prepared, &frame);
+
+        assert_eq!(
+            *prepared.result_metadata_id.read().unwrap(),
+            Some(CBytesShort::new(vec![7]))
+        );
+    }
+
+    #[cfg(not(feature = "v3"))]
+    #[tokio::test]
+    async fn prepared_keyspace_check() {
+        use crate::authenticators::NoneAuthenticator;
+        use crate::cluster::test_node::{void, TestNode};
+        use crate::cluster::{DirectSession, NodeTcpConfigBuilder};
+        use crate::compression::Compression;
+        use crate::query::{QueryDefaults, QueryExecutor};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static UNPREPARED: AtomicBool = AtomicBool::new(false);
+
+        fn answer(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            match opcode {
+                Opcode::Prepare => Some((
+                    Opcode::Result,
+                    vec![
+                        0, 0, 0, 4, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0,
+                        0, 0,
+                    ],
+                )),
+                Opcode::Execute if UNPREPARED.swap(false, Ordering::SeqCst) => {
+                    Some((Opcode::Error, vec![0, 0, 0x25, 0, 0, 0, 0, 1, 2]))
+                }
+                Opcode::Query if body[4..].starts_with(b"USE ks") => {
+                    Some((Opcode::Result, vec![0, 0, 0, 3, 0, 2, b'k', b's']))
+                }
+                _ => void(opcode, body),
+            }
+        }
+
+        let node = TestNode::start_with(answer).await;
+        let unchecked = DirectSession::connect(node.addr).await.unwrap();
+        let node_config = NodeTcpConfigBuilder::new(node.addr, Arc::new(NoneAuthenticator)).build();
+        let query_defaults = QueryDefaults {
+            check_prepared_keyspace: true,
+            ..Default::default()
+        };
+        let checked =
+            DirectSession::connect_with_config(node_config, Compression::None, query_defaults)
+                .await
+                .unwrap();
+
+        // the keyspace is recorded even if it is not checked
+        unchecked.query("USE ks").await.unwrap();
+        let in_ks = unchecked.prepare("SELECT * FROM t").await.unwrap();
+        assert_eq!(in_ks.keyspace().as_deref(), Some("ks"));
+        assert!(unchecked.exec(&in_ks).await.is_ok());
+        assert!(checked.exec(&in_ks).await.is_err());
+
+        // statements prepared without a keyspace are checked too
+        let without_ks = checked.prepare("SELECT * FROM t").await.unwrap();
+        assert_eq!(without_ks.keyspace(), None);
+        assert!(checked.exec(&without_ks).await.is_ok());
+        checked.query("USE ks").await.unwrap();
+        assert!(checked.exec(&without_ks).await.is_err());
+        assert!(checked.exec(&in_ks).await.is_ok());
+
+        // re-preparing records the keyspace of the connection
+        UNPREPARED.store(true, Ordering::SeqCst);
+        assert!(unchecked.exec(&without_ks).await.is_ok());
+        assert_eq!(without_ks.keyspace().as_deref(), Some("ks"));
+        assert!(checked.exec(&without_ks).await.is_ok());
+    }
 }
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::query::normalize_query;
+
+/// Stable fingerprint of a statement: a 64-bit FNV-1a hash of its normalized text (see
+/// `normalize_query`), so statements differing only in literal values or whitespace share
+/// a fingerprint. It does not depend on the process, platform or driver version, which makes it
+/// suitable as a low-cardinality label of metrics and traces instead of full query strings.
+/// Displayed as 16 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QueryFingerprint(u64);
+
+impl QueryFingerprint {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    /// Computes the fingerprint of given query text.
+    pub fn of(query: &str) -> Self {
+        let hash = normalize_query(query)
+            .bytes()
+            .fold(Self::OFFSET_BASIS, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(Self::PRIME)
+            });
+
+        QueryFingerprint(hash)
+    }
+
+    /// Returns the fingerprint as a number.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for QueryFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_of_normalized_text() {
+        // reference values of FNV-1a 64
+        assert_eq!(QueryFingerprint::of("").as_u64(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(QueryFingerprint::of("a").as_u64(), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(QueryFingerprint::of("a").to_string(), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn ignores_literals_and_whitespace() {
+        assert_eq!(
+            QueryFingerprint::of("SELECT * FROM t WHERE id = 1"),
+            QueryFingerprint::of(" SELECT *  FROM t\nWHERE id = 42 ")
+        );
+        assert_eq!(
+            QueryFingerprint::of("SELECT * FROM t WHERE name = 'a'"),
+            QueryFingerprint::of("SELECT * FROM t WHERE name = ?")
+        );
+        assert_ne!(
+            QueryFingerprint::of("SELECT * FROM t WHERE id = 1"),
+            QueryFingerprint::of("SELECT * FROM u WHERE id = 1")
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+use crate::consistency::Consistency;
+use crate::error;
+use crate::query::{frame_rows, QueryExecutor, QueryParamsBuilder, QueryValues};
+use crate::transport::CDRSTransport;
+use crate::types::rows::Row;
+use crate::types::value::Value;
+use crate::types::IntoRustByName;
+
+const MIGRATIONS_TABLE: &str = "schema_migrations";
+const APPLIED: &str = "applied";
+const RUNNING: &str = "running";
+
+const AGREEMENT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const RUNNER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Versioned schema changes of a keyspace, applied in order of their versions by `run`.
+/// Applied versions are recorded in the `schema_migrations` table of the keyspace, so every
+/// migration is applied once, also when several instances of a service run migrations at the
+/// same time: a runner claims a version with a lightweight transaction before applying it and
+/// others wait until the version is applied. The cluster is awaited to agree on the schema
+/// after every statement.
+///
+/// A migration which fails is released, so the next run applies it again from its first
+/// statement. Statements should therefore be idempotent, e.g. `CREATE TABLE IF NOT EXISTS`.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    keyspace: String,
+    replication: Option<String>,
+    migrations: Vec<Migration>,
+    agreement_timeout: Option<Duration>,
+    runner_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct Migration {
+    version: i64,
+    name: String,
+    statements: Vec<String>,
+}
+
+enum Claim {
+    Acquired,
+    Applied,
+    Running,
+}
+
+impl Migrations {
+    const DEFAULT_AGREEMENT_TIMEOUT: Duration = Duration::from_secs(10);
+    const DEFAULT_RUNNER_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Creates migrations of given keyspace.
+    pub fn new(keyspace: &str) -> Self {
+        Migrations {
+            keyspace: keyspace.to_string(),
+            replication: None,
+            migrations: vec![],
+            agreement_timeout: None,
+            runner_timeout: None,
+        }
+    }
+
+    /// Creates the keyspace, if it doesn't exist, with given replication options, e.g.
+    /// `{'class': 'NetworkTopologyStrategy', 'dc1': 3}`.
+    pub fn create_keyspace(mut self, replication: &str) -> Self {
+        self.replication = Some(replication.to_string());
+        self
+    }
+
+    /// Adds a migration with given version, name and CQL script. Statements of the script are
+    /// separated by semicolons. Versions need to be added in ascending order.
+    pub fn add(mut self, version: i64, name: &str, script: &str) -> Self {
+        self.migrations.push(Migration {
+            version,
+            name: name.to_string(),
+            statements: split_script(script),
+        });
+        self
+    }
+
+    /// Sets how long the cluster is awaited to agree on the schema after each statement.
+    /// Defaults to 10 seconds.
+    pub fn schema_agreement_timeout(mut self, timeout: Duration) -> Self {
+        self.agreement_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a migration being applied by another runner is awaited.
+    /// Defaults to 60 seconds.
+    pub fn runner_timeout(mut self, timeout: Duration) -> Self {
+        self.runner_timeout = Some(timeout);
+        self
+    }
+
+    /// Applies migrations which have not been applied yet, in order of their versions. Returns
+    /// versions applied by this run.
+    pub async fn run<S, T, M>(&self, session: &S) -> error::Result<Vec<i64>>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        if self
+            .migrations
+            .windows(2)
+            .any(|pair| pair[0].version >= pair[1].version)
+        {
+            return Err("Migration versions need to be unique and ascending".into());
+        }
+
+        if let Some(replication) = &self.replication {
+            self.apply(
+                session,
+                &format!(
+                    "CREATE KEYSPACE IF NOT EXISTS {} WITH replication = {}",
+                    self.keyspace, replication
+                ),
+            )
+            .await?;
+        }
+
+        self.apply(
+            session,
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (version bigint PRIMARY KEY, name text, \
+                 state text, applied_at timestamp)",
+                self.table()
+            ),
+        )
+        .await?;
+
+        let mut applied = vec![];
+        for migration in &self.migrations {
+            loop {
+                match self.claim(session, migration).await? {
+                    Claim::Acquired => {
+                        self.apply_claimed(session, migration).await?;
+                        applied.push(migration.version);
+                        break;
+                    }
+                    Claim::Applied => break,
+                    Claim::Running => {
+                        if self.await_runner(session, migration).await? {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(applied)
+    }
+
+    fn table(&self) -> String {
+        format!("{}.{}", self.keyspace, MIGRATIONS_TABLE)
+    }
+
+    async fn apply<S, T, M>(&self, session: &S, statement: &str) -> error::Result<()>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        session.query(statement).await?;
+        await_schema_agreement(
+            session,
+            self.agreement_timeout
+                .unwrap_or(Self::DEFAULT_AGREEMENT_TIMEOUT),
+        )
+        .await
+    }
+
+    async fn claim<S, T, M>(&self, session: &S, migration: &Migration) -> error::Result<Claim>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let row = lwt(
+            session,
+            format!(
+                "INSERT INTO {} (version, name, state) VALUES (?, ?, '{}') IF NOT EXISTS",
+                self.table(),
+                RUNNING
+            ),
+            vec![
+                Value::from(migration.version),
+                Value::from(migration.name.as_str()),
+            ],
+        )
+        .await?;
+
+        let applied: bool = row.get_r_by_name("[applied]")?;
+        if applied {
+            return Ok(Claim::Acquired);
+        }
+
+        let state: Option<String> = row.get_by_name("state")?;
+        Ok(match state.as_deref() {
+            Some(APPLIED) => Claim::Applied,
+            _ => Claim::Running,
+        })
+    }
+
+    async fn apply_claimed<S, T, M>(&self, session: &S, migration: &Migration) -> error::Result<()>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        for statement in &migration.statements {
+            if let Err(error) = self.apply(session, statement).await {
+                let released = lwt(
+                    session,
+                    format!(
+                        "DELETE FROM {} WHERE version = ? IF state = '{}'",
+                        self.table(),
+                        RUNNING
+                    ),
+                    vec![Value::from(migration.version)],
+                )
+                .await;
+                if let Err(release_error) = released {
+                    error!(
+                        "Cannot release migration {} after failure: {}",
+                        migration.version, release_error
+                    );
+                }
+
+                return Err(error);
+            }
+        }
+
+        let row = lwt(
+            session,
+            format!(
+                "UPDATE {} SET state = '{}', applied_at = toTimestamp(now()) WHERE version = ? \
+                 IF state = '{}'",
+                self.table(),
+                APPLIED,
+                RUNNING
+            ),
+            vec![Value::from(migration.version)],
+        )
+        .await?;
+
+        let applied: bool = row.get_r_by_name("[applied]")?;
+        if applied {
+            Ok(())
+        } else {
+            Err(format!(
+                "Migration {} has been applied, but its claim was lost",
+                migration.version
+            )
+            .into())
+        }
+    }
+
+    /// Waits until another runner finishes given migration. Returns `true` if it has been
+    /// applied and `false` if the runner failed and released it.
+    async fn await_runner<S, T, M>(&self, session: &S, migration: &Migration) -> error::Result<bool>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let deadline = Instant::now() + self.runner_timeout.unwrap_or(Self::DEFAULT_RUNNER_TIMEOUT);
+
+        loop {
+            let params = QueryParamsBuilder::new()
+                .consistency(Consistency::Serial)
+                .values(QueryValues::SimpleValues(vec![Value::from(
+                    migration.version,
+                )]))
+                .finalize();
+            let rows = frame_rows(
+                session
+                    .query_with_params(
+                        format!("SELECT state FROM {} WHERE version = ?", self.table()),
+                        params,
+                    )
+                    .await?,
+            )?;
+
+            let state: Option<String> = match rows.first() {
+                Some(row) => row.get_by_name("state")?,
+                None => return Ok(false),
+            };
+            if state.as_deref() == Some(APPLIED) {
+                return Ok(true);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Migration {} is still being applied by another runner",
+                    migration.version
+                )
+                .into());
+            }
+
+            sleep(RUNNER_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Waits until all nodes known to the cluster agree on the schema version, e.g. after DDL
+/// statements, failing after given timeout. Nodes which are down are not waited for.
+pub async fn await_schema_agreement<S, T, M>(session: &S, timeout: Duration) -> error::Result<()>
+where
+    S: QueryExecutor<T, M>,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let mut rows = frame_rows(
+            session
+                .query("SELECT schema_version FROM system.local")
+                .await?,
+        )?;
+        rows.extend(frame_rows(
+            session
+                .query("SELECT schema_version FROM system.peers")
+                .await?,
+        )?);
+
+        let versions = rows
+            .iter()
+            .map(|row| row.get_by_name("schema_version"))
+            .collect::<error::Result<Vec<Option<Uuid>>>>()?;
+        if versions.into_iter().flatten().collect::<HashSet<_>>().len() <= 1 {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err("Schema agreement has not been reached".into());
+        }
+
+        sleep(AGREEMENT_POLL_INTERVAL).await;
+    }
+}
+
+async fn lwt<S, T, M>(session: &S, query: String, values: Vec<Value>) -> error::Result<Row>
+where
+    S: QueryExecutor<T, M>,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let params = QueryParamsBuilder::new()
+        .consistency(Consistency::Quorum)
+        .serial_consistency(Consistency::Serial)
+        .values(QueryValues::SimpleValues(values))
+        .finalize();
+
+    frame_rows(session.query_with_params(query, params).await?)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Lightweight transaction should yield a row".into())
+}
+
+/// Splits a CQL script into statements separated by semicolons, ignoring semicolons in string
+/// literals, quoted identifiers and comments. Comments are removed.
+fn split_script(script: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut statement = String::new();
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => statements.push(std::mem::take(&mut statement)),
+            '\'' | '"' => {
+                statement.push(c);
+                for quoted in chars.by_ref() {
+                    statement.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                statement.push_str("$$");
+                chars.next();
+                while let Some(quoted) = chars.next() {
+                    statement.push(quoted);
+                    if quoted == '$' && chars.peek() == Some(&'$') {
+                        statement.push('$');
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            '-' | '/' if chars.peek() == Some(&c) => {
+                for comment in chars.by_ref() {
+                    if comment == '\n' {
+                        statement.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                for comment in chars.by_ref() {
+                    if previous == Some('*') && comment == '/' {
+                        break;
+                    }
+                    previous = Some(comment);
+                }
+                statement.push(' ');
+            }
+            _ => statement.push(c),
+        }
+    }
+    statements.push(statement);
+
+    statements
+        .into_iter()
+        .map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::sync::{Arc, Mutex as SyncMutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::cluster::DirectSession;
+    use crate::frame::{AsByte, Opcode, Version};
+
+    const SCHEMA_VERSION: [u8; 16] = [7; 16];
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    fn rows_body(columns: &[(&str, u16)], row: Vec<Option<Vec<u8>>>) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 2, 0, 0, 0, 1];
+        body.extend_from_slice(&(columns.len() as i32).to_be_bytes());
+        body.extend(string("ks"));
+        body.extend(string("t"));
+        for (name, type_id) in columns {
+            body.extend(string(name));
+            body.extend_from_slice(&type_id.to_be_bytes());
+        }
+
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        for cell in row {
+            match cell {
+                Some(value) => {
+                    body.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                    body.extend(value);
+                }
+                None => body.extend_from_slice(&(-1_i32).to_be_bytes()),
+            }
+        }
+
+        body
+    }
+
+    /// Answers queries of migrations, with version 1 already applied by another runner.
+    fn answer(query: &str, body: &[u8]) -> (Opcode, Vec<u8>) {
+        if query.contains("schema_version") {
+            let row = vec![Some(SCHEMA_VERSION.to_vec())];
+            return (
+                Opcode::Result,
+                rows_body(&[("schema_version", 0x000C)], row),
+            );
+        }
+
+        let applied = if query.starts_with("INSERT") {
+            // the version is the first bound value, after consistency, flags and value count
+            let offset = 4 + query.len() + 3 + 2 + 4;
+            let version = i64::from_be_bytes(body[offset..offset + 8].try_into().unwrap());
+            version != 1
+        } else if query.starts_with("UPDATE") {
+            true
+        } else {
+            return (Opcode::Result, vec![0, 0, 0, 1]);
+        };
+
+        let row = vec![
+            Some(vec![applied as u8]),
+            Some(if applied { vec![] } else { b"applied".to_vec() }),
+        ];
+        (
+            Opcode::Result,
+            rows_body(&[("[applied]", 0x0004), ("state", 0x000D)], row),
+        )
+    }
+
+    async fn serve(listener: TcpListener, queries: Arc<SyncMutex<Vec<String>>>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut header = [0; 9];
+
+        while stream.read_exact(&mut header).await.is_ok() {
+            let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+            let mut body = vec![0; length as usize];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let (opcode, body) = match Opcode::from(header[4]) {
+                Opcode::Startup => (Opcode::Ready, vec![]),
+                Opcode::Options => (Opcode::Supported, vec![0, 0]),
+                _ => {
+                    let query_len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+                    let query =
+                        String::from_utf8(body[4..4 + query_len as usize].to_vec()).unwrap();
+                    queries.lock().unwrap().push(query.clone());
+                    answer(&query, &body)
+                }
+            };
+
+            let mut response = vec![
+                Version::Response.as_byte(),
+                0,
+                header[2],
+                header[3],
+                opcode.as_byte(),
+            ];
+            response.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            response.extend_from_slice(&body);
+            stream.write_all(&response).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_pending_migrations() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let queries = Arc::new(SyncMutex::new(vec![]));
+        tokio::spawn(serve(listener, queries.clone()));
+
+        let session = DirectSession::connect(addr).await.unwrap();
+
+        let unordered = Migrations::new("ks")
+            .add(2, "second", "CREATE TABLE ks.b (id int PRIMARY KEY)")
+            .add(1, "first", "CREATE TABLE ks.a (id int PRIMARY KEY)");
+        assert!(unordered.run(&session).await.is_err());
+        assert!(queries.lock().unwrap().is_empty());
+
+        let applied = Migrations::new("ks")
+            .add(1, "first", "CREATE TABLE ks.a (id int PRIMARY KEY)")
+            .add(
+                2,
+                "second",
+                "CREATE TABLE ks.b (id int PRIMARY KEY); CREATE INDEX ON ks.b (id);",
+            )
+            .run(&session)
+            .await
+            .unwrap();
+        assert_eq!(applied, vec![2]);
+
+        let queries = queries.lock().unwrap();
+        let ddl: Vec<&String> = queries
+            .iter()
+            .filter(|query| query.starts_with("CREATE"))
+            .collect();
+        assert_eq!(
+            ddl,
+            vec![
+                "CREATE TABLE IF NOT EXISTS ks.schema_migrations (version bigint PRIMARY KEY, \
+                 name text, state text, applied_at timestamp)",
+                "CREATE TABLE ks.b (id int PRIMARY KEY)",
+                "CREATE INDEX ON ks.b (id)",
+            ]
+        );
+        assert_eq!(
+            queries
+                .iter()
+                .filter(|query| query.starts_with("UPDATE"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn splits_scripts() {
+        assert_eq!(
+            split_script(
+                "-- users\nCREATE TABLE t (id int PRIMARY KEY, \"a;b\" text);\n\
+                 INSERT INTO t (id, \"a;b\") VALUES (1, 'x;''y'); /* c; */ \
+                 CREATE FUNCTION f() RETURNS NULL ON NULL INPUT RETURNS int LANGUAGE java \
+                 AS $$ return 1; $$;; // done"
+            ),
+            vec![
+                "CREATE TABLE t (id int PRIMARY KEY, \"a;b\" text)",
+                "INSERT INTO t (id, \"a;b\") VALUES (1, 'x;''y')",
+                "CREATE FUNCTION f() RETURNS NULL ON NULL INPUT RETURNS int LANGUAGE java \
+                 AS $$ return 1; $$",
+            ]
+        );
+    }
+}
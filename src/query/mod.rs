@@ -1,30 +1,131 @@
+mod ann_query;
+#[cfg(feature = "runtime")]
 mod batch_executor;
 mod batch_query_builder;
+#[cfg(feature = "runtime")]
+mod bulk_writer;
+mod consistency_fallback;
+#[cfg(feature = "continuous-paging")]
+mod continuous_paging;
+mod coordinator_listener;
+mod counter_update;
+mod debug_cql;
+#[cfg(feature = "runtime")]
 mod exec_executor;
+mod fingerprint;
+#[cfg(feature = "runtime")]
+mod migrations;
+mod prepare_advisor;
+#[cfg(feature = "runtime")]
 mod prepare_executor;
+mod prepared_cache;
 mod prepared_query;
+mod query_defaults;
+#[cfg(feature = "runtime")]
 mod query_executor;
 mod query_flags;
 mod query_params;
 mod query_params_builder;
+mod query_response;
 mod query_values;
+mod routing_key;
+mod statement;
+mod statement_description;
+#[cfg(feature = "runtime")]
+mod statement_executor;
+mod statement_interceptor;
+#[cfg(feature = "runtime")]
+mod table_export;
+mod timestamp_generator;
+mod tombstone_warning;
+mod tracing_sampling;
+#[cfg(feature = "runtime")]
 mod utils;
+mod warning_log;
+mod write_options;
 
+pub use crate::query::ann_query::AnnQuery;
+#[cfg(feature = "runtime")]
 pub use crate::query::batch_executor::BatchExecutor;
-pub use crate::query::batch_query_builder::{BatchQueryBuilder, QueryBatch};
+pub use crate::query::batch_query_builder::{BatchQueryBuilder, PartitionCheck, QueryBatch};
+#[cfg(feature = "runtime")]
+pub use crate::query::bulk_writer::{BulkWriteFailure, BulkWriteResult, BulkWriter};
+pub use crate::query::consistency_fallback::ConsistencyFallback;
+#[cfg(feature = "continuous-paging")]
+pub use crate::query::continuous_paging::{
+    ContinuousPages, ContinuousPagingOptions, CONTINUOUS_PAGING_PAYLOAD_KEY,
+};
+#[cfg(feature = "runtime")]
+pub(crate) use crate::query::coordinator_listener::notify_coordinator;
+pub use crate::query::coordinator_listener::CoordinatorListener;
+pub use crate::query::counter_update::CounterUpdate;
+pub use crate::query::debug_cql::debug_cql;
+#[cfg(feature = "runtime")]
 pub use crate::query::exec_executor::ExecExecutor;
+pub use crate::query::fingerprint::QueryFingerprint;
+#[cfg(feature = "runtime")]
+pub use crate::query::migrations::{await_schema_agreement, Migrations};
+pub use crate::query::prepare_advisor::{
+    normalize_query, PrepareAdvisor, PrepareSuggestion, PrepareSuggestionListener,
+};
+#[cfg(feature = "runtime")]
 pub use crate::query::prepare_executor::PrepareExecutor;
+pub use crate::query::prepared_cache::{CacheStats, PreparedCache};
 pub use crate::query::prepared_query::PreparedQuery;
+#[cfg(test)]
+pub(crate) use crate::query::prepared_query::RoutingMetadata;
+pub use crate::query::query_defaults::QueryDefaults;
+#[cfg(feature = "runtime")]
 pub use crate::query::query_executor::QueryExecutor;
-pub use crate::query::query_flags::QueryFlags;
-pub use crate::query::query_params::QueryParams;
+pub use crate::query::query_flags::{QueryFlagSet, QueryFlags};
+pub use crate::query::query_params::{QueryParams, PROXY_EXECUTE_PAYLOAD_KEY};
 pub use crate::query::query_params_builder::QueryParamsBuilder;
+pub use crate::query::query_response::{QueryResponse, WithFrame};
 pub use crate::query::query_values::QueryValues;
+pub use crate::query::routing_key::RoutingKey;
+pub use crate::query::statement::Statement;
+pub(crate) use crate::query::statement_description::describe_columns;
+pub use crate::query::statement_description::{ColumnDescription, StatementDescription};
+#[cfg(feature = "runtime")]
+pub use crate::query::statement_executor::StatementExecutor;
+pub use crate::query::statement_interceptor::StatementInterceptor;
+#[cfg(feature = "runtime")]
+pub use crate::query::table_export::{import_rows, ExportedRows, TableExport};
+pub use crate::query::timestamp_generator::TimestampGenerator;
+pub use crate::query::tombstone_warning::TombstoneWarning;
+pub(crate) use crate::query::tracing_sampling::notify_trace;
+#[cfg(feature = "runtime")]
+pub(crate) use crate::query::tracing_sampling::should_trace;
+pub use crate::query::tracing_sampling::TracingListener;
+#[cfg(feature = "runtime")]
+pub(crate) use crate::query::utils::frame_rows;
+#[cfg(feature = "unstable-raw-frames")]
+pub(crate) use crate::query::utils::send_frame;
+#[cfg(feature = "runtime")]
+pub use crate::query::utils::with_deadline;
+#[cfg(feature = "runtime")]
+pub(crate) use crate::query::utils::with_type_registry;
+pub use crate::query::warning_log::WarningLog;
+pub use crate::query::write_options::{Ttl, Using, WriteTimestamp, MAX_TTL};
+pub use crate::token::TokenRange;
 
 /// Structure that represents CQL query and parameters which will be applied during
 /// its execution
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Query {
     pub query: String,
     pub params: QueryParams,
 }
+
+impl Query {
+    /// Renders the query with its values inlined as literals. For debugging only, see
+    /// `debug_cql`.
+    pub fn to_debug_cql(&self) -> String {
+        debug_cql(&self.query, self.params.values.as_ref())
+    }
+
+    /// Returns the fingerprint of the query text, e.g. to label metrics of the query.
+    pub fn fingerprint(&self) -> QueryFingerprint {
+        QueryFingerprint::of(&self.query)
+    }
+}
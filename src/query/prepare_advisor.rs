@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Suggestion to prepare a simple query, reported by a `PrepareAdvisor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrepareSuggestion {
+    /// Text of a query, typically with inlined literal values, is longer than the maximum size.
+    LargeQuery { query: String, size: usize },
+    /// Query was executed more times than the threshold. Given query text is normalized, i.e.
+    /// with literal values replaced by `?`.
+    RepeatedQuery { query: String, executions: u64 },
+}
+
+/// Hook notified about simple queries which should be prepared.
+pub trait PrepareSuggestionListener: Send + Sync {
+    /// Called with a suggestion when a query crosses one of the thresholds of the advisor.
+    fn on_suggestion(&self, suggestion: PrepareSuggestion);
+}
+
+impl<F: Fn(PrepareSuggestion) + Send + Sync> PrepareSuggestionListener for F {
+    fn on_suggestion(&self, suggestion: PrepareSuggestion) {
+        self(suggestion)
+    }
+}
+
+impl fmt::Debug for dyn PrepareSuggestionListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrepareSuggestionListener")
+    }
+}
+
+/// Opt-in observer of simple queries suggesting to prepare ones which are large or executed
+/// repeatedly, e.g. to drive adoption of prepared statements across teams sharing a cluster.
+/// Repeated queries are recognized by their normalized text, so queries differing only in
+/// literal values count as one. Each repeated query is reported once.
+///
+/// Set as `SessionHooks::prepare_advisor`, it observes queries sent with `query*` methods.
+/// Both checks are disabled until their thresholds are set.
+#[derive(Debug)]
+pub struct PrepareAdvisor {
+    listener: Arc<dyn PrepareSuggestionListener>,
+    max_query_size: Option<usize>,
+    repeat_threshold: Option<u64>,
+    executions: Mutex<HashMap<String, u64>>,
+}
+
+impl PrepareAdvisor {
+    /// Number of distinct normalized queries counted before the counts are reset.
+    const MAX_TRACKED_QUERIES: usize = 10_000;
+
+    pub fn new(listener: Arc<dyn PrepareSuggestionListener>) -> Self {
+        PrepareAdvisor {
+            listener,
+            max_query_size: None,
+            repeat_threshold: None,
+            executions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Suggests preparing queries with text longer than given number of bytes.
+    pub fn max_query_size(mut self, bytes: usize) -> Self {
+        self.max_query_size = Some(bytes);
+        self
+    }
+
+    /// Suggests preparing queries executed more than given number of times.
+    pub fn repeat_threshold(mut self, executions: u64) -> Self {
+        self.repeat_threshold = Some(executions);
+        self
+    }
+
+    /// Records execution of a simple query, notifying the listener if it crossed a threshold.
+    pub(crate) fn observe(&self, query: &str) {
+        if let Some(max_query_size) = self.max_query_size {
+            if query.len() > max_query_size {
+                self.listener.on_suggestion(PrepareSuggestion::LargeQuery {
+                    query: query.to_string(),
+                    size: query.len(),
+                });
+            }
+        }
+
+        if let Some(repeat_threshold) = self.repeat_threshold {
+            let query = normalize_query(query);
+            let executions = {
+                let mut counts = self.executions.lock().unwrap();
+                if counts.len() >= Self::MAX_TRACKED_QUERIES && !counts.contains_key(&query) {
+                    counts.clear();
+                }
+
+                let executions = counts.entry(query.clone()).or_insert(0);
+                *executions += 1;
+                *executions
+            };
+
+            if executions == repeat_threshold + 1 {
+                self.listener
+                    .on_suggestion(PrepareSuggestion::RepeatedQuery { query, executions });
+            }
+        }
+    }
+}
+
+/// Returns query text with string, numeric, UUID and blob literals replaced by `?` and
+/// whitespace collapsed, e.g. `SELECT * FROM t WHERE id = ?` for
+/// `SELECT * FROM t  WHERE id = 42`.
+pub fn normalize_query(query: &str) -> String {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut normalized = String::with_capacity(query.len());
+    let mut chars = query.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        let previous = normalized.chars().next_back();
+
+        if c == '\'' {
+            // quotes are escaped by doubling them
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                    }
+                    Some('\'') | None => break,
+                    Some(_) => {}
+                }
+            }
+            normalized.push('?');
+        } else if c.is_ascii_digit() && !previous.is_some_and(is_identifier) {
+            while chars
+                .next_if(|&c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+                .is_some()
+            {}
+            normalized.push('?');
+        } else if c.is_whitespace() {
+            if previous != Some(' ') {
+                normalized.push(' ');
+            }
+        } else {
+            normalized.push(c);
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording_advisor() -> (PrepareAdvisor, Arc<Mutex<Vec<PrepareSuggestion>>>) {
+        let suggestions = Arc::new(Mutex::new(vec![]));
+        let recorded = suggestions.clone();
+        let listener: Arc<dyn PrepareSuggestionListener> =
+            Arc::new(move |suggestion| recorded.lock().unwrap().push(suggestion));
+
+        (PrepareAdvisor::new(listener), suggestions)
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            normalize_query("SELECT * FROM ks.t1  WHERE id = 42 AND name = 'O''Brien'\n"),
+            "SELECT * FROM ks.t1 WHERE id = ? AND name = ?"
+        );
+        assert_eq!(
+            normalize_query(
+                "INSERT INTO t (id, v, b) VALUES (550e8400-e29b-41d4-a716-446655440000, -1.5, 0xCAFE)"
+            ),
+            "INSERT INTO t (id, v, b) VALUES (?, -?, ?)"
+        );
+    }
+
+    #[test]
+    fn large_query() {
+        let (advisor, suggestions) = recording_advisor();
+        let advisor = advisor.max_query_size(20);
+
+        advisor.observe("SELECT * FROM t");
+        advisor.observe("SELECT * FROM t WHERE id = 1");
+
+        assert_eq!(
+            *suggestions.lock().unwrap(),
+            vec![PrepareSuggestion::LargeQuery {
+                query: "SELECT * FROM t WHERE id = 1".into(),
+                size: 28,
+            }]
+        );
+    }
+
+    #[test]
+    fn repeated_query_reported_once() {
+        let (advisor, suggestions) = recording_advisor();
+        let advisor = advisor.repeat_threshold(2);
+
+        for id in 0..5 {
+            advisor.observe(&format!("SELECT * FROM t WHERE id = {}", id));
+        }
+        advisor.observe("SELECT * FROM other");
+
+        assert_eq!(
+            *suggestions.lock().unwrap(),
+            vec![PrepareSuggestion::RepeatedQuery {
+                query: "SELECT * FROM t WHERE id = ?".into(),
+                executions: 3,
+            }]
+        );
+    }
+}
@@ -1,22 +1,26 @@
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
-use crate::cluster::{GetCompressor, GetConnection, ResponseCache};
+use crate::cluster::{
+    GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks, ResponseCache,
+};
 use crate::error;
 use crate::frame::frame_result::BodyResResultPrepared;
-use crate::frame::{AsBytes, Frame};
-use crate::query::PreparedQuery;
+use crate::frame::Frame;
+use crate::query::prepared_query::RoutingMetadata;
+use crate::query::{PreparedQuery, StatementDescription};
 use crate::transport::CDRSTransport;
 
-use super::utils::{prepare_flags, send_frame};
+use super::utils::{prepare_flags, send_frame_in_keyspace, KeyspaceCheck};
 
 #[async_trait]
 pub trait PrepareExecutor<
     T: CDRSTransport + Unpin + 'static,
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
->: GetConnection<T, M> + GetCompressor + ResponseCache + Sync
+>:
+    GetConnection<T, M> + GetCompressor + GetQueryDefaults + GetSessionHooks + ResponseCache + Sync
 {
     /// It prepares a query for execution, along with query itself the
     /// method takes `with_tracing` and `with_warnings` flags to get
@@ -28,17 +32,41 @@ pub trait PrepareExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<BodyResResultPrepared> {
+        self.prepare_raw_in_keyspace(query, with_tracing, with_warnings)
+            .await
+            .map(|(prepared, _, _)| prepared)
+    }
+
+    /// It prepares a query like `prepare_raw_tw`, additionally returning the keyspace used by
+    /// the connection and whether the query is a lightweight transaction. The latter is only known for nodes supporting the
+    /// ScyllaDB `SCYLLA_LWT_ADD_METADATA_MARK` extension.
+    async fn prepare_raw_in_keyspace<Q: ToString + Sync + Send>(
+        &self,
+        query: Q,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<(BodyResResultPrepared, Option<String>, bool)> {
         let flags = prepare_flags(with_tracing, with_warnings);
 
-        let query_frame = Frame::new_req_prepare(query.to_string(), flags);
+        let query = query.to_string();
+        let build = |protocol_version| {
+            Ok(Frame::new_req_prepare_for(
+                query.clone(),
+                flags.clone(),
+                protocol_version,
+            ))
+        };
 
-        send_frame(self, query_frame.as_bytes(), query_frame.stream)
-            .await
-            .and_then(|response| response.get_body())
-            .map(|body| {
-                body.into_prepared()
-                    .expect("CDRS BUG: cannot convert frame into prepared")
-            })
+        let (response, keyspace) =
+            send_frame_in_keyspace(self, &build, KeyspaceCheck::Record).await?;
+        let lwt_metadata_mask = response.lwt_metadata_mask;
+        let prepared = response
+            .get_body()?
+            .into_prepared()
+            .expect("CDRS BUG: cannot convert frame into prepared");
+        let is_lwt = lwt_metadata_mask.is_some_and(|mask| prepared.metadata.flags & mask != 0);
+
+        Ok((prepared, keyspace, is_lwt))
     }
 
     /// It prepares query without additional tracing information and warnings.
@@ -61,11 +89,14 @@ pub trait PrepareExecutor<
         with_warnings: bool,
     ) -> error::Result<PreparedQuery> {
         let s = query.to_string();
-        self.prepare_raw_tw(query, with_tracing, with_warnings)
+        self.prepare_raw_in_keyspace(query, with_tracing, with_warnings)
             .await
-            .map(|x| PreparedQuery {
-                id: RwLock::new(x.id),
+            .map(|(x, keyspace, is_lwt)| PreparedQuery {
+                id: Arc::new(RwLock::new(x.id)),
+                result_metadata_id: Arc::new(RwLock::new(x.result_metadata_id)),
                 query: s,
+                keyspace: Arc::new(RwLock::new(keyspace)),
+                routing: RoutingMetadata::new(&x.metadata, is_lwt),
             })
     }
 
@@ -77,4 +108,39 @@ pub trait PrepareExecutor<
     {
         self.prepare_tw(query, false, false).await
     }
+
+    /// Prepares a query like `prepare`, reusing the statement from the session's prepared
+    /// cache if the same query text was already prepared. Without a cache it always prepares.
+    async fn prepare_cached<Q: ToString + Sync + Send>(
+        &self,
+        query: Q,
+    ) -> error::Result<PreparedQuery> {
+        let query = query.to_string();
+        let cache = match self.hooks().prepared_cache.as_ref() {
+            Some(cache) => cache,
+            None => return self.prepare(query).await,
+        };
+
+        if let Some(prepared) = cache.get(&query) {
+            return Ok(prepared);
+        }
+
+        let prepared = self.prepare(query.clone()).await?;
+        cache.insert(query, prepared.share());
+        Ok(prepared)
+    }
+
+    /// Prepares a query, bypassing the prepared cache, and returns types of its bind markers
+    /// and result columns, e.g. for code generators mapping a schema to Rust types.
+    async fn describe_statement<Q: ToString + Sync + Send>(
+        &self,
+        query: Q,
+    ) -> error::Result<StatementDescription> {
+        let query = query.to_string();
+        let (prepared, _, is_lwt) = self
+            .prepare_raw_in_keyspace(query.clone(), false, false)
+            .await?;
+
+        Ok(StatementDescription::new(query, &prepared, is_lwt))
+    }
 }
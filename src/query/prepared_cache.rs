@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::query::PreparedQuery;
+
+/// Snapshot of counters of a `PreparedCache`, e.g. to debug statement churn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Number of lookups which found a prepared statement.
+    pub hits: u64,
+    /// Number of lookups which had to prepare the statement.
+    pub misses: u64,
+    /// Number of statements evicted to make room for new ones.
+    pub evictions: u64,
+    /// Number of statements re-prepared after a node reported them as unprepared.
+    pub re_prepares: u64,
+    /// Number of currently cached statements.
+    pub size: usize,
+}
+
+impl CacheStats {
+    /// Returns the fraction of lookups which found a prepared statement, or 0 without lookups.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// Bounded cache of prepared statements keyed by query text, used by
+/// `PrepareExecutor::prepare_cached` when set as `SessionHooks::prepared_cache`. Least recently
+/// used statements are evicted once the cache is full. Concurrent lookups of a statement which
+/// is not cached yet may prepare it more than once.
+#[derive(Debug)]
+pub struct PreparedCache {
+    max_entries: usize,
+    entries: Mutex<CacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    re_prepares: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    statements: HashMap<String, (PreparedQuery, u64)>,
+    last_use: u64,
+}
+
+impl PreparedCache {
+    const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+    pub fn new() -> Self {
+        PreparedCache {
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            entries: Default::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            re_prepares: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the maximum number of cached statements.
+    /// Defaults to 1000.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Returns the statement prepared for given query text, counting a hit or a miss. The
+    /// returned handle shares the query id with the cached one, so re-preparing it updates the
+    /// cache.
+    pub fn get(&self, query: &str) -> Option<PreparedQuery> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.last_use += 1;
+        let last_use = entries.last_use;
+
+        match entries.statements.get_mut(query) {
+            Some((prepared, used)) => {
+                *used = last_use;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(prepared.share())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Caches a statement prepared for given query text, evicting the least recently used
+    /// statement if the cache is full.
+    pub fn insert(&self, query: String, prepared: PreparedQuery) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.last_use += 1;
+        let last_use = entries.last_use;
+
+        if entries.statements.len() >= self.max_entries && !entries.statements.contains_key(&query)
+        {
+            let least_recent = entries
+                .statements
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(query, _)| query.clone());
+            if let Some(least_recent) = least_recent {
+                entries.statements.remove(&least_recent);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        entries.statements.insert(query, (prepared, last_use));
+    }
+
+    /// Removes the statement of given query text, e.g. after its table was altered.
+    pub fn invalidate(&self, query: &str) {
+        self.entries.lock().unwrap().statements.remove(query);
+    }
+
+    /// Removes all cached statements. Counters are kept.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().statements.clear();
+    }
+
+    /// Returns current values of the counters.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            re_prepares: self.re_prepares.load(Ordering::Relaxed),
+            size: self.entries.lock().unwrap().statements.len(),
+        }
+    }
+
+    pub(crate) fn record_re_prepare(&self) {
+        self.re_prepares.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for PreparedCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::prepared_query::RoutingMetadata;
+    use crate::types::CBytesShort;
+    use std::sync::{Arc, RwLock};
+
+    fn prepared(query: &str) -> PreparedQuery {
+        PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(query.as_bytes().to_vec()))),
+            result_metadata_id: Arc::new(RwLock::new(None)),
+            query: query.to_string(),
+            keyspace: Default::default(),
+            routing: RoutingMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let cache = PreparedCache::new();
+
+        assert!(cache.get("SELECT * FROM t").is_none());
+        cache.insert("SELECT * FROM t".into(), prepared("SELECT * FROM t"));
+        assert_eq!(
+            cache.get("SELECT * FROM t").unwrap().query,
+            "SELECT * FROM t"
+        );
+        cache.record_re_prepare();
+
+        let stats = cache.stats();
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 0,
+                re_prepares: 1,
+                size: 1,
+            }
+        );
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = PreparedCache::new().max_entries(2);
+
+        cache.insert("a".into(), prepared("a"));
+        cache.insert("b".into(), prepared("b"));
+        cache.get("a");
+        cache.insert("c".into(), prepared("c"));
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().size, 2);
+    }
+}
@@ -1,23 +1,224 @@
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
+use crate::frame::frame_result::PreparedMetadata;
+use crate::query::{QueryFingerprint, QueryValues, RoutingKey};
+use crate::types::value::{Value, ValueType};
 use crate::types::CBytesShort;
 
+/// Handle of a prepared query. Clones are independent copies, while handles added to batches
+/// and returned by the prepared cache share the query id with the original, so when a query is
+/// re-prepared after a node reported it as unprepared, all of them use the new id. The same
+/// applies to the id of result metadata, updated when the node reports changed metadata, and
+/// to the keyspace the query was prepared in.
 #[derive(Debug)]
 pub struct PreparedQuery {
-    pub(crate) id: RwLock<CBytesShort>,
+    pub(crate) id: Arc<RwLock<CBytesShort>>,
+    pub(crate) result_metadata_id: Arc<RwLock<Option<CBytesShort>>>,
     pub(crate) query: String,
+    pub(crate) keyspace: Arc<RwLock<Option<String>>>,
+    pub(crate) routing: RoutingMetadata,
 }
 
 impl Clone for PreparedQuery {
     fn clone(&self) -> Self {
         PreparedQuery {
-            id: RwLock::new(
+            id: Arc::new(RwLock::new(
                 self.id
                     .read()
                     .expect("Cannot read prepared query id!")
                     .clone(),
-            ),
+            )),
+            result_metadata_id: Arc::new(RwLock::new(
+                self.result_metadata_id
+                    .read()
+                    .expect("Cannot read result metadata id!")
+                    .clone(),
+            )),
+            query: self.query.clone(),
+            keyspace: Arc::new(RwLock::new(self.keyspace())),
+            routing: self.routing.clone(),
+        }
+    }
+}
+
+impl PreparedQuery {
+    /// Returns a handle sharing the query id, the result metadata id and the keyspace with
+    /// this one, so re-preparing either of them updates both.
+    pub(crate) fn share(&self) -> Self {
+        PreparedQuery {
+            id: self.id.clone(),
+            result_metadata_id: self.result_metadata_id.clone(),
             query: self.query.clone(),
+            keyspace: self.keyspace.clone(),
+            routing: self.routing.clone(),
+        }
+    }
+
+    /// Returns the keyspace used by the connection the query was last prepared over, if any.
+    pub fn keyspace(&self) -> Option<String> {
+        self.keyspace
+            .read()
+            .expect("Cannot read prepared query keyspace!")
+            .clone()
+    }
+
+    /// Returns the fingerprint of the query text, e.g. to label metrics of executions.
+    pub fn fingerprint(&self) -> QueryFingerprint {
+        QueryFingerprint::of(&self.query)
+    }
+
+    /// Returns indexes of bound values which make up the partition key, in order of partition
+    /// key columns. Empty with protocol v3 or if the query does not bind the whole partition
+    /// key.
+    pub fn pk_indexes(&self) -> &[i16] {
+        &self.routing.pk_indexes
+    }
+
+    /// Returns `true` if the query is a lightweight transaction, e.g. `INSERT ... IF NOT
+    /// EXISTS`. Only known for queries prepared on ScyllaDB nodes supporting the
+    /// `SCYLLA_LWT_ADD_METADATA_MARK` extension, `false` otherwise. Token aware routing sends
+    /// them to the primary replica while it is up, so that concurrent transactions on
+    /// a partition don't contend across coordinators.
+    pub fn is_lwt(&self) -> bool {
+        self.routing.is_lwt
+    }
+
+    /// Computes the routing key of the partition addressed by given values, i.e. the serialized
+    /// partition key, whose token determines replicas of the partition (see `RoutingKey`). Returns `None` if the
+    /// query does not bind the whole partition key or any of its values is null or unset.
+    pub fn routing_key(&self, values: &QueryValues) -> Option<Vec<u8>> {
+        let components = self
+            .routing
+            .pk_indexes
+            .iter()
+            .zip(&self.routing.pk_names)
+            .map(|(index, name)| match values {
+                QueryValues::SimpleValues(values) => values.get(*index as usize),
+                QueryValues::NamedValues(values) => values.get(name),
+            })
+            .map(|value| match value {
+                Some(Value {
+                    body,
+                    value_type: ValueType::Normal(_),
+                }) => Some(body.as_slice()),
+                _ => None,
+            })
+            .collect::<Option<Vec<&[u8]>>>()?;
+
+        RoutingKey::from_components(&components).map(RoutingKey::into_bytes)
+    }
+
+    /// Returns the keyspace of the table the query reads or writes, used to look up replicas of
+    /// routing keys.
+    pub(crate) fn routing_keyspace(&self) -> Option<&str> {
+        self.routing.keyspace.as_deref()
+    }
+}
+
+/// Partition key metadata of a prepared query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct RoutingMetadata {
+    keyspace: Option<String>,
+    pk_indexes: Vec<i16>,
+    pk_names: Vec<String>,
+    is_lwt: bool,
+}
+
+impl RoutingMetadata {
+    pub fn new(metadata: &PreparedMetadata, is_lwt: bool) -> Self {
+        let keyspace = match &metadata.global_table_spec {
+            Some((keyspace, _)) => Some(keyspace.as_plain()),
+            None => metadata
+                .col_specs
+                .first()
+                .and_then(|col_spec| col_spec.ksname.as_ref())
+                .map(|keyspace| keyspace.as_plain()),
+        };
+
+        let pk_names = metadata
+            .pk_indexes
+            .iter()
+            .map(|index| {
+                metadata
+                    .col_specs
+                    .get(*index as usize)
+                    .map(|col_spec| col_spec.name.as_plain())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        RoutingMetadata {
+            keyspace,
+            pk_indexes: metadata.pk_indexes.clone(),
+            pk_names,
+            is_lwt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn prepared(pk_indexes: Vec<i16>, pk_names: Vec<&str>) -> PreparedQuery {
+        PreparedQuery {
+            id: Arc::new(RwLock::new(CBytesShort::new(vec![1]))),
+            result_metadata_id: Default::default(),
+            query: "SELECT * FROM t WHERE a = ? AND b = ? AND c = ?".into(),
+            keyspace: Default::default(),
+            routing: RoutingMetadata {
+                keyspace: Some("ks".into()),
+                pk_indexes,
+                pk_names: pk_names.into_iter().map(String::from).collect(),
+                is_lwt: false,
+            },
         }
     }
+
+    #[test]
+    fn single_column_routing_key() {
+        let prepared = prepared(vec![1], vec!["b"]);
+        let values = QueryValues::SimpleValues(vec![1i32.into(), 2i32.into(), 3i32.into()]);
+
+        assert_eq!(prepared.routing_key(&values), Some(vec![0, 0, 0, 2]));
+
+        let mut named = HashMap::new();
+        named.insert("b".to_string(), Value::from(2i32));
+        assert_eq!(
+            prepared.routing_key(&QueryValues::NamedValues(named)),
+            Some(vec![0, 0, 0, 2])
+        );
+    }
+
+    #[test]
+    fn composite_routing_key() {
+        let prepared = prepared(vec![2, 0], vec!["c", "a"]);
+        let values = QueryValues::SimpleValues(vec![1i32.into(), 2i32.into(), "x".into()]);
+
+        assert_eq!(
+            prepared.routing_key(&values),
+            Some(vec![0, 1, b'x', 0, 0, 4, 0, 0, 0, 1, 0])
+        );
+    }
+
+    #[test]
+    fn missing_routing_key() {
+        let values = QueryValues::SimpleValues(vec![Value::new_null(), 2i32.into()]);
+
+        assert_eq!(prepared(vec![0], vec!["a"]).routing_key(&values), None);
+        assert_eq!(prepared(vec![5], vec!["f"]).routing_key(&values), None);
+        assert_eq!(prepared(vec![], vec![]).routing_key(&values), None);
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let prepared = prepared(vec![0], vec!["a"]);
+        let clone = prepared.clone();
+        let shared = prepared.share();
+
+        *prepared.id.write().unwrap() = CBytesShort::new(vec![2]);
+        assert_eq!(*clone.id.read().unwrap(), CBytesShort::new(vec![1]));
+        assert_eq!(*shared.id.read().unwrap(), CBytesShort::new(vec![2]));
+    }
 }
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use crate::consistency::Consistency;
+
+/// Session level defaults used for query parameters which were not explicitly set, e.g. when
+/// executing queries via `query` or `exec` without parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryDefaults {
+    /// Default consistency. Defaults to `One`.
+    pub consistency: Consistency,
+    /// Default serial consistency. Defaults to none, meaning server default is used.
+    pub serial_consistency: Option<Consistency>,
+    /// Default page size. Defaults to none, meaning server default is used.
+    pub page_size: Option<i32>,
+    /// Maximum time to wait for a response to a request. The connection is closed when the
+    /// timeout elapses, since a partially read response cannot be recovered. Defaults to no
+    /// timeout.
+    pub request_timeout: Option<Duration>,
+    /// Refuses to execute prepared statements on connections which `USE` a different keyspace
+    /// than the one recorded when they were last prepared, or none, since unqualified table
+    /// names would silently resolve to another table. Defaults to `false`.
+    pub check_prepared_keyspace: bool,
+    /// Fraction of requests, between 0 and 1, traced by the server when not explicitly traced,
+    /// e.g. 0.01 to trace 1% of queries. Can be overridden per query with
+    /// `QueryParams::tracing`. Applies to queries and executions of prepared statements.
+    /// Defaults to 0.
+    pub tracing_sample_rate: f64,
+    /// Number of times a request rejected by a node which is overloaded or bootstrapping is
+    /// retried on the next node. Rejected requests are not executed, so they are retried
+    /// regardless of idempotence. Requests are not retried if the load balancer picks the same
+    /// node again, e.g. by `DirectSession`. Defaults to 1.
+    pub overload_retries: usize,
+}
+
+impl Default for QueryDefaults {
+    fn default() -> Self {
+        QueryDefaults {
+            consistency: Default::default(),
+            serial_consistency: None,
+            page_size: None,
+            request_timeout: None,
+            check_prepared_keyspace: false,
+            tracing_sample_rate: 0.0,
+            overload_retries: 1,
+        }
+    }
+}
+
+impl QueryDefaults {
+    /// Randomly decides if a request should be traced according to `tracing_sample_rate`.
+    #[cfg(feature = "runtime")]
+    pub fn sample_tracing(&self) -> bool {
+        self.tracing_sample_rate > 0.0 && rand::random::<f64>() < self.tracing_sample_rate
+    }
+}
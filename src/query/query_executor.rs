@@ -1,19 +1,37 @@
 use async_trait::async_trait;
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::cluster::{GetCompressor, GetConnection, ResponseCache};
+use crate::cluster::{
+    GetCompressor, GetConnection, GetQueryDefaults, GetSessionHooks, ResponseCache,
+};
 use crate::error;
-use crate::frame::{AsBytes, Frame};
-use crate::query::{Query, QueryParams, QueryParamsBuilder, QueryValues};
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::{Frame, TryFromRow};
+use crate::query::{
+    notify_trace, should_trace, AnnQuery, ConsistencyFallback, CounterUpdate, Query, QueryParams,
+    QueryParamsBuilder, QueryResponse, QueryValues, StatementInterceptor, WithFrame,
+};
 use crate::transport::CDRSTransport;
 
-use super::utils::{prepare_flags, send_frame};
+#[cfg(feature = "continuous-paging")]
+use crate::query::{ContinuousPages, ContinuousPagingOptions};
+#[cfg(feature = "serde_json")]
+use crate::types::json::{from_json_row, json_error};
+
+#[cfg(feature = "serde_json")]
+use super::utils::session_rows_of;
+use super::utils::{
+    join_bounded, prepare_flags, send_frame, session_typed_rows_of, try_send_frame,
+    with_consistency_fallback, with_request_deadline,
+};
 
 #[async_trait]
 pub trait QueryExecutor<
     T: CDRSTransport + Unpin + 'static,
     M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
->: GetConnection<T, M> + GetCompressor + ResponseCache + Sync
+>:
+    GetConnection<T, M> + GetCompressor + GetQueryDefaults + GetSessionHooks + ResponseCache + Sync
 {
     async fn query_with_params_tw<Q: ToString + Send>(
         &self,
@@ -22,16 +40,68 @@ pub trait QueryExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
-        let query = Query {
-            query: query.to_string(),
-            params: query_params,
-        };
+        let mut query_params = query_params;
+        if let Some(generator) = self.hooks().timestamp_generator.as_ref() {
+            generator.stamp(&mut query_params);
+        }
 
+        let query = query.to_string();
+        if let Some(advisor) = self.hooks().prepare_advisor.as_ref() {
+            advisor.observe(&query);
+        }
+        let with_tracing = should_trace(&self.query_defaults(), &query_params, with_tracing);
+        let deadline = query_params.deadline;
+        let intercepted = intercepted_query(
+            &self.hooks().statement_interceptors,
+            query.clone(),
+            query_params,
+        );
         let flags = prepare_flags(with_tracing, with_warnings);
+        let build =
+            |protocol_version| Frame::new_query_for(&intercepted, flags.clone(), protocol_version);
+
+        let result = with_request_deadline(deadline, send_frame(self, &build)).await;
+        if let (true, Ok(frame)) = (with_tracing, &result) {
+            notify_trace(&self.hooks().tracing_listeners, &query, frame);
+        }
+
+        result
+    }
+
+    /// Executes a query with given parameters like `query_with_params`, but instead of waiting
+    /// for a connection fails immediately with `Error::PoolExhausted` if all connections to the
+    /// picked node are in use. If the node replies it is overloaded, `Error::Overloaded` is
+    /// returned. Allows shedding load instead of queueing requests.
+    async fn try_query_with_params<Q: ToString + Send>(
+        &self,
+        query: Q,
+        query_params: QueryParams,
+    ) -> error::Result<Frame> {
+        let mut query_params = query_params;
+        if let Some(generator) = self.hooks().timestamp_generator.as_ref() {
+            generator.stamp(&mut query_params);
+        }
+
+        let query = query.to_string();
+        if let Some(advisor) = self.hooks().prepare_advisor.as_ref() {
+            advisor.observe(&query);
+        }
 
-        let query_frame = Frame::new_query(query, flags);
+        let deadline = query_params.deadline;
+        let intercepted =
+            intercepted_query(&self.hooks().statement_interceptors, query, query_params);
+        let build = |protocol_version| Frame::new_query_for(&intercepted, vec![], protocol_version);
 
-        send_frame(self, query_frame.as_bytes(), query_frame.stream).await
+        with_request_deadline(deadline, try_send_frame(self, &build)).await
+    }
+
+    /// Executes a query with default parameters like `query`, but fails immediately if no
+    /// connection is available. See `try_query_with_params`.
+    async fn try_query<Q: ToString + Send>(&self, query: Q) -> error::Result<Frame> {
+        let query_params = QueryParamsBuilder::new()
+            .defaults(&self.query_defaults())
+            .finalize();
+        self.try_query_with_params(query, query_params).await
     }
 
     /// Executes a query with default parameters:
@@ -48,7 +118,9 @@ pub trait QueryExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
-        let query_params = QueryParamsBuilder::new().finalize();
+        let query_params = QueryParamsBuilder::new()
+            .defaults(&self.query_defaults())
+            .finalize();
         self.query_with_params_tw(query, query_params, with_tracing, with_warnings)
             .await
     }
@@ -62,6 +134,29 @@ pub trait QueryExecutor<
         self.query_with_values_tw(query, values, false, false).await
     }
 
+    /// Executes independent statements with their values, with at most `concurrency` of them
+    /// in flight at once. Returns results of all statements in order of the statements, e.g.
+    /// for bulk loading.
+    async fn execute_concurrent<Q, V, I>(
+        &self,
+        statements: I,
+        concurrency: usize,
+    ) -> Vec<error::Result<Frame>>
+    where
+        Q: ToString + Send,
+        V: Into<QueryValues> + Send,
+        I: IntoIterator<Item = (Q, V)> + Send,
+        I::IntoIter: Send,
+    {
+        join_bounded(
+            statements
+                .into_iter()
+                .map(|(query, values)| self.query_with_values(query, values)),
+            concurrency,
+        )
+        .await
+    }
+
     /// Executes a query with bounded values (either with or without names)
     /// and ability to see warnings, trace a request and default parameters.
     async fn query_with_values_tw<Q: ToString + Send, V: Into<QueryValues> + Send>(
@@ -71,7 +166,7 @@ pub trait QueryExecutor<
         with_tracing: bool,
         with_warnings: bool,
     ) -> error::Result<Frame> {
-        let query_params_builder = QueryParamsBuilder::new();
+        let query_params_builder = QueryParamsBuilder::new().defaults(&self.query_defaults());
         let query_params = query_params_builder.values(values.into()).finalize();
         self.query_with_params_tw(query, query_params, with_tracing, with_warnings)
             .await
@@ -86,4 +181,256 @@ pub trait QueryExecutor<
         self.query_with_params_tw(query, query_params, false, false)
             .await
     }
+
+    /// Executes an approximate nearest neighbor query and converts returned rows into `R`,
+    /// nearest first.
+    async fn query_ann<R: TryFromRow + Send>(&self, query: &AnnQuery) -> error::Result<Vec<R>> {
+        self.query_ann_with_frame(query)
+            .await
+            .map(WithFrame::into_value)
+    }
+
+    /// Executes an approximate nearest neighbor query like `query_ann`, additionally returning
+    /// the response frame.
+    async fn query_ann_with_frame<R: TryFromRow + Send>(
+        &self,
+        query: &AnnQuery,
+    ) -> error::Result<WithFrame<Vec<R>>> {
+        let frame = self
+            .query_with_values(query.query(), query.values())
+            .await?;
+        Ok(WithFrame {
+            value: session_typed_rows_of(&frame, self.hooks().type_registry.as_ref())?,
+            frame,
+        })
+    }
+
+    /// Adds `delta` to `counter_column` of a row of `table` identified by named key values,
+    /// e.g. `query_values!("id" => 1)`, via `UPDATE ... SET c = c + ? WHERE ...`.
+    async fn increment(
+        &self,
+        table: &str,
+        key_values: QueryValues,
+        counter_column: &str,
+        delta: i64,
+    ) -> error::Result<Frame> {
+        let update = CounterUpdate::with_key_values(table, key_values, counter_column, delta)?;
+        self.update_counter(&update).await
+    }
+
+    /// Executes a counter update.
+    async fn update_counter(&self, update: &CounterUpdate) -> error::Result<Frame> {
+        self.query_with_values(update.query(), update.values())
+            .await
+    }
+
+    /// Executes a `SELECT JSON` query with bound values and deserializes the `[json]` column of
+    /// returned rows into `R`, e.g. `serde_json::Value` or a type implementing `Deserialize`.
+    #[cfg(feature = "serde_json")]
+    async fn query_json<R, Q, V>(&self, query: Q, values: V) -> error::Result<Vec<R>>
+    where
+        R: serde::de::DeserializeOwned + Send,
+        Q: ToString + Send,
+        V: Into<QueryValues> + Send,
+    {
+        self.query_json_with_frame(query, values)
+            .await
+            .map(WithFrame::into_value)
+    }
+
+    /// Executes a `SELECT JSON` query like `query_json`, additionally returning the response
+    /// frame.
+    #[cfg(feature = "serde_json")]
+    async fn query_json_with_frame<R, Q, V>(
+        &self,
+        query: Q,
+        values: V,
+    ) -> error::Result<WithFrame<Vec<R>>>
+    where
+        R: serde::de::DeserializeOwned + Send,
+        Q: ToString + Send,
+        V: Into<QueryValues> + Send,
+    {
+        let frame = self.query_with_values(query, values).await?;
+        let value = session_rows_of(&frame, self.hooks().type_registry.as_ref())?
+            .iter()
+            .map(from_json_row)
+            .collect::<error::Result<_>>()?;
+
+        Ok(WithFrame { value, frame })
+    }
+
+    /// Inserts a value serialized as a JSON object into given table via `INSERT INTO ... JSON`.
+    /// Columns missing in the object are set to `null`.
+    #[cfg(feature = "serde_json")]
+    async fn insert_json<V: serde::Serialize + Sync>(
+        &self,
+        table: &str,
+        value: &V,
+    ) -> error::Result<Frame> {
+        let json = serde_json::to_string(value).map_err(json_error)?;
+        self.query_with_values(format!("INSERT INTO {} JSON ?", table), vec![json])
+            .await
+    }
+
+    /// Executes a query with continuous paging, requested with the custom payload entry
+    /// described in `ContinuousPagingOptions`. Pages are streamed by the server over a single
+    /// connection; servers which don't read the entry return a single regular page.
+    #[cfg(feature = "continuous-paging")]
+    async fn query_continuous<Q: ToString + Send>(
+        &self,
+        query: Q,
+        mut query_params: QueryParams,
+        options: ContinuousPagingOptions,
+    ) -> error::Result<ContinuousPages> {
+        options.apply(&mut query_params);
+        let query = intercepted_query(
+            &self.hooks().statement_interceptors,
+            query.to_string(),
+            query_params,
+        );
+
+        let node = self
+            .get_connection()
+            .await
+            .ok_or_else(|| error::Error::from("Unable to get transport"))?;
+
+        Ok(ContinuousPages::start(
+            node,
+            self.get_compressor(),
+            query,
+            options.buffered_pages,
+        ))
+    }
+
+    /// Executes a query with given parameters, retrying it with consistency levels from
+    /// `fallback` while the coordinator reports too few alive replicas. The response tells
+    /// which consistency the query succeeded with, if it was downgraded.
+    async fn query_with_fallback<Q: ToString + Send>(
+        &self,
+        query: Q,
+        query_params: QueryParams,
+        fallback: &ConsistencyFallback,
+    ) -> error::Result<QueryResponse<ResponseBody>> {
+        let query = query.to_string();
+        with_consistency_fallback(fallback, |consistency| {
+            let mut query_params = query_params.clone();
+            if let Some(consistency) = consistency {
+                query_params.consistency = consistency;
+            }
+
+            self.query_with_params(query.as_str(), query_params)
+        })
+        .await
+    }
+}
+
+fn intercepted_query(
+    interceptors: &[Arc<dyn StatementInterceptor>],
+    query: String,
+    params: QueryParams,
+) -> Query {
+    let mut query = Query { query, params };
+
+    for interceptor in interceptors {
+        interceptor.intercept(&mut query);
+    }
+
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticators::NoneAuthenticator;
+    use crate::cluster::session;
+    use crate::cluster::test_node::{void, TestNode};
+    use crate::cluster::{ClusterTcpConfig, DirectSession, NodeTcpConfigBuilder, SessionHooks};
+    use crate::frame::Opcode;
+    use crate::load_balancing::RoundRobin;
+
+    #[tokio::test]
+    async fn statement_interceptors() {
+        fn only_traced(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            let query_len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+            if body[4..4 + query_len].ends_with(b"/* trace-id: 1 */") {
+                void(opcode, body)
+            } else {
+                Some((Opcode::Error, vec![0, 0, 0x22, 0, 0, 0]))
+            }
+        }
+
+        let node = TestNode::start_with(only_traced).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        assert!(session.query("SELECT * FROM t").await.is_err());
+
+        let session = session.with_hooks(SessionHooks {
+            statement_interceptors: vec![Arc::new(|query: &mut Query| {
+                query.query.push_str(" /* trace-id: 1 */")
+            })],
+            ..Default::default()
+        });
+        assert!(session.query("SELECT * FROM t").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn try_query_without_waiting() {
+        fn overloaded(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            let query_len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+            if &body[4..4 + query_len] == b"INSERT" {
+                Some((Opcode::Error, vec![0, 0, 0x10, 0x01, 0, 0]))
+            } else {
+                void(opcode, body)
+            }
+        }
+
+        let node = TestNode::start_with(overloaded).await;
+        let config = ClusterTcpConfig::new(vec![NodeTcpConfigBuilder::new(
+            node.addr,
+            Arc::new(NoneAuthenticator),
+        )
+        .max_size(1)
+        .build()]);
+        let session = session::new(&config, RoundRobin::new()).await.unwrap();
+        let pool = session.get_connection().await.unwrap();
+        let connection = pool.checkout().await.unwrap();
+
+        match session.try_query("SELECT * FROM t").await {
+            Err(error::Error::PoolExhausted(addr)) => assert_eq!(addr, node.addr),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        drop(connection);
+        assert!(session.try_query("SELECT * FROM t").await.is_ok());
+
+        match session.try_query("INSERT").await {
+            Err(error::Error::Overloaded(addr)) => assert_eq!(addr, node.addr),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_concurrent() {
+        fn fail_on_t0(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            let query_len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+            if body[4..4 + query_len].starts_with(b"INSERT INTO t0 ") {
+                Some((Opcode::Error, vec![0, 0, 0x22, 0, 0, 0]))
+            } else {
+                void(opcode, body)
+            }
+        }
+
+        let node = TestNode::start_with(fail_on_t0).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let results = session
+            .execute_concurrent(
+                (0..5).map(|i| (format!("INSERT INTO t{} (id) VALUES (?)", 4 - i), vec![i])),
+                2,
+            )
+            .await;
+
+        assert_eq!(results.len(), 5);
+        assert!(results[..4].iter().all(Result::is_ok));
+        assert!(results[4].is_err());
+    }
 }
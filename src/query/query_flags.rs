@@ -1,4 +1,8 @@
-use crate::frame::AsByte;
+use std::io::Cursor;
+
+use crate::error;
+use crate::frame::{AsByte, AsBytes, ProtocolVersion};
+use crate::types::{cursor_next_value, to_int, try_from_bytes, INT_LEN};
 
 const FLAGS_VALUE: u8 = 0x01;
 const FLAGS_SKIP_METADATA: u8 = 0x02;
@@ -7,6 +11,10 @@ const WITH_PAGING_STATE: u8 = 0x08;
 const WITH_SERIAL_CONSISTENCY: u8 = 0x10;
 const WITH_DEFAULT_TIMESTAMP: u8 = 0x20;
 const WITH_NAME_FOR_VALUES: u8 = 0x40;
+/// Bit of the v5 flag set when the request carries a keyspace.
+pub(crate) const WITH_KEYSPACE: u32 = 0x80;
+/// Bit of the v5 flag set when the request carries the current time in seconds.
+pub(crate) const WITH_NOW_IN_SECONDS: u32 = 0x100;
 
 /// Cassandra Query Flags.
 #[derive(Clone, Debug)]
@@ -113,6 +121,113 @@ impl AsByte for QueryFlags {
     }
 }
 
+impl QueryFlags {
+    const ALL: [QueryFlags; 7] = [
+        QueryFlags::Value,
+        QueryFlags::SkipMetadata,
+        QueryFlags::PageSize,
+        QueryFlags::WithPagingState,
+        QueryFlags::WithSerialConsistency,
+        QueryFlags::WithDefaultTimestamp,
+        QueryFlags::WithNamesForValues,
+    ];
+
+    /// Returns the bit of the flag.
+    pub fn bits(&self) -> u32 {
+        u32::from(self.as_byte())
+    }
+}
+
+/// Set of query flags as sent in `QUERY`, `EXECUTE` and `BATCH` requests. Unlike a list of
+/// `QueryFlags`, the set keeps bits unknown to the driver, e.g. ones added by newer protocol
+/// versions, so flags of custom frames can be parsed and written back unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct QueryFlagSet(u32);
+
+impl QueryFlagSet {
+    /// Creates an empty set.
+    pub fn empty() -> Self {
+        QueryFlagSet(0)
+    }
+
+    /// Creates a set from raw bits, including unknown ones.
+    pub fn from_bits(bits: u32) -> Self {
+        QueryFlagSet(bits)
+    }
+
+    /// Returns raw bits of the set.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns bits which don't correspond to any of `QueryFlags`.
+    pub fn unknown_bits(&self) -> u32 {
+        self.0 & !Self::from_flags(&QueryFlags::ALL).bits()
+    }
+
+    /// Creates a set of given flags.
+    pub fn from_flags(flags: &[QueryFlags]) -> Self {
+        QueryFlagSet(flags.iter().fold(0, |bits, flag| bits | flag.bits()))
+    }
+
+    /// Returns known flags of the set in the order of their bits.
+    pub fn flags(&self) -> Vec<QueryFlags> {
+        QueryFlags::ALL
+            .iter()
+            .filter(|flag| self.contains(flag))
+            .cloned()
+            .collect()
+    }
+
+    /// Checks if the set contains given flag.
+    pub fn contains(&self, flag: &QueryFlags) -> bool {
+        self.0 & flag.bits() != 0
+    }
+
+    /// Adds given flag to the set.
+    pub fn insert(&mut self, flag: &QueryFlags) {
+        self.0 |= flag.bits();
+    }
+
+    /// Removes given flag from the set.
+    pub fn remove(&mut self, flag: &QueryFlags) {
+        self.0 &= !flag.bits();
+    }
+
+    /// Reads flags encoded for given protocol version: a single byte before v5 and an int
+    /// since.
+    pub fn from_cursor_for(
+        cursor: &mut Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> error::Result<Self> {
+        let len = if version.has_int_flags() { INT_LEN } else { 1 };
+        let bytes = cursor_next_value(cursor, len as u64)?;
+        Ok(QueryFlagSet(try_from_bytes(&bytes)? as u32))
+    }
+}
+
+impl AsBytes for QueryFlagSet {
+    fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    /// Writes flags encoded for given protocol version. Versions before v5 only carry the
+    /// lowest 8 bits.
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
+        if version.has_int_flags() {
+            to_int(self.0 as i32)
+        } else {
+            vec![self.0 as u8]
+        }
+    }
+}
+
+impl From<&[QueryFlags]> for QueryFlagSet {
+    fn from(flags: &[QueryFlags]) -> Self {
+        Self::from_flags(flags)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,4 +423,41 @@ mod tests {
             "should propery convert with name for values flag"
         );
     }
+
+    #[test]
+    fn flag_set() {
+        let mut flags = QueryFlagSet::from_flags(&[QueryFlags::Value, QueryFlags::PageSize]);
+        assert!(flags.contains(&QueryFlags::Value));
+        assert!(!flags.contains(&QueryFlags::SkipMetadata));
+
+        flags.insert(&QueryFlags::WithDefaultTimestamp);
+        flags.remove(&QueryFlags::Value);
+        assert_eq!(
+            flags.bits(),
+            WITH_PAGE_SIZE as u32 | WITH_DEFAULT_TIMESTAMP as u32
+        );
+        assert_eq!(flags.flags().len(), 2);
+        assert_eq!(flags.unknown_bits(), 0);
+    }
+
+    #[test]
+    fn flag_set_keeps_unknown_bits() {
+        let flags = QueryFlagSet::from_bits(0x0100_0081);
+        assert_eq!(flags.unknown_bits(), 0x0100_0080);
+        assert!(matches!(flags.flags().as_slice(), [QueryFlags::Value]));
+
+        let bytes = flags.as_bytes_for(ProtocolVersion::V5);
+        assert_eq!(bytes, vec![0x01, 0x00, 0x00, 0x81]);
+        let parsed =
+            QueryFlagSet::from_cursor_for(&mut Cursor::new(bytes.as_slice()), ProtocolVersion::V5)
+                .unwrap();
+        assert_eq!(parsed, flags);
+
+        let bytes = flags.as_bytes_for(ProtocolVersion::V4);
+        assert_eq!(bytes, vec![0x81]);
+        let parsed =
+            QueryFlagSet::from_cursor_for(&mut Cursor::new(bytes.as_slice()), ProtocolVersion::V4)
+                .unwrap();
+        assert_eq!(parsed.bits(), 0x81);
+    }
 }
@@ -1,9 +1,16 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
 use crate::consistency::Consistency;
-use crate::frame::AsByte;
-use crate::frame::AsBytes;
-use crate::query::query_flags::QueryFlags;
+use crate::error;
+use crate::frame::{AsBytes, ProtocolVersion};
+use crate::query::query_flags::{QueryFlagSet, QueryFlags, WITH_KEYSPACE, WITH_NOW_IN_SECONDS};
 use crate::query::query_values::QueryValues;
-use crate::types::{to_bigint, to_int, to_short, CBytes};
+use crate::types::{to_bigint, to_int, to_short, CBytes, CString};
+
+/// Key of the custom payload entry naming the user a request is executed as, supported by
+/// DSE proxy authorization.
+pub const PROXY_EXECUTE_PAYLOAD_KEY: &str = "ProxyExecute";
 
 /// Parameters of Query for query operation.
 #[derive(Debug, Default, Clone)]
@@ -24,6 +31,21 @@ pub struct QueryParams {
     pub serial_consistency: Option<Consistency>,
     /// Timestamp.
     pub timestamp: Option<i64>,
+    /// Custom payload sent with the request, e.g. options of a custom query handler.
+    pub custom_payload: HashMap<String, Vec<u8>>,
+    /// Overrides session level tracing sampling: `Some(true)` traces the request and
+    /// `Some(false)` excludes it from sampling.
+    pub tracing: Option<bool>,
+    /// Absolute deadline of the request, which is not sent to the server. Requests past their
+    /// deadline fail with `Error::DeadlineExceeded` instead of being sent, others time out when
+    /// it passes.
+    pub deadline: Option<Instant>,
+    /// Keyspace the request is executed in, overriding the keyspace of the connection.
+    /// Requires protocol v5.
+    pub keyspace: Option<String>,
+    /// Current time in seconds the server uses for the request, e.g. to test TTLs. Requires
+    /// protocol v5.
+    pub now_in_seconds: Option<i32>,
 }
 
 impl QueryParams {
@@ -33,53 +55,91 @@ impl QueryParams {
         self.values = Some(values);
     }
 
-    fn flags_as_byte(&self) -> u8 {
-        self.flags.iter().fold(0, |acc, flag| acc | flag.as_byte())
+    /// Executes the request on behalf of given user, if the authenticated user is allowed to
+    /// do so by the cluster, e.g. with DSE `GRANT PROXY.EXECUTE`.
+    pub fn execute_as(&mut self, user: &str) {
+        self.custom_payload
+            .insert(PROXY_EXECUTE_PAYLOAD_KEY.into(), user.as_bytes().to_vec());
     }
 
-    #[allow(dead_code)]
-    fn parse_query_flags(byte: u8) -> Vec<QueryFlags> {
-        let mut flags: Vec<QueryFlags> = vec![];
+    /// Returns the user the request is executed as, set with `execute_as`.
+    pub fn executed_as(&self) -> Option<&str> {
+        self.custom_payload
+            .get(PROXY_EXECUTE_PAYLOAD_KEY)
+            .and_then(|user| std::str::from_utf8(user).ok())
+    }
 
-        if QueryFlags::has_value(byte) {
-            flags.push(QueryFlags::Value);
-        }
-        if QueryFlags::has_skip_metadata(byte) {
-            flags.push(QueryFlags::SkipMetadata);
-        }
-        if QueryFlags::has_page_size(byte) {
-            flags.push(QueryFlags::PageSize);
-        }
-        if QueryFlags::has_with_paging_state(byte) {
-            flags.push(QueryFlags::WithPagingState);
-        }
-        if QueryFlags::has_with_serial_consistency(byte) {
-            flags.push(QueryFlags::WithSerialConsistency);
+    /// Returns flags of the parameters as a set.
+    pub fn flag_set(&self) -> QueryFlagSet {
+        QueryFlagSet::from_flags(&self.flags)
+    }
+
+    /// Checks if the parameters can be sent with given protocol version. Unset values are
+    /// only supported since v4, keyspace and current time of the request since v5.
+    pub fn check_version(&self, version: ProtocolVersion) -> error::Result<()> {
+        let has_unset_values = self
+            .values
+            .as_ref()
+            .is_some_and(QueryValues::has_unset_values);
+        if has_unset_values && !version.supports_unset_values() {
+            return Err(format!("Unset values are not supported by protocol {:?}", version).into());
         }
-        if QueryFlags::has_with_default_timestamp(byte) {
-            flags.push(QueryFlags::WithDefaultTimestamp);
+        if self.keyspace.is_some() && !version.supports_request_keyspace() {
+            return Err(format!(
+                "Keyspace of requests is not supported by protocol {:?}",
+                version
+            )
+            .into());
         }
-        if QueryFlags::has_with_names_for_values(byte) {
-            flags.push(QueryFlags::WithNamesForValues);
+        if self.now_in_seconds.is_some() && !version.supports_now_in_seconds() {
+            return Err(format!(
+                "Current time of requests is not supported by protocol {:?}",
+                version
+            )
+            .into());
         }
 
-        flags
+        Ok(())
     }
 }
 
 impl AsBytes for QueryParams {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
+        let flags = self.flag_set();
+        let keyspace = self
+            .keyspace
+            .as_ref()
+            .filter(|_| version.supports_request_keyspace());
+        let now_in_seconds = self
+            .now_in_seconds
+            .filter(|_| version.supports_now_in_seconds());
+
+        let mut flag_bits = flags.bits();
+        if keyspace.is_some() {
+            flag_bits |= WITH_KEYSPACE;
+        }
+        if now_in_seconds.is_some() {
+            flag_bits |= WITH_NOW_IN_SECONDS;
+        }
 
         v.extend_from_slice(self.consistency.as_bytes().as_slice());
-        v.push(self.flags_as_byte());
-        if QueryFlags::has_value(self.flags_as_byte()) {
+        v.extend_from_slice(
+            QueryFlagSet::from_bits(flag_bits)
+                .as_bytes_for(version)
+                .as_slice(),
+        );
+        if flags.contains(&QueryFlags::Value) {
             if let Some(ref values) = self.values {
                 v.extend_from_slice(to_short(values.len() as i16).as_slice());
-                v.extend_from_slice(values.as_bytes().as_slice());
+                v.extend_from_slice(values.as_bytes_for(version).as_slice());
             }
         }
-        if QueryFlags::has_page_size(self.flags_as_byte()) && self.page_size.is_some() {
+        if flags.contains(&QueryFlags::PageSize) && self.page_size.is_some() {
             // XXX clone
             v.extend_from_slice(
                 to_int(
@@ -92,7 +152,7 @@ impl AsBytes for QueryParams {
                 .as_slice(),
             );
         }
-        if QueryFlags::has_with_paging_state(self.flags_as_byte()) && self.paging_state.is_some() {
+        if flags.contains(&QueryFlags::WithPagingState) && self.paging_state.is_some() {
             // XXX clone
             v.extend_from_slice(
                 self.paging_state
@@ -104,9 +164,7 @@ impl AsBytes for QueryParams {
                     .as_slice(),
             );
         }
-        if QueryFlags::has_with_serial_consistency(self.flags_as_byte())
-            && self.serial_consistency.is_some()
-        {
+        if flags.contains(&QueryFlags::WithSerialConsistency) && self.serial_consistency.is_some() {
             // XXX clone
             v.extend_from_slice(
                 self.serial_consistency
@@ -118,11 +176,16 @@ impl AsBytes for QueryParams {
                     .as_slice(),
             );
         }
-        if QueryFlags::has_with_default_timestamp(self.flags_as_byte()) && self.timestamp.is_some()
-        {
+        if flags.contains(&QueryFlags::WithDefaultTimestamp) && self.timestamp.is_some() {
             // unwrap is safe as we've checked that self.timestamp.is_some()
             v.extend_from_slice(to_bigint(self.timestamp.unwrap()).as_slice());
         }
+        if let Some(keyspace) = keyspace {
+            v.extend_from_slice(CString::new(keyspace.clone()).as_bytes().as_slice());
+        }
+        if let Some(now_in_seconds) = now_in_seconds {
+            v.extend_from_slice(to_int(now_in_seconds).as_slice());
+        }
 
         v
     }
@@ -1,10 +1,14 @@
-use super::{QueryFlags, QueryParams, QueryValues};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::query_params::PROXY_EXECUTE_PAYLOAD_KEY;
+use super::{QueryDefaults, QueryFlags, QueryParams, QueryValues, WriteTimestamp};
 use crate::consistency::Consistency;
 use crate::types::CBytes;
 
 #[derive(Debug, Default)]
 pub struct QueryParamsBuilder {
-    consistency: Consistency,
+    consistency: Option<Consistency>,
     flags: Option<Vec<QueryFlags>>,
     values: Option<QueryValues>,
     with_names: Option<bool>,
@@ -12,6 +16,11 @@ pub struct QueryParamsBuilder {
     paging_state: Option<CBytes>,
     serial_consistency: Option<Consistency>,
     timestamp: Option<i64>,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
+    tracing: Option<bool>,
+    deadline: Option<Instant>,
+    keyspace: Option<String>,
+    now_in_seconds: Option<i32>,
 }
 
 impl QueryParamsBuilder {
@@ -23,11 +32,23 @@ impl QueryParamsBuilder {
 
     /// Sets new query consistency
     pub fn consistency(mut self, consistency: Consistency) -> Self {
-        self.consistency = consistency;
+        self.consistency = Some(consistency);
 
         self
     }
 
+    /// Fills consistency, serial consistency and page size from given defaults, unless they
+    /// were already set.
+    pub fn defaults(mut self, defaults: &QueryDefaults) -> Self {
+        self.consistency = self.consistency.or(Some(defaults.consistency));
+        self.serial_consistency = self.serial_consistency.or(defaults.serial_consistency);
+
+        match defaults.page_size {
+            Some(size) if self.page_size.is_none() => self.page_size(size),
+            _ => self,
+        }
+    }
+
     // Sets new flags.
     builder_opt_field!(flags, Vec<QueryFlags>);
 
@@ -81,10 +102,48 @@ impl QueryParamsBuilder {
     // Sets new timestamp value.
     builder_opt_field!(timestamp, i64);
 
+    /// Sets default timestamp of the query from a typed value, e.g. `SystemTime` or `DateTime`,
+    /// converting it to microseconds. Unlike `timestamp`, also sets the flag required for the
+    /// timestamp to be sent to the server.
+    pub fn default_timestamp<T: Into<WriteTimestamp>>(mut self, timestamp: T) -> Self {
+        self.timestamp = Some(timestamp.into().as_micros());
+        self.flags = self.flags.or_else(|| Some(vec![])).map(|mut flags| {
+            flags.push(QueryFlags::WithDefaultTimestamp);
+            flags
+        });
+
+        self
+    }
+
+    // Sets new custom_payload value.
+    builder_opt_field!(custom_payload, HashMap<String, Vec<u8>>);
+
+    /// Executes the request on behalf of given user, see `QueryParams::execute_as`. Setting
+    /// `custom_payload` afterwards replaces the user.
+    pub fn execute_as(mut self, user: &str) -> Self {
+        self.custom_payload
+            .get_or_insert_with(HashMap::new)
+            .insert(PROXY_EXECUTE_PAYLOAD_KEY.into(), user.as_bytes().to_vec());
+
+        self
+    }
+
+    // Sets new tracing value, overriding session level tracing sampling.
+    builder_opt_field!(tracing, bool);
+
+    // Sets new absolute deadline of the request.
+    builder_opt_field!(deadline, Instant);
+
+    // Sets new keyspace of the request, requires protocol v5.
+    builder_opt_field!(keyspace, String);
+
+    // Sets new current time in seconds of the request, requires protocol v5.
+    builder_opt_field!(now_in_seconds, i32);
+
     /// Finalizes query building process and returns query itself
     pub fn finalize(self) -> QueryParams {
         QueryParams {
-            consistency: self.consistency,
+            consistency: self.consistency.unwrap_or_default(),
             flags: self.flags.unwrap_or_default(),
             values: self.values,
             with_names: self.with_names,
@@ -92,6 +151,56 @@ impl QueryParamsBuilder {
             paging_state: self.paging_state,
             serial_consistency: self.serial_consistency,
             timestamp: self.timestamp,
+            custom_payload: self.custom_payload.unwrap_or_default(),
+            tracing: self.tracing,
+            deadline: self.deadline,
+            keyspace: self.keyspace,
+            now_in_seconds: self.now_in_seconds,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_fill_missing_fields() {
+        let defaults = QueryDefaults {
+            consistency: Consistency::Quorum,
+            serial_consistency: Some(Consistency::LocalSerial),
+            page_size: Some(100),
+            ..Default::default()
+        };
+
+        let params = QueryParamsBuilder::new().defaults(&defaults).finalize();
+        assert_eq!(params.consistency, Consistency::Quorum);
+        assert_eq!(params.serial_consistency, Some(Consistency::LocalSerial));
+        assert_eq!(params.page_size, Some(100));
+
+        let params = QueryParamsBuilder::new()
+            .consistency(Consistency::All)
+            .page_size(10)
+            .defaults(&defaults)
+            .finalize();
+        assert_eq!(params.consistency, Consistency::All);
+        assert_eq!(params.page_size, Some(10));
+    }
+
+    #[test]
+    fn execute_as_keeps_custom_payload() {
+        let mut custom_payload = HashMap::new();
+        custom_payload.insert("k".to_string(), vec![1]);
+
+        let params = QueryParamsBuilder::new()
+            .custom_payload(custom_payload)
+            .execute_as("alice")
+            .finalize();
+        assert_eq!(params.executed_as(), Some("alice"));
+        assert_eq!(params.custom_payload.get("k"), Some(&vec![1]));
+        assert_eq!(
+            params.custom_payload.get(PROXY_EXECUTE_PAYLOAD_KEY),
+            Some(&b"alice".to_vec())
+        );
+    }
+}
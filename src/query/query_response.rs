@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use uuid::Uuid;
+
+use crate::consistency::Consistency;
+use crate::error;
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::Frame;
+use crate::query::TombstoneWarning;
+use crate::types::rows::Row;
+
+/// Response envelope which carries protocol metadata alongside a decoded body, so tracing id,
+/// warnings and custom payload are not lost when working with typed results instead of raw
+/// frames.
+#[derive(Debug)]
+pub struct QueryResponse<T> {
+    /// Decoded response body.
+    pub body: T,
+    /// Tracing id, if tracing was requested.
+    pub tracing_id: Option<Uuid>,
+    /// Warnings returned by the server, if requested.
+    pub warnings: Vec<String>,
+    /// Custom payload returned by the server.
+    pub custom_payload: HashMap<String, Vec<u8>>,
+    /// Consistency the query succeeded with, if it was downgraded by a `ConsistencyFallback`.
+    pub downgraded_consistency: Option<Consistency>,
+    /// Address of the node which coordinated the request, if it was sent by a session.
+    pub coordinator: Option<SocketAddr>,
+}
+
+impl<T> QueryResponse<T> {
+    /// Transforms the body, keeping metadata intact.
+    pub fn map<R, F: FnOnce(T) -> R>(self, f: F) -> QueryResponse<R> {
+        QueryResponse {
+            body: f(self.body),
+            tracing_id: self.tracing_id,
+            warnings: self.warnings,
+            custom_payload: self.custom_payload,
+            downgraded_consistency: self.downgraded_consistency,
+            coordinator: self.coordinator,
+        }
+    }
+
+    /// Transforms the body with a fallible function, keeping metadata intact.
+    pub fn try_map<R, F: FnOnce(T) -> error::Result<R>>(
+        self,
+        f: F,
+    ) -> error::Result<QueryResponse<R>> {
+        Ok(QueryResponse {
+            body: f(self.body)?,
+            tracing_id: self.tracing_id,
+            warnings: self.warnings,
+            custom_payload: self.custom_payload,
+            downgraded_consistency: self.downgraded_consistency,
+            coordinator: self.coordinator,
+        })
+    }
+
+    /// Returns the body, discarding metadata.
+    pub fn into_body(self) -> T {
+        self.body
+    }
+
+    /// Returns warnings about reading too many tombstones.
+    pub fn tombstone_warnings(&self) -> Vec<TombstoneWarning> {
+        self.warnings
+            .iter()
+            .filter_map(|warning| TombstoneWarning::parse(warning))
+            .collect()
+    }
+}
+
+impl QueryResponse<ResponseBody> {
+    /// Decodes a frame into a response envelope.
+    pub fn from_frame(frame: Frame) -> error::Result<Self> {
+        let body = frame.get_body()?;
+
+        Ok(QueryResponse {
+            body,
+            tracing_id: frame.tracing_id,
+            warnings: frame.warnings,
+            custom_payload: frame.custom_payload,
+            downgraded_consistency: None,
+            coordinator: frame.coordinator,
+        })
+    }
+
+    /// Converts the body into rows. Returns an error if the response does not contain rows.
+    pub fn into_rows(self) -> error::Result<QueryResponse<Vec<Row>>> {
+        self.try_map(|body| {
+            body.into_rows()
+                .ok_or_else(|| error::Error::from("Response does not contain rows"))
+        })
+    }
+}
+
+/// Typed result along with the frame it was decoded from, returned by `*_with_frame` variants
+/// of typed methods, e.g. `ExecExecutor::exec_rows_with_frame`. Gives access to flags, tracing
+/// id and the raw body of the response.
+#[derive(Debug)]
+pub struct WithFrame<T> {
+    pub value: T,
+    pub frame: Frame,
+}
+
+impl<T> WithFrame<T> {
+    /// Returns the typed result, discarding the frame.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Transforms the typed result, keeping the frame.
+    pub fn map<R, F: FnOnce(T) -> R>(self, f: F) -> WithFrame<R> {
+        WithFrame {
+            value: f(self.value),
+            frame: self.frame,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Flag, Opcode, Version};
+
+    fn result_frame(body: Vec<u8>) -> Frame {
+        let mut frame = Frame::new(
+            Version::Response,
+            vec![Flag::Warning],
+            Opcode::Result,
+            body,
+            None,
+            vec!["warning".into()],
+        );
+        frame.custom_payload.insert("key".into(), vec![1, 2, 3]);
+        frame
+    }
+
+    #[test]
+    fn keeps_metadata() {
+        let response = QueryResponse::from_frame(result_frame(vec![0, 0, 0, 1])).unwrap();
+        let response = response.map(|_| 42);
+
+        assert_eq!(response.body, 42);
+        assert_eq!(response.warnings, vec!["warning".to_string()]);
+        assert_eq!(response.custom_payload.get("key"), Some(&vec![1, 2, 3]));
+        assert_eq!(response.coordinator, None);
+    }
+
+    #[test]
+    fn tombstone_warnings() {
+        let mut frame = result_frame(vec![0, 0, 0, 1]);
+        frame
+            .warnings
+            .push("Read 1 live rows and 2000 tombstone cells for query SELECT * FROM t".into());
+
+        let warnings = QueryResponse::from_frame(frame)
+            .unwrap()
+            .tombstone_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].tombstones, 2000);
+        assert_eq!(warnings[0].table.as_deref(), Some("t"));
+    }
+
+    #[test]
+    fn with_frame_keeps_frame() {
+        let with_frame = WithFrame {
+            value: vec![1, 2],
+            frame: result_frame(vec![0, 0, 0, 1]),
+        }
+        .map(|values| values.len());
+
+        assert_eq!(with_frame.frame.warnings(), &vec!["warning".to_string()]);
+        assert_eq!(with_frame.frame.body, vec![0, 0, 0, 1]);
+        assert_eq!(with_frame.into_value(), 2);
+    }
+
+    #[test]
+    fn into_rows_of_void() {
+        let response = QueryResponse::from_frame(result_frame(vec![0, 0, 0, 1])).unwrap();
+        assert!(response.into_rows().is_err());
+    }
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 
-use crate::frame::AsBytes;
-use crate::types::value::Value;
+use crate::frame::{AsBytes, ProtocolVersion};
+use crate::types::value::{Value, ValueType};
 use crate::types::CString;
 
 /// Enum that represents two types of query values:
@@ -33,16 +33,29 @@ impl QueryValues {
         self.len() == 0
     }
 
-    fn named_value_into_bytes_fold(mut bytes: Vec<u8>, vals: (&String, &Value)) -> Vec<u8> {
+    /// Returns `true` if any of the values is not set.
+    pub fn has_unset_values(&self) -> bool {
+        let is_unset = |value: &Value| value.value_type == ValueType::NotSet;
+        match *self {
+            QueryValues::SimpleValues(ref v) => v.iter().any(is_unset),
+            QueryValues::NamedValues(ref m) => m.values().any(is_unset),
+        }
+    }
+
+    fn named_value_into_bytes(
+        mut bytes: Vec<u8>,
+        vals: (&String, &Value),
+        version: ProtocolVersion,
+    ) -> Vec<u8> {
         let mut name_bytes = CString::new(vals.0.clone()).as_bytes();
-        let mut vals_bytes = vals.1.as_bytes();
+        let mut vals_bytes = vals.1.as_bytes_for(version);
         bytes.append(&mut name_bytes);
         bytes.append(&mut vals_bytes);
         bytes
     }
 
-    fn value_into_bytes_fold(mut bytes: Vec<u8>, val: &Value) -> Vec<u8> {
-        let mut val_bytes = val.as_bytes();
+    fn value_into_bytes(mut bytes: Vec<u8>, val: &Value, version: ProtocolVersion) -> Vec<u8> {
+        let mut val_bytes = val.as_bytes_for(version);
         bytes.append(&mut val_bytes);
         bytes
     }
@@ -80,14 +93,18 @@ impl<S: ToString + Hash + Eq, V: Into<Value> + Clone> From<HashMap<S, V>> for Qu
 
 impl AsBytes for QueryValues {
     fn as_bytes(&self) -> Vec<u8> {
+        self.as_bytes_for(ProtocolVersion::default())
+    }
+
+    fn as_bytes_for(&self, version: ProtocolVersion) -> Vec<u8> {
         let bytes: Vec<u8> = vec![];
         match *self {
-            QueryValues::SimpleValues(ref v) => {
-                v.iter().fold(bytes, QueryValues::value_into_bytes_fold)
-            }
-            QueryValues::NamedValues(ref v) => v
-                .iter()
-                .fold(bytes, QueryValues::named_value_into_bytes_fold),
+            QueryValues::SimpleValues(ref v) => v.iter().fold(bytes, |bytes, value| {
+                QueryValues::value_into_bytes(bytes, value, version)
+            }),
+            QueryValues::NamedValues(ref v) => v.iter().fold(bytes, |bytes, value| {
+                QueryValues::named_value_into_bytes(bytes, value, version)
+            }),
         }
     }
 }
@@ -0,0 +1,104 @@
+use crate::token::murmur3_token;
+use crate::types::value::{Value, ValueType};
+
+/// Serialized partition key of a row, whose token determines replicas of its partition.
+/// Partition keys of a single column are serialized like the value of the column; composite
+/// ones like values of Cassandra's `CompositeType`, i.e. each component prefixed with its
+/// 2-byte length and followed by a zero byte.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutingKey(Vec<u8>);
+
+impl RoutingKey {
+    /// Creates a routing key from values of partition key columns, in order of their
+    /// declaration. Returns `None` if there are no values or any of them is null or unset.
+    pub fn composite(values: &[Value]) -> Option<Self> {
+        values
+            .iter()
+            .map(|value| match value {
+                Value {
+                    body,
+                    value_type: ValueType::Normal(_),
+                } => Some(body.as_slice()),
+                _ => None,
+            })
+            .collect::<Option<Vec<&[u8]>>>()
+            .and_then(|components| Self::from_components(&components))
+    }
+
+    /// Creates a routing key from serialized values of partition key columns.
+    pub(crate) fn from_components(components: &[&[u8]]) -> Option<Self> {
+        match components {
+            [] => None,
+            [component] => Some(RoutingKey(component.to_vec())),
+            components => {
+                let mut routing_key = vec![];
+                for component in components {
+                    routing_key.extend_from_slice(&(component.len() as u16).to_be_bytes());
+                    routing_key.extend_from_slice(component);
+                    routing_key.push(0);
+                }
+                Some(RoutingKey(routing_key))
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Returns the token of the routing key computed by the `Murmur3Partitioner`.
+    pub fn token(&self) -> i64 {
+        murmur3_token(&self.0)
+    }
+}
+
+impl AsRef<[u8]> for RoutingKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_component() {
+        assert_eq!(
+            RoutingKey::composite(&[1i32.into()]).unwrap().as_bytes(),
+            &[0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn multiple_components() {
+        assert_eq!(
+            RoutingKey::composite(&["x".into(), 1i32.into()])
+                .unwrap()
+                .into_bytes(),
+            vec![0, 1, b'x', 0, 0, 4, 0, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn murmur3_token_of_key() {
+        assert_eq!(
+            RoutingKey::composite(&[1i32.into()]).unwrap().token(),
+            -4069959284402364209
+        );
+    }
+
+    #[test]
+    fn missing_components() {
+        assert_eq!(RoutingKey::composite(&[]), None);
+        assert_eq!(
+            RoutingKey::composite(&[1i32.into(), Value::new_null()]),
+            None
+        );
+        assert_eq!(RoutingKey::composite(&[Value::new_not_set()]), None);
+    }
+}
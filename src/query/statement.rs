@@ -0,0 +1,92 @@
+use crate::consistency::Consistency;
+use crate::query::{PreparedQuery, Query, QueryBatch, QueryFingerprint, QueryParams};
+
+/// Request of any kind - a simple query, an execution of a prepared query or a batch - so code
+/// wrapping requests, e.g. middlewares, retry policies or metrics, can handle all of them
+/// uniformly. Executed with `StatementExecutor::execute`.
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Simple(Query),
+    Prepared(PreparedQuery, QueryParams),
+    Batch(QueryBatch),
+}
+
+impl Statement {
+    /// Returns the query text of simple and prepared statements. Batches don't have one.
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            Statement::Simple(query) => Some(&query.query),
+            Statement::Prepared(prepared, _) => Some(&prepared.query),
+            Statement::Batch(_) => None,
+        }
+    }
+
+    /// Returns the fingerprint of the query text of simple and prepared statements.
+    pub fn fingerprint(&self) -> Option<QueryFingerprint> {
+        self.query().map(QueryFingerprint::of)
+    }
+
+    pub fn consistency(&self) -> Consistency {
+        match self {
+            Statement::Simple(query) => query.params.consistency,
+            Statement::Prepared(_, params) => params.consistency,
+            Statement::Batch(batch) => batch.consistency,
+        }
+    }
+
+    pub fn set_consistency(&mut self, consistency: Consistency) {
+        match self {
+            Statement::Simple(query) => query.params.consistency = consistency,
+            Statement::Prepared(_, params) => params.consistency = consistency,
+            Statement::Batch(batch) => batch.consistency = consistency,
+        }
+    }
+}
+
+impl From<Query> for Statement {
+    fn from(query: Query) -> Self {
+        Statement::Simple(query)
+    }
+}
+
+impl From<(PreparedQuery, QueryParams)> for Statement {
+    fn from((prepared, params): (PreparedQuery, QueryParams)) -> Self {
+        Statement::Prepared(prepared, params)
+    }
+}
+
+impl From<QueryBatch> for Statement {
+    fn from(batch: QueryBatch) -> Self {
+        Statement::Batch(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::BatchQueryBuilder;
+
+    #[test]
+    fn uniform_consistency() {
+        let mut statements: Vec<Statement> = vec![
+            Query {
+                query: "SELECT * FROM t".into(),
+                params: Default::default(),
+            }
+            .into(),
+            BatchQueryBuilder::new().finalize().unwrap().into(),
+        ];
+
+        for statement in &mut statements {
+            statement.set_consistency(Consistency::LocalQuorum);
+            assert_eq!(statement.consistency(), Consistency::LocalQuorum);
+        }
+
+        assert_eq!(statements[0].query(), Some("SELECT * FROM t"));
+        assert_eq!(
+            statements[0].fingerprint(),
+            Some(QueryFingerprint::of("SELECT * FROM t"))
+        );
+        assert_eq!(statements[1].query(), None);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::frame::frame_result::{BodyResResultPrepared, ColSpec, ColTypeOption};
+use crate::types::CString;
+
+/// Column bound by or returned from a statement, as described by the server. Types include
+/// their options, e.g. element types of collections and fields of UDTs and tuples.
+#[derive(Debug, Clone)]
+pub struct ColumnDescription {
+    pub keyspace: String,
+    pub table: String,
+    pub name: String,
+    pub col_type: ColTypeOption,
+}
+
+/// Metadata of a statement as returned by `PrepareExecutor::describe_statement`: types of its
+/// bind markers and result columns, e.g. to generate Rust types mapping a schema.
+#[derive(Debug, Clone)]
+pub struct StatementDescription {
+    pub query: String,
+    /// Bind markers in order of their appearance. Named markers are named after their
+    /// markers, others after the columns they are bound to.
+    pub parameters: Vec<ColumnDescription>,
+    /// Columns of result rows. Empty for statements which don't return rows, e.g. writes
+    /// other than lightweight transactions.
+    pub columns: Vec<ColumnDescription>,
+    /// Indexes of parameters binding partition key columns, in order of their declaration.
+    /// Empty if the statement doesn't bind the whole partition key.
+    pub partition_key_indexes: Vec<usize>,
+    /// Whether the statement is a lightweight transaction, only known for nodes supporting the
+    /// ScyllaDB `SCYLLA_LWT_ADD_METADATA_MARK` extension.
+    pub is_lwt: bool,
+}
+
+impl StatementDescription {
+    pub fn new(query: String, prepared: &BodyResResultPrepared, is_lwt: bool) -> Self {
+        let parameters = describe_columns(
+            &prepared.metadata.col_specs,
+            prepared
+                .metadata
+                .global_table_spec
+                .as_ref()
+                .map(|(keyspace, table)| (keyspace, table)),
+        );
+        let columns = prepared.result_metadata.columns();
+
+        StatementDescription {
+            query,
+            parameters,
+            columns,
+            partition_key_indexes: prepared
+                .metadata
+                .pk_indexes
+                .iter()
+                .map(|index| *index as usize)
+                .collect(),
+            is_lwt,
+        }
+    }
+
+    /// Returns parameters binding partition key columns, in order of their declaration.
+    pub fn partition_key(&self) -> impl Iterator<Item = &ColumnDescription> {
+        self.partition_key_indexes
+            .iter()
+            .filter_map(move |index| self.parameters.get(*index))
+    }
+}
+
+pub(crate) fn describe_columns(
+    col_specs: &[ColSpec],
+    global_table_spec: Option<(&CString, &CString)>,
+) -> Vec<ColumnDescription> {
+    col_specs
+        .iter()
+        .map(|col_spec| {
+            let (keyspace, table) = match (&col_spec.ksname, &col_spec.tablename) {
+                (Some(keyspace), Some(table)) => (keyspace.as_plain(), table.as_plain()),
+                _ => global_table_spec
+                    .map(|(keyspace, table)| (keyspace.as_plain(), table.as_plain()))
+                    .unwrap_or_default(),
+            };
+
+            ColumnDescription {
+                keyspace,
+                table,
+                name: col_spec.name.as_plain(),
+                col_type: col_spec.col_type.clone(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{ColType, PreparedMetadata, RowsMetadata};
+    use crate::types::CBytesShort;
+
+    fn col_spec(table: Option<&str>, name: &str, id: ColType) -> ColSpec {
+        ColSpec {
+            ksname: table.map(|_| CString::new("ks".into())),
+            tablename: table.map(|table| CString::new(table.into())),
+            name: CString::new(name.into()),
+            col_type: ColTypeOption { id, value: None },
+        }
+    }
+
+    #[test]
+    fn describe_prepared() {
+        let prepared = BodyResResultPrepared {
+            id: CBytesShort::new(vec![1]),
+            result_metadata_id: None,
+            metadata: PreparedMetadata {
+                flags: 1,
+                columns_count: 2,
+                pk_count: 1,
+                pk_indexes: vec![1],
+                global_table_spec: Some((CString::new("ks".into()), CString::new("t".into()))),
+                col_specs: vec![
+                    col_spec(None, "bucket", ColType::Int),
+                    col_spec(None, "id", ColType::Uuid),
+                ],
+            },
+            result_metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 1,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![col_spec(Some("u"), "name", ColType::Varchar)],
+            },
+        };
+
+        let description =
+            StatementDescription::new("SELECT name FROM t WHERE ...".into(), &prepared, false);
+
+        assert_eq!(description.parameters.len(), 2);
+        assert_eq!(description.parameters[0].table, "t");
+        assert_eq!(description.parameters[0].col_type.to_string(), "int");
+        assert_eq!(
+            description
+                .partition_key()
+                .map(|column| column.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["id"]
+        );
+        assert_eq!(description.columns[0].keyspace, "ks");
+        assert_eq!(description.columns[0].table, "u");
+        assert_eq!(description.columns[0].col_type.to_string(), "text");
+    }
+}
@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::error;
+use crate::frame::frame_response::ResponseBody;
+use crate::frame::Frame;
+use crate::query::{
+    BatchExecutor, ConsistencyFallback, ExecExecutor, QueryExecutor, QueryResponse, Statement,
+};
+use crate::transport::CDRSTransport;
+
+use super::utils::with_consistency_fallback;
+
+/// Executes statements of any kind, delegating to `QueryExecutor`, `ExecExecutor` or
+/// `BatchExecutor`. Implemented for all types implementing the three.
+#[async_trait]
+pub trait StatementExecutor<
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+>: QueryExecutor<T, M> + ExecExecutor<T, M> + BatchExecutor<T, M>
+{
+    async fn execute_tw(
+        &self,
+        statement: Statement,
+        with_tracing: bool,
+        with_warnings: bool,
+    ) -> error::Result<Frame> {
+        match statement {
+            Statement::Simple(query) => {
+                self.query_with_params_tw(query.query, query.params, with_tracing, with_warnings)
+                    .await
+            }
+            Statement::Prepared(prepared, params) => {
+                self.exec_with_params_tw(&prepared, params, with_tracing, with_warnings)
+                    .await
+            }
+            Statement::Batch(batch) => {
+                self.batch_with_params_tw(batch, with_tracing, with_warnings)
+                    .await
+            }
+        }
+    }
+
+    /// Executes a statement without warnings and tracing.
+    async fn execute(&self, statement: Statement) -> error::Result<Frame> {
+        self.execute_tw(statement, false, false).await
+    }
+
+    /// Executes a statement, retrying it with consistency levels from `fallback` while the
+    /// coordinator reports too few alive replicas, like `QueryExecutor::query_with_fallback`.
+    async fn execute_with_fallback(
+        &self,
+        statement: Statement,
+        fallback: &ConsistencyFallback,
+    ) -> error::Result<QueryResponse<ResponseBody>> {
+        with_consistency_fallback(fallback, |consistency| {
+            let mut statement = statement.clone();
+            if let Some(consistency) = consistency {
+                statement.set_consistency(consistency);
+            }
+
+            self.execute(statement)
+        })
+        .await
+    }
+}
+
+impl<S, T, M> StatementExecutor<T, M> for S
+where
+    S: QueryExecutor<T, M> + ExecExecutor<T, M> + BatchExecutor<T, M>,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+}
@@ -0,0 +1,47 @@
+use std::fmt;
+
+use crate::query::Query;
+
+/// Hook which can rewrite query text and parameters before a query is sent, e.g. to inject
+/// tenant predicates or add comments with trace ids. Interceptors of a session are applied in
+/// order to every query executed via `QueryExecutor`; prepared statements and batches are not
+/// affected.
+pub trait StatementInterceptor: Send + Sync {
+    /// Rewrites given query in place.
+    fn intercept(&self, query: &mut Query);
+}
+
+impl<F: Fn(&mut Query) + Send + Sync> StatementInterceptor for F {
+    fn intercept(&self, query: &mut Query) {
+        self(query)
+    }
+}
+
+impl fmt::Debug for dyn StatementInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StatementInterceptor")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consistency::Consistency;
+
+    #[test]
+    fn closure_interceptor() {
+        let interceptor = |query: &mut Query| {
+            query.query.push_str(" /* trace-id: 1 */");
+            query.params.consistency = Consistency::Quorum;
+        };
+
+        let mut query = Query {
+            query: "SELECT * FROM t".into(),
+            params: Default::default(),
+        };
+        StatementInterceptor::intercept(&interceptor, &mut query);
+
+        assert_eq!(query.query, "SELECT * FROM t /* trace-id: 1 */");
+        assert_eq!(query.params.consistency, Consistency::Quorum);
+    }
+}
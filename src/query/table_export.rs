@@ -0,0 +1,375 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::consistency::Consistency;
+use crate::error;
+use crate::frame::frame_result::RowsMetadataFlag;
+use crate::query::{
+    BatchExecutor, BulkWriteResult, BulkWriter, PrepareExecutor, QueryExecutor, QueryParamsBuilder,
+    QueryValues,
+};
+use crate::token::{split_ring, TokenRange};
+use crate::transport::CDRSTransport;
+use crate::types::rows::Row;
+use crate::types::CBytes;
+
+/// Exports all rows of a table by scanning the token ring in parallel: the ring is split into
+/// contiguous token ranges and each range is read with a separate paged query. Together with
+/// `import_rows` forms a minimal toolkit for copying data between tables or clusters.
+///
+/// Rows of different ranges are interleaved, so the export does not keep any particular order.
+#[derive(Debug, Clone)]
+pub struct TableExport {
+    table: String,
+    partition_key: Vec<String>,
+    columns: Vec<String>,
+    splits: Option<usize>,
+    concurrency: Option<usize>,
+    page_size: Option<i32>,
+    buffered_rows: Option<usize>,
+    consistency: Option<Consistency>,
+}
+
+impl TableExport {
+    const DEFAULT_SPLITS: usize = 256;
+    const DEFAULT_CONCURRENCY: usize = 8;
+    const DEFAULT_PAGE_SIZE: i32 = 1000;
+    const DEFAULT_BUFFERED_ROWS: usize = 10_000;
+
+    /// Creates an export of given table, e.g. `ks.users`, with its partition key columns in
+    /// order of their declaration.
+    pub fn new(table: &str, partition_key: &[&str]) -> Self {
+        TableExport {
+            table: table.to_string(),
+            partition_key: partition_key
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+            columns: vec![],
+            splits: None,
+            concurrency: None,
+            page_size: None,
+            buffered_rows: None,
+            consistency: None,
+        }
+    }
+
+    /// Sets exported columns. Defaults to all columns.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    /// Sets number of token ranges the ring is split into.
+    /// Defaults to 256.
+    pub fn splits(mut self, splits: usize) -> Self {
+        self.splits = Some(splits.max(1));
+        self
+    }
+
+    /// Sets maximum number of token ranges read at once.
+    /// Defaults to 8.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    /// Sets page size of range queries.
+    /// Defaults to 1000.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Sets number of rows read ahead of the consumer before range queries are paused.
+    /// Defaults to 10000.
+    pub fn buffered_rows(mut self, buffered_rows: usize) -> Self {
+        self.buffered_rows = Some(buffered_rows.max(1));
+        self
+    }
+
+    /// Sets consistency of range queries.
+    /// Defaults to `Consistency::One`.
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = Some(consistency);
+        self
+    }
+
+    /// Returns token ranges covering the whole ring, in ascending order.
+    pub fn token_ranges(&self) -> Vec<TokenRange> {
+        split_ring(self.splits.unwrap_or(Self::DEFAULT_SPLITS))
+    }
+
+    /// Returns CQL text of the query reading a single token range, bound to the first and last
+    /// token of the range.
+    pub fn range_query(&self) -> String {
+        let columns = if self.columns.is_empty() {
+            "*".to_string()
+        } else {
+            self.columns.join(", ")
+        };
+        let token = format!("token({})", self.partition_key.join(", "));
+
+        format!(
+            "SELECT {} FROM {} WHERE {} >= ? AND {} <= ?",
+            columns, self.table, token, token
+        )
+    }
+
+    /// Starts the export in the background, returning a stream of exported rows.
+    pub fn start<S, T, M>(&self, session: Arc<S>) -> ExportedRows
+    where
+        S: QueryExecutor<T, M> + Send + 'static,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let (rows_tx, rows) =
+            mpsc::channel(self.buffered_rows.unwrap_or(Self::DEFAULT_BUFFERED_ROWS));
+        let ranges = Arc::new(self.token_ranges());
+        let next_range = Arc::new(AtomicUsize::new(0));
+        let query = self.range_query();
+
+        for _ in 0..self.concurrency.unwrap_or(Self::DEFAULT_CONCURRENCY) {
+            tokio::spawn(export_ranges(
+                session.clone(),
+                query.clone(),
+                self.page_size.unwrap_or(Self::DEFAULT_PAGE_SIZE),
+                self.consistency.unwrap_or(Consistency::One),
+                ranges.clone(),
+                next_range.clone(),
+                rows_tx.clone(),
+            ));
+        }
+
+        ExportedRows { rows }
+    }
+}
+
+/// Stream of rows of a running `TableExport`. Dropping it stops the export.
+#[derive(Debug)]
+pub struct ExportedRows {
+    rows: mpsc::Receiver<error::Result<Row>>,
+}
+
+impl ExportedRows {
+    /// Returns the next row, or `None` after all ranges have been read. A failed range is
+    /// reported once with its error and is not read further; other ranges continue.
+    pub async fn next(&mut self) -> Option<error::Result<Row>> {
+        self.rows.recv().await
+    }
+
+    /// Reads all remaining rows, failing on the first error.
+    pub async fn collect(mut self) -> error::Result<Vec<Row>> {
+        let mut rows = vec![];
+        while let Some(row) = self.next().await {
+            rows.push(row?);
+        }
+
+        Ok(rows)
+    }
+}
+
+async fn export_ranges<S, T, M>(
+    session: Arc<S>,
+    query: String,
+    page_size: i32,
+    consistency: Consistency,
+    ranges: Arc<Vec<TokenRange>>,
+    next_range: Arc<AtomicUsize>,
+    rows: mpsc::Sender<error::Result<Row>>,
+) where
+    S: QueryExecutor<T, M> + Send + 'static,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    while let Some(&(start, end)) = ranges.get(next_range.fetch_add(1, Ordering::Relaxed)) {
+        let mut paging_state: Option<CBytes> = None;
+
+        loop {
+            let page = read_page(
+                session.as_ref(),
+                &query,
+                (start, end),
+                page_size,
+                consistency,
+                paging_state.take(),
+            )
+            .await;
+
+            match page {
+                Ok((page, next_paging_state)) => {
+                    for row in page {
+                        if rows.send(Ok(row)).await.is_err() {
+                            return;
+                        }
+                    }
+
+                    match next_paging_state {
+                        Some(state) => paging_state = Some(state),
+                        None => break,
+                    }
+                }
+                Err(error) => {
+                    if rows.send(Err(error)).await.is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn read_page<S, T, M>(
+    session: &S,
+    query: &str,
+    (start, end): TokenRange,
+    page_size: i32,
+    consistency: Consistency,
+    paging_state: Option<CBytes>,
+) -> error::Result<(Vec<Row>, Option<CBytes>)>
+where
+    S: QueryExecutor<T, M>,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let mut params = QueryParamsBuilder::new()
+        .consistency(consistency)
+        .page_size(page_size)
+        .values(vec![start, end].into());
+    if let Some(paging_state) = paging_state {
+        params = params.paging_state(paging_state);
+    }
+
+    let body = session
+        .query_with_params(query, params.finalize())
+        .await?
+        .get_body()?;
+    let metadata = body
+        .as_rows_metadata()
+        .ok_or_else(|| error::Error::from("Export query should yield a vector of rows"))?;
+    let has_more_pages = RowsMetadataFlag::has_has_more_pages(metadata.flags);
+    let paging_state = metadata.paging_state.filter(|_| has_more_pages);
+    let rows = body
+        .into_rows()
+        .ok_or_else(|| error::Error::from("Export query should yield a vector of rows"))?;
+
+    Ok((rows, paging_state))
+}
+
+/// Imports rows, e.g. read by a `TableExport`, with given bulk writer. The statement of the
+/// writer needs to bind all columns of the rows in their order, e.g.
+/// `INSERT INTO ks.users_copy (id, name) VALUES (?, ?)` for rows of `SELECT id, name ...`.
+/// Rows are grouped into batches by values of given partition key columns and written in
+/// chunks of `chunk_size` rows, so the source does not need to fit in memory.
+pub async fn import_rows<T, M, E, I>(
+    session: &E,
+    writer: &BulkWriter,
+    partition_key: &[&str],
+    rows: I,
+    chunk_size: usize,
+) -> BulkWriteResult
+where
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    E: BatchExecutor<T, M> + PrepareExecutor<T, M>,
+    I: IntoIterator<Item = Row>,
+{
+    let rows = rows.into_iter().map(|row| {
+        let values = row.to_values();
+        (partition_key_of(&row, &values, partition_key), values)
+    });
+
+    writer.write_chunked(session, rows, chunk_size).await
+}
+
+/// Returns raw values of partition key columns, identifying the partition of a row.
+fn partition_key_of(row: &Row, values: &QueryValues, partition_key: &[&str]) -> Vec<Vec<u8>> {
+    let columns = row.column_names();
+
+    match values {
+        QueryValues::SimpleValues(values) => partition_key
+            .iter()
+            .filter_map(|name| columns.iter().position(|column| column == name))
+            .map(|index| values[index].body.clone())
+            .collect(),
+        QueryValues::NamedValues(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{
+        BodyResResultRows, ColSpec, ColType, ColTypeOption, RowsMetadata,
+    };
+    use crate::types::CString;
+
+    #[test]
+    fn token_ranges_cover_ring() {
+        let ranges = TableExport::new("t", &["pk"]).splits(3).token_ranges();
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].0, i64::MIN);
+        assert_eq!(ranges[2].1, i64::MAX);
+        for window in ranges.windows(2) {
+            assert_eq!(window[0].1 + 1, window[1].0);
+        }
+
+        assert_eq!(
+            TableExport::new("t", &["pk"]).splits(1).token_ranges(),
+            vec![(i64::MIN, i64::MAX)]
+        );
+    }
+
+    #[test]
+    fn partition_key_of_row() {
+        let col_spec = |name: &str| ColSpec {
+            ksname: None,
+            tablename: None,
+            name: CString::new(name.into()),
+            col_type: ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            },
+        };
+        let rows = Row::from_frame_body(BodyResResultRows {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 3,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![col_spec("ck"), col_spec("a"), col_spec("b")],
+            },
+            rows_count: 1,
+            rows_content: vec![vec![
+                CBytes::new(vec![1]),
+                CBytes::new(vec![2]),
+                CBytes::new(vec![3]),
+            ]],
+        });
+
+        let values = rows[0].to_values();
+        assert_eq!(
+            partition_key_of(&rows[0], &values, &["a", "b"]),
+            vec![vec![2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn range_query() {
+        assert_eq!(
+            TableExport::new("ks.t", &["a", "b"]).range_query(),
+            "SELECT * FROM ks.t WHERE token(a, b) >= ? AND token(a, b) <= ?"
+        );
+        assert_eq!(
+            TableExport::new("t", &["pk"])
+                .columns(&["pk", "value"])
+                .range_query(),
+            "SELECT pk, value FROM t WHERE token(pk) >= ? AND token(pk) <= ?"
+        );
+    }
+}
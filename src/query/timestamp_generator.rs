@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::query::{QueryBatch, QueryFlags, QueryParams, WriteTimestamp};
+
+/// Generator of client side write timestamps which are strictly increasing, even when called
+/// concurrently from many tasks or when the system clock goes back. Writes with equal
+/// timestamps are resolved by comparing values rather than by their order, so a timestamp
+/// colliding with the previous one is bumped by a microsecond.
+///
+/// Set as `SessionHooks::timestamp_generator`, it stamps all queries, executions and batches
+/// which don't have a default timestamp. It can be shared with other parts of an application,
+/// e.g. to stamp records sent to external systems consistently with the writes.
+#[derive(Debug, Default)]
+pub struct TimestampGenerator {
+    last: AtomicI64,
+}
+
+impl TimestampGenerator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns current time as a write timestamp, or the last returned timestamp incremented
+    /// by one if the clock hasn't advanced since.
+    pub fn next(&self) -> WriteTimestamp {
+        let now = WriteTimestamp::now().as_micros();
+        let mut last = self.last.load(Ordering::Relaxed);
+
+        loop {
+            let next = now.max(last + 1);
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return WriteTimestamp::from_micros(next),
+                Err(current) => last = current,
+            }
+        }
+    }
+
+    /// Sets default timestamp of given query parameters, unless they already have one.
+    pub(crate) fn stamp(&self, params: &mut QueryParams) {
+        if params.timestamp.is_none() {
+            params.timestamp = Some(self.next().as_micros());
+            params.flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+    }
+
+    /// Sets default timestamp of given batch, unless it already has one.
+    pub(crate) fn stamp_batch(&self, batch: &mut QueryBatch) {
+        if batch.timestamp.is_none() {
+            batch.timestamp = Some(self.next().as_micros());
+            batch.query_flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn increasing_after_clock_goes_back() {
+        let generator = TimestampGenerator::new();
+        let future = WriteTimestamp::now().as_micros() + 60_000_000;
+        generator.last.store(future, Ordering::Relaxed);
+
+        assert_eq!(generator.next().as_micros(), future + 1);
+        assert_eq!(generator.next().as_micros(), future + 2);
+    }
+
+    #[test]
+    fn unique_across_threads() {
+        let generator = Arc::new(TimestampGenerator::new());
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let generator = generator.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .map(|_| generator.next().as_micros())
+                        .collect::<Vec<i64>>()
+                })
+            })
+            .collect();
+
+        let mut timestamps = HashSet::new();
+        for thread in threads {
+            let thread_timestamps = thread.join().unwrap();
+            assert!(thread_timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+            timestamps.extend(thread_timestamps);
+        }
+
+        assert_eq!(timestamps.len(), 4000);
+    }
+
+    #[test]
+    fn keeps_explicit_timestamp() {
+        let generator = TimestampGenerator::new();
+
+        let mut params = QueryParams {
+            timestamp: Some(1),
+            ..Default::default()
+        };
+        generator.stamp(&mut params);
+        assert_eq!(params.timestamp, Some(1));
+
+        let mut params = QueryParams::default();
+        generator.stamp(&mut params);
+        assert!(params.timestamp.is_some());
+        assert!(params
+            .flags
+            .iter()
+            .any(|flag| matches!(flag, QueryFlags::WithDefaultTimestamp)));
+    }
+}
@@ -0,0 +1,77 @@
+/// Warning sent by the server when a read scanned more tombstones than
+/// `tombstone_warn_threshold`, e.g.
+/// `Read 10 live rows and 1500 tombstone cells for query SELECT * FROM ks.t (see tombstone_warn_threshold)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TombstoneWarning {
+    /// Number of live rows read.
+    pub live: u64,
+    /// Number of tombstone cells read.
+    pub tombstones: u64,
+    /// Table the query read from, if it could be determined from the warning.
+    pub table: Option<String>,
+}
+
+impl TombstoneWarning {
+    /// Parses a server warning. Returns `None` if the warning is not about tombstones.
+    pub fn parse(warning: &str) -> Option<Self> {
+        let rest = warning.trim_start().strip_prefix("Read ")?;
+        let (live, rest) = rest.split_once(" live rows and ")?;
+        let (tombstones, rest) = rest.split_once(" tombstone cells")?;
+
+        Some(TombstoneWarning {
+            live: live.trim().parse().ok()?,
+            tombstones: tombstones.trim().parse().ok()?,
+            table: Self::parse_table(rest),
+        })
+    }
+
+    fn parse_table(query: &str) -> Option<String> {
+        // ASCII uppercase keeps byte offsets intact
+        let start = query.to_ascii_uppercase().find(" FROM ")? + " FROM ".len();
+        query[start..]
+            .split_whitespace()
+            .next()
+            .map(|table| table.trim_end_matches(&[';', ','][..]))
+            .filter(|table| !table.is_empty())
+            .map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tombstone_warning() {
+        let warning = TombstoneWarning::parse(
+            "Read 10 live rows and 1500 tombstone cells for query SELECT * FROM ks.t WHERE id = 1 \
+             LIMIT 5000 (see tombstone_warn_threshold)",
+        )
+        .unwrap();
+
+        assert_eq!(warning.live, 10);
+        assert_eq!(warning.tombstones, 1500);
+        assert_eq!(warning.table.as_deref(), Some("ks.t"));
+    }
+
+    #[test]
+    fn parse_tombstone_warning_without_table() {
+        let warning =
+            TombstoneWarning::parse("Read 0 live rows and 1001 tombstone cells for query").unwrap();
+
+        assert_eq!(warning.tombstones, 1001);
+        assert_eq!(warning.table, None);
+    }
+
+    #[test]
+    fn ignore_other_warnings() {
+        assert_eq!(
+            TombstoneWarning::parse("Aggregation query used without partition key"),
+            None
+        );
+        assert_eq!(
+            TombstoneWarning::parse("Read many live rows and 5 tombstone cells"),
+            None
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::frame::Frame;
+use crate::query::{QueryDefaults, QueryParams};
+
+/// Hook notified about tracing ids of traced requests, both sampled according to
+/// `QueryDefaults::tracing_sample_rate` and explicitly traced ones, e.g. to continuously collect
+/// server side traces. Tracing sessions can be read from `system_traces` tables.
+pub trait TracingListener: Send + Sync {
+    /// Called with given statement, tracing id returned by the server and the coordinator which
+    /// executed the request.
+    fn on_trace(&self, query: &str, tracing_id: Uuid, coordinator: Option<SocketAddr>);
+}
+
+impl<F: Fn(&str, Uuid, Option<SocketAddr>) + Send + Sync> TracingListener for F {
+    fn on_trace(&self, query: &str, tracing_id: Uuid, coordinator: Option<SocketAddr>) {
+        self(query, tracing_id, coordinator)
+    }
+}
+
+impl fmt::Debug for dyn TracingListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TracingListener")
+    }
+}
+
+/// Decides if a request should be traced: explicitly requested tracing always wins, then the
+/// per-query override, then session level sampling.
+#[cfg(feature = "runtime")]
+pub(crate) fn should_trace(
+    defaults: &QueryDefaults,
+    params: &QueryParams,
+    with_tracing: bool,
+) -> bool {
+    with_tracing || params.tracing.unwrap_or_else(|| defaults.sample_tracing())
+}
+
+/// Notifies listeners about the tracing id of a response, if it has one.
+pub(crate) fn notify_trace(listeners: &[Arc<dyn TracingListener>], query: &str, frame: &Frame) {
+    if let Some(tracing_id) = frame.tracing_id {
+        for listener in listeners {
+            listener.on_trace(query, tracing_id, frame.coordinator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Opcode, Version};
+    use std::sync::Mutex;
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn per_query_override() {
+        let always = QueryDefaults {
+            tracing_sample_rate: 1.0,
+            ..Default::default()
+        };
+        let never = QueryDefaults::default();
+
+        let mut params = QueryParams::default();
+        assert!(should_trace(&always, &params, false));
+        assert!(!should_trace(&never, &params, false));
+        assert!(should_trace(&never, &params, true));
+
+        params.tracing = Some(false);
+        assert!(!should_trace(&always, &params, false));
+
+        params.tracing = Some(true);
+        assert!(should_trace(&never, &params, false));
+    }
+
+    #[test]
+    fn notifies_listeners() {
+        let traces = Arc::new(Mutex::new(vec![]));
+        let recorded = traces.clone();
+        let listener: Arc<dyn TracingListener> =
+            Arc::new(move |query: &str, tracing_id, _coordinator| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((query.to_string(), tracing_id));
+            });
+
+        let tracing_id = Uuid::from_u128(1);
+        let mut frame = Frame::new(
+            Version::Response,
+            vec![],
+            Opcode::Result,
+            vec![],
+            None,
+            vec![],
+        );
+        notify_trace(&[listener.clone()], "SELECT 1", &frame);
+        assert!(traces.lock().unwrap().is_empty());
+
+        frame.tracing_id = Some(tracing_id);
+        notify_trace(&[listener], "SELECT 1", &frame);
+        assert_eq!(
+            *traces.lock().unwrap(),
+            vec![("SELECT 1".to_string(), tracing_id)]
+        );
+    }
+}
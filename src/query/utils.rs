@@ -1,13 +1,59 @@
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Instant;
 use tokio::sync::Mutex;
+use tokio::time::{timeout, timeout_at};
 
-use crate::cluster::{GetCompressor, GetConnection, ResponseCache};
+use crate::cluster::{
+    report_node_failure, ConnectionPool, GetCompressor, GetConnection, GetQueryDefaults,
+    GetSessionHooks, ResponseCache,
+};
+use crate::consistency::Consistency;
 use crate::error;
+use crate::frame::frame_response::ResponseBody;
 use crate::frame::frame_result::ResultKind;
-use crate::frame::parser::from_connection;
-use crate::frame::{Flag, Frame, FromBytes, Opcode, StreamId};
-use crate::transport::CDRSTransport;
+use crate::frame::parser::{convert_frame_into_result, frame_from_bytes, read_from_transport};
+use crate::frame::{
+    AsBytes, Flag, Frame, FromBytes, Opcode, ProtocolVersion, StreamId, TryFromRow,
+};
+use crate::query::{notify_coordinator, ConsistencyFallback, QueryResponse};
+use crate::transport::{write_frame, CDRSTransport, ResponseRoutes};
+use crate::types::registry::TypeRegistry;
+use crate::types::rows::Row;
 use crate::types::INT_LEN;
 
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs given future, e.g. handling an upstream request, with an absolute deadline applying to
+/// all requests it sends. Requests past the deadline fail immediately with
+/// `Error::DeadlineExceeded`, others time out when it passes, regardless of the default request
+/// timeout. Nested deadlines can only shorten outer ones.
+pub async fn with_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    let deadline = current_deadline().map_or(deadline, |outer| outer.min(deadline));
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Runs given future with the deadline of a request, if it has any.
+pub(crate) async fn with_request_deadline<F: Future>(
+    deadline: Option<Instant>,
+    future: F,
+) -> F::Output {
+    match deadline {
+        Some(deadline) => with_deadline(deadline, future).await,
+        None => future.await,
+    }
+}
+
+fn current_deadline() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
 pub fn prepare_flags(with_tracing: bool, with_warnings: bool) -> Vec<Flag> {
     let mut flags = vec![];
 
@@ -22,63 +68,651 @@ pub fn prepare_flags(with_tracing: bool, with_warnings: bool) -> Vec<Flag> {
     flags
 }
 
-pub async fn send_frame<S: ?Sized, T, M>(
+/// Extracts rows contained in a result frame.
+pub fn frame_rows(frame: Frame) -> error::Result<Vec<Row>> {
+    rows_of(&frame)
+}
+
+/// Extracts rows contained in a result frame, keeping the frame.
+pub fn rows_of(frame: &Frame) -> error::Result<Vec<Row>> {
+    frame
+        .get_body()?
+        .into_rows()
+        .ok_or_else(|| error::Error::from("Response does not contain rows"))
+}
+
+/// Attaches registry of conversions of a session to rows it converts.
+pub(crate) fn with_type_registry(rows: Vec<Row>, registry: Option<&Arc<TypeRegistry>>) -> Vec<Row> {
+    match registry {
+        Some(registry) => rows
+            .into_iter()
+            .map(|row| row.with_type_registry(registry.clone()))
+            .collect(),
+        None => rows,
+    }
+}
+
+/// Extracts rows contained in a result frame like `rows_of`, with registry of conversions of
+/// a session attached.
+pub(crate) fn session_rows_of(
+    frame: &Frame,
+    registry: Option<&Arc<TypeRegistry>>,
+) -> error::Result<Vec<Row>> {
+    rows_of(frame).map(|rows| with_type_registry(rows, registry))
+}
+
+/// Converts rows contained in a result frame into values of type `R`, with registry of
+/// conversions of a session attached.
+pub(crate) fn session_typed_rows_of<R: TryFromRow>(
+    frame: &Frame,
+    registry: Option<&Arc<TypeRegistry>>,
+) -> error::Result<Vec<R>> {
+    session_rows_of(frame, registry)?
+        .into_iter()
+        .map(R::try_from_row)
+        .collect()
+}
+
+/// Builds a request frame encoded with the protocol version of the connection it is sent over.
+pub(crate) type BuildFrame<'a> = dyn Fn(ProtocolVersion) -> error::Result<Frame> + Send + Sync + 'a;
+
+pub async fn send_frame<S: ?Sized, T, M>(sender: &S, build: &BuildFrame<'_>) -> error::Result<Frame>
+where
+    S: GetConnection<T, M>
+        + GetCompressor
+        + GetQueryDefaults
+        + GetSessionHooks
+        + ResponseCache
+        + Sync,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    send_frame_in_keyspace(sender, build, KeyspaceCheck::Skip)
+        .await
+        .map(|(frame, _)| frame)
+}
+
+/// Handling of the keyspace used by the connection a frame is sent over.
+#[derive(Clone, Copy, Debug)]
+pub enum KeyspaceCheck<'a> {
+    /// The keyspace is neither read nor checked.
+    Skip,
+    /// The keyspace is read, e.g. to record the keyspace a statement is prepared in.
+    Record,
+    /// The frame is sent only if the connection uses given keyspace, or none if it is `None`,
+    /// when `QueryDefaults::check_prepared_keyspace` is enabled.
+    Expect(Option<&'a str>),
+}
+
+/// Sends a frame like `send_frame`, additionally returning the keyspace used by the connection
+/// unless `keyspace` is `KeyspaceCheck::Skip`.
+pub async fn send_frame_in_keyspace<S, T, M>(
     sender: &S,
+    build: &BuildFrame<'_>,
+    keyspace: KeyspaceCheck<'_>,
+) -> error::Result<(Frame, Option<String>)>
+where
+    S: GetConnection<T, M>
+        + GetCompressor
+        + GetQueryDefaults
+        + GetSessionHooks
+        + ResponseCache
+        + Sync
+        + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    send_frame_routed(sender, build, keyspace, None).await
+}
+
+/// Partition addressed by a request, for token aware routing.
+#[derive(Clone, Copy, Debug)]
+pub struct Routing<'a> {
+    pub keyspace: &'a str,
+    pub routing_key: &'a [u8],
+    /// Whether the request goes to the primary replica while it is up, e.g. a lightweight
+    /// transaction. Other requests are spread across the replicas.
+    pub primary_first: bool,
+}
+
+/// Sends a frame like `send_frame_in_keyspace`. If `routing` is given, the frame is sent to
+/// a replica of the addressed partition. Requests rejected
+/// by an overloaded or bootstrapping node are retried on the next node, up to
+/// `QueryDefaults::overload_retries` times.
+pub async fn send_frame_routed<S, T, M>(
+    sender: &S,
+    build: &BuildFrame<'_>,
+    keyspace: KeyspaceCheck<'_>,
+    routing: Option<Routing<'_>>,
+) -> error::Result<(Frame, Option<String>)>
+where
+    S: GetConnection<T, M>
+        + GetCompressor
+        + GetQueryDefaults
+        + GetSessionHooks
+        + ResponseCache
+        + Sync
+        + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let mut retries = sender.query_defaults().overload_retries;
+    let mut node = pick_node(sender, routing)
+        .await
+        .ok_or_else(|| error::Error::from("Unable to get transport"))?;
+
+    loop {
+        let result = send_frame_to_node(sender, &node, build, keyspace, true).await;
+        match result {
+            Err(error::Error::Server(ref error))
+                if retries > 0 && (error.is_overloaded() || error.is_bootstrapping()) =>
+            {
+                // the rejected node is cooling down now, so the next pick prefers another one
+                match pick_node(sender, routing).await {
+                    Some(next) if !Arc::ptr_eq(&next, &node) => {
+                        debug!(
+                            "Retrying request rejected by {} on {}",
+                            node.get_addr(),
+                            next.get_addr()
+                        );
+                        retries -= 1;
+                        node = next;
+                    }
+                    _ => return result,
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn pick_node<S, T, M>(
+    sender: &S,
+    routing: Option<Routing<'_>>,
+) -> Option<Arc<ConnectionPool<M>>>
+where
+    S: GetConnection<T, M> + Sync + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    match routing {
+        Some(routing) if routing.primary_first => {
+            sender
+                .get_primary_replica_connection(routing.keyspace, routing.routing_key)
+                .await
+        }
+        Some(routing) => {
+            sender
+                .get_routed_connection(routing.keyspace, routing.routing_key)
+                .await
+        }
+        None => sender.get_connection().await,
+    }
+}
+
+/// Sends a frame like `send_frame`, but instead of waiting for a connection fails with
+/// `Error::PoolExhausted` if all connections to the picked node are in use. Overloaded error
+/// responses are returned as `Error::Overloaded`.
+pub async fn try_send_frame<S, T, M>(sender: &S, build: &BuildFrame<'_>) -> error::Result<Frame>
+where
+    S: GetConnection<T, M>
+        + GetCompressor
+        + GetQueryDefaults
+        + GetSessionHooks
+        + ResponseCache
+        + Sync
+        + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let node = sender
+        .get_connection()
+        .await
+        .ok_or_else(|| error::Error::from("Unable to get transport"))?;
+
+    match send_frame_to_node(sender, &node, build, KeyspaceCheck::Skip, false).await {
+        Err(error::Error::Server(ref error)) if error.is_overloaded() => {
+            Err(error::Error::Overloaded(node.get_addr()))
+        }
+        result => result.map(|(frame, _)| frame),
+    }
+}
+
+async fn send_frame_to_node<S, T, M>(
+    sender: &S,
+    node: &Arc<ConnectionPool<M>>,
+    build: &BuildFrame<'_>,
+    keyspace: KeyspaceCheck<'_>,
+    wait: bool,
+) -> error::Result<(Frame, Option<String>)>
+where
+    S: GetCompressor + GetQueryDefaults + GetSessionHooks + ResponseCache + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+{
+    let query_defaults = sender.query_defaults();
+
+    let deadline = current_deadline();
+    if matches!(deadline, Some(deadline) if deadline <= Instant::now()) {
+        return Err(error::Error::DeadlineExceeded);
+    }
+
+    // without waiting, requests fail if all connections are in use
+    let checkout = async {
+        if wait {
+            node.checkout().await.map(Some)
+        } else {
+            node.try_checkout().await
+        }
+    };
+
+    let wait_started = Instant::now();
+    let pool = match deadline {
+        Some(deadline) => timeout_at(deadline.into(), checkout).await,
+        None => Ok(checkout.await),
+    };
+    node.wait_times().record(wait_started.elapsed());
+
+    let pool = pool.map_err(|_| error::Error::DeadlineExceeded)?;
+
+    let pool = pool.map_err(|error| {
+        report_node_failure(node);
+        match error {
+            bb8::RunError::User(error) => error,
+            bb8::RunError::TimedOut => {
+                io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for connection").into()
+            }
+        }
+    })?;
+    let pool = pool.ok_or_else(|| error::Error::PoolExhausted(node.get_addr()))?;
+
+    let current_keyspace = match keyspace {
+        KeyspaceCheck::Skip => None,
+        KeyspaceCheck::Record => pool.lock().await.current_keyspace().await,
+        KeyspaceCheck::Expect(_) if !query_defaults.check_prepared_keyspace => None,
+        KeyspaceCheck::Expect(keyspace) => {
+            let current_keyspace = pool.lock().await.current_keyspace().await;
+            if current_keyspace.as_deref() != keyspace {
+                return Err(error::Error::from(format!(
+                    "Statement prepared in keyspace {} cannot be executed in keyspace {}",
+                    keyspace.unwrap_or("<none>"),
+                    current_keyspace.as_deref().unwrap_or("<none>")
+                )));
+            }
+
+            current_keyspace
+        }
+    };
+
+    let (lwt_metadata_mask, protocol_version) = {
+        let transport = pool.lock().await;
+        (transport.lwt_metadata_mask(), transport.protocol_version())
+    };
+
+    let frame = build(protocol_version)?;
+    let stream_id = frame.stream;
+    let frame_bytes = if frame.flags.contains(&Flag::Compression) {
+        frame.encode_with(sender.get_compressor())?
+    } else {
+        frame.as_bytes()
+    };
+
+    // the deadline takes precedence over the request timeout if it passes first
+    let deadline_timeout =
+        deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+    let deadline_first = match (deadline_timeout, query_defaults.request_timeout) {
+        (Some(deadline_timeout), Some(request_timeout)) => deadline_timeout <= request_timeout,
+        (deadline_timeout, _) => deadline_timeout.is_some(),
+    };
+    let request_timeout = if deadline_first {
+        deadline_timeout
+    } else {
+        query_defaults.request_timeout
+    };
+
+    let sent_at = Instant::now();
+    let result = match request_timeout {
+        Some(request_timeout) => {
+            match timeout(
+                request_timeout,
+                exchange_frame(sender, &*pool, frame_bytes, stream_id),
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(_) => {
+                    // the response might be partially read, so the connection cannot be
+                    // reused, unless its responses are routed and the stream is orphaned
+                    let mut transport = pool.lock().await;
+                    if transport.response_routes().is_none() {
+                        let _ = transport.close(net::Shutdown::Both).await;
+                    }
+
+                    if deadline_first {
+                        Err(error::Error::DeadlineExceeded)
+                    } else {
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "Request timed out").into())
+                    }
+                }
+            }
+        }
+        None => exchange_frame(sender, &*pool, frame_bytes, stream_id).await,
+    };
+
+    notify_coordinator(
+        &sender.hooks().coordinator_listeners,
+        node.get_addr(),
+        sent_at.elapsed(),
+        &result,
+    );
+
+    match result {
+        Err(error::Error::Io(_)) => report_node_failure(node),
+        Err(error::Error::Server(ref error))
+            if error.is_overloaded() || error.is_bootstrapping() =>
+        {
+            node.health().report_success();
+            if node.health().report_overloaded(error.is_bootstrapping()) {
+                warn!(
+                    "Node {} replied it is {}, deprioritizing it",
+                    node.get_addr(),
+                    if error.is_bootstrapping() {
+                        "bootstrapping"
+                    } else {
+                        "overloaded"
+                    }
+                );
+            }
+        }
+        _ => node.health().report_success(),
+    }
+
+    if let (Some(warning_log), Ok(frame)) = (sender.hooks().warning_log.as_ref(), &result) {
+        warning_log.observe(&frame.warnings, Some(node.get_addr()));
+    }
+
+    result.map(|mut frame| {
+        frame.coordinator = Some(node.get_addr());
+        frame.lwt_metadata_mask = lwt_metadata_mask;
+        (frame, current_keyspace)
+    })
+}
+
+async fn exchange_frame<S, T>(
+    sender: &S,
+    pool: &Mutex<T>,
     frame_bytes: Vec<u8>,
     stream_id: StreamId,
 ) -> error::Result<Frame>
 where
-    S: GetConnection<T, M> + GetCompressor + ResponseCache,
+    S: GetCompressor + ResponseCache + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+{
+    let response_routes = pool.lock().await.response_routes();
+    let frame = match response_routes {
+        Some(response_routes) => {
+            exchange_routed_frame(sender, pool, &response_routes, frame_bytes, stream_id).await
+        }
+        None => exchange_exclusive_frame(sender, pool, frame_bytes, stream_id).await,
+    };
+
+    // server errors are received in well-formed frames, which are matched by their stream
+    // like other responses before being converted into errors
+    let frame = convert_frame_into_result(frame?)?;
+
+    // in case we get a SetKeyspace result, we need to store current keyspace
+    // checks are done manually for speed
+    if frame.opcode == Opcode::Result {
+        let result_kind = ResultKind::from_bytes(&frame.body[..INT_LEN])?;
+        if result_kind == ResultKind::SetKeyspace {
+            let response_body = frame.get_body()?;
+            let set_keyspace = response_body
+                .into_set_keyspace()
+                .expect("SetKeyspace not found with SetKeyspace opcode!");
+
+            let transport = pool.lock().await;
+            transport
+                .set_current_keyspace(set_keyspace.body.as_str())
+                .await;
+        }
+    }
+
+    Ok(frame)
+}
+
+/// Sends a frame over a connection whose reader task routes the response to the request, so
+/// the connection can carry other requests at the same time.
+async fn exchange_routed_frame<S, T>(
+    sender: &S,
+    pool: &Mutex<T>,
+    response_routes: &Arc<ResponseRoutes>,
+    frame_bytes: Vec<u8>,
+    stream_id: StreamId,
+) -> error::Result<Frame>
+where
+    S: GetCompressor + ?Sized,
+    T: CDRSTransport + Unpin + 'static,
+{
+    // registered first, since the response can arrive before the write completes
+    let mut response = response_routes.register(stream_id)?;
+    if let Err(error) = write_frame(pool, frame_bytes.as_slice()).await {
+        response.cancel();
+        return Err(error.into());
+    }
+
+    let frame_bytes = response.recv().await?;
+    let (compression_stats, max_decompressed_length) = {
+        let transport = pool.lock().await;
+        (
+            transport.compression_stats(),
+            transport.max_decompressed_length(),
+        )
+    };
+
+    match frame_from_bytes(
+        frame_bytes,
+        sender.get_compressor(),
+        max_decompressed_length,
+        compression_stats.as_deref(),
+    ) {
+        Ok(frame) => Ok(frame),
+        Err(error) => {
+            let _ = pool.lock().await.close(net::Shutdown::Both).await;
+            Err(error::Error::ConnectionPoisoned(Box::new(error)))
+        }
+    }
+}
+
+/// Sends a frame over a connection used exclusively by the request, reading frames from it until
+/// the response arrives.
+async fn exchange_exclusive_frame<S, T>(
+    sender: &S,
+    pool: &Mutex<T>,
+    frame_bytes: Vec<u8>,
+    stream_id: StreamId,
+) -> error::Result<Frame>
+where
+    S: GetCompressor + ResponseCache + ?Sized,
     T: CDRSTransport + Unpin + 'static,
-    M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
 {
     let compression = sender.get_compressor();
 
-    let transport = sender
-        .get_connection()
-        .await
-        .ok_or_else(|| error::Error::from("Unable to get transport"))?
-        .get_pool();
+    write_frame(pool, frame_bytes.as_slice()).await?;
+    let pending = PendingResponse { pool, read: false };
 
-    let pool = transport
-        .get()
-        .await
-        .map_err(|error| error::Error::from(error.to_string()))?;
+    loop {
+        let frame = match read_from_transport(pool, compression).await {
+            Ok(frame) => frame,
+            Err(error) => {
+                // the stream is left at an unknown position, so the connection can't be reused
+                pending.finish();
+                let _ = pool.lock().await.close(net::Shutdown::Both).await;
+                return Err(match error {
+                    error::Error::Io(error) => error::Error::Io(error),
+                    error => error::Error::ConnectionPoisoned(Box::new(error)),
+                });
+            }
+        };
+        let frame_stream = frame.stream;
+        let frame = sender.match_or_cache_response(stream_id, frame).await;
 
-    pool.lock()
-        .await
-        .write_all(frame_bytes.as_slice())
-        .await
-        .map_err(error::Error::from)?;
+        // connections are used exclusively, so responses to other streams belong to requests
+        // abandoned before their responses were read
+        if frame_stream != stream_id {
+            pool.lock().await.release_orphaned_stream();
+        }
+
+        if let Some(frame) = frame {
+            pending.finish();
+            return Ok(frame);
+        }
+    }
+}
+
+/// Sends a request with the requested consistency, then with consistency levels from `fallback`
+/// while the coordinator reports too few alive replicas. `send` is given the consistency level
+/// to downgrade to, if any. The response tells which consistency the request succeeded with,
+/// if it was downgraded.
+pub(crate) async fn with_consistency_fallback<F, R>(
+    fallback: &ConsistencyFallback,
+    mut send: F,
+) -> error::Result<QueryResponse<ResponseBody>>
+where
+    F: FnMut(Option<Consistency>) -> R,
+    R: Future<Output = error::Result<Frame>>,
+{
+    let mut levels = fallback.levels().iter();
+    let mut downgraded_consistency = None;
 
     loop {
-        let frame = from_connection(&pool, compression).await?;
-        if let Some(frame) = sender.match_or_cache_response(stream_id, frame).await {
-            // in case we get a SetKeyspace result, we need to store current keyspace
-            // checks are done manually for speed
-            if frame.opcode == Opcode::Result {
-                let result_kind = ResultKind::from_bytes(&frame.body[..INT_LEN])?;
-                if result_kind == ResultKind::SetKeyspace {
-                    let response_body = frame.get_body()?;
-                    let set_keyspace = response_body
-                        .into_set_keyspace()
-                        .expect("SetKeyspace not found with SetKeyspace opcode!");
-
-                    let transport = pool.lock().await;
-                    transport
-                        .set_current_keyspace(set_keyspace.body.as_str())
-                        .await;
+        let result = send(downgraded_consistency).await;
+
+        if let Err(error::Error::Server(error)) = &result {
+            if error.is_unavailable() {
+                if let Some(&consistency) = levels.next() {
+                    downgraded_consistency = Some(consistency);
+                    continue;
                 }
             }
+        }
 
-            return Ok(frame);
+        return result
+            .and_then(QueryResponse::from_frame)
+            .map(|mut response| {
+                response.downgraded_consistency = downgraded_consistency;
+                response
+            });
+    }
+}
+
+/// Response to a request written to a connection. If the request is abandoned, e.g. because it
+/// timed out, before the response is read, its stream is recorded as orphaned until the response
+/// is drained by a later request.
+struct PendingResponse<'a, T: CDRSTransport> {
+    pool: &'a Mutex<T>,
+    read: bool,
+}
+
+impl<T: CDRSTransport> PendingResponse<'_, T> {
+    fn finish(mut self) {
+        self.read = true;
+    }
+}
+
+impl<T: CDRSTransport> Drop for PendingResponse<'_, T> {
+    fn drop(&mut self) {
+        if self.read {
+            return;
+        }
+
+        // connections are used exclusively, so the lock is only held by the abandoned request
+        if let Ok(transport) = self.pool.try_lock() {
+            transport.record_orphaned_stream();
         }
     }
 }
 
+/// Runs given futures with at most `concurrency` of them in progress at once, returning their
+/// outputs in order. Futures are polled within the calling task, so they can borrow from it.
+pub(crate) async fn join_bounded<F, I>(futures: I, concurrency: usize) -> Vec<F::Output>
+where
+    I: IntoIterator<Item = F>,
+    F: Future + Unpin,
+{
+    let mut futures = futures.into_iter().enumerate();
+    let mut outputs: Vec<Option<F::Output>> = vec![];
+    let mut running: Vec<(usize, F)> = Vec::with_capacity(concurrency.max(1));
+
+    loop {
+        while running.len() < concurrency.max(1) {
+            match futures.next() {
+                Some((index, future)) => {
+                    outputs.push(None);
+                    running.push((index, future));
+                }
+                None => break,
+            }
+        }
+
+        if running.is_empty() {
+            break;
+        }
+
+        poll_fn(|cx| {
+            let before = running.len();
+            running.retain_mut(|(index, future)| match Pin::new(future).poll(cx) {
+                Poll::Ready(output) => {
+                    outputs[*index] = Some(output);
+                    false
+                }
+                Poll::Pending => true,
+            });
+
+            if running.len() < before {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
+    outputs
+        .into_iter()
+        .map(|output| output.expect("all futures are completed"))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn join_bounded_keeps_order_and_limit() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let outputs = join_bounded(
+            (0..10u64).map(|i| {
+                let running = &running;
+                let max_running = &max_running;
+                Box::pin(async move {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10 - i)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                })
+            }),
+            3,
+        )
+        .await;
+
+        assert_eq!(outputs, (0..10).collect::<Vec<u64>>());
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    }
 
     #[test]
     fn prepare_flags_test() {
@@ -89,4 +723,214 @@ mod test {
             vec![Flag::Tracing, Flag::Warning]
         );
     }
+
+    #[test]
+    fn session_typed_rows_of_void() {
+        struct Never;
+
+        impl TryFromRow for Never {
+            fn try_from_row(_: Row) -> error::Result<Self> {
+                unreachable!()
+            }
+        }
+
+        let frame = Frame::new(
+            crate::frame::Version::Response,
+            vec![],
+            Opcode::Result,
+            vec![0, 0, 0, 1],
+            None,
+            vec![],
+        );
+        assert!(session_typed_rows_of::<Never>(&frame, None).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn abandoned_requests_are_orphaned_until_drained() {
+        use crate::cluster::test_node::{void, TestNode};
+        use crate::cluster::DirectSession;
+        use crate::query::QueryExecutor;
+
+        // blocks the node's worker thread, so responses arrive late
+        fn slow(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            std::thread::sleep(Duration::from_millis(100));
+            void(opcode, body)
+        }
+
+        let node = TestNode::start_with(slow).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let connection = session.get_connection().await.unwrap();
+        let orphaned_streams = || async {
+            let checkout = connection.checkout().await.unwrap();
+            let orphaned_streams = checkout.lock().await.orphaned_streams();
+            orphaned_streams
+        };
+
+        // cancelled by the caller, unlike requests timing out, which close the connection
+        let result = timeout(Duration::from_millis(20), session.query("SELECT * FROM t")).await;
+        assert!(result.is_err());
+        assert_eq!(orphaned_streams().await, 1);
+
+        // the late response is drained by the next request
+        session.query("SELECT * FROM t").await.unwrap();
+        assert_eq!(orphaned_streams().await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn late_errors_of_orphaned_streams_are_drained() {
+        use crate::cluster::test_node::{void, TestNode};
+        use crate::cluster::DirectSession;
+        use crate::query::QueryExecutor;
+
+        // blocks the node's worker thread, so the error arrives after the query is abandoned
+        fn slow_error(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            if body.windows(4).any(|window| window == b"fail") {
+                std::thread::sleep(Duration::from_millis(100));
+                Some((
+                    Opcode::Error,
+                    vec![0, 0, 0, 0, 0, 4, b'f', b'a', b'i', b'l'],
+                ))
+            } else {
+                void(opcode, body)
+            }
+        }
+
+        let node = TestNode::start_with(slow_error).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+
+        let result = timeout(Duration::from_millis(20), session.query("SELECT fail")).await;
+        assert!(result.is_err());
+
+        // the late error belongs to the abandoned query, not to the next one
+        session.query("SELECT * FROM t").await.unwrap();
+
+        let connection = session.get_connection().await.unwrap();
+        let checkout = connection.checkout().await.unwrap();
+        assert_eq!(checkout.lock().await.orphaned_streams(), 0);
+    }
+
+    #[tokio::test]
+    async fn consistency_fallback() {
+        use crate::cluster::test_node::{void, TestNode};
+        use crate::cluster::DirectSession;
+        use crate::frame::AsBytes;
+        use crate::query::{
+            Query, QueryExecutor, QueryParamsBuilder, Statement, StatementExecutor,
+        };
+
+        fn unavailable_on_quorum(opcode: Opcode, body: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            let query_len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+            let consistency = &body[4 + query_len..6 + query_len];
+            if consistency == Consistency::Quorum.as_bytes().as_slice() {
+                let mut error = vec![0, 0, 0x10, 0, 0, 0];
+                error.extend_from_slice(consistency);
+                error.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 1]);
+                Some((Opcode::Error, error))
+            } else {
+                void(opcode, body)
+            }
+        }
+
+        let node = TestNode::start_with(unavailable_on_quorum).await;
+        let session = DirectSession::connect(node.addr).await.unwrap();
+        let params = QueryParamsBuilder::new()
+            .consistency(Consistency::Quorum)
+            .finalize();
+
+        match session
+            .query_with_fallback("SELECT * FROM t", params.clone(), &Default::default())
+            .await
+        {
+            Err(error::Error::Server(error)) => assert!(error.is_unavailable()),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let fallback = ConsistencyFallback::new().then(Consistency::One);
+        let response = session
+            .query_with_fallback("SELECT * FROM t", params.clone(), &fallback)
+            .await
+            .unwrap();
+        assert_eq!(response.downgraded_consistency, Some(Consistency::One));
+
+        let statement = Statement::Simple(Query {
+            query: "SELECT * FROM t".into(),
+            params,
+        });
+        let response = session
+            .execute_with_fallback(statement, &fallback)
+            .await
+            .unwrap();
+        assert_eq!(response.downgraded_consistency, Some(Consistency::One));
+    }
+
+    #[tokio::test]
+    async fn request_timeout() {
+        use crate::authenticators::NoneAuthenticator;
+        use crate::cluster::test_node::TestNode;
+        use crate::cluster::{DirectSession, NodeTcpConfigBuilder};
+        use crate::compression::Compression;
+        use crate::query::{QueryDefaults, QueryExecutor};
+
+        fn unanswered(_: Opcode, _: &[u8]) -> Option<(Opcode, Vec<u8>)> {
+            None
+        }
+
+        let node = TestNode::start_with(unanswered).await;
+        let node_config = NodeTcpConfigBuilder::new(node.addr, Arc::new(NoneAuthenticator)).build();
+        let query_defaults = QueryDefaults {
+            request_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let session =
+            DirectSession::connect_with_config(node_config, Compression::None, query_defaults)
+                .await
+                .unwrap();
+
+        match session.query("SELECT * FROM t").await {
+            Err(error::Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::TimedOut),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_frame_poisons_connection() {
+        use crate::cluster::test_node::{handshake, response_frame, Reply, Request, TestNode};
+        use crate::cluster::DirectSession;
+        use crate::query::QueryExecutor;
+        use std::sync::atomic::AtomicBool;
+
+        // first query of the node is answered with an oversized frame header
+        let malformed_sent = AtomicBool::new(false);
+        let node = TestNode::start_scripted(Arc::new(move |request: &Request| {
+            if let Some(reply) = handshake(&request.opcode) {
+                return vec![reply];
+            }
+
+            let mut response = response_frame(
+                request.version,
+                request.stream,
+                Opcode::Result,
+                &[0, 0, 0, 1],
+            );
+            if !malformed_sent.swap(true, Ordering::SeqCst) {
+                response.truncate(5);
+                response.extend_from_slice(&u32::MAX.to_be_bytes());
+            }
+            vec![Reply::Raw(response)]
+        }))
+        .await;
+
+        let addr = node.addr;
+        let session = DirectSession::connect(addr).await.unwrap();
+        match session.query("SELECT * FROM t").await {
+            Err(error::Error::ConnectionPoisoned(_)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // the poisoned connection is closed rather than reused
+        match session.query("SELECT * FROM t").await {
+            Err(error::Error::Io(_)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
 }
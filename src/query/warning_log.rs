@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::query::{normalize_query, TombstoneWarning};
+
+/// Logs warnings sent by the server, rate-limiting similar ones, so that e.g. tombstone
+/// warnings of queries executed in a tight loop don't flood the log. Warnings are similar if
+/// they have the same text after normalization like `normalize_query`; tombstone warnings are
+/// similar if they concern the same table. Of similar warnings, at most `burst` are logged per
+/// window and the number of suppressed ones is logged with the first warning of the next
+/// window.
+///
+/// Set as `SessionHooks::warning_log`, it logs warnings of all responses.
+#[derive(Debug)]
+pub struct WarningLog {
+    window: Duration,
+    burst: u32,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    window_start: Instant,
+    logged: u32,
+    suppressed: u64,
+}
+
+impl Default for WarningLog {
+    fn default() -> Self {
+        WarningLog {
+            window: Self::DEFAULT_WINDOW,
+            burst: Self::DEFAULT_BURST,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl WarningLog {
+    const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+    const DEFAULT_BURST: u32 = 1;
+
+    /// Number of distinct warnings tracked before the state is reset.
+    const MAX_TRACKED_WARNINGS: usize = 10_000;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the window similar warnings are counted in.
+    /// Defaults to 60 seconds.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets how many similar warnings are logged per window.
+    /// Defaults to 1.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Logs given warnings of a response from given coordinator, unless they are suppressed.
+    pub(crate) fn observe(&self, warnings: &[String], coordinator: Option<SocketAddr>) {
+        let now = Instant::now();
+        for warning in warnings {
+            if let Some(message) = self.message(warning, now) {
+                match coordinator {
+                    Some(coordinator) => warn!("Warning from {}: {}", coordinator, message),
+                    None => warn!("Server warning: {}", message),
+                }
+            }
+        }
+    }
+
+    /// Returns the message to log for given warning, or `None` if it should be suppressed.
+    fn message(&self, warning: &str, now: Instant) -> Option<String> {
+        let key = match TombstoneWarning::parse(warning) {
+            Some(TombstoneWarning {
+                table: Some(table), ..
+            }) => format!("tombstones {}", table),
+            _ => normalize_query(warning),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= Self::MAX_TRACKED_WARNINGS && !entries.contains_key(&key) {
+            entries.clear();
+        }
+
+        let entry = entries.entry(key).or_insert(Entry {
+            window_start: now,
+            logged: 0,
+            suppressed: 0,
+        });
+
+        let mut suppressed = 0;
+        if now.duration_since(entry.window_start) >= self.window {
+            suppressed = entry.suppressed;
+            *entry = Entry {
+                window_start: now,
+                logged: 0,
+                suppressed: 0,
+            };
+        }
+
+        if entry.logged >= self.burst {
+            entry.suppressed += 1;
+            return None;
+        }
+
+        entry.logged += 1;
+        Some(if suppressed > 0 {
+            format!("{} ({} similar warnings suppressed)", warning, suppressed)
+        } else {
+            warning.to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOMBSTONES: &str =
+        "Read 10 live rows and 1500 tombstone cells for query SELECT * FROM ks.t LIMIT 100";
+
+    #[test]
+    fn suppresses_similar_warnings_in_window() {
+        let log = WarningLog::new().window(Duration::from_secs(10)).burst(2);
+        let start = Instant::now();
+
+        assert!(log.message(TOMBSTONES, start).is_some());
+        assert!(log
+            .message(
+                "Read 2 live rows and 1001 tombstone cells for query SELECT * FROM ks.t",
+                start
+            )
+            .is_some());
+        assert!(log.message(TOMBSTONES, start).is_none());
+        assert!(log
+            .message(&TOMBSTONES.replace("ks.t", "ks.u"), start)
+            .is_some());
+
+        assert_eq!(
+            log.message(TOMBSTONES, start + Duration::from_secs(10)),
+            Some(format!("{} (1 similar warnings suppressed)", TOMBSTONES))
+        );
+    }
+
+    #[test]
+    fn normalizes_other_warnings() {
+        let log = WarningLog::new();
+        let now = Instant::now();
+
+        assert!(log
+            .message("Batch for [ks.t] is of size 6.2KiB", now)
+            .is_some());
+        assert!(log
+            .message("Batch for [ks.t] is of size 7.5KiB", now)
+            .is_none());
+        assert!(log
+            .message("Aggregation query used without partition key", now)
+            .is_some());
+    }
+}
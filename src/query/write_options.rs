@@ -0,0 +1,248 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::error::{Error, Result};
+use crate::types::value::Bytes;
+
+/// Maximum TTL accepted by Cassandra - 20 years.
+pub const MAX_TTL: Duration = Duration::from_secs(20 * 365 * 24 * 60 * 60);
+
+/// Time to live of written data, as used in `USING TTL ?`. Cassandra expects TTL in whole
+/// seconds, so sub-second precision is truncated. A TTL of 0 means the data never expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Ttl(i32);
+
+impl Ttl {
+    /// Creates TTL from given duration. Returns an error if the duration exceeds `MAX_TTL`.
+    pub fn new(duration: Duration) -> Result<Self> {
+        if duration > MAX_TTL {
+            return Err(Error::General(format!(
+                "TTL of {} seconds exceeds maximum of {} seconds",
+                duration.as_secs(),
+                MAX_TTL.as_secs()
+            )));
+        }
+
+        Ok(Ttl(duration.as_secs() as i32))
+    }
+
+    /// TTL meaning the data never expires.
+    pub fn none() -> Self {
+        Ttl(0)
+    }
+
+    /// TTL in seconds, as sent to the server.
+    pub fn as_secs(&self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<Duration> for Ttl {
+    type Error = Error;
+
+    fn try_from(duration: Duration) -> Result<Self> {
+        Ttl::new(duration)
+    }
+}
+
+/// Converts a non-negative duration, e.g. `chrono::Duration::days(7)`, like `Ttl::new`.
+impl TryFrom<chrono::Duration> for Ttl {
+    type Error = Error;
+
+    fn try_from(duration: chrono::Duration) -> Result<Self> {
+        duration
+            .to_std()
+            .map_err(|_| {
+                Error::General(format!(
+                    "TTL of {} seconds is negative",
+                    duration.num_seconds()
+                ))
+            })
+            .and_then(Ttl::new)
+    }
+}
+
+impl From<Ttl> for Bytes {
+    fn from(ttl: Ttl) -> Bytes {
+        ttl.0.into()
+    }
+}
+
+/// Write timestamp, as used in `USING TIMESTAMP ?` and as the default timestamp of query
+/// parameters. Cassandra expects microseconds since the unix epoch; negative values represent
+/// pre-epoch instants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WriteTimestamp(i64);
+
+impl WriteTimestamp {
+    /// Creates write timestamp from raw microseconds since the unix epoch.
+    pub fn from_micros(micros: i64) -> Self {
+        WriteTimestamp(micros)
+    }
+
+    /// Current time as write timestamp. Read via chrono, which also works in browsers where
+    /// `SystemTime::now` is not available.
+    pub fn now() -> Self {
+        Utc::now().into()
+    }
+
+    /// Microseconds since the unix epoch, as sent to the server.
+    pub fn as_micros(&self) -> i64 {
+        self.0
+    }
+}
+
+impl From<SystemTime> for WriteTimestamp {
+    fn from(time: SystemTime) -> Self {
+        let micros = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_micros() as i64,
+            Err(error) => {
+                let duration = error.duration();
+                let micros = duration.as_micros() as i64;
+                if duration.subsec_nanos() % 1_000 == 0 {
+                    -micros
+                } else {
+                    -micros - 1
+                }
+            }
+        };
+
+        WriteTimestamp(micros)
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for WriteTimestamp {
+    fn from(date_time: DateTime<Tz>) -> Self {
+        WriteTimestamp(
+            date_time.timestamp() * 1_000_000 + i64::from(date_time.timestamp_subsec_micros()),
+        )
+    }
+}
+
+impl From<WriteTimestamp> for Bytes {
+    fn from(timestamp: WriteTimestamp) -> Bytes {
+        timestamp.0.into()
+    }
+}
+
+/// `USING` clause of `INSERT` and `UPDATE` statements rendered from typed values, so TTLs and
+/// timestamps don't need to be formatted into CQL by hand. Renders as an empty string if
+/// neither is set.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use cdrs_tokio::query::{Ttl, Using, WriteTimestamp};
+///
+/// let using = Using::new()
+///     .ttl(Ttl::new(Duration::from_secs(3600)).unwrap())
+///     .timestamp(WriteTimestamp::from_micros(1_000));
+/// let query = format!("INSERT INTO ks.t (id) VALUES (?) {}", using);
+/// assert_eq!(query, "INSERT INTO ks.t (id) VALUES (?) USING TTL 3600 AND TIMESTAMP 1000");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Using {
+    ttl: Option<Ttl>,
+    timestamp: Option<WriteTimestamp>,
+}
+
+impl Using {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets TTL of written data.
+    pub fn ttl(mut self, ttl: Ttl) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets write timestamp of written data.
+    pub fn timestamp<T: Into<WriteTimestamp>>(mut self, timestamp: T) -> Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+}
+
+impl fmt::Display for Using {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.ttl, self.timestamp) {
+            (Some(ttl), Some(timestamp)) => write!(
+                f,
+                "USING TTL {} AND TIMESTAMP {}",
+                ttl.as_secs(),
+                timestamp.as_micros()
+            ),
+            (Some(ttl), None) => write!(f, "USING TTL {}", ttl.as_secs()),
+            (None, Some(timestamp)) => write!(f, "USING TIMESTAMP {}", timestamp.as_micros()),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn ttl_from_duration() {
+        assert_eq!(
+            Ttl::new(Duration::from_millis(90_500)).unwrap().as_secs(),
+            90
+        );
+        assert_eq!(
+            Ttl::new(MAX_TTL).unwrap().as_secs() as u64,
+            MAX_TTL.as_secs()
+        );
+        assert!(Ttl::new(MAX_TTL + Duration::from_secs(1)).is_err());
+        assert_eq!(Ttl::none().as_secs(), 0);
+    }
+
+    #[test]
+    fn ttl_from_chrono_duration() {
+        assert_eq!(
+            Ttl::try_from(chrono::Duration::days(7)).unwrap().as_secs(),
+            7 * 24 * 60 * 60
+        );
+        assert!(Ttl::try_from(chrono::Duration::seconds(-1)).is_err());
+        assert!(Ttl::try_from(chrono::Duration::days(21 * 365)).is_err());
+    }
+
+    #[test]
+    fn write_timestamp_from_date_time() {
+        let date_time = Utc.timestamp_opt(1_614_000_000, 123_456_789).unwrap();
+        assert_eq!(
+            WriteTimestamp::from(date_time).as_micros(),
+            1_614_000_000_123_456
+        );
+
+        let date_time = Utc.timestamp_opt(-1, 999_999_000).unwrap();
+        assert_eq!(WriteTimestamp::from(date_time).as_micros(), -1);
+    }
+
+    #[test]
+    fn write_timestamp_from_system_time() {
+        let time = UNIX_EPOCH + Duration::from_nanos(1_500);
+        assert_eq!(WriteTimestamp::from(time).as_micros(), 1);
+
+        let time = UNIX_EPOCH - Duration::from_nanos(1_500);
+        assert_eq!(WriteTimestamp::from(time).as_micros(), -2);
+    }
+
+    #[test]
+    fn using_clause() {
+        assert_eq!(Using::new().to_string(), "");
+        assert_eq!(Using::new().ttl(Ttl::none()).to_string(), "USING TTL 0");
+        assert_eq!(
+            Using::new()
+                .timestamp(Utc.timestamp_opt(1, 0).unwrap())
+                .to_string(),
+            "USING TIMESTAMP 1000000"
+        );
+    }
+}
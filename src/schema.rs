@@ -0,0 +1,721 @@
+//! Schema of tables as stored in `system_schema`, e.g. for code generation.
+//!
+//! A `SchemaSnapshot` of whole keyspaces can be exported into JSON (behind the `serde_json`
+//! feature) and imported without a live cluster, to generate code or validate statements in
+//! environments with no Cassandra:
+//!
+//! ```no_run
+//! # #[cfg(feature = "serde_json")]
+//! # fn run() -> cdrs_tokio::Result<()> {
+//! use cdrs_tokio::schema::SchemaSnapshot;
+//!
+//! let snapshot = SchemaSnapshot::import(&std::fs::read_to_string("schema.json")?)?;
+//! snapshot.validate(Some("ks"), "SELECT name FROM users WHERE id = ?")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde_json")]
+use serde_json::{json, Value as JsonValue};
+#[cfg(feature = "runtime")]
+use tokio::sync::Mutex;
+
+use crate::error;
+#[cfg(feature = "runtime")]
+use crate::query::{frame_rows, QueryExecutor, QueryValues};
+#[cfg(feature = "runtime")]
+use crate::transport::CDRSTransport;
+use crate::types::rows::Row;
+use crate::types::IntoRustByName;
+
+/// Role of a column in its table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColumnKind {
+    PartitionKey,
+    Clustering,
+    Static,
+    Regular,
+}
+
+impl ColumnKind {
+    /// Parses the `kind` of a column as stored in `system_schema.columns`.
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "partition_key" => Some(ColumnKind::PartitionKey),
+            "clustering" => Some(ColumnKind::Clustering),
+            "static" => Some(ColumnKind::Static),
+            "regular" => Some(ColumnKind::Regular),
+            _ => None,
+        }
+    }
+
+    /// Returns the `kind` of a column as stored in `system_schema.columns`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColumnKind::PartitionKey => "partition_key",
+            ColumnKind::Clustering => "clustering",
+            ColumnKind::Static => "static",
+            ColumnKind::Regular => "regular",
+        }
+    }
+
+    /// Returns `true` for partition key and clustering columns, which cannot be null.
+    pub fn is_primary_key(self) -> bool {
+        matches!(self, ColumnKind::PartitionKey | ColumnKind::Clustering)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// Type as written in CQL, e.g. `frozen<list<text>>`.
+    pub cql_type: String,
+    pub kind: ColumnKind,
+    /// Position among partition key or clustering columns, -1 for other columns.
+    pub position: i32,
+}
+
+impl ColumnSchema {
+    /// Reads a column from a row of `system_schema.columns` with `column_name`, `kind`,
+    /// `position` and `type` columns.
+    pub fn from_row(row: &Row) -> error::Result<Self> {
+        let kind: String = row.get_r_by_name("kind")?;
+        Ok(ColumnSchema {
+            name: row.get_r_by_name("column_name")?,
+            cql_type: row.get_r_by_name("type")?,
+            kind: ColumnKind::parse(&kind)
+                .ok_or_else(|| format!("Unknown column kind {}", kind))?,
+            position: row.get_r_by_name("position")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema {
+    pub keyspace: String,
+    pub name: String,
+    /// Columns in the order Cassandra lists them: partition key, clustering columns and other
+    /// columns by name.
+    pub columns: Vec<ColumnSchema>,
+}
+
+impl TableSchema {
+    pub fn new(keyspace: &str, name: &str, mut columns: Vec<ColumnSchema>) -> Self {
+        // static and regular columns are ordered together
+        columns.sort_by(|a, b| {
+            (a.kind.min(ColumnKind::Static), a.position, &a.name).cmp(&(
+                b.kind.min(ColumnKind::Static),
+                b.position,
+                &b.name,
+            ))
+        });
+
+        TableSchema {
+            keyspace: keyspace.to_string(),
+            name: name.to_string(),
+            columns,
+        }
+    }
+
+    /// Reads a table from rows of `system_schema.columns` with `column_name`, `kind`,
+    /// `position` and `type` columns.
+    pub fn from_rows(keyspace: &str, name: &str, rows: &[Row]) -> error::Result<Self> {
+        let columns = rows
+            .iter()
+            .map(ColumnSchema::from_row)
+            .collect::<error::Result<_>>()?;
+
+        Ok(Self::new(keyspace, name, columns))
+    }
+
+    /// Reads given table from `system_schema.columns`. Fails if the table doesn't exist.
+    #[cfg(feature = "runtime")]
+    pub async fn read<S, T, M>(session: &S, keyspace: &str, name: &str) -> error::Result<Self>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let rows = frame_rows(
+            session
+                .query_with_values(
+                    "SELECT column_name, kind, position, type FROM system_schema.columns \
+                     WHERE keyspace_name = ? AND table_name = ?",
+                    QueryValues::SimpleValues(vec![keyspace.into(), name.into()]),
+                )
+                .await?,
+        )?;
+
+        if rows.is_empty() {
+            return Err(format!("Unknown table {}.{}", keyspace, name).into());
+        }
+
+        Self::from_rows(keyspace, name, &rows)
+    }
+
+    /// Returns partition key columns in order of their declaration.
+    pub fn partition_key(&self) -> impl Iterator<Item = &ColumnSchema> {
+        self.columns_of(ColumnKind::PartitionKey)
+    }
+
+    /// Returns clustering columns in order of their declaration.
+    pub fn clustering_columns(&self) -> impl Iterator<Item = &ColumnSchema> {
+        self.columns_of(ColumnKind::Clustering)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    fn columns_of(&self, kind: ColumnKind) -> impl Iterator<Item = &ColumnSchema> {
+        self.columns
+            .iter()
+            .filter(move |column| column.kind == kind)
+    }
+}
+
+/// Format version of exported snapshots.
+#[cfg(feature = "serde_json")]
+const SNAPSHOT_VERSION: u64 = 1;
+
+/// Snapshot of tables of one or more keyspaces, which can be exported and imported as JSON and
+/// used instead of a live cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaSnapshot {
+    /// Tables ordered by keyspace and name.
+    pub tables: Vec<TableSchema>,
+}
+
+impl SchemaSnapshot {
+    pub fn new(mut tables: Vec<TableSchema>) -> Self {
+        tables.sort_by(|a, b| (&a.keyspace, &a.name).cmp(&(&b.keyspace, &b.name)));
+        SchemaSnapshot { tables }
+    }
+
+    /// Reads tables from rows of `system_schema.columns` with `keyspace_name` and `table_name`
+    /// columns, along with the ones read by `ColumnSchema::from_row`.
+    pub fn from_rows(rows: &[Row]) -> error::Result<Self> {
+        let mut tables: BTreeMap<(String, String), Vec<ColumnSchema>> = BTreeMap::new();
+        for row in rows {
+            tables
+                .entry((
+                    row.get_r_by_name("keyspace_name")?,
+                    row.get_r_by_name("table_name")?,
+                ))
+                .or_default()
+                .push(ColumnSchema::from_row(row)?);
+        }
+
+        Ok(Self::new(
+            tables
+                .into_iter()
+                .map(|((keyspace, name), columns)| TableSchema::new(&keyspace, &name, columns))
+                .collect(),
+        ))
+    }
+
+    /// Reads all tables of given keyspaces from `system_schema.columns`.
+    #[cfg(feature = "runtime")]
+    pub async fn read<S, T, M>(session: &S, keyspaces: &[&str]) -> error::Result<Self>
+    where
+        S: QueryExecutor<T, M>,
+        T: CDRSTransport + Unpin + 'static,
+        M: bb8::ManageConnection<Connection = Mutex<T>, Error = error::Error>,
+    {
+        let mut rows = vec![];
+        for keyspace in keyspaces {
+            rows.extend(frame_rows(
+                session
+                    .query_with_values(
+                        "SELECT keyspace_name, table_name, column_name, kind, position, type \
+                         FROM system_schema.columns WHERE keyspace_name = ?",
+                        QueryValues::SimpleValues(vec![(*keyspace).into()]),
+                    )
+                    .await?,
+            )?);
+        }
+
+        Self::from_rows(&rows)
+    }
+
+    pub fn table(&self, keyspace: &str, name: &str) -> Option<&TableSchema> {
+        self.tables
+            .iter()
+            .find(|table| table.keyspace == keyspace && table.name == name)
+    }
+
+    /// Returns tables of given keyspace.
+    pub fn keyspace_tables<'a>(
+        &'a self,
+        keyspace: &'a str,
+    ) -> impl Iterator<Item = &'a TableSchema> {
+        self.tables
+            .iter()
+            .filter(move |table| table.keyspace == keyspace)
+    }
+
+    /// Exports the snapshot as pretty printed JSON.
+    #[cfg(feature = "serde_json")]
+    pub fn export(&self) -> String {
+        let tables: Vec<JsonValue> = self
+            .tables
+            .iter()
+            .map(|table| {
+                let columns: Vec<JsonValue> = table
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        json!({
+                            "name": column.name,
+                            "type": column.cql_type,
+                            "kind": column.kind.as_str(),
+                            "position": column.position,
+                        })
+                    })
+                    .collect();
+
+                json!({
+                    "keyspace": table.keyspace,
+                    "name": table.name,
+                    "columns": columns,
+                })
+            })
+            .collect();
+
+        format!(
+            "{:#}",
+            json!({
+                "version": SNAPSHOT_VERSION,
+                "tables": tables,
+            })
+        )
+    }
+
+    /// Imports a snapshot exported by `export`.
+    #[cfg(feature = "serde_json")]
+    pub fn import(json: &str) -> error::Result<Self> {
+        let snapshot: JsonValue =
+            serde_json::from_str(json).map_err(crate::types::json::json_error)?;
+
+        match snapshot.get("version").and_then(JsonValue::as_u64) {
+            Some(SNAPSHOT_VERSION) => {}
+            Some(version) => {
+                return Err(format!("Unsupported schema snapshot version {}", version).into())
+            }
+            None => return Err("Missing schema snapshot version".into()),
+        }
+
+        let tables = json_array(&snapshot, "tables")?
+            .iter()
+            .map(|table| {
+                let columns = json_array(table, "columns")?
+                    .iter()
+                    .map(|column| {
+                        let kind = json_str(column, "kind")?;
+                        Ok(ColumnSchema {
+                            name: json_str(column, "name")?.to_string(),
+                            cql_type: json_str(column, "type")?.to_string(),
+                            kind: ColumnKind::parse(kind)
+                                .ok_or_else(|| format!("Unknown column kind {}", kind))?,
+                            position: column
+                                .get("position")
+                                .and_then(JsonValue::as_i64)
+                                .ok_or("Missing schema snapshot field position")?
+                                as i32,
+                        })
+                    })
+                    .collect::<error::Result<_>>()?;
+
+                Ok(TableSchema::new(
+                    json_str(table, "keyspace")?,
+                    json_str(table, "name")?,
+                    columns,
+                ))
+            })
+            .collect::<error::Result<_>>()?;
+
+        Ok(Self::new(tables))
+    }
+
+    /// Checks that tables and columns referenced by a `SELECT`, `INSERT`, `UPDATE` or `DELETE`
+    /// statement exist, e.g. to test statements without a cluster. Tables which aren't
+    /// qualified with a keyspace are looked up in given keyspace or, if none is given, by name
+    /// among all tables. Other statements are not checked.
+    ///
+    /// Only plain column references are checked: selectors, inserted columns and columns
+    /// compared or assigned in `WHERE`, `SET` and `IF` clauses. Columns used as arguments of
+    /// functions or in tuples are ignored.
+    pub fn validate(&self, keyspace: Option<&str>, query: &str) -> error::Result<()> {
+        let tokens = tokenize(query)?;
+        let keyword = match tokens.first() {
+            Some(CqlToken::Word(word)) => word.to_ascii_uppercase(),
+            _ => return Ok(()),
+        };
+
+        let mut rest = &tokens[1..];
+        let mut columns = vec![];
+        let table = match keyword.as_str() {
+            "SELECT" | "DELETE" => {
+                while matches!(rest.first(), Some(token) if token.is_keyword("JSON") || token.is_keyword("DISTINCT"))
+                {
+                    rest = &rest[1..];
+                }
+
+                let from = rest
+                    .iter()
+                    .position(|token| token.is_keyword("FROM"))
+                    .ok_or("Missing FROM clause")?;
+                columns.extend(selected_columns(&rest[..from]));
+
+                let (table, remaining) = self.resolve_table(keyspace, &rest[from + 1..])?;
+                rest = remaining;
+                table
+            }
+            "INSERT" => {
+                if !matches!(rest.first(), Some(token) if token.is_keyword("INTO")) {
+                    return Err("Missing INTO clause".into());
+                }
+
+                let (table, remaining) = self.resolve_table(keyspace, &rest[1..])?;
+                if remaining.first() == Some(&CqlToken::Symbol("(")) {
+                    columns.extend(
+                        remaining[1..]
+                            .iter()
+                            .take_while(|token| **token != CqlToken::Symbol(")"))
+                            .filter_map(CqlToken::identifier),
+                    );
+                }
+
+                rest = remaining;
+                table
+            }
+            "UPDATE" => {
+                let (table, remaining) = self.resolve_table(keyspace, rest)?;
+                rest = remaining;
+                table
+            }
+            _ => return Ok(()),
+        };
+
+        columns.extend(compared_columns(rest));
+
+        match columns
+            .into_iter()
+            .find(|column| table.column(column).is_none())
+        {
+            Some(column) => Err(format!(
+                "Unknown column {} in {}.{}",
+                column, table.keyspace, table.name
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds the table named at the beginning of given tokens and returns remaining tokens.
+    fn resolve_table<'a, 'b>(
+        &'a self,
+        keyspace: Option<&str>,
+        tokens: &'b [CqlToken],
+    ) -> error::Result<(&'a TableSchema, &'b [CqlToken])> {
+        let first = tokens
+            .first()
+            .and_then(CqlToken::identifier)
+            .ok_or("Missing table name")?;
+
+        let (table, rest) = match (tokens.get(1), tokens.get(2).and_then(CqlToken::identifier)) {
+            (Some(CqlToken::Symbol(".")), Some(name)) => (self.table(&first, &name), &tokens[3..]),
+            _ => {
+                let table = match keyspace {
+                    Some(keyspace) => self.table(keyspace, &first),
+                    None => {
+                        let mut tables = self.tables.iter().filter(|table| table.name == first);
+                        match (tables.next(), tables.next()) {
+                            (Some(_), Some(_)) => {
+                                return Err(
+                                    format!("Table {} exists in multiple keyspaces", first).into()
+                                )
+                            }
+                            (table, _) => table,
+                        }
+                    }
+                };
+
+                (table, &tokens[1..])
+            }
+        };
+
+        let table = table.ok_or_else(|| {
+            let name: Vec<String> = tokens[..tokens.len() - rest.len()]
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            format!("Unknown table {}", name.concat())
+        })?;
+
+        Ok((table, rest))
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn json_array<'a>(value: &'a JsonValue, field: &str) -> error::Result<&'a Vec<JsonValue>> {
+    value
+        .get(field)
+        .and_then(JsonValue::as_array)
+        .ok_or_else(|| format!("Missing schema snapshot field {}", field).into())
+}
+
+#[cfg(feature = "serde_json")]
+fn json_str<'a>(value: &'a JsonValue, field: &str) -> error::Result<&'a str> {
+    value
+        .get(field)
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| format!("Missing schema snapshot field {}", field).into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CqlToken {
+    /// Unquoted identifier, keyword or number.
+    Word(String),
+    Quoted(String),
+    Literal,
+    Symbol(&'static str),
+}
+
+impl CqlToken {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, CqlToken::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Returns the name of an identifier, unquoted identifiers being case insensitive.
+    fn identifier(&self) -> Option<String> {
+        match self {
+            CqlToken::Word(word) if !word.starts_with(|c: char| c.is_ascii_digit()) => {
+                Some(word.to_lowercase())
+            }
+            CqlToken::Quoted(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether the token follows a column compared or assigned to.
+    fn is_operator(&self) -> bool {
+        match self {
+            CqlToken::Symbol(symbol) => {
+                ["=", "<", ">", "<=", ">=", "!=", "+=", "-=", "["].contains(symbol)
+            }
+            token => ["IN", "CONTAINS", "LIKE"]
+                .iter()
+                .any(|keyword| token.is_keyword(keyword)),
+        }
+    }
+}
+
+impl std::fmt::Display for CqlToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CqlToken::Word(word) => f.write_str(word),
+            CqlToken::Quoted(name) => write!(f, "\"{}\"", name.replace('"', "\"\"")),
+            CqlToken::Literal => f.write_str("'...'"),
+            CqlToken::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 5] = ["<=", ">=", "!=", "+=", "-="];
+
+fn tokenize(query: &str) -> error::Result<Vec<CqlToken>> {
+    let mut tokens = vec![];
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c == '"' || c == '\'' {
+            // quotes are escaped by doubling them
+            let mut end = 1;
+            loop {
+                end += rest[end..]
+                    .find(c)
+                    .ok_or_else(|| format!("Unterminated {} in statement", c))?;
+                if rest[end + 1..].starts_with(c) {
+                    end += 2;
+                } else {
+                    break;
+                }
+            }
+
+            tokens.push(if c == '"' {
+                CqlToken::Quoted(rest[1..end].replace("\"\"", "\""))
+            } else {
+                CqlToken::Literal
+            });
+            rest = &rest[end + 1..];
+        } else if let Some(body) = rest.strip_prefix("$$") {
+            let end = body.find("$$").ok_or("Unterminated $$ in statement")?;
+            tokens.push(CqlToken::Literal);
+            rest = &body[end + 2..];
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(CqlToken::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .copied()
+                .unwrap_or_else(|| single_char_symbol(c));
+            tokens.push(CqlToken::Symbol(symbol));
+            rest = &rest[c.len_utf8().max(symbol.len())..];
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn single_char_symbol(c: char) -> &'static str {
+    const CHARS: &str = "()[]{},.;:?=<>+-*/%!";
+    CHARS
+        .find(c)
+        .map(|index| &CHARS[index..index + 1])
+        .unwrap_or("")
+}
+
+/// Returns columns selected by plain identifiers, optionally aliased.
+fn selected_columns(selectors: &[CqlToken]) -> impl Iterator<Item = String> + '_ {
+    selectors
+        .split(|token| *token == CqlToken::Symbol(","))
+        .filter_map(|selector| match selector {
+            [column] | [column, _, _] if selector.len() == 1 || selector[1].is_keyword("AS") => {
+                column.identifier()
+            }
+            _ => None,
+        })
+}
+
+/// Returns columns directly followed by a comparison or assignment operator.
+fn compared_columns(tokens: &[CqlToken]) -> impl Iterator<Item = String> + '_ {
+    tokens.windows(3).filter_map(|window| match window {
+        [previous, column, operator]
+            if *previous != CqlToken::Symbol(".") && operator.is_operator() =>
+        {
+            column.identifier()
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, cql_type: &str, kind: ColumnKind, position: i32) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            cql_type: cql_type.to_string(),
+            kind,
+            position,
+        }
+    }
+
+    fn snapshot() -> SchemaSnapshot {
+        SchemaSnapshot::new(vec![
+            TableSchema::new(
+                "ks",
+                "users",
+                vec![
+                    column("name", "text", ColumnKind::Regular, -1),
+                    column("id", "uuid", ColumnKind::PartitionKey, 0),
+                    column("Tags", "map<text, int>", ColumnKind::Regular, -1),
+                ],
+            ),
+            TableSchema::new(
+                "ks",
+                "events",
+                vec![
+                    column("user_id", "uuid", ColumnKind::PartitionKey, 0),
+                    column("at", "timestamp", ColumnKind::Clustering, 0),
+                ],
+            ),
+            TableSchema::new(
+                "other",
+                "events",
+                vec![column("id", "int", ColumnKind::PartitionKey, 0)],
+            ),
+        ])
+    }
+
+    #[test]
+    fn snapshot_order() {
+        let snapshot = snapshot();
+        let names: Vec<_> = snapshot
+            .tables
+            .iter()
+            .map(|table| format!("{}.{}", table.keyspace, table.name))
+            .collect();
+
+        assert_eq!(names, vec!["ks.events", "ks.users", "other.events"]);
+        assert_eq!(snapshot.keyspace_tables("ks").count(), 2);
+        assert_eq!(snapshot.tables[1].columns[0].name, "id");
+    }
+
+    #[test]
+    fn validate_statements() {
+        let snapshot = snapshot();
+
+        for query in &[
+            "SELECT name AS n, \"Tags\" FROM ks.users WHERE id = ?",
+            "select json * from users where id in (?, ?) and \"Tags\" contains key 'a'",
+            "SELECT COUNT(*), writetime(name) FROM users WHERE token(id) > ? LIMIT 5",
+            "INSERT INTO users (id, NAME) VALUES (?, 'it''s') USING TTL 10",
+            "UPDATE users USING TIMESTAMP ? SET \"Tags\"['a'] = ?, name = $$x = 1$$ \
+             WHERE id = ? IF name != ?",
+            "DELETE name FROM users WHERE id = ? IF EXISTS",
+            "SELECT at FROM events WHERE user_id = ? AND at >= ?",
+            "TRUNCATE unknown",
+        ] {
+            assert!(
+                snapshot.validate(Some("ks"), query).is_ok(),
+                "{} is valid",
+                query
+            );
+        }
+
+        for (keyspace, query) in &[
+            (Some("ks"), "SELECT email FROM users WHERE id = ?"),
+            (Some("ks"), "SELECT name FROM users WHERE tags = ?"),
+            (Some("ks"), "INSERT INTO users (id, email) VALUES (?, ?)"),
+            (Some("ks"), "UPDATE users SET email = ? WHERE id = ?"),
+            (Some("ks"), "DELETE FROM accounts WHERE id = ?"),
+            (Some("ks"), "SELECT * FROM other.users"),
+            (None, "SELECT * FROM events"),
+            (Some("ks"), "SELECT * FROM users WHERE name = 'unterminated"),
+        ] {
+            assert!(
+                snapshot.validate(*keyspace, query).is_err(),
+                "{} is invalid",
+                query
+            );
+        }
+
+        assert!(snapshot
+            .validate(None, "SELECT * FROM users WHERE id = ?")
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn export_import() {
+        let snapshot = snapshot();
+        let exported = snapshot.export();
+
+        assert_eq!(SchemaSnapshot::import(&exported).unwrap(), snapshot);
+        assert!(
+            SchemaSnapshot::import(&exported.replace("\"version\": 1", "\"version\": 2")).is_err()
+        );
+        assert!(SchemaSnapshot::import("{\"version\": 1}").is_err());
+    }
+}
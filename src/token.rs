@@ -0,0 +1,332 @@
+//! Tokens of partitions, e.g. for sharding work by token ranges or computing tokens of
+//! partitions client side. Besides the `Murmur3Partitioner`, the default partitioner of
+//! Cassandra, tokens of the legacy `RandomPartitioner` and `ByteOrderedPartitioner` are
+//! supported.
+use std::fmt::Write;
+
+/// Minimum token of the ring. It is never assigned to partitions.
+pub const MIN_TOKEN: i64 = i64::MIN;
+
+/// Maximum token of the ring.
+pub const MAX_TOKEN: i64 = i64::MAX;
+
+/// Inclusive range of Murmur3 partitioner tokens.
+pub type TokenRange = (i64, i64);
+
+/// Token of a partition. Tokens of different partitioners are not comparable in a meaningful
+/// way, since a cluster uses a single partitioner.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Token {
+    Murmur3(i64),
+    Random(u128),
+    ByteOrdered(Vec<u8>),
+}
+
+impl From<i64> for Token {
+    fn from(token: i64) -> Self {
+        Token::Murmur3(token)
+    }
+}
+
+/// Partitioner of a cluster, distributing partitions by tokens of their keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Partitioner {
+    Murmur3,
+    Random,
+    ByteOrdered,
+}
+
+impl Partitioner {
+    /// Recognizes a partitioner by its class name, as found in `system.local`, e.g.
+    /// `org.apache.cassandra.dht.Murmur3Partitioner`. Returns `None` for other partitioners.
+    pub fn from_class_name(class_name: &str) -> Option<Self> {
+        match class_name.rsplit('.').next()? {
+            "Murmur3Partitioner" => Some(Partitioner::Murmur3),
+            "RandomPartitioner" => Some(Partitioner::Random),
+            "ByteOrderedPartitioner" => Some(Partitioner::ByteOrdered),
+            _ => None,
+        }
+    }
+
+    /// Computes the token of a serialized partition key (see `RoutingKey`).
+    pub fn token(self, key: &[u8]) -> Token {
+        match self {
+            Partitioner::Murmur3 => Token::Murmur3(murmur3_token(key)),
+            Partitioner::Random => Token::Random(random_token(key)),
+            Partitioner::ByteOrdered => Token::ByteOrdered(key.to_vec()),
+        }
+    }
+
+    /// Parses a token as stored in the `tokens` column of system tables: decimal numbers for
+    /// `Murmur3Partitioner` and `RandomPartitioner` and hex strings for `ByteOrderedPartitioner`.
+    pub fn parse_token(self, token: &str) -> Option<Token> {
+        match self {
+            Partitioner::Murmur3 => token.parse().ok().map(Token::Murmur3),
+            Partitioner::Random => token.parse().ok().map(Token::Random),
+            Partitioner::ByteOrdered => {
+                if !token.len().is_multiple_of(2) || !token.is_ascii() {
+                    return None;
+                }
+
+                (0..token.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&token[i..i + 2], 16).ok())
+                    .collect::<Option<Vec<u8>>>()
+                    .map(Token::ByteOrdered)
+            }
+        }
+    }
+}
+
+impl Token {
+    /// Formats the token like the `tokens` column of system tables, see
+    /// `Partitioner::parse_token`.
+    pub fn to_cql_string(&self) -> String {
+        match self {
+            Token::Murmur3(token) => token.to_string(),
+            Token::Random(token) => token.to_string(),
+            Token::ByteOrdered(bytes) => bytes.iter().fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{:02x}", byte);
+                hex
+            }),
+        }
+    }
+}
+
+/// Computes the token of a serialized partition key (see `RoutingKey`) the same way Cassandra's
+/// `Murmur3Partitioner` does, i.e. the first half of x64 128-bit MurmurHash3 with trailing
+/// bytes sign-extended. Matches `SELECT token(...)` of tables using the default partitioner.
+pub fn murmur3_token(key: &[u8]) -> i64 {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+    fn fmix(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        k ^ (k >> 33)
+    }
+
+    let mut h1: u64 = 0;
+    let mut h2: u64 = 0;
+
+    let blocks = key.chunks_exact(16);
+    let tail = blocks.remainder();
+
+    for block in blocks {
+        let mut k1 = u64::from_le_bytes([
+            block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7],
+        ]);
+        let mut k2 = u64::from_le_bytes([
+            block[8], block[9], block[10], block[11], block[12], block[13], block[14], block[15],
+        ]);
+
+        k1 = k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+
+        k2 = k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 ^= k2;
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+
+    // Cassandra reads trailing bytes as signed
+    let tail_byte = |i: usize| tail[i] as i8 as i64 as u64;
+
+    let mut k1: u64 = 0;
+    let mut k2: u64 = 0;
+
+    if tail.len() > 8 {
+        for i in (8..tail.len()).rev() {
+            k2 ^= tail_byte(i) << ((i - 8) * 8);
+        }
+        k2 = k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 ^= k2;
+    }
+
+    if !tail.is_empty() {
+        for i in (0..tail.len().min(8)).rev() {
+            k1 ^= tail_byte(i) << (i * 8);
+        }
+        k1 = k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 ^= k1;
+    }
+
+    h1 ^= key.len() as u64;
+    h2 ^= key.len() as u64;
+
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+
+    h1 = h1.wrapping_add(h2);
+
+    // minimum token is reserved by Cassandra
+    match h1 as i64 {
+        MIN_TOKEN => MAX_TOKEN,
+        token => token,
+    }
+}
+
+/// Computes the token of a serialized partition key the same way Cassandra's
+/// `RandomPartitioner` does, i.e. the absolute value of its MD5 digest read as a signed
+/// big-endian number.
+pub fn random_token(key: &[u8]) -> u128 {
+    i128::from_be_bytes(md5::compute(key).0).unsigned_abs()
+}
+
+/// Splits the whole ring into given number of contiguous token ranges of equal size, in
+/// ascending order.
+pub fn split_ring(splits: usize) -> Vec<TokenRange> {
+    let splits = splits.max(1) as i128;
+    let ring_size = 1i128 << 64;
+    let start_of = |split: i128| MIN_TOKEN as i128 + ring_size * split / splits;
+
+    (0..splits)
+        .map(|split| (start_of(split) as i64, (start_of(split + 1) - 1) as i64))
+        .collect()
+}
+
+/// Returns the index of the owner of given token in sorted tokens of a ring, i.e. of the first
+/// token not lower than it, wrapping around to the first one. Returns `None` for an empty ring.
+pub fn ring_owner(ring: &[i64], token: i64) -> Option<usize> {
+    if ring.is_empty() {
+        return None;
+    }
+
+    match ring.partition_point(|ring_token| *ring_token < token) {
+        index if index == ring.len() => Some(0),
+        index => Some(index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur3_matches_cassandra() {
+        // SELECT token(id) for int partition keys
+        assert_eq!(murmur3_token(&1i32.to_be_bytes()), -4069959284402364209);
+        assert_eq!(murmur3_token(&2i32.to_be_bytes()), -3248873570005575792);
+        assert_eq!(murmur3_token(&[]), 0);
+
+        // text partition keys, covering whole blocks and tails of all lengths
+        assert_eq!(murmur3_token(b"a"), -8839064797231613815);
+        assert_eq!(murmur3_token(b"hello"), -3758069500696749310);
+        assert_eq!(murmur3_token(b"0123456789abcdef"), 5467490433528156583);
+        assert_eq!(
+            murmur3_token(b"The quick brown fox jumps over the lazy dog"),
+            -2068352364225029268
+        );
+    }
+
+    #[test]
+    fn murmur3_sign_extends_tail() {
+        assert_eq!(murmur3_token(&[0xff; 9]), 8434364767658694854);
+        let key: Vec<u8> = (200..223).collect();
+        assert_eq!(murmur3_token(&key), 2426168040070925508);
+    }
+
+    #[test]
+    fn random_matches_cassandra() {
+        assert_eq!(random_token(&[]), 58332598431525814501020785164969033090);
+        assert_eq!(
+            random_token(&1i32.to_be_bytes()),
+            19580090105725936846312850328329299579
+        );
+        assert_eq!(
+            random_token(b"hello"),
+            123957004363873451094272536567338222994
+        );
+    }
+
+    #[test]
+    fn partitioners() {
+        assert_eq!(
+            Partitioner::from_class_name("org.apache.cassandra.dht.Murmur3Partitioner"),
+            Some(Partitioner::Murmur3)
+        );
+        assert_eq!(
+            Partitioner::from_class_name("org.apache.cassandra.dht.RandomPartitioner"),
+            Some(Partitioner::Random)
+        );
+        assert_eq!(
+            Partitioner::from_class_name("ByteOrderedPartitioner"),
+            Some(Partitioner::ByteOrdered)
+        );
+        assert_eq!(
+            Partitioner::from_class_name("org.apache.cassandra.dht.OrderPreservingPartitioner"),
+            None
+        );
+
+        assert_eq!(
+            Partitioner::Murmur3.token(&1i32.to_be_bytes()),
+            Token::Murmur3(-4069959284402364209)
+        );
+        assert_eq!(
+            Partitioner::ByteOrdered.token(b"ab"),
+            Token::ByteOrdered(b"ab".to_vec())
+        );
+    }
+
+    #[test]
+    fn parse_tokens() {
+        for (partitioner, token) in &[
+            (Partitioner::Murmur3, "-4069959284402364209"),
+            (
+                Partitioner::Random,
+                "19580090105725936846312850328329299579",
+            ),
+            (Partitioner::ByteOrdered, "00ff7a"),
+        ] {
+            let parsed = partitioner.parse_token(token).unwrap();
+            assert_eq!(parsed.to_cql_string(), *token);
+        }
+
+        assert_eq!(
+            Partitioner::ByteOrdered.parse_token("00ff7a"),
+            Some(Token::ByteOrdered(vec![0, 0xff, 0x7a]))
+        );
+        assert_eq!(Partitioner::ByteOrdered.parse_token("0"), None);
+        assert_eq!(Partitioner::Murmur3.parse_token("x"), None);
+        assert_eq!(Partitioner::Random.parse_token("-1"), None);
+    }
+
+    #[test]
+    fn split_ring_covers_ring() {
+        let ranges = split_ring(3);
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].0, MIN_TOKEN);
+        assert_eq!(ranges[2].1, MAX_TOKEN);
+        for window in ranges.windows(2) {
+            assert_eq!(window[0].1 + 1, window[1].0);
+        }
+
+        assert_eq!(split_ring(1), vec![(MIN_TOKEN, MAX_TOKEN)]);
+        assert_eq!(split_ring(0), vec![(MIN_TOKEN, MAX_TOKEN)]);
+    }
+
+    #[test]
+    fn owners_of_tokens() {
+        let ring = [-100, 0, 100];
+
+        assert_eq!(ring_owner(&ring, -200), Some(0));
+        assert_eq!(ring_owner(&ring, -100), Some(0));
+        assert_eq!(ring_owner(&ring, -99), Some(1));
+        assert_eq!(ring_owner(&ring, 101), Some(0));
+        assert_eq!(ring_owner(&[], 1), None);
+    }
+}
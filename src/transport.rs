@@ -11,25 +11,37 @@
 //!with Apache Cassandra server. **Note:** this option is available if and only if CDRS is imported
 //!with `rust-tls` feature.
 use async_trait::async_trait;
+use fxhash::FxHashMap;
+#[cfg(feature = "rust-tls")]
+use rustls::Session as _;
+use socket2::{SockRef, TcpKeepalive};
 use std::io;
-use std::io::Error;
+use std::mem;
 use std::net;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex};
 use std::task::Context;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::macros::support::{Pin, Poll};
-use tokio::net::TcpStream;
+use tokio::net::{lookup_host, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::{timeout, timeout_at, Instant};
 #[cfg(feature = "rust-tls")]
 use tokio_rustls::{client::TlsStream as RustlsStream, TlsConnector as RustlsConnector};
 
-use crate::cluster::KeyspaceHolder;
+use crate::cluster::{ConnectionState, ConnectionStateCell, ConnectionStates, KeyspaceHolder};
+use crate::compression::{CompressionStats, DEFAULT_MAX_DECOMPRESSED_LENGTH};
+use crate::frame::{ProtocolVersion, StreamId, HEADER_LEN, MAX_BODY_LEN};
 
 // TODO [v x.x.x]: CDRSTransport: ... + BufReader + ButWriter + ...
 ///General CDRS transport trait. Both [`TransportTcp`]
 ///and [`TransportRustls`] has their own implementations of this trait. Generaly
-///speaking it extends/includes `io::Read` and `io::Write` traits and should be thread safe.
+///speaking it extends/includes `io::Read` trait and should be thread safe. Frames are written by
+///the writer task of the connection, see [`FrameWriter`].
 #[async_trait]
-pub trait CDRSTransport: Sized + AsyncRead + AsyncWriteExt + Send + Sync {
+pub trait CDRSTransport: Sized + AsyncRead + Send + Sync {
     /// Creates a new independently owned handle to the underlying socket.
     ///
     /// The returned TcpStream is a reference to the same stream that this object references.
@@ -40,18 +52,794 @@ pub trait CDRSTransport: Sized + AsyncRead + AsyncWriteExt + Send + Sync {
     /// Shuts down the read, write, or both halves of this connection.
     async fn close(&mut self, close: net::Shutdown) -> io::Result<()>;
 
-    /// Method that checks that transport is alive
-    fn is_alive(&self) -> bool;
+    /// Returns lifecycle state of the connection.
+    fn state(&self) -> ConnectionState;
+
+    /// Marks the connection ready for requests, once its startup handshake completes.
+    fn mark_ready(&self) {}
+
+    /// Records the bit of prepared metadata flags marking lightweight transactions, negotiated
+    /// with the node during startup.
+    fn set_lwt_metadata_mask(&self, _mask: i32) {}
+
+    /// Returns the bit of prepared metadata flags marking lightweight transactions, if the
+    /// node supports the `SCYLLA_LWT_ADD_METADATA_MARK` extension.
+    fn lwt_metadata_mask(&self) -> Option<i32> {
+        None
+    }
+
+    /// Records the protocol version the connection uses, negotiated with the node during
+    /// startup.
+    fn set_protocol_version(&self, _version: ProtocolVersion) {}
+
+    /// Returns the protocol version requests sent over the connection are encoded with.
+    fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::default()
+    }
+
+    /// Method that checks that transport is alive, i.e. it is neither being closed nor closed.
+    fn is_alive(&self) -> bool {
+        self.state() < ConnectionState::Draining
+    }
+
+    /// Returns handle of the writer task of the connection. Custom transports spawn the task
+    /// for their writing half with `FrameWriter::spawn` when connecting.
+    fn frame_writer(&self) -> FrameWriter;
+
+    /// Returns requests in flight on the connection, whose responses are routed to them by the
+    /// reader task of the connection. Requests sharing such a connection are sent on separate
+    /// streams and don't wait for responses to each other. Defaults to `None`, in which case
+    /// requests read their responses from the transport one at a time.
+    fn response_routes(&self) -> Option<Arc<ResponseRoutes>> {
+        None
+    }
+
+    /// Returns the address of the node the transport is connected to, if known, e.g. to
+    /// identify the connection in logs.
+    fn addr(&self) -> Option<String> {
+        None
+    }
 
     /// Sets last USEd keyspace for further connections from the same pool
     async fn set_current_keyspace(&self, keyspace: &str);
+
+    /// Returns last USEd keyspace, if the transport tracks it.
+    async fn current_keyspace(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns statistics of compressed frames received over this transport, if it collects
+    /// them.
+    fn compression_stats(&self) -> Option<Arc<CompressionStats>> {
+        None
+    }
+
+    /// Returns the maximum length of a decompressed frame body received over this transport.
+    fn max_decompressed_length(&self) -> usize {
+        DEFAULT_MAX_DECOMPRESSED_LENGTH
+    }
+
+    /// Records a request abandoned before its response was read, e.g. because it was cancelled
+    /// or timed out. Requests routed by `response_routes` are recorded when abandoned.
+    fn record_orphaned_stream(&self) {}
+
+    /// Records a drained response to a request recorded by `record_orphaned_stream`.
+    fn release_orphaned_stream(&self) {}
+
+    /// Returns number of abandoned requests whose responses haven't been drained yet, if the
+    /// transport tracks them.
+    fn orphaned_streams(&self) -> usize {
+        0
+    }
+}
+
+/// Writes whole frame to given transport and flushes it, so no data is left in intermediate
+/// buffers. The transport is not locked while the frame is written.
+pub(crate) async fn write_frame<T: CDRSTransport>(
+    transport: &Mutex<T>,
+    frame: &[u8],
+) -> io::Result<()> {
+    let writer = transport.lock().await.frame_writer();
+    writer.write(frame.to_vec()).await
+}
+
+/// Socket level options applied to TCP connections right after they are established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpSocketOptions {
+    /// Sets `TCP_NODELAY`. Defaults to `true`.
+    pub nodelay: bool,
+    /// Enables `SO_KEEPALIVE` with given idle time before the first probe. Defaults to `None`.
+    pub keepalive: Option<Duration>,
+    /// Interval between keep-alive probes. Only used when `keepalive` is set.
+    pub keepalive_interval: Option<Duration>,
+    /// Sets `SO_SNDBUF`. Defaults to `None` (system default).
+    pub send_buffer_size: Option<usize>,
+    /// Sets `SO_RCVBUF`. Defaults to `None` (system default).
+    pub recv_buffer_size: Option<usize>,
+    /// Delay before starting a connection attempt to the next address, if a node resolves to
+    /// multiple ones, e.g. both IPv6 and IPv4. Attempts alternate between address families and
+    /// the first established connection wins ("happy eyeballs"). Defaults to 250ms.
+    pub connection_attempt_delay: Duration,
+}
+
+impl Default for TcpSocketOptions {
+    fn default() -> Self {
+        TcpSocketOptions {
+            nodelay: true,
+            keepalive: None,
+            keepalive_interval: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            connection_attempt_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+impl TcpSocketOptions {
+    /// Applies options to given stream.
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+
+        let socket = SockRef::from(stream);
+
+        if let Some(keepalive) = self.keepalive {
+            let mut params = TcpKeepalive::new().with_time(keepalive);
+            if let Some(interval) = self.keepalive_interval {
+                params = params.with_interval(interval);
+            }
+
+            socket.set_tcp_keepalive(&params)?;
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+
+    async fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<TcpStream> {
+        let addrs = interleave_families(lookup_host(addr).await?.collect());
+        trace!("Connecting to {:?}", addrs);
+
+        let stream = match addrs.as_slice() {
+            [addr] => TcpStream::connect(addr).await,
+            _ => connect_happy_eyeballs(addrs.clone(), self.connection_attempt_delay).await,
+        }
+        .map_err(|error| {
+            debug!("Connecting to {:?} failed: {}", addrs, error);
+            error
+        })?;
+
+        if let (Ok(peer_addr), Ok(local_addr)) = (stream.peer_addr(), stream.local_addr()) {
+            debug!(
+                "Connected to {} from {} with {:?}",
+                peer_addr, local_addr, self
+            );
+        }
+
+        self.apply(&stream)?;
+        Ok(stream)
+    }
+}
+
+/// Orders addresses so that consecutive ones belong to different families, starting with the
+/// family of the first resolved address (RFC 8305).
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return interleaved,
+            (preferred, other) => {
+                interleaved.extend(preferred);
+                interleaved.extend(other);
+            }
+        }
+    }
+}
+
+/// Starts connection attempts to given addresses one after another, each after the previous
+/// one failed or `attempt_delay` passed, and returns the first established connection.
+async fn connect_happy_eyeballs(
+    addrs: Vec<SocketAddr>,
+    attempt_delay: Duration,
+) -> io::Result<TcpStream> {
+    let (results_tx, mut results) = mpsc::channel(addrs.len().max(1));
+    let mut addrs = addrs.into_iter().peekable();
+    let mut attempts = Vec::new();
+    let mut running = 0;
+    let mut last_error = None;
+
+    let stream = loop {
+        if let Some(addr) = addrs.next() {
+            let results_tx = results_tx.clone();
+            attempts.push(tokio::spawn(async move {
+                let _ = results_tx.send(TcpStream::connect(addr).await).await;
+            }));
+            running += 1;
+        }
+
+        if running == 0 {
+            break Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "No addresses to connect to")
+            }));
+        }
+
+        let result = if addrs.peek().is_some() {
+            match timeout(attempt_delay, results.recv()).await {
+                Ok(result) => result,
+                // start next attempt without abandoning running ones
+                Err(_) => continue,
+            }
+        } else {
+            results.recv().await
+        };
+
+        match result {
+            Some(Ok(stream)) => break Ok(stream),
+            Some(Err(error)) => {
+                running -= 1;
+                last_error = Some(error);
+            }
+            None => unreachable!("sender is held by this function"),
+        }
+    };
+
+    for attempt in attempts {
+        attempt.abort();
+    }
+
+    stream
+}
+
+/// Write coalescing configuration. Outgoing frames queued for the writer task of a connection
+/// are gathered in a buffer and sent with a single write once the buffer grows past
+/// `max_buffer_size` or the first buffered frame waited for `max_delay` - whichever happens
+/// first. Without coalescing, queued frames are written one by one and flushed together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteCoalescing {
+    /// Maximum time data can wait in the buffer.
+    pub max_delay: Duration,
+    /// Buffer size which triggers a write.
+    pub max_buffer_size: usize,
+}
+
+impl Default for WriteCoalescing {
+    fn default() -> Self {
+        WriteCoalescing {
+            max_delay: Duration::from_micros(200),
+            max_buffer_size: 16 * 1024,
+        }
+    }
+}
+
+/// Stream which can be used as the underlying stream of a transport.
+pub trait TransportStream: AsyncRead + AsyncWrite + Send + Sync + Unpin {
+    /// Checks if the stream is still connected.
+    fn is_alive(&self) -> bool;
+}
+
+impl TransportStream for TcpStream {
+    fn is_alive(&self) -> bool {
+        self.peer_addr().is_ok()
+    }
+}
+
+/// Hook which allows wrapping freshly established TCP streams before the driver uses them, e.g.
+/// to perform a custom handshake or add custom framing required by a proxy. Wrapping happens
+/// before TLS negotiation for TLS transports.
+#[async_trait]
+pub trait TransportWrapper: Send + Sync {
+    /// Wraps given stream. Socket options are already applied to it.
+    async fn wrap(&self, stream: TcpStream) -> io::Result<Box<dyn TransportStream>>;
+}
+
+/// Number of outgoing frames queued for the writer task of a connection before writers wait for
+/// the queue to drain.
+const WRITE_QUEUE_SIZE: usize = 64;
+/// Number of frames without routes read ahead from the socket by the reader task of
+/// a connection.
+const READ_QUEUE_SIZE: usize = 16;
+/// Minimum size of a chunk read from the socket at once.
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Response routed to a request, or the error which ended the connection.
+type RoutedFrame = io::Result<Vec<u8>>;
+
+#[derive(Debug)]
+enum Route {
+    Waiting(oneshot::Sender<RoutedFrame>),
+    // the request was abandoned, so its response is dropped once it arrives
+    Orphaned,
+}
+
+/// Requests in flight on a connection, whose responses are routed to them by stream id by the
+/// reader task of the connection. Responses to other streams, e.g. handshakes and events, are
+/// read from the transport.
+#[derive(Debug)]
+pub struct ResponseRoutes {
+    // `None` once the connection is closed
+    routes: SyncMutex<Option<FxHashMap<StreamId, Route>>>,
+    orphaned: AtomicUsize,
+}
+
+impl Default for ResponseRoutes {
+    fn default() -> Self {
+        ResponseRoutes {
+            routes: SyncMutex::new(Some(Default::default())),
+            orphaned: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ResponseRoutes {
+    /// Registers a request sent on given stream, before its frame is written. Fails if the
+    /// connection is closed or another request in flight uses the stream.
+    pub fn register(self: &Arc<Self>, stream: StreamId) -> io::Result<RoutedResponse> {
+        let (route, response) = oneshot::channel();
+        let mut routes = self.routes.lock().unwrap();
+        let routes = routes.as_mut().ok_or_else(connection_closed)?;
+        if routes.contains_key(&stream) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("Stream {} is in use", stream),
+            ));
+        }
+
+        routes.insert(stream, Route::Waiting(route));
+        Ok(RoutedResponse {
+            routes: self.clone(),
+            stream,
+            response,
+            done: false,
+        })
+    }
+
+    /// Returns number of abandoned requests whose responses haven't arrived yet.
+    pub fn orphaned(&self) -> usize {
+        self.orphaned.load(Ordering::Relaxed)
+    }
+
+    fn record_orphaned(&self) {
+        self.orphaned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn release_orphaned(&self) {
+        let _ = self
+            .orphaned
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |orphaned| {
+                orphaned.checked_sub(1)
+            });
+    }
+
+    /// Passes given frame to the request waiting for it, returning it back if no request
+    /// waits for its stream.
+    fn route(&self, stream: StreamId, frame: Vec<u8>) -> Option<Vec<u8>> {
+        let route = match self.routes.lock().unwrap().as_mut() {
+            Some(routes) => routes.remove(&stream),
+            None => None,
+        };
+
+        match route {
+            Some(Route::Waiting(route)) => {
+                // the request can be abandoned after the frame is passed, but then its
+                // response has arrived anyway
+                let _ = route.send(Ok(frame));
+                None
+            }
+            Some(Route::Orphaned) => {
+                self.release_orphaned();
+                None
+            }
+            None => Some(frame),
+        }
+    }
+
+    /// Fails all requests in flight with given error, as well as requests registered later.
+    fn close(&self, error: &io::Error) {
+        if let Some(routes) = self.routes.lock().unwrap().take() {
+            for route in routes.into_values() {
+                if let Route::Waiting(route) = route {
+                    let _ = route.send(Err(copy_error(error)));
+                }
+            }
+        }
+    }
+
+    fn remove(&self, stream: StreamId, orphan: bool) {
+        if let Some(routes) = self.routes.lock().unwrap().as_mut() {
+            if let Some(Route::Waiting(_)) = routes.get(&stream) {
+                if orphan {
+                    routes.insert(stream, Route::Orphaned);
+                    self.record_orphaned();
+                } else {
+                    routes.remove(&stream);
+                }
+            }
+        }
+    }
+}
+
+/// Response to a request registered with `ResponseRoutes::register`. If the request is
+/// abandoned before its response arrives, e.g. because it timed out or was cancelled, its
+/// stream is recorded as orphaned until the response arrives.
+#[derive(Debug)]
+pub struct RoutedResponse {
+    routes: Arc<ResponseRoutes>,
+    stream: StreamId,
+    response: oneshot::Receiver<RoutedFrame>,
+    done: bool,
+}
+
+impl RoutedResponse {
+    /// Waits for the response frame, header included.
+    pub async fn recv(&mut self) -> io::Result<Vec<u8>> {
+        let response = (&mut self.response)
+            .await
+            .unwrap_or_else(|_| Err(connection_closed()));
+        self.done = true;
+        response
+    }
+
+    /// Unregisters the request without waiting for its response, e.g. because its frame
+    /// could not be written.
+    pub fn cancel(mut self) {
+        self.done = true;
+        self.routes.remove(self.stream, false);
+    }
+}
+
+impl Drop for RoutedResponse {
+    fn drop(&mut self) {
+        if !self.done {
+            self.routes.remove(self.stream, true);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum WriteRequest {
+    Frame(Vec<u8>, oneshot::Sender<io::Result<()>>),
+    Shutdown(oneshot::Sender<io::Result<()>>),
+}
+
+/// Handle of the writer task of a connection. Frames are queued and written to the socket by the
+/// task, so writers don't contend for the socket and wait only if the queue is full. Frames
+/// queued at the same time are written with a single flush.
+#[derive(Debug, Clone)]
+pub struct FrameWriter {
+    requests: mpsc::Sender<WriteRequest>,
+}
+
+impl FrameWriter {
+    /// Spawns a writer task writing to given writer, e.g. the writing half of a custom
+    /// transport.
+    pub fn spawn<W: AsyncWrite + Send + Unpin + 'static>(writer: W) -> Self {
+        Self::spawn_with(writer, Default::default(), Default::default())
+    }
+
+    fn spawn_with<W: AsyncWrite + Send + Unpin + 'static>(
+        writer: W,
+        write_coalescing: Arc<SyncMutex<Option<WriteCoalescing>>>,
+        state: Arc<ConnectionStateCell>,
+    ) -> Self {
+        let (requests, requests_rx) = mpsc::channel(WRITE_QUEUE_SIZE);
+        tokio::spawn(run_writer(writer, requests_rx, write_coalescing, state));
+
+        FrameWriter { requests }
+    }
+
+    /// Queues given frame and waits until it is written and flushed.
+    pub async fn write(&self, frame: Vec<u8>) -> io::Result<()> {
+        let (ack_tx, ack) = oneshot::channel();
+        self.send(WriteRequest::Frame(frame, ack_tx), ack).await
+    }
+
+    /// Writes queued frames and shuts down the writing half of the connection.
+    pub async fn shutdown(&self) -> io::Result<()> {
+        let (ack_tx, ack) = oneshot::channel();
+        self.send(WriteRequest::Shutdown(ack_tx), ack).await
+    }
+
+    async fn send(
+        &self,
+        request: WriteRequest,
+        ack: oneshot::Receiver<io::Result<()>>,
+    ) -> io::Result<()> {
+        self.requests
+            .send(request)
+            .await
+            .map_err(|_| connection_closed())?;
+        ack.await.unwrap_or_else(|_| Err(connection_closed()))
+    }
+}
+
+fn connection_closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "Connection is closed")
+}
+
+fn copy_error(error: &io::Error) -> io::Error {
+    io::Error::new(error.kind(), error.to_string())
+}
+
+fn copy_result(result: &io::Result<()>) -> io::Result<()> {
+    result.as_ref().map(|_| ()).map_err(copy_error)
+}
+
+async fn run_writer<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut requests: mpsc::Receiver<WriteRequest>,
+    write_coalescing: Arc<SyncMutex<Option<WriteCoalescing>>>,
+    state: Arc<ConnectionStateCell>,
+) {
+    // reused by all batches, so coalescing doesn't allocate once the buffer has grown
+    let mut buffer = vec![];
+
+    while let Some(request) = requests.recv().await {
+        let write_coalescing = *write_coalescing
+            .lock()
+            .expect("Cannot read write coalescing!");
+        let deadline =
+            write_coalescing.map(|write_coalescing| Instant::now() + write_coalescing.max_delay);
+
+        let mut result = if state.get() != ConnectionState::Closed {
+            Ok(())
+        } else {
+            Err(connection_closed())
+        };
+        let mut acks = vec![];
+        let mut shutdown = None;
+        let mut request = Some(request);
+
+        // write all frames queued so far, or coalesced until the deadline, before flushing
+        while let Some(next) = request.take() {
+            match next {
+                WriteRequest::Frame(frame, ack) => {
+                    if result.is_ok() {
+                        result = match write_coalescing {
+                            Some(write_coalescing) => {
+                                coalesce(&mut writer, &mut buffer, &frame, &write_coalescing).await
+                            }
+                            None => writer.write_all(&frame).await,
+                        };
+                    }
+                    acks.push(ack);
+                }
+                WriteRequest::Shutdown(ack) => {
+                    shutdown = Some(ack);
+                    break;
+                }
+            }
+
+            request = match (requests.try_recv(), deadline) {
+                (Ok(next), _) => Some(next),
+                (Err(_), Some(deadline)) if !buffer.is_empty() => {
+                    timeout_at(deadline, requests.recv()).await.ok().flatten()
+                }
+                (Err(_), _) => None,
+            };
+        }
+
+        if result.is_ok() && !buffer.is_empty() {
+            result = writer.write_all(&buffer).await;
+        }
+        buffer.clear();
+
+        if result.is_ok() {
+            result = writer.flush().await;
+        }
+
+        if result.is_err() {
+            state.advance(ConnectionState::Closed);
+        }
+
+        for ack in acks {
+            let _ = ack.send(copy_result(&result));
+        }
+
+        if let Some(ack) = shutdown {
+            let result = writer.shutdown().await;
+            state.advance(ConnectionState::Closed);
+            let _ = ack.send(result);
+            return;
+        }
+    }
+}
+
+/// Appends given frame to the coalescing buffer, writing the buffer first with a single write if
+/// the frame doesn't fit in it.
+async fn coalesce<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    buffer: &mut Vec<u8>,
+    frame: &[u8],
+    write_coalescing: &WriteCoalescing,
+) -> io::Result<()> {
+    if !buffer.is_empty() && buffer.len() + frame.len() > write_coalescing.max_buffer_size {
+        writer.write_all(buffer).await?;
+        buffer.clear();
+    }
+
+    buffer.extend_from_slice(frame);
+    if buffer.len() >= write_coalescing.max_buffer_size {
+        writer.write_all(buffer).await?;
+        buffer.clear();
+        // frames larger than the buffer are not kept allocated
+        buffer.shrink_to(write_coalescing.max_buffer_size);
+    }
+
+    Ok(())
+}
+
+/// Returns the length of the first frame in given buffer, if the buffer holds all of it.
+/// A header declaring a body longer than `MAX_BODY_LEN` is returned alone, so it fails to parse.
+fn frame_len(buffer: &[u8]) -> Option<usize> {
+    let header = buffer.get(..HEADER_LEN)?;
+    match body_len(header) {
+        body_len if body_len > MAX_BODY_LEN => Some(HEADER_LEN),
+        body_len => Some(HEADER_LEN + body_len).filter(|frame_len| buffer.len() >= *frame_len),
+    }
+}
+
+fn body_len(header: &[u8]) -> usize {
+    u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize
+}
+
+async fn run_reader<R: AsyncRead + Unpin>(
+    mut reader: R,
+    frames: mpsc::Sender<io::Result<Vec<u8>>>,
+    routes: Arc<ResponseRoutes>,
+    state: Arc<ConnectionStateCell>,
+) {
+    let mut buffer = Vec::with_capacity(READ_CHUNK_SIZE);
+
+    let error = 'read: loop {
+        while let Some(frame_len) = frame_len(&buffer) {
+            let rest = buffer.split_off(frame_len);
+            let frame = mem::replace(&mut buffer, rest);
+            let malformed = body_len(&frame) > MAX_BODY_LEN;
+            let stream = i16::from_be_bytes([frame[2], frame[3]]);
+
+            if let Some(frame) = routes.route(stream, frame) {
+                if frames.send(Ok(frame)).await.is_err() {
+                    return;
+                }
+            }
+
+            if malformed {
+                // following bytes cannot be split into frames
+                break 'read io::Error::new(io::ErrorKind::InvalidData, "Malformed frame received");
+            }
+        }
+
+        buffer.reserve(READ_CHUNK_SIZE);
+        let result = tokio::select! {
+            result = reader.read_buf(&mut buffer) => result,
+            _ = frames.closed() => return,
+        };
+
+        match result {
+            // end of stream - dropping the sender ends reads
+            Ok(0) => {
+                // a partially received frame fails to parse as truncated
+                if !buffer.is_empty() {
+                    let _ = frames.send(Ok(mem::take(&mut buffer))).await;
+                }
+                break connection_closed();
+            }
+            Ok(_) => {}
+            Err(error) => {
+                let _ = frames.send(Err(copy_error(&error))).await;
+                break error;
+            }
+        }
+    };
+
+    state.advance(ConnectionState::Closed);
+    routes.close(&error);
+}
+
+/// Reading and writing ends of a connection, served by dedicated reader and writer tasks. The
+/// reader task splits received data into frames and routes responses to requests registered in
+/// `routes`. Other frames are read from the connection, ahead by at most `READ_QUEUE_SIZE`
+/// frames, so a slow consumer slows down reading from the socket. Both tasks end when the
+/// connection is dropped.
+struct ConnectionIo {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+    writer: FrameWriter,
+    write_coalescing: Arc<SyncMutex<Option<WriteCoalescing>>>,
+    routes: Arc<ResponseRoutes>,
+    state: Arc<ConnectionStateCell>,
+}
+
+impl ConnectionIo {
+    fn spawn<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let (chunks_tx, chunks) = mpsc::channel(READ_QUEUE_SIZE);
+        let write_coalescing: Arc<SyncMutex<Option<WriteCoalescing>>> = Default::default();
+        let routes: Arc<ResponseRoutes> = Default::default();
+        let state: Arc<ConnectionStateCell> = Default::default();
+
+        tokio::spawn(run_reader(reader, chunks_tx, routes.clone(), state.clone()));
+        let writer = FrameWriter::spawn_with(writer, write_coalescing.clone(), state.clone());
+
+        ConnectionIo {
+            chunks,
+            chunk: vec![],
+            position: 0,
+            writer,
+            write_coalescing,
+            routes,
+            state,
+        }
+    }
+
+    fn set_write_coalescing(&self, write_coalescing: Option<WriteCoalescing>) {
+        *self
+            .write_coalescing
+            .lock()
+            .expect("Cannot write write coalescing!") = write_coalescing;
+    }
+
+    fn write_coalescing(&self) -> Option<WriteCoalescing> {
+        *self
+            .write_coalescing
+            .lock()
+            .expect("Cannot read write coalescing!")
+    }
+
+    async fn close(&self) -> io::Result<()> {
+        self.state.advance(ConnectionState::Draining);
+        self.writer.shutdown().await
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.position < self.chunk.len() {
+                let len = buf.remaining().min(self.chunk.len() - self.position);
+                buf.put_slice(&self.chunk[self.position..self.position + len]);
+                self.position += len;
+                return Poll::Ready(Ok(()));
+            }
+
+            match self.chunks.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Err(error)),
+                // end of stream
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
 }
 
 /// Default Tcp transport.
 pub struct TransportTcp {
-    tcp: TcpStream,
+    io: ConnectionIo,
     addr: String,
     keyspace_holder: Arc<KeyspaceHolder>,
+    socket_options: TcpSocketOptions,
+    transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    compression_stats: Arc<CompressionStats>,
+    max_decompressed_length: usize,
+    lwt_metadata_mask: AtomicI32,
+    protocol_version: AtomicU8,
 }
 
 impl TransportTcp {
@@ -69,12 +857,77 @@ impl TransportTcp {
     /// }
     /// ```
     pub async fn new(addr: &str, keyspace_holder: Arc<KeyspaceHolder>) -> io::Result<TransportTcp> {
-        TcpStream::connect(addr).await.map(|socket| TransportTcp {
-            tcp: socket,
+        Self::with_options(addr, keyspace_holder, Default::default()).await
+    }
+
+    /// Constructs a new `TransportTcp` with given socket options.
+    pub async fn with_options(
+        addr: &str,
+        keyspace_holder: Arc<KeyspaceHolder>,
+        socket_options: TcpSocketOptions,
+    ) -> io::Result<TransportTcp> {
+        Self::with_transport_wrapper(addr, keyspace_holder, socket_options, None).await
+    }
+
+    /// Constructs a new `TransportTcp` with given socket options, wrapping the established
+    /// stream with given wrapper if any.
+    pub async fn with_transport_wrapper(
+        addr: &str,
+        keyspace_holder: Arc<KeyspaceHolder>,
+        socket_options: TcpSocketOptions,
+        transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    ) -> io::Result<TransportTcp> {
+        let socket = socket_options.connect(addr).await?;
+        let io = match transport_wrapper {
+            Some(ref transport_wrapper) => {
+                ConnectionIo::spawn(transport_wrapper.wrap(socket).await?)
+            }
+            None => ConnectionIo::spawn(socket),
+        };
+
+        Ok(TransportTcp {
+            io,
             addr: addr.to_string(),
             keyspace_holder,
+            socket_options,
+            transport_wrapper,
+            compression_stats: Default::default(),
+            max_decompressed_length: DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            lwt_metadata_mask: AtomicI32::new(0),
+            protocol_version: AtomicU8::new(ProtocolVersion::default().request_byte()),
         })
     }
+
+    /// Enables or disables write coalescing.
+    pub fn with_write_coalescing(self, write_coalescing: Option<WriteCoalescing>) -> Self {
+        self.io.set_write_coalescing(write_coalescing);
+        self
+    }
+
+    /// Returns current write coalescing configuration.
+    pub fn write_coalescing(&self) -> Option<WriteCoalescing> {
+        self.io.write_coalescing()
+    }
+
+    /// Counts the connection in given states of connections to its node.
+    pub fn with_connection_states(self, states: Arc<ConnectionStates>) -> Self {
+        self.io.state.track(states);
+        self
+    }
+
+    /// Records compressed frames in given statistics, e.g. shared by all connections to the
+    /// node.
+    pub fn with_compression_stats(mut self, compression_stats: Arc<CompressionStats>) -> Self {
+        self.compression_stats = compression_stats;
+        self
+    }
+
+    /// Sets the maximum length of a decompressed frame body. Frames declaring longer bodies
+    /// fail to parse before their bodies are allocated.
+    pub fn with_max_decompressed_length(mut self, max_decompressed_length: usize) -> Self {
+        self.max_decompressed_length = max_decompressed_length;
+        self
+    }
 }
 
 impl AsyncRead for TransportTcp {
@@ -83,60 +936,113 @@ impl AsyncRead for TransportTcp {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.tcp).poll_read(cx, buf)
+        self.io.poll_read(cx, buf)
     }
 }
 
-impl AsyncWrite for TransportTcp {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, Error>> {
-        Pin::new(&mut self.tcp).poll_write(cx, buf)
+#[async_trait]
+impl CDRSTransport for TransportTcp {
+    async fn try_clone(&self) -> io::Result<TransportTcp> {
+        Self::with_transport_wrapper(
+            self.addr.as_str(),
+            self.keyspace_holder.clone(),
+            self.socket_options,
+            self.transport_wrapper.clone(),
+        )
+        .await
+        .map(|transport| {
+            transport.set_protocol_version(self.protocol_version());
+            transport
+                .with_write_coalescing(self.write_coalescing())
+                .with_compression_stats(self.compression_stats.clone())
+                .with_max_decompressed_length(self.max_decompressed_length)
+        })
     }
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Pin::new(&mut self.tcp).poll_flush(cx)
+    async fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
+        self.io.close().await
     }
 
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Pin::new(&mut self.tcp).poll_shutdown(cx)
+    fn state(&self) -> ConnectionState {
+        self.io.state.get()
     }
-}
 
-#[async_trait]
-impl CDRSTransport for TransportTcp {
-    async fn try_clone(&self) -> io::Result<TransportTcp> {
-        TcpStream::connect(self.addr.as_str())
-            .await
-            .map(|socket| TransportTcp {
-                tcp: socket,
-                addr: self.addr.clone(),
-                keyspace_holder: self.keyspace_holder.clone(),
-            })
+    fn mark_ready(&self) {
+        self.io.state.advance(ConnectionState::Ready);
     }
 
-    async fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
-        self.tcp.shutdown().await
+    fn set_lwt_metadata_mask(&self, mask: i32) {
+        self.lwt_metadata_mask.store(mask, Ordering::Relaxed);
     }
 
-    fn is_alive(&self) -> bool {
-        self.tcp.peer_addr().is_ok()
+    fn lwt_metadata_mask(&self) -> Option<i32> {
+        Some(self.lwt_metadata_mask.load(Ordering::Relaxed)).filter(|mask| *mask != 0)
+    }
+
+    fn set_protocol_version(&self, version: ProtocolVersion) {
+        self.protocol_version
+            .store(version.request_byte(), Ordering::Relaxed);
+    }
+
+    fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::from_byte(self.protocol_version.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    fn frame_writer(&self) -> FrameWriter {
+        self.io.writer.clone()
+    }
+
+    fn response_routes(&self) -> Option<Arc<ResponseRoutes>> {
+        Some(self.io.routes.clone())
+    }
+
+    fn addr(&self) -> Option<String> {
+        Some(self.addr.clone())
     }
 
     async fn set_current_keyspace(&self, keyspace: &str) {
         self.keyspace_holder.set_current_keyspace(keyspace).await;
     }
+
+    async fn current_keyspace(&self) -> Option<String> {
+        self.keyspace_holder.current_keyspace().await
+    }
+
+    fn compression_stats(&self) -> Option<Arc<CompressionStats>> {
+        Some(self.compression_stats.clone())
+    }
+
+    fn max_decompressed_length(&self) -> usize {
+        self.max_decompressed_length
+    }
+
+    fn record_orphaned_stream(&self) {
+        self.io.routes.record_orphaned();
+    }
+
+    fn release_orphaned_stream(&self) {
+        self.io.routes.release_orphaned();
+    }
+
+    fn orphaned_streams(&self) -> usize {
+        self.io.routes.orphaned()
+    }
 }
 
 #[cfg(feature = "rust-tls")]
 pub struct TransportRustls {
-    inner: RustlsStream<TcpStream>,
+    io: ConnectionIo,
     config: Arc<rustls::ClientConfig>,
     addr: net::SocketAddr,
     dns_name: webpki::DNSName,
     keyspace_holder: Arc<KeyspaceHolder>,
+    socket_options: TcpSocketOptions,
+    transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    compression_stats: Arc<CompressionStats>,
+    max_decompressed_length: usize,
+    lwt_metadata_mask: AtomicI32,
+    protocol_version: AtomicU8,
 }
 
 #[cfg(feature = "rust-tls")]
@@ -148,18 +1054,116 @@ impl TransportRustls {
         config: Arc<rustls::ClientConfig>,
         keyspace_holder: Arc<KeyspaceHolder>,
     ) -> io::Result<Self> {
-        let stream = TcpStream::connect(addr).await?;
-        let connector = RustlsConnector::from(config.clone());
-        let stream = connector.connect(dns_name.as_ref(), stream).await?;
+        Self::with_options(addr, dns_name, config, keyspace_holder, Default::default()).await
+    }
+
+    ///Creates new instance with provided configuration and socket options
+    pub async fn with_options(
+        addr: net::SocketAddr,
+        dns_name: webpki::DNSName,
+        config: Arc<rustls::ClientConfig>,
+        keyspace_holder: Arc<KeyspaceHolder>,
+        socket_options: TcpSocketOptions,
+    ) -> io::Result<Self> {
+        Self::with_transport_wrapper(
+            addr,
+            dns_name,
+            config,
+            keyspace_holder,
+            socket_options,
+            None,
+        )
+        .await
+    }
+
+    ///Creates new instance with provided configuration and socket options, wrapping the
+    ///established stream with given wrapper if any before TLS negotiation
+    pub async fn with_transport_wrapper(
+        addr: net::SocketAddr,
+        dns_name: webpki::DNSName,
+        config: Arc<rustls::ClientConfig>,
+        keyspace_holder: Arc<KeyspaceHolder>,
+        socket_options: TcpSocketOptions,
+        transport_wrapper: Option<Arc<dyn TransportWrapper>>,
+    ) -> io::Result<Self> {
+        let socket = socket_options.connect(addr).await?;
+        let io = match transport_wrapper {
+            Some(ref transport_wrapper) => {
+                let stream = transport_wrapper.wrap(socket).await?;
+                Self::connect_tls(stream, addr, &dns_name, &config).await?
+            }
+            None => Self::connect_tls(socket, addr, &dns_name, &config).await?,
+        };
 
         Ok(Self {
-            inner: stream,
+            io,
             config,
             addr,
             dns_name,
             keyspace_holder,
+            socket_options,
+            transport_wrapper,
+            compression_stats: Default::default(),
+            max_decompressed_length: DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            lwt_metadata_mask: AtomicI32::new(0),
+            protocol_version: AtomicU8::new(ProtocolVersion::default().request_byte()),
         })
     }
+
+    async fn connect_tls<S: AsyncRead + AsyncWrite + Send + Unpin + 'static>(
+        stream: S,
+        addr: net::SocketAddr,
+        dns_name: &webpki::DNSName,
+        config: &Arc<rustls::ClientConfig>,
+    ) -> io::Result<ConnectionIo> {
+        let connector = RustlsConnector::from(config.clone());
+        let stream: RustlsStream<S> =
+            connector
+                .connect(dns_name.as_ref(), stream)
+                .await
+                .map_err(|error| {
+                    debug!("TLS handshake with {} failed: {}", addr, error);
+                    error
+                })?;
+        debug!(
+            "TLS handshake with {} completed using {:?}",
+            addr,
+            stream.get_ref().1.get_protocol_version()
+        );
+
+        Ok(ConnectionIo::spawn(stream))
+    }
+
+    /// Enables or disables write coalescing.
+    pub fn with_write_coalescing(self, write_coalescing: Option<WriteCoalescing>) -> Self {
+        self.io.set_write_coalescing(write_coalescing);
+        self
+    }
+
+    /// Returns current write coalescing configuration.
+    pub fn write_coalescing(&self) -> Option<WriteCoalescing> {
+        self.io.write_coalescing()
+    }
+
+    /// Counts the connection in given states of connections to its node.
+    pub fn with_connection_states(self, states: Arc<ConnectionStates>) -> Self {
+        self.io.state.track(states);
+        self
+    }
+
+    /// Records compressed frames in given statistics, e.g. shared by all connections to the
+    /// node.
+    pub fn with_compression_stats(mut self, compression_stats: Arc<CompressionStats>) -> Self {
+        self.compression_stats = compression_stats;
+        self
+    }
+
+    /// Sets the maximum length of a decompressed frame body. Frames declaring longer bodies
+    /// fail to parse before their bodies are allocated.
+    pub fn with_max_decompressed_length(mut self, max_decompressed_length: usize) -> Self {
+        self.max_decompressed_length = max_decompressed_length;
+        self
+    }
 }
 
 #[cfg(feature = "rust-tls")]
@@ -170,29 +1174,7 @@ impl AsyncRead for TransportRustls {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.inner).poll_read(cx, buf)
-    }
-}
-
-#[cfg(feature = "rust-tls")]
-impl AsyncWrite for TransportRustls {
-    #[inline]
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, Error>> {
-        Pin::new(&mut self.inner).poll_write(cx, buf)
-    }
-
-    #[inline]
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Pin::new(&mut self.inner).poll_flush(cx)
-    }
-
-    #[inline]
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Pin::new(&mut self.inner).poll_shutdown(cx)
+        self.io.poll_read(cx, buf)
     }
 }
 
@@ -201,24 +1183,381 @@ impl AsyncWrite for TransportRustls {
 impl CDRSTransport for TransportRustls {
     #[inline]
     async fn try_clone(&self) -> io::Result<Self> {
-        Self::new(
+        Self::with_transport_wrapper(
             self.addr,
             self.dns_name.clone(),
             self.config.clone(),
             self.keyspace_holder.clone(),
+            self.socket_options,
+            self.transport_wrapper.clone(),
         )
         .await
+        .map(|transport| {
+            transport.set_protocol_version(self.protocol_version());
+            transport
+                .with_write_coalescing(self.write_coalescing())
+                .with_compression_stats(self.compression_stats.clone())
+                .with_max_decompressed_length(self.max_decompressed_length)
+        })
     }
 
     async fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
-        self.inner.get_mut().0.shutdown().await
+        self.io.close().await
     }
 
-    fn is_alive(&self) -> bool {
-        self.inner.get_ref().0.peer_addr().is_ok()
+    fn state(&self) -> ConnectionState {
+        self.io.state.get()
+    }
+
+    fn mark_ready(&self) {
+        self.io.state.advance(ConnectionState::Ready);
+    }
+
+    fn set_lwt_metadata_mask(&self, mask: i32) {
+        self.lwt_metadata_mask.store(mask, Ordering::Relaxed);
+    }
+
+    fn lwt_metadata_mask(&self) -> Option<i32> {
+        Some(self.lwt_metadata_mask.load(Ordering::Relaxed)).filter(|mask| *mask != 0)
+    }
+
+    fn set_protocol_version(&self, version: ProtocolVersion) {
+        self.protocol_version
+            .store(version.request_byte(), Ordering::Relaxed);
+    }
+
+    fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::from_byte(self.protocol_version.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    fn frame_writer(&self) -> FrameWriter {
+        self.io.writer.clone()
+    }
+
+    fn response_routes(&self) -> Option<Arc<ResponseRoutes>> {
+        Some(self.io.routes.clone())
+    }
+
+    fn addr(&self) -> Option<String> {
+        Some(self.addr.to_string())
     }
 
     async fn set_current_keyspace(&self, keyspace: &str) {
         self.keyspace_holder.set_current_keyspace(keyspace).await;
     }
+
+    async fn current_keyspace(&self) -> Option<String> {
+        self.keyspace_holder.current_keyspace().await
+    }
+
+    fn compression_stats(&self) -> Option<Arc<CompressionStats>> {
+        Some(self.compression_stats.clone())
+    }
+
+    fn max_decompressed_length(&self) -> usize {
+        self.max_decompressed_length
+    }
+
+    fn record_orphaned_stream(&self) {
+        self.io.routes.record_orphaned();
+    }
+
+    fn release_orphaned_stream(&self) {
+        self.io.routes.release_orphaned();
+    }
+
+    fn orphaned_streams(&self) -> usize {
+        self.io.routes.orphaned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn interleave_address_families() {
+        let addrs: Vec<SocketAddr> = vec![
+            "[::1]:9042".parse().unwrap(),
+            "[::2]:9042".parse().unwrap(),
+            "[::3]:9042".parse().unwrap(),
+            "127.0.0.1:9042".parse().unwrap(),
+        ];
+
+        assert_eq!(
+            interleave_families(addrs.clone()),
+            vec![addrs[0], addrs[3], addrs[1], addrs[2]]
+        );
+    }
+
+    #[tokio::test]
+    async fn happy_eyeballs_skips_failed_addresses() {
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // a refused connection starts the next attempt without waiting for the delay
+        let stream = timeout(
+            Duration::from_secs(5),
+            connect_happy_eyeballs(vec![closed_addr, addr], Duration::from_secs(60)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+
+        assert!(
+            connect_happy_eyeballs(vec![closed_addr], Duration::from_secs(60))
+                .await
+                .is_err()
+        );
+    }
+
+    fn response_frame(stream: StreamId, body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x84, 0];
+        frame.extend_from_slice(&stream.to_be_bytes());
+        frame.push(0x08);
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    async fn read_unrouted(io: &mut ConnectionIo, len: usize) -> io::Result<Vec<u8>> {
+        let mut read = vec![0; len];
+        let mut filled = 0;
+        while filled < len {
+            let read_len = poll_fn(|cx| {
+                let mut buf = ReadBuf::new(&mut read[filled..]);
+                io.poll_read(cx, &mut buf).map_ok(|()| buf.filled().len())
+            })
+            .await?;
+            if read_len == 0 {
+                break;
+            }
+            filled += read_len;
+        }
+
+        read.truncate(filled);
+        Ok(read)
+    }
+
+    /// Writer recording written data along with the number of writes.
+    #[derive(Default)]
+    struct CountingWriter {
+        written: Arc<SyncMutex<(usize, Vec<u8>)>>,
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let mut written = self.written.lock().unwrap();
+            written.0 += 1;
+            written.1.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Queues three frames at the same time with given coalescing, returning the number of
+    /// writes and written data.
+    async fn write_frames(write_coalescing: Option<WriteCoalescing>) -> (usize, Vec<u8>) {
+        let writer = CountingWriter::default();
+        let written = writer.written.clone();
+        let frame_writer = FrameWriter::spawn_with(
+            writer,
+            Arc::new(SyncMutex::new(write_coalescing)),
+            Default::default(),
+        );
+
+        let (first, second, third) = tokio::join!(
+            frame_writer.write(vec![1, 2, 3, 4]),
+            frame_writer.write(vec![5, 6, 7, 8]),
+            frame_writer.write(vec![9, 10, 11, 12])
+        );
+        first.unwrap();
+        second.unwrap();
+        third.unwrap();
+
+        let written = written.lock().unwrap().clone();
+        written
+    }
+
+    #[tokio::test]
+    async fn frames_are_coalesced() {
+        let frames: Vec<u8> = (1..=12).collect();
+        assert_eq!(write_frames(None).await, (3, frames.clone()));
+
+        let write_coalescing = WriteCoalescing {
+            max_delay: Duration::from_millis(20),
+            max_buffer_size: 1024,
+        };
+        assert_eq!(
+            write_frames(Some(write_coalescing)).await,
+            (1, frames.clone())
+        );
+
+        // full buffers are written without waiting for the delay
+        let write_coalescing = WriteCoalescing {
+            max_delay: Duration::from_millis(20),
+            max_buffer_size: 8,
+        };
+        assert_eq!(write_frames(Some(write_coalescing)).await, (2, frames));
+    }
+
+    #[tokio::test]
+    async fn connection_io_tasks() {
+        let (stream, mut peer) = tokio::io::duplex(64);
+        let mut io = ConnectionIo::spawn(stream);
+
+        let writer = io.writer.clone();
+        let (first, second) = tokio::join!(writer.write(vec![1, 2]), writer.write(vec![3]));
+        first.unwrap();
+        second.unwrap();
+
+        let mut written = [0; 3];
+        peer.read_exact(&mut written).await.unwrap();
+        written.sort_unstable();
+        assert_eq!(written, [1, 2, 3]);
+
+        // frames split across writes are read whole
+        let frame = response_frame(1, &[4, 5, 6]);
+        peer.write_all(&frame[..5]).await.unwrap();
+        peer.write_all(&frame[5..]).await.unwrap();
+        assert_eq!(read_unrouted(&mut io, frame.len()).await.unwrap(), frame);
+        assert_eq!(io.state.get(), ConnectionState::Connecting);
+
+        drop(peer);
+        assert!(read_unrouted(&mut io, 1).await.unwrap().is_empty());
+        assert_eq!(io.state.get(), ConnectionState::Closed);
+        assert!(writer.write(vec![7]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn responses_are_routed_by_stream() {
+        let (stream, mut peer) = tokio::io::duplex(1024);
+        let mut io = ConnectionIo::spawn(stream);
+
+        let mut first = io.routes.register(1).unwrap();
+        let mut second = io.routes.register(2).unwrap();
+        assert!(io.routes.register(2).is_err());
+        drop(io.routes.register(3).unwrap());
+        assert_eq!(io.routes.orphaned(), 1);
+
+        // responses arrive in any order, the one to the abandoned request is dropped and
+        // frames of other streams are read from the connection
+        let mut frames = response_frame(3, &[3]);
+        frames.extend(response_frame(2, &[2]));
+        frames.extend(response_frame(-1, &[0]));
+        frames.extend(response_frame(1, &[1]));
+        peer.write_all(&frames).await.unwrap();
+
+        assert_eq!(second.recv().await.unwrap(), response_frame(2, &[2]));
+        assert_eq!(first.recv().await.unwrap(), response_frame(1, &[1]));
+        assert_eq!(
+            read_unrouted(&mut io, HEADER_LEN + 1).await.unwrap(),
+            response_frame(-1, &[0])
+        );
+        assert_eq!(io.routes.orphaned(), 0);
+
+        // streams can be reused once their responses arrive
+        let mut pending = io.routes.register(1).unwrap();
+        drop(peer);
+        assert!(pending.recv().await.is_err());
+        assert!(io.routes.register(4).is_err());
+    }
+
+    /// Wrapper sending a preamble over each stream, as proxies requiring a custom handshake
+    /// would.
+    #[derive(Default)]
+    struct PreambleWrapper {
+        wrapped: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TransportWrapper for PreambleWrapper {
+        async fn wrap(&self, mut stream: TcpStream) -> io::Result<Box<dyn TransportStream>> {
+            self.wrapped.fetch_add(1, Ordering::Relaxed);
+            stream.write_all(b"PROXY").await?;
+            Ok(Box::new(stream))
+        }
+    }
+
+    struct FailingWrapper;
+
+    #[async_trait]
+    impl TransportWrapper for FailingWrapper {
+        async fn wrap(&self, _stream: TcpStream) -> io::Result<Box<dyn TransportStream>> {
+            Err(io::Error::new(io::ErrorKind::Other, "rejected by proxy"))
+        }
+    }
+
+    async fn read_preamble(listener: &TcpListener) -> TcpStream {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut preamble = [0; 5];
+        socket.read_exact(&mut preamble).await.unwrap();
+        assert_eq!(&preamble, b"PROXY");
+        socket
+    }
+
+    #[tokio::test]
+    async fn transport_wrapper_wraps_streams() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let wrapper = Arc::new(PreambleWrapper::default());
+
+        let transport = TransportTcp::with_transport_wrapper(
+            &addr,
+            Default::default(),
+            Default::default(),
+            Some(wrapper.clone()),
+        )
+        .await
+        .unwrap();
+        let mut socket = read_preamble(&listener).await;
+        assert_eq!(wrapper.wrapped.load(Ordering::Relaxed), 1);
+
+        // frames are written to the wrapped stream
+        transport.frame_writer().write(vec![1, 2, 3]).await.unwrap();
+        let mut written = [0; 3];
+        socket.read_exact(&mut written).await.unwrap();
+        assert_eq!(written, [1, 2, 3]);
+
+        // clones connect through the wrapper as well
+        let (_clone, _socket) = tokio::join!(transport.try_clone(), read_preamble(&listener));
+        assert_eq!(wrapper.wrapped.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn transport_wrapper_errors_fail_connecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let result = TransportTcp::with_transport_wrapper(
+            &addr,
+            Default::default(),
+            Default::default(),
+            Some(Arc::new(FailingWrapper)),
+        )
+        .await;
+        assert_eq!(
+            result.map(|_| ()).unwrap_err().to_string(),
+            "rejected by proxy"
+        );
+    }
 }
@@ -0,0 +1,29 @@
+/// Value of a Cassandra `custom` column, e.g. `DynamicCompositeType` or a type provided by a
+/// server plugin. The driver doesn't know how to decode such values, so they are exposed as
+/// raw bytes together with the Java class name of the type, for applications to decode.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct CustomValue {
+    class_name: String,
+    bytes: Vec<u8>,
+}
+
+impl CustomValue {
+    pub fn new(class_name: String, bytes: Vec<u8>) -> Self {
+        CustomValue { class_name, bytes }
+    }
+
+    /// Returns fully qualified class name of the custom type, as sent in result metadata.
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// Returns serialized value.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns serialized value, consuming the custom value.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
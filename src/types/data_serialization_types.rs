@@ -4,8 +4,8 @@ use std::string::FromUtf8Error;
 
 use super::blob::Blob;
 use super::decimal::Decimal;
+use super::duration::{self, CqlDuration};
 use super::*;
-use crate::error;
 use crate::frame::FromCursor;
 
 // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L813
@@ -72,6 +72,11 @@ pub fn decode_decimal(bytes: &[u8]) -> Result<Decimal, io::Error> {
     Ok(Decimal::new(unscaled, scale))
 }
 
+// Decodes Cassandra `duration` data (bytes) into Rust's `Result<CqlDuration, io::Error>`
+pub fn decode_duration(bytes: &[u8]) -> Result<CqlDuration, io::Error> {
+    duration::decode(bytes)
+}
+
 // Decodes Cassandra `double` data (bytes) into Rust's `Result<f32, io::Error>`
 pub fn decode_double(bytes: &[u8]) -> Result<f64, io::Error> {
     try_f64_from_bytes(bytes)
@@ -82,6 +87,30 @@ pub fn decode_float(bytes: &[u8]) -> Result<f32, io::Error> {
     try_f32_from_bytes(bytes)
 }
 
+// Decodes Cassandra `vector<float, n>` data (bytes) into Rust's `Result<Vec<f32>, io::Error>`
+pub fn decode_float_vector(bytes: &[u8], dimensions: usize) -> Result<Vec<f32>, io::Error> {
+    // dimensions come from the server, so the expected length can overflow
+    let expected = dimensions.checked_mul(4).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Too many dimensions of vector<float, {}>", dimensions),
+        )
+    })?;
+    if bytes.len() != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Expected {} bytes of vector<float, {}> but got {}",
+                expected,
+                dimensions,
+                bytes.len()
+            ),
+        ));
+    }
+
+    bytes.chunks(4).map(decode_float).collect()
+}
+
 // Decodes Cassandra `inet` data (bytes) into Rust's `Result<net::IpAddr, io::Error>`
 #[allow(clippy::many_single_char_names)]
 pub fn decode_inet(bytes: &[u8]) -> Result<net::IpAddr, io::Error> {
@@ -162,7 +191,10 @@ pub fn decode_smallint(bytes: &[u8]) -> Result<i16, io::Error> {
 
 // Decodes Cassandra `tinyint` data (bytes) into Rust's `Result<i8, io::Error>`
 pub fn decode_tinyint(bytes: &[u8]) -> Result<i8, io::Error> {
-    Ok(bytes[0] as i8)
+    bytes
+        .first()
+        .map(|byte| *byte as i8)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Empty tinyint value"))
 }
 
 // Decodes Cassandra `text` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
@@ -187,21 +219,19 @@ pub fn decode_varint(bytes: &[u8]) -> Result<i64, io::Error> {
 
 // Decodes Cassandra `Udt` data (bytes) into Rust's `Result<Vec<CBytes>, io::Error>`
 // each `CBytes` is encoded type of field of user defined type
+// Values serialized before fields were added to the type end early, so missing trailing fields
+// are decoded as nulls. Fields unknown to `l`, added after the metadata was obtained, are
+// ignored.
 pub fn decode_udt(bytes: &[u8], l: usize) -> Result<Vec<CBytes>, io::Error> {
     let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
     let mut udt = Vec::with_capacity(l);
     for _ in 0..l {
+        if cursor.position() as usize >= bytes.len() {
+            udt.push(CBytes::new_empty());
+            continue;
+        }
+
         let v = CBytes::from_cursor(&mut cursor)
-            .or_else(|err| match err {
-                error::Error::Io(io_err) => {
-                    if io_err.kind() == io::ErrorKind::UnexpectedEof {
-                        Ok(CBytes::new_empty())
-                    } else {
-                        Err(io_err.into())
-                    }
-                }
-                _ => Err(err),
-            })
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
         udt.push(v);
     }
@@ -226,9 +256,21 @@ mod tests {
     use super::super::super::error::*;
     use super::super::super::frame::frame_result::*;
     use super::*;
+    use crate::types::custom::CustomValue;
+    use crate::types::vector::{Vector, VectorType};
     use float_eq::*;
     use std::net::IpAddr;
 
+    #[test]
+    fn decode_float_vector_test() {
+        assert_eq!(
+            decode_float_vector(&[0x3f, 0x80, 0, 0, 0x40, 0, 0, 0], 2).unwrap(),
+            vec![1.0, 2.0]
+        );
+        assert!(decode_float_vector(&[0x3f, 0x80, 0, 0], 2).is_err());
+        assert!(decode_float_vector(&[], usize::MAX).is_err());
+    }
+
     #[test]
     fn decode_custom_test() {
         assert_eq!(decode_custom(b"abcd").unwrap(), "abcd".to_string());
@@ -413,6 +455,23 @@ mod tests {
         assert_eq!(udt[0].as_plain().unwrap(), vec![1, 2]);
     }
 
+    #[test]
+    fn decode_udt_missing_and_extra_fields() {
+        let udt = decode_udt(&[0, 0, 0, 1, 7], 3).unwrap();
+        assert_eq!(udt.len(), 3);
+        assert_eq!(udt[0].as_plain().unwrap(), vec![7]);
+        assert!(udt[1].is_empty());
+        assert!(udt[2].is_empty());
+
+        let udt = decode_udt(&[0, 0, 0, 1, 7, 0, 0, 0, 1, 8], 1).unwrap();
+        assert_eq!(udt.len(), 1);
+        assert_eq!(udt[0].as_plain().unwrap(), vec![7]);
+
+        // truncated fields are not mistaken for missing ones
+        assert!(decode_udt(&[0, 0, 0, 2, 7], 1).is_err());
+        assert!(decode_udt(&[0, 0, 0, 1, 7, 0, 0], 2).is_err());
+    }
+
     #[test]
     fn as_rust_blob_test() {
         let d_type = DataType { id: ColType::Blob };
@@ -454,6 +513,63 @@ mod tests {
         assert!(as_rust_type!(wrong_type, data, String).is_err());
     }
 
+    #[test]
+    fn as_rust_custom_value_test() {
+        let type_custom = ColTypeOption {
+            id: ColType::Custom,
+            value: Some(ColTypeOptionValue::CString(CString::new(
+                "org.apache.cassandra.db.marshal.DynamicCompositeType".into(),
+            ))),
+        };
+        let data = CBytes::new(vec![0x80, 0x62, 0, 1, 0x61, 0]);
+        let value = as_rust_type!(type_custom, data, CustomValue)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.class_name(),
+            "org.apache.cassandra.db.marshal.DynamicCompositeType"
+        );
+        assert_eq!(value.bytes(), &[0x80, 0x62, 0, 1, 0x61, 0]);
+
+        let null = CBytes::new_empty();
+        assert!(as_rust_type!(type_custom, null, CustomValue)
+            .unwrap()
+            .is_none());
+
+        let wrong_type = ColTypeOption {
+            id: ColType::Blob,
+            value: None,
+        };
+        assert!(as_rust_type!(wrong_type, data, CustomValue).is_err());
+    }
+
+    #[test]
+    fn as_rust_vector_test() {
+        let type_vector = ColTypeOption {
+            id: ColType::Custom,
+            value: Some(ColTypeOptionValue::CString(CString::new(
+                "org.apache.cassandra.db.marshal.VectorType(org.apache.cassandra.db.marshal.FloatType, 2)"
+                    .into(),
+            ))),
+        };
+        assert_eq!(type_vector.vector_type().unwrap().dimensions(), 2);
+
+        let data = CBytes::new(vec![0x3f, 0x80, 0, 0, 0xc0, 0x20, 0, 0]);
+        let vector = as_rust_type!(type_vector, data, Vector).unwrap().unwrap();
+        assert_eq!(vector.into_vec(), vec![1.0, -2.5]);
+
+        let wrong_len = CBytes::new(vec![0x3f, 0x80, 0, 0]);
+        assert!(as_rust_type!(type_vector, wrong_len, Vector).is_err());
+
+        let type_custom = ColTypeOption {
+            id: ColType::Custom,
+            value: Some(ColTypeOptionValue::CString(CString::new(
+                "org.apache.cassandra.db.marshal.DynamicCompositeType".into(),
+            ))),
+        };
+        assert!(as_rust_type!(type_custom, data, Vector).is_err());
+    }
+
     #[test]
     fn as_rust_bool_test() {
         let type_boolean = DataType {
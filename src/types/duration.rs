@@ -0,0 +1,209 @@
+use std::convert::TryFrom;
+use std::io;
+
+use crate::error::{Error, Result};
+use crate::frame::traits::AsBytes;
+
+/// Cassandra `duration` type. Months and days are kept apart from the time of a day, since their
+/// length depends on the date the duration is added to. All components need to have the same
+/// sign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CqlDuration {
+    months: i32,
+    days: i32,
+    nanoseconds: i64,
+}
+
+impl CqlDuration {
+    /// Creates a duration from its components. Returns an error if they have different signs.
+    pub fn new(months: i32, days: i32, nanoseconds: i64) -> Result<Self> {
+        let signs = [
+            months.signum() as i64,
+            days.signum() as i64,
+            nanoseconds.signum(),
+        ];
+        if signs.contains(&1) && signs.contains(&-1) {
+            return Err(Error::General(format!(
+                "Components of duration {}mo{}d{}ns need to have the same sign",
+                months, days, nanoseconds
+            )));
+        }
+
+        Ok(CqlDuration {
+            months,
+            days,
+            nanoseconds,
+        })
+    }
+
+    pub fn months(&self) -> i32 {
+        self.months
+    }
+
+    pub fn days(&self) -> i32 {
+        self.days
+    }
+
+    pub fn nanoseconds(&self) -> i64 {
+        self.nanoseconds
+    }
+}
+
+/// Converts the duration into nanoseconds only, i.e. a day is always 24 hours. Returns an error
+/// for durations longer than about 292 years.
+impl TryFrom<std::time::Duration> for CqlDuration {
+    type Error = Error;
+
+    fn try_from(duration: std::time::Duration) -> Result<Self> {
+        i64::try_from(duration.as_nanos())
+            .map_err(|_| out_of_range(duration.as_secs()))
+            .and_then(|nanoseconds| CqlDuration::new(0, 0, nanoseconds))
+    }
+}
+
+/// Converts the duration into nanoseconds only, i.e. a day is always 24 hours. Returns an error
+/// for durations longer than about 292 years in either direction.
+impl TryFrom<chrono::Duration> for CqlDuration {
+    type Error = Error;
+
+    fn try_from(duration: chrono::Duration) -> Result<Self> {
+        duration
+            .num_nanoseconds()
+            .ok_or_else(|| out_of_range(duration.num_seconds().unsigned_abs()))
+            .and_then(|nanoseconds| CqlDuration::new(0, 0, nanoseconds))
+    }
+}
+
+fn out_of_range(seconds: u64) -> Error {
+    Error::General(format!(
+        "Duration of {} seconds does not fit in nanoseconds of a CQL duration",
+        seconds
+    ))
+}
+
+impl AsBytes for CqlDuration {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        encode_vint(self.months as i64, &mut bytes);
+        encode_vint(self.days as i64, &mut bytes);
+        encode_vint(self.nanoseconds, &mut bytes);
+
+        bytes
+    }
+}
+
+/// Decodes a duration serialized as three variable length integers.
+pub(crate) fn decode(bytes: &[u8]) -> io::Result<CqlDuration> {
+    let mut bytes = bytes;
+    let months = decode_vint(&mut bytes)?;
+    let days = decode_vint(&mut bytes)?;
+    let nanoseconds = decode_vint(&mut bytes)?;
+
+    match (i32::try_from(months), i32::try_from(days)) {
+        (Ok(months), Ok(days)) => CqlDuration::new(months, days, nanoseconds)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string())),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Months and days of a duration need to fit in 32 bits",
+        )),
+    }
+}
+
+// Variable length integers are zigzag encoded and prefixed by as many leading one bits in the
+// first byte as there are bytes following it.
+fn encode_vint(value: i64, bytes: &mut Vec<u8>) {
+    let value = ((value << 1) ^ (value >> 63)) as u64;
+    let extra_bytes = (0..8)
+        .find(|extra_bytes| value < 1 << (7 * extra_bytes + 7))
+        .unwrap_or(8);
+
+    let encoded = value.to_be_bytes();
+    let first = match extra_bytes {
+        8 => 0xFF,
+        extra_bytes => encoded[7 - extra_bytes] | !(0xFF >> extra_bytes),
+    };
+
+    bytes.push(first);
+    bytes.extend_from_slice(&encoded[8 - extra_bytes..]);
+}
+
+fn decode_vint(bytes: &mut &[u8]) -> io::Result<i64> {
+    let (&first, rest) = bytes
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no bytes were found"))?;
+    let extra_bytes = first.leading_ones() as usize;
+    if rest.len() < extra_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "variable length integer is truncated",
+        ));
+    }
+
+    let first = match extra_bytes {
+        8 => 0,
+        extra_bytes => first & (0xFF >> extra_bytes),
+    };
+    let value = rest[..extra_bytes]
+        .iter()
+        .fold(first as u64, |value, byte| (value << 8) | *byte as u64);
+    *bytes = &rest[extra_bytes..];
+
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vint_round_trip() {
+        for value in [
+            0,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            1 << 20,
+            -(1 << 40),
+            i64::MAX,
+            i64::MIN,
+        ] {
+            let mut bytes = vec![];
+            encode_vint(value, &mut bytes);
+            assert_eq!(decode_vint(&mut bytes.as_slice()).unwrap(), value);
+        }
+
+        let mut bytes = vec![];
+        encode_vint(-1, &mut bytes);
+        encode_vint(64, &mut bytes);
+        assert_eq!(bytes, vec![0x01, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn from_std_duration() {
+        let duration = CqlDuration::try_from(std::time::Duration::from_millis(1_500)).unwrap();
+        assert_eq!(duration, CqlDuration::new(0, 0, 1_500_000_000).unwrap());
+        assert_eq!(decode(&duration.as_bytes()).unwrap(), duration);
+
+        assert!(CqlDuration::try_from(std::time::Duration::from_secs(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn from_chrono_duration() {
+        let duration = CqlDuration::try_from(chrono::Duration::seconds(-2)).unwrap();
+        assert_eq!(duration.nanoseconds(), -2_000_000_000);
+
+        assert!(CqlDuration::try_from(chrono::Duration::days(200 * 365 * 2)).is_err());
+    }
+
+    #[test]
+    fn mixed_signs() {
+        assert!(CqlDuration::new(1, -1, 0).is_err());
+        assert!(CqlDuration::new(-1, -2, -3).is_ok());
+        assert_eq!(
+            CqlDuration::new(1, 2, 3).unwrap().as_bytes(),
+            vec![0x02, 0x04, 0x06]
+        );
+    }
+}
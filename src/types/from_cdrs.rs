@@ -1,5 +1,6 @@
 use std::net::IpAddr;
 use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8};
+use std::time::SystemTime;
 
 use chrono::prelude::*;
 use time::PrimitiveDateTime;
@@ -55,6 +56,7 @@ impl FromCDRS for NonZeroI32 {}
 impl FromCDRS for NonZeroI64 {}
 impl FromCDRS for NaiveDateTime {}
 impl<Tz: TimeZone> FromCDRS for DateTime<Tz> {}
+impl FromCDRS for SystemTime {}
 
 pub trait FromCDRSByName {
     fn from_cdrs_by_name<T>(cdrs_type: &T, name: &str) -> CDRSResult<Option<Self>>
@@ -97,3 +99,4 @@ impl FromCDRSByName for NonZeroI32 {}
 impl FromCDRSByName for NonZeroI64 {}
 impl FromCDRSByName for NaiveDateTime {}
 impl<Tz: TimeZone> FromCDRSByName for DateTime<Tz> {}
+impl FromCDRSByName for SystemTime {}
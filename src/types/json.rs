@@ -0,0 +1,445 @@
+//! Conversion of column values into JSON, using column types from result metadata. Values
+//! which have no exact JSON counterpart are represented as strings: blobs as `0x` prefixed hex,
+//! decimals in plain notation (scientific for extreme scales), varints out of the 64 bit range
+//! as decimal digits, uuids and inet addresses in their usual form, dates, times and timestamps
+//! in ISO 8601 format. Non-finite floating point numbers become `null`.
+//!
+//! Rows returned by `SELECT JSON` queries, which are converted into JSON by the server, can be
+//! deserialized with `from_json_row`.
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde::de::DeserializeOwned;
+use serde_json::{Map as JsonMap, Number, Value as JsonValue};
+
+use crate::error::{Error, Result};
+use crate::frame::frame_result::{ColType, ColTypeOption, ColTypeOptionValue};
+use crate::types::data_serialization_types::*;
+use crate::types::rows::Row;
+use crate::types::timestamp::timestamp_to_date_time;
+use crate::types::vector::VectorType;
+use crate::types::{CBytes, IntoRustByName};
+
+/// Name of the single column of rows returned by `SELECT JSON` queries.
+pub const JSON_COLUMN: &str = "[json]";
+
+/// Deserializes the `[json]` column of a row returned by a `SELECT JSON` query, e.g. into
+/// `serde_json::Value` or a type implementing `Deserialize`.
+pub fn from_json_row<R: DeserializeOwned>(row: &Row) -> Result<R> {
+    let json: String = row.get_r_by_name(JSON_COLUMN)?;
+    serde_json::from_str(&json).map_err(json_error)
+}
+
+pub(crate) fn json_error(error: serde_json::Error) -> Error {
+    Error::General(format!("Invalid JSON: {}", error))
+}
+
+/// Converts a serialized value of given type into JSON.
+pub fn to_json(col_type: &ColTypeOption, value: &CBytes) -> Result<JsonValue> {
+    match value.as_slice() {
+        Some(bytes) => bytes_to_json(col_type, bytes),
+        None => Ok(JsonValue::Null),
+    }
+}
+
+fn bytes_to_json(col_type: &ColTypeOption, bytes: &[u8]) -> Result<JsonValue> {
+    let json = match col_type.id {
+        ColType::Custom => match col_type.vector_type() {
+            Some(ref vector_type) if vector_type.is_float() => {
+                float_vector_to_json(vector_type, bytes)?
+            }
+            _ => JsonValue::String(to_hex(bytes)),
+        },
+        ColType::Ascii | ColType::Varchar => JsonValue::String(decode_varchar(bytes)?),
+        ColType::Bigint | ColType::Counter => decode_bigint(fixed(bytes, 8, col_type)?)?.into(),
+        ColType::Blob => JsonValue::String(to_hex(bytes)),
+        ColType::Boolean => decode_boolean(fixed(bytes, 1, col_type)?)?.into(),
+        ColType::Decimal => {
+            if bytes.len() <= 4 {
+                return Err(invalid_length(col_type, bytes));
+            }
+            let (scale, unscaled) = bytes.split_at(4);
+            JsonValue::String(scale_number(
+                &varint_to_string(unscaled),
+                decode_int(scale)?,
+            ))
+        }
+        ColType::Double => float_to_json(decode_double(fixed(bytes, 8, col_type)?)?),
+        ColType::Float => float_to_json(decode_float(fixed(bytes, 4, col_type)?)? as f64),
+        ColType::Int => decode_int(fixed(bytes, 4, col_type)?)?.into(),
+        ColType::Timestamp => {
+            let millis = decode_timestamp(fixed(bytes, 8, col_type)?)?;
+            match timestamp_to_date_time(millis) {
+                Some(date_time) => JsonValue::String(date_time.to_rfc3339()),
+                None => millis.into(),
+            }
+        }
+        ColType::Uuid | ColType::Timeuuid => {
+            JsonValue::String(decode_timeuuid(fixed(bytes, 16, col_type)?)?.to_string())
+        }
+        ColType::Varint => {
+            if bytes.is_empty() {
+                return Err(invalid_length(col_type, bytes));
+            }
+            if bytes.len() <= 8 {
+                decode_varint(bytes)?.into()
+            } else {
+                // out of range of JSON numbers without arbitrary precision
+                JsonValue::String(varint_to_string(bytes))
+            }
+        }
+        ColType::Inet => JsonValue::String(decode_inet(bytes)?.to_string()),
+        ColType::Date => {
+            // days are counted from 2^31, which represents the unix epoch
+            let days = decode_date(fixed(bytes, 4, col_type)?)? as u32 as i64 - (1 << 31);
+            match NaiveDate::from_ymd_opt(1970, 1, 1)
+                .and_then(|epoch| epoch.checked_add_signed(Duration::days(days)))
+            {
+                Some(date) => JsonValue::String(date.to_string()),
+                None => days.into(),
+            }
+        }
+        ColType::Time => {
+            let nanos = decode_time(fixed(bytes, 8, col_type)?)?;
+            let time = NaiveTime::from_num_seconds_from_midnight_opt(
+                (nanos / 1_000_000_000) as u32,
+                (nanos % 1_000_000_000) as u32,
+            );
+            match time {
+                Some(time) if nanos >= 0 => JsonValue::String(time.to_string()),
+                _ => nanos.into(),
+            }
+        }
+        ColType::Smallint => decode_smallint(fixed(bytes, 2, col_type)?)?.into(),
+        ColType::Tinyint => decode_tinyint(fixed(bytes, 1, col_type)?)?.into(),
+        ColType::List | ColType::Set => match col_type.value {
+            Some(ColTypeOptionValue::CList(ref item_type))
+            | Some(ColTypeOptionValue::CSet(ref item_type)) => JsonValue::Array(
+                decode_list(bytes)?
+                    .iter()
+                    .map(|item| to_json(item_type, item))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => return Err(missing_metadata(col_type)),
+        },
+        ColType::Map => match col_type.value {
+            Some(ColTypeOptionValue::CMap((ref key_type, ref value_type))) => {
+                let mut map = JsonMap::new();
+                for (key, value) in decode_map(bytes)? {
+                    let key = match to_json(key_type, &key)? {
+                        JsonValue::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    map.insert(key, to_json(value_type, &value)?);
+                }
+                JsonValue::Object(map)
+            }
+            _ => return Err(missing_metadata(col_type)),
+        },
+        ColType::Udt => match col_type.value {
+            Some(ColTypeOptionValue::UdtType(ref udt)) => {
+                let fields = decode_udt(bytes, udt.descriptions.len())?;
+                let mut map = JsonMap::new();
+                for ((name, field_type), value) in udt.descriptions.iter().zip(fields.iter()) {
+                    map.insert(name.as_plain(), to_json(field_type, value)?);
+                }
+                JsonValue::Object(map)
+            }
+            _ => return Err(missing_metadata(col_type)),
+        },
+        ColType::Tuple => match col_type.value {
+            Some(ColTypeOptionValue::TupleType(ref tuple)) => {
+                let items = decode_tuple(bytes, tuple.types.len())?;
+                JsonValue::Array(
+                    tuple
+                        .types
+                        .iter()
+                        .zip(items.iter())
+                        .map(|(item_type, item)| to_json(item_type, item))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            _ => return Err(missing_metadata(col_type)),
+        },
+        ColType::Null => JsonValue::Null,
+    };
+
+    Ok(json)
+}
+
+fn fixed<'a>(bytes: &'a [u8], len: usize, col_type: &ColTypeOption) -> Result<&'a [u8]> {
+    if bytes.len() == len {
+        Ok(bytes)
+    } else {
+        Err(invalid_length(col_type, bytes))
+    }
+}
+
+fn invalid_length(col_type: &ColTypeOption, bytes: &[u8]) -> Error {
+    Error::General(format!(
+        "Invalid length {} of {:?} value",
+        bytes.len(),
+        col_type.id
+    ))
+}
+
+fn missing_metadata(col_type: &ColTypeOption) -> Error {
+    Error::General(format!("Missing type metadata of {:?} value", col_type.id))
+}
+
+fn float_to_json(value: f64) -> JsonValue {
+    Number::from_f64(value)
+        .map(JsonValue::Number)
+        .unwrap_or(JsonValue::Null)
+}
+
+fn float_vector_to_json(vector_type: &VectorType, bytes: &[u8]) -> Result<JsonValue> {
+    Ok(JsonValue::Array(
+        decode_float_vector(bytes, vector_type.dimensions())?
+            .into_iter()
+            .map(|value| float_to_json(value as f64))
+            .collect(),
+    ))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+/// Longest run of zeros padding a decimal in plain notation. Decimals needing more, which can
+/// only be sent with extreme scales, are written in scientific notation instead.
+const MAX_DECIMAL_PADDING: usize = 64;
+
+/// Places the decimal point into an integer given as decimal digits, optionally signed.
+fn scale_number(number: &str, scale: i32) -> String {
+    let (sign, digits) = match number.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", number),
+    };
+
+    let exponent = scale.unsigned_abs() as usize;
+    if scale < 0 {
+        if digits == "0" {
+            return digits.to_string();
+        }
+        if exponent > MAX_DECIMAL_PADDING {
+            return format!("{}{}E+{}", sign, digits, exponent);
+        }
+        return format!("{}{}{}", sign, digits, "0".repeat(exponent));
+    }
+
+    if exponent == 0 {
+        return number.to_string();
+    }
+
+    let mut digits = digits.to_string();
+    if digits.len() <= exponent {
+        let padding = exponent + 1 - digits.len();
+        if padding > MAX_DECIMAL_PADDING {
+            return format!("{}{}E-{}", sign, digits, exponent);
+        }
+        digits.insert_str(0, &"0".repeat(padding));
+    }
+    digits.insert(digits.len() - exponent, '.');
+
+    format!("{}{}", sign, digits)
+}
+
+/// Converts a big-endian two's complement integer of any length into decimal digits.
+fn varint_to_string(bytes: &[u8]) -> String {
+    let negative = matches!(bytes.first(), Some(byte) if byte & 0x80 != 0);
+    let mut magnitude = bytes.to_vec();
+    if negative {
+        // two's complement negation
+        for byte in magnitude.iter_mut() {
+            *byte = !*byte;
+        }
+        for byte in magnitude.iter_mut().rev() {
+            let (sum, overflow) = byte.overflowing_add(1);
+            *byte = sum;
+            if !overflow {
+                break;
+            }
+        }
+    }
+
+    let mut digits = vec![];
+    while magnitude.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in magnitude.iter_mut() {
+            let value = (remainder << 8) | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    if negative {
+        digits.push(b'-');
+    }
+
+    digits.iter().rev().map(|digit| *digit as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{BodyResResultRows, CTuple, ColSpec, RowsMetadata};
+    use crate::types::CString;
+    use serde_json::json;
+
+    fn col_type(id: ColType) -> ColTypeOption {
+        ColTypeOption { id, value: None }
+    }
+
+    #[test]
+    fn scalars_to_json() {
+        let int = CBytes::new(vec![0, 0, 0, 42]);
+        assert_eq!(to_json(&col_type(ColType::Int), &int).unwrap(), json!(42));
+
+        let text = CBytes::new(b"abc".to_vec());
+        assert_eq!(
+            to_json(&col_type(ColType::Varchar), &text).unwrap(),
+            json!("abc")
+        );
+
+        let blob = CBytes::new(vec![0xca, 0xfe]);
+        assert_eq!(
+            to_json(&col_type(ColType::Blob), &blob).unwrap(),
+            json!("0xcafe")
+        );
+
+        let uuid = CBytes::new(vec![0x12; 16]);
+        assert_eq!(
+            to_json(&col_type(ColType::Uuid), &uuid).unwrap(),
+            json!("12121212-1212-1212-1212-121212121212")
+        );
+
+        let date = CBytes::new(vec![0x80, 0, 0, 1]);
+        assert_eq!(
+            to_json(&col_type(ColType::Date), &date).unwrap(),
+            json!("1970-01-02")
+        );
+
+        let null = CBytes::new_empty();
+        assert_eq!(
+            to_json(&col_type(ColType::Int), &null).unwrap(),
+            JsonValue::Null
+        );
+    }
+
+    #[test]
+    fn invalid_length_is_error() {
+        let short = CBytes::new(vec![0, 1]);
+        assert!(to_json(&col_type(ColType::Bigint), &short).is_err());
+        assert!(to_json(&col_type(ColType::Tinyint), &CBytes::new(vec![])).is_err());
+    }
+
+    #[test]
+    fn json_row() {
+        let row = Row::from_frame_body(BodyResResultRows {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 1,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![ColSpec {
+                    ksname: None,
+                    tablename: None,
+                    name: CString::new(JSON_COLUMN.into()),
+                    col_type: col_type(ColType::Varchar),
+                }],
+            },
+            rows_count: 1,
+            rows_content: vec![vec![CBytes::new(br#"{"id": 1, "tags": ["a"]}"#.to_vec())]],
+        })
+        .remove(0);
+
+        let value: JsonValue = from_json_row(&row).unwrap();
+        assert_eq!(value, json!({"id": 1, "tags": ["a"]}));
+
+        let tags: std::collections::HashMap<String, JsonValue> = from_json_row(&row).unwrap();
+        assert_eq!(tags["tags"], json!(["a"]));
+        assert!(from_json_row::<Vec<i32>>(&row).is_err());
+    }
+
+    #[test]
+    fn decimal_to_json() {
+        assert_eq!(scale_number("-12345", 2), "-123.45");
+        assert_eq!(scale_number("5", 3), "0.005");
+        assert_eq!(scale_number("7", 0), "7");
+        assert_eq!(scale_number("-12", -3), "-12000");
+        assert_eq!(scale_number("0", -3), "0");
+        assert_eq!(scale_number("12", i32::MIN), "12E+2147483648");
+        assert_eq!(scale_number("12", i32::MAX), "12E-2147483647");
+
+        let decimal = CBytes::new(vec![
+            0, 0, 0, 1, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ]);
+        assert_eq!(
+            to_json(&col_type(ColType::Decimal), &decimal).unwrap(),
+            json!("236118324143482260684.7")
+        );
+    }
+
+    #[test]
+    fn varint_to_json() {
+        assert_eq!(
+            to_json(&col_type(ColType::Varint), &CBytes::new(vec![0xff, 0x7f])).unwrap(),
+            json!(-129)
+        );
+
+        let big = CBytes::new(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            to_json(&col_type(ColType::Varint), &big).unwrap(),
+            json!("18446744073709551616")
+        );
+
+        let negative = CBytes::new(vec![0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            to_json(&col_type(ColType::Varint), &negative).unwrap(),
+            json!("-18446744073709551616")
+        );
+        assert_eq!(varint_to_string(&[0x80]), "-128");
+        assert_eq!(varint_to_string(&[0x00]), "0");
+    }
+
+    #[test]
+    fn collections_to_json() {
+        let map_type = ColTypeOption {
+            id: ColType::Map,
+            value: Some(ColTypeOptionValue::CMap((
+                Box::new(col_type(ColType::Int)),
+                Box::new(col_type(ColType::Varchar)),
+            ))),
+        };
+        let map = CBytes::new(vec![0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, b'a']);
+        assert_eq!(to_json(&map_type, &map).unwrap(), json!({"1": "a"}));
+
+        let tuple_type = ColTypeOption {
+            id: ColType::Tuple,
+            value: Some(ColTypeOptionValue::TupleType(CTuple {
+                types: vec![col_type(ColType::Boolean), col_type(ColType::Ascii)],
+            })),
+        };
+        let tuple = CBytes::new(vec![0, 0, 0, 1, 1, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(to_json(&tuple_type, &tuple).unwrap(), json!([true, null]));
+
+        let vector_type = ColTypeOption {
+            id: ColType::Custom,
+            value: Some(ColTypeOptionValue::CString(CString::new(
+                "org.apache.cassandra.db.marshal.VectorType(org.apache.cassandra.db.marshal.FloatType, 2)"
+                    .into(),
+            ))),
+        };
+        let vector = CBytes::new(vec![0x3f, 0x80, 0, 0, 0xc0, 0x20, 0, 0]);
+        assert_eq!(to_json(&vector_type, &vector).unwrap(), json!([1.0, -2.5]));
+    }
+}
@@ -1,12 +1,16 @@
 use crate::error::{Error, Result};
 use crate::frame::frame_result::{ColType, ColTypeOption, ColTypeOptionValue};
 use crate::types::blob::Blob;
+use crate::types::custom::CustomValue;
 use crate::types::data_serialization_types::*;
 use crate::types::decimal::Decimal;
 use crate::types::map::Map;
 use crate::types::tuple::Tuple;
 use crate::types::udt::UDT;
+use crate::types::vector::{Vector, VectorType};
 use crate::types::{AsRust, AsRustType, CBytes};
+use std::collections::{BTreeSet, HashSet};
+use std::hash::Hash;
 use std::net::IpAddr;
 use uuid::Uuid;
 
@@ -35,6 +39,7 @@ impl List {
 impl AsRust for List {}
 
 list_as_rust!(Blob);
+list_as_rust!(CustomValue);
 list_as_rust!(String);
 list_as_rust!(bool);
 list_as_rust!(i64);
@@ -50,3 +55,53 @@ list_as_rust!(Map);
 list_as_rust!(UDT);
 list_as_rust!(Tuple);
 list_as_rust!(Decimal);
+list_as_rust!(Vector);
+
+/// Converts set (or list) columns into a `HashSet` of any item type a `Vec` can be read as.
+impl<T: Hash + Eq> AsRustType<HashSet<T>> for List
+where
+    List: AsRustType<Vec<T>>,
+{
+    fn as_rust_type(&self) -> Result<Option<HashSet<T>>> {
+        AsRustType::<Vec<T>>::as_rust_type(self)
+            .map(|items| items.map(|items| items.into_iter().collect()))
+    }
+}
+
+/// Converts set (or list) columns into a `BTreeSet` of any item type a `Vec` can be read as.
+impl<T: Ord> AsRustType<BTreeSet<T>> for List
+where
+    List: AsRustType<Vec<T>>,
+{
+    fn as_rust_type(&self) -> Result<Option<BTreeSet<T>>> {
+        AsRustType::<Vec<T>>::as_rust_type(self)
+            .map(|items| items.map(|items| items.into_iter().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::to_int;
+
+    #[test]
+    fn set_as_rust_sets() {
+        let metadata = ColTypeOption {
+            id: ColType::Set,
+            value: Some(ColTypeOptionValue::CSet(Box::new(ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            }))),
+        };
+        let set = List::new(
+            vec![CBytes::new(to_int(3)), CBytes::new(to_int(1))],
+            metadata,
+        );
+
+        let hash_set: HashSet<i32> = set.as_r_type().unwrap();
+        assert_eq!(hash_set, vec![1, 3].into_iter().collect());
+
+        let btree_set: BTreeSet<i32> = set.as_r_type().unwrap();
+        assert_eq!(btree_set.into_iter().collect::<Vec<_>>(), vec![1, 3]);
+    }
+}
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::time::SystemTime;
+
+use chrono::prelude::*;
 use time::PrimitiveDateTime;
 use uuid::Uuid;
 
@@ -233,3 +236,147 @@ map_as_rust!({ Tuple }, { Map });
 map_as_rust!({ Tuple }, { UDT });
 map_as_rust!({ Tuple }, { Tuple });
 map_as_rust!({ Tuple }, { Decimal });
+
+// Timestamp keys and values can be read as `chrono` types and `SystemTime` as well.
+
+map_as_rust!({ String }, { NaiveDateTime });
+map_as_rust!({ String }, { DateTime<Utc> });
+map_as_rust!({ String }, { SystemTime });
+map_as_rust!({ i64 }, { NaiveDateTime });
+map_as_rust!({ i64 }, { DateTime<Utc> });
+map_as_rust!({ i64 }, { SystemTime });
+map_as_rust!({ i32 }, { NaiveDateTime });
+map_as_rust!({ i32 }, { DateTime<Utc> });
+map_as_rust!({ i32 }, { SystemTime });
+map_as_rust!({ IpAddr }, { NaiveDateTime });
+map_as_rust!({ IpAddr }, { DateTime<Utc> });
+map_as_rust!({ IpAddr }, { SystemTime });
+map_as_rust!({ Uuid }, { NaiveDateTime });
+map_as_rust!({ Uuid }, { DateTime<Utc> });
+map_as_rust!({ Uuid }, { SystemTime });
+
+map_as_rust!({ NaiveDateTime }, { Blob });
+map_as_rust!({ NaiveDateTime }, { String });
+map_as_rust!({ NaiveDateTime }, { bool });
+map_as_rust!({ NaiveDateTime }, { i64 });
+map_as_rust!({ NaiveDateTime }, { i32 });
+map_as_rust!({ NaiveDateTime }, { i16 });
+map_as_rust!({ NaiveDateTime }, { i8 });
+map_as_rust!({ NaiveDateTime }, { f64 });
+map_as_rust!({ NaiveDateTime }, { f32 });
+map_as_rust!({ NaiveDateTime }, { IpAddr });
+map_as_rust!({ NaiveDateTime }, { Uuid });
+map_as_rust!({ NaiveDateTime }, { PrimitiveDateTime });
+map_as_rust!({ NaiveDateTime }, { List });
+map_as_rust!({ NaiveDateTime }, { Map });
+map_as_rust!({ NaiveDateTime }, { UDT });
+map_as_rust!({ NaiveDateTime }, { Tuple });
+map_as_rust!({ NaiveDateTime }, { Decimal });
+map_as_rust!({ NaiveDateTime }, { NaiveDateTime });
+map_as_rust!({ NaiveDateTime }, { DateTime<Utc> });
+map_as_rust!({ NaiveDateTime }, { SystemTime });
+
+map_as_rust!({ DateTime<Utc> }, { Blob });
+map_as_rust!({ DateTime<Utc> }, { String });
+map_as_rust!({ DateTime<Utc> }, { bool });
+map_as_rust!({ DateTime<Utc> }, { i64 });
+map_as_rust!({ DateTime<Utc> }, { i32 });
+map_as_rust!({ DateTime<Utc> }, { i16 });
+map_as_rust!({ DateTime<Utc> }, { i8 });
+map_as_rust!({ DateTime<Utc> }, { f64 });
+map_as_rust!({ DateTime<Utc> }, { f32 });
+map_as_rust!({ DateTime<Utc> }, { IpAddr });
+map_as_rust!({ DateTime<Utc> }, { Uuid });
+map_as_rust!({ DateTime<Utc> }, { PrimitiveDateTime });
+map_as_rust!({ DateTime<Utc> }, { List });
+map_as_rust!({ DateTime<Utc> }, { Map });
+map_as_rust!({ DateTime<Utc> }, { UDT });
+map_as_rust!({ DateTime<Utc> }, { Tuple });
+map_as_rust!({ DateTime<Utc> }, { Decimal });
+map_as_rust!({ DateTime<Utc> }, { NaiveDateTime });
+map_as_rust!({ DateTime<Utc> }, { DateTime<Utc> });
+map_as_rust!({ DateTime<Utc> }, { SystemTime });
+
+map_as_rust!({ SystemTime }, { Blob });
+map_as_rust!({ SystemTime }, { String });
+map_as_rust!({ SystemTime }, { bool });
+map_as_rust!({ SystemTime }, { i64 });
+map_as_rust!({ SystemTime }, { i32 });
+map_as_rust!({ SystemTime }, { i16 });
+map_as_rust!({ SystemTime }, { i8 });
+map_as_rust!({ SystemTime }, { f64 });
+map_as_rust!({ SystemTime }, { f32 });
+map_as_rust!({ SystemTime }, { IpAddr });
+map_as_rust!({ SystemTime }, { Uuid });
+map_as_rust!({ SystemTime }, { PrimitiveDateTime });
+map_as_rust!({ SystemTime }, { List });
+map_as_rust!({ SystemTime }, { Map });
+map_as_rust!({ SystemTime }, { UDT });
+map_as_rust!({ SystemTime }, { Tuple });
+map_as_rust!({ SystemTime }, { Decimal });
+map_as_rust!({ SystemTime }, { NaiveDateTime });
+map_as_rust!({ SystemTime }, { DateTime<Utc> });
+map_as_rust!({ SystemTime }, { SystemTime });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{to_bigint, to_int};
+    use std::net::Ipv4Addr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn map_type(key: ColType, value: ColType) -> ColTypeOption {
+        let option = |id| ColTypeOption { id, value: None };
+        ColTypeOption {
+            id: ColType::Map,
+            value: Some(ColTypeOptionValue::CMap((
+                Box::new(option(key)),
+                Box::new(option(value)),
+            ))),
+        }
+    }
+
+    #[test]
+    fn non_string_keys() {
+        let uuid = Uuid::from_u128(42);
+        let map = Map::new(
+            vec![(
+                CBytes::new(uuid.as_bytes().to_vec()),
+                CBytes::new(to_int(1)),
+            )],
+            map_type(ColType::Uuid, ColType::Int),
+        );
+        let converted: HashMap<Uuid, i32> = map.as_rust_type().unwrap().unwrap();
+        assert_eq!(converted, vec![(uuid, 1)].into_iter().collect());
+
+        let map = Map::new(
+            vec![(CBytes::new(vec![127, 0, 0, 1]), CBytes::new(to_bigint(2)))],
+            map_type(ColType::Inet, ColType::Bigint),
+        );
+        let converted: HashMap<IpAddr, i64> = map.as_rust_type().unwrap().unwrap();
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        assert_eq!(converted, vec![(localhost, 2)].into_iter().collect());
+
+        let map = Map::new(
+            vec![(
+                CBytes::new(to_bigint(1_000)),
+                CBytes::new(b"second".to_vec()),
+            )],
+            map_type(ColType::Timestamp, ColType::Varchar),
+        );
+        let converted: HashMap<SystemTime, String> = map.as_rust_type().unwrap().unwrap();
+        let second = UNIX_EPOCH + Duration::from_secs(1);
+        assert_eq!(
+            converted,
+            vec![(second, "second".into())].into_iter().collect()
+        );
+
+        let converted: HashMap<DateTime<Utc>, String> = map.as_rust_type().unwrap().unwrap();
+        assert_eq!(
+            converted,
+            vec![(Utc.timestamp_millis_opt(1_000).unwrap(), "second".into())]
+                .into_iter()
+                .collect()
+        );
+    }
+}
@@ -15,27 +15,39 @@ pub const UUID_LEN: usize = 16;
 
 #[macro_use]
 pub mod blob;
+pub mod custom;
 pub mod data_serialization_types;
 pub mod decimal;
+pub mod duration;
 pub mod from_cdrs;
+#[cfg(feature = "serde_json")]
+pub mod json;
 pub mod list;
 pub mod map;
+pub mod numeric;
+pub mod partitions;
+pub mod registry;
 pub mod rows;
+pub mod timestamp;
 pub mod tuple;
 pub mod udt;
 pub mod value;
+pub mod vector;
 
 pub mod prelude {
     pub use crate::error::{Error, Result};
     pub use crate::frame::{TryFromRow, TryFromUDT};
     pub use crate::types::blob::Blob;
+    pub use crate::types::custom::CustomValue;
     pub use crate::types::decimal::Decimal;
+    pub use crate::types::duration::CqlDuration;
     pub use crate::types::list::List;
     pub use crate::types::map::Map;
     pub use crate::types::rows::Row;
     pub use crate::types::tuple::Tuple;
     pub use crate::types::udt::UDT;
     pub use crate::types::value::{Bytes, Value};
+    pub use crate::types::vector::Vector;
     pub use crate::types::AsRustType;
 }
 
@@ -158,7 +170,7 @@ pub fn i_to_n_bytes(int: i64, n: usize) -> Vec<u8> {
 
 /// Tries to decode bytes array into `u64`.
 pub fn try_from_bytes(bytes: &[u8]) -> Result<u64, io::Error> {
-    let l = bytes.len();
+    let l = check_int_len(bytes)?;
     let mut c = Cursor::new(bytes);
     c.read_uint::<BigEndian>(l)
 }
@@ -171,11 +183,22 @@ pub fn try_u16_from_bytes(bytes: &[u8]) -> Result<u16, io::Error> {
 
 /// Tries to decode bytes array into `i64`.
 pub fn try_i_from_bytes(bytes: &[u8]) -> Result<i64, io::Error> {
-    let l = bytes.len();
+    let l = check_int_len(bytes)?;
     let mut c = Cursor::new(bytes);
     c.read_int::<BigEndian>(l)
 }
 
+// Variable length integers are read from 1 to 8 bytes, e.g. empty values cannot be decoded.
+fn check_int_len(bytes: &[u8]) -> Result<usize, io::Error> {
+    match bytes.len() {
+        l @ 1..=8 => Ok(l),
+        l => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Cannot decode {} bytes as an integer", l),
+        )),
+    }
+}
+
 /// Tries to decode bytes array into `i32`.
 pub fn try_i32_from_bytes(bytes: &[u8]) -> Result<i32, io::Error> {
     let mut c = Cursor::new(bytes);
@@ -595,7 +618,7 @@ impl AsBytes for CBytes {
 }
 
 /// Cassandra short bytes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CBytesShort {
     bytes: Option<Vec<u8>>,
 }
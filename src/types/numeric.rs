@@ -0,0 +1,105 @@
+//! Relaxed numeric conversions. Unlike `AsRustType`, which requires an exact match between
+//! column type and Rust type, functions in this module accept any integral or floating point
+//! column, widening values losslessly and checking for overflow when narrowing.
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+use crate::error::{Error, Result};
+use crate::frame::frame_result::{ColType, ColTypeOption};
+use crate::types::data_serialization_types::*;
+use crate::types::CBytes;
+
+/// Decodes a value of any integral column type (`tinyint`, `smallint`, `int`, `bigint`,
+/// `counter` and `varint`) into requested integer type. Returns `Error::NumericOverflow`
+/// if the value does not fit.
+pub fn as_integer<T, N>(col_type: &ColTypeOption, data: &CBytes, column: N) -> Result<Option<T>>
+where
+    T: TryFrom<i64>,
+    N: Display,
+{
+    let value = match data.as_slice() {
+        Some(bytes) => match col_type.id {
+            ColType::Tinyint => decode_tinyint(bytes)? as i64,
+            ColType::Smallint => decode_smallint(bytes)? as i64,
+            ColType::Int => decode_int(bytes)? as i64,
+            ColType::Bigint | ColType::Counter => decode_bigint(bytes)?,
+            ColType::Varint => decode_varint(bytes)?,
+            _ => {
+                return Err(Error::General(format!(
+                    "Invalid conversion. \
+                     Cannot convert {:?} into an integer (valid types: Tinyint, Smallint, Int, \
+                     Bigint, Counter, Varint).",
+                    col_type.id
+                )))
+            }
+        },
+        None => return Ok(None),
+    };
+
+    T::try_from(value)
+        .map(Some)
+        .map_err(|_| Error::NumericOverflow {
+            column: column.to_string(),
+            value,
+            target_type: std::any::type_name::<T>(),
+        })
+}
+
+/// Decodes a value of any floating point column type (`float` and `double`) into `f64`.
+pub fn as_float(col_type: &ColTypeOption, data: &CBytes) -> Result<Option<f64>> {
+    match data.as_slice() {
+        Some(bytes) => match col_type.id {
+            ColType::Float => Ok(Some(decode_float(bytes)? as f64)),
+            ColType::Double => Ok(Some(decode_double(bytes)?)),
+            _ => Err(Error::General(format!(
+                "Invalid conversion. \
+                 Cannot convert {:?} into a floating point number (valid types: Float, Double).",
+                col_type.id
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{to_bigint, to_int, to_short};
+
+    fn col_type(id: ColType) -> ColTypeOption {
+        ColTypeOption { id, value: None }
+    }
+
+    #[test]
+    fn widening() {
+        let data = CBytes::new(to_int(42));
+        let value: Option<i64> = as_integer(&col_type(ColType::Int), &data, "a").unwrap();
+        assert_eq!(value, Some(42));
+
+        let data = CBytes::new(to_short(-7));
+        let value: Option<i32> = as_integer(&col_type(ColType::Smallint), &data, "a").unwrap();
+        assert_eq!(value, Some(-7));
+    }
+
+    #[test]
+    fn narrowing() {
+        let data = CBytes::new(to_bigint(1000));
+        let value: Option<i16> = as_integer(&col_type(ColType::Bigint), &data, "a").unwrap();
+        assert_eq!(value, Some(1000));
+
+        let data = CBytes::new(to_bigint(i64::from(i32::MAX) + 1));
+        let result: Result<Option<i32>> = as_integer(&col_type(ColType::Bigint), &data, "a");
+        assert!(matches!(result, Err(Error::NumericOverflow { .. })));
+    }
+
+    #[test]
+    fn null_and_invalid() {
+        let data = CBytes::new_empty();
+        let value: Option<i64> = as_integer(&col_type(ColType::Int), &data, "a").unwrap();
+        assert_eq!(value, None);
+
+        let data = CBytes::new(to_int(1));
+        let result: Result<Option<i64>> = as_integer(&col_type(ColType::Varchar), &data, "a");
+        assert!(result.is_err());
+    }
+}
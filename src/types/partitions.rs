@@ -0,0 +1,233 @@
+//! Grouping of rows by partition, e.g. for time series reads returning many clustering rows
+//! of each partition.
+use std::collections::HashMap;
+use std::slice;
+use std::vec;
+
+use crate::error::{Error, Result};
+use crate::frame::frame_result::RowsMetadata;
+use crate::types::rows::Row;
+use crate::types::value::{Value, ValueType};
+
+/// Values of partition key columns identifying a partition, serialized like column values.
+/// Null values are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartitionKey(Vec<Option<Vec<u8>>>);
+
+impl PartitionKey {
+    /// Returns serialized values of partition key columns, in order of the columns.
+    pub fn values(&self) -> &[Option<Vec<u8>>] {
+        &self.0
+    }
+}
+
+impl From<Vec<Value>> for PartitionKey {
+    /// Creates a key from values of partition key columns, e.g. to look up a partition with
+    /// the values it was queried with. Unset values are treated as nulls.
+    fn from(values: Vec<Value>) -> Self {
+        PartitionKey(
+            values
+                .into_iter()
+                .map(|value| match value.value_type {
+                    ValueType::Normal(_) => Some(value.body),
+                    ValueType::Null | ValueType::NotSet => None,
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Rows grouped by values of partition key columns, in order of the first row of each
+/// partition. Rows of a partition keep their order, i.e. the clustering order of the query.
+#[derive(Debug, Clone, Default)]
+pub struct PartitionGroups {
+    partitions: Vec<(PartitionKey, Vec<Row>)>,
+    indexes: HashMap<PartitionKey, usize>,
+}
+
+impl PartitionGroups {
+    /// Groups rows described by given metadata by values of given partition key columns.
+    /// Fails if any of the columns is not present in the metadata, i.e. was not selected.
+    pub fn new(metadata: &RowsMetadata, rows: Vec<Row>, partition_key: &[&str]) -> Result<Self> {
+        let key_indexes = partition_key
+            .iter()
+            .map(|name| {
+                metadata
+                    .col_specs
+                    .iter()
+                    .position(|spec| spec.name.as_str() == *name)
+                    .ok_or_else(|| {
+                        Error::General(format!("Partition key column '{}' is not selected", name))
+                    })
+            })
+            .collect::<Result<Vec<usize>>>()?;
+
+        let mut groups = PartitionGroups::default();
+        for row in rows {
+            let key = PartitionKey(
+                key_indexes
+                    .iter()
+                    .map(|index| row.raw_value(*index).map(<[u8]>::to_vec))
+                    .collect(),
+            );
+
+            match groups.indexes.get(&key) {
+                Some(index) => groups.partitions[*index].1.push(row),
+                None => {
+                    groups.indexes.insert(key.clone(), groups.partitions.len());
+                    groups.partitions.push((key, vec![row]));
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Returns rows of a partition with given key.
+    pub fn get(&self, key: &PartitionKey) -> Option<&[Row]> {
+        self.indexes
+            .get(key)
+            .map(|index| self.partitions[*index].1.as_slice())
+    }
+
+    /// Checks if there are rows of a partition with given key.
+    pub fn contains_key(&self, key: &PartitionKey) -> bool {
+        self.indexes.contains_key(key)
+    }
+
+    /// Returns keys of all partitions.
+    pub fn keys(&self) -> impl Iterator<Item = &PartitionKey> {
+        self.partitions.iter().map(|(key, _)| key)
+    }
+
+    /// Returns keys of all partitions along with their rows.
+    pub fn iter(&self) -> slice::Iter<'_, (PartitionKey, Vec<Row>)> {
+        self.partitions.iter()
+    }
+
+    /// Returns the number of partitions.
+    pub fn len(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// Checks if there are no rows.
+    pub fn is_empty(&self) -> bool {
+        self.partitions.is_empty()
+    }
+}
+
+impl IntoIterator for PartitionGroups {
+    type Item = (PartitionKey, Vec<Row>);
+    type IntoIter = vec::IntoIter<(PartitionKey, Vec<Row>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.partitions.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PartitionGroups {
+    type Item = &'a (PartitionKey, Vec<Row>);
+    type IntoIter = slice::Iter<'a, (PartitionKey, Vec<Row>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.partitions.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{BodyResResultRows, ColSpec, ColType, ColTypeOption};
+    use crate::types::{CBytes, CString, IntoRustByName};
+
+    fn col_spec(name: &str) -> ColSpec {
+        ColSpec {
+            ksname: None,
+            tablename: None,
+            name: CString::new(name.into()),
+            col_type: ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            },
+        }
+    }
+
+    fn metadata() -> RowsMetadata {
+        RowsMetadata {
+            flags: 0,
+            columns_count: 3,
+            paging_state: None,
+            new_metadata_id: None,
+            continuous_page_number: None,
+            global_table_space: None,
+            col_specs: vec![col_spec("sensor"), col_spec("day"), col_spec("value")],
+        }
+    }
+
+    fn rows(values: &[(i32, i32, i32)]) -> Vec<Row> {
+        let rows_content: Vec<Vec<CBytes>> = values
+            .iter()
+            .map(|(sensor, day, value)| {
+                vec![
+                    CBytes::new(sensor.to_be_bytes().to_vec()),
+                    CBytes::new(day.to_be_bytes().to_vec()),
+                    CBytes::new(value.to_be_bytes().to_vec()),
+                ]
+            })
+            .collect();
+
+        Row::from_frame_body(BodyResResultRows {
+            metadata: metadata(),
+            rows_count: rows_content.len() as i32,
+            rows_content,
+        })
+    }
+
+    fn values(rows: &[Row]) -> Vec<i32> {
+        rows.iter()
+            .map(|row| row.get_r_by_name("value").unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn groups_by_composite_key() {
+        let groups = PartitionGroups::new(
+            &metadata(),
+            rows(&[(1, 1, 10), (1, 1, 11), (2, 1, 20), (1, 2, 12), (1, 1, 13)]),
+            &["sensor", "day"],
+        )
+        .unwrap();
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            values(groups.get(&vec![1.into(), 1.into()].into()).unwrap()),
+            vec![10, 11, 13]
+        );
+        assert_eq!(
+            values(groups.get(&vec![1.into(), 2.into()].into()).unwrap()),
+            vec![12]
+        );
+        assert!(!groups.contains_key(&vec![2.into(), 2.into()].into()));
+
+        let keys: Vec<PartitionKey> = groups.keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                vec![1.into(), 1.into()].into(),
+                vec![2.into(), 1.into()].into(),
+                vec![1.into(), 2.into()].into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_key_column() {
+        assert!(PartitionGroups::new(&metadata(), vec![], &["missing"]).is_err());
+    }
+
+    #[test]
+    fn no_rows() {
+        let groups = PartitionGroups::new(&metadata(), vec![], &["sensor"]).unwrap();
+        assert!(groups.is_empty());
+    }
+}
@@ -0,0 +1,341 @@
+//! Registry of user defined conversions of custom types and UDTs into Rust types, e.g. of a
+//! `point` UDT into a geometry library type, which cannot implement driver traits directly.
+//! Registries are attached to rows, either directly or by sessions for rows they convert, e.g.
+//! in `exec_rows`, and their conversions are consulted before built-in ones when reading
+//! columns by name or index, and fields of UDTs and tuples read from such rows. Values of
+//! types without built-in conversions are read as `Custom<T>`:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use cdrs_tokio::types::prelude::*;
+//! use cdrs_tokio::types::registry::{Custom, TypeRegistry};
+//! use cdrs_tokio::types::IntoRustByName;
+//!
+//! struct Point(f64, f64);
+//!
+//! let mut registry = TypeRegistry::new();
+//! registry.register_udt("point", |udt: &UDT| {
+//!     Ok(Point(udt.get_r_by_name("x")?, udt.get_r_by_name("y")?))
+//! });
+//! let registry = Arc::new(registry);
+//!
+//! # fn read(row: Row, registry: Arc<TypeRegistry>) -> Result<()> {
+//! let row = row.with_type_registry(registry);
+//! let Custom(location): Custom<Point> = row.get_r_by_name("location")?;
+//! # Ok(())
+//! # }
+//! ```
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::frame::frame_result::{ColType, ColTypeOption, ColTypeOptionValue};
+use crate::types::data_serialization_types::decode_udt;
+use crate::types::udt::UDT;
+use crate::types::CBytes;
+
+/// Value converted with a registered conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Custom<T>(pub T);
+
+impl<T> Custom<T> {
+    /// Returns the converted value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+type Decoder<T> = Arc<dyn Fn(&ColTypeOption, &[u8]) -> Result<T> + Send + Sync>;
+
+/// Conversions of custom types and UDTs into Rust types, keyed by the target type and the name
+/// of the custom type class or UDT.
+#[derive(Default)]
+pub struct TypeRegistry {
+    decoders: HashMap<(TypeId, String), Arc<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypeRegistry")
+            .field("conversions", &self.decoders.len())
+            .finish()
+    }
+}
+
+impl TypeRegistry {
+    /// Creates a registry without conversions.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn register<T: 'static>(&mut self, type_name: &str, decoder: Decoder<T>) {
+        self.decoders.insert(
+            (TypeId::of::<T>(), type_name.to_string()),
+            Arc::new(decoder),
+        );
+    }
+
+    /// Registers a conversion of values of a custom type with given class name, e.g.
+    /// `org.apache.cassandra.db.marshal.DynamicCompositeType`, into `T`. Replaces a conversion
+    /// registered before for the same class and type.
+    pub fn register_custom_type<T, F>(&mut self, class_name: &str, decode: F)
+    where
+        T: 'static,
+        F: Fn(&[u8]) -> Result<T> + Send + Sync + 'static,
+    {
+        self.register::<T>(class_name, Arc::new(move |_, bytes| decode(bytes)));
+    }
+
+    /// Registers a conversion of UDT values with given name into `T`. The name can be
+    /// qualified with a keyspace, e.g. `geo.point`, to apply to a single keyspace only.
+    /// Replaces a conversion registered before for the same name and type.
+    pub fn register_udt<T, F>(&mut self, udt_name: &str, decode: F)
+    where
+        T: 'static,
+        F: Fn(&UDT) -> Result<T> + Send + Sync + 'static,
+    {
+        self.register::<T>(
+            udt_name,
+            Arc::new(move |col_type, bytes| match &col_type.value {
+                Some(ColTypeOptionValue::UdtType(udt_type)) => {
+                    let data = decode_udt(bytes, udt_type.descriptions.len())?;
+                    decode(&UDT::new(data, udt_type))
+                }
+                value => Err(Error::General(format!("Invalid UDT type {:?}", value))),
+            }),
+        );
+    }
+
+    fn decoder<T: 'static>(&self, col_type: &ColTypeOption) -> Option<Decoder<T>> {
+        type_names(col_type)?.iter().find_map(|type_name| {
+            self.decoders
+                .get(&(TypeId::of::<T>(), type_name.clone()))
+                .and_then(|decoder| decoder.downcast_ref::<Decoder<T>>())
+                .cloned()
+        })
+    }
+}
+
+/// Names conversions of values of given type are registered under, most specific first.
+fn type_names(col_type: &ColTypeOption) -> Option<Vec<String>> {
+    match (&col_type.id, &col_type.value) {
+        (ColType::Custom, Some(ColTypeOptionValue::CString(class_name))) => {
+            Some(vec![class_name.as_plain()])
+        }
+        (ColType::Udt, Some(ColTypeOptionValue::UdtType(udt_type))) => Some(vec![
+            format!("{}.{}", udt_type.ks.as_str(), udt_type.udt_name.as_str()),
+            udt_type.udt_name.as_plain(),
+        ]),
+        _ => None,
+    }
+}
+
+fn decode<T: 'static>(
+    decoder: Decoder<T>,
+    col_type: &ColTypeOption,
+    data: &CBytes,
+) -> Result<Option<T>> {
+    match data.as_slice() {
+        Some(bytes) => decoder(col_type, bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Passes on values which don't carry a registry of their own.
+pub(crate) fn keep<T>(value: T, _: Option<&Arc<TypeRegistry>>) -> T {
+    value
+}
+
+/// Converts a value with a conversion registered for its type and `T`, if the registry has
+/// one. Returns `None` to fall back to built-in conversions.
+pub(crate) fn as_registered<T: 'static>(
+    registry: Option<&Arc<TypeRegistry>>,
+    col_type: &ColTypeOption,
+    data: &CBytes,
+) -> Option<Result<Option<T>>> {
+    registry?
+        .decoder::<T>(col_type)
+        .map(|decoder| decode(decoder, col_type, data))
+}
+
+/// Converts a value of a custom type or UDT with a conversion registered for its type.
+pub(crate) fn as_custom<T: 'static>(
+    registry: Option<&Arc<TypeRegistry>>,
+    col_type: &ColTypeOption,
+    data: &CBytes,
+) -> Result<Option<Custom<T>>> {
+    let type_names = type_names(col_type).ok_or_else(|| {
+        Error::General(format!(
+            "Invalid conversion. \
+             Cannot convert {:?} into Custom (valid types: Custom, UDT).",
+            col_type.id
+        ))
+    })?;
+
+    let decoder = registry
+        .and_then(|registry| registry.decoder::<T>(col_type))
+        .ok_or_else(|| {
+            Error::General(format!(
+                "No conversion of {} into {} registered",
+                type_names[0],
+                std::any::type_name::<T>()
+            ))
+        })?;
+
+    decode(decoder, col_type, data).map(|value| value.map(Custom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame_result::{BodyResResultRows, CUdt, ColSpec, RowsMetadata};
+    use crate::types::rows::Row;
+    use crate::types::{CString, IntoRustByIndex, IntoRustByName};
+
+    #[derive(Debug, PartialEq)]
+    struct Point(i32, i32);
+
+    fn point_type(ks: &str) -> ColTypeOption {
+        let int = ColTypeOption {
+            id: ColType::Int,
+            value: None,
+        };
+
+        ColTypeOption {
+            id: ColType::Udt,
+            value: Some(ColTypeOptionValue::UdtType(CUdt {
+                ks: CString::new(ks.into()),
+                udt_name: CString::new("point".into()),
+                descriptions: vec![
+                    (CString::new("x".into()), int.clone()),
+                    (CString::new("y".into()), int),
+                ],
+            })),
+        }
+    }
+
+    fn reversed_type() -> ColTypeOption {
+        ColTypeOption {
+            id: ColType::Custom,
+            value: Some(ColTypeOptionValue::CString(CString::new(
+                "org.example.Reversed".into(),
+            ))),
+        }
+    }
+
+    fn point_registry() -> Arc<TypeRegistry> {
+        let mut registry = TypeRegistry::new();
+        registry.register_udt("point", |udt: &UDT| {
+            Ok(Point(udt.get_r_by_name("x")?, udt.get_r_by_name("y")?))
+        });
+        registry.register_udt("other_ks.point", |_: &UDT| Ok(Point(0, 0)));
+        registry.register_custom_type("org.example.Reversed", |bytes: &[u8]| {
+            Ok(String::from_utf8_lossy(bytes)
+                .chars()
+                .rev()
+                .collect::<String>())
+        });
+        Arc::new(registry)
+    }
+
+    fn point_data() -> CBytes {
+        CBytes::new(vec![0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 2])
+    }
+
+    fn row(columns: Vec<(&str, ColTypeOption, CBytes)>) -> Row {
+        let (col_specs, row_content) = columns
+            .into_iter()
+            .map(|(name, col_type, value)| {
+                let col_spec = ColSpec {
+                    ksname: None,
+                    tablename: None,
+                    name: CString::new(name.into()),
+                    col_type,
+                };
+                (col_spec, value)
+            })
+            .unzip();
+
+        Row::from_frame_body(BodyResResultRows {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 0,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs,
+            },
+            rows_count: 1,
+            rows_content: vec![row_content],
+        })
+        .remove(0)
+    }
+
+    #[test]
+    fn registered_udt() {
+        let registry = point_registry();
+        let registry = Some(&registry);
+
+        assert_eq!(
+            as_custom::<Point>(registry, &point_type("ks"), &point_data()).unwrap(),
+            Some(Custom(Point(1, 2)))
+        );
+        assert_eq!(
+            as_custom::<Point>(registry, &point_type("other_ks"), &point_data()).unwrap(),
+            Some(Custom(Point(0, 0)))
+        );
+        assert_eq!(
+            as_custom::<Point>(registry, &point_type("ks"), &CBytes::new_empty()).unwrap(),
+            None
+        );
+        assert!(as_custom::<String>(registry, &point_type("ks"), &point_data()).is_err());
+        assert!(as_custom::<Point>(None, &point_type("ks"), &point_data()).is_err());
+    }
+
+    #[test]
+    fn rows_with_registry() {
+        let columns = vec![
+            ("location", point_type("ks"), point_data()),
+            ("name", reversed_type(), CBytes::new(b"abc".to_vec())),
+        ];
+
+        let unregistered = row(columns.clone());
+        assert!(IntoRustByName::<Custom<Point>>::get_by_name(&unregistered, "location").is_err());
+        let name: String = unregistered.get_r_by_name("name").unwrap();
+        assert_eq!(name, "abc");
+
+        let registered = row(columns).with_type_registry(point_registry());
+        let location: Custom<Point> = registered.get_r_by_name("location").unwrap();
+        assert_eq!(location, Custom(Point(1, 2)));
+        // registered conversions are consulted before built-in ones
+        let name: String = registered.get_r_by_name("name").unwrap();
+        assert_eq!(name, "cba");
+        let name: String = registered.get_r_by_index(1).unwrap();
+        assert_eq!(name, "cba");
+    }
+
+    #[test]
+    fn udts_inherit_registry() {
+        let wrapper_type = ColTypeOption {
+            id: ColType::Udt,
+            value: Some(ColTypeOptionValue::UdtType(CUdt {
+                ks: CString::new("ks".into()),
+                udt_name: CString::new("wrapper".into()),
+                descriptions: vec![(CString::new("point".into()), point_type("ks"))],
+            })),
+        };
+        let mut data = vec![0, 0, 0, 16];
+        data.extend(point_data().into_plain().unwrap());
+
+        let row = row(vec![("wrapper", wrapper_type, CBytes::new(data))])
+            .with_type_registry(point_registry());
+        let wrapper: UDT = row.get_r_by_name("wrapper").unwrap();
+        let point: Custom<Point> = wrapper.get_r_by_name("point").unwrap();
+
+        assert_eq!(point, Custom(Point(1, 2)));
+    }
+}
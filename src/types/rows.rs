@@ -1,5 +1,8 @@
+use std::convert::TryFrom;
 use std::net::IpAddr;
 use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use chrono::prelude::*;
 use time::PrimitiveDateTime;
@@ -9,19 +12,26 @@ use crate::error::{column_is_empty_err, Error, Result};
 use crate::frame::frame_result::{
     BodyResResultRows, ColSpec, ColType, ColTypeOption, ColTypeOptionValue, RowsMetadata,
 };
+use crate::query::{ColumnDescription, QueryValues};
 use crate::types::blob::Blob;
+use crate::types::custom::CustomValue;
 use crate::types::data_serialization_types::*;
 use crate::types::decimal::Decimal;
 use crate::types::list::List;
 use crate::types::map::Map;
+use crate::types::numeric::{as_float, as_integer};
+use crate::types::registry::{as_custom, Custom, TypeRegistry};
 use crate::types::tuple::Tuple;
 use crate::types::udt::UDT;
+use crate::types::value::{Bytes, Value};
+use crate::types::vector::{Vector, VectorType};
 use crate::types::{ByIndex, ByName, CBytes, IntoRustByIndex, IntoRustByName};
 
 #[derive(Clone, Debug)]
 pub struct Row {
     metadata: RowsMetadata,
     row_content: Vec<CBytes>,
+    registry: Option<Arc<TypeRegistry>>,
 }
 
 impl Row {
@@ -31,10 +41,165 @@ impl Row {
             .map(|row| Row {
                 metadata: body.metadata.clone(),
                 row_content: row.clone(),
+                registry: None,
             })
             .collect()
     }
 
+    /// Attaches a registry of conversions consulted before built-in conversions when reading
+    /// columns, and passed on to UDTs and tuples read from them.
+    pub fn with_type_registry(mut self, registry: Arc<TypeRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Checks if a column with given name is present in the row.
+    pub fn contains_column(&self, name: &str) -> bool {
+        self.metadata
+            .col_specs
+            .iter()
+            .any(|spec| spec.name.as_str() == name)
+    }
+
+    /// Returns names of all columns in the row, in the order of their appearance.
+    pub fn column_names(&self) -> Vec<&str> {
+        self.metadata
+            .col_specs
+            .iter()
+            .map(|spec| spec.name.as_str())
+            .collect()
+    }
+
+    /// Returns metadata of the rows the row was received with.
+    pub fn metadata(&self) -> &RowsMetadata {
+        &self.metadata
+    }
+
+    /// Describes all columns in the row, in the order of their appearance, including their
+    /// keyspaces, tables and types with options, e.g. to inspect rows of arbitrary queries.
+    pub fn columns(&self) -> Vec<ColumnDescription> {
+        self.metadata.columns()
+    }
+
+    /// Returns the type of a column with given name, including options of collection, UDT and
+    /// tuple types, or `None` if there is no such column.
+    pub fn column_type(&self, name: &str) -> Option<&ColTypeOption> {
+        self.metadata
+            .col_specs
+            .iter()
+            .find(|spec| spec.name.as_str() == name)
+            .map(|spec| &spec.col_type)
+    }
+
+    /// Returns raw values of all columns, in the order of their appearance, e.g. to write the
+    /// row back with a statement binding the same columns. Null values stay null.
+    pub fn to_values(&self) -> QueryValues {
+        let values = self
+            .row_content
+            .iter()
+            .map(|value| match value.as_slice() {
+                Some(bytes) => Value::new_normal(Bytes::new(bytes.to_vec())),
+                None => Value::new_null(),
+            })
+            .collect();
+
+        QueryValues::SimpleValues(values)
+    }
+
+    /// Returns value of a column with given name, or `None` if the value is null. Unlike
+    /// `get_by_name`, empty values of types other than text and blobs (e.g. an `int` set to
+    /// an empty blob) are also returned as `None` instead of failing to decode. Fails if the
+    /// column does not exist or cannot be converted into `T`.
+    ///
+    /// Note that Cassandra does not distinguish between empty and null non-frozen collections:
+    /// both are returned as null, so `None` should be treated as an empty collection. Frozen
+    /// collections can be empty, in which case an empty collection is returned.
+    pub fn get_opt_by_name<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        Self: IntoRustByName<T>,
+    {
+        let (col_spec, value) = self
+            .get_col_spec_by_name(name)
+            .ok_or_else(|| column_is_empty_err(name))?;
+
+        if is_null_value(&col_spec.col_type, value) {
+            Ok(None)
+        } else {
+            self.get_by_name(name)
+        }
+    }
+
+    /// Returns value of a column with given index like `get_opt_by_name`.
+    pub fn get_opt_by_index<T>(&self, index: usize) -> Result<Option<T>>
+    where
+        Self: IntoRustByIndex<T>,
+    {
+        let (col_spec, value) = self
+            .get_col_spec_by_index(index)
+            .ok_or_else(|| column_is_empty_err(index))?;
+
+        if is_null_value(&col_spec.col_type, value) {
+            Ok(None)
+        } else {
+            self.get_by_index(index)
+        }
+    }
+
+    /// Checks if a column with given name is null or empty, as understood by
+    /// `get_opt_by_name`. Fails if the column does not exist.
+    pub fn is_null_by_name(&self, name: &str) -> Result<bool> {
+        self.get_col_spec_by_name(name)
+            .map(|(col_spec, value)| is_null_value(&col_spec.col_type, value))
+            .ok_or_else(|| column_is_empty_err(name))
+    }
+
+    /// Returns integer value of a column with given name, accepting any integral column type.
+    /// Smaller types are widened losslessly, while bigger ones are checked for overflow.
+    pub fn get_integer_by_name<T: TryFrom<i64>>(&self, name: &str) -> Result<Option<T>> {
+        self.get_col_spec_by_name(name)
+            .ok_or_else(|| column_is_empty_err(name))
+            .and_then(|(col_spec, cbytes)| as_integer(&col_spec.col_type, cbytes, name))
+    }
+
+    /// Returns integer value of a column with given index, accepting any integral column type.
+    /// Smaller types are widened losslessly, while bigger ones are checked for overflow.
+    pub fn get_integer_by_index<T: TryFrom<i64>>(&self, index: usize) -> Result<Option<T>> {
+        self.get_col_spec_by_index(index)
+            .ok_or_else(|| column_is_empty_err(index))
+            .and_then(|(col_spec, cbytes)| as_integer(&col_spec.col_type, cbytes, index))
+    }
+
+    /// Returns floating point value of a column with given name, accepting both `float` and
+    /// `double` columns.
+    pub fn get_float_by_name(&self, name: &str) -> Result<Option<f64>> {
+        self.get_col_spec_by_name(name)
+            .ok_or_else(|| column_is_empty_err(name))
+            .and_then(|(col_spec, cbytes)| as_float(&col_spec.col_type, cbytes))
+    }
+
+    /// Returns floating point value of a column with given index, accepting both `float` and
+    /// `double` columns.
+    pub fn get_float_by_index(&self, index: usize) -> Result<Option<f64>> {
+        self.get_col_spec_by_index(index)
+            .ok_or_else(|| column_is_empty_err(index))
+            .and_then(|(col_spec, cbytes)| as_float(&col_spec.col_type, cbytes))
+    }
+
+    /// Converts the row into a JSON object with values of all columns, keyed by column names.
+    /// See `types::json` for representation of particular types.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        let mut map = serde_json::Map::with_capacity(self.metadata.col_specs.len());
+        for (col_spec, value) in self.metadata.col_specs.iter().zip(self.row_content.iter()) {
+            map.insert(
+                col_spec.name.as_plain(),
+                crate::types::json::to_json(&col_spec.col_type, value)?,
+            );
+        }
+
+        Ok(serde_json::Value::Object(map))
+    }
+
     fn get_col_spec_by_name(&self, name: &str) -> Option<(&ColSpec, &CBytes)> {
         self.metadata
             .col_specs
@@ -47,6 +212,11 @@ impl Row {
             })
     }
 
+    /// Returns raw value of a column with given index, or `None` if the value is null.
+    pub(crate) fn raw_value(&self, index: usize) -> Option<&[u8]> {
+        self.row_content.get(index).and_then(CBytes::as_slice)
+    }
+
     fn get_col_spec_by_index(&self, index: usize) -> Option<(&ColSpec, &CBytes)> {
         let specs = self.metadata.col_specs.iter();
         let values = self.row_content.iter();
@@ -54,9 +224,34 @@ impl Row {
     }
 }
 
+/// Adds the column and requested type to an error of converting a column value.
+fn column_decode_err(col_spec: &ColSpec, rust_type: &'static str, error: Error) -> Error {
+    Error::ColumnDecode {
+        column: col_spec.name.as_plain(),
+        cql_type: col_spec.col_type.to_string(),
+        rust_type,
+        source: Box::new(error),
+    }
+}
+
+/// Checks if a value is null, or empty for a type which has no empty representation in Rust.
+fn is_null_value(col_type: &ColTypeOption, value: &CBytes) -> bool {
+    match value.as_slice() {
+        None => true,
+        Some(bytes) => {
+            bytes.is_empty()
+                && !matches!(
+                    col_type.id,
+                    ColType::Ascii | ColType::Varchar | ColType::Blob | ColType::Custom
+                )
+        }
+    }
+}
+
 impl ByName for Row {}
 
 into_rust_by_name!(Row, Blob);
+into_rust_by_name!(Row, CustomValue);
 into_rust_by_name!(Row, String);
 into_rust_by_name!(Row, bool);
 into_rust_by_name!(Row, i64);
@@ -73,16 +268,19 @@ into_rust_by_name!(Row, UDT);
 into_rust_by_name!(Row, Tuple);
 into_rust_by_name!(Row, PrimitiveDateTime);
 into_rust_by_name!(Row, Decimal);
+into_rust_by_name!(Row, Vector);
 into_rust_by_name!(Row, NonZeroI8);
 into_rust_by_name!(Row, NonZeroI16);
 into_rust_by_name!(Row, NonZeroI32);
 into_rust_by_name!(Row, NonZeroI64);
 into_rust_by_name!(Row, NaiveDateTime);
 into_rust_by_name!(Row, DateTime<Utc>);
+into_rust_by_name!(Row, SystemTime);
 
 impl ByIndex for Row {}
 
 into_rust_by_index!(Row, Blob);
+into_rust_by_index!(Row, CustomValue);
 into_rust_by_index!(Row, String);
 into_rust_by_index!(Row, bool);
 into_rust_by_index!(Row, i64);
@@ -99,9 +297,291 @@ into_rust_by_index!(Row, UDT);
 into_rust_by_index!(Row, Tuple);
 into_rust_by_index!(Row, PrimitiveDateTime);
 into_rust_by_index!(Row, Decimal);
+into_rust_by_index!(Row, Vector);
 into_rust_by_index!(Row, NonZeroI8);
 into_rust_by_index!(Row, NonZeroI16);
 into_rust_by_index!(Row, NonZeroI32);
 into_rust_by_index!(Row, NonZeroI64);
 into_rust_by_index!(Row, NaiveDateTime);
 into_rust_by_index!(Row, DateTime<Utc>);
+into_rust_by_index!(Row, SystemTime);
+
+impl<T: 'static> IntoRustByName<Custom<T>> for Row {
+    fn get_by_name(&self, name: &str) -> Result<Option<Custom<T>>> {
+        self.get_col_spec_by_name(name)
+            .ok_or_else(|| column_is_empty_err(name))
+            .and_then(|(col_spec, cbytes)| {
+                as_custom(self.registry.as_ref(), &col_spec.col_type, cbytes)
+                    .map_err(|error| column_decode_err(col_spec, std::any::type_name::<T>(), error))
+            })
+    }
+}
+
+impl<T: 'static> IntoRustByIndex<Custom<T>> for Row {
+    fn get_by_index(&self, index: usize) -> Result<Option<Custom<T>>> {
+        self.get_col_spec_by_index(index)
+            .ok_or_else(|| column_is_empty_err(index))
+            .and_then(|(col_spec, cbytes)| {
+                as_custom(self.registry.as_ref(), &col_spec.col_type, cbytes)
+                    .map_err(|error| column_decode_err(col_spec, std::any::type_name::<T>(), error))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsRustType, CString};
+
+    fn col_spec(name: &str) -> ColSpec {
+        ColSpec {
+            ksname: None,
+            tablename: None,
+            name: CString::new(name.into()),
+            col_type: ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            },
+        }
+    }
+
+    #[test]
+    fn column_presence() {
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 2,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![col_spec("id"), col_spec("value")],
+            },
+            row_content: vec![CBytes::new_empty(), CBytes::new_empty()],
+            registry: None,
+        };
+
+        assert!(row.contains_column("id"));
+        assert!(row.contains_column("value"));
+        assert!(!row.contains_column("missing"));
+        assert_eq!(row.column_names(), vec!["id", "value"]);
+    }
+
+    #[test]
+    fn column_metadata() {
+        let mut values = col_spec("values");
+        values.col_type = ColTypeOption {
+            id: ColType::List,
+            value: Some(ColTypeOptionValue::CList(Box::new(values.col_type))),
+        };
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 1,
+                columns_count: 2,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: Some(vec![CString::new("ks".into()), CString::new("t".into())]),
+                col_specs: vec![col_spec("id"), values],
+            },
+            row_content: vec![CBytes::new_empty(), CBytes::new_empty()],
+            registry: None,
+        };
+
+        let columns = row.columns();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[1].keyspace, "ks");
+        assert_eq!(columns[1].table, "t");
+        assert_eq!(columns[1].name, "values");
+        assert_eq!(columns[1].col_type.to_string(), "list<int>");
+        assert_eq!(
+            row.column_type("id").map(|col_type| col_type.to_string()),
+            Some("int".to_string())
+        );
+        assert!(row.column_type("missing").is_none());
+        assert_eq!(row.metadata().columns_count, 2);
+    }
+
+    #[test]
+    fn raw_values() {
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 2,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![col_spec("id"), col_spec("value")],
+            },
+            row_content: vec![CBytes::new(vec![0, 0, 0, 1]), CBytes::new_empty()],
+            registry: None,
+        };
+
+        assert_eq!(
+            row.to_values(),
+            QueryValues::SimpleValues(vec![1.into(), Value::new_null()])
+        );
+    }
+
+    #[test]
+    fn decode_error_context() {
+        let list_of_ints = ColTypeOption {
+            id: ColType::List,
+            value: Some(ColTypeOptionValue::CList(Box::new(ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            }))),
+        };
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 2,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![
+                    col_spec("id"),
+                    ColSpec {
+                        col_type: list_of_ints,
+                        ..col_spec("scores")
+                    },
+                ],
+            },
+            row_content: vec![CBytes::new(vec![0; 9]), CBytes::new(vec![0, 0, 0, 0])],
+            registry: None,
+        };
+
+        let error = IntoRustByName::<i32>::get_by_name(&row, "id").unwrap_err();
+        match &error {
+            Error::ColumnDecode {
+                column,
+                cql_type,
+                rust_type,
+                ..
+            } => {
+                assert_eq!(column, "id");
+                assert_eq!(cql_type, "int");
+                assert_eq!(*rust_type, "i32");
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert!(error
+            .to_string()
+            .starts_with("Cannot read column 'id' of type int as i32: "));
+
+        let error = IntoRustByIndex::<String>::get_by_index(&row, 1).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Cannot read column 'scores' of type list<int> as String: "));
+    }
+
+    #[test]
+    fn optional_values() {
+        let int = ColTypeOption {
+            id: ColType::Int,
+            value: None,
+        };
+        let typed_col_spec = |name: &str, col_type: ColTypeOption| ColSpec {
+            col_type,
+            ..col_spec(name)
+        };
+
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 6,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![
+                    col_spec("null_int"),
+                    col_spec("empty_int"),
+                    col_spec("int"),
+                    typed_col_spec(
+                        "empty_text",
+                        ColTypeOption {
+                            id: ColType::Varchar,
+                            value: None,
+                        },
+                    ),
+                    typed_col_spec(
+                        "null_list",
+                        ColTypeOption {
+                            id: ColType::List,
+                            value: Some(ColTypeOptionValue::CList(Box::new(int.clone()))),
+                        },
+                    ),
+                    typed_col_spec(
+                        "empty_list",
+                        ColTypeOption {
+                            id: ColType::List,
+                            value: Some(ColTypeOptionValue::CList(Box::new(int))),
+                        },
+                    ),
+                ],
+            },
+            row_content: vec![
+                CBytes::new_empty(),
+                CBytes::new(vec![]),
+                CBytes::new(vec![0, 0, 0, 1]),
+                CBytes::new(vec![]),
+                CBytes::new_empty(),
+                CBytes::new(vec![0, 0, 0, 0]),
+            ],
+            registry: None,
+        };
+
+        assert_eq!(row.get_opt_by_name::<i32>("null_int").unwrap(), None);
+        assert_eq!(row.get_opt_by_name::<i32>("empty_int").unwrap(), None);
+        assert!(IntoRustByName::<i32>::get_by_name(&row, "empty_int").is_err());
+        assert_eq!(row.get_opt_by_name::<i32>("int").unwrap(), Some(1));
+        assert_eq!(row.get_opt_by_index::<i32>(2).unwrap(), Some(1));
+        assert_eq!(
+            row.get_opt_by_name::<String>("empty_text").unwrap(),
+            Some(String::new())
+        );
+        assert!(row.get_opt_by_name::<String>("int").is_err());
+        assert!(row.get_opt_by_name::<i32>("missing").is_err());
+
+        // null collections are distinct from empty frozen ones
+        assert!(row.get_opt_by_name::<List>("null_list").unwrap().is_none());
+        let empty_list: Vec<i32> = row
+            .get_opt_by_name::<List>("empty_list")
+            .unwrap()
+            .unwrap()
+            .as_r_type()
+            .unwrap();
+        assert!(empty_list.is_empty());
+
+        assert!(row.is_null_by_name("null_list").unwrap());
+        assert!(!row.is_null_by_name("empty_list").unwrap());
+        assert!(!row.is_null_by_name("empty_text").unwrap());
+        assert!(row.is_null_by_name("missing").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn row_to_json() {
+        let row = Row {
+            metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 2,
+                paging_state: None,
+                new_metadata_id: None,
+                continuous_page_number: None,
+                global_table_space: None,
+                col_specs: vec![col_spec("id"), col_spec("value")],
+            },
+            row_content: vec![CBytes::new(vec![0, 0, 0, 1]), CBytes::new_empty()],
+            registry: None,
+        };
+
+        assert_eq!(
+            row.to_json().unwrap(),
+            serde_json::json!({"id": 1, "value": null})
+        );
+    }
+}
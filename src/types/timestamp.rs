@@ -0,0 +1,113 @@
+//! Conversions between Cassandra `timestamp` values and Rust time types. Cassandra timestamps
+//! are stored as milliseconds since the unix epoch, so sub-millisecond precision is truncated
+//! towards negative infinity when encoding. Negative values represent pre-epoch instants.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::prelude::*;
+
+/// Converts milliseconds since the unix epoch into `DateTime<Utc>`. Returns `None` if the value
+/// is out of range.
+pub fn timestamp_to_date_time(millis: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// Converts milliseconds since the unix epoch into `DateTime` in given timezone. Returns `None`
+/// if the value is out of range.
+pub fn timestamp_to_date_time_tz<Tz: TimeZone>(millis: i64, tz: &Tz) -> Option<DateTime<Tz>> {
+    timestamp_to_date_time(millis).map(|date_time| date_time.with_timezone(tz))
+}
+
+/// Converts milliseconds since the unix epoch into `NaiveDateTime` in UTC. Returns `None` if the
+/// value is out of range.
+pub fn timestamp_to_naive_date_time(millis: i64) -> Option<NaiveDateTime> {
+    timestamp_to_date_time(millis).map(|date_time| date_time.naive_utc())
+}
+
+/// Converts `DateTime` in any timezone into milliseconds since the unix epoch.
+pub fn date_time_to_timestamp<Tz: TimeZone>(date_time: &DateTime<Tz>) -> i64 {
+    date_time.timestamp_millis()
+}
+
+/// Converts milliseconds since the unix epoch into `SystemTime`.
+pub fn timestamp_to_system_time(millis: i64) -> SystemTime {
+    let offset = Duration::from_millis(millis.unsigned_abs());
+    if millis >= 0 {
+        UNIX_EPOCH + offset
+    } else {
+        UNIX_EPOCH - offset
+    }
+}
+
+/// Converts `SystemTime` into milliseconds since the unix epoch, truncating sub-millisecond
+/// precision towards negative infinity.
+pub fn system_time_to_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(error) => {
+            let duration = error.duration();
+            let millis = duration.as_millis() as i64;
+            if duration.subsec_nanos() % 1_000_000 == 0 {
+                -millis
+            } else {
+                -millis - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time_round_trip() {
+        for millis in &[
+            0,
+            1,
+            999,
+            1_000,
+            1_614_000_000_123,
+            -1,
+            -999,
+            -1_000,
+            -1_001,
+        ] {
+            let date_time = timestamp_to_date_time(*millis).unwrap();
+            assert_eq!(date_time_to_timestamp(&date_time), *millis);
+        }
+    }
+
+    #[test]
+    fn pre_epoch_date_time() {
+        let date_time = timestamp_to_date_time(-1).unwrap();
+        assert_eq!(
+            date_time,
+            Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 59).unwrap()
+                + chrono::Duration::milliseconds(999)
+        );
+    }
+
+    #[test]
+    fn date_time_with_timezone() {
+        let tz = FixedOffset::east_opt(3600).unwrap();
+        let date_time = timestamp_to_date_time_tz(0, &tz).unwrap();
+        assert_eq!(date_time.hour(), 1);
+        assert_eq!(date_time_to_timestamp(&date_time), 0);
+    }
+
+    #[test]
+    fn system_time_round_trip() {
+        for millis in &[0, 1, 1_614_000_000_123, -1, -1_001] {
+            let time = timestamp_to_system_time(*millis);
+            assert_eq!(system_time_to_timestamp(time), *millis);
+        }
+    }
+
+    #[test]
+    fn system_time_truncation() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_500);
+        assert_eq!(system_time_to_timestamp(time), 1);
+        let time = UNIX_EPOCH - Duration::from_micros(1_500);
+        assert_eq!(system_time_to_timestamp(time), -2);
+    }
+}
@@ -1,4 +1,5 @@
 use std::net::IpAddr;
+use std::time::SystemTime;
 
 use chrono::prelude::*;
 use time::PrimitiveDateTime;
@@ -7,18 +8,23 @@ use uuid::Uuid;
 use crate::error::{column_is_empty_err, Error, Result};
 use crate::frame::frame_result::{CTuple, ColType, ColTypeOption, ColTypeOptionValue};
 use crate::types::blob::Blob;
+use crate::types::custom::CustomValue;
 use crate::types::data_serialization_types::*;
 use crate::types::decimal::Decimal;
 use crate::types::list::List;
 use crate::types::map::Map;
+use crate::types::registry::{as_custom, Custom, TypeRegistry};
 use crate::types::udt::UDT;
+use crate::types::vector::{Vector, VectorType};
 use crate::types::{ByIndex, CBytes, IntoRustByIndex};
 
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct Tuple {
     data: Vec<(ColTypeOption, CBytes)>,
+    registry: Option<Arc<TypeRegistry>>,
 }
 
 impl PartialEq for Tuple {
@@ -56,13 +62,29 @@ impl Tuple {
             a
         });
 
-        Tuple { data: d }
+        Tuple {
+            data: d,
+            registry: None,
+        }
+    }
+
+    /// Attaches a registry of conversions consulted before built-in conversions when reading
+    /// elements, and passed on to UDTs and tuples read from them.
+    pub fn with_type_registry(mut self, registry: Arc<TypeRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub(crate) fn inherit_registry(mut self, registry: Option<&Arc<TypeRegistry>>) -> Self {
+        self.registry = registry.cloned();
+        self
     }
 }
 
 impl ByIndex for Tuple {}
 
 into_rust_by_index!(Tuple, Blob);
+into_rust_by_index!(Tuple, CustomValue);
 into_rust_by_index!(Tuple, String);
 into_rust_by_index!(Tuple, bool);
 into_rust_by_index!(Tuple, i64);
@@ -79,5 +101,16 @@ into_rust_by_index!(Tuple, UDT);
 into_rust_by_index!(Tuple, Tuple);
 into_rust_by_index!(Tuple, PrimitiveDateTime);
 into_rust_by_index!(Tuple, Decimal);
+into_rust_by_index!(Tuple, Vector);
 into_rust_by_index!(Tuple, NaiveDateTime);
 into_rust_by_index!(Tuple, DateTime<Utc>);
+into_rust_by_index!(Tuple, SystemTime);
+
+impl<T: 'static> IntoRustByIndex<Custom<T>> for Tuple {
+    fn get_by_index(&self, index: usize) -> Result<Option<Custom<T>>> {
+        self.data
+            .get(index)
+            .ok_or_else(|| column_is_empty_err(index))
+            .and_then(|(col_type, bytes)| as_custom(self.registry.as_ref(), col_type, bytes))
+    }
+}
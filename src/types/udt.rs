@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use chrono::prelude::*;
 use time::PrimitiveDateTime;
@@ -9,16 +11,20 @@ use uuid::Uuid;
 use crate::error::{column_is_empty_err, Error, Result};
 use crate::frame::frame_result::{CUdt, ColType, ColTypeOption, ColTypeOptionValue};
 use crate::types::blob::Blob;
+use crate::types::custom::CustomValue;
 use crate::types::data_serialization_types::*;
 use crate::types::decimal::Decimal;
 use crate::types::list::List;
 use crate::types::map::Map;
+use crate::types::registry::{as_custom, Custom, TypeRegistry};
 use crate::types::tuple::Tuple;
+use crate::types::vector::{Vector, VectorType};
 use crate::types::{ByName, CBytes, IntoRustByName};
 
 #[derive(Clone, Debug)]
 pub struct UDT {
     data: HashMap<String, (ColTypeOption, CBytes)>,
+    registry: Option<Arc<TypeRegistry>>,
 }
 
 impl UDT {
@@ -35,13 +41,29 @@ impl UDT {
             a
         });
 
-        UDT { data: d }
+        UDT {
+            data: d,
+            registry: None,
+        }
+    }
+
+    /// Attaches a registry of conversions consulted before built-in conversions when reading
+    /// fields, and passed on to UDTs and tuples read from them.
+    pub fn with_type_registry(mut self, registry: Arc<TypeRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub(crate) fn inherit_registry(mut self, registry: Option<&Arc<TypeRegistry>>) -> Self {
+        self.registry = registry.cloned();
+        self
     }
 }
 
 impl ByName for UDT {}
 
 into_rust_by_name!(UDT, Blob);
+into_rust_by_name!(UDT, CustomValue);
 into_rust_by_name!(UDT, String);
 into_rust_by_name!(UDT, bool);
 into_rust_by_name!(UDT, i64);
@@ -58,9 +80,72 @@ into_rust_by_name!(UDT, UDT);
 into_rust_by_name!(UDT, Tuple);
 into_rust_by_name!(UDT, PrimitiveDateTime);
 into_rust_by_name!(UDT, Decimal);
+into_rust_by_name!(UDT, Vector);
 into_rust_by_name!(UDT, NonZeroI8);
 into_rust_by_name!(UDT, NonZeroI16);
 into_rust_by_name!(UDT, NonZeroI32);
 into_rust_by_name!(UDT, NonZeroI64);
 into_rust_by_name!(UDT, NaiveDateTime);
 into_rust_by_name!(UDT, DateTime<Utc>);
+into_rust_by_name!(UDT, SystemTime);
+
+impl<T: 'static> IntoRustByName<Custom<T>> for UDT {
+    fn get_by_name(&self, name: &str) -> Result<Option<Custom<T>>> {
+        self.data
+            .get(name)
+            .ok_or_else(|| column_is_empty_err(name))
+            .and_then(|(col_type, bytes)| as_custom(self.registry.as_ref(), col_type, bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CString;
+
+    fn point_type(fields: &[(&str, ColType)]) -> CUdt {
+        CUdt {
+            ks: CString::new("ks".into()),
+            udt_name: CString::new("point".into()),
+            descriptions: fields
+                .iter()
+                .map(|(name, id)| {
+                    (
+                        CString::new(name.to_string()),
+                        ColTypeOption {
+                            id: id.clone(),
+                            value: None,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn tolerates_schema_evolution() {
+        // value written before the label field was added
+        let metadata = point_type(&[
+            ("x", ColType::Int),
+            ("y", ColType::Int),
+            ("label", ColType::Varchar),
+        ]);
+        let bytes = [0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 2];
+        let udt = UDT::new(decode_udt(&bytes, 3).unwrap(), &metadata);
+
+        let x: Option<i32> = udt.get_by_name("x").unwrap();
+        let y: Option<i32> = udt.get_by_name("y").unwrap();
+        let label: Option<String> = udt.get_by_name("label").unwrap();
+        assert_eq!(x, Some(1));
+        assert_eq!(y, Some(2));
+        assert_eq!(label, None);
+
+        // metadata obtained before the y field was added
+        let metadata = point_type(&[("x", ColType::Int)]);
+        let udt = UDT::new(decode_udt(&bytes, 1).unwrap(), &metadata);
+
+        let x: Option<i32> = udt.get_by_name("x").unwrap();
+        assert_eq!(x, Some(1));
+        assert!(IntoRustByName::<i32>::get_by_name(&udt, "y").is_err());
+    }
+}
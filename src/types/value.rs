@@ -1,10 +1,11 @@
 use std::cmp::Eq;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::convert::Into;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::net::IpAddr;
 use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8};
+use std::time::SystemTime;
 
 use chrono::prelude::*;
 use uuid::Uuid;
@@ -13,7 +14,9 @@ use crate::frame::AsBytes;
 use crate::time::PrimitiveDateTime;
 
 use super::blob::Blob;
+use super::custom::CustomValue;
 use super::decimal::Decimal;
+use super::duration::CqlDuration;
 use super::*;
 
 /// Types of Cassandra value: normal value (bits), null value and not-set value
@@ -250,15 +253,33 @@ impl Into<Bytes> for Decimal {
     }
 }
 
+impl From<CqlDuration> for Bytes {
+    fn from(duration: CqlDuration) -> Bytes {
+        Bytes(duration.as_bytes())
+    }
+}
+
 impl Into<Bytes> for NaiveDateTime {
     fn into(self) -> Bytes {
         self.timestamp_millis().into()
     }
 }
 
-impl Into<Bytes> for DateTime<Utc> {
+impl<Tz: TimeZone> Into<Bytes> for DateTime<Tz> {
     fn into(self) -> Bytes {
-        self.timestamp_millis().into()
+        crate::types::timestamp::date_time_to_timestamp(&self).into()
+    }
+}
+
+impl From<CustomValue> for Bytes {
+    fn from(value: CustomValue) -> Self {
+        Bytes(value.into_bytes())
+    }
+}
+
+impl From<SystemTime> for Bytes {
+    fn from(time: SystemTime) -> Bytes {
+        crate::types::timestamp::system_time_to_timestamp(time).into()
     }
 }
 
@@ -275,6 +296,18 @@ impl<T: Into<Bytes> + Clone + Debug> From<Vec<T>> for Bytes {
     }
 }
 
+impl<T: Into<Bytes> + Clone + Debug + Hash + Eq> From<HashSet<T>> for Bytes {
+    fn from(set: HashSet<T>) -> Bytes {
+        set.into_iter().collect::<Vec<T>>().into()
+    }
+}
+
+impl<T: Into<Bytes> + Clone + Debug + Ord> From<BTreeSet<T>> for Bytes {
+    fn from(set: BTreeSet<T>) -> Bytes {
+        set.into_iter().collect::<Vec<T>>().into()
+    }
+}
+
 impl<K, V> From<HashMap<K, V>> for Bytes
 where
     K: Into<Bytes> + Clone + Debug + Hash + Eq,
@@ -343,14 +376,14 @@ mod tests {
     #[test]
     fn test_new_null_value() {
         let null_value = Value::new_null();
-        assert_eq!(null_value.body, vec![]);
+        assert_eq!(null_value.body, Vec::<u8>::new());
         assert_eq!(null_value.value_type, ValueType::Null);
     }
 
     #[test]
     fn test_new_not_set_value() {
         let not_set_value = Value::new_not_set();
-        assert_eq!(not_set_value.body, vec![]);
+        assert_eq!(not_set_value.body, Vec::<u8>::new());
         assert_eq!(not_set_value.value_type, ValueType::NotSet);
     }
 
@@ -359,4 +392,16 @@ mod tests {
         let value = Value::new_normal(1_u8);
         assert_eq!(value.as_bytes(), vec![0, 0, 0, 1, 1]);
     }
+
+    #[test]
+    fn test_set_into_bytes() {
+        let set: BTreeSet<i32> = vec![2, 1].into_iter().collect();
+        let bytes: Bytes = set.into();
+        let list: Bytes = vec![1, 2].into();
+        assert_eq!(bytes.0, list.0);
+
+        let set: HashSet<i32> = vec![7].into_iter().collect();
+        let bytes: Bytes = set.into();
+        assert_eq!(bytes.0, vec![0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 7]);
+    }
 }
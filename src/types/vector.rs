@@ -0,0 +1,142 @@
+use crate::types::to_float;
+use crate::types::value::Bytes;
+
+const VECTOR_TYPE_CLASS: &str = "org.apache.cassandra.db.marshal.VectorType";
+const FLOAT_TYPE_CLASS: &str = "org.apache.cassandra.db.marshal.FloatType";
+
+/// Cassandra `vector<type, n>` column type. Vectors are sent in result metadata as `custom`
+/// columns, with the element type and dimensions encoded in the class name, e.g.
+/// `org.apache.cassandra.db.marshal.VectorType(org.apache.cassandra.db.marshal.FloatType,3)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VectorType {
+    element_class: String,
+    dimensions: usize,
+}
+
+impl VectorType {
+    /// Parses a custom type class name. Returns `None` if the class is not a vector type.
+    pub fn parse(class_name: &str) -> Option<Self> {
+        let params = class_name
+            .trim()
+            .strip_prefix(VECTOR_TYPE_CLASS)?
+            .strip_prefix('(')?
+            .strip_suffix(')')?;
+
+        // element type can be parametrized itself, so dimensions are after the last comma
+        let (element_class, dimensions) = params.rsplit_once(',')?;
+
+        Some(VectorType {
+            element_class: element_class.trim().to_string(),
+            dimensions: dimensions.trim().parse().ok()?,
+        })
+    }
+
+    /// Returns class name of vector elements.
+    pub fn element_class(&self) -> &str {
+        &self.element_class
+    }
+
+    /// Returns number of vector elements.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Checks if vector elements are `float`s.
+    pub fn is_float(&self) -> bool {
+        self.element_class == FLOAT_TYPE_CLASS
+    }
+}
+
+/// Cassandra `vector<float, n>` value, used for approximate nearest neighbor search.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[repr(transparent)]
+pub struct Vector(Vec<f32>);
+
+impl Vector {
+    pub fn new(values: Vec<f32>) -> Self {
+        Vector(values)
+    }
+
+    /// Returns number of vector elements.
+    pub fn dimensions(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns vector elements.
+    pub fn as_slice(&self) -> &[f32] {
+        &self.0
+    }
+
+    /// Returns underlying vector of elements.
+    pub fn into_vec(self) -> Vec<f32> {
+        self.0
+    }
+}
+
+impl From<Vec<f32>> for Vector {
+    fn from(values: Vec<f32>) -> Self {
+        Vector::new(values)
+    }
+}
+
+impl From<&[f32]> for Vector {
+    fn from(values: &[f32]) -> Self {
+        Vector::new(values.to_vec())
+    }
+}
+
+impl From<Vector> for Vec<f32> {
+    fn from(vector: Vector) -> Self {
+        vector.into_vec()
+    }
+}
+
+impl From<Vector> for Bytes {
+    // elements of fixed size are serialized without length prefixes
+    fn from(vector: Vector) -> Self {
+        Bytes::new(vector.0.into_iter().flat_map(to_float).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::value::Value;
+
+    #[test]
+    fn parse_vector_type() {
+        let vector_type = VectorType::parse(
+            "org.apache.cassandra.db.marshal.VectorType(org.apache.cassandra.db.marshal.FloatType, 3)",
+        )
+        .unwrap();
+
+        assert!(vector_type.is_float());
+        assert_eq!(vector_type.dimensions(), 3);
+    }
+
+    #[test]
+    fn parse_parametrized_element_type() {
+        let vector_type = VectorType::parse(
+            "org.apache.cassandra.db.marshal.VectorType(org.apache.cassandra.db.marshal.MapType(\
+             org.apache.cassandra.db.marshal.Int32Type,org.apache.cassandra.db.marshal.Int32Type),2)",
+        )
+        .unwrap();
+
+        assert!(!vector_type.is_float());
+        assert_eq!(vector_type.dimensions(), 2);
+    }
+
+    #[test]
+    fn parse_other_custom_type() {
+        assert_eq!(
+            VectorType::parse("org.apache.cassandra.db.marshal.DynamicCompositeType"),
+            None
+        );
+    }
+
+    #[test]
+    fn vector_into_bytes() {
+        let value = Value::new_normal(Vector::new(vec![1.0, -2.5]));
+        assert_eq!(value.body, vec![0x3f, 0x80, 0, 0, 0xc0, 0x20, 0, 0]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Records frames exchanged with a real node into `fixtures/frame/captured`, which are checked
+//! by the frame snapshot tests. Run against a single node listening on 127.0.0.1:9042, once per
+//! protocol version:
+//!
+//! ```text
+//! cargo test --features e2e-tests --test capture_frames -- --ignored
+//! cargo test --features e2e-tests,v3 --test capture_frames -- --ignored
+//! ```
+#![cfg(feature = "e2e-tests")]
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use cdrs_tokio::authenticators::NoneAuthenticator;
+use cdrs_tokio::cluster::{DirectSession, NodeTcpConfigBuilder};
+use cdrs_tokio::compression::Compression;
+use cdrs_tokio::frame::{Opcode, ProtocolVersion};
+use cdrs_tokio::query::{
+    BatchExecutor, BatchQueryBuilder, ExecExecutor, PrepareExecutor, QueryExecutor, QueryValues,
+};
+use cdrs_tokio::transport::{TransportStream, TransportWrapper};
+use cdrs_tokio::types::value::Value;
+use cdrs_tokio::types::IntoRustByName;
+
+const ADDR: &str = "127.0.0.1:9042";
+const HEADER_LEN: usize = 9;
+
+/// Bytes sent and received over a connection.
+#[derive(Default)]
+struct Recording {
+    sent: Mutex<Vec<u8>>,
+    received: Mutex<Vec<u8>>,
+}
+
+/// Wrapper recording the connection it wrapped last.
+struct Recorder(Arc<Recording>);
+
+struct RecordingStream {
+    stream: TcpStream,
+    recording: Arc<Recording>,
+}
+
+impl AsyncRead for RecordingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.stream).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let mut received = self.recording.received.lock().unwrap();
+            received.extend_from_slice(&buf.filled()[filled..]);
+        }
+
+        result
+    }
+}
+
+impl AsyncWrite for RecordingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            let mut sent = self.recording.sent.lock().unwrap();
+            sent.extend_from_slice(&buf[..written]);
+        }
+
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl TransportStream for RecordingStream {
+    fn is_alive(&self) -> bool {
+        self.stream.peer_addr().is_ok()
+    }
+}
+
+#[async_trait]
+impl TransportWrapper for Recorder {
+    async fn wrap(&self, stream: TcpStream) -> io::Result<Box<dyn TransportStream>> {
+        // connections rejecting the protocol version are replaced, so only the last one is kept
+        self.0.sent.lock().unwrap().clear();
+        self.0.received.lock().unwrap().clear();
+
+        Ok(Box::new(RecordingStream {
+            stream,
+            recording: self.0.clone(),
+        }))
+    }
+}
+
+/// Splits recorded bytes into whole frames.
+fn frames(mut bytes: &[u8]) -> Vec<&[u8]> {
+    let mut frames = vec![];
+    while bytes.len() >= HEADER_LEN {
+        let body_len = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) as usize;
+        let (frame, rest) = bytes.split_at(HEADER_LEN + body_len);
+        frames.push(frame);
+        bytes = rest;
+    }
+
+    frames
+}
+
+fn stream(frame: &[u8]) -> i16 {
+    i16::from_be_bytes([frame[2], frame[3]])
+}
+
+fn opcode_name(frame: &[u8]) -> String {
+    format!("{:?}", Opcode::from(frame[4])).to_lowercase()
+}
+
+/// Writes request frames as `<n>-<opcode>-request` and responses as `<n>-<opcode>-response`,
+/// where `n` is the number of the request they answer.
+fn write_fixtures(dir: &PathBuf, recording: &Recording) {
+    let sent = recording.sent.lock().unwrap();
+    let received = recording.received.lock().unwrap();
+    let requests = frames(&sent);
+
+    for (n, request) in requests.iter().enumerate() {
+        let name = format!("{:02}-{}-request", n, opcode_name(request));
+        fs::write(dir.join(name), request).unwrap();
+    }
+
+    for response in frames(&received) {
+        // responses to other streams, e.g. events, are not captured
+        if let Some(n) = requests
+            .iter()
+            .position(|request| stream(request) == stream(response))
+        {
+            let name = format!("{:02}-{}-response", n, opcode_name(response));
+            fs::write(dir.join(name), response).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn capture_frames() {
+    let recording = Arc::new(Recording::default());
+    let node_config = NodeTcpConfigBuilder::new(ADDR, Arc::new(NoneAuthenticator))
+        .transport_wrapper(Some(Arc::new(Recorder(recording.clone()))))
+        .build();
+    let session =
+        DirectSession::connect_with_config(node_config, Compression::None, Default::default())
+            .await
+            .expect("session should be created");
+
+    let release_version: String = session
+        .query("SELECT release_version FROM system.local")
+        .await
+        .unwrap()
+        .get_body()
+        .unwrap()
+        .into_rows()
+        .unwrap()[0]
+        .get_r_by_name("release_version")
+        .unwrap();
+
+    session
+        .query("DROP KEYSPACE IF EXISTS cdrs_capture")
+        .await
+        .unwrap();
+    session
+        .query(
+            "CREATE KEYSPACE cdrs_capture WITH replication = \
+             {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .await
+        .unwrap();
+    session.query("USE cdrs_capture").await.unwrap();
+    session
+        .query("CREATE TABLE tbl (id int PRIMARY KEY, value text)")
+        .await
+        .unwrap();
+
+    let values = || QueryValues::SimpleValues(vec![Value::from(7_i32)]);
+    session
+        .query_with_values("INSERT INTO tbl (id, value) VALUES (?, 'a')", values())
+        .await
+        .unwrap();
+    session
+        .query_with_values("SELECT id FROM tbl WHERE id = ?", values())
+        .await
+        .unwrap();
+    session
+        .query_tw("SELECT id FROM tbl", true, false)
+        .await
+        .unwrap();
+
+    let prepared = session
+        .prepare("SELECT id FROM tbl WHERE id = ?")
+        .await
+        .unwrap();
+    session.exec_with_values(&prepared, values()).await.unwrap();
+
+    // batches larger than 5 kB are answered with a warning by default
+    let large_value = Value::from("x".repeat(6 * 1024));
+    let batch = BatchQueryBuilder::new()
+        .add_query(
+            "INSERT INTO tbl (id, value) VALUES (8, ?)",
+            QueryValues::SimpleValues(vec![large_value]),
+        )
+        .finalize()
+        .unwrap();
+    session.batch_with_params(batch).await.unwrap();
+
+    assert!(session.query("SELECT id FROM missing").await.is_err());
+
+    let version = ProtocolVersion::default().request_byte();
+    let dir: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "fixtures/frame/captured",
+        &format!("v{}", version),
+    ]
+    .iter()
+    .collect();
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    write_fixtures(&dir, &recording);
+    fs::write(dir.join("SERVER"), format!("{}\n", release_version)).unwrap();
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "codegen")]
+
+use std::time::SystemTime;
+
+use cdrs_tokio::frame::IntoQueryValues;
+use cdrs_tokio::query::QueryValues;
+use cdrs_tokio::types::value::Value;
+
+include!("../fixtures/codegen_user_events.rs");
+
+#[test]
+fn generated_query_values() {
+    let events = UserEvents {
+        user_id: uuid::Uuid::nil(),
+        at: SystemTime::UNIX_EPOCH,
+        counts: Default::default(),
+        payload_size: None,
+        tags: vec!["a".into()],
+        r#type: Some("click".into()),
+    };
+
+    assert_eq!(UserEvents::TABLE, "ks.user_events");
+    match events.into_query_values() {
+        QueryValues::NamedValues(values) => {
+            assert_eq!(values.len(), 6);
+            assert_eq!(values["type"], Value::from("click"));
+            assert_eq!(values["payloadSize"], Value::new_null());
+        }
+        values => panic!("unexpected values {:?}", values),
+    }
+}
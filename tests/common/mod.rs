@@ -31,7 +31,7 @@ pub async fn setup(create_table_cql: &'static str) -> Result<CurrentSession> {
 #[cfg(feature = "e2e-tests")]
 pub async fn setup_multiple(create_cqls: &[&'static str]) -> Result<CurrentSession> {
     let node = NodeTcpConfigBuilder::new(ADDR, Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let session = new_session(&cluster_config, lb)
         .await
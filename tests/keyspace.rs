@@ -22,7 +22,7 @@ use cdrs_tokio::types::{AsRust, ByName, IntoRustByName};
 #[cfg(feature = "e2e-tests")]
 async fn create_keyspace() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let session = new_session(&cluster_config, lb)
         .await
@@ -90,7 +90,7 @@ async fn create_keyspace() {
 #[cfg(feature = "e2e-tests")]
 async fn alter_keyspace() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let session = new_session(&cluster_config, lb)
         .await
@@ -149,7 +149,7 @@ async fn alter_keyspace() {
 #[cfg(feature = "e2e-tests")]
 async fn use_keyspace() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let session = new_session(&cluster_config, lb)
         .await
@@ -181,7 +181,7 @@ async fn use_keyspace() {
 #[cfg(feature = "e2e-tests")]
 async fn drop_keyspace() {
     let node = NodeTcpConfigBuilder::new("127.0.0.1:9042", Arc::new(NoneAuthenticator {})).build();
-    let cluster_config = ClusterTcpConfig(vec![node]);
+    let cluster_config = ClusterTcpConfig::new(vec![node]);
     let lb = RoundRobin::new();
     let session = new_session(&cluster_config, lb)
         .await